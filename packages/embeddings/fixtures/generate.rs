@@ -4,6 +4,12 @@
 use rusqlite::{params, Connection};
 use std::path::PathBuf;
 
+/// Fixture rows are written inline as wide tuples, one per table row.
+type S = &'static str;
+type CodeRow = (i64, S, S, S, S, S, S, S);
+type ConstitutionRow = (i64, i64, S, S, S, S, S, i64);
+type CourtRow = (i64, S, S, S, S, S, S, S, S);
+
 fn main() {
     let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "fixtures", "test-virginia.db"]
         .iter()
@@ -31,7 +37,7 @@ fn main() {
     )
     .unwrap();
 
-    let code_rows: &[(i64, &str, &str, &str, &str, &str, &str, &str)] = &[
+    let code_rows: &[CodeRow] = &[
         (1, "1", "General Provisions", "1", "Common Law", "1-200",
          "Rule of construction",
          "The common law of England, insofar as it is not repugnant to the principles of the Bill of Rights and Constitution of this Commonwealth, shall continue in full force."),
@@ -86,7 +92,7 @@ fn main() {
     )
     .unwrap();
 
-    let const_rows: &[(i64, i64, &str, &str, &str, &str, &str, i64)] = &[
+    let const_rows: &[ConstitutionRow] = &[
         (1, 1, "I", "Bill of Rights", "Section 1",
          "Equality and rights of men",
          "That all men are by nature equally free and independent and have certain inherent rights, of which, when they enter into a state of society, they cannot, by any compact, deprive or divest their posterity.",
@@ -176,7 +182,7 @@ fn main() {
     )
     .unwrap();
 
-    let court_rows: &[(i64, &str, &str, &str, &str, &str, &str, &str, &str)] = &[
+    let court_rows: &[CourtRow] = &[
         (1, "Supreme Court of Virginia", "Richmond", "Supreme",
          "Statewide", "100 N 9th St", "Richmond", "VA", "23219"),
         (2, "Court of Appeals of Virginia", "Richmond", "Appellate",
@@ -228,6 +234,37 @@ fn main() {
         .unwrap();
     }
 
+    // ── acts ────────────────────────────────────────────────────────────
+    db.execute_batch(
+        "CREATE TABLE acts (
+            id      INTEGER PRIMARY KEY,
+            year    INTEGER,
+            chapter TEXT,
+            title   TEXT,
+            body    TEXT
+        )",
+    )
+    .unwrap();
+
+    let act_rows: &[(i64, i64, &str, &str, &str)] = &[
+        (1, 2023, "123",
+         "An Act to amend and reenact §§ 8.01-230 and 8.01-243 of the Code of Virginia, relating to limitations of actions.",
+         "<p>1. That §§ 8.01-230 and 8.01-243 of the Code of Virginia are amended and reenacted as follows:</p><p>§ 8.01-230. Accrual of right of action. In every action for which a limitation period is prescribed, the right of action shall be deemed to accrue when the injury is sustained.</p>"),
+        (2, 2024, "45",
+         "An Act to amend the Code of Virginia by adding a section numbered 1-200.1, relating to definitions.",
+         "<p>1. That the Code of Virginia is amended by adding a section numbered 1-200.1 as follows:</p><p>§ 1-200.1. Definitions. As used in this Code, unless the context requires otherwise, \"Commonwealth\" means the Commonwealth of Virginia.</p>"),
+        (3, 2024, "310",
+         "An Act to amend and reenact § 46.2-852 of the Code of Virginia, relating to reckless driving; general rule.",
+         "<p>1. That § 46.2-852 of the Code of Virginia is amended and reenacted as follows:</p><p>§ 46.2-852. Reckless driving; general rule. Irrespective of the maximum speeds permitted by law, any person who drives a vehicle on any highway recklessly shall be guilty of reckless driving.</p>"),
+    ];
+    for r in act_rows {
+        db.execute(
+            "INSERT INTO acts VALUES (?1,?2,?3,?4,?5)",
+            params![r.0, r.1, r.2, r.3, r.4],
+        )
+        .unwrap();
+    }
+
     // ── documents ───────────────────────────────────────────────────────
     db.execute_batch(
         "CREATE TABLE documents (
@@ -274,10 +311,11 @@ fn main() {
     println!("  authorities:    {} rows", auth_rows.len());
     println!("  courts:         {} rows", court_rows.len());
     println!("  popular_names:  {} rows", pop_rows.len());
+    println!("  acts:           {} rows", act_rows.len());
     println!("  documents:      {} rows", doc_rows.len());
     println!(
         "  total:          {} rows",
         code_rows.len() + const_rows.len() + auth_rows.len()
-            + court_rows.len() + pop_rows.len() + doc_rows.len()
+            + court_rows.len() + pop_rows.len() + act_rows.len() + doc_rows.len()
     );
 }
@@ -3,7 +3,7 @@ set -euo pipefail
 
 SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
 
-OMP_NUM_THREADS=10 cargo run --release --bin proseva-embeddings -- \
+OMP_NUM_THREADS=10 cargo run --release --bin proseva-embeddings -- build \
   --input "$SCRIPT_DIR/../datasets/data/virginia.db" \
   --output "$SCRIPT_DIR/../datasets/data/graph.sqlite.db" \
   "$@"
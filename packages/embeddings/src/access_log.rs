@@ -0,0 +1,132 @@
+//! Structured access log for the embedding server: one JSON object per
+//! request, appended to a file (or stderr with `-`).
+//!
+//! Query text is logged truncated, next to a hash of the full query so
+//! repeated queries can be grouped. With raw query logging disabled only the
+//! hash is written.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Serialize)]
+pub struct AccessEntry {
+    pub ts: String,
+    pub endpoint: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corpus: Option<String>,
+    pub status: u16,
+    pub latency_ms: f64,
+    pub results: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+pub struct AccessLog {
+    out: Mutex<Box<dyn Write + Send>>,
+    raw_queries: bool,
+    max_query_chars: usize,
+}
+
+impl AccessLog {
+    /// `path` of `-` logs to stderr; anything else is appended to.
+    pub fn open(path: &Path, raw_queries: bool, max_query_chars: usize) -> Result<Self> {
+        let out: Box<dyn Write + Send> = if path == Path::new("-") {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open access log {}", path.display()))?,
+            )
+        };
+        Ok(Self::new(out, raw_queries, max_query_chars))
+    }
+
+    fn new(out: Box<dyn Write + Send>, raw_queries: bool, max_query_chars: usize) -> Self {
+        Self {
+            out: Mutex::new(out),
+            raw_queries,
+            max_query_chars,
+        }
+    }
+
+    /// Build an entry for a request that started at `started`.
+    pub fn entry(
+        &self,
+        endpoint: &'static str,
+        started: Instant,
+        status: u16,
+        results: usize,
+        query: Option<&str>,
+    ) -> AccessEntry {
+        AccessEntry {
+            ts: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            endpoint,
+            corpus: None,
+            status,
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            results,
+            query_hash: query.map(query_hash),
+            query: query
+                .filter(|_| self.raw_queries)
+                .map(|q| truncate(q, self.max_query_chars)),
+        }
+    }
+
+    /// Logging never fails a request; write errors are reported and dropped.
+    pub fn write(&self, entry: &AccessEntry) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        let mut out = self.out.lock().unwrap();
+        if let Err(e) = writeln!(out, "{line}").and_then(|_| out.flush()) {
+            eprintln!("Access log write failed: {e}");
+        }
+    }
+}
+
+/// First 16 hex chars of SHA-256 over the trimmed, lowercased query.
+fn query_hash(query: &str) -> String {
+    let digest = Sha256::digest(query.trim().to_lowercase().as_bytes());
+    digest[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_respects_privacy_flag() {
+        let started = Instant::now();
+        let raw = AccessLog::new(Box::new(std::io::sink()), true, 5);
+        let entry = raw.entry("search", started, 200, 3, Some("Reckless Driving"));
+        assert_eq!(entry.query.as_deref(), Some("Reckl…"));
+        assert_eq!(entry.query_hash, Some(query_hash(" reckless driving ")));
+        assert_eq!(entry.query_hash.as_ref().unwrap().len(), 16);
+
+        let private = AccessLog::new(Box::new(std::io::sink()), false, 5);
+        let entry = private.entry("search", started, 200, 3, Some("Reckless Driving"));
+        assert_eq!(entry.query, None);
+        assert!(entry.query_hash.is_some());
+        let line = serde_json::to_string(&entry).unwrap();
+        assert!(!line.contains("Reckl"));
+    }
+}
@@ -1,19 +1,26 @@
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use axum::{
-    extract::State,
-    routing::post,
+    extract::{ConnectInfo, MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
+use axum::http::{HeaderName, HeaderValue, Method};
 use clap::Parser;
 use serde::{Deserialize, Serialize};
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use utoipa::{OpenApi, ToSchema};
 
-#[path = "../embed/mod.rs"]
-mod embed;
+use proseva_embeddings::{access_log, db, embed, logging, query_log, rate_limit, search};
 
 #[derive(Parser)]
 #[command(name = "embedding-server")]
-#[command(about = "OpenAI-compatible embeddings server using EmbeddingGemma300M")]
+#[command(about = "OpenAI-compatible embeddings and search server")]
 struct Args {
     /// Port to listen on
     #[arg(long, short, default_value_t = 8000)]
@@ -22,23 +29,129 @@ struct Args {
     /// Batch size for internal processing
     #[arg(long, default_value_t = 64)]
     batch_size: usize,
+
+    /// Device to run the embedding model on
+    #[arg(long, value_enum, default_value_t = embed::Device::Auto)]
+    device: embed::Device,
+
+    /// Embedding model preset (default: the model the mounted corpora were
+    /// embedded with, or embeddinggemma-300m without any)
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(embed::models::names()))]
+    model: Option<String>,
+
+    /// Output DB to serve `/v1/search` from (embeddings.sqlite.db); mounted
+    /// as the corpus named "default"
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// Mount an additional corpus as NAME=PATH (repeatable), selected by the
+    /// `corpus` field of search requests. The first mounted corpus is the default.
+    #[arg(long = "corpus", value_name = "NAME=PATH", value_parser = search::parse_corpus_arg)]
+    corpora: Vec<(String, PathBuf)>,
+
+    /// Hold the search vectors as f32; as int8 for a quarter of the memory
+    /// and faster scoring, at a small cost in recall; or as binary, 1 bit
+    /// per component, re-scoring Hamming-nearest candidates in f32
+    #[arg(long, default_value = "f32")]
+    precision: search::Precision,
+
+    /// Reload a corpus when its DB file changes
+    #[arg(long, default_value_t = false)]
+    watch: bool,
+
+    /// Seconds between --watch checks
+    #[arg(long, default_value_t = 10)]
+    watch_interval: u64,
+
+    /// Flag a corpus as stale in `/healthz`, and warn at load, once its
+    /// source was scraped (or, when unknown, built) more than this many days ago
+    #[arg(long, value_name = "DAYS")]
+    stale_after_days: Option<u32>,
+
+    /// Append one JSON line per request to this file (`-` for stderr)
+    #[arg(long)]
+    access_log: Option<PathBuf>,
+
+    /// Log only a hash of each query, never its text
+    #[arg(long, default_value_t = false)]
+    no_raw_queries: bool,
+
+    /// Characters of query text kept in the access log
+    #[arg(long, default_value_t = 80)]
+    log_query_chars: usize,
+
+    /// Record searches and `/v1/feedback` clicks in this SQLite DB (opt-in;
+    /// export with `proseva-embeddings export queries`)
+    #[arg(long)]
+    query_log: Option<PathBuf>,
+
+    /// Most that thumbs-up/down feedback can add to or take off a node's
+    /// search score (0 turns boosting off); needs --query-log
+    #[arg(long, default_value_t = 0.1)]
+    boost_strength: f32,
+
+    /// Neutral pseudo-votes added to every node, so a few votes barely move it
+    #[arg(long, default_value_t = 5.0)]
+    boost_prior: f32,
+
+    /// Seconds between recomputing boosts from the feedback so far
+    #[arg(long, default_value_t = 300)]
+    boost_interval: u64,
+
+    /// Requests per second allowed per client IP on each of /v1/embeddings
+    /// and /v1/search (unlimited when unset)
+    #[arg(long)]
+    ip_rate: Option<f64>,
+
+    /// Requests per second allowed per API key (`x-api-key` or
+    /// `Authorization: Bearer`) on each rate-limited route, across all IPs;
+    /// keyed requests are held to --ip-rate as well
+    #[arg(long)]
+    key_rate: Option<f64>,
+
+    /// Bucket size: requests a client may burst above the steady rate
+    #[arg(long, default_value_t = 10.0)]
+    rate_burst: f64,
+
+    /// Origin allowed to call the API from a browser (repeatable); `*` allows any
+    #[arg(long = "cors-origin", value_name = "ORIGIN", default_value = "*")]
+    cors_origins: Vec<String>,
+
+    #[command(flatten)]
+    log: logging::LogArgs,
 }
 
-#[derive(Deserialize)]
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "proseva embedding server"),
+    paths(
+        embeddings_handler,
+        search_handler,
+        feedback_handler,
+        corpora_handler,
+        stats_handler,
+        node_context_handler,
+        reload_handler,
+        healthz_handler
+    )
+)]
+struct ApiDoc;
+
+#[derive(Deserialize, ToSchema)]
 struct EmbeddingRequest {
     #[allow(dead_code)]
     model: String,
     input: Input,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(untagged)]
 enum Input {
     Single(String),
     Multiple(Vec<String>),
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct EmbeddingResponse {
     object: String,
     data: Vec<EmbeddingData>,
@@ -46,57 +159,331 @@ struct EmbeddingResponse {
     usage: Usage,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct EmbeddingData {
     object: String,
     embedding: Vec<f32>,
     index: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct Usage {
     prompt_tokens: usize,
     total_tokens: usize,
 }
 
+#[derive(Deserialize, ToSchema)]
+struct SearchRequest {
+    query: String,
+    /// Corpus name; the default corpus when omitted.
+    corpus: Option<String>,
+    #[serde(default = "default_top_k")]
+    #[schema(default = 10)]
+    top_k: usize,
+    /// Share of a section's score taken from its heading, in [0, 1]; only
+    /// matters for corpora built with `--title-embeddings`.
+    #[serde(default = "default_title_weight")]
+    #[schema(default = 0.3)]
+    title_weight: f32,
+    /// Terms whose hits are removed or demoted, matched as whole words;
+    /// only for corpora built with `--fts`.
+    #[serde(default)]
+    exclude_terms: Vec<String>,
+    #[serde(default)]
+    exclude_mode: search::ExcludeMode,
+}
+
+fn default_top_k() -> usize {
+    10
+}
+
+fn default_title_weight() -> f32 {
+    search::DEFAULT_TITLE_WEIGHT
+}
+
+#[derive(Serialize, ToSchema)]
+struct SearchResponse {
+    corpus: String,
+    model: String,
+    results: Vec<search::SearchHit>,
+    /// Id to report clicks against via `/v1/feedback`; only with `--query-log`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_id: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct FeedbackRequest {
+    /// `query_id` from the search response.
+    query_id: i64,
+    /// The result the feedback is about.
+    node_id: i64,
+    /// Thumbs up or down; a plain click when omitted.
+    rating: Option<Rating>,
+}
+
+#[derive(Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Rating {
+    Up,
+    Down,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct ReloadRequest {
+    /// Corpus to reload; the default corpus when omitted.
+    corpus: Option<String>,
+    /// Serve this DB from now on; defaults to reloading the current one.
+    #[schema(value_type = Option<String>)]
+    db: Option<PathBuf>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReloadResponse {
+    corpus: String,
+    #[schema(value_type = String)]
+    db: PathBuf,
+    nodes: usize,
+}
+
+#[derive(Deserialize)]
+struct CorpusQuery {
+    corpus: Option<String>,
+}
+
+/// Neighbours listed per node by `/v1/nodes/{id}`.
+const MAX_NEIGHBOURS: usize = 25;
+
+#[derive(Serialize, ToSchema)]
+struct CorpusInfo {
+    name: String,
+    #[schema(value_type = String)]
+    db: PathBuf,
+    model: String,
+    dimensions: usize,
+    nodes: usize,
+}
+
+/// How current one corpus is, from its `build_info`.
+#[derive(Serialize, ToSchema)]
+struct CorpusHealth {
+    name: String,
+    /// RFC 3339; absent for DBs built before `build_info`.
+    built_at: Option<String>,
+    /// When the source was scraped, RFC 3339.
+    source_scraped_at: Option<String>,
+    /// `metadata` (the input's `metadata.scraped_at`) or `mtime` (the input
+    /// file's modification time).
+    source_scraped_from: Option<String>,
+    /// SHA-256 of the input snapshot the corpus was built from, when built
+    /// with `--snapshot-input`.
+    source_sha256: Option<String>,
+    /// Days since the scrape, or since the build when the scrape time is unknown.
+    age_days: Option<f64>,
+    /// Older than `--stale-after-days`.
+    stale: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct HealthResponse {
+    /// `ok`, or `stale` when any corpus is.
+    status: &'static str,
+    corpora: Vec<CorpusHealth>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+type ApiError = (StatusCode, String);
+
 struct AppState {
     embedder: embed::Embedder,
+    corpora: search::Corpora,
+    stale_after: Option<chrono::Duration>,
+    access_log: Option<access_log::AccessLog>,
+    query_log: Option<Mutex<rusqlite::Connection>>,
+    ip_limiter: Option<rate_limit::RateLimiter>,
+    key_limiter: Option<rate_limit::RateLimiter>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    logging::init(&args.log, env!("CARGO_CRATE_NAME"))?;
 
-    let embedder = embed::Embedder::new(args.batch_size).await?;
-    let state = Arc::new(AppState { embedder });
+    if args.watch && args.db.is_none() && args.corpora.is_empty() {
+        anyhow::bail!("--watch needs --db or --corpus");
+    }
 
-    let app = Router::new()
+    let stale_after = args
+        .stale_after_days
+        .map(|days| chrono::Duration::days(days.into()));
+    let mut corpora = search::Corpora::default();
+    let mounts = args.db.iter().map(|db| ("default".to_string(), db.clone()));
+    for (name, path) in mounts.chain(args.corpora.iter().cloned()) {
+        let index = search::SearchIndex::load_with(&path, args.precision)?;
+        println!(
+            "Corpus {}: {} {} vectors from {} ({})",
+            name,
+            index.len(),
+            index.precision.as_str(),
+            path.display(),
+            index.model_name
+        );
+        if let Some(warning) = staleness(&name, &index, stale_after) {
+            eprintln!("{warning}");
+        }
+        corpora.insert(name, index)?;
+    }
+
+    let model = match args.model {
+        Some(ref name) => embed::models::find(name)?,
+        None => match corpora.iter().next() {
+            Some((_, handle)) => embed::models::find(&handle.current().model_name)?,
+            None => embed::models::find(embed::models::DEFAULT_MODEL)?,
+        },
+    };
+    let embedder = embed::Embedder::new(model, args.device, args.batch_size, None).await?;
+    for (name, handle) in corpora.iter() {
+        let index = handle.current();
+        if index.model_name != model.model_id {
+            anyhow::bail!(
+                "Corpus {} was embedded with {}, the query model is {}",
+                name,
+                index.model_name,
+                model.model_id
+            );
+        }
+        let dims = index.dims;
+        if dims != embedder.model_dimensions() {
+            anyhow::bail!(
+                "Corpus {} has {}-dimensional embeddings, the query model produces {}",
+                name,
+                dims,
+                embedder.model_dimensions()
+            );
+        }
+        if args.watch {
+            tokio::spawn(watch_db(
+                name.to_string(),
+                handle.clone(),
+                Duration::from_secs(args.watch_interval),
+                stale_after,
+            ));
+        }
+    }
+    let access_log = args
+        .access_log
+        .as_deref()
+        .map(|path| access_log::AccessLog::open(path, !args.no_raw_queries, args.log_query_chars))
+        .transpose()?;
+    let query_log = args
+        .query_log
+        .as_deref()
+        .map(|path| query_log::open(path).map(Mutex::new))
+        .transpose()?;
+    let limiter = |rate: Option<f64>| {
+        rate.filter(|r| *r > 0.0)
+            .map(|r| rate_limit::RateLimiter::new(r, args.rate_burst))
+    };
+    let state = Arc::new(AppState {
+        embedder,
+        corpora,
+        stale_after,
+        access_log,
+        query_log,
+        ip_limiter: limiter(args.ip_rate),
+        key_limiter: limiter(args.key_rate),
+    });
+
+    if state.query_log.is_some() && args.boost_strength > 0.0 {
+        tokio::spawn(refresh_boosts(
+            state.clone(),
+            args.boost_strength,
+            args.boost_prior,
+            Duration::from_secs(args.boost_interval),
+        ));
+    }
+
+    // The routes that hit the model are rate limited; metadata and admin are not
+    let limited = Router::new()
         .route("/v1/embeddings", post(embeddings_handler))
-        .layer(CorsLayer::permissive())
+        .route("/v1/search", post(search_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit_layer));
+    let app = Router::new()
+        .merge(limited)
+        .route("/v1/feedback", post(feedback_handler))
+        .route("/v1/corpora", get(corpora_handler))
+        .route("/v1/stats", get(stats_handler))
+        .route("/admin/reload", post(reload_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/v1/nodes/{id}", get(node_context_handler))
+        .route("/openapi.json", get(openapi_handler))
+        .route("/ui", get(ui_handler))
+        .layer(cors_layer(&args.cors_origins)?)
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", args.port)).await?;
     println!("Embedding server listening on port {}...", args.port);
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
+/// `*` (the default) keeps the old permissive behaviour; otherwise only the
+/// listed origins may call the API.
+fn cors_layer(origins: &[String]) -> anyhow::Result<CorsLayer> {
+    if origins.iter().any(|o| o == "*") {
+        return Ok(CorsLayer::permissive());
+    }
+    let origins = origins
+        .iter()
+        .map(|o| HeaderValue::from_str(o))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static("x-api-key"),
+        ]))
+}
+
+/// Minimal search page for sanity-checking a build; see `src/ui/index.html`.
+async fn ui_handler() -> Html<&'static str> {
+    Html(include_str!("../ui/index.html"))
+}
+
+async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// OpenAI-compatible embeddings (query prefix applied).
+#[utoipa::path(
+    post,
+    path = "/v1/embeddings",
+    request_body = EmbeddingRequest,
+    responses(
+        (status = 200, body = EmbeddingResponse),
+        (status = 429, description = "Rate limited; see Retry-After"),
+    )
+)]
 async fn embeddings_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<EmbeddingRequest>,
 ) -> Json<EmbeddingResponse> {
+    let started = Instant::now();
     let texts = match payload.input {
         Input::Single(s) => vec![s],
         Input::Multiple(v) => v,
     };
 
-    // Apply EmbeddingGemma query prefix for search queries
-    let prefixed: Vec<String> = texts.iter().map(|t| embed::format_query(t)).collect();
+    // Apply the model's query prefix for search queries
+    let model = state.embedder.model();
+    let prefixed: Vec<String> = texts.iter().map(|t| model.format_query(t)).collect();
 
     // Note: We don't have a tokenizer exposed here to count tokens accurately,
     // so we'll just report 0 for now or use a heuristic. OpenAI expects usage.
-    let embeddings = state.embedder.pool.embed(prefixed, None).await.expect("Failed to generate embeddings");
+    let embeddings = state.embedder.embed(prefixed).await.expect("Failed to generate embeddings");
 
     let data = embeddings
         .into_iter()
@@ -106,15 +493,477 @@ async fn embeddings_handler(
             embedding,
             index: i,
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    if let Some(log) = &state.access_log {
+        log.write(&log.entry("embeddings", started, 200, data.len(), None));
+    }
 
     Json(EmbeddingResponse {
         object: "list".to_string(),
         data,
-        model: "onnx-community/embeddinggemma-300m-ONNX".to_string(),
+        model: model.model_id.to_string(),
         usage: Usage {
             prompt_tokens: 0,
             total_tokens: 0,
         },
     })
 }
+
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+}
+
+/// Every request spends from its IP's bucket, and also from its API key's
+/// bucket when it sends one. Keys aren't verified here, so they can't be used
+/// to dodge the IP limit.
+async fn rate_limit_layer(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    route: MatchedPath,
+    req: Request,
+    next: Next,
+) -> Response {
+    let now = Instant::now();
+    let route = route.as_str();
+    let mut checks = Vec::new();
+    if let Some(ref limiter) = state.ip_limiter {
+        checks.push((limiter, format!("{} ip:{}", route, addr.ip())));
+    }
+    if let (Some(limiter), Some(key)) = (&state.key_limiter, api_key(req.headers())) {
+        checks.push((limiter, format!("{} key:{}", route, key)));
+    }
+    for (limiter, bucket) in checks {
+        if let Err(wait) = limiter.check(&bucket, now) {
+            let secs = rate_limit::retry_after_secs(wait);
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, secs.to_string())],
+                format!("Rate limit exceeded; retry in {}s", secs),
+            )
+                .into_response();
+        }
+    }
+    next.run(req).await
+}
+
+fn corpus<'a>(
+    state: &'a AppState,
+    name: Option<&str>,
+) -> Result<&'a Arc<search::IndexHandle>, ApiError> {
+    state.corpora.get(name).map_err(|e| {
+        let status = match state.corpora.is_empty() {
+            true => StatusCode::SERVICE_UNAVAILABLE,
+            false => StatusCode::NOT_FOUND,
+        };
+        (status, e.to_string())
+    })
+}
+
+fn corpus_name(state: &AppState, name: Option<String>) -> String {
+    name.or_else(|| state.corpora.iter().next().map(|(n, _)| n.to_string()))
+        .unwrap_or_default()
+}
+
+/// Mounted corpora with their model and vector counts.
+#[utoipa::path(get, path = "/v1/corpora", responses((status = 200, body = Vec<CorpusInfo>)))]
+async fn corpora_handler(State(state): State<Arc<AppState>>) -> Json<Vec<CorpusInfo>> {
+    Json(
+        state
+            .corpora
+            .iter()
+            .map(|(name, handle)| {
+                let index = handle.current();
+                CorpusInfo {
+                    name: name.to_string(),
+                    db: index.path.clone(),
+                    model: index.model_name.clone(),
+                    dimensions: index.dims,
+                    nodes: index.len(),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Corpus statistics for the UI, precomputed by the build.
+#[utoipa::path(
+    get,
+    path = "/v1/stats",
+    params(("corpus" = Option<String>, Query, description = "Corpus name; the default corpus when omitted")),
+    responses(
+        (status = 200, body = db::corpus_stats::CorpusStats),
+        (status = 404, description = "Unknown corpus, or a DB built before corpus_stats"),
+    )
+)]
+async fn stats_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CorpusQuery>,
+) -> Result<Json<db::corpus_stats::CorpusStats>, ApiError> {
+    let index = corpus(&state, query.corpus.as_deref())?.current();
+    index.corpus_stats.clone().map(Json).ok_or((
+        StatusCode::NOT_FOUND,
+        format!(
+            "{} has no corpus_stats; rebuild it to add them",
+            index.path.display()
+        ),
+    ))
+}
+
+/// A warning when `index` is older than `--stale-after-days`.
+fn staleness(
+    name: &str,
+    index: &search::SearchIndex,
+    stale_after: Option<chrono::Duration>,
+) -> Option<String> {
+    let limit = stale_after?;
+    let age = index.age(chrono::Utc::now())?;
+    (age > limit).then(|| {
+        format!(
+            "Corpus {}: source is {} days old, over --stale-after-days {}",
+            name,
+            age.num_days(),
+            limit.num_days()
+        )
+    })
+}
+
+/// Liveness, plus how old each corpus's source is. Always 200; a stale corpus
+/// is reported in `status` and `warnings`, not as a failure.
+#[utoipa::path(get, path = "/healthz", responses((status = 200, body = HealthResponse)))]
+async fn healthz_handler(State(state): State<Arc<AppState>>) -> Json<HealthResponse> {
+    let now = chrono::Utc::now();
+    let mut warnings = Vec::new();
+    let corpora: Vec<CorpusHealth> = state
+        .corpora
+        .iter()
+        .map(|(name, handle)| {
+            let index = handle.current();
+            let warning = staleness(name, &index, state.stale_after);
+            let stale = warning.is_some();
+            warnings.extend(warning);
+            CorpusHealth {
+                name: name.to_string(),
+                built_at: index.built_at.map(|t| t.to_rfc3339()),
+                source_scraped_at: index.source_scraped_at.map(|t| t.to_rfc3339()),
+                source_scraped_from: index.source_scraped_from.clone(),
+                source_sha256: index.source_sha256.clone(),
+                age_days: index
+                    .age(now)
+                    .map(|age| age.num_seconds() as f64 / 86_400.0),
+                stale,
+            }
+        })
+        .collect();
+    Json(HealthResponse {
+        status: if warnings.is_empty() { "ok" } else { "stale" },
+        corpora,
+        warnings,
+    })
+}
+
+/// Semantic search over one corpus.
+#[utoipa::path(
+    post,
+    path = "/v1/search",
+    request_body = SearchRequest,
+    responses(
+        (status = 200, body = SearchResponse),
+        (status = 400, description = "title_weight outside [0, 1], or exclude_terms on a corpus without --fts"),
+        (status = 404, description = "Unknown corpus"),
+        (status = 429, description = "Rate limited; see Retry-After"),
+        (status = 503, description = "No corpus loaded"),
+    )
+)]
+async fn search_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SearchRequest>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let started = Instant::now();
+    let result = search(&state, &payload).await;
+
+    if let Some(log) = &state.access_log {
+        let (status, results) = match &result {
+            Ok(response) => (StatusCode::OK, response.results.len()),
+            Err((status, _)) => (*status, 0),
+        };
+        let mut entry = log.entry(
+            "search",
+            started,
+            status.as_u16(),
+            results,
+            Some(&payload.query),
+        );
+        entry.corpus = Some(corpus_name(&state, payload.corpus.clone()));
+        log.write(&entry);
+    }
+
+    result.map(Json)
+}
+
+async fn search(state: &AppState, payload: &SearchRequest) -> Result<SearchResponse, ApiError> {
+    // Pin the index for the whole request so a concurrent reload can't swap
+    // it out from under us.
+    let (index, boosts) = corpus(state, payload.corpus.as_deref())?.current_with_boosts();
+    if !(0.0..=1.0).contains(&payload.title_weight) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("title_weight {} is not in [0, 1]", payload.title_weight),
+        ));
+    }
+    let exclusion = index
+        .exclusion(&payload.exclude_terms, payload.exclude_mode)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    let query = state.embedder.model().format_query(&payload.query);
+    let mut embeddings = state
+        .embedder
+        .embed(vec![query])
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let corpus = corpus_name(state, payload.corpus.clone());
+    // Scoring is CPU-bound, and a binary index reads its candidates' f32
+    // rows from disk, so keep it off the async workers.
+    let (vector, top_k, title_weight) = (embeddings.remove(0), payload.top_k, payload.title_weight);
+    let searched = index.clone();
+    let results = tokio::task::spawn_blocking(move || {
+        searched.search(&vector, top_k, Some(&boosts), title_weight, Some(&exclusion))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let query_id = match &state.query_log {
+        Some(log) => {
+            let hits: Vec<query_log::LoggedHit> = results
+                .iter()
+                .map(|h| query_log::LoggedHit {
+                    node_id: h.node.node_id,
+                    source: h.node.source.clone(),
+                    source_id: h.node.source_id.clone(),
+                    chunk_idx: h.node.chunk_idx,
+                    score: h.score,
+                })
+                .collect();
+            let conn = log.lock().unwrap();
+            match query_log::record_query(&conn, &corpus, &payload.query, &hits) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    // Losing a log row shouldn't fail the search.
+                    eprintln!("Query log write failed: {e:#}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    Ok(SearchResponse {
+        corpus,
+        model: index.model_name.clone(),
+        results,
+        query_id,
+    })
+}
+
+/// Record a click on, or a thumbs-up/down for, a search result.
+#[utoipa::path(
+    post,
+    path = "/v1/feedback",
+    request_body = FeedbackRequest,
+    responses(
+        (status = 204, description = "Recorded"),
+        (status = 404, description = "Unknown query_id"),
+        (status = 503, description = "Query logging is off"),
+    )
+)]
+async fn feedback_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<FeedbackRequest>,
+) -> Result<StatusCode, ApiError> {
+    let Some(log) = &state.query_log else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Query logging is off; start the server with --query-log".to_string(),
+        ));
+    };
+    let conn = log.lock().unwrap();
+    let recorded = match payload.rating {
+        Some(rating) => query_log::record_vote(
+            &conn,
+            payload.query_id,
+            payload.node_id,
+            matches!(rating, Rating::Up),
+        ),
+        None => query_log::record_click(&conn, payload.query_id, payload.node_id),
+    };
+    match recorded {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            format!("No logged query {}", payload.query_id),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"))),
+    }
+}
+
+/// Breadcrumbs (containing title/chapter/...) and citation neighbours of a node.
+#[utoipa::path(
+    get,
+    path = "/v1/nodes/{id}",
+    params(
+        ("id" = i64, Path, description = "Node id"),
+        ("corpus" = Option<String>, Query, description = "Corpus; the default when omitted"),
+    ),
+    responses(
+        (status = 200, body = search::NodeContext),
+        (status = 404, description = "Unknown node or corpus"),
+    )
+)]
+async fn node_context_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<CorpusQuery>,
+) -> Result<Json<search::NodeContext>, ApiError> {
+    let path = corpus(&state, query.corpus.as_deref())?.current().path.clone();
+    let context =
+        tokio::task::spawn_blocking(move || search::node_context(&path, id, MAX_NEIGHBOURS))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+    context
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("No node {}", id)))
+}
+
+/// Load a new DB for a corpus in the background and swap it in.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    request_body(content = Option<ReloadRequest>),
+    responses(
+        (status = 200, body = ReloadResponse),
+        (status = 409, description = "Reload failed or already running; the old index keeps serving"),
+    )
+)]
+async fn reload_handler(
+    State(state): State<Arc<AppState>>,
+    payload: Option<Json<ReloadRequest>>,
+) -> Result<Json<ReloadResponse>, ApiError> {
+    let Json(payload) = payload.unwrap_or_default();
+    let new = corpus(&state, payload.corpus.as_deref())?
+        .reload(payload.db)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, format!("{e:#}")))?;
+    let name = corpus_name(&state, payload.corpus);
+    println!("Corpus {}: reloaded {} vectors from {}", name, new.len(), new.path.display());
+    if let Some(warning) = staleness(&name, &new, state.stale_after) {
+        eprintln!("{warning}");
+    }
+    Ok(Json(ReloadResponse {
+        corpus: name,
+        db: new.path.clone(),
+        nodes: new.len(),
+    }))
+}
+
+/// Recompute every corpus's boosts from the feedback so far, now and then
+/// every `interval`. New votes affect ranking from the next run on.
+async fn refresh_boosts(state: Arc<AppState>, strength: f32, prior: f32, interval: Duration) {
+    loop {
+        let task_state = state.clone();
+        let computed = tokio::task::spawn_blocking(move || {
+            let log = task_state.query_log.as_ref().expect("boosts need --query-log");
+            let mut boosts = query_log::compute_boosts(&log.lock().unwrap(), strength, prior)?;
+            for (name, handle) in task_state.corpora.iter() {
+                handle.set_boosts(boosts.remove(name).unwrap_or_default());
+            }
+            anyhow::Ok(())
+        })
+        .await;
+        match computed {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Boost refresh failed, keeping the previous boosts: {e:#}"),
+            Err(e) => eprintln!("Boost refresh failed, keeping the previous boosts: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Poll the served DB's mtime and reload once it has changed and then stayed
+/// put for a full interval, so a build still writing the file isn't picked up.
+async fn watch_db(
+    name: String,
+    index: Arc<search::IndexHandle>,
+    interval: Duration,
+    stale_after: Option<chrono::Duration>,
+) {
+    let mtime = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut loaded: Option<SystemTime> = mtime(&index.current().path);
+    let mut pending: Option<SystemTime> = None;
+
+    loop {
+        tokio::time::sleep(interval).await;
+        let path = index.current().path.clone();
+        let seen = mtime(&path);
+        if seen == loaded {
+            pending = None;
+            continue;
+        }
+        if seen != pending {
+            pending = seen;
+            continue;
+        }
+        match index.reload(Some(path.clone())).await {
+            Ok(new) => {
+                println!(
+                    "Corpus {}: reloaded {} vectors from {} (changed on disk)",
+                    name,
+                    new.len(),
+                    path.display()
+                );
+                if let Some(warning) = staleness(&name, &new, stale_after) {
+                    eprintln!("{warning}");
+                }
+            }
+            Err(e) => eprintln!(
+                "Corpus {}: reload of {} failed, still serving the old index: {e:#}",
+                name,
+                path.display()
+            ),
+        }
+        loaded = seen;
+        pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_covers_routes() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        for path in [
+            "/v1/embeddings",
+            "/v1/search",
+            "/v1/feedback",
+            "/v1/corpora",
+            "/v1/nodes/{id}",
+            "/admin/reload",
+            "/healthz",
+        ] {
+            assert!(spec["paths"][path].is_object(), "missing {path}");
+        }
+        assert!(spec["components"]["schemas"]["SearchHit"].is_object());
+    }
+}
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use rusqlite::Connection;
+
+use proseva_embeddings::query;
+
+#[derive(Parser)]
+#[command(name = "graph-query")]
+#[command(about = "Run traversal queries against graph.sqlite.db")]
+#[command(after_help = r#"Examples:
+  graph-query --db graph.sqlite.db 'node("virginia_code","46.2-862") -cites-> *'
+  graph-query --db graph.sqlite.db 'node("virginia_code","46.2-862") -cites-> * -contains^-> chapter'
+  graph-query --db graph.sqlite.db 'section -contains+^-> title'
+  graph-query --db graph.sqlite.db 'mentions("46.2-862")'"#)]
+struct Args {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    db: PathBuf,
+
+    /// Query, e.g. `node("virginia_code","46.2-862") -cites-> * -contains^-> title`
+    query: String,
+
+    /// Print results as JSON lines instead of a table
+    #[arg(long, default_value_t = false)]
+    json: bool,
+
+    /// Maximum number of results to print
+    #[arg(long)]
+    limit: Option<usize>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let query = query::parse(&args.query)?;
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let nodes = query::evaluate(&conn, &query)?;
+    query::print_nodes(&nodes, args.json, args.limit)
+}
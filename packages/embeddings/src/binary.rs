@@ -0,0 +1,161 @@
+//! 1-bit vectors for the search index's first stage (`--precision binary`):
+//! one bit per component, a 32nd of the memory of f32 rows, for the WASM and
+//! desktop deployments.
+//!
+//! A component's bit is its sign. The Hamming distance between two sign
+//! vectors tracks the angle between the originals (`cos(π · h / dims)` for
+//! random hyperplanes), so the rows that score best on it are good
+//! candidates; [`crate::search`] then re-scores them with their f32 vectors
+//! from a [`SideFile`] and ranks on those exact scores.
+
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+
+use anyhow::Result;
+
+/// Row-major sign bits, `words` u64s per row.
+#[derive(Debug, Clone, Default)]
+pub struct BinaryVectors {
+    dims: usize,
+    words: usize,
+    bits: Vec<u64>,
+}
+
+/// The sign bits of `v`, packed low bit first.
+pub fn pack(v: &[f32]) -> Vec<u64> {
+    let mut words = vec![0u64; v.len().div_ceil(64)];
+    for (i, x) in v.iter().enumerate() {
+        if *x > 0.0 {
+            words[i / 64] |= 1 << (i % 64);
+        }
+    }
+    words
+}
+
+impl BinaryVectors {
+    pub fn new(dims: usize) -> Self {
+        Self {
+            dims,
+            words: dims.div_ceil(64),
+            bits: Vec::new(),
+        }
+    }
+
+    /// Append the sign bits of `v`, which must have `dims` components.
+    pub fn push(&mut self, v: &[f32]) {
+        debug_assert_eq!(v.len(), self.dims);
+        self.bits.extend(pack(v));
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len().checked_div(self.words).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Bits that differ between row `row` and `query` (from [`pack`]).
+    pub fn hamming(&self, row: usize, query: &[u64]) -> u32 {
+        self.bits[row * self.words..(row + 1) * self.words]
+            .iter()
+            .zip(query)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+
+    /// Cosine similarity estimated from the Hamming distance.
+    pub fn cosine(&self, row: usize, query: &[u64]) -> f32 {
+        (std::f32::consts::PI * self.hamming(row, query) as f32 / self.dims as f32).cos()
+    }
+}
+
+/// The f32 rows behind a binary index, in an unlinked temporary file written
+/// at load: they sit on disk and in the page cache rather than in the
+/// process's memory, and belong to the loaded index, so a rebuild that
+/// replaces the DB can't change them under it.
+#[derive(Debug)]
+pub struct SideFile {
+    file: File,
+    dims: usize,
+    rows: usize,
+}
+
+impl SideFile {
+    pub fn new(dims: usize) -> Result<Self> {
+        Ok(Self {
+            file: tempfile::tempfile()?,
+            dims,
+            rows: 0,
+        })
+    }
+
+    /// Append `v`, which must have `dims` components.
+    pub fn push(&mut self, v: &[f32]) -> Result<()> {
+        debug_assert_eq!(v.len(), self.dims);
+        let bytes: Vec<u8> = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+        self.file.write_all(&bytes)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Row `row` as written.
+    pub fn read(&self, row: usize) -> Result<Vec<f32>> {
+        let mut bytes = vec![0u8; self.dims * 4];
+        self.file
+            .read_exact_at(&mut bytes, (row * self.dims * 4) as u64)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_and_hamming() {
+        // 70 components: the sign bits span two words.
+        let v: Vec<f32> = (0..70)
+            .map(|i| if i % 3 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let packed = pack(&v);
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[0].count_ones() + packed[1].count_ones(), 24);
+
+        let mut vectors = BinaryVectors::new(70);
+        vectors.push(&v);
+        vectors.push(&v.iter().map(|x| -x).collect::<Vec<_>>());
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors.hamming(0, &packed), 0);
+        assert_eq!(vectors.hamming(1, &packed), 70);
+        assert!((vectors.cosine(0, &packed) - 1.0).abs() < 1e-6);
+        assert!((vectors.cosine(1, &packed) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_side_file_round_trips_rows() {
+        let mut side = SideFile::new(3).unwrap();
+        side.push(&[0.5, -0.25, 1.0]).unwrap();
+        side.push(&[0.0, 2.0, -3.5]).unwrap();
+        assert_eq!(side.len(), 2);
+        assert_eq!(side.read(1).unwrap(), [0.0, 2.0, -3.5]);
+        assert_eq!(side.read(0).unwrap(), [0.5, -0.25, 1.0]);
+        assert!(side.read(2).is_err());
+    }
+}
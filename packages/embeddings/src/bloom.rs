@@ -0,0 +1,131 @@
+//! Bloom filter over the section numbers a document mentions.
+//!
+//! Stored per document node in `document_section_filters` so "does this
+//! document mention § X" can be answered without loading edge lists. False
+//! positives are possible (~1% by default); false negatives are not.
+//!
+//! The layout is deliberately simple so other consumers can read it: `bits`
+//! is little-endian u64 words, bit `i` is `word[i / 64] >> (i % 64) & 1`, and
+//! probe `j` of key `k` is `(h1 + j * h2) % num_bits`, where `h1` / `h2` are
+//! FNV-1a 64 of the normalized key with offset bases [`SEED_1`] / [`SEED_2`].
+
+const SEED_1: u64 = 0xcbf2_9ce4_8422_2325;
+const SEED_2: u64 = 0x8422_2325_cbf2_9ce4;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Target false-positive rate used by [`SectionFilter::for_items`].
+pub const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionFilter {
+    pub num_bits: u64,
+    pub num_hashes: u32,
+    words: Vec<u64>,
+}
+
+/// Keys are compared without a leading `§`, surrounding whitespace, or case.
+fn normalize_key(key: &str) -> String {
+    key.trim().trim_start_matches('§').trim().to_lowercase()
+}
+
+fn fnv1a(seed: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(seed, |h, &b| (h ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+impl SectionFilter {
+    /// An empty filter sized for `n` keys at [`FALSE_POSITIVE_RATE`].
+    pub fn for_items(n: usize) -> Self {
+        let n = n.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = bits.max(64).next_multiple_of(64);
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 16.0) as u32;
+        Self {
+            num_bits,
+            num_hashes,
+            words: vec![0; (num_bits / 64) as usize],
+        }
+    }
+
+    pub fn from_keys<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let keys: Vec<&str> = keys.into_iter().collect();
+        let mut filter = Self::for_items(keys.len());
+        for key in keys {
+            filter.insert(key);
+        }
+        filter
+    }
+
+    fn probes(&self, key: &str) -> impl Iterator<Item = u64> + '_ {
+        let key = normalize_key(key);
+        let h1 = fnv1a(SEED_1, key.as_bytes());
+        let h2 = fnv1a(SEED_2, key.as_bytes()) | 1;
+        (0..self.num_hashes as u64).map(move |j| h1.wrapping_add(j.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        let probes: Vec<u64> = self.probes(key).collect();
+        for bit in probes {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// `false` means definitely not mentioned; `true` means probably mentioned.
+    pub fn may_contain(&self, key: &str) -> bool {
+        self.probes(key)
+            .all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    pub fn from_parts(num_bits: u64, num_hashes: u32, bytes: &[u8]) -> anyhow::Result<Self> {
+        if num_bits == 0 || !num_bits.is_multiple_of(64) || bytes.len() as u64 * 8 != num_bits {
+            anyhow::bail!(
+                "Malformed section filter: {} bits in {} bytes",
+                num_bits,
+                bytes.len()
+            );
+        }
+        let words = bytes
+            .chunks_exact(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            num_bits,
+            num_hashes,
+            words,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_membership_and_round_trip() {
+        let filter = SectionFilter::from_keys(["46.2-862", "8.01-243", "19.2-392"]);
+        assert!(filter.may_contain("46.2-862"));
+        assert!(filter.may_contain("§ 8.01-243"));
+
+        let restored =
+            SectionFilter::from_parts(filter.num_bits, filter.num_hashes, &filter.to_bytes())
+                .unwrap();
+        assert_eq!(restored, filter);
+        assert!(restored.may_contain("19.2-392"));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let keys: Vec<String> = (0..500).map(|i| format!("1-{i}")).collect();
+        let filter = SectionFilter::from_keys(keys.iter().map(String::as_str));
+        let false_positives = (0..10_000)
+            .filter(|i| filter.may_contain(&format!("2-{i}")))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+}
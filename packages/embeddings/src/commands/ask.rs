@@ -0,0 +1,328 @@
+//! `ask`: a reference retrieval-augmented answer over an output DB. Vector
+//! search and, when the DB was built with `--fts`, keyword search each
+//! nominate candidates, fused by reciprocal rank. The best passages are
+//! rebuilt from `--input`, numbered into a context with their citations, and
+//! sent with the question to a chat-completions endpoint, whose answer cites
+//! the sections it relies on. Those citations are then checked against the
+//! passages and the graph (see [`crate::verify`]).
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::db::{build_info, fts};
+use crate::embed;
+use crate::graph::store;
+use crate::search::{self, IndexedNode, SearchIndex};
+use crate::summarize;
+use crate::verify;
+
+const SYSTEM_PROMPT: &str = "You answer questions about Virginia law using only the numbered \
+     passages provided. Cite each statute you rely on by section, e.g. § 46.2-862, with the \
+     passage number in brackets, e.g. [2]. If the passages don't answer the question, say so \
+     instead of guessing. Give legal information, not legal advice.";
+
+#[derive(Args, Debug)]
+pub struct AskArgs {
+    /// Question, e.g. "is 20 over the limit reckless driving?"
+    pub question: String,
+
+    /// Path to embeddings.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// virginia.db the DB was built from; passage texts are rebuilt from it
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Chat-completions URL, e.g. `http://localhost:11434/v1/chat/completions`
+    #[arg(long)]
+    pub llm_endpoint: String,
+
+    /// Model name sent to --llm-endpoint
+    #[arg(long)]
+    pub llm_model: String,
+
+    /// Environment variable holding a bearer token for --llm-endpoint
+    #[arg(long)]
+    pub api_key_env: Option<String>,
+
+    /// Passages in the context
+    #[arg(long, default_value_t = 8)]
+    pub top_k: usize,
+
+    /// Candidates each of vector and keyword search nominate before fusion
+    #[arg(long, default_value_t = 50)]
+    pub candidates: usize,
+
+    /// Characters of passage text in the context, at most
+    #[arg(long, default_value_t = 12_000)]
+    pub context_chars: usize,
+
+    /// Longest answer, in tokens
+    #[arg(long, default_value_t = 512)]
+    pub max_tokens: u32,
+
+    /// Print the answer and its sources as one JSON object
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Fail when the answer cites a section that isn't one of the passages
+    /// (one a passage only cites counts as unverified too)
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+}
+
+/// One numbered passage of the context.
+#[derive(Debug, Serialize)]
+struct Passage {
+    number: usize,
+    citation: String,
+    #[serde(flatten)]
+    node: IndexedNode,
+    /// Fused reciprocal-rank score.
+    score: f32,
+    #[serde(skip)]
+    text: String,
+}
+
+/// How a node is cited, e.g. `Va. Code § 46.2-862` or `42 U.S.C. § 1983`.
+fn citation(source: &str, source_id: &str) -> String {
+    let parts: Vec<&str> = source_id.split(':').collect();
+    match (source, parts.as_slice()) {
+        ("virginia_code", _) => format!("Va. Code § {source_id}"),
+        ("constitution", [article, section]) => format!("Va. Const. art. {article}, § {section}"),
+        ("acts", [year, chapter]) => format!("Acts {year}, c. {chapter}"),
+        ("federal_code", ["usc", title, section]) => format!("{title} U.S.C. § {section}"),
+        ("federal_code", ["cfr", title, section]) => format!("{title} C.F.R. § {section}"),
+        _ => format!("{source} {source_id}"),
+    }
+}
+
+/// The first `top_k` fused nodes with a text, within `budget` characters of
+/// text. A passage that would overrun the budget is skipped for a shorter
+/// one, except the first, which is cut to fit.
+fn select_passages(
+    fused: &[(i64, f32)],
+    node: impl Fn(i64) -> Option<IndexedNode>,
+    texts: &mut HashMap<store::TextKey, String>,
+    top_k: usize,
+    budget: usize,
+) -> Vec<Passage> {
+    let mut passages: Vec<Passage> = Vec::new();
+    let mut used = 0;
+    for &(node_id, score) in fused {
+        if passages.len() == top_k {
+            break;
+        }
+        let Some(node) = node(node_id) else { continue };
+        let key = (node.source.clone(), node.source_id.clone(), node.chunk_idx);
+        let Some(text) = texts.remove(&key) else {
+            continue;
+        };
+        let len = text.chars().count();
+        let text = match (used + len > budget, passages.is_empty()) {
+            (false, _) => text,
+            (true, true) => store::summarize(&text, budget),
+            (true, false) => continue,
+        };
+        used += text.chars().count();
+        passages.push(Passage {
+            number: passages.len() + 1,
+            citation: citation(&node.source, &node.source_id),
+            node,
+            score,
+            text,
+        });
+    }
+    passages
+}
+
+/// The user message: the numbered passages, then the question.
+fn user_message(question: &str, passages: &[Passage]) -> String {
+    let mut message = String::from("Passages:\n");
+    for p in passages {
+        message.push_str(&format!("\n[{}] {}\n{}\n", p.number, p.citation, p.text));
+    }
+    message.push_str(&format!("\nQuestion: {question}"));
+    message
+}
+
+pub async fn run(args: AskArgs) -> Result<()> {
+    let api_key = match args.api_key_env {
+        Some(ref var) => {
+            Some(std::env::var(var).with_context(|| format!("--api-key-env: ${var} is not set"))?)
+        }
+        None => None,
+    };
+    let index = SearchIndex::load(&args.db)?;
+    if index.is_empty() {
+        bail!("{} has no embeddings to search", args.db.display());
+    }
+    // Queries must be embedded by the model that embedded the DB.
+    let model = embed::models::find(&index.model_name).with_context(|| {
+        format!(
+            "{} was embedded with an unsupported model",
+            args.db.display()
+        )
+    })?;
+
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let keyword = if fts::has_fts(&conn)? {
+        fts::ranked_matches(&conn, &args.question, args.candidates)?
+    } else {
+        info!("No full-text index (build with --fts); retrieving by vector search only");
+        Vec::new()
+    };
+    let embedder = embed::Embedder::new(model, embed::Device::Auto, 1, None).await?;
+    let mut vectors = embedder
+        .embed(vec![model.format_query(&args.question)])
+        .await?;
+    let hits = index.search(
+        &vectors.remove(0),
+        args.candidates,
+        None,
+        search::DEFAULT_TITLE_WEIGHT,
+        None,
+    )?;
+    let semantic: Vec<i64> = hits.iter().map(|h| h.node.node_id).collect();
+    let fused = search::fuse_rankings(&[semantic, keyword]);
+
+    // Only the fused candidates' texts are rebuilt, not the whole corpus's.
+    let keys: HashSet<store::TextKey> = fused
+        .iter()
+        .filter_map(|&(id, _)| index.node(id))
+        .map(|n| (n.source.clone(), n.source_id.clone(), n.chunk_idx))
+        .collect();
+    let mut texts =
+        store::rebuild_texts_for(&args.input, build_info::read_chunking(&conn)?, &keys)?;
+    let passages = select_passages(
+        &fused,
+        |id| index.node(id).cloned(),
+        &mut texts,
+        args.top_k,
+        args.context_chars,
+    );
+    if passages.is_empty() {
+        bail!(
+            "None of the retrieved nodes has a text in {}; is it the input the DB was built from?",
+            args.input.display()
+        );
+    }
+
+    let body = serde_json::json!({
+        "model": args.llm_model,
+        "max_tokens": args.max_tokens,
+        "temperature": 0,
+        "messages": [
+            {"role": "system", "content": SYSTEM_PROMPT},
+            {"role": "user", "content": user_message(&args.question, &passages)},
+        ],
+    });
+    let client = reqwest::Client::new();
+    let answer = summarize::chat(&client, &args.llm_endpoint, api_key.as_deref(), &body).await?;
+
+    let context: Vec<(&str, &str, &str)> = passages
+        .iter()
+        .map(|p| (&*p.node.source, &*p.node.source_id, &*p.text))
+        .collect();
+    let citations = verify::check_citations(&answer, &context, |source, source_id| {
+        verify::has_node(&conn, source, source_id)
+    })?;
+    let unverified: Vec<&verify::CheckedCitation> = citations
+        .iter()
+        .filter(|c| !c.grounding.grounded(args.strict))
+        .collect();
+    for c in &unverified {
+        warn!(
+            citation = %c.text,
+            grounding = ?c.grounding,
+            "Answer cites a section outside its context"
+        );
+    }
+
+    if args.json {
+        let value = serde_json::json!({
+            "answer": answer,
+            "sources": passages,
+            "citations": citations,
+        });
+        println!("{}", value);
+    } else {
+        println!("{answer}\n\nSources:");
+        for p in &passages {
+            println!(
+                "  [{}] {:<28} {} {} #{}",
+                p.number, p.citation, p.node.source, p.node.source_id, p.node.chunk_idx
+            );
+        }
+        if !unverified.is_empty() {
+            println!("\nUnverified citations:");
+            for c in &unverified {
+                println!("  {:<28} {}", c.text, c.grounding.describe());
+            }
+        }
+    }
+    if args.strict && !unverified.is_empty() {
+        bail!(
+            "The answer cites {} section(s) outside its context",
+            unverified.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passages_and_context() {
+        assert_eq!(citation("virginia_code", "46.2-862"), "Va. Code § 46.2-862");
+        assert_eq!(citation("constitution", "1:8"), "Va. Const. art. 1, § 8");
+        assert_eq!(citation("federal_code", "usc:42:1983"), "42 U.S.C. § 1983");
+        assert_eq!(citation("authorities", "VA-AG-OP"), "authorities VA-AG-OP");
+
+        let node = |id: i64| {
+            Some(IndexedNode {
+                node_id: id,
+                source: "virginia_code".into(),
+                source_id: format!("46.2-86{id}"),
+                chunk_idx: 0,
+                node_type: "section".into(),
+            })
+        };
+        let key = |id: i64| ("virginia_code".to_string(), format!("46.2-86{id}"), 0);
+        let mut texts = HashMap::from([
+            (key(1), "Reckless driving; speed. ".repeat(4)),
+            (key(2), "x".repeat(70)),
+            (
+                key(3),
+                "Twenty miles per hour or more above the limit.".into(),
+            ),
+        ]);
+        // 4 has no text; 2 doesn't fit after 1
+        let fused = [(4, 0.04), (1, 0.03), (2, 0.02), (3, 0.01)];
+        let passages = select_passages(&fused, node, &mut texts.clone(), 5, 160);
+        let picked: Vec<(usize, &str)> = passages
+            .iter()
+            .map(|p| (p.number, p.node.source_id.as_str()))
+            .collect();
+        assert_eq!(picked, [(1, "46.2-861"), (2, "46.2-863")]);
+
+        let message = user_message("Is 20 over reckless?", &passages);
+        assert!(message.starts_with("Passages:\n\n[1] Va. Code § 46.2-861\nReckless driving;"));
+        assert!(message.contains("\n[2] Va. Code § 46.2-863\nTwenty miles"));
+        assert!(message.ends_with("\nQuestion: Is 20 over reckless?"));
+
+        // A first passage longer than the budget is cut to fit
+        let passages = select_passages(&fused, node, &mut texts, 5, 10);
+        assert_eq!(passages.len(), 1);
+        assert_eq!(passages[0].text, "Reckless d…");
+    }
+}
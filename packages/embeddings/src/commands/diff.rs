@@ -0,0 +1,475 @@
+//! `diff`: what changed between two builds. Node ids are reassigned on every
+//! build, so nodes are matched on `(source, source_id, chunk_idx)` and edges
+//! on the keys of their endpoints.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::Args;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::validate::has_table;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// The older output DB
+    #[arg(long)]
+    pub old: PathBuf,
+
+    /// The newer output DB
+    #[arg(long)]
+    pub new: PathBuf,
+
+    /// Changes listed per kind in the report (counts always cover everything)
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Print the full diff as one JSON object instead of a report
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+type NodeKey = (String, String, i64);
+type EdgeKey = (NodeKey, NodeKey, String);
+
+struct NodeInfo {
+    id: i64,
+    node_type: String,
+    /// `node_hashes.text_hash`; `None` for structural nodes and older DBs.
+    text_hash: Option<String>,
+}
+
+/// Added, removed and modified items, each sorted.
+#[derive(Debug, Default, Serialize)]
+struct Changes {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct SourceCounts {
+    added: usize,
+    removed: usize,
+    modified: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Drifted {
+    node: String,
+    distance: f32,
+}
+
+/// Cosine distance between the old and new embedding of every node embedded
+/// in both DBs.
+#[derive(Debug, Serialize)]
+struct Drift {
+    compared: usize,
+    mean: f32,
+    median: f32,
+    p90: f32,
+    max: f32,
+    /// Largest distances first.
+    top: Vec<Drifted>,
+}
+
+#[derive(Debug, Serialize)]
+struct Diff {
+    old_nodes: usize,
+    new_nodes: usize,
+    nodes: Changes,
+    nodes_by_source: BTreeMap<String, SourceCounts>,
+    old_edges: usize,
+    new_edges: usize,
+    edges: Changes,
+    /// `None` when either DB has no embeddings or the models differ.
+    drift: Option<Drift>,
+    /// Why `drift` is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    drift_skipped: Option<String>,
+}
+
+/// `source:source_id`, with `#chunk_idx` for chunks after the first.
+fn spec((source, source_id, chunk_idx): &NodeKey) -> String {
+    match chunk_idx {
+        0 => format!("{}:{}", source, source_id),
+        n => format!("{}:{}#{}", source, source_id, n),
+    }
+}
+
+fn edge_spec((from, to, rel_type): &EdgeKey) -> String {
+    format!("{} -{}-> {}", spec(from), rel_type, spec(to))
+}
+
+fn open(path: &Path) -> Result<Connection> {
+    let conn = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    for table in ["nodes", "edges", "embeddings", "model_info"] {
+        if !has_table(&conn, table)? {
+            bail!(
+                "{} is not an output DB: missing table {}",
+                path.display(),
+                table
+            );
+        }
+    }
+    Ok(conn)
+}
+
+fn load_nodes(conn: &Connection) -> Result<HashMap<NodeKey, NodeInfo>> {
+    let sql = match has_table(conn, "node_hashes")? {
+        true => {
+            "SELECT n.id, n.source, n.source_id, n.chunk_idx, n.node_type, h.text_hash
+             FROM nodes n LEFT JOIN node_hashes h ON h.node_id = n.id"
+        }
+        false => "SELECT id, source, source_id, chunk_idx, node_type, NULL FROM nodes",
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |r| {
+        Ok((
+            (r.get(1)?, r.get(2)?, r.get(3)?),
+            NodeInfo {
+                id: r.get(0)?,
+                node_type: r.get(4)?,
+                text_hash: r.get(5)?,
+            },
+        ))
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn load_edges(
+    conn: &Connection,
+    nodes: &HashMap<NodeKey, NodeInfo>,
+) -> Result<HashMap<EdgeKey, Option<f64>>> {
+    let keys: HashMap<i64, &NodeKey> = nodes.iter().map(|(k, n)| (n.id, k)).collect();
+    let mut stmt = conn.prepare("SELECT from_id, to_id, rel_type, weight FROM edges")?;
+    let mut rows = stmt.query([])?;
+    let mut edges = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let (Some(from), Some(to)) = (
+            keys.get(&row.get::<_, i64>(0)?),
+            keys.get(&row.get::<_, i64>(1)?),
+        ) else {
+            continue;
+        };
+        edges.insert(((*from).clone(), (*to).clone(), row.get(2)?), row.get(3)?);
+    }
+    Ok(edges)
+}
+
+fn model(conn: &Connection) -> Result<Option<(String, String)>> {
+    let get = |key: &str| -> Result<Option<String>> {
+        match conn.query_row("SELECT value FROM model_info WHERE key = ?1", [key], |r| {
+            r.get(0)
+        }) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    };
+    Ok(get("model_name")?.zip(get("dimensions")?))
+}
+
+fn load_embeddings(conn: &Connection, ids: &HashMap<i64, usize>) -> Result<Vec<Option<Vec<f32>>>> {
+    let mut out = vec![None; ids.len()];
+    let mut stmt = conn.prepare("SELECT node_id, embedding FROM embeddings")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let Some(&slot) = ids.get(&row.get::<_, i64>(0)?) else {
+            continue;
+        };
+        let blob: Vec<u8> = row.get(1)?;
+        out[slot] = Some(
+            blob.chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        );
+    }
+    Ok(out)
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let n = norm(a) * norm(b);
+    if n == 0.0 {
+        // Zero vectors have no direction; only an exact match counts as unchanged.
+        return if a == b { 0.0 } else { 1.0 };
+    }
+    1.0 - dot / n
+}
+
+fn drift(
+    old: &Connection,
+    new: &Connection,
+    shared: &[(&NodeKey, i64, i64)],
+    top: usize,
+) -> Result<Drift> {
+    let old_ids = shared.iter().enumerate().map(|(i, s)| (s.1, i)).collect();
+    let new_ids = shared.iter().enumerate().map(|(i, s)| (s.2, i)).collect();
+    let old_vecs = load_embeddings(old, &old_ids)?;
+    let new_vecs = load_embeddings(new, &new_ids)?;
+
+    let mut distances: Vec<(f32, &NodeKey)> = shared
+        .iter()
+        .zip(old_vecs.iter().zip(&new_vecs))
+        .filter_map(|((key, _, _), pair)| match pair {
+            (Some(a), Some(b)) if a.len() == b.len() => Some((cosine_distance(a, b), *key)),
+            _ => None,
+        })
+        .collect();
+    distances.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let at = |q: f64| -> f32 {
+        match distances.len() {
+            0 => 0.0,
+            n => distances[n - 1 - ((n - 1) as f64 * q).round() as usize].0,
+        }
+    };
+    Ok(Drift {
+        compared: distances.len(),
+        mean: match distances.len() {
+            0 => 0.0,
+            n => distances.iter().map(|d| d.0).sum::<f32>() / n as f32,
+        },
+        median: at(0.5),
+        p90: at(0.9),
+        max: distances.first().map(|d| d.0).unwrap_or(0.0),
+        top: distances
+            .iter()
+            .take(top)
+            .map(|(distance, key)| Drifted {
+                node: spec(key),
+                distance: *distance,
+            })
+            .collect(),
+    })
+}
+
+fn diff(old: &Connection, new: &Connection, top: usize) -> Result<Diff> {
+    let old_nodes = load_nodes(old)?;
+    let new_nodes = load_nodes(new)?;
+
+    let mut nodes = Changes::default();
+    let mut by_source: BTreeMap<String, SourceCounts> = BTreeMap::new();
+    let mut shared = Vec::new();
+    for (key, n) in &new_nodes {
+        let counts = by_source.entry(key.0.clone()).or_default();
+        match old_nodes.get(key) {
+            None => {
+                nodes.added.push(spec(key));
+                counts.added += 1;
+            }
+            Some(o) => {
+                let edited = matches!((&o.text_hash, &n.text_hash), (Some(a), Some(b)) if a != b);
+                if edited || o.node_type != n.node_type {
+                    nodes.modified.push(spec(key));
+                    counts.modified += 1;
+                }
+                shared.push((key, o.id, n.id));
+            }
+        }
+    }
+    for key in old_nodes.keys().filter(|k| !new_nodes.contains_key(*k)) {
+        nodes.removed.push(spec(key));
+        by_source.entry(key.0.clone()).or_default().removed += 1;
+    }
+
+    let old_edges = load_edges(old, &old_nodes)?;
+    let new_edges = load_edges(new, &new_nodes)?;
+    let mut edges = Changes::default();
+    for (key, weight) in &new_edges {
+        match old_edges.get(key) {
+            None => edges.added.push(edge_spec(key)),
+            Some(w) if w != weight => edges.modified.push(edge_spec(key)),
+            Some(_) => {}
+        }
+    }
+    for key in old_edges.keys().filter(|k| !new_edges.contains_key(*k)) {
+        edges.removed.push(edge_spec(key));
+    }
+    for list in [
+        &mut nodes.added,
+        &mut nodes.removed,
+        &mut nodes.modified,
+        &mut edges.added,
+        &mut edges.removed,
+        &mut edges.modified,
+    ] {
+        list.sort();
+    }
+    by_source.retain(|_, c| c.added + c.removed + c.modified > 0);
+
+    let (drift, drift_skipped) = match (model(old)?, model(new)?) {
+        (Some(a), Some(b)) if a == b => {
+            shared.sort_by(|a, b| a.0.cmp(b.0));
+            (Some(self::drift(old, new, &shared, top)?), None)
+        }
+        (Some(a), Some(b)) => (
+            None,
+            Some(format!(
+                "models differ ({} x {} vs {} x {})",
+                a.0, a.1, b.0, b.1
+            )),
+        ),
+        _ => (None, Some("no embeddings in one or both DBs".to_string())),
+    };
+
+    Ok(Diff {
+        old_nodes: old_nodes.len(),
+        new_nodes: new_nodes.len(),
+        nodes,
+        nodes_by_source: by_source,
+        old_edges: old_edges.len(),
+        new_edges: new_edges.len(),
+        edges,
+        drift,
+        drift_skipped,
+    })
+}
+
+fn print_changes(changes: &Changes, limit: usize) {
+    for (sign, list) in [
+        ("+", &changes.added),
+        ("-", &changes.removed),
+        ("~", &changes.modified),
+    ] {
+        for item in list.iter().take(limit) {
+            println!("    {} {}", sign, item);
+        }
+        if list.len() > limit {
+            println!("    {} ... {} more", sign, list.len() - limit);
+        }
+    }
+}
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    let old = open(&args.old)?;
+    let new = open(&args.new)?;
+    let diff = diff(&old, &new, args.limit)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    let n = &diff.nodes;
+    println!(
+        "Nodes: {} -> {}  (+{} -{} ~{})",
+        diff.old_nodes,
+        diff.new_nodes,
+        n.added.len(),
+        n.removed.len(),
+        n.modified.len()
+    );
+    for (source, c) in &diff.nodes_by_source {
+        println!(
+            "    {:<20} +{:<7} -{:<7} ~{}",
+            source, c.added, c.removed, c.modified
+        );
+    }
+    print_changes(n, args.limit);
+
+    let e = &diff.edges;
+    println!(
+        "Edges: {} -> {}  (+{} -{} ~{})",
+        diff.old_edges,
+        diff.new_edges,
+        e.added.len(),
+        e.removed.len(),
+        e.modified.len()
+    );
+    print_changes(e, args.limit);
+
+    match (&diff.drift, &diff.drift_skipped) {
+        (Some(d), _) => {
+            println!(
+                "Embedding drift over {} shared nodes (cosine distance): mean {:.4}  median {:.4}  p90 {:.4}  max {:.4}",
+                d.compared, d.mean, d.median, d.p90, d.max
+            );
+            for t in &d.top {
+                println!("    {:.4}  {}", t.distance, t.node);
+            }
+        }
+        (None, reason) => println!(
+            "Embedding drift: skipped ({})",
+            reason.as_deref().unwrap_or("unknown")
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::{create_output_db, write_model_info};
+
+    fn build(dir: &std::path::Path, name: &str, sql: &str) -> Connection {
+        let conn = create_output_db(dir.join(name).to_str().unwrap()).unwrap();
+        write_model_info(&conn, &crate::embed::models::MODELS[0], 512, 2).unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn
+    }
+
+    fn blob(v: [f32; 2]) -> Vec<u8> {
+        v.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_diff_matches_nodes_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = build(
+            dir.path(),
+            "old.db",
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type) VALUES
+               (1, 'virginia_code', '1-1', 0, 'section'),
+               (2, 'virginia_code', '1-2', 0, 'section'),
+               (3, 'virginia_code', '1-3', 0, 'section');
+             INSERT INTO node_hashes (node_id, text_hash) VALUES (1, 'a'), (2, 'b'), (3, 'c');
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES (1, 2, 'cites'), (1, 3, 'cites');",
+        );
+        // Same nodes under new ids; 1-2 edited, 1-3 removed, 1-4 added.
+        let new = build(
+            dir.path(),
+            "new.db",
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type) VALUES
+               (10, 'virginia_code', '1-2', 0, 'section'),
+               (11, 'virginia_code', '1-1', 0, 'section'),
+               (12, 'virginia_code', '1-4', 0, 'section');
+             INSERT INTO node_hashes (node_id, text_hash) VALUES (10, 'B'), (11, 'a'), (12, 'd');
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES (11, 10, 'cites'), (11, 12, 'cites');",
+        );
+        let insert = |conn: &Connection, id: i64, v: [f32; 2]| {
+            conn.execute(
+                "INSERT INTO embeddings (node_id, embedding) VALUES (?1, ?2)",
+                rusqlite::params![id, blob(v)],
+            )
+            .unwrap();
+        };
+        insert(&old, 1, [1.0, 0.0]);
+        insert(&old, 2, [1.0, 0.0]);
+        insert(&new, 11, [1.0, 0.0]);
+        insert(&new, 10, [0.0, 1.0]);
+
+        let d = diff(&old, &new, 10).unwrap();
+        assert_eq!(d.nodes.added, vec!["virginia_code:1-4"]);
+        assert_eq!(d.nodes.removed, vec!["virginia_code:1-3"]);
+        assert_eq!(d.nodes.modified, vec!["virginia_code:1-2"]);
+        assert_eq!(
+            d.edges.added,
+            vec!["virginia_code:1-1 -cites-> virginia_code:1-4"]
+        );
+        assert_eq!(
+            d.edges.removed,
+            vec!["virginia_code:1-1 -cites-> virginia_code:1-3"]
+        );
+        assert!(d.edges.modified.is_empty());
+
+        let drift = d.drift.unwrap();
+        assert_eq!(drift.compared, 2);
+        assert_eq!(drift.top[0].node, "virginia_code:1-2");
+        assert!((drift.max - 1.0).abs() < 1e-6);
+        assert!(drift.top[1].distance.abs() < 1e-6);
+    }
+}
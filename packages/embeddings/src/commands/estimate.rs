@@ -0,0 +1,340 @@
+//! `estimate`: plan a build before spending GPU or API budget on it. Runs ETL
+//! and node building over a sample of `virginia.db`, counts the tokens Pass 3
+//! would embed, extrapolates to the full input, and prices that per backend.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::db::reader;
+use crate::embed::{self, ModelSpec};
+use crate::etl;
+use crate::graph::nodes::build_nodes;
+use crate::sample::{self, Sampling};
+use crate::text::chunker::{approx_token_count, ChunkConfig};
+
+/// Default local throughput, the same figure `build --dry-run` assumes.
+const LOCAL_TOKENS_PER_SEC: f64 = 1000.0;
+
+#[derive(Args, Debug)]
+pub struct EstimateArgs {
+    /// Path to virginia.db, or a `postgres://` URL
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Fraction (0, 1] of each source table to run through ETL and count
+    #[arg(long, default_value_t = 0.1, value_parser = sample::parse_rate)]
+    pub sample_rate: f64,
+
+    /// TOML file of `[[backend]]` throughput/cost baselines (default: the
+    /// local model at the --dry-run throughput)
+    #[arg(long)]
+    pub baselines: Option<PathBuf>,
+
+    /// Build config whose `[sources]` section maps --input's tables (other
+    /// sections are ignored here)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Also measure the local model's throughput on up to N sampled texts
+    /// (loads the model)
+    #[arg(long)]
+    pub calibrate: Option<usize>,
+
+    /// Model preset whose prompt prefix is counted and that --calibrate loads
+    #[arg(
+        long,
+        default_value = embed::models::DEFAULT_MODEL,
+        value_parser = clap::builder::PossibleValuesParser::new(embed::models::names())
+    )]
+    pub model: String,
+
+    /// Print the estimate as one JSON object instead of a report
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// Throughput (and, for remote APIs, price) of one embedding backend, e.g.
+///
+/// ```toml
+/// [[backend]]
+/// name = "local-gpu"
+/// tokens_per_sec = 12000
+///
+/// [[backend]]
+/// name = "hosted-api"
+/// tokens_per_sec = 50000
+/// usd_per_million_tokens = 0.02
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Backend {
+    pub name: String,
+    pub tokens_per_sec: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_per_million_tokens: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Baselines {
+    backend: Vec<Backend>,
+}
+
+#[derive(Debug, Serialize)]
+struct BackendEstimate {
+    #[serde(flatten)]
+    backend: Backend,
+    seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Estimate {
+    sample_rate: f64,
+    sampled_texts: usize,
+    sampled_tokens: usize,
+    /// Extrapolated to every row of the input.
+    texts: usize,
+    tokens: usize,
+    backends: Vec<BackendEstimate>,
+}
+
+fn load_backends(args: &EstimateArgs) -> Result<Vec<Backend>> {
+    let Some(ref path) = args.baselines else {
+        return Ok(vec![Backend {
+            name: "local".to_string(),
+            tokens_per_sec: LOCAL_TOKENS_PER_SEC,
+            usd_per_million_tokens: None,
+        }]);
+    };
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read baselines {}", path.display()))?;
+    let baselines: Baselines =
+        toml::from_str(&raw).with_context(|| format!("Invalid baselines {}", path.display()))?;
+    if let Some(b) = baselines.backend.iter().find(|b| b.tokens_per_sec <= 0.0) {
+        bail!("Backend {} has a non-positive tokens_per_sec", b.name);
+    }
+    Ok(baselines.backend)
+}
+
+/// Embeddable texts of the sampled rows, with the factor that scales each to
+/// the full input (total rows / sampled rows of its source table).
+fn sample_texts(
+    input: &Path,
+    rate: f64,
+    sources: &reader::SourceMapping,
+) -> Result<Vec<(String, f64)>> {
+    let sampling = Sampling {
+        rate: Some(rate),
+        ..Default::default()
+    };
+    let mut scale: HashMap<&str, f64> = HashMap::new();
+    let mut sampled = |table: &'static str, total: usize, kept: usize| {
+        scale.insert(table, total as f64 / kept.max(1) as f64);
+    };
+
+    let mut code_rows: Vec<reader::VirginiaCodeRow> = reader::read_input(input, sources)?;
+    let total = code_rows.len();
+    sampling.apply("virginia_code", &mut code_rows, |r| r.id);
+    sampled("virginia_code", total, code_rows.len());
+    let mut constitution_rows: Vec<reader::ConstitutionRow> = reader::read_input(input, sources)?;
+    let total = constitution_rows.len();
+    sampling.apply("constitution", &mut constitution_rows, |r| r.id);
+    sampled("constitution", total, constitution_rows.len());
+    let mut authority_rows: Vec<reader::AuthorityRow> = reader::read_input(input, sources)?;
+    let total = authority_rows.len();
+    sampling.apply("authorities", &mut authority_rows, |r| r.id);
+    sampled("authorities", total, authority_rows.len());
+    let mut court_rows: Vec<reader::CourtRow> = reader::read_input(input, sources)?;
+    let total = court_rows.len();
+    sampling.apply("courts", &mut court_rows, |r| r.id);
+    sampled("courts", total, court_rows.len());
+    let mut popular_name_rows: Vec<reader::PopularNameRow> = reader::read_input(input, sources)?;
+    let total = popular_name_rows.len();
+    sampling.apply("popular_names", &mut popular_name_rows, |r| r.id);
+    sampled("popular_names", total, popular_name_rows.len());
+    let mut act_rows: Vec<reader::ActRow> = reader::read_input(input, sources)?;
+    let total = act_rows.len();
+    sampling.apply("acts", &mut act_rows, |r| r.id);
+    sampled("acts", total, act_rows.len());
+    let mut federal_rows: Vec<reader::FederalRow> = reader::read_input(input, sources)?;
+    let total = federal_rows.len();
+    sampling.apply("federal_code", &mut federal_rows, |r| r.id);
+    sampled("federal_code", total, federal_rows.len());
+    let mut document_rows: Vec<reader::DocumentRow> = reader::read_input(input, sources)?;
+    let total = document_rows.len();
+    sampling.apply("documents", &mut document_rows, |r| r.id);
+    sampled("documents", total, document_rows.len());
+
+    let cleaned = etl::run_etl(
+        &code_rows,
+        &constitution_rows,
+        &authority_rows,
+        &court_rows,
+        &popular_name_rows,
+        &act_rows,
+        &federal_rows,
+        &document_rows,
+    )?;
+    let mut built = build_nodes(&cleaned, ChunkConfig::default())?;
+    Ok(built
+        .nodes
+        .iter()
+        .filter(|n| !n.synthetic)
+        .filter_map(|n| {
+            let text = built.texts.remove(&n.id).filter(|t| !t.is_empty())?;
+            // Node sources are named after their source tables.
+            Some((text, scale.get(n.source.as_str()).copied().unwrap_or(1.0)))
+        })
+        .collect())
+}
+
+/// Time the local model on `texts` and return its throughput in tokens/s.
+async fn calibrate(model: &'static ModelSpec, texts: &[String], tokens: usize) -> Result<f64> {
+    let mut embedder = embed::Embedder::new(model, embed::Device::Auto, 64, None).await?;
+    let ids: Vec<i64> = (0..texts.len() as i64).collect();
+    let start = Instant::now();
+    embedder.embed_batched(&ids, texts, |_, _, _| Ok(())).await?;
+    Ok(tokens as f64 / start.elapsed().as_secs_f64().max(1e-9))
+}
+
+/// Approximate tokens the model sees for a document, prompt prefix included.
+fn document_tokens(model: &ModelSpec, text: &str) -> usize {
+    approx_token_count(&model.format_document(text))
+}
+
+fn estimate(
+    model: &ModelSpec,
+    texts: &[(String, f64)],
+    rate: f64,
+    backends: Vec<Backend>,
+) -> Estimate {
+    let mut sampled_tokens = 0;
+    let mut total_texts = 0.0;
+    let mut total_tokens = 0.0;
+    for (text, scale) in texts {
+        let tokens = document_tokens(model, text);
+        sampled_tokens += tokens;
+        total_texts += scale;
+        total_tokens += tokens as f64 * scale;
+    }
+    let backends = backends
+        .into_iter()
+        .map(|backend| BackendEstimate {
+            seconds: total_tokens / backend.tokens_per_sec,
+            usd: backend
+                .usd_per_million_tokens
+                .map(|price| total_tokens / 1e6 * price),
+            backend,
+        })
+        .collect();
+    Estimate {
+        sample_rate: rate,
+        sampled_texts: texts.len(),
+        sampled_tokens,
+        texts: total_texts.round() as usize,
+        tokens: total_tokens.round() as usize,
+        backends,
+    }
+}
+
+fn hms(seconds: f64) -> String {
+    let s = seconds.round() as u64;
+    format!("{}h {:02}m {:02}s", s / 3600, s / 60 % 60, s % 60)
+}
+
+pub async fn run(args: EstimateArgs) -> Result<()> {
+    let model = embed::models::find(&args.model)?;
+    let mut backends = load_backends(&args)?;
+    let sources = match args.config {
+        Some(ref path) => config::load(path)?.sources,
+        None => reader::SourceMapping::new(),
+    };
+    let texts = sample_texts(&args.input, args.sample_rate, &sources)?;
+    if texts.is_empty() {
+        bail!(
+            "No embeddable texts in a {} sample of {}; raise --sample-rate",
+            args.sample_rate,
+            reader::display_input(&args.input)
+        );
+    }
+
+    if let Some(n) = args.calibrate {
+        let calibration: Vec<String> = texts.iter().take(n).map(|t| t.0.clone()).collect();
+        let tokens = calibration.iter().map(|t| document_tokens(model, t)).sum();
+        let tokens_per_sec = calibrate(model, &calibration, tokens).await?;
+        backends.insert(
+            0,
+            Backend {
+                name: format!("local (calibrated on {} texts)", calibration.len()),
+                tokens_per_sec,
+                usd_per_million_tokens: None,
+            },
+        );
+    }
+
+    let estimate = estimate(model, &texts, args.sample_rate, backends);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&estimate)?);
+        return Ok(());
+    }
+
+    println!(
+        "Sample ({:.0}% of rows): {} texts, {} tokens (approx)",
+        args.sample_rate * 100.0,
+        estimate.sampled_texts,
+        estimate.sampled_tokens
+    );
+    println!(
+        "Full input (extrapolated): {} texts, {} tokens",
+        estimate.texts, estimate.tokens
+    );
+    for b in &estimate.backends {
+        let cost = match b.usd {
+            Some(usd) => format!("  ${:.2}", usd),
+            None => String::new(),
+        };
+        println!(
+            "  {:<36} {:>10.0} tokens/s  {}{}",
+            b.backend.name,
+            b.backend.tokens_per_sec,
+            hms(b.seconds),
+            cost
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_scales_and_prices() {
+        // Two sampled texts, each standing for 10 rows.
+        let texts = vec![
+            ("one two three".to_string(), 10.0),
+            ("four five".to_string(), 10.0),
+        ];
+        let model = embed::models::find(embed::models::DEFAULT_MODEL).unwrap();
+        let per_text = document_tokens(model, "");
+        let backends = vec![Backend {
+            name: "api".into(),
+            tokens_per_sec: 100.0,
+            usd_per_million_tokens: Some(1.0),
+        }];
+        let e = estimate(model, &texts, 0.1, backends);
+        let tokens = (5 + 2 * per_text) * 10;
+        assert_eq!((e.texts, e.tokens), (20, tokens));
+        assert!((e.backends[0].seconds - tokens as f64 / 100.0).abs() < 1e-9);
+        assert!((e.backends[0].usd.unwrap() - tokens as f64 / 1e6).abs() < 1e-12);
+        assert_eq!(hms(3725.0), "1h 02m 05s");
+    }
+}
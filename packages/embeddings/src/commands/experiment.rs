@@ -0,0 +1,479 @@
+//! `experiment`: the chunking / template / model tuning loop as one command.
+//! Builds a small index per variant of a plan from the same sample of
+//! `virginia.db`, scores each against an eval set (what `export queries`
+//! writes) and prints a comparison table.
+//!
+//! ```toml
+//! [[variant]]
+//! name = "baseline"
+//!
+//! [[variant]]
+//! name = "small-chunks"
+//! chunk_tokens = 256
+//! chunk_overlap = 32
+//!
+//! [[variant]]
+//! name = "breadcrumbs"
+//! templates = { virginia_code = "{title_name} › {chapter_name} › § {section}: {title}. {body}" }
+//! ```
+//!
+//! Each variant is an ordinary `build` run of this binary, so anything a
+//! build can be configured with can be varied. `--int8` and `--binary` also
+//! score every variant on an int8 or a binary index, the recall cost of the
+//! server's `--precision int8` / `binary`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::embed;
+use crate::graph::store;
+use crate::sample;
+use crate::search::{Precision, SearchIndex};
+
+#[derive(Args, Debug)]
+pub struct ExperimentArgs {
+    /// Path to virginia.db
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// TOML file of `[[variant]]` build settings to compare
+    #[arg(long)]
+    pub plan: PathBuf,
+
+    /// Eval JSONL: one `{"query", "relevant"}` object per line
+    #[arg(long)]
+    pub eval: PathBuf,
+
+    /// Directory for each variant's output DB and generated config
+    #[arg(long, default_value = "experiments")]
+    pub out_dir: PathBuf,
+
+    /// Fraction (0, 1] of each source table every variant is built from
+    #[arg(long, default_value_t = 0.1, value_parser = sample::parse_rate)]
+    pub sample_rate: f64,
+
+    /// Results per query that count towards recall
+    #[arg(long, default_value_t = 10)]
+    pub top_k: usize,
+
+    /// Device queries are embedded on (variants embed on `--device` in their
+    /// `args`, default auto)
+    #[arg(long, value_enum, default_value_t = embed::Device::Auto)]
+    pub device: embed::Device,
+
+    /// Also score each variant with its vectors held as int8, for the
+    /// embedding server's `--precision int8`
+    #[arg(long, default_value_t = false)]
+    pub int8: bool,
+
+    /// Also score each variant on a binary index, as the embedding server's
+    /// `--precision binary` searches it
+    #[arg(long, default_value_t = false)]
+    pub binary: bool,
+
+    /// Score the DBs already in --out-dir instead of rebuilding them
+    #[arg(long, default_value_t = false)]
+    pub skip_build: bool,
+
+    /// Print the results as JSON lines instead of a table
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Plan {
+    #[serde(default)]
+    variant: Vec<Variant>,
+}
+
+/// One build to compare. Unset settings keep the build's defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Variant {
+    name: String,
+    /// `--model` preset.
+    model: Option<String>,
+    /// `--chunk-tokens`.
+    chunk_tokens: Option<usize>,
+    /// `--chunk-overlap`.
+    chunk_overlap: Option<usize>,
+    /// Build `--config`; `templates` are added to a copy of it.
+    config: Option<PathBuf>,
+    /// `[templates]` for this variant.
+    #[serde(default)]
+    templates: BTreeMap<String, String>,
+    /// Further `build` flags, e.g. `["--title-embeddings"]`.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+fn load_plan(path: &Path) -> Result<Vec<Variant>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read plan {}", path.display()))?;
+    let plan: Plan =
+        toml::from_str(&raw).with_context(|| format!("Invalid plan {}", path.display()))?;
+    if plan.variant.is_empty() {
+        bail!("{} has no [[variant]]s", path.display());
+    }
+    let mut names = HashSet::new();
+    for v in &plan.variant {
+        if v.name.is_empty() || v.name.contains(['/', '\\']) {
+            bail!("Invalid variant name {:?} in {}", v.name, path.display());
+        }
+        if !names.insert(v.name.as_str()) {
+            bail!("Duplicate variant {:?} in {}", v.name, path.display());
+        }
+        if let Some(ref model) = v.model {
+            embed::models::find(model)
+                .with_context(|| format!("Variant {} in {}", v.name, path.display()))?;
+        }
+    }
+    Ok(plan.variant)
+}
+
+#[derive(Debug, Deserialize)]
+struct EvalCase {
+    query: String,
+    #[serde(default)]
+    relevant: Vec<String>,
+}
+
+fn load_eval(path: &Path) -> Result<Vec<EvalCase>> {
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    let mut cases = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case: EvalCase = serde_json::from_str(&line)
+            .with_context(|| format!("{} line {}", path.display(), i + 1))?;
+        if !case.relevant.is_empty() {
+            cases.push(case);
+        }
+    }
+    if cases.is_empty() {
+        bail!("{} has no queries with relevant nodes", path.display());
+    }
+    Ok(cases)
+}
+
+/// How one variant's index did on the eval set.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct Scores {
+    /// Queries scored: those with a relevant node in the sampled index.
+    queries: usize,
+    /// Mean share of a query's relevant nodes in its top k.
+    recall: f64,
+    /// Mean reciprocal rank of the first relevant node (0 when none is in
+    /// the top k).
+    mrr: f64,
+    /// Share of queries with any relevant node in the top k.
+    hit_rate: f64,
+}
+
+/// Score ranked retrievals against their relevant sets. Rankings name nodes
+/// as `source:source_id`, best first, and are cut at `top_k` distinct nodes;
+/// relevant nodes not in `indexed` are left out, since a sample can't find
+/// them, and a query with none left isn't scored.
+fn score(results: &[(Vec<String>, &[String])], indexed: &HashSet<String>, top_k: usize) -> Scores {
+    let mut scores = Scores::default();
+    for (ranked, relevant) in results {
+        let relevant: HashSet<&String> = relevant.iter().filter(|r| indexed.contains(*r)).collect();
+        if relevant.is_empty() {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        let top: Vec<&String> = ranked
+            .iter()
+            .filter(|spec| seen.insert(spec.as_str()))
+            .take(top_k)
+            .collect();
+        let found = top.iter().filter(|spec| relevant.contains(*spec)).count();
+        scores.queries += 1;
+        scores.recall += found as f64 / relevant.len() as f64;
+        if let Some(rank) = top.iter().position(|spec| relevant.contains(*spec)) {
+            scores.mrr += 1.0 / (rank + 1) as f64;
+            scores.hit_rate += 1.0;
+        }
+    }
+    if scores.queries > 0 {
+        let n = scores.queries as f64;
+        scores.recall /= n;
+        scores.mrr /= n;
+        scores.hit_rate /= n;
+    }
+    scores
+}
+
+#[derive(Debug, Serialize)]
+struct Outcome {
+    variant: String,
+    model: String,
+    precision: Precision,
+    vectors: usize,
+    build_secs: Option<f64>,
+    #[serde(flatten)]
+    scores: Scores,
+}
+
+/// The `--config` for `variant`: its own, or a copy of it with `templates`
+/// added under `out_dir`.
+fn variant_config(variant: &Variant, out_dir: &Path) -> Result<Option<PathBuf>> {
+    if variant.templates.is_empty() {
+        return Ok(variant.config.clone());
+    }
+    let mut config = match variant.config {
+        Some(ref path) => {
+            let raw = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config {}", path.display()))?;
+            toml::from_str::<toml::Table>(&raw)
+                .with_context(|| format!("Invalid config {}", path.display()))?
+        }
+        None => toml::Table::new(),
+    };
+    let templates = variant
+        .templates
+        .iter()
+        .map(|(source, template)| (source.clone(), toml::Value::String(template.clone())))
+        .collect();
+    config.insert("templates".into(), toml::Value::Table(templates));
+    let path = out_dir.join(format!("{}.toml", variant.name));
+    std::fs::write(&path, toml::to_string(&config)?)?;
+    Ok(Some(path))
+}
+
+/// Run `build` for `variant` into `db`.
+fn build(args: &ExperimentArgs, variant: &Variant, db: &Path) -> Result<()> {
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.arg("build")
+        .arg("--input")
+        .arg(&args.input)
+        .arg("--output")
+        .arg(db)
+        .arg("--sample-rate")
+        .arg(args.sample_rate.to_string());
+    if let Some(ref model) = variant.model {
+        cmd.args(["--model", model]);
+    }
+    if let Some(tokens) = variant.chunk_tokens {
+        cmd.args(["--chunk-tokens", &tokens.to_string()]);
+    }
+    if let Some(overlap) = variant.chunk_overlap {
+        cmd.args(["--chunk-overlap", &overlap.to_string()]);
+    }
+    if let Some(config) = variant_config(variant, &args.out_dir)? {
+        cmd.arg("--config").arg(config);
+    }
+    cmd.args(&variant.args);
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to start the {} build", variant.name))?;
+    if !status.success() {
+        bail!("The {} build exited with {}", variant.name, status);
+    }
+    Ok(())
+}
+
+/// Embed every query with the model `db` was built with and score the hits,
+/// once per precision the index is loaded at.
+async fn evaluate(
+    db: &Path,
+    cases: &[EvalCase],
+    top_k: usize,
+    device: embed::Device,
+    precisions: &[Precision],
+) -> Result<(String, usize, Vec<(Precision, Scores)>)> {
+    let index_f32 = SearchIndex::load(db)?;
+    if index_f32.is_empty() {
+        bail!("{} has no embeddings to search", db.display());
+    }
+    let model = embed::models::find(&index_f32.model_name)
+        .with_context(|| format!("{} was embedded with an unsupported model", db.display()))?;
+    let indexed: HashSet<String> = store::load_nodes(&rusqlite::Connection::open_with_flags(
+        db,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?)?
+    .into_values()
+    .map(|n| format!("{}:{}", n.source, n.source_id))
+    .collect();
+
+    let embedder = embed::Embedder::new(model, device, 16, None).await?;
+    let queries = cases.iter().map(|c| model.format_query(&c.query)).collect();
+    let vectors = embedder.embed(queries).await?;
+    // Chunks of the same node share a spec, so over-fetch before the cut.
+    let fetch = top_k * 4;
+    let mut scores = Vec::with_capacity(precisions.len());
+    for &precision in precisions {
+        let quantized = match precision {
+            Precision::F32 => None,
+            precision => Some(SearchIndex::load_with(db, precision)?),
+        };
+        let index = quantized.as_ref().unwrap_or(&index_f32);
+        let mut results = Vec::with_capacity(cases.len());
+        for (case, vector) in cases.iter().zip(&vectors) {
+            let hits = index.search(
+                vector,
+                fetch,
+                None,
+                crate::search::DEFAULT_TITLE_WEIGHT,
+                None,
+            )?;
+            let ranked = hits
+                .iter()
+                .map(|h| format!("{}:{}", h.node.source, h.node.source_id))
+                .collect();
+            results.push((ranked, case.relevant.as_slice()));
+        }
+        scores.push((precision, score(&results, &indexed, top_k)));
+    }
+    Ok((index_f32.model_name.clone(), index_f32.len(), scores))
+}
+
+pub async fn run(args: ExperimentArgs) -> Result<()> {
+    let variants = load_plan(&args.plan)?;
+    let cases = load_eval(&args.eval)?;
+    std::fs::create_dir_all(&args.out_dir)?;
+    let mut precisions = vec![Precision::F32];
+    if args.int8 {
+        precisions.push(Precision::Int8);
+    }
+    if args.binary {
+        precisions.push(Precision::Binary);
+    }
+
+    let mut outcomes = Vec::new();
+    let mut build_secs: HashMap<&str, f64> = HashMap::new();
+    for variant in &variants {
+        let db = args.out_dir.join(format!("{}.db", variant.name));
+        if !args.skip_build {
+            eprintln!("Building variant {} -> {}", variant.name, db.display());
+            let start = Instant::now();
+            build(&args, variant, &db)?;
+            build_secs.insert(&variant.name, start.elapsed().as_secs_f64());
+        } else if !db.exists() {
+            bail!("{} not found; run without --skip-build first", db.display());
+        }
+        let (model, vectors, scores) =
+            evaluate(&db, &cases, args.top_k, args.device, &precisions).await?;
+        for (precision, scores) in scores {
+            outcomes.push(Outcome {
+                variant: variant.name.clone(),
+                model: model.clone(),
+                precision,
+                vectors,
+                build_secs: build_secs.get(variant.name.as_str()).copied(),
+                scores,
+            });
+        }
+    }
+
+    if args.json {
+        for outcome in &outcomes {
+            println!("{}", serde_json::to_string(outcome)?);
+        }
+        return Ok(());
+    }
+
+    let k = args.top_k;
+    println!(
+        "{:<20} {:<24} {:<6} {:>8} {:>7} {:>9} {:>7} {:>10} {:>8}",
+        "variant",
+        "model",
+        "prec",
+        "vectors",
+        "queries",
+        format!("recall@{k}"),
+        "mrr",
+        format!("hit@{k}"),
+        "build"
+    );
+    for o in &outcomes {
+        let build = o
+            .build_secs
+            .map_or_else(|| "-".to_string(), |s| format!("{:.0}s", s));
+        println!(
+            "{:<20} {:<24} {:<6} {:>8} {:>7} {:>9.3} {:>7.3} {:>10.3} {:>8}",
+            o.variant,
+            o.model,
+            o.precision.as_str(),
+            o.vectors,
+            o.scores.queries,
+            o.scores.recall,
+            o.scores.mrr,
+            o.scores.hit_rate,
+            build
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        let indexed: HashSet<String> = ["a:1", "a:2", "a:3", "a:4"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let rel = |specs: &[&str]| specs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let ranked = |specs: &[&str]| specs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let (r1, r2, r3) = (rel(&["a:2"]), rel(&["a:1", "a:4"]), rel(&["a:9"]));
+        let results = vec![
+            // Two chunks of a:1 count once, so a:2 is at rank 2.
+            (ranked(&["a:1", "a:1", "a:2", "a:3"]), r1.as_slice()),
+            // a:4 falls outside the top 2.
+            (ranked(&["a:1", "a:3", "a:4"]), r2.as_slice()),
+            // Nothing relevant was sampled: not scored.
+            (ranked(&["a:1"]), r3.as_slice()),
+        ];
+        let scores = score(&results, &indexed, 2);
+        assert_eq!(scores.queries, 2);
+        assert!((scores.recall - 0.75).abs() < 1e-9);
+        assert!((scores.mrr - 0.75).abs() < 1e-9);
+        assert!((scores.hit_rate - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_variant_config_adds_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.toml");
+        std::fs::write(&base, "dedup = \"keep_longest\"\n").unwrap();
+        let plan = dir.path().join("plan.toml");
+        std::fs::write(
+            &plan,
+            format!(
+                "[[variant]]\nname = \"base\"\n\n[[variant]]\nname = \"tpl\"\nconfig = {:?}\n\
+                 templates = {{ acts = \"{{year}}: {{body}}\" }}\n",
+                base
+            ),
+        )
+        .unwrap();
+        let variants = load_plan(&plan).unwrap();
+        assert_eq!(variant_config(&variants[0], dir.path()).unwrap(), None);
+
+        let path = variant_config(&variants[1], dir.path()).unwrap().unwrap();
+        let config = crate::config::load(&path).unwrap();
+        assert_eq!(config.dedup, crate::etl::dedup::DedupPolicy::KeepLongest);
+        assert_eq!(config.templates["acts"], "{year}: {body}");
+
+        std::fs::write(
+            &plan,
+            "[[variant]]\nname = \"a\"\n[[variant]]\nname = \"a\"\n",
+        )
+        .unwrap();
+        assert!(load_plan(&plan).is_err());
+    }
+}
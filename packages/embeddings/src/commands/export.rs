@@ -0,0 +1,33 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use super::{export_pairs, export_queries, export_tables, export_triples};
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    pub command: ExportCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    /// Dump nodes, edges, texts and embeddings as JSONL or Parquet
+    Tables(export_tables::ExportTablesArgs),
+    /// Export the server's query log as eval JSONL (query, retrieved, clicked)
+    Queries(export_queries::ExportQueriesArgs),
+    /// Sample (query, positive, hard negative) triples from an eval set for
+    /// reranker training
+    Triples(export_triples::ExportTriplesArgs),
+    /// Contrastive pairs from the graph (popular name ↔ section, adjacent
+    /// chunks, citing ↔ cited) for fine-tuning the embedding model
+    TrainingPairs(export_pairs::ExportPairsArgs),
+}
+
+pub fn run(args: ExportArgs) -> Result<()> {
+    match args.command {
+        ExportCommand::Tables(args) => export_tables::run(args),
+        ExportCommand::Queries(args) => export_queries::run(args),
+        ExportCommand::Triples(args) => export_triples::run(args),
+        ExportCommand::TrainingPairs(args) => export_pairs::run(args),
+    }
+}
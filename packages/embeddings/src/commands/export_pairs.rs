@@ -0,0 +1,192 @@
+//! `export training-pairs`: contrastive (anchor, positive) pairs mined from the
+//! graph itself, as weak supervision for domain-adapting the embedding model.
+//!
+//! Written in the sentence-transformers JSONL layout (`{"anchor", "positive"}`
+//! per line) so the file loads directly as a dataset for
+//! `MultipleNegativesRankingLoss`, which uses the other pairs in a batch as
+//! negatives.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::build_info;
+use crate::graph::store::{self, Adjacency, GraphEdge, GraphNode};
+
+#[derive(Args, Debug)]
+pub struct ExportPairsArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// virginia.db the graph was built from (texts are rebuilt from it)
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Where to write the pairs JSONL
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Pair kinds to generate (default: all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub kinds: Vec<PairKind>,
+
+    /// Keep at most this many pairs of each kind
+    #[arg(long)]
+    pub max_per_kind: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum PairKind {
+    /// A popular name and the section it cites
+    PopularName,
+    /// Consecutive chunks of the same node
+    AdjacentChunk,
+    /// A citing node and the section it cites
+    Citation,
+}
+
+#[derive(Debug, Serialize)]
+struct Pair<'a> {
+    anchor: &'a str,
+    positive: &'a str,
+}
+
+type Texts = HashMap<(String, String, i64), String>;
+
+fn text<'a>(texts: &'a Texts, node: &GraphNode) -> Option<&'a String> {
+    texts.get(&(node.source.clone(), node.source_id.clone(), node.chunk_idx))
+}
+
+/// `(anchor, positive)` node pairs of each kind, in a stable order. Citation
+/// edges point at every chunk of the cited section; only the first chunk is
+/// paired so a long section isn't over-represented.
+fn node_pairs(
+    nodes: &HashMap<i64, GraphNode>,
+    edges: &[GraphEdge],
+) -> BTreeMap<PairKind, Vec<(i64, i64)>> {
+    let mut pairs: BTreeMap<PairKind, Vec<(i64, i64)>> = BTreeMap::new();
+
+    for edge in edges.iter().filter(|e| e.rel_type == "cites") {
+        let (Some(from), Some(to)) = (nodes.get(&edge.from_id), nodes.get(&edge.to_id)) else {
+            continue;
+        };
+        if to.chunk_idx != 0 || (from.source == to.source && from.source_id == to.source_id) {
+            continue;
+        }
+        let kind = match from.node_type.as_str() {
+            "popular_name" => PairKind::PopularName,
+            _ => PairKind::Citation,
+        };
+        pairs.entry(kind).or_default().push((from.id, to.id));
+    }
+
+    let mut chunks: HashMap<(&str, &str), Vec<&GraphNode>> = HashMap::new();
+    for node in nodes.values() {
+        chunks
+            .entry((node.source.as_str(), node.source_id.as_str()))
+            .or_default()
+            .push(node);
+    }
+    let adjacent = pairs.entry(PairKind::AdjacentChunk).or_default();
+    for group in chunks.values_mut() {
+        group.sort_by_key(|n| n.chunk_idx);
+        for w in group.windows(2) {
+            if w[1].chunk_idx == w[0].chunk_idx + 1 {
+                adjacent.push((w[0].id, w[1].id));
+            }
+        }
+    }
+
+    for list in pairs.values_mut() {
+        list.sort_unstable();
+    }
+    pairs
+}
+
+pub fn run(args: ExportPairsArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let nodes = store::load_nodes(&conn)?;
+    let adj = Adjacency::load(&conn, &[])?;
+    let texts = store::rebuild_texts(&args.input, build_info::read_chunking(&conn)?)?;
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&args.out)?);
+    let mut seen: HashSet<(&str, &str)> = HashSet::new();
+    let mut total = 0;
+    for (kind, pairs) in node_pairs(&nodes, &adj.edges) {
+        if !args.kinds.is_empty() && !args.kinds.contains(&kind) {
+            continue;
+        }
+        let mut written = 0;
+        for (a, p) in pairs {
+            if args.max_per_kind.is_some_and(|max| written >= max) {
+                break;
+            }
+            let (Some(anchor), Some(positive)) =
+                (text(&texts, &nodes[&a]), text(&texts, &nodes[&p]))
+            else {
+                continue;
+            };
+            if anchor == positive || !seen.insert((anchor, positive)) {
+                continue;
+            }
+            serde_json::to_writer(&mut out, &Pair { anchor, positive })?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+        println!("  {:<16} {:>8} pairs", kind.to_possible_value().unwrap().get_name(), written);
+        total += written;
+    }
+    out.flush()?;
+    println!("Wrote {} training pairs to {}", total, args.out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64, source: &str, source_id: &str, chunk_idx: i64, node_type: &str) -> GraphNode {
+        GraphNode {
+            id,
+            source: source.into(),
+            source_id: source_id.into(),
+            chunk_idx,
+            node_type: node_type.into(),
+        }
+    }
+
+    fn cites(from_id: i64, to_id: i64) -> GraphEdge {
+        GraphEdge {
+            from_id,
+            to_id,
+            rel_type: "cites".into(),
+            weight: Some(1.0),
+        }
+    }
+
+    #[test]
+    fn test_pairs_by_kind() {
+        let nodes: HashMap<i64, GraphNode> = [
+            node(1, "virginia_code", "18.2-31", 0, "section"),
+            node(2, "virginia_code", "18.2-31", 1, "section"),
+            node(3, "virginia_code", "18.2-32", 0, "section"),
+            node(4, "popular_names", "Murder Act", 0, "popular_name"),
+        ]
+        .into_iter()
+        .map(|n| (n.id, n))
+        .collect();
+        // 3 cites both chunks of 18.2-31; 2 cites its own section.
+        let edges = [cites(3, 1), cites(3, 2), cites(4, 1), cites(2, 1)];
+
+        let pairs = node_pairs(&nodes, &edges);
+        assert_eq!(pairs[&PairKind::Citation], vec![(3, 1)]);
+        assert_eq!(pairs[&PairKind::PopularName], vec![(4, 1)]);
+        assert_eq!(pairs[&PairKind::AdjacentChunk], vec![(1, 2)]);
+    }
+}
@@ -0,0 +1,31 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::query_log;
+
+#[derive(Args, Debug)]
+pub struct ExportQueriesArgs {
+    /// Query log written by `embedding-server --query-log`
+    #[arg(long)]
+    pub log: PathBuf,
+
+    /// Where to write the eval JSONL (one query per line)
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Only export queries with at least one click
+    #[arg(long, default_value_t = false)]
+    pub clicked_only: bool,
+}
+
+pub fn run(args: ExportQueriesArgs) -> Result<()> {
+    let conn = query_log::open(&args.log)?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&args.out)?);
+    let written = query_log::export_eval(&conn, &mut out, args.clicked_only)?;
+    out.flush()?;
+    println!("Wrote {} queries to {}", written, args.out.display());
+    Ok(())
+}
@@ -0,0 +1,422 @@
+//! `export tables`: dump nodes, edges, texts and embeddings from an output DB
+//! as JSONL or Parquet, one file per table, so Python/Polars consumers get
+//! embeddings as float lists instead of raw little-endian f32 blobs.
+
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use polars::prelude::*;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::build_info;
+use crate::graph::store;
+
+#[derive(Args, Debug)]
+pub struct ExportTablesArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Directory to write `<table>.jsonl` / `<table>.parquet` into (created if missing)
+    #[arg(long)]
+    pub out_dir: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = Format::Parquet)]
+    pub format: Format,
+
+    /// Tables to export (default: nodes, edges and embeddings, plus texts when
+    /// `--input` is given)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub tables: Vec<Table>,
+
+    /// virginia.db the graph was built from; needed for `texts`, which the
+    /// output DB doesn't store
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Jsonl,
+    Parquet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Table {
+    Nodes,
+    Edges,
+    Texts,
+    Embeddings,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Table::Nodes => "nodes",
+            Table::Edges => "edges",
+            Table::Texts => "texts",
+            Table::Embeddings => "embeddings",
+        }
+    }
+}
+
+/// A row type that can be written as a JSON line or gathered into a frame.
+trait ExportRow: Serialize + Sized {
+    fn frame(rows: &[Self]) -> PolarsResult<DataFrame>;
+}
+
+#[derive(Debug, Serialize)]
+struct NodeRow {
+    id: i64,
+    source: String,
+    source_id: String,
+    chunk_idx: i64,
+    node_type: String,
+    /// Offsets of the chunk in its source text (`chunk_meta`); null for
+    /// unchunked nodes.
+    char_start: Option<i64>,
+    char_end: Option<i64>,
+}
+
+impl ExportRow for NodeRow {
+    fn frame(rows: &[Self]) -> PolarsResult<DataFrame> {
+        DataFrame::new(vec![
+            Column::new("id".into(), rows.iter().map(|r| r.id).collect::<Vec<_>>()),
+            Column::new(
+                "source".into(),
+                rows.iter().map(|r| r.source.as_str()).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "source_id".into(),
+                rows.iter()
+                    .map(|r| r.source_id.as_str())
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "chunk_idx".into(),
+                rows.iter().map(|r| r.chunk_idx).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "node_type".into(),
+                rows.iter()
+                    .map(|r| r.node_type.as_str())
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "char_start".into(),
+                rows.iter().map(|r| r.char_start).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "char_end".into(),
+                rows.iter().map(|r| r.char_end).collect::<Vec<_>>(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EdgeRow {
+    from_id: i64,
+    to_id: i64,
+    rel_type: String,
+    weight: Option<f64>,
+}
+
+impl ExportRow for EdgeRow {
+    fn frame(rows: &[Self]) -> PolarsResult<DataFrame> {
+        DataFrame::new(vec![
+            Column::new(
+                "from_id".into(),
+                rows.iter().map(|r| r.from_id).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "to_id".into(),
+                rows.iter().map(|r| r.to_id).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "rel_type".into(),
+                rows.iter().map(|r| r.rel_type.as_str()).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "weight".into(),
+                rows.iter().map(|r| r.weight).collect::<Vec<_>>(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TextRow {
+    node_id: i64,
+    text: String,
+}
+
+impl ExportRow for TextRow {
+    fn frame(rows: &[Self]) -> PolarsResult<DataFrame> {
+        DataFrame::new(vec![
+            Column::new(
+                "node_id".into(),
+                rows.iter().map(|r| r.node_id).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "text".into(),
+                rows.iter().map(|r| r.text.as_str()).collect::<Vec<_>>(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRow {
+    node_id: i64,
+    embedding: Vec<f32>,
+}
+
+impl ExportRow for EmbeddingRow {
+    fn frame(rows: &[Self]) -> PolarsResult<DataFrame> {
+        let embeddings: ListChunked = rows
+            .iter()
+            .map(|r| Some(Series::new(PlSmallStr::EMPTY, &r.embedding)))
+            .collect();
+        DataFrame::new(vec![
+            Column::new(
+                "node_id".into(),
+                rows.iter().map(|r| r.node_id).collect::<Vec<_>>(),
+            ),
+            embeddings.with_name("embedding".into()).into_column(),
+        ])
+    }
+}
+
+fn query_rows<R>(
+    conn: &Connection,
+    sql: &str,
+    f: impl FnMut(&rusqlite::Row) -> rusqlite::Result<R>,
+) -> Result<Vec<R>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], f)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn node_rows(conn: &Connection) -> Result<Vec<NodeRow>> {
+    query_rows(
+        conn,
+        "SELECT n.id, n.source, n.source_id, n.chunk_idx, n.node_type, c.char_start, c.char_end
+         FROM nodes n LEFT JOIN chunk_meta c ON c.node_id = n.id ORDER BY n.id",
+        |r| {
+            Ok(NodeRow {
+                id: r.get(0)?,
+                source: r.get(1)?,
+                source_id: r.get(2)?,
+                chunk_idx: r.get(3)?,
+                node_type: r.get(4)?,
+                char_start: r.get(5)?,
+                char_end: r.get(6)?,
+            })
+        },
+    )
+}
+
+fn edge_rows(conn: &Connection) -> Result<Vec<EdgeRow>> {
+    query_rows(
+        conn,
+        "SELECT from_id, to_id, rel_type, weight FROM edges ORDER BY from_id, to_id, rel_type",
+        |r| {
+            Ok(EdgeRow {
+                from_id: r.get(0)?,
+                to_id: r.get(1)?,
+                rel_type: r.get(2)?,
+                weight: r.get(3)?,
+            })
+        },
+    )
+}
+
+fn embedding_rows(conn: &Connection) -> Result<Vec<EmbeddingRow>> {
+    query_rows(
+        conn,
+        "SELECT node_id, embedding FROM embeddings ORDER BY node_id",
+        |r| {
+            let blob: Vec<u8> = r.get(1)?;
+            Ok(EmbeddingRow {
+                node_id: r.get(0)?,
+                embedding: blob
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            })
+        },
+    )
+}
+
+/// Texts of every node that has one, matched to this graph's ids by key.
+fn text_rows(conn: &Connection, input: &Path) -> Result<Vec<TextRow>> {
+    let mut texts = store::rebuild_texts(input, build_info::read_chunking(conn)?)?;
+    let mut rows: Vec<TextRow> = store::load_nodes(conn)?
+        .into_values()
+        .filter_map(|n| {
+            let text = texts.remove(&(n.source, n.source_id, n.chunk_idx))?;
+            Some(TextRow {
+                node_id: n.id,
+                text,
+            })
+        })
+        .collect();
+    rows.sort_by_key(|r| r.node_id);
+    Ok(rows)
+}
+
+fn write_rows<R: ExportRow>(rows: &[R], path: &Path, format: Format) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    match format {
+        Format::Jsonl => {
+            let mut out = BufWriter::new(file);
+            for row in rows {
+                serde_json::to_writer(&mut out, row)?;
+                out.write_all(b"\n")?;
+            }
+            out.flush()?;
+        }
+        Format::Parquet => {
+            ParquetWriter::new(file).finish(&mut R::frame(rows)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Export `tables` into `out_dir`; returns `(path, rows)` per file written.
+fn export(
+    conn: &Connection,
+    tables: &[Table],
+    input: Option<&Path>,
+    out_dir: &Path,
+    format: Format,
+) -> Result<Vec<(PathBuf, usize)>> {
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+    let ext = match format {
+        Format::Jsonl => "jsonl",
+        Format::Parquet => "parquet",
+    };
+    let mut written = Vec::new();
+    for &table in tables {
+        let path = out_dir.join(format!("{}.{}", table.name(), ext));
+        let count = match table {
+            Table::Nodes => {
+                let rows = node_rows(conn)?;
+                write_rows(&rows, &path, format)?;
+                rows.len()
+            }
+            Table::Edges => {
+                let rows = edge_rows(conn)?;
+                write_rows(&rows, &path, format)?;
+                rows.len()
+            }
+            Table::Texts => {
+                let Some(input) = input else {
+                    bail!("texts are rebuilt from virginia.db: pass --input");
+                };
+                let rows = text_rows(conn, input)?;
+                write_rows(&rows, &path, format)?;
+                rows.len()
+            }
+            Table::Embeddings => {
+                let rows = embedding_rows(conn)?;
+                write_rows(&rows, &path, format)?;
+                rows.len()
+            }
+        };
+        written.push((path, count));
+    }
+    Ok(written)
+}
+
+pub fn run(args: ExportTablesArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let tables = match args.tables.is_empty() {
+        false => args.tables,
+        true => {
+            let mut tables = vec![Table::Nodes, Table::Edges, Table::Embeddings];
+            if args.input.is_some() {
+                tables.insert(2, Table::Texts);
+            }
+            tables
+        }
+    };
+    for (path, count) in export(
+        &conn,
+        &tables,
+        args.input.as_deref(),
+        &args.out_dir,
+        args.format,
+    )? {
+        println!("Wrote {} rows to {}", count, path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    #[test]
+    fn test_export_jsonl_and_parquet() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("graph.sqlite.db").to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type) VALUES
+               (1, 'virginia_code', '1-1', 0, 'section'),
+               (2, 'virginia_code', '1-2', 0, 'section');
+             INSERT INTO edges (from_id, to_id, rel_type, weight) VALUES (1, 2, 'cites', 2.0);",
+        )
+        .unwrap();
+        let blob: Vec<u8> = [0.5f32, -1.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        conn.execute(
+            "INSERT INTO embeddings (node_id, embedding) VALUES (1, ?1)",
+            [blob],
+        )
+        .unwrap();
+
+        let tables = [Table::Nodes, Table::Edges, Table::Embeddings];
+        let out = dir.path().join("jsonl");
+        let written = export(&conn, &tables, None, &out, Format::Jsonl).unwrap();
+        assert_eq!(
+            written.iter().map(|w| w.1).collect::<Vec<_>>(),
+            vec![2, 1, 1]
+        );
+        let line = std::fs::read_to_string(out.join("embeddings.jsonl")).unwrap();
+        let row: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(row["embedding"], serde_json::json!([0.5, -1.0]));
+
+        let out = dir.path().join("parquet");
+        export(&conn, &tables, None, &out, Format::Parquet).unwrap();
+        let df = ParquetReader::new(std::fs::File::open(out.join("embeddings.parquet")).unwrap())
+            .finish()
+            .unwrap();
+        let embedding = df
+            .column("embedding")
+            .unwrap()
+            .list()
+            .unwrap()
+            .get_as_series(0)
+            .unwrap();
+        assert_eq!(
+            embedding
+                .f32()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<_>>(),
+            vec![0.5, -1.0]
+        );
+
+        assert!(export(&conn, &[Table::Texts], None, &out, Format::Jsonl).is_err());
+    }
+}
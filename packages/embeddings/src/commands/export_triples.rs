@@ -0,0 +1,315 @@
+//! `export triples`: (query, positive chunk, hard-negative chunk) training
+//! data for a reranker, from an eval set such as `export queries` writes.
+//!
+//! Each relevant node is a positive. Hard negatives are, in order, the
+//! retrieved nodes the user didn't mark relevant (what the retriever got
+//! wrong), then the nearest neighbours of the positive in the embedding index
+//! when retrieval didn't supply enough. Eval sets name sections, not chunks,
+//! so each node is represented by its first chunk with text.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::build_info;
+use crate::graph::store::{self, GraphNode};
+
+#[derive(Args, Debug)]
+pub struct ExportTriplesArgs {
+    /// Eval JSONL: one `{"query", "relevant", "retrieved"}` object per line
+    #[arg(long)]
+    pub eval: PathBuf,
+
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// virginia.db the graph was built from (chunk texts are rebuilt from it)
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Where to write the triples JSONL
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Hard negatives per (query, positive) pair
+    #[arg(long, default_value_t = 3)]
+    pub negatives: usize,
+
+    /// Only use retrieved-but-not-relevant nodes as negatives; never mine the
+    /// embedding index
+    #[arg(long, default_value_t = false)]
+    pub retrieved_only: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct EvalCase {
+    query: String,
+    #[serde(default)]
+    relevant: Vec<String>,
+    #[serde(default)]
+    retrieved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum NegativeKind {
+    Retrieved,
+    Neighbour,
+}
+
+#[derive(Debug, Serialize)]
+struct Triple {
+    query: String,
+    positive: String,
+    negative: String,
+    positive_node: String,
+    negative_node: String,
+    negative_kind: NegativeKind,
+}
+
+type Key = (String, String);
+
+/// The first chunk with text for every `(source, source_id)`.
+struct Corpus {
+    chunks: HashMap<Key, (GraphNode, String)>,
+    /// L2-normalized embeddings of those chunks, for neighbour mining.
+    vectors: Vec<(Key, Vec<f32>)>,
+}
+
+impl Corpus {
+    fn load(
+        conn: &Connection,
+        mut texts: HashMap<(String, String, i64), String>,
+        vectors: bool,
+    ) -> Result<Self> {
+        let mut nodes: Vec<GraphNode> = store::load_nodes(conn)?.into_values().collect();
+        nodes.sort_by_key(|n| n.chunk_idx);
+        let mut chunks = HashMap::new();
+        for node in nodes {
+            let key = (node.source.clone(), node.source_id.clone());
+            if chunks.contains_key(&key) {
+                continue;
+            }
+            if let Some(text) =
+                texts.remove(&(node.source.clone(), node.source_id.clone(), node.chunk_idx))
+            {
+                chunks.insert(key, (node, text));
+            }
+        }
+
+        let mut loaded = Vec::new();
+        if vectors {
+            let ids: HashMap<i64, &Key> = chunks.iter().map(|(k, (n, _))| (n.id, k)).collect();
+            let mut stmt = conn.prepare("SELECT node_id, embedding FROM embeddings")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let Some(key) = ids.get(&row.get::<_, i64>(0)?) else {
+                    continue;
+                };
+                let blob: Vec<u8> = row.get(1)?;
+                let mut v: Vec<f32> = blob
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norm > 0.0 {
+                    v.iter_mut().for_each(|x| *x /= norm);
+                }
+                loaded.push(((*key).clone(), v));
+            }
+        }
+        Ok(Self {
+            chunks,
+            vectors: loaded,
+        })
+    }
+
+    /// The `n` nodes closest to `key` by cosine similarity, best first.
+    fn neighbours(&self, key: &Key, n: usize) -> Vec<&Key> {
+        let Some((_, target)) = self.vectors.iter().find(|(k, _)| k == key) else {
+            return Vec::new();
+        };
+        let mut scored: Vec<(&Key, f32)> = self
+            .vectors
+            .iter()
+            .filter(|(k, _)| k != key)
+            .map(|(k, v)| (k, v.iter().zip(target).map(|(a, b)| a * b).sum()))
+            .collect();
+        // Only the best `n` are ever used; select them before sorting.
+        if n < scored.len() {
+            scored.select_nth_unstable_by(n, |a, b| b.1.total_cmp(&a.1));
+            scored.truncate(n);
+        }
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().map(|(k, _)| k).collect()
+    }
+}
+
+fn spec(key: &Key) -> String {
+    format!("{}:{}", key.0, key.1)
+}
+
+fn triples(case: &EvalCase, corpus: &Corpus, negatives: usize, mine: bool) -> Vec<Triple> {
+    let relevant: HashSet<Key> = case
+        .relevant
+        .iter()
+        .map(|s| store::parse_node_spec(s))
+        .collect();
+    let retrieved: Vec<Key> = case
+        .retrieved
+        .iter()
+        .map(|s| store::parse_node_spec(s))
+        .filter(|k| !relevant.contains(k) && corpus.chunks.contains_key(k))
+        .collect();
+
+    let mut out = Vec::new();
+    for positive in &case.relevant {
+        let positive = store::parse_node_spec(positive);
+        let Some((_, positive_text)) = corpus.chunks.get(&positive) else {
+            continue;
+        };
+
+        let mut chosen: Vec<(Key, NegativeKind)> = Vec::new();
+        for key in &retrieved {
+            if chosen.len() == negatives {
+                break;
+            }
+            if !chosen.iter().any(|(k, _)| k == key) {
+                chosen.push((key.clone(), NegativeKind::Retrieved));
+            }
+        }
+        if mine && chosen.len() < negatives {
+            // Enough to fill up even if every relevant or already chosen node
+            // is among the nearest.
+            let n = negatives + relevant.len() + chosen.len();
+            for key in corpus.neighbours(&positive, n) {
+                if chosen.len() == negatives {
+                    break;
+                }
+                if !relevant.contains(key) && !chosen.iter().any(|(k, _)| k == key) {
+                    chosen.push((key.clone(), NegativeKind::Neighbour));
+                }
+            }
+        }
+
+        for (key, kind) in chosen {
+            out.push(Triple {
+                query: case.query.clone(),
+                positive: positive_text.clone(),
+                negative: corpus.chunks[&key].1.clone(),
+                positive_node: spec(&positive),
+                negative_node: spec(&key),
+                negative_kind: kind,
+            });
+        }
+    }
+    out
+}
+
+pub fn run(args: ExportTriplesArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let texts = store::rebuild_texts(&args.input, build_info::read_chunking(&conn)?)?;
+    let corpus = Corpus::load(&conn, texts, !args.retrieved_only)?;
+
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(&args.eval)
+            .with_context(|| format!("Failed to open {}", args.eval.display()))?,
+    );
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&args.out)?);
+    let (mut cases, mut written) = (0, 0);
+    let mut kinds: HashMap<&str, usize> = HashMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case: EvalCase = serde_json::from_str(&line)
+            .with_context(|| format!("{} line {}", args.eval.display(), i + 1))?;
+        cases += 1;
+        for triple in triples(&case, &corpus, args.negatives, !args.retrieved_only) {
+            let kind = match triple.negative_kind {
+                NegativeKind::Retrieved => "retrieved",
+                NegativeKind::Neighbour => "neighbour",
+            };
+            *kinds.entry(kind).or_default() += 1;
+            serde_json::to_writer(&mut out, &triple)?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+    }
+    out.flush()?;
+
+    println!(
+        "Wrote {} triples from {} eval cases to {} ({} retrieved negatives, {} neighbour negatives)",
+        written,
+        cases,
+        args.out.display(),
+        kinds.get("retrieved").unwrap_or(&0),
+        kinds.get("neighbour").unwrap_or(&0)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    #[test]
+    fn test_negatives_prefer_retrieved_then_neighbours() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("g.db").to_str().unwrap()).unwrap();
+        let mut texts = HashMap::new();
+        let sections = [
+            ("1-1", [1.0f32, 0.0]),
+            ("1-2", [0.0, 1.0]),
+            ("1-3", [0.9, 0.1]),
+            ("1-4", [0.5, 0.5]),
+        ];
+        for (i, (sid, v)) in sections.iter().enumerate() {
+            let id = i as i64 + 1;
+            conn.execute(
+                "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type)
+                 VALUES (?1, 'virginia_code', ?2, 0, 'section')",
+                rusqlite::params![id, sid],
+            )
+            .unwrap();
+            let blob: Vec<u8> = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+            conn.execute(
+                "INSERT INTO embeddings VALUES (?1, ?2)",
+                rusqlite::params![id, blob],
+            )
+            .unwrap();
+            texts.insert(
+                ("virginia_code".to_string(), sid.to_string(), 0),
+                format!("text {sid}"),
+            );
+        }
+        let corpus = Corpus::load(&conn, texts, true).unwrap();
+
+        let case = EvalCase {
+            query: "q".into(),
+            relevant: vec!["virginia_code:1-1".into()],
+            retrieved: vec!["virginia_code:1-1".into(), "virginia_code:1-2".into()],
+        };
+        let got: Vec<_> = triples(&case, &corpus, 3, true)
+            .into_iter()
+            .map(|t| (t.negative_node, t.negative_kind))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("virginia_code:1-2".to_string(), NegativeKind::Retrieved),
+                ("virginia_code:1-3".to_string(), NegativeKind::Neighbour),
+                ("virginia_code:1-4".to_string(), NegativeKind::Neighbour),
+            ]
+        );
+        assert_eq!(triples(&case, &corpus, 3, false).len(), 1);
+    }
+}
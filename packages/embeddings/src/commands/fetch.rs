@@ -0,0 +1,613 @@
+//! `fetch`: refresh the `virginia_code` table of a `virginia.db` from the
+//! Code of Virginia on law.lis.virginia.gov, so the whole refresh loop runs
+//! from this crate. Each title's page lists its chapters, each chapter's (and
+//! article's) page its sections, and each section has a page of its own.
+//!
+//! Requests are paced to `--rate` per second, one at a time, and rate-limit
+//! and server errors are retried, honouring `Retry-After`. A title is written
+//! in one transaction once all its pages are read: sections keep their row
+//! ids, new ones are appended, and sections no longer listed are deleted. A
+//! section whose page couldn't be read keeps its old row. A crawl of every
+//! title also stamps `metadata.scraped_at`, which the build records as the
+//! corpus's age (see `db::build_info`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use scraper::{ElementRef, Html, Node, Selector};
+use tracing::{info, warn};
+use url::Url;
+
+const DEFAULT_BASE_URL: &str = "https://law.lis.virginia.gov";
+/// Tries per page before giving up on it.
+const ATTEMPTS: u32 = 5;
+/// Longest `Retry-After` honoured.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Args, Debug)]
+pub struct FetchArgs {
+    /// SQLite DB to write, usually virginia.db; created if missing
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Code title to fetch, e.g. `18.2`; repeat for more (default: every
+    /// title in the Code)
+    #[arg(long = "title", value_name = "TITLE")]
+    pub titles: Vec<String>,
+
+    /// Requests per second
+    #[arg(long, default_value_t = 1.0)]
+    pub rate: f64,
+
+    /// Site to crawl, e.g. a mirror
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    pub base_url: Url,
+}
+
+/// A chapter or title listed on a page: its number, name and page.
+#[derive(Debug, Clone, PartialEq)]
+struct Link {
+    num: String,
+    name: String,
+    url: Url,
+}
+
+/// One section as read from its page, in `virginia_code`'s columns.
+#[derive(Debug, Clone)]
+struct FetchedSection {
+    chapter_num: String,
+    chapter_name: String,
+    section: String,
+    title: String,
+    body: String,
+}
+
+/// What writing a title changed.
+#[derive(Debug, Default, PartialEq)]
+struct TitleCounts {
+    added: usize,
+    changed: usize,
+    unchanged: usize,
+    removed: usize,
+}
+
+/// A paced, retrying HTTP client.
+pub(super) struct Client {
+    http: reqwest::Client,
+    interval: Duration,
+    next: Instant,
+    /// `Authorization` header sent with every request.
+    authorization: Option<String>,
+}
+
+impl Client {
+    pub(super) fn new(rate: f64) -> Result<Self> {
+        if rate.is_nan() || rate <= 0.0 {
+            bail!("--rate must be more than zero");
+        }
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .user_agent(concat!("proseva-embeddings/", env!("CARGO_PKG_VERSION")))
+                .timeout(Duration::from_secs(60))
+                .build()?,
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next: Instant::now(),
+            authorization: None,
+        })
+    }
+
+    pub(super) fn with_authorization(mut self, authorization: Option<String>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// GET `url`'s body, retried with backoff on 429, server errors and
+    /// dropped connections.
+    pub(super) async fn get(&mut self, url: &Url) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            tokio::time::sleep_until(self.next.into()).await;
+            self.next = Instant::now() + self.interval;
+            let mut wait = Duration::from_secs(1 << attempt);
+            let mut request = self.http.get(url.clone());
+            if let Some(ref authorization) = self.authorization {
+                request = request.header(reqwest::header::AUTHORIZATION, authorization);
+            }
+            let retryable = match request.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(resp.text().await?),
+                Ok(resp) => {
+                    let status = resp.status();
+                    if !(status.as_u16() == 429 || status.is_server_error()) {
+                        bail!("GET {}: HTTP {}", url, status);
+                    }
+                    if let Some(secs) = resp
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok()?.trim().parse::<u64>().ok())
+                    {
+                        wait = Duration::from_secs(secs).min(MAX_RETRY_AFTER);
+                    }
+                    anyhow::anyhow!("GET {}: HTTP {}", url, status)
+                }
+                Err(err) => anyhow::Error::from(err).context(format!("GET {}", url)),
+            };
+            if attempt >= ATTEMPTS {
+                return Err(retryable);
+            }
+            warn!(wait_secs = wait.as_secs(), "{retryable:#}; retrying");
+            self.next = self.next.max(Instant::now() + wait);
+        }
+    }
+
+    async fn page(&mut self, url: &Url) -> Result<Html> {
+        Ok(Html::parse_document(&self.get(url).await?))
+    }
+}
+
+/// An element's text with whitespace collapsed.
+fn text_of(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The links on `page` whose path's last segment matches `pattern`, in page
+/// order and once each: the first capture, the link text, and the URL.
+fn links(html: &Html, page: &Url, pattern: &Regex) -> Vec<Link> {
+    let anchors = Selector::parse("a[href]").unwrap();
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for a in html.select(&anchors) {
+        let Some(url) = a.value().attr("href").and_then(|h| page.join(h).ok()) else {
+            continue;
+        };
+        let last = url
+            .path()
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("");
+        let Some(num) = pattern.captures(last).map(|c| c[1].to_string()) else {
+            continue;
+        };
+        if seen.insert(num.clone()) {
+            out.push(Link {
+                num,
+                name: text_of(a),
+                url,
+            });
+        }
+    }
+    out
+}
+
+/// `name` without a leading `Chapter 4.` label.
+fn strip_label(name: &str, label: &str, num: &str) -> String {
+    let re = Regex::new(&format!(r"(?i)^{}\s+{}\.?\s*", label, regex::escape(num))).unwrap();
+    re.replace(name, "").trim().to_string()
+}
+
+/// The titles listed on the Code's index page.
+fn title_links(html: &Html, page: &Url) -> Vec<Link> {
+    links(
+        html,
+        page,
+        &Regex::new(r"^title([0-9][0-9A-Za-z.:]*)$").unwrap(),
+    )
+}
+
+/// A title's name from its page's `Title 18.2. Crimes and Offenses
+/// Generally` heading.
+fn title_name(html: &Html, title: &str) -> String {
+    let headings = Selector::parse("h1, h2, h3").unwrap();
+    let re = Regex::new(&format!(
+        r"(?i)^Title\s+{}\.?\s+(.+)$",
+        regex::escape(title)
+    ))
+    .unwrap();
+    html.select(&headings)
+        .find_map(|h| re.captures(&text_of(h)).map(|c| c[1].trim().to_string()))
+        .unwrap_or_default()
+}
+
+/// The chapters below a title's page, not those of other titles its
+/// navigation links to.
+fn chapter_links(html: &Html, page: &Url) -> Vec<Link> {
+    let re = Regex::new(r"^chapter([0-9][0-9A-Za-z.:]*)$").unwrap();
+    links(html, page, &re)
+        .into_iter()
+        .filter(|l| l.url.path().starts_with(page.path()))
+        .map(|mut l| {
+            l.name = strip_label(&l.name, "Chapter", &l.num);
+            l
+        })
+        .collect()
+}
+
+fn article_links(html: &Html, page: &Url) -> Vec<Link> {
+    links(
+        html,
+        page,
+        &Regex::new(r"^article([0-9][0-9A-Za-z.:]*)$").unwrap(),
+    )
+}
+
+fn section_links(html: &Html, page: &Url) -> Vec<Link> {
+    links(
+        html,
+        page,
+        &Regex::new(r"^section([0-9][0-9A-Za-z.:-]*)$").unwrap(),
+    )
+}
+
+/// A section page's caption and body: the text after `§ 18.2-31.` in the
+/// section's heading, and the HTML that follows the heading.
+fn parse_section(html: &Html, section: &str) -> Option<(String, String)> {
+    let candidates = Selector::parse("h1, h2, h3, h4, h5, p, b, strong").unwrap();
+    let re = Regex::new(&format!(r"^§+\s*{}\.?\s*(.*)$", regex::escape(section))).unwrap();
+    let (mut heading, caption) = html
+        .select(&candidates)
+        .find_map(|e| re.captures(&text_of(e)).map(|c| (e, c[1].to_string())))?;
+    // A bold caption inside a paragraph: the body follows the paragraph
+    while heading.next_siblings().all(|n| !n.value().is_element()) {
+        heading = ElementRef::wrap(heading.parent()?)?;
+    }
+    let mut body = String::new();
+    for node in heading.next_siblings() {
+        match node.value() {
+            Node::Element(e) if matches!(e.name(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                break;
+            }
+            Node::Element(_) => body.push_str(&ElementRef::wrap(node)?.html()),
+            Node::Text(t) if !t.trim().is_empty() => body.push_str(t.trim()),
+            _ => {}
+        }
+    }
+    Some((caption.trim().trim_end_matches('.').to_string(), body))
+}
+
+fn ensure_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS virginia_code (
+            id           INTEGER PRIMARY KEY,
+            title_num    TEXT,
+            title_name   TEXT,
+            chapter_num  TEXT,
+            chapter_name TEXT,
+            section      TEXT,
+            title        TEXT,
+            body         TEXT
+        );
+        CREATE TABLE IF NOT EXISTS metadata (key TEXT PRIMARY KEY, value TEXT);",
+    )?;
+    Ok(())
+}
+
+/// Replace title `title_num`'s rows with `sections`. Sections in `failed`
+/// weren't read, so their old rows stay.
+fn write_title(
+    conn: &mut Connection,
+    title_num: &str,
+    title_name: &str,
+    sections: &[FetchedSection],
+    failed: &HashSet<String>,
+) -> Result<TitleCounts> {
+    let tx = conn.transaction()?;
+    let mut counts = TitleCounts::default();
+    {
+        let mut existing: HashMap<String, i64> = tx
+            .prepare("SELECT section, id FROM virginia_code WHERE title_num = ?1")?
+            .query_map([title_num], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let mut next_id: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(id), 0) + 1 FROM virginia_code",
+            [],
+            |r| r.get(0),
+        )?;
+        let mut update = tx.prepare(
+            "UPDATE virginia_code
+             SET title_name = ?2, chapter_num = ?3, chapter_name = ?4, title = ?5, body = ?6
+             WHERE id = ?1 AND (title_name IS NOT ?2 OR chapter_num IS NOT ?3
+                 OR chapter_name IS NOT ?4 OR title IS NOT ?5 OR body IS NOT ?6)",
+        )?;
+        let mut insert = tx.prepare(
+            "INSERT INTO virginia_code
+             (id, title_num, title_name, chapter_num, chapter_name, section, title, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for s in sections {
+            let row = (&s.chapter_num, &s.chapter_name, &s.title, &s.body);
+            match existing.remove(&s.section) {
+                Some(id) => {
+                    match update.execute(params![id, title_name, row.0, row.1, row.2, row.3])? {
+                        0 => counts.unchanged += 1,
+                        _ => counts.changed += 1,
+                    }
+                }
+                None => {
+                    insert.execute(params![
+                        next_id, title_num, title_name, row.0, row.1, s.section, row.2, row.3
+                    ])?;
+                    next_id += 1;
+                    counts.added += 1;
+                }
+            }
+        }
+        for (section, id) in existing {
+            if !failed.contains(&section) {
+                tx.execute("DELETE FROM virginia_code WHERE id = ?1", [id])?;
+                counts.removed += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// Read every section of title `title` from its page.
+async fn fetch_title(
+    client: &mut Client,
+    base: &Url,
+    title: &str,
+) -> Result<(String, Vec<FetchedSection>, HashSet<String>)> {
+    let title_url = base.join(&format!("vacode/title{}/", title))?;
+    let title_page = client.page(&title_url).await?;
+    let name = title_name(&title_page, title);
+    let chapters = chapter_links(&title_page, &title_url);
+    if chapters.is_empty() {
+        bail!("{} lists no chapters; is {} a title?", title_url, title);
+    }
+
+    // Chapter pages list their sections, directly or on article pages
+    let mut listed: Vec<(Link, Link)> = Vec::new();
+    let mut seen = HashSet::new();
+    for chapter in &chapters {
+        let page = client.page(&chapter.url).await?;
+        let mut sections = section_links(&page, &chapter.url);
+        for article in article_links(&page, &chapter.url) {
+            let page = client.page(&article.url).await?;
+            sections.extend(section_links(&page, &article.url));
+        }
+        // Sections are numbered `{title}-{n}`; other numbers are cross-links
+        let own = format!("{}-", title);
+        for section in sections {
+            if section.num.starts_with(&own) && seen.insert(section.num.clone()) {
+                listed.push((chapter.clone(), section));
+            }
+        }
+    }
+
+    let pb = ProgressBar::new(listed.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("  [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} {eta}")
+            .unwrap(),
+    );
+    pb.set_message(format!("Title {}", title));
+    let mut fetched = Vec::with_capacity(listed.len());
+    let mut failed = HashSet::new();
+    for (chapter, section) in listed {
+        let parsed = client.page(&section.url).await.and_then(|page| {
+            parse_section(&page, &section.num)
+                .with_context(|| format!("{} has no § {} heading", section.url, section.num))
+        });
+        match parsed {
+            Ok((caption, body)) => fetched.push(FetchedSection {
+                chapter_num: chapter.num,
+                chapter_name: chapter.name,
+                section: section.num,
+                title: caption,
+                body,
+            }),
+            Err(e) => {
+                pb.suspend(|| warn!(section = %section.num, "Skipping section: {e:#}"));
+                failed.insert(section.num);
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+    Ok((name, fetched, failed))
+}
+
+fn open_output(path: &Path) -> Result<Connection> {
+    let conn =
+        Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    ensure_tables(&conn)?;
+    Ok(conn)
+}
+
+pub async fn run(args: FetchArgs) -> Result<()> {
+    let mut conn = open_output(&args.output)?;
+    let mut client = Client::new(args.rate)?;
+    let base = &args.base_url;
+
+    let every_title = args.titles.is_empty();
+    let titles = if every_title {
+        let index = base.join("vacode/")?;
+        let titles: Vec<String> = title_links(&client.page(&index).await?, &index)
+            .into_iter()
+            .map(|l| l.num)
+            .collect();
+        if titles.is_empty() {
+            bail!("{} lists no titles", index);
+        }
+        info!(titles = titles.len(), "Fetching every title");
+        titles
+    } else {
+        args.titles.clone()
+    };
+
+    let started = chrono::Utc::now();
+    let mut skipped = 0;
+    for title in &titles {
+        let (name, sections, failed) = fetch_title(&mut client, base, title)
+            .await
+            .with_context(|| format!("Failed to fetch title {}", title))?;
+        let counts = write_title(&mut conn, title, &name, &sections, &failed)?;
+        skipped += failed.len();
+        info!(
+            title = %title,
+            sections = sections.len(),
+            added = counts.added,
+            changed = counts.changed,
+            unchanged = counts.unchanged,
+            removed = counts.removed,
+            skipped = failed.len(),
+            "Title written"
+        );
+    }
+
+    if every_title {
+        conn.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('scraped_at', ?1)",
+            [started.to_rfc3339()],
+        )?;
+    }
+    let scraped_at: Option<String> = conn
+        .query_row(
+            "SELECT value FROM metadata WHERE key = 'scraped_at'",
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    info!(
+        output = %args.output.display(),
+        titles = titles.len(),
+        skipped,
+        scraped_at = scraped_at.as_deref().unwrap_or("(not set)"),
+        "Fetch done"
+    );
+    if skipped > 0 {
+        warn!(
+            sections = skipped,
+            "Some section pages couldn't be read; their old rows were kept"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lis_pages() {
+        let base = Url::parse("https://law.lis.virginia.gov/vacode/title18.2/").unwrap();
+        let title = Html::parse_document(
+            r#"<nav><a href="/vacode/title18.1/">Previous</a></nav>
+            <h2>Title 18.2. Crimes and Offenses Generally</h2>
+            <ul><li><a href="chapter1/">Chapter 1. In General</a></li>
+            <li><a href="/vacode/title18.2/chapter4/">Chapter 4. Crimes Against the Person</a></li>
+            <li><a href="chapter4/">Chapter 4</a></li></ul>"#,
+        );
+        assert_eq!(title_name(&title, "18.2"), "Crimes and Offenses Generally");
+        let chapters: Vec<_> = chapter_links(&title, &base)
+            .into_iter()
+            .map(|l| (l.num, l.name, l.url.path().to_string()))
+            .collect();
+        assert_eq!(
+            chapters,
+            [
+                (
+                    "1".into(),
+                    "In General".into(),
+                    "/vacode/title18.2/chapter1/".into()
+                ),
+                (
+                    "4".into(),
+                    "Crimes Against the Person".into(),
+                    "/vacode/title18.2/chapter4/".into()
+                ),
+            ]
+        );
+
+        let chapter_url = base.join("chapter4/").unwrap();
+        let chapter = Html::parse_document(
+            r#"<a href="article1/">Article 1. Homicide</a>
+            <a href="section18.2-30/">§ 18.2-30</a> <a href="section18.2-31/">§ 18.2-31</a>"#,
+        );
+        let sections: Vec<_> = section_links(&chapter, &chapter_url)
+            .into_iter()
+            .map(|l| l.num)
+            .collect();
+        assert_eq!(sections, ["18.2-30", "18.2-31"]);
+        assert_eq!(article_links(&chapter, &chapter_url)[0].num, "1");
+
+        let section = Html::parse_document(
+            r#"<div id="va_code"><p><b>§ 18.2-31. Capital murder defined; punishment.</b></p>
+            <p>The following offenses shall constitute capital murder:</p>
+            <p>1. The willful killing of any person.</p><h3>Notes</h3><p>ignored</p></div>"#,
+        );
+        let (caption, body) = parse_section(&section, "18.2-31").unwrap();
+        assert_eq!(caption, "Capital murder defined; punishment");
+        assert_eq!(
+            body,
+            "<p>The following offenses shall constitute capital murder:</p>\
+             <p>1. The willful killing of any person.</p>"
+        );
+        assert!(parse_section(&section, "18.2-32").is_none());
+    }
+
+    #[test]
+    fn test_write_title_keeps_ids() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        ensure_tables(&conn).unwrap();
+        let section = |num: &str, body: &str| FetchedSection {
+            chapter_num: "4".into(),
+            chapter_name: "Crimes Against the Person".into(),
+            section: num.into(),
+            title: format!("Section {num}"),
+            body: body.into(),
+        };
+        let first = [
+            section("18.2-30", "a"),
+            section("18.2-31", "b"),
+            section("18.2-32", "c"),
+        ];
+        let counts = write_title(&mut conn, "18.2", "Crimes", &first, &HashSet::new()).unwrap();
+        assert_eq!(counts.added, 3);
+
+        // 18.2-30 repealed, 18.2-31 amended, 18.2-32 unreadable, 18.2-33 new
+        let second = [section("18.2-31", "b amended"), section("18.2-33", "d")];
+        let failed = HashSet::from(["18.2-32".to_string()]);
+        let counts = write_title(&mut conn, "18.2", "Crimes", &second, &failed).unwrap();
+        assert_eq!(
+            counts,
+            TitleCounts {
+                added: 1,
+                changed: 1,
+                unchanged: 0,
+                removed: 1
+            }
+        );
+        let rows: Vec<(i64, String, String)> = conn
+            .prepare("SELECT id, section, body FROM virginia_code ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (2, "18.2-31".into(), "b amended".into()),
+                (3, "18.2-32".into(), "c".into()),
+                (4, "18.2-33".into(), "d".into()),
+            ]
+        );
+        let counts = write_title(&mut conn, "18.2", "Crimes", &second, &failed).unwrap();
+        assert_eq!((counts.unchanged, counts.removed), (2, 0));
+    }
+}
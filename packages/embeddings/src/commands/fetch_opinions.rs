@@ -0,0 +1,490 @@
+//! `fetch-opinions`: pull Virginia appellate opinions from the CourtListener
+//! API into the `documents` table of a `virginia.db`, tagged
+//! `dataset = 'case-law'`, so a build's document→statute `references` edges
+//! come from real opinions.
+//!
+//! CourtListener groups the opinions of one decision (majority, concurrences,
+//! dissents) in a cluster. Each cluster becomes one document, named
+//! `courtlistener/{cluster id}` and titled `{case name} ({year})`; its content
+//! is every opinion's HTML under an `<h2>` naming its type and author, so
+//! chunking splits at opinion boundaries. The HTML with CourtListener's
+//! citation links is preferred, then the other HTML sources, then plain text.
+//!
+//! Clusters are read page by page through the API's cursor, and each page is
+//! written in one transaction: documents keep their row ids, new ones are
+//! appended, and none are deleted, so `--since` refreshes recent decisions
+//! without touching older ones. A cluster whose opinions couldn't be read
+//! keeps its old row. Requests go through `fetch`'s paced, retrying client.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use tracing::{info, warn};
+use url::Url;
+
+use super::fetch::Client;
+
+const DEFAULT_BASE_URL: &str = "https://www.courtlistener.com/api/rest/v4/";
+/// The Supreme Court of Virginia and the Court of Appeals of Virginia.
+const DEFAULT_COURTS: &[&str] = &["va", "vactapp"];
+const DATASET: &str = "case-law";
+const FILENAME_PREFIX: &str = "courtlistener/";
+
+#[derive(Args, Debug)]
+pub struct FetchOpinionsArgs {
+    /// SQLite DB to write, usually virginia.db; created if missing
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// CourtListener court id; repeat for more (default: va and vactapp)
+    #[arg(long = "court", value_name = "COURT")]
+    pub courts: Vec<String>,
+
+    /// Only decisions filed on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<chrono::NaiveDate>,
+
+    /// Stop after this many decisions per court, e.g. for a sample
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Environment variable holding the CourtListener API token
+    #[arg(long, default_value = "COURTLISTENER_TOKEN")]
+    pub token_env: String,
+
+    /// Requests per second; CourtListener allows 5,000 an hour
+    #[arg(long, default_value_t = 1.0)]
+    pub rate: f64,
+
+    /// API root, e.g. a mirror
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    pub base_url: Url,
+}
+
+/// One page of a list endpoint.
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    #[serde(default)]
+    count: Option<usize>,
+    next: Option<String>,
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cluster {
+    id: i64,
+    #[serde(default)]
+    case_name: String,
+    #[serde(default)]
+    date_filed: Option<String>,
+    #[serde(default)]
+    sub_opinions: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Opinion {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    author_str: Option<String>,
+    html_with_citations: Option<String>,
+    html_columbia: Option<String>,
+    html_lawbox: Option<String>,
+    xml_harvard: Option<String>,
+    html: Option<String>,
+    plain_text: Option<String>,
+}
+
+/// A cluster as a `documents` row.
+#[derive(Debug, Clone, PartialEq)]
+struct FetchedDocument {
+    filename: String,
+    title: String,
+    content: String,
+}
+
+/// What writing a page of documents changed.
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    added: usize,
+    changed: usize,
+    unchanged: usize,
+}
+
+/// Label for CourtListener's opinion `type` codes.
+fn opinion_label(kind: Option<&str>) -> &'static str {
+    match kind.unwrap_or("") {
+        "020lead" => "Majority opinion",
+        "025plurality" => "Plurality opinion",
+        "030concurrence" => "Concurrence",
+        "035concurrenceinpart" => "Concurrence in part",
+        "040dissent" => "Dissent",
+        "050addendum" => "Addendum",
+        "060remittitur" => "Remittitur",
+        "070rehearing" => "On rehearing",
+        "080onthemerits" => "On the merits",
+        "090onmotiontostrike" => "On motion to strike",
+        _ => "Opinion",
+    }
+}
+
+/// An opinion's text as HTML, from the best source it has.
+fn opinion_html(opinion: &Opinion) -> Option<String> {
+    let html = [
+        &opinion.html_with_citations,
+        &opinion.html_columbia,
+        &opinion.html_lawbox,
+        &opinion.xml_harvard,
+        &opinion.html,
+    ]
+    .into_iter()
+    .flatten()
+    .find(|h| !h.trim().is_empty());
+    if let Some(html) = html {
+        return Some(html.clone());
+    }
+    let text = opinion
+        .plain_text
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())?;
+    Some(format!("<pre>{}</pre>", escape(text)))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A cluster and its opinions as one document; `None` if no opinion has
+/// text.
+fn document(cluster: &Cluster, opinions: &[Opinion]) -> Option<FetchedDocument> {
+    let mut content = String::new();
+    for opinion in opinions {
+        let Some(html) = opinion_html(opinion) else {
+            continue;
+        };
+        let label = opinion_label(opinion.kind.as_deref());
+        match opinion.author_str.as_deref().map(str::trim) {
+            Some(author) if !author.is_empty() => {
+                content.push_str(&format!("<h2>{} ({})</h2>\n", label, escape(author)))
+            }
+            _ => content.push_str(&format!("<h2>{}</h2>\n", label)),
+        }
+        content.push_str(&html);
+        content.push('\n');
+    }
+    if content.is_empty() {
+        return None;
+    }
+    let case_name = match cluster.case_name.trim() {
+        "" => format!("Cluster {}", cluster.id),
+        name => name.to_string(),
+    };
+    let year = cluster
+        .date_filed
+        .as_deref()
+        .and_then(|d| d.get(..4))
+        .filter(|y| y.chars().all(|c| c.is_ascii_digit()));
+    Some(FetchedDocument {
+        filename: format!("{}{}", FILENAME_PREFIX, cluster.id),
+        title: match year {
+            Some(year) => format!("{} ({})", case_name, year),
+            None => case_name,
+        },
+        content,
+    })
+}
+
+fn ensure_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS documents (
+            id       INTEGER PRIMARY KEY,
+            dataset  TEXT,
+            filename TEXT,
+            title    TEXT,
+            content  TEXT
+        );",
+    )?;
+    Ok(())
+}
+
+/// Insert or update `documents` by filename within the case-law dataset.
+fn write_documents(conn: &mut Connection, documents: &[FetchedDocument]) -> Result<Counts> {
+    let tx = conn.transaction()?;
+    let mut counts = Counts::default();
+    {
+        let mut lookup =
+            tx.prepare("SELECT id FROM documents WHERE dataset = ?1 AND filename = ?2")?;
+        let mut update = tx.prepare(
+            "UPDATE documents SET title = ?2, content = ?3
+             WHERE id = ?1 AND (title IS NOT ?2 OR content IS NOT ?3)",
+        )?;
+        let mut insert = tx.prepare(
+            "INSERT INTO documents (id, dataset, filename, title, content)
+             VALUES ((SELECT COALESCE(MAX(id), 0) + 1 FROM documents), ?1, ?2, ?3, ?4)",
+        )?;
+        for d in documents {
+            let existing: Option<i64> = lookup
+                .query_map(params![DATASET, d.filename], |r| r.get(0))?
+                .next()
+                .transpose()?;
+            match existing {
+                Some(id) => match update.execute(params![id, d.title, d.content])? {
+                    0 => counts.unchanged += 1,
+                    _ => counts.changed += 1,
+                },
+                None => {
+                    insert.execute(params![DATASET, d.filename, d.title, d.content])?;
+                    counts.added += 1;
+                }
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(counts)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(client: &mut Client, url: &Url) -> Result<T> {
+    let body = client.get(url).await?;
+    serde_json::from_str(&body).with_context(|| format!("Unexpected response from {}", url))
+}
+
+/// The first page of `court`'s clusters, oldest first.
+fn clusters_url(base: &Url, court: &str, since: Option<chrono::NaiveDate>) -> Result<Url> {
+    let mut url = base.join("clusters/")?;
+    {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("docket__court", court)
+            .append_pair("order_by", "id")
+            .append_pair("fields", "id,case_name,date_filed,sub_opinions");
+        if let Some(since) = since {
+            query.append_pair("date_filed__gte", &since.to_string());
+        }
+    }
+    Ok(url)
+}
+
+/// Fetch and write every cluster of `court`.
+async fn fetch_court(
+    client: &mut Client,
+    conn: &mut Connection,
+    args: &FetchOpinionsArgs,
+    court: &str,
+) -> Result<(Counts, usize)> {
+    let mut totals = Counts::default();
+    let mut skipped = 0;
+    let mut seen = 0;
+    let mut next = Some(clusters_url(&args.base_url, court, args.since)?);
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("  [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} {eta}")
+            .unwrap(),
+    );
+    pb.set_message(court.to_string());
+    while let Some(url) = next.take() {
+        let page: Page<Cluster> = get_json(client, &url).await?;
+        if let Some(count) = page.count {
+            pb.set_length(args.limit.map_or(count, |l| l.min(count)) as u64);
+        }
+        let mut documents = Vec::with_capacity(page.results.len());
+        for cluster in &page.results {
+            if args.limit.is_some_and(|l| seen >= l) {
+                break;
+            }
+            seen += 1;
+            let mut opinions = Vec::with_capacity(cluster.sub_opinions.len());
+            let mut failed = None;
+            for opinion_url in &cluster.sub_opinions {
+                let opinion = match Url::parse(opinion_url) {
+                    Ok(url) => get_json::<Opinion>(client, &url).await,
+                    Err(e) => Err(anyhow::Error::from(e).context(opinion_url.clone())),
+                };
+                match opinion {
+                    Ok(opinion) => opinions.push(opinion),
+                    Err(e) => {
+                        failed = Some(e);
+                        break;
+                    }
+                }
+            }
+            match failed {
+                Some(e) => {
+                    pb.suspend(|| warn!(cluster = cluster.id, "Skipping decision: {e:#}"));
+                    skipped += 1;
+                }
+                None => documents.extend(document(cluster, &opinions)),
+            }
+            pb.inc(1);
+        }
+        let counts = write_documents(conn, &documents)?;
+        totals.added += counts.added;
+        totals.changed += counts.changed;
+        totals.unchanged += counts.unchanged;
+        if args.limit.is_none_or(|l| seen < l) {
+            next = page
+                .next
+                .map(|n| Url::parse(&n))
+                .transpose()
+                .context("Invalid next page URL")?;
+        }
+    }
+    pb.finish_and_clear();
+    Ok((totals, skipped))
+}
+
+pub async fn run(args: FetchOpinionsArgs) -> Result<()> {
+    let mut conn = Connection::open(&args.output)
+        .with_context(|| format!("Failed to open {}", args.output.display()))?;
+    ensure_tables(&conn)?;
+    let token = std::env::var(&args.token_env).ok();
+    if token.is_none() {
+        warn!(
+            token_env = %args.token_env,
+            "No CourtListener token set; anonymous requests may be refused"
+        );
+    }
+    let mut client =
+        Client::new(args.rate)?.with_authorization(token.map(|t| format!("Token {}", t)));
+
+    let courts: Vec<String> = if args.courts.is_empty() {
+        DEFAULT_COURTS.iter().map(|c| c.to_string()).collect()
+    } else {
+        args.courts.clone()
+    };
+    let mut skipped = 0;
+    for court in &courts {
+        let (counts, court_skipped) = fetch_court(&mut client, &mut conn, &args, court)
+            .await
+            .with_context(|| format!("Failed to fetch court {}", court))?;
+        skipped += court_skipped;
+        info!(
+            court = %court,
+            added = counts.added,
+            changed = counts.changed,
+            unchanged = counts.unchanged,
+            skipped = court_skipped,
+            "Court written"
+        );
+    }
+
+    let by_dataset: HashMap<String, i64> = conn
+        .prepare("SELECT dataset, COUNT(*) FROM documents GROUP BY dataset")?
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                r.get(1)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    info!(
+        output = %args.output.display(),
+        case_law = by_dataset.get(DATASET).copied().unwrap_or(0),
+        skipped,
+        "Fetch done"
+    );
+    if skipped > 0 {
+        warn!(
+            decisions = skipped,
+            "Some decisions' opinions couldn't be read; their old rows were kept"
+        );
+    }
+    if by_dataset.get(DATASET).copied().unwrap_or(0) == 0 {
+        bail!("No opinions were written; check --court and --since");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_becomes_document() {
+        let page: Page<Cluster> = serde_json::from_str(
+            r#"{"count": 1, "next": null, "results": [{
+                "id": 4242, "case_name": "Smith v. Commonwealth", "date_filed": "2021-06-10",
+                "sub_opinions": ["https://www.courtlistener.com/api/rest/v4/opinions/1/",
+                                 "https://www.courtlistener.com/api/rest/v4/opinions/2/"]
+            }]}"#,
+        )
+        .unwrap();
+        let cluster = &page.results[0];
+        assert_eq!(cluster.sub_opinions.len(), 2);
+        let opinions: Vec<Opinion> = serde_json::from_str(
+            r#"[{"type": "020lead", "author_str": "Kelsey", "html_with_citations": "",
+                 "html": "<p>Under Code § 18.2-31 ...</p>", "plain_text": "ignored"},
+                {"type": "040dissent", "author_str": null, "html_with_citations": null,
+                 "plain_text": "I dissent from § 19.2-264.2 <sic>."}]"#,
+        )
+        .unwrap();
+        let doc = document(cluster, &opinions).unwrap();
+        assert_eq!(doc.filename, "courtlistener/4242");
+        assert_eq!(doc.title, "Smith v. Commonwealth (2021)");
+        assert_eq!(
+            doc.content,
+            "<h2>Majority opinion (Kelsey)</h2>\n<p>Under Code § 18.2-31 ...</p>\n\
+             <h2>Dissent</h2>\n<pre>I dissent from § 19.2-264.2 &lt;sic&gt;.</pre>\n"
+        );
+        assert!(document(cluster, &[Opinion::default()]).is_none());
+
+        let url = clusters_url(
+            &Url::parse(DEFAULT_BASE_URL).unwrap(),
+            "va",
+            Some("2020-01-01".parse().unwrap()),
+        )
+        .unwrap();
+        assert_eq!(url.path(), "/api/rest/v4/clusters/");
+        assert!(url.query().unwrap().contains("docket__court=va"));
+        assert!(url.query().unwrap().contains("date_filed__gte=2020-01-01"));
+    }
+
+    #[test]
+    fn test_write_documents_keeps_ids() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        ensure_tables(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO documents VALUES (7, 'legislation', 'hb-1.txt', 'HB 1', 'text')",
+            [],
+        )
+        .unwrap();
+        let doc = |id: i64, content: &str| FetchedDocument {
+            filename: format!("courtlistener/{id}"),
+            title: format!("Case {id} (2021)"),
+            content: content.into(),
+        };
+        let counts = write_documents(&mut conn, &[doc(1, "a"), doc(2, "b")]).unwrap();
+        assert_eq!(counts.added, 2);
+        let counts = write_documents(&mut conn, &[doc(2, "b amended"), doc(1, "a")]).unwrap();
+        assert_eq!(
+            counts,
+            Counts {
+                added: 0,
+                changed: 1,
+                unchanged: 1
+            }
+        );
+        let rows: Vec<(i64, String, String)> = conn
+            .prepare("SELECT id, dataset, content FROM documents ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (7, "legislation".into(), "text".into()),
+                (8, "case-law".into(), "a".into()),
+                (9, "case-law".into(), "b amended".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Subcommands that work on an existing graph DB (or plan or feed a build)
+//! rather than building one.
+
+pub mod ask;
+pub mod diff;
+pub mod estimate;
+pub mod experiment;
+pub mod export;
+pub mod export_pairs;
+pub mod export_queries;
+pub mod export_tables;
+pub mod export_triples;
+pub mod fetch;
+pub mod fetch_opinions;
+pub mod path;
+pub mod query;
+pub mod serve;
+pub mod stats;
+pub mod subgraph;
+pub mod validate;
+pub mod validate_input;
+
+use clap::Subcommand;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Semantic search over an output DB, or graph traversals, paths and subgraphs
+    Query(query::QueryArgs),
+    /// Answer a question from an output DB's passages with a chat-completions model, citing sections
+    Ask(ask::AskArgs),
+    /// Print node, edge and embedding counts for an output DB
+    Stats(stats::StatsArgs),
+    /// Compare two output DBs: added, removed and modified nodes and edges, and embedding drift
+    Diff(diff::DiffArgs),
+    /// Estimate tokens, wall time and API cost of a build from a sample of its input
+    Estimate(estimate::EstimateArgs),
+    /// Build a small index per variant of a plan and compare their eval scores
+    Experiment(experiment::ExperimentArgs),
+    /// Export data derived from an output DB or the server
+    Export(export::ExportArgs),
+    /// Check an output DB for broken references and unfinished embeddings
+    Validate(validate::ValidateArgs),
+    /// Check a virginia.db's tables, column types, NULL rates and broken rows before building
+    ValidateInput(validate_input::ValidateInputArgs),
+    /// Refresh virginia_code in a virginia.db from law.lis.virginia.gov
+    Fetch(fetch::FetchArgs),
+    /// Add Virginia appellate opinions from CourtListener to a virginia.db's documents
+    FetchOpinions(fetch_opinions::FetchOpinionsArgs),
+    /// Run the embedding server (`embedding-server`) with the given flags
+    Serve(serve::ServeArgs),
+}
+
+pub async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Query(args) => query::run(args).await,
+        Command::Ask(args) => ask::run(args).await,
+        Command::Stats(args) => stats::run(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Estimate(args) => estimate::run(args).await,
+        Command::Experiment(args) => experiment::run(args).await,
+        Command::Export(args) => export::run(args),
+        Command::Validate(args) => validate::run(args),
+        Command::ValidateInput(args) => validate_input::run(args),
+        Command::Fetch(args) => fetch::run(args).await,
+        Command::FetchOpinions(args) => fetch_opinions::run(args).await,
+        Command::Serve(args) => serve::run(args),
+    }
+}
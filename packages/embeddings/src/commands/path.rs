@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+use rusqlite::Connection;
+
+use crate::db::build_info;
+use crate::graph::path::shortest_paths;
+use crate::graph::store::{self, Adjacency, GraphNode};
+
+#[derive(Args, Debug)]
+pub struct PathArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Start node: a Code section (`18.2-31`, `§ 18.2-31`) or `source:source_id`
+    #[arg(long)]
+    pub from: String,
+
+    /// End node, same forms as --from
+    #[arg(long)]
+    pub to: String,
+
+    /// Edge types to traverse (direction is ignored)
+    #[arg(long, value_delimiter = ',', default_value = "cites,references,contains,amends,enacts")]
+    pub rel: Vec<String>,
+
+    /// Maximum path length in hops
+    #[arg(long, default_value_t = 6)]
+    pub max_hops: usize,
+
+    /// Maximum number of (equally short) paths to print
+    #[arg(long, default_value_t = 5)]
+    pub max_paths: usize,
+
+    /// virginia.db the graph was built from; adds a text summary to each node
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+}
+
+pub fn run(args: PathArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let from: Vec<i64> = store::resolve(&conn, &args.from)?.iter().map(|n| n.id).collect();
+    let to: Vec<i64> = store::resolve(&conn, &args.to)?.iter().map(|n| n.id).collect();
+    let adj = Adjacency::load(&conn, &args.rel)?;
+    let nodes = store::load_nodes(&conn)?;
+    let texts = match args.input {
+        Some(ref input) => store::rebuild_texts(input, build_info::read_chunking(&conn)?)?,
+        None => HashMap::new(),
+    };
+
+    let paths = shortest_paths(&adj, &from, &to, args.max_hops, args.max_paths);
+    if paths.is_empty() {
+        println!(
+            "No path from {} to {} within {} hops via {}",
+            args.from,
+            args.to,
+            args.max_hops,
+            args.rel.join(",")
+        );
+        return Ok(());
+    }
+
+    for (i, path) in paths.iter().enumerate() {
+        println!("Path {} ({} hops)", i + 1, path.hops.len());
+        for (j, id) in path.nodes.iter().enumerate() {
+            if j > 0 {
+                let hop = path.hops[j - 1];
+                let rel = &adj.edges[hop.edge].rel_type;
+                if hop.forward {
+                    println!("      │ -{}->", rel);
+                } else {
+                    println!("      │ <-{}-", rel);
+                }
+            }
+            println!("  {}", describe(&nodes[id], &texts));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn describe(node: &GraphNode, texts: &HashMap<(String, String, i64), String>) -> String {
+    let mut line = format!("[{}] {} {}", node.node_type, node.source, node.source_id);
+    if node.chunk_idx > 0 {
+        line.push_str(&format!(" (chunk {})", node.chunk_idx));
+    }
+    let key = (node.source.clone(), node.source_id.clone(), node.chunk_idx);
+    if let Some(text) = texts.get(&key) {
+        line.push_str(" — ");
+        line.push_str(&store::summarize(text, 100));
+    }
+    line
+}
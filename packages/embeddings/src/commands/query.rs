@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use rusqlite::Connection;
+
+use super::{path, subgraph};
+use crate::db::build_info;
+use crate::embed;
+use crate::graph::store;
+use crate::query;
+use crate::search::{self, SearchIndex};
+
+/// `query "text"` runs a semantic search; `query graph|path|subgraph` work on
+/// the graph structure.
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct QueryArgs {
+    #[command(subcommand)]
+    pub command: Option<QueryCommand>,
+
+    #[command(flatten)]
+    pub search: SearchArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Natural-language query, e.g. "reckless driving penalties"
+    #[arg(required = true)]
+    pub text: Option<String>,
+
+    /// Path to embeddings.sqlite.db
+    #[arg(long, required = true)]
+    pub db: Option<PathBuf>,
+
+    /// Number of results
+    #[arg(long, default_value_t = 10)]
+    pub top_k: usize,
+
+    /// virginia.db the DB was built from; adds a text snippet to each result
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+
+    /// Share of a section's score taken from its heading, when the DB was
+    /// built with --title-embeddings
+    #[arg(long, default_value_t = search::DEFAULT_TITLE_WEIGHT, value_parser = parse_weight)]
+    pub title_weight: f32,
+
+    /// Leave out results whose text contains any of these terms
+    /// (comma-separated, e.g. `repealed,expired`); needs a DB built with --fts
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_terms: Vec<String>,
+
+    /// `remove` drops results with an excluded term, `demote` keeps them at a
+    /// lower score
+    #[arg(long, default_value = "remove")]
+    pub exclude_mode: search::ExcludeMode,
+
+    /// Characters of text per snippet
+    #[arg(long, default_value_t = 160)]
+    pub snippet_chars: usize,
+
+    /// Print results as JSON lines instead of a table
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QueryCommand {
+    /// Run a traversal query, e.g. `node("virginia_code","46.2-862") -cites-> *`
+    Graph(GraphArgs),
+    /// Find shortest paths between two nodes through the citation graph
+    Path(path::PathArgs),
+    /// Extract the induced subgraph around a node as JSON
+    Subgraph(subgraph::SubgraphArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct GraphArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Query in the `graph-query` language
+    pub query: String,
+
+    /// Print results as JSON lines instead of a table
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Maximum number of results to print
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+pub async fn run(args: QueryArgs) -> Result<()> {
+    match args.command {
+        Some(QueryCommand::Graph(args)) => graph(args),
+        Some(QueryCommand::Path(args)) => path::run(args),
+        Some(QueryCommand::Subgraph(args)) => subgraph::run(args),
+        None => search(args.search).await,
+    }
+}
+
+async fn search(args: SearchArgs) -> Result<()> {
+    let (Some(text), Some(db)) = (args.text, args.db) else {
+        bail!("query needs a query text and --db");
+    };
+    let index = SearchIndex::load(&db)?;
+    if index.is_empty() {
+        bail!("{} has no embeddings to search", db.display());
+    }
+    // Queries must be embedded by the model that embedded the DB.
+    let model = embed::models::find(&index.model_name)
+        .with_context(|| format!("{} was embedded with an unsupported model", db.display()))?;
+    // Before loading the model, so a DB without --fts fails fast.
+    let exclusion = index.exclusion(&args.exclude_terms, args.exclude_mode)?;
+    let embedder = embed::Embedder::new(model, embed::Device::Auto, 1, None).await?;
+    let mut vectors = embedder.embed(vec![model.format_query(&text)]).await?;
+    let hits = index.search(
+        &vectors.remove(0),
+        args.top_k,
+        None,
+        args.title_weight,
+        Some(&exclusion),
+    )?;
+    let mut texts = match args.input {
+        Some(ref input) => {
+            let conn =
+                Connection::open_with_flags(&db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            let keys: HashSet<store::TextKey> = hits
+                .iter()
+                .map(|h| (h.node.source.clone(), h.node.source_id.clone(), h.node.chunk_idx))
+                .collect();
+            store::rebuild_texts_for(input, build_info::read_chunking(&conn)?, &keys)?
+        }
+        None => Default::default(),
+    };
+
+    if args.json {
+        for hit in &hits {
+            let key = (hit.node.source.clone(), hit.node.source_id.clone(), hit.node.chunk_idx);
+            let snippet = texts.remove(&key).map(|t| store::summarize(&t, args.snippet_chars));
+            let mut value = serde_json::to_value(hit)?;
+            value["snippet"] = serde_json::json!(snippet);
+            println!("{}", value);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{:>4}  {:>6}  {:<14} {:<24} {:>5}",
+        "rank", "score", "source", "source_id", "chunk"
+    );
+    for (rank, hit) in hits.iter().enumerate() {
+        let node = &hit.node;
+        println!(
+            "{:>4}  {:>6.3}  {:<14} {:<24} {:>5}",
+            rank + 1,
+            hit.score,
+            node.source,
+            node.source_id,
+            node.chunk_idx
+        );
+        let key = (node.source.clone(), node.source_id.clone(), node.chunk_idx);
+        if let Some(text) = texts.remove(&key) {
+            println!("        {}", store::summarize(&text, args.snippet_chars));
+        }
+    }
+    Ok(())
+}
+
+fn parse_weight(s: &str) -> Result<f32, String> {
+    let weight: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&weight) {
+        Ok(weight)
+    } else {
+        Err(format!("{weight} is not in [0, 1]"))
+    }
+}
+
+fn graph(args: GraphArgs) -> Result<()> {
+    let query = query::parse(&args.query)?;
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let nodes = query::evaluate(&conn, &query)?;
+    query::print_nodes(&nodes, args.json, args.limit)
+}
@@ -0,0 +1,33 @@
+use std::ffi::OsString;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Flags passed through to `embedding-server` (see `embedding-server --help`)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<OsString>,
+}
+
+/// The server pulls in axum and the query-time model, so it stays its own
+/// binary; `serve` runs the `embedding-server` built next to this one.
+pub fn run(args: ServeArgs) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let server = exe.with_file_name(format!("embedding-server{}", std::env::consts::EXE_SUFFIX));
+    if !server.exists() {
+        bail!(
+            "{} not found; build it with `cargo build --release --bin embedding-server`",
+            server.display()
+        );
+    }
+    let status = Command::new(&server)
+        .args(&args.args)
+        .status()
+        .with_context(|| format!("Failed to start {}", server.display()))?;
+    if !status.success() {
+        bail!("embedding-server exited with {}", status);
+    }
+    Ok(())
+}
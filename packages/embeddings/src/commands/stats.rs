@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Args;
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Print the statistics as one JSON object instead of a report
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// Upper bounds of the degree histogram buckets; the last bucket is open.
+const DEGREE_BUCKETS: [i64; 6] = [0, 1, 4, 9, 49, 199];
+
+#[derive(Debug, Serialize)]
+struct Coverage {
+    node_type: String,
+    nodes: i64,
+    embedded: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct DegreeBucket {
+    /// e.g. `0`, `2-4`, `200+`
+    degree: String,
+    nodes: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct DegreeStats {
+    min: i64,
+    median: i64,
+    p90: i64,
+    p99: i64,
+    max: i64,
+    mean: f64,
+    histogram: Vec<DegreeBucket>,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    nodes: i64,
+    nodes_by_type: Vec<(String, i64)>,
+    nodes_by_source: Vec<(String, i64)>,
+    edges: i64,
+    edges_by_rel_type: Vec<(String, i64)>,
+    /// Total (in + out) degree over all edge types.
+    degree: DegreeStats,
+    embeddings: i64,
+    coverage: Vec<Coverage>,
+    model_info: Vec<(String, String)>,
+    /// Main DB file plus any `-wal` file.
+    db_bytes: u64,
+}
+
+fn grouped<T: rusqlite::types::FromSql>(
+    conn: &Connection,
+    sql: &str,
+) -> Result<Vec<(String, T)>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn degree_stats(conn: &Connection) -> Result<DegreeStats> {
+    let mut stmt = conn.prepare(
+        "SELECT COUNT(e.id) FROM nodes n
+         LEFT JOIN (SELECT from_id AS id FROM edges UNION ALL SELECT to_id FROM edges) e
+           ON e.id = n.id
+         GROUP BY n.id",
+    )?;
+    let mut degrees: Vec<i64> = stmt
+        .query_map([], |r| r.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    degrees.sort_unstable();
+
+    let at = |q: f64| -> i64 {
+        match degrees.len() {
+            0 => 0,
+            n => degrees[((n - 1) as f64 * q).round() as usize],
+        }
+    };
+    let mut histogram = Vec::new();
+    let mut lower = 0;
+    for &upper in &DEGREE_BUCKETS {
+        let label = match lower == upper {
+            true => upper.to_string(),
+            false => format!("{}-{}", lower, upper),
+        };
+        histogram.push(DegreeBucket {
+            degree: label,
+            nodes: degrees.iter().filter(|&&d| d >= lower && d <= upper).count() as i64,
+        });
+        lower = upper + 1;
+    }
+    histogram.push(DegreeBucket {
+        degree: format!("{}+", lower),
+        nodes: degrees.iter().filter(|&&d| d >= lower).count() as i64,
+    });
+
+    Ok(DegreeStats {
+        min: degrees.first().copied().unwrap_or(0),
+        median: at(0.5),
+        p90: at(0.9),
+        p99: at(0.99),
+        max: degrees.last().copied().unwrap_or(0),
+        mean: match degrees.len() {
+            0 => 0.0,
+            n => degrees.iter().sum::<i64>() as f64 / n as f64,
+        },
+        histogram,
+    })
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn collect(conn: &Connection, db: &Path) -> Result<Stats> {
+    let count = |table: &str| -> Result<i64> {
+        Ok(conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))?)
+    };
+
+    let mut coverage_stmt = conn.prepare(
+        "SELECT n.node_type, COUNT(*), COUNT(e.node_id) FROM nodes n
+         LEFT JOIN embeddings e ON e.node_id = n.id
+         GROUP BY n.node_type ORDER BY n.node_type",
+    )?;
+    let coverage = coverage_stmt
+        .query_map([], |r| {
+            Ok(Coverage {
+                node_type: r.get(0)?,
+                nodes: r.get(1)?,
+                embedded: r.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut wal = db.as_os_str().to_owned();
+    wal.push("-wal");
+
+    Ok(Stats {
+        nodes: count("nodes")?,
+        nodes_by_type: grouped(
+            conn,
+            "SELECT node_type, COUNT(*) FROM nodes GROUP BY node_type ORDER BY COUNT(*) DESC",
+        )?,
+        nodes_by_source: grouped(
+            conn,
+            "SELECT source, COUNT(*) FROM nodes GROUP BY source ORDER BY COUNT(*) DESC",
+        )?,
+        edges: count("edges")?,
+        edges_by_rel_type: grouped(
+            conn,
+            "SELECT rel_type, COUNT(*) FROM edges GROUP BY rel_type ORDER BY COUNT(*) DESC",
+        )?,
+        degree: degree_stats(conn)?,
+        embeddings: count("embeddings")?,
+        coverage,
+        model_info: grouped(conn, "SELECT key, value FROM model_info ORDER BY key")?,
+        db_bytes: file_size(db) + file_size(Path::new(&wal)),
+    })
+}
+
+fn percent(part: i64, whole: i64) -> f64 {
+    match whole {
+        0 => 0.0,
+        _ => 100.0 * part as f64 / whole as f64,
+    }
+}
+
+pub fn run(args: StatsArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let stats = collect(&conn, &args.db)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} ({:.1} MB)",
+        args.db.display(),
+        stats.db_bytes as f64 / 1_048_576.0
+    );
+    println!("Nodes: {}", stats.nodes);
+    println!("  by type:");
+    for (node_type, n) in &stats.nodes_by_type {
+        println!("    {:<20} {:>8}", node_type, n);
+    }
+    println!("  by source:");
+    for (source, n) in &stats.nodes_by_source {
+        println!("    {:<20} {:>8}", source, n);
+    }
+
+    println!("Edges: {}", stats.edges);
+    for (rel_type, n) in &stats.edges_by_rel_type {
+        println!("    {:<20} {:>8}", rel_type, n);
+    }
+    let d = &stats.degree;
+    println!(
+        "Degree: min {}  median {}  p90 {}  p99 {}  max {}  mean {:.2}",
+        d.min, d.median, d.p90, d.p99, d.max, d.mean
+    );
+    for bucket in &d.histogram {
+        println!("    {:<20} {:>8}", bucket.degree, bucket.nodes);
+    }
+
+    println!(
+        "Embeddings: {} ({:.1}% of nodes)",
+        stats.embeddings,
+        percent(stats.embeddings, stats.nodes)
+    );
+    // Structural nodes (title, chapter, article) are never embedded.
+    for c in &stats.coverage {
+        println!(
+            "    {:<20} {:>8} / {:<8} {:>5.1}%",
+            c.node_type,
+            c.embedded,
+            c.nodes,
+            percent(c.embedded, c.nodes)
+        );
+    }
+    for (key, value) in &stats.model_info {
+        println!("  {:<22} {}", key, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    #[test]
+    fn test_degree_and_coverage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.sqlite.db");
+        let conn = create_output_db(path.to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type) VALUES
+               (1, 'virginia_code', '1-1', 0, 'section'),
+               (2, 'virginia_code', '1-2', 0, 'section'),
+               (3, 'courts', 'c', 0, 'court');
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES (1, 2, 'cites'), (2, 1, 'cites');
+             INSERT INTO embeddings (node_id, embedding) VALUES (1, zeroblob(8));",
+        )
+        .unwrap();
+
+        let stats = collect(&conn, &path).unwrap();
+        assert_eq!((stats.degree.min, stats.degree.max), (0, 2));
+        let histogram: Vec<_> = stats
+            .degree
+            .histogram
+            .iter()
+            .map(|b| (b.degree.as_str(), b.nodes))
+            .collect();
+        assert_eq!(&histogram[..3], &[("0", 1), ("1", 0), ("2-4", 2)]);
+        assert_eq!(histogram.last().unwrap().0, "200+");
+
+        let code = stats
+            .coverage
+            .iter()
+            .find(|c| c.node_type == "section")
+            .unwrap();
+        assert_eq!((code.nodes, code.embedded), (2, 1));
+        assert!(stats.db_bytes > 0);
+    }
+}
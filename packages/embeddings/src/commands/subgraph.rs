@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::db::build_info;
+use crate::graph::store::{self, Adjacency, GraphEdge, GraphNode};
+use crate::graph::subgraph::neighbourhood;
+
+#[derive(Args, Debug)]
+pub struct SubgraphArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+
+    /// Center node: a Code section (`8.01-243`, `§ 8.01-243`) or `source:source_id`
+    #[arg(long)]
+    pub center: String,
+
+    /// Number of hops (in either edge direction) to include
+    #[arg(long, default_value_t = 1)]
+    pub hops: usize,
+
+    /// Edge types to follow and keep (default: all)
+    #[arg(long, value_delimiter = ',')]
+    pub rel: Vec<String>,
+
+    /// Where to write the subgraph JSON
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// virginia.db the graph was built from; includes each node's text
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+
+    /// Include embedding vectors for nodes that have them
+    #[arg(long, default_value_t = false)]
+    pub vectors: bool,
+}
+
+#[derive(Serialize)]
+struct SubgraphNode {
+    #[serde(flatten)]
+    node: GraphNode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding: Option<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct Subgraph {
+    center: Vec<i64>,
+    hops: usize,
+    nodes: Vec<SubgraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+pub fn run(args: SubgraphArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let center: Vec<i64> = store::resolve(&conn, &args.center)?.iter().map(|n| n.id).collect();
+    let adj = Adjacency::load(&conn, &args.rel)?;
+    let mut all_nodes = store::load_nodes(&conn)?;
+    let mut texts = match args.input {
+        Some(ref input) => store::rebuild_texts(input, build_info::read_chunking(&conn)?)?,
+        None => HashMap::new(),
+    };
+
+    let (ids, edge_idx) = neighbourhood(&adj, &center, args.hops);
+
+    let mut embedding_stmt = conn.prepare("SELECT embedding FROM embeddings WHERE node_id = ?1")?;
+    let mut nodes = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let Some(node) = all_nodes.remove(id) else {
+            continue;
+        };
+        let text = texts.remove(&(node.source.clone(), node.source_id.clone(), node.chunk_idx));
+        let embedding = if args.vectors {
+            embedding_stmt
+                .query_row([id], |r| r.get::<_, Vec<u8>>(0))
+                .optional()?
+                .map(|blob| {
+                    blob.chunks_exact(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect()
+                })
+        } else {
+            None
+        };
+        nodes.push(SubgraphNode {
+            node,
+            text,
+            embedding,
+        });
+    }
+    let edges: Vec<GraphEdge> = edge_idx.into_iter().map(|i| adj.edges[i].clone()).collect();
+
+    let subgraph = Subgraph {
+        center,
+        hops: args.hops,
+        nodes,
+        edges,
+    };
+    let file = std::io::BufWriter::new(std::fs::File::create(&args.out)?);
+    serde_json::to_writer_pretty(file, &subgraph)?;
+
+    println!(
+        "Wrote {} nodes, {} edges around {} ({} hops) to {}",
+        subgraph.nodes.len(),
+        subgraph.edges.len(),
+        args.center,
+        args.hops,
+        args.out.display()
+    );
+    Ok(())
+}
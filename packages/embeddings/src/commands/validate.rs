@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Args;
+use rusqlite::Connection;
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// Path to graph.sqlite.db
+    #[arg(long)]
+    pub db: PathBuf,
+}
+
+struct Check {
+    name: &'static str,
+    /// Offending rows; 0 passes.
+    failures: i64,
+}
+
+fn scalar(conn: &Connection, sql: &str) -> Result<i64> {
+    Ok(conn.query_row(sql, [], |r| r.get(0))?)
+}
+
+pub fn has_table(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [name],
+        |r| r.get(0),
+    )?)
+}
+
+/// Structural checks on a finished output DB: referential integrity between
+/// nodes, edges and embeddings, vector sizes against `model_info`, and no
+/// embedding work left pending.
+fn checks(conn: &Connection) -> Result<Vec<Check>> {
+    for table in ["nodes", "edges", "embeddings", "model_info"] {
+        if !has_table(conn, table)? {
+            bail!("Not an output DB: missing table {}", table);
+        }
+    }
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+
+    let mut checks = vec![
+        Check {
+            name: "sqlite integrity_check",
+            failures: (integrity != "ok") as i64,
+        },
+        Check {
+            name: "edges reference existing nodes",
+            failures: scalar(
+                conn,
+                "SELECT COUNT(*) FROM edges
+                 WHERE from_id NOT IN (SELECT id FROM nodes) OR to_id NOT IN (SELECT id FROM nodes)",
+            )?,
+        },
+        Check {
+            name: "embeddings reference existing nodes",
+            failures: scalar(
+                conn,
+                "SELECT COUNT(*) FROM embeddings WHERE node_id NOT IN (SELECT id FROM nodes)",
+            )?,
+        },
+    ];
+
+    let dims: Option<i64> = conn
+        .query_row(
+            "SELECT CAST(value AS INTEGER) FROM model_info WHERE key = 'dimensions'",
+            [],
+            |r| r.get(0),
+        )
+        .ok();
+    let embeddings = scalar(conn, "SELECT COUNT(*) FROM embeddings")?;
+    checks.push(Check {
+        name: "embeddings match model_info dimensions",
+        failures: match dims {
+            Some(dims) => conn.query_row(
+                "SELECT COUNT(*) FROM embeddings WHERE length(embedding) != ?1 * 4",
+                [dims],
+                |r| r.get(0),
+            )?,
+            // Vectors without a recorded model can't be interpreted.
+            None => embeddings,
+        },
+    });
+
+    if has_table(conn, "edge_provenance")? {
+        checks.push(Check {
+            name: "edge provenance belongs to existing edges",
+            failures: scalar(
+                conn,
+                "SELECT COUNT(*) FROM edge_provenance p
+                 WHERE NOT EXISTS (SELECT 1 FROM edges e WHERE e.from_id = p.from_id
+                                   AND e.to_id = p.to_id AND e.rel_type = p.rel_type)
+                    OR p.char_end <= p.char_start",
+            )?,
+        });
+    }
+
+    if has_table(conn, "pending_embeddings")? {
+        checks.push(Check {
+            name: "no embeddings left pending (finish with --resume)",
+            failures: scalar(
+                conn,
+                "SELECT COUNT(*) FROM pending_embeddings WHERE status = 'pending'",
+            )?,
+        });
+    }
+    Ok(checks)
+}
+
+pub fn run(args: ValidateArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let checks = checks(&conn)?;
+    let mut failed = 0;
+    for check in &checks {
+        if check.failures == 0 {
+            println!("  ok:   {}", check.name);
+        } else {
+            println!("  FAIL: {} ({} rows)", check.name, check.failures);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} checks failed for {}", failed, checks.len(), args.db.display());
+    }
+    println!("{} is valid", args.db.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::{create_output_db, write_model_info};
+
+    #[test]
+    fn test_validate_flags_broken_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.sqlite.db");
+        let conn = create_output_db(path.to_str().unwrap()).unwrap();
+        write_model_info(&conn, &crate::embed::models::MODELS[0], 512, 2).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type)
+             VALUES (1, 'virginia_code', '1-1', 0, 'section');
+             INSERT INTO embeddings (node_id, embedding) VALUES (1, zeroblob(8));",
+        )
+        .unwrap();
+        assert!(checks(&conn).unwrap().iter().all(|c| c.failures == 0));
+
+        // Foreign keys are enforced when building, but not by other writers.
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES (1, 99, 'cites');
+             INSERT INTO embeddings (node_id, embedding) VALUES (2, zeroblob(12));
+             INSERT INTO edge_provenance VALUES (1, 2, 'cites', 0, 4, 11, '§ 1-200');",
+        )
+        .unwrap();
+        let failures: Vec<_> = checks(&conn)
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.failures > 0)
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                "edges reference existing nodes",
+                "embeddings reference existing nodes",
+                "embeddings match model_info dimensions",
+                "edge provenance belongs to existing edges",
+            ]
+        );
+    }
+}
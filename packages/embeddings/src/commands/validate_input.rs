@@ -0,0 +1,456 @@
+//! `validate-input`: check a `virginia.db` before a build commits hours to
+//! it. Each source table is checked for the columns the reader needs, values
+//! of the wrong type (rows the build would skip as `row_errors`), NULL rates,
+//! duplicate ids, and rows the ETL would drop or mangle: empty sections and
+//! HTML cut off mid-tag.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::Args;
+use rusqlite::Connection;
+
+use crate::config;
+use crate::db::reader::{self, SourceColumn, SourceMapping, SourceTable};
+
+#[derive(Args, Debug)]
+pub struct ValidateInputArgs {
+    /// Path to virginia.db
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Build config whose `[sources]` section maps --input's tables (other
+    /// sections are ignored here)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Also exit non-zero on warnings
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+}
+
+/// A column at least this NULL is flagged; the build reads NULL as empty.
+const NULL_WARN_RATE: f64 = 0.5;
+
+/// Example ids listed per finding.
+const EXAMPLE_IDS: usize = 5;
+
+/// Tables whose ETL drops rows with an empty `section` (`empty_section`).
+const EMPTY_SECTION_TABLES: [&str; 2] = ["virginia_code", "federal_code"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+struct Finding {
+    level: Level,
+    message: String,
+}
+
+#[derive(Debug)]
+struct TableReport {
+    /// The Virginia table name.
+    table: &'static str,
+    /// `None` when the table is missing.
+    rows: Option<i64>,
+    findings: Vec<Finding>,
+}
+
+impl TableReport {
+    fn push(&mut self, level: Level, message: impl Into<String>) {
+        self.findings.push(Finding {
+            level,
+            message: message.into(),
+        });
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn id_list(ids: &[i64]) -> String {
+    let ids: Vec<String> = ids.iter().map(i64::to_string).collect();
+    format!("ids {}", ids.join(", "))
+}
+
+/// The ids of up to [`EXAMPLE_IDS`] rows of `table` matching `condition`.
+fn example_ids(conn: &Connection, table: &SourceTable, condition: &str) -> Result<Vec<i64>> {
+    let id = &table.columns[0].expr;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {id} FROM {} WHERE {condition} AND typeof({id}) = 'integer' LIMIT {EXAMPLE_IDS}",
+        quote(&table.name)
+    ))?;
+    let ids = stmt.query_map([], |r| r.get(0))?;
+    Ok(ids.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Whether `text` stops inside an HTML tag: a `<` opening a tag with no `>`
+/// after it, as a scrape cut off mid-page leaves it.
+fn ends_inside_tag(text: &str) -> bool {
+    let Some(open) = text.rfind('<') else {
+        return false;
+    };
+    let rest = &text[open + 1..];
+    rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!')
+        && !rest.contains('>')
+}
+
+/// Check one column that `SELECT`s cleanly: value types and NULL rate, and,
+/// for text, truncated HTML.
+fn check_column(
+    conn: &Connection,
+    table: &SourceTable,
+    column: &SourceColumn,
+    rows: i64,
+    report: &mut TableReport,
+) -> Result<()> {
+    let expr = &column.expr;
+    let from = quote(&table.name);
+    let wanted = if column.integer { "integer" } else { "text" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT typeof({expr}), COUNT(*) FROM {from} GROUP BY 1 ORDER BY 2 DESC"
+    ))?;
+    let types: Vec<(String, i64)> = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let nulls = types
+        .iter()
+        .find(|(t, _)| t == "null")
+        .map_or(0, |(_, n)| *n);
+    // `id` is read as is; every other column reads NULL as empty.
+    let bad: Vec<&(String, i64)> = types
+        .iter()
+        .filter(|(t, _)| t != wanted && (t != "null" || column.name == "id"))
+        .collect();
+    if !bad.is_empty() {
+        let count: i64 = bad.iter().map(|(_, n)| n).sum();
+        let found: Vec<String> = bad.iter().map(|(t, n)| format!("{n} {t}")).collect();
+        let ids = example_ids(
+            conn,
+            table,
+            &format!("typeof({expr}) NOT IN ('{wanted}', 'null')"),
+        )?;
+        let hint = if column.name == "id" {
+            "every row needs an integer id".to_string()
+        } else {
+            format!(
+                "cast it in the build config: [sources.{}] columns = {{ {} = \"CAST({} AS {})\" }}",
+                table.table,
+                column.name,
+                expr,
+                wanted.to_uppercase()
+            )
+        };
+        let examples = match ids.is_empty() {
+            true => String::new(),
+            false => format!(", e.g. {}", id_list(&ids)),
+        };
+        // A column wrong in every row is a schema problem, not bad rows.
+        let level = if count == rows {
+            Level::Fail
+        } else {
+            Level::Warn
+        };
+        report.push(
+            level,
+            format!(
+                "{}: {} of {} values are not {} ({}); those rows will be skipped as row_errors{}. To fix, {}",
+                column.name,
+                count,
+                rows,
+                wanted,
+                found.join(", "),
+                examples,
+                hint
+            ),
+        );
+    }
+
+    if nulls > 0 && column.name != "id" {
+        let rate = nulls as f64 / rows as f64;
+        report.push(
+            if rate >= NULL_WARN_RATE {
+                Level::Warn
+            } else {
+                Level::Ok
+            },
+            format!(
+                "{}: {:.1}% NULL ({} rows), read as empty",
+                column.name,
+                rate * 100.0,
+                nulls
+            ),
+        );
+    }
+
+    if !column.integer {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, {expr} FROM {from} WHERE typeof({expr}) = 'text' AND instr({expr}, '<') > 0",
+            table.columns[0].expr
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut truncated = 0;
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next()? {
+            let text: String = row.get(1)?;
+            if ends_inside_tag(&text) {
+                truncated += 1;
+                if ids.len() < EXAMPLE_IDS {
+                    if let Ok(id) = row.get::<_, i64>(0) {
+                        ids.push(id);
+                    }
+                }
+            }
+        }
+        if truncated > 0 {
+            report.push(
+                Level::Warn,
+                format!(
+                    "{}: {} rows end inside an HTML tag, likely truncated by the scraper ({}); re-fetch them",
+                    column.name,
+                    truncated,
+                    id_list(&ids)
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_table(conn: &Connection, table: &SourceTable) -> Result<TableReport> {
+    let mut report = TableReport {
+        table: table.table,
+        rows: None,
+        findings: Vec::new(),
+    };
+    if !reader::table_exists(conn, &table.name)? {
+        let level = if table.optional {
+            Level::Ok
+        } else {
+            Level::Warn
+        };
+        report.push(
+            level,
+            format!(
+                "no {} table; it will read as empty. If the input names it differently, map it: [sources.{}] table = \"...\"",
+                table.name, table.table
+            ),
+        );
+        return Ok(report);
+    }
+    let from = quote(&table.name);
+    let rows: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {from}"), [], |r| r.get(0))?;
+    report.rows = Some(rows);
+
+    let mut readable = Vec::new();
+    for column in &table.columns {
+        match conn.prepare(&format!("SELECT {} FROM {from} LIMIT 0", column.expr)) {
+            Ok(_) => readable.push(column),
+            Err(err) => report.push(
+                Level::Fail,
+                format!(
+                    "{}: can't read {:?} ({}). Add the column, or map it in the build config: [sources.{}] columns = {{ {} = \"other_column\" }} (\"NULL\" reads it as empty)",
+                    column.name, column.expr, err, table.table, column.name
+                ),
+            ),
+        }
+    }
+    if readable.len() == table.columns.len() {
+        report.push(
+            Level::Ok,
+            format!("all {} columns present", table.columns.len()),
+        );
+    }
+    if rows == 0 {
+        report.push(Level::Warn, "table is empty");
+        return Ok(report);
+    }
+    for &column in &readable {
+        check_column(conn, table, column, rows, &mut report)?;
+    }
+
+    let readable_column = |name: &str| readable.iter().find(|c| c.name == name).copied();
+    if let Some(id) = readable_column("id") {
+        let id = &id.expr;
+        let duplicates: i64 = conn.query_row(
+            &format!("SELECT COUNT({id}) - COUNT(DISTINCT {id}) FROM {from}"),
+            [],
+            |r| r.get(0),
+        )?;
+        if duplicates > 0 {
+            report.push(
+                Level::Warn,
+                format!("id: {duplicates} duplicate ids; dropped_rows and row_errors can't tell those rows apart"),
+            );
+        }
+    }
+
+    if EMPTY_SECTION_TABLES.contains(&table.table) {
+        if let Some(section) = readable_column("section") {
+            let condition = format!("COALESCE({}, '') = ''", section.expr);
+            let empty: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM {from} WHERE {condition}"),
+                [],
+                |r| r.get(0),
+            )?;
+            if empty > 0 {
+                let ids = example_ids(conn, table, &condition)?;
+                report.push(
+                    Level::Warn,
+                    format!(
+                        "section: {} rows have an empty section and will be dropped as empty_section ({})",
+                        empty,
+                        id_list(&ids)
+                    ),
+                );
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn check_input(conn: &Connection, sources: &SourceMapping) -> Result<Vec<TableReport>> {
+    reader::source_tables(sources)
+        .iter()
+        .map(|table| check_table(conn, table))
+        .collect()
+}
+
+pub fn run(args: ValidateInputArgs) -> Result<()> {
+    if reader::postgres_url(&args.input).is_some() {
+        bail!("validate-input reads a SQLite --input, not a PostgreSQL URL");
+    }
+    let sources = match args.config {
+        Some(ref path) => config::load(path)?.sources,
+        None => SourceMapping::new(),
+    };
+    let conn = reader::open_input(&args.input)?;
+    let reports = check_input(&conn, &sources)?;
+
+    let (mut failures, mut warnings) = (0, 0);
+    for report in &reports {
+        match report.rows {
+            Some(rows) => println!("{} ({} rows)", report.table, rows),
+            None => println!("{} (missing)", report.table),
+        }
+        for finding in &report.findings {
+            let label = match finding.level {
+                Level::Ok => "ok:  ",
+                Level::Warn => "warn:",
+                Level::Fail => "FAIL:",
+            };
+            println!("  {} {}", label, finding.message);
+        }
+        failures += report
+            .findings
+            .iter()
+            .filter(|f| f.level == Level::Fail)
+            .count();
+        warnings += report
+            .findings
+            .iter()
+            .filter(|f| f.level == Level::Warn)
+            .count();
+    }
+    println!();
+
+    if reports.iter().all(|r| r.rows.is_none()) {
+        bail!(
+            "{} has none of the source tables; is it the right file?",
+            args.input.display()
+        );
+    }
+    if failures > 0 || (args.strict && warnings > 0) {
+        bail!(
+            "{} failures and {} warnings in {}; fix them before building",
+            failures,
+            warnings,
+            args.input.display()
+        );
+    }
+    println!(
+        "{} is ready to build ({} warnings)",
+        args.input.display(),
+        warnings
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(report: &TableReport, level: Level) -> Vec<&str> {
+        report
+            .findings
+            .iter()
+            .filter(|f| f.level == level)
+            .map(|f| f.message.split(':').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_check_input_flags_broken_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE virginia_code (
+                 id INTEGER, title_num, title_name TEXT, chapter_num TEXT,
+                 chapter_name TEXT, section TEXT, title TEXT, body TEXT
+             );
+             INSERT INTO virginia_code VALUES
+                 (1, '1', 'General', '1', 'Ch', '1-1', 'Heading', '<p>Text</p>'),
+                 (2, '1', 'General', '1', 'Ch', '', 'Heading', '<p>Text</p>'),
+                 (3, 1, 'General', '1', NULL, '1-3', 'Heading', '<p>Cut off <a hr'),
+                 (3, '1', 'General', '1', NULL, '1-4', 'Heading', 'Plain 1 < 2 text');
+             CREATE TABLE constitution (id INTEGER, article TEXT);
+             INSERT INTO constitution VALUES (1, 'I');",
+        )
+        .unwrap();
+
+        let reports = check_input(&conn, &SourceMapping::new()).unwrap();
+        let code = &reports[0];
+        assert_eq!(code.rows, Some(4));
+        assert!(levels(code, Level::Fail).is_empty());
+        assert_eq!(
+            levels(code, Level::Warn),
+            ["title_num", "chapter_name", "body", "id", "section"]
+        );
+        assert!(code
+            .findings
+            .iter()
+            .any(|f| f.message.starts_with("body: 1 rows") && f.message.contains("ids 3")));
+
+        let constitution = &reports[1];
+        assert_eq!(
+            levels(constitution, Level::Fail),
+            [
+                "article_id",
+                "article_name",
+                "section_name",
+                "section_title",
+                "section_text",
+                "section_count"
+            ]
+        );
+
+        // Optional tables are not warned about; required ones are.
+        let missing = |table: &str| reports.iter().find(|r| r.table == table).unwrap();
+        assert_eq!(missing("acts").findings[0].level, Level::Ok);
+        assert_eq!(missing("documents").findings[0].level, Level::Warn);
+    }
+
+    #[test]
+    fn test_ends_inside_tag() {
+        assert!(ends_inside_tag("text <p clas"));
+        assert!(ends_inside_tag("text </di"));
+        assert!(!ends_inside_tag("<p>text</p>"));
+        assert!(!ends_inside_tag("if a < 2"));
+        assert!(!ends_inside_tag("no tags"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::db::reader::{self, SourceMapping};
+use crate::embed::ChainConfig;
+use crate::etl::dedup::DedupPolicy;
+use crate::etl::filters::{Filters, SourceFilters};
+use crate::etl::templates::TextTemplates;
+use crate::etl::transforms::TransformRule;
+use crate::graph::prune::PruneOptions;
+use crate::graph::weights::Formula;
+use crate::quality::QualityRule;
+use crate::scrub::ScrubConfig;
+use crate::summarize::SummaryConfig;
+
+/// Pipeline configuration loaded from `--config <file>.toml`.
+///
+/// Every section is optional; a missing file section means "use the defaults".
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Data-quality expectations checked after each pass.
+    pub quality: Vec<QualityRule>,
+    /// When present, also write a pruned `presentation_edges` table.
+    pub presentation: Option<PruneOptions>,
+    /// When present, redact PII in the source rows before ETL.
+    pub scrub: Option<ScrubConfig>,
+    /// Weight formula per edge type, applied when edges are written.
+    pub weights: BTreeMap<String, Formula>,
+    /// When present, summarize nodes with an LLM endpoint after Pass 3.
+    pub summaries: Option<SummaryConfig>,
+    /// Where each source table lives in an input DB whose schema differs
+    /// from `virginia.db`, keyed by Virginia table name.
+    pub sources: SourceMapping,
+    /// When present, the backends Pass 3 embeds on, in order of preference,
+    /// instead of `--device`.
+    pub embedding: Option<ChainConfig>,
+    /// When present, the per-title cleanup run on code sections before ETL,
+    /// replacing the built-in defaults.
+    pub transforms: Option<Vec<TransformRule>>,
+    /// Which of several code sections with the same text ETL keeps.
+    pub dedup: DedupPolicy,
+    /// `clean_text` template per source, replacing its built-in format.
+    pub templates: BTreeMap<String, String>,
+    /// Length thresholds per source, replacing the built-in ones.
+    pub filters: BTreeMap<String, SourceFilters>,
+}
+
+pub fn load(path: &Path) -> Result<Config> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config {}", path.display()))?;
+    let config: Config =
+        toml::from_str(&raw).with_context(|| format!("Invalid config {}", path.display()))?;
+    reader::check_mapping(&config.sources)
+        .with_context(|| format!("Invalid [sources] in {}", path.display()))?;
+    if let Some(ref chain) = config.embedding {
+        chain
+            .check()
+            .with_context(|| format!("Invalid [embedding] in {}", path.display()))?;
+    }
+    TextTemplates::new(&config.templates)
+        .with_context(|| format!("Invalid [templates] in {}", path.display()))?;
+    Filters::new(&config.filters)
+        .with_context(|| format!("Invalid [filters] in {}", path.display()))?;
+    Ok(config)
+}
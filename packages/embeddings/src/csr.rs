@@ -0,0 +1,104 @@
+//! Compressed sparse row adjacency, stored per `(rel_type, direction)` in the
+//! `adjacency` table so graph walks don't issue one SQL query per hop.
+//!
+//! Node ids index the offsets directly: the neighbours of node `i` are
+//! `targets[offsets[i]..offsets[i + 1]]`, sorted ascending. Both blobs are
+//! little-endian u32 arrays; `offsets` has `max_id + 2` entries.
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Csr {
+    offsets: Vec<u32>,
+    targets: Vec<u32>,
+}
+
+impl Csr {
+    /// Build from `(from, to)` pairs. Duplicate pairs are kept once.
+    pub fn build(pairs: impl IntoIterator<Item = (i64, i64)>) -> Result<Self> {
+        let mut pairs: Vec<(u32, u32)> = pairs
+            .into_iter()
+            .map(|(from, to)| Ok((to_u32(from)?, to_u32(to)?)))
+            .collect::<Result<_>>()?;
+        pairs.sort_unstable();
+        pairs.dedup();
+
+        let max_id = pairs.iter().map(|&(f, t)| f.max(t)).max().unwrap_or(0) as usize;
+        let mut offsets = vec![0u32; max_id + 2];
+        for &(from, _) in &pairs {
+            offsets[from as usize + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let targets = pairs.into_iter().map(|(_, to)| to).collect();
+        Ok(Self { offsets, targets })
+    }
+
+    pub fn neighbours(&self, id: i64) -> &[u32] {
+        let Ok(i) = usize::try_from(id) else {
+            return &[];
+        };
+        match (self.offsets.get(i), self.offsets.get(i + 1)) {
+            (Some(&start), Some(&end)) => &self.targets[start as usize..end as usize],
+            _ => &[],
+        }
+    }
+
+    /// `(offsets, targets)` blobs.
+    pub fn to_blobs(&self) -> (Vec<u8>, Vec<u8>) {
+        (encode(&self.offsets), encode(&self.targets))
+    }
+
+    pub fn from_blobs(offsets: &[u8], targets: &[u8]) -> Result<Self> {
+        let offsets = decode(offsets)?;
+        let targets = decode(targets)?;
+        let valid = !offsets.is_empty()
+            && offsets.windows(2).all(|w| w[0] <= w[1])
+            && offsets.last().map(|&n| n as usize) == Some(targets.len());
+        if !valid {
+            bail!(
+                "Malformed adjacency: {} offsets for {} targets",
+                offsets.len(),
+                targets.len()
+            );
+        }
+        Ok(Self { offsets, targets })
+    }
+}
+
+fn to_u32(id: i64) -> Result<u32> {
+    u32::try_from(id).map_err(|_| anyhow::anyhow!("Node id {} does not fit in u32", id))
+}
+
+fn encode(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Result<Vec<u32>> {
+    if !bytes.len().is_multiple_of(4) {
+        bail!("Adjacency blob length {} is not a multiple of 4", bytes.len());
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_round_trip() {
+        let csr = Csr::build([(3, 4), (1, 2), (1, 3), (1, 2)]).unwrap();
+        assert_eq!(csr.neighbours(1), &[2, 3]);
+        assert_eq!(csr.neighbours(3), &[4]);
+        assert!(csr.neighbours(2).is_empty());
+        assert!(csr.neighbours(99).is_empty());
+
+        let (offsets, targets) = csr.to_blobs();
+        assert_eq!(Csr::from_blobs(&offsets, &targets).unwrap(), csr);
+        assert!(Csr::from_blobs(&offsets, &targets[..4]).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! `build_info`: when the output was built and how fresh its source was, so
+//! the server can tell users whether results reflect the latest session, and
+//! the chunking its node keys were made with, so commands that rebuild texts
+//! from the input chunk them the same way.
+//!
+//! The source's age comes from `metadata.scraped_at` in the input when the
+//! scraper wrote one, else from the input file's modification time. A
+//! PostgreSQL input without the row has no known age.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+
+use crate::db::reader;
+use crate::text::chunker::ChunkConfig;
+
+pub const BUILT_AT: &str = "built_at";
+pub const SCRAPED_AT: &str = "source_scraped_at";
+/// `metadata` or `mtime`: where [`SCRAPED_AT`] came from.
+pub const SCRAPED_FROM: &str = "source_scraped_from";
+/// SHA-256 of the `--snapshot-input` copy the build read.
+pub const SOURCE_SHA256: &str = "source_sha256";
+/// The source tables the input had, comma-separated.
+pub const SOURCE_TABLES: &str = "source_tables";
+/// `--chunk-tokens` / `--chunk-overlap` of the build.
+pub const CHUNK_TOKENS: &str = "chunk_tokens";
+pub const CHUNK_OVERLAP: &str = "chunk_overlap";
+
+/// The input's `metadata` key holding its scrape time.
+const METADATA_KEY: &str = "scraped_at";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFreshness {
+    pub scraped_at: DateTime<Utc>,
+    /// `metadata` or `mtime`.
+    pub from: &'static str,
+}
+
+/// An RFC 3339 timestamp, or a plain `YYYY-MM-DD` date taken as midnight UTC.
+pub fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+            Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+        })
+}
+
+/// When `input`'s corpus was scraped, as far as it can tell.
+pub fn source_freshness(input: &Path) -> Result<Option<SourceFreshness>> {
+    if let Some(value) = reader::read_metadata(input, METADATA_KEY)? {
+        let scraped_at = parse_timestamp(&value).with_context(|| {
+            format!(
+                "metadata.{} {:?} is not an RFC 3339 timestamp or YYYY-MM-DD date",
+                METADATA_KEY, value
+            )
+        })?;
+        return Ok(Some(SourceFreshness {
+            scraped_at,
+            from: "metadata",
+        }));
+    }
+    if reader::postgres_url(input).is_some() {
+        return Ok(None);
+    }
+    let modified = std::fs::metadata(input)
+        .and_then(|m| m.modified())
+        .with_context(|| format!("Failed to stat {}", input.display()))?;
+    Ok(Some(SourceFreshness {
+        scraped_at: modified.into(),
+        from: "mtime",
+    }))
+}
+
+/// Record the build time, the source's freshness and tables, the chunking
+/// and, for a snapshotted input, the input's hash, replacing earlier rows.
+pub fn write_build_info(
+    conn: &Connection,
+    built_at: DateTime<Utc>,
+    source: Option<&SourceFreshness>,
+    source_sha256: Option<&str>,
+    source_tables: &[&str],
+    chunking: ChunkConfig,
+) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS build_info (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        DELETE FROM build_info;
+        ",
+    )?;
+    let mut rows = vec![
+        (BUILT_AT, built_at.to_rfc3339()),
+        (SOURCE_TABLES, source_tables.join(",")),
+        (CHUNK_TOKENS, chunking.max_tokens.to_string()),
+        (CHUNK_OVERLAP, chunking.overlap_tokens.to_string()),
+    ];
+    if let Some(source) = source {
+        rows.push((SCRAPED_AT, source.scraped_at.to_rfc3339()));
+        rows.push((SCRAPED_FROM, source.from.to_string()));
+    }
+    if let Some(sha256) = source_sha256 {
+        rows.push((SOURCE_SHA256, sha256.to_string()));
+    }
+    let mut stmt = conn.prepare("INSERT INTO build_info (key, value) VALUES (?1, ?2)")?;
+    for (key, value) in rows {
+        stmt.execute([key, &value])?;
+    }
+    Ok(())
+}
+
+/// Every `build_info` row; empty for DBs built before the table existed.
+pub fn read_build_info(conn: &Connection) -> Result<HashMap<String, String>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'build_info')",
+        [],
+        |r| r.get(0),
+    )?;
+    if !exists {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare("SELECT key, value FROM build_info")?;
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// The chunking a graph DB was built with; the defaults for DBs built before
+/// it was recorded.
+pub fn read_chunking(conn: &Connection) -> Result<ChunkConfig> {
+    let info = read_build_info(conn)?;
+    let (Some(tokens), Some(overlap)) = (info.get(CHUNK_TOKENS), info.get(CHUNK_OVERLAP)) else {
+        return Ok(ChunkConfig::default());
+    };
+    let parse = |key: &str, value: &str| {
+        value
+            .parse::<usize>()
+            .with_context(|| format!("build_info.{} {:?} is not a number", key, value))
+    };
+    ChunkConfig::new(parse(CHUNK_TOKENS, tokens)?, parse(CHUNK_OVERLAP, overlap)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_freshness_prefers_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("virginia.db");
+        let conn = Connection::open(&input).unwrap();
+        conn.execute_batch("CREATE TABLE courts (id INTEGER PRIMARY KEY);")
+            .unwrap();
+        let freshness = source_freshness(&input).unwrap().unwrap();
+        assert_eq!(freshness.from, "mtime");
+        assert!(Utc::now() - freshness.scraped_at < chrono::Duration::minutes(5));
+
+        conn.execute_batch(
+            "CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO metadata VALUES ('scraped_at', '2026-01-14');",
+        )
+        .unwrap();
+        let freshness = source_freshness(&input).unwrap().unwrap();
+        assert_eq!(freshness.from, "metadata");
+        assert_eq!(
+            freshness.scraped_at.to_rfc3339(),
+            "2026-01-14T00:00:00+00:00"
+        );
+
+        conn.execute("UPDATE metadata SET value = 'last week'", [])
+            .unwrap();
+        assert!(source_freshness(&input).is_err());
+
+        let out = Connection::open_in_memory().unwrap();
+        assert!(read_build_info(&out).unwrap().is_empty());
+        assert_eq!(read_chunking(&out).unwrap(), ChunkConfig::default());
+        let built_at = parse_timestamp("2026-02-01T12:00:00Z").unwrap();
+        let tables = ["virginia_code", "courts"];
+        let chunking = ChunkConfig::new(256, 32).unwrap();
+        write_build_info(
+            &out,
+            built_at,
+            Some(&freshness),
+            Some("ab12"),
+            &tables,
+            chunking,
+        )
+        .unwrap();
+        let info = read_build_info(&out).unwrap();
+        assert_eq!(info[SCRAPED_FROM], "metadata");
+        assert_eq!(info[SOURCE_TABLES], "virginia_code,courts");
+        assert_eq!(info[SOURCE_SHA256], "ab12");
+        assert_eq!(parse_timestamp(&info[BUILT_AT]), Some(built_at));
+        assert_eq!(read_chunking(&out).unwrap(), chunking);
+    }
+}
@@ -0,0 +1,302 @@
+//! `corpus_stats`: statistics about the corpus the UI shows, computed once
+//! per build from the output DB instead of ad hoc by the web app, and served
+//! by `embedding-server` at `/v1/stats`.
+//!
+//! Each statistic is one row, its value JSON. Every build mode
+//! recomputes them after Pass 3, so embedding coverage is current, and
+//! documents are counted by the `dataset` recorded in `document_datasets`.
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Entries kept in the ranked lists (`most_cited`, `largest_chapters`).
+pub const TOP_N: usize = 25;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TitleSections {
+    /// Code title, e.g. `46.2`.
+    pub title: String,
+    pub sections: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CitedSection {
+    pub section: String,
+    /// Distinct sections, opinions and documents citing or referencing it.
+    pub cited_by: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ChapterSections {
+    /// `title:chapter`, e.g. `46.2:8`.
+    pub chapter: String,
+    pub sections: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DatasetDocuments {
+    pub dataset: String,
+    pub documents: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NodeTypeCoverage {
+    pub node_type: String,
+    pub nodes: i64,
+    pub embedded: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CorpusStats {
+    /// RFC 3339.
+    pub computed_at: String,
+    /// Every title, in Code order.
+    pub sections_per_title: Vec<TitleSections>,
+    /// The [`TOP_N`] most-cited sections.
+    pub most_cited: Vec<CitedSection>,
+    /// The [`TOP_N`] chapters with the most sections.
+    pub largest_chapters: Vec<ChapterSections>,
+    pub documents_per_dataset: Vec<DatasetDocuments>,
+    pub embedding_coverage: Vec<NodeTypeCoverage>,
+}
+
+fn rows<T>(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+    row: impl FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params, row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn has_table(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [name],
+        |r| r.get(0),
+    )?)
+}
+
+/// Compute every statistic from `nodes`, `edges`, `embeddings` and
+/// `document_datasets`.
+pub fn compute(conn: &Connection) -> Result<CorpusStats> {
+    let top = TOP_N as i64;
+    let sections_per_title = rows(
+        conn,
+        "SELECT t.source_id, COUNT(DISTINCT s.source_id) FROM nodes t
+         JOIN edges tc ON tc.from_id = t.id AND tc.rel_type = 'contains'
+         JOIN edges cs ON cs.from_id = tc.to_id AND cs.rel_type = 'contains'
+         JOIN nodes s ON s.id = cs.to_id AND s.node_type = 'section'
+         WHERE t.node_type = 'title'
+         GROUP BY t.source_id ORDER BY CAST(t.source_id AS REAL), t.source_id",
+        [],
+        |r| {
+            Ok(TitleSections {
+                title: r.get(0)?,
+                sections: r.get(1)?,
+            })
+        },
+    )?;
+    let most_cited = rows(
+        conn,
+        "SELECT t.source_id, COUNT(DISTINCT f.source || ':' || f.source_id) AS n FROM edges e
+         JOIN nodes t ON t.id = e.to_id AND t.node_type = 'section'
+         JOIN nodes f ON f.id = e.from_id
+         WHERE e.rel_type IN ('cites', 'references')
+         GROUP BY t.source_id ORDER BY n DESC, t.source_id LIMIT ?1",
+        [top],
+        |r| {
+            Ok(CitedSection {
+                section: r.get(0)?,
+                cited_by: r.get(1)?,
+            })
+        },
+    )?;
+    let largest_chapters = rows(
+        conn,
+        "SELECT c.source_id, COUNT(DISTINCT s.source_id) AS n FROM nodes c
+         JOIN edges e ON e.from_id = c.id AND e.rel_type = 'contains'
+         JOIN nodes s ON s.id = e.to_id AND s.node_type = 'section'
+         WHERE c.node_type = 'chapter'
+         GROUP BY c.source_id ORDER BY n DESC, c.source_id LIMIT ?1",
+        [top],
+        |r| {
+            Ok(ChapterSections {
+                chapter: r.get(0)?,
+                sections: r.get(1)?,
+            })
+        },
+    )?;
+    let documents_per_dataset = match has_table(conn, "document_datasets")? {
+        true => rows(
+            conn,
+            "SELECT d.dataset, COUNT(DISTINCT n.source_id) AS n FROM nodes n
+             JOIN document_datasets d ON d.filename = n.source_id
+             WHERE n.source = 'documents'
+             GROUP BY d.dataset ORDER BY n DESC, d.dataset",
+            [],
+            |r| {
+                Ok(DatasetDocuments {
+                    dataset: r.get(0)?,
+                    documents: r.get(1)?,
+                })
+            },
+        )?,
+        false => Vec::new(),
+    };
+    let embedding_coverage = rows(
+        conn,
+        "SELECT n.node_type, COUNT(*), COUNT(e.node_id) FROM nodes n
+         LEFT JOIN embeddings e ON e.node_id = n.id
+         GROUP BY n.node_type ORDER BY n.node_type",
+        [],
+        |r| {
+            Ok(NodeTypeCoverage {
+                node_type: r.get(0)?,
+                nodes: r.get(1)?,
+                embedded: r.get(2)?,
+            })
+        },
+    )?;
+    Ok(CorpusStats {
+        computed_at: chrono::Utc::now().to_rfc3339(),
+        sections_per_title,
+        most_cited,
+        largest_chapters,
+        documents_per_dataset,
+        embedding_coverage,
+    })
+}
+
+/// (Re)compute the statistics and replace the `corpus_stats` rows. Safe to
+/// call again after embeddings change.
+pub fn refresh(conn: &Connection) -> Result<CorpusStats> {
+    let stats = compute(conn)?;
+    let value = serde_json::to_value(&stats)?;
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS corpus_stats (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        DELETE FROM corpus_stats;
+        ",
+    )?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO corpus_stats (key, value) VALUES (?1, ?2)")?;
+        for (key, value) in value.as_object().into_iter().flatten() {
+            stmt.execute([key, &value.to_string()])?;
+        }
+    }
+    tx.commit()?;
+    Ok(stats)
+}
+
+/// The statistics the last build recorded; `None` for DBs built before
+/// `corpus_stats` existed.
+pub fn read(conn: &Connection) -> Result<Option<CorpusStats>> {
+    if !has_table(conn, "corpus_stats")? {
+        return Ok(None);
+    }
+    let mut object = serde_json::Map::new();
+    let mut stmt = conn.prepare("SELECT key, value FROM corpus_stats")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let value: String = row.get(1)?;
+        object.insert(row.get(0)?, serde_json::from_str(&value)?);
+    }
+    if object.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_value(object.into())?))
+}
+
+/// The `dataset` of each document, by filename (its nodes' `source_id`).
+pub fn write_document_datasets<'a>(
+    conn: &Connection,
+    documents: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut written = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO document_datasets (filename, dataset) VALUES (?1, ?2)",
+        )?;
+        for (filename, dataset) in documents {
+            written += stmt.execute([filename, dataset])?;
+        }
+    }
+    tx.commit()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    #[test]
+    fn test_refresh_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("out.db").to_str().unwrap()).unwrap();
+        assert_eq!(read(&conn).unwrap(), None);
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, node_type) VALUES
+                 (1, 'virginia_code', '46.2', 'title'),
+                 (2, 'virginia_code', '46.2:8', 'chapter'),
+                 (3, 'virginia_code', '46.2-862', 'section'),
+                 (4, 'virginia_code', '46.2-862', 'section'),
+                 (5, 'virginia_code', '46.2-868', 'section'),
+                 (6, 'virginia_code', '18.2', 'title'),
+                 (7, 'virginia_code', '18.2:7', 'chapter'),
+                 (8, 'virginia_code', '18.2-266', 'section'),
+                 (9, 'documents', 'opinion.txt', 'manual_chunk'),
+                 (10, 'documents', 'opinion.txt', 'manual_chunk');
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES
+                 (1, 2, 'contains'), (2, 3, 'contains'), (2, 4, 'contains'),
+                 (2, 5, 'contains'), (6, 7, 'contains'), (7, 8, 'contains'),
+                 (5, 3, 'cites'), (9, 3, 'references'), (10, 3, 'references'),
+                 (9, 8, 'references');
+             INSERT INTO embeddings (node_id, embedding) VALUES (3, x''), (9, x'');",
+        )
+        .unwrap();
+        write_document_datasets(&conn, [("opinion.txt", "case-law"), ("gone.txt", "x")]).unwrap();
+
+        let stats = refresh(&conn).unwrap();
+        let titles: Vec<(&str, i64)> = stats
+            .sections_per_title
+            .iter()
+            .map(|t| (t.title.as_str(), t.sections))
+            .collect();
+        assert_eq!(titles, [("18.2", 1), ("46.2", 2)]);
+        let cited: Vec<(&str, i64)> = stats
+            .most_cited
+            .iter()
+            .map(|c| (c.section.as_str(), c.cited_by))
+            .collect();
+        // Two chunks of one document count once
+        assert_eq!(cited, [("46.2-862", 2), ("18.2-266", 1)]);
+        assert_eq!(stats.largest_chapters[0].chapter, "46.2:8");
+        assert_eq!(stats.largest_chapters[0].sections, 2);
+        assert_eq!(
+            stats.documents_per_dataset,
+            [DatasetDocuments {
+                dataset: "case-law".into(),
+                documents: 1
+            }]
+        );
+        let manual = stats
+            .embedding_coverage
+            .iter()
+            .find(|c| c.node_type == "manual_chunk")
+            .unwrap();
+        assert_eq!((manual.nodes, manual.embedded), (2, 1));
+
+        assert_eq!(read(&conn).unwrap(), Some(stats));
+    }
+}
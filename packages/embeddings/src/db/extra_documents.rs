@@ -0,0 +1,272 @@
+//! `--extra-documents`: ad-hoc documents from a folder, embedded alongside
+//! `virginia.db`'s `documents` table.
+//!
+//! Each CSV or JSONL record has a `filename`, an optional `title` and the
+//! `content`. Each plain-text, Markdown or Word (`.docx`) file is one
+//! document, named by its path under the folder and titled by its first
+//! heading; a `.docx` is read as HTML with its headings kept, so chunking
+//! splits at them. The rows go through the same ETL, chunking, node building
+//! and citation extraction as the input's documents. Their ids are negative, in file and
+//! record order, so they never collide with the input's.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use polars::prelude::*;
+use serde::Deserialize;
+
+use crate::db::reader::DocumentRow;
+use crate::text::docx::docx_to_html;
+
+#[derive(Deserialize)]
+struct Record {
+    filename: String,
+    #[serde(default)]
+    title: String,
+    content: String,
+}
+
+/// Extensions read as one document per file.
+const TEXT_EXTENSIONS: &[&str] = &["txt", "md", "markdown", "docx"];
+
+/// The files under `path` with a known extension, sorted, or `path` itself
+/// if it is a file. Hidden files and directories are skipped.
+fn files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read --extra-documents {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let file = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(file);
+            } else if extension(&file).is_some_and(|e| {
+                matches!(e.as_str(), "csv" | "jsonl") || TEXT_EXTENSIONS.contains(&e.as_str())
+            }) {
+                files.push(file);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_ascii_lowercase())
+}
+
+fn read_csv(path: &Path) -> Result<Vec<Record>> {
+    // No schema inference: every column is read as text.
+    let df = CsvReadOptions::default()
+        .with_has_header(true)
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(path.to_path_buf()))?
+        .finish()?;
+    let column = |name: &str| -> Result<Option<Vec<String>>> {
+        let Ok(c) = df.column(name) else {
+            return Ok(None);
+        };
+        Ok(Some(
+            c.str()?
+                .into_iter()
+                .map(|v| v.unwrap_or("").to_string())
+                .collect(),
+        ))
+    };
+    let (Some(filenames), Some(contents)) = (column("filename")?, column("content")?) else {
+        bail!("needs a header with filename and content columns (title is optional)");
+    };
+    let titles = column("title")?.unwrap_or_else(|| vec![String::new(); df.height()]);
+    Ok(filenames
+        .into_iter()
+        .zip(titles)
+        .zip(contents)
+        .map(|((filename, title), content)| Record {
+            filename,
+            title,
+            content,
+        })
+        .collect())
+}
+
+fn read_jsonl(path: &Path) -> Result<Vec<Record>> {
+    let text = std::fs::read_to_string(path)?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("line {}", i + 1)))
+        .collect()
+}
+
+/// A text, Markdown or `.docx` file as one record, named by its path under
+/// `root` (with `/` separators) and titled by its first heading.
+fn read_text(root: &Path, path: &Path) -> Result<Vec<Record>> {
+    let (title, content) = if extension(path).as_deref() == Some("docx") {
+        let docx = docx_to_html(&std::fs::read(path)?)?;
+        (docx.title.unwrap_or_default(), docx.html)
+    } else {
+        let content = std::fs::read_to_string(path)?;
+        (
+            first_heading(&content).unwrap_or_default().to_string(),
+            content,
+        )
+    };
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        // `root` is the file itself
+        _ => Path::new(path.file_name().unwrap_or_default()),
+    };
+    let filename = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok(vec![Record {
+        filename,
+        title,
+        content,
+    }])
+}
+
+/// The first Markdown heading (`# Title`, or a line underlined with `===` or
+/// `---`), else the first non-blank line.
+fn first_heading(text: &str) -> Option<&str> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    for (i, line) in lines.iter().enumerate() {
+        let hashes = line.len() - line.trim_start_matches('#').len();
+        if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+            return Some(line[hashes..].trim().trim_end_matches('#').trim_end());
+        }
+        let underline = lines.get(i + 1).copied().unwrap_or("");
+        if !line.is_empty()
+            && underline.len() >= 3
+            && (underline.chars().all(|c| c == '=') || underline.chars().all(|c| c == '-'))
+        {
+            return Some(line);
+        }
+    }
+    lines.into_iter().find(|l| !l.is_empty())
+}
+
+/// Every document under `path`, as rows of the input's `documents` table with
+/// the file name as their `dataset`. Filenames must be unique.
+pub fn read_extra_documents(path: &Path) -> Result<Vec<DocumentRow>> {
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files(path)? {
+        let records = match extension(&file).as_deref() {
+            Some("csv") => read_csv(&file),
+            Some("jsonl") => read_jsonl(&file),
+            _ => read_text(path, &file),
+        }
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+        let dataset = file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        for record in records {
+            if !seen.insert(record.filename.clone()) {
+                bail!(
+                    "{}: filename {:?} appears more than once in --extra-documents",
+                    file.display(),
+                    record.filename
+                );
+            }
+            rows.push(DocumentRow {
+                id: -(rows.len() as i64) - 1,
+                dataset: dataset.clone(),
+                filename: record.filename,
+                title: record.title,
+                content: record.content,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_extra_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.csv"),
+            "filename,title,content\nmemo.txt,Memo,\"See § 18.2-57,\nthen appeal.\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("b.jsonl"),
+            "{\"filename\": \"faq.html\", \"content\": \"<p>Fees</p>\"}\n\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.pdf"), "ignored").unwrap();
+
+        let rows = read_extra_documents(dir.path()).unwrap();
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.id,
+                    r.dataset.as_str(),
+                    r.filename.as_str(),
+                    r.title.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (-1, "a.csv", "memo.txt", "Memo"),
+                (-2, "b.jsonl", "faq.html", "")
+            ]
+        );
+        assert_eq!(rows[0].content, "See § 18.2-57,\nthen appeal.");
+
+        std::fs::create_dir(dir.path().join("manuals")).unwrap();
+        std::fs::write(
+            dir.path().join("manuals/gdc.md"),
+            "<!-- draft -->\n\n## General District Court ##\n\nFiling fees...\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("manuals/.gdc.md.swp"), "x").unwrap();
+        std::fs::write(dir.path().join("readme.txt"), "\n  Read me first\nbody").unwrap();
+        let rows = read_extra_documents(dir.path()).unwrap();
+        let text: Vec<_> = rows[2..]
+            .iter()
+            .map(|r| (r.filename.as_str(), r.title.as_str()))
+            .collect();
+        assert_eq!(
+            text,
+            [
+                ("manuals/gdc.md", "General District Court"),
+                ("readme.txt", "Read me first")
+            ]
+        );
+        assert_eq!(first_heading("Setext title\n===\n"), Some("Setext title"));
+        let file = read_extra_documents(&dir.path().join("readme.txt")).unwrap();
+        assert_eq!(file[0].filename, "readme.txt");
+
+        std::fs::write(
+            dir.path().join("c.jsonl"),
+            "{\"filename\": \"memo.txt\", \"content\": \"x\"}",
+        )
+        .unwrap();
+        assert!(read_extra_documents(dir.path()).is_err());
+        std::fs::write(dir.path().join("c.jsonl"), "{\"filename\": \"c\"}").unwrap();
+        assert!(read_extra_documents(dir.path()).is_err());
+        std::fs::write(dir.path().join("c.jsonl"), "").unwrap();
+        std::fs::write(dir.path().join("d.csv"), "name,body\nx,y\n").unwrap();
+        assert!(read_extra_documents(dir.path()).is_err());
+    }
+}
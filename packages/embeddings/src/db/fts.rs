@@ -0,0 +1,124 @@
+//! `--fts`: a full-text index over node texts, so search can drop or demote
+//! hits containing a term ("repealed") before any vector is scored.
+//!
+//! `node_fts` is a contentless FTS5 table keyed by node id: it holds the
+//! index, not the text, which the output DB still doesn't store.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+
+/// Rebuild `node_fts` from `texts`, keyed by node id.
+pub fn write_fts(conn: &Connection, texts: &HashMap<i64, String>) -> Result<usize> {
+    conn.execute_batch(
+        "
+        DROP TABLE IF EXISTS node_fts;
+        CREATE VIRTUAL TABLE node_fts USING fts5(text, content='');
+        ",
+    )?;
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO node_fts (rowid, text) VALUES (?1, ?2)")?;
+        for (node_id, text) in texts {
+            stmt.execute(rusqlite::params![node_id, text])?;
+        }
+    }
+    tx.commit()?;
+    Ok(texts.len())
+}
+
+pub fn has_fts(conn: &Connection) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'node_fts')",
+        [],
+        |r| r.get(0),
+    )?)
+}
+
+/// An FTS5 query matching any of `terms`, each as a phrase so punctuation and
+/// FTS operators in a term are taken literally.
+fn any_of(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Nodes whose text contains any of `terms` (case-insensitive, whole words).
+pub fn matching_nodes(conn: &Connection, terms: &[String]) -> Result<HashSet<i64>> {
+    let terms: Vec<String> = terms
+        .iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if terms.is_empty() {
+        return Ok(HashSet::new());
+    }
+    if !has_fts(conn)? {
+        bail!("The graph DB has no full-text index; rebuild it with --fts to exclude terms");
+    }
+    let mut stmt = conn.prepare("SELECT rowid FROM node_fts WHERE node_fts MATCH ?1")?;
+    let rows = stmt.query_map([any_of(&terms)], |r| r.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Up to `limit` nodes matching any word of `text`, best BM25 score first;
+/// for keyword recall alongside vector search. Words are matched whole,
+/// with surrounding punctuation ignored.
+pub fn ranked_matches(conn: &Connection, text: &str, limit: usize) -> Result<Vec<i64>> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_string())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt =
+        conn.prepare("SELECT rowid FROM node_fts WHERE node_fts MATCH ?1 ORDER BY rank LIMIT ?2")?;
+    let rows = stmt.query_map(rusqlite::params![any_of(&words), limit as i64], |r| {
+        r.get(0)
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_nodes() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(matching_nodes(&conn, &["repealed".to_string()]).is_err());
+
+        let texts = HashMap::from([
+            (1, "§ 18.2-57 Assault and battery; penalty".to_string()),
+            (2, "§ 18.2-58 Repealed by Acts 1975, c. 14.".to_string()),
+            (3, "Reckless driving; \"general rule\"".to_string()),
+        ]);
+        assert_eq!(write_fts(&conn, &texts).unwrap(), 3);
+
+        let find = |terms: &[&str]| {
+            let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+            let mut ids: Vec<i64> = matching_nodes(&conn, &terms).unwrap().into_iter().collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(find(&["REPEALED"]), [2]);
+        assert_eq!(find(&["repeal"]), Vec::<i64>::new());
+        assert_eq!(find(&["repealed", "general rule"]), [2, 3]);
+        assert_eq!(find(&["\"general", " "]), [3]);
+        assert_eq!(find(&[]), Vec::<i64>::new());
+
+        // BM25 order; the shorter text wins a tie, words in no text are ignored
+        let ranked = ranked_matches(&conn, "Is it reckless? (penalty)", 10).unwrap();
+        assert_eq!(ranked, [3, 1]);
+        assert!(ranked_matches(&conn, " ?! ", 10).unwrap().is_empty());
+
+        // A rebuild replaces the index.
+        write_fts(&conn, &HashMap::from([(4, "repealed".to_string())])).unwrap();
+        assert_eq!(find(&["repealed"]), [4]);
+    }
+}
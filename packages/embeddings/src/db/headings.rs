@@ -0,0 +1,108 @@
+//! `--title-embeddings`: a second vector per code and constitution section,
+//! computed from its heading alone. Short queries ("reckless driving") match
+//! a heading far better than a chunk of statute text, so search can blend the
+//! two scores.
+//!
+//! `headings` holds the text and `title_embeddings` the vectors, both keyed by
+//! the node id of the section's first chunk, the same layout as `embeddings`.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+/// Replace the rows of `headings`, creating it and `title_embeddings` if
+/// needed, and drop title vectors of nodes that no longer have a heading.
+pub fn write_headings(conn: &Connection, headings: &HashMap<i64, String>) -> Result<usize> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS headings (
+            node_id INTEGER PRIMARY KEY REFERENCES nodes(id),
+            heading TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS title_embeddings (
+            node_id   INTEGER PRIMARY KEY REFERENCES nodes(id),
+            embedding BLOB NOT NULL
+        );
+        DELETE FROM headings;
+        ",
+    )?;
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO headings (node_id, heading) VALUES (?1, ?2)")?;
+        for (node_id, heading) in headings {
+            stmt.execute(rusqlite::params![node_id, heading])?;
+        }
+    }
+    tx.execute(
+        "DELETE FROM title_embeddings WHERE node_id NOT IN (SELECT node_id FROM headings)",
+        [],
+    )?;
+    tx.commit()?;
+    Ok(headings.len())
+}
+
+/// Headings with no title vector yet, in node id order.
+pub fn unembedded(conn: &Connection) -> Result<(Vec<i64>, Vec<String>)> {
+    let mut stmt = conn.prepare(
+        "SELECT h.node_id, h.heading FROM headings h
+         LEFT JOIN title_embeddings e ON e.node_id = h.node_id
+         WHERE e.node_id IS NULL
+         ORDER BY h.node_id",
+    )?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+    Ok(rows
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .unzip())
+}
+
+pub fn write_title_embeddings_batch(
+    conn: &Connection,
+    node_ids: &[i64],
+    embeddings: &[Vec<f32>],
+) -> Result<()> {
+    assert_eq!(node_ids.len(), embeddings.len());
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO title_embeddings (node_id, embedding) VALUES (?1, ?2)",
+        )?;
+        for (node_id, embedding) in node_ids.iter().zip(embeddings) {
+            let bytes: Vec<u8> = embedding.iter().flat_map(|&f| f.to_le_bytes()).collect();
+            insert.execute(rusqlite::params![node_id, bytes])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headings_track_title_embeddings() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE nodes (id INTEGER PRIMARY KEY); INSERT INTO nodes VALUES (1), (2);",
+        )
+        .unwrap();
+        let headings = HashMap::from([
+            (1, "§ 46.2-862 Exceeding speed limit".to_string()),
+            (2, "§ 46.2-852 Reckless driving".to_string()),
+        ]);
+        assert_eq!(write_headings(&conn, &headings).unwrap(), 2);
+        write_title_embeddings_batch(&conn, &[1], &[vec![1.0, 0.0]]).unwrap();
+        let (ids, texts) = unembedded(&conn).unwrap();
+        assert_eq!(ids, [2]);
+        assert_eq!(texts, ["§ 46.2-852 Reckless driving"]);
+
+        // A rebuild without node 1's heading drops its vector.
+        write_headings(&conn, &HashMap::from([(2, "Reckless".to_string())])).unwrap();
+        let vectors: i64 = conn
+            .query_row("SELECT COUNT(*) FROM title_embeddings", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(vectors, 0);
+    }
+}
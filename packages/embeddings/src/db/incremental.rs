@@ -0,0 +1,262 @@
+//! `--incremental` builds: carry embeddings over from the previous output DB
+//! for nodes whose text hasn't changed, so only new or edited rows are embedded.
+//!
+//! Node ids are reassigned on every build, so nodes are matched on
+//! `(source, source_id, chunk_idx)` plus the SHA-256 of their text
+//! (`node_hashes`). The graph itself (ETL, nodes, edges) is always rebuilt in
+//! full; it takes seconds, the embeddings take hours.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+pub fn text_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+pub fn write_node_hashes(conn: &Connection, texts: &HashMap<i64, String>) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt =
+            tx.prepare("INSERT INTO node_hashes (node_id, text_hash) VALUES (?1, ?2)")?;
+        for (node_id, text) in texts {
+            stmt.execute(rusqlite::params![node_id, text_hash(text)])?;
+        }
+    }
+    tx.commit()?;
+    Ok(texts.len())
+}
+
+/// Where the previous build is kept while the new one is written.
+pub fn previous_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".prev");
+    PathBuf::from(name)
+}
+
+/// Move the existing output aside so the new build can be written in its
+/// place. A `.prev` left behind by a failed incremental run is kept in
+/// preference to the (possibly partial) output. Returns `None` when there is
+/// no previous build.
+pub fn stash_previous(output: &Path) -> Result<Option<PathBuf>> {
+    let prev = previous_path(output);
+    if prev.exists() {
+        return Ok(Some(prev));
+    }
+    if !output.exists() {
+        return Ok(None);
+    }
+    std::fs::rename(output, &prev).with_context(|| {
+        format!(
+            "Failed to move {} aside to {}",
+            output.display(),
+            prev.display()
+        )
+    })?;
+    Ok(Some(prev))
+}
+
+/// Remove the stashed previous build and its WAL/SHM files.
+pub fn discard_previous(prev: &Path) -> Result<()> {
+    std::fs::remove_file(prev)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut name = prev.as_os_str().to_owned();
+        name.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(name));
+    }
+    Ok(())
+}
+
+/// The `model_info` keys a vector depends on besides its text: another model,
+/// size, document prefix or truncation embeds the same text differently.
+const EMBEDDING_KEYS: [&str; 4] = ["model_name", "dimensions", "document_prefix", "max_length"];
+
+/// Copy embeddings (and `model_info`) from `prev` for every node whose key and
+/// text hash match, returning the ids that were filled. A previous build
+/// without `node_hashes` or embeddings, or whose `model_info` differs from
+/// this run's (`db::writer::model_info`) in any of [`EMBEDDING_KEYS`],
+/// contributes nothing.
+pub fn reuse_embeddings(
+    conn: &Connection,
+    prev: &Path,
+    model_info: &[(&str, String)],
+) -> Result<HashSet<i64>> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS prev",
+        [prev.to_str().context("Non-UTF-8 path")?],
+    )?;
+    let result = copy_matching(conn, model_info);
+    conn.execute("DETACH DATABASE prev", [])?;
+    result
+}
+
+fn copy_matching(conn: &Connection, model_info: &[(&str, String)]) -> Result<HashSet<i64>> {
+    let has_table = |name: &str| -> Result<bool> {
+        Ok(conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM prev.sqlite_master WHERE type = 'table' AND name = ?1)",
+            [name],
+            |r| r.get(0),
+        )?)
+    };
+    if !has_table("node_hashes")? || !has_table("embeddings")? {
+        return Ok(HashSet::new());
+    }
+    for key in EMBEDDING_KEYS {
+        let prev_value: Option<String> = conn
+            .query_row(
+                "SELECT value FROM prev.model_info WHERE key = ?1",
+                [key],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let value = model_info.iter().find(|(k, _)| *k == key).map(|(_, v)| v);
+        if prev_value.as_ref() != value {
+            info!(
+                key,
+                previous = prev_value.as_deref().unwrap_or("(none)"),
+                "The previous build's model_info differs; re-embedding every node"
+            );
+            return Ok(HashSet::new());
+        }
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT OR REPLACE INTO model_info (key, value) SELECT key, value FROM prev.model_info",
+        [],
+    )?;
+    tx.execute(
+        "INSERT INTO embeddings (node_id, embedding)
+         SELECT n.id, pe.embedding
+         FROM nodes n
+         JOIN node_hashes h ON h.node_id = n.id
+         JOIN prev.nodes pn
+           ON pn.source = n.source AND pn.source_id = n.source_id AND pn.chunk_idx = n.chunk_idx
+         JOIN prev.node_hashes ph ON ph.node_id = pn.id AND ph.text_hash = h.text_hash
+         JOIN prev.embeddings pe ON pe.node_id = pn.id",
+        [],
+    )?;
+    // A reused vector was cut off where the previous run cut it
+    let prev_has_truncated: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('nodes', 'prev') WHERE name = 'truncated'",
+        [],
+        |r| r.get(0),
+    )?;
+    if prev_has_truncated {
+        tx.execute(
+            "UPDATE nodes SET truncated = 1
+             WHERE id IN (SELECT n.id FROM nodes n
+                          JOIN embeddings e ON e.node_id = n.id
+                          JOIN prev.nodes pn
+                            ON pn.source = n.source AND pn.source_id = n.source_id
+                           AND pn.chunk_idx = n.chunk_idx
+                          WHERE pn.truncated = 1)",
+            [],
+        )?;
+    }
+    let reused = {
+        let mut stmt = tx.prepare("SELECT node_id FROM embeddings")?;
+        let ids = stmt.query_map([], |r| r.get(0))?;
+        ids.collect::<rusqlite::Result<_>>()?
+    };
+    tx.commit()?;
+    Ok(reused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    fn model_info(prefix: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("model_name", "test".into()),
+            ("dimensions", "1".into()),
+            ("max_length", "512".into()),
+            ("document_prefix", prefix.into()),
+        ]
+    }
+
+    fn build(path: &Path, texts: &[(&str, &str)], embed: bool) -> Connection {
+        let conn = create_output_db(path.to_str().unwrap()).unwrap();
+        let mut by_id = HashMap::new();
+        for (i, (source_id, text)) in texts.iter().enumerate() {
+            let id = i as i64 + 1;
+            conn.execute(
+                "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type)
+                 VALUES (?1, 'virginia_code', ?2, 0, 'section')",
+                rusqlite::params![id, source_id],
+            )
+            .unwrap();
+            if embed {
+                for (key, value) in model_info("doc: ") {
+                    conn.execute(
+                        "INSERT OR REPLACE INTO model_info (key, value) VALUES (?1, ?2)",
+                        rusqlite::params![key, value],
+                    )
+                    .unwrap();
+                }
+                conn.execute(
+                    "INSERT INTO embeddings (node_id, embedding) VALUES (?1, ?2)",
+                    rusqlite::params![id, vec![id as u8; 4]],
+                )
+                .unwrap();
+            }
+            by_id.insert(id, text.to_string());
+        }
+        write_node_hashes(&conn, &by_id).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_reuses_only_unchanged_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("graph.sqlite.db");
+        let previous = build(
+            &output,
+            &[("1-1", "one"), ("1-2", "two"), ("1-3", "three")],
+            true,
+        );
+        crate::db::writer::mark_truncated(&previous, &[2, 3]).unwrap();
+        drop(previous);
+
+        let prev = stash_previous(&output).unwrap().unwrap();
+        assert!(!output.exists());
+        // Ids shift (1-1 dropped) and 1-3's text changes.
+        let conn = build(&output, &[("1-2", "two"), ("1-3", "THREE"), ("1-4", "four")], false);
+
+        // Another model, or the same one with another document prefix, embeds
+        // the same text differently.
+        let mut other = model_info("doc: ");
+        other[0].1 = "other".into();
+        assert!(reuse_embeddings(&conn, &prev, &other).unwrap().is_empty());
+        assert!(reuse_embeddings(&conn, &prev, &model_info("")).unwrap().is_empty());
+        let reused = reuse_embeddings(&conn, &prev, &model_info("doc: ")).unwrap();
+        assert_eq!(reused, HashSet::from([1]));
+        let blob: Vec<u8> = conn
+            .query_row("SELECT embedding FROM embeddings WHERE node_id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(blob, vec![2u8; 4], "1-2's old embedding (old id 2)");
+        // 1-2's vector keeps its truncated flag; 1-3 is re-embedded.
+        let truncated: Vec<i64> = conn
+            .prepare("SELECT id FROM nodes WHERE truncated = 1")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(truncated, [1]);
+
+        // A leftover .prev from a failed run wins over the partial output.
+        assert_eq!(stash_previous(&output).unwrap(), Some(prev.clone()));
+        discard_previous(&prev).unwrap();
+        assert!(!prev.exists());
+    }
+}
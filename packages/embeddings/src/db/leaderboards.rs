@@ -0,0 +1,150 @@
+//! `section_leaderboards`: the most-cited sections and the sections nothing
+//! cites, over the section-level view. Rebuilt after `section_nodes` /
+//! `section_edges` at the end of every run.
+//!
+//! The top of the list feeds "frequently referenced statutes"; a long or
+//! suddenly growing orphan list usually means citation extraction missed a
+//! form of reference.
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// Sections kept on the `most_cited` board.
+pub const TOP_N: usize = 100;
+
+/// Section node types that can be cited.
+const SECTION_TYPES: &str = "'section', 'constitution_section', 'federal_section'";
+
+/// Edge types that count as a citation.
+const CITATION_RELS: &str = "'cites', 'references'";
+
+/// A section on a board, as the build report lists it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RankedSection {
+    pub source: String,
+    pub section: String,
+    /// Distinct sections, opinions and documents citing it.
+    pub cited_by: i64,
+}
+
+pub struct Leaderboards {
+    pub most_cited: Vec<RankedSection>,
+    /// Every uncited section, in `(source, section)` order.
+    pub orphans: Vec<RankedSection>,
+}
+
+/// (Re)build `section_leaderboards` from `section_nodes` / `section_edges`:
+/// the [`TOP_N`] most-cited sections (`board = 'most_cited'`) and every
+/// section never cited by another (`board = 'orphan'`), each ranked from 1.
+pub fn materialize_leaderboards(conn: &Connection) -> Result<Leaderboards> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(&format!(
+        "
+        DROP TABLE IF EXISTS section_leaderboards;
+
+        CREATE TABLE section_leaderboards (
+            board     TEXT NOT NULL,
+            rank      INTEGER NOT NULL,
+            node_id   INTEGER NOT NULL REFERENCES section_nodes(id),
+            source    TEXT NOT NULL,
+            source_id TEXT NOT NULL,
+            cited_by  INTEGER NOT NULL,
+            PRIMARY KEY (board, rank)
+        );
+
+        CREATE TEMP TABLE citation_counts AS
+            SELECT n.id, n.source, n.source_id, COUNT(DISTINCT e.from_id) AS cited_by
+            FROM section_nodes n
+            LEFT JOIN section_edges e ON e.to_id = n.id AND e.rel_type IN ({CITATION_RELS})
+            WHERE n.node_type IN ({SECTION_TYPES})
+            GROUP BY n.id;
+
+        INSERT INTO section_leaderboards (board, rank, node_id, source, source_id, cited_by)
+            SELECT 'most_cited', ROW_NUMBER() OVER (ORDER BY cited_by DESC, source, source_id),
+                   id, source, source_id, cited_by
+            FROM citation_counts WHERE cited_by > 0
+            ORDER BY cited_by DESC, source, source_id
+            LIMIT {TOP_N};
+
+        INSERT INTO section_leaderboards (board, rank, node_id, source, source_id, cited_by)
+            SELECT 'orphan', ROW_NUMBER() OVER (ORDER BY source, source_id),
+                   id, source, source_id, 0
+            FROM citation_counts WHERE cited_by = 0;
+
+        DROP TABLE temp.citation_counts;
+        "
+    ))?;
+    let board = |name: &str| -> Result<Vec<RankedSection>> {
+        let mut stmt = tx.prepare(
+            "SELECT source, source_id, cited_by FROM section_leaderboards
+             WHERE board = ?1 ORDER BY rank",
+        )?;
+        let rows = stmt.query_map([name], |r| {
+            Ok(RankedSection {
+                source: r.get(0)?,
+                section: r.get(1)?,
+                cited_by: r.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    };
+    let leaderboards = Leaderboards {
+        most_cited: board("most_cited")?,
+        orphans: board("orphan")?,
+    };
+    tx.commit()?;
+    Ok(leaderboards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sections::materialize_sections;
+    use crate::db::writer::create_output_db;
+
+    #[test]
+    fn test_materialize_leaderboards() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("out.db").to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, node_type) VALUES
+                 (1, 'virginia_code', '1-1', 'section'),
+                 (2, 'virginia_code', '1-2', 'section'),
+                 (3, 'virginia_code', '1-2', 'section'),
+                 (4, 'virginia_code', '1-3', 'section'),
+                 (5, 'virginia_code', '1-4', 'section'),
+                 (6, 'documents', 'brief.txt', 'manual_chunk'),
+                 (7, 'virginia_code', '1', 'title');
+             INSERT INTO edges (from_id, to_id, rel_type) VALUES
+                 (1, 4, 'cites'), (6, 4, 'references'), (6, 1, 'references'),
+                 (4, 1, 'cites'), (1, 2, 'cites'), (3, 2, 'cites'),
+                 (7, 5, 'contains');",
+        )
+        .unwrap();
+        materialize_sections(&conn).unwrap();
+
+        let boards = materialize_leaderboards(&conn).unwrap();
+        let ranked = |sections: &[RankedSection]| -> Vec<(String, i64)> {
+            sections
+                .iter()
+                .map(|s| (s.section.clone(), s.cited_by))
+                .collect()
+        };
+        // A self-citation (1-2's chunks) and `contains` don't count.
+        assert_eq!(
+            ranked(&boards.most_cited),
+            [("1-1".into(), 2), ("1-3".into(), 2), ("1-2".into(), 1)]
+        );
+        assert_eq!(ranked(&boards.orphans), [("1-4".into(), 0)]);
+
+        let rank: i64 = conn
+            .query_row(
+                "SELECT rank FROM section_leaderboards WHERE board = 'most_cited' AND source_id = '1-2'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(rank, 3);
+    }
+}
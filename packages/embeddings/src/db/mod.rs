@@ -1,2 +1,21 @@
+//! The input (`reader`) and output (`writer`) databases, `--extra-documents`
+//! files, the bookkeeping tables behind `--incremental`, `--resume`,
+//! `--title-embeddings` and the section-level view, the section leaderboards,
+//! the `--fts` term index, `build_info`, `corpus_stats`, `source_hashes`,
+//! `section_history`, `--raw-texts`, and the `--max-memory` text spill.
+
+pub mod build_info;
+pub mod corpus_stats;
+pub mod extra_documents;
+pub mod fts;
+pub mod headings;
+pub mod incremental;
+pub mod leaderboards;
+pub mod raw_texts;
 pub mod reader;
+pub mod resume;
+pub mod section_history;
+pub mod sections;
+pub mod source_hashes;
+pub mod spill;
 pub mod writer;
@@ -0,0 +1,155 @@
+//! `--raw-texts`: each node's source text as read (after scrubbing, before
+//! ETL), so a bad chunk can be traced back to the HTML it was cleaned from.
+//!
+//! `raw_texts` is keyed by node id and points back at the input row by its
+//! `id`. Every chunk of a row shares its text; a node whose key several rows
+//! share (e.g. deduplicated code sections) gets one row per source row.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::db::reader::SourceRow;
+use crate::db::source_hashes::SourceKey;
+use crate::graph::nodes::Node;
+
+/// One source row's text, under the key its nodes get.
+#[derive(Debug, Clone)]
+pub struct RawText {
+    pub source: &'static str,
+    pub source_id: String,
+    pub row_id: i64,
+    pub text: String,
+}
+
+/// The raw text of every row of `rows` that has a key.
+pub fn raw_texts<T: SourceKey + SourceRow>(rows: &[T]) -> Vec<RawText> {
+    rows.iter()
+        .filter_map(|row| {
+            let source_id = row.source_id();
+            (!source_id.is_empty()).then(|| RawText {
+                source: T::SOURCE,
+                source_id,
+                row_id: row.id(),
+                text: row.raw_text(),
+            })
+        })
+        .collect()
+}
+
+/// Replace the rows of `raw_texts`, creating it if needed: one per
+/// non-synthetic node and source row behind its key. Returns the rows
+/// written.
+pub fn write_raw_texts(conn: &Connection, nodes: &[Node], raw: &[RawText]) -> Result<usize> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS raw_texts (
+            node_id       INTEGER NOT NULL REFERENCES nodes(id),
+            source_row_id INTEGER NOT NULL,
+            raw_text      TEXT NOT NULL,
+            PRIMARY KEY (node_id, source_row_id)
+        );
+        DELETE FROM raw_texts;
+        ",
+    )?;
+    let mut by_key: HashMap<(&str, &str), Vec<&RawText>> = HashMap::new();
+    for r in raw {
+        by_key
+            .entry((r.source, r.source_id.as_str()))
+            .or_default()
+            .push(r);
+    }
+    let mut written = 0;
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO raw_texts (node_id, source_row_id, raw_text)
+             VALUES (?1, ?2, ?3)",
+        )?;
+        for node in nodes.iter().filter(|n| !n.synthetic) {
+            let Some(rows) = by_key.get(&(node.source.as_str(), node.source_id.as_str())) else {
+                continue;
+            };
+            for row in rows {
+                written += stmt.execute(rusqlite::params![node.id, row.row_id, row.text])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::reader::VirginiaCodeRow;
+    use crate::db::writer::{create_output_db, write_nodes};
+
+    fn section(id: i64, section: &str, body: &str) -> VirginiaCodeRow {
+        VirginiaCodeRow {
+            id,
+            title_num: "1".into(),
+            title_name: "General Provisions".into(),
+            chapter_num: "1".into(),
+            chapter_name: "Definitions".into(),
+            section: section.into(),
+            title: "Heading".into(),
+            body: body.into(),
+        }
+    }
+
+    fn node(id: i64, source_id: &str, chunk_idx: i64, synthetic: bool) -> Node {
+        Node {
+            id,
+            source: "virginia_code".into(),
+            source_id: source_id.into(),
+            chunk_idx,
+            node_type: if synthetic { "title" } else { "section" }.into(),
+            synthetic,
+            status: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_write_raw_texts() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("out.db").to_str().unwrap()).unwrap();
+
+        let raw = raw_texts(&[
+            section(10, "1-1", "<p>One</p>"),
+            section(11, "1-2", "<p>Two</p>"),
+            section(12, "1-2", "<p>Two again</p>"),
+            section(13, "", "<p>No section</p>"),
+        ]);
+        assert_eq!(raw.len(), 3);
+        let nodes = [
+            node(1, "1", 0, true),
+            node(2, "1-1", 0, false),
+            node(3, "1-1", 1, false),
+            node(4, "1-2", 0, false),
+        ];
+        write_nodes(&conn, &nodes).unwrap();
+        assert_eq!(write_raw_texts(&conn, &nodes, &raw).unwrap(), 4);
+
+        let rows: Vec<(i64, i64, String)> = conn
+            .prepare("SELECT node_id, source_row_id, raw_text FROM raw_texts ORDER BY node_id, source_row_id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (2, 10, "<p>One</p>".into()),
+                (3, 10, "<p>One</p>".into()),
+                (4, 11, "<p>Two</p>".into()),
+                (4, 12, "<p>Two again</p>".into()),
+            ]
+        );
+
+        // Rewriting replaces rather than appends.
+        assert_eq!(write_raw_texts(&conn, &nodes[..2], &raw).unwrap(), 1);
+    }
+}
@@ -0,0 +1,70 @@
+//! Source tables read from PostgreSQL, for teams that keep the scraped corpus
+//! there. The query is the SQLite one with casts (see `Dialect`), run through
+//! a portal so only one batch is fetched at a time.
+
+use anyhow::{Context, Result};
+use postgres::{Client, NoTls};
+
+use super::{Batcher, SourceRow, SourceValues};
+
+impl SourceValues for postgres::Row {
+    fn int(&self, idx: usize) -> Result<i64> {
+        Ok(self.try_get(idx)?)
+    }
+
+    fn text(&self, idx: usize) -> Result<String> {
+        Ok(self.try_get(idx)?)
+    }
+}
+
+pub(super) fn read<T: SourceRow>(
+    url: &str,
+    table: &str,
+    sql: &str,
+    out: &mut Batcher<T>,
+) -> Result<()> {
+    let mut client = Client::connect(url, NoTls).context("Failed to connect to PostgreSQL")?;
+    if !table_exists(&mut client, table)? {
+        return Ok(());
+    }
+    let mut tx = client.transaction()?;
+    let portal = tx.bind(sql, &[])?;
+    let fetch = i32::try_from(out.size).unwrap_or(i32::MAX);
+    loop {
+        let rows = tx.query_portal(&portal, fetch)?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        for row in &rows {
+            if !out.read(row) {
+                return Ok(()); // the consumer hung up
+            }
+        }
+    }
+}
+
+fn table_exists(client: &mut Client, table: &str) -> Result<bool> {
+    Ok(client
+        .query_one("SELECT to_regclass(quote_ident($1)) IS NOT NULL", &[&table])?
+        .get(0))
+}
+
+pub(super) fn tables_exist(url: &str, tables: &[String]) -> Result<Vec<bool>> {
+    let mut client = Client::connect(url, NoTls).context("Failed to connect to PostgreSQL")?;
+    tables
+        .iter()
+        .map(|t| table_exists(&mut client, t))
+        .collect()
+}
+
+pub(super) fn read_metadata(url: &str, key: &str) -> Result<Option<String>> {
+    let mut client = Client::connect(url, NoTls).context("Failed to connect to PostgreSQL")?;
+    let exists: bool = client
+        .query_one("SELECT to_regclass('metadata') IS NOT NULL", &[])?
+        .get(0);
+    if !exists {
+        return Ok(None);
+    }
+    let row = client.query_opt("SELECT value::text FROM metadata WHERE key = $1", &[&key])?;
+    Ok(row.and_then(|r| r.get(0)))
+}
@@ -0,0 +1,208 @@
+//! `--resume`: finish a Pass 3 that died partway.
+//!
+//! The output DB keeps its `pending_embeddings` work list, so only the texts
+//! for nodes still marked `pending` are needed. They are rebuilt from the
+//! input DB and checked against `node_hashes`, so resuming against a changed
+//! input fails instead of mixing embeddings from two different builds.
+//!
+//! `build-embeddings` uses the same text matching to embed every node of a
+//! graph written by `build-graph`.
+//!
+//! A pass stopped by Ctrl-C also leaves `model_info.interrupted_at`, cleared
+//! once a resume has nothing left pending.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+
+use crate::db::incremental::text_hash;
+
+pub struct PendingNode {
+    pub id: i64,
+    pub source: String,
+    pub source_id: String,
+    pub chunk_idx: i64,
+    pub text_hash: Option<String>,
+}
+
+/// `(id, source, source_id, chunk_idx, text_hash)` row to a [`PendingNode`].
+fn node_row(r: &rusqlite::Row) -> rusqlite::Result<PendingNode> {
+    Ok(PendingNode {
+        id: r.get(0)?,
+        source: r.get(1)?,
+        source_id: r.get(2)?,
+        chunk_idx: r.get(3)?,
+        text_hash: r.get(4)?,
+    })
+}
+
+/// Nodes still waiting for an embedding. Fails if the DB never started Pass 3.
+pub fn pending_nodes(conn: &Connection) -> Result<Vec<PendingNode>> {
+    let has_table: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'pending_embeddings')",
+        [],
+        |r| r.get(0),
+    )?;
+    if !has_table {
+        bail!("Output has no pending_embeddings table; there is no embedding pass to resume");
+    }
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.source, n.source_id, n.chunk_idx, h.text_hash
+         FROM pending_embeddings p
+         JOIN nodes n ON n.id = p.node_id
+         LEFT JOIN node_hashes h ON h.node_id = p.node_id
+         WHERE p.status = 'pending'
+         ORDER BY n.id",
+    )?;
+    let nodes = stmt
+        .query_map([], node_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(nodes)
+}
+
+/// Every node with a text (so a `node_hashes` row), for `build-embeddings`
+/// to embed from scratch.
+pub fn embeddable_nodes(conn: &Connection) -> Result<Vec<PendingNode>> {
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.source, n.source_id, n.chunk_idx, h.text_hash
+         FROM nodes n
+         JOIN node_hashes h ON h.node_id = n.id
+         ORDER BY n.id",
+    )?;
+    let nodes = stmt
+        .query_map([], node_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(nodes)
+}
+
+const INTERRUPTED_KEY: &str = "interrupted_at";
+
+/// Record that Pass 3 was stopped early (Ctrl-C) with work still pending.
+pub fn mark_interrupted(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO model_info (key, value) VALUES (?1, ?2)",
+        rusqlite::params![INTERRUPTED_KEY, chrono::Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Drop the interrupted marker once a resume has nothing left pending.
+pub fn clear_interrupted(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM model_info WHERE key = ?1", [INTERRUPTED_KEY])?;
+    Ok(())
+}
+
+/// Pair each pending node with its rebuilt text, `(node_ids, texts)`. Where
+/// several texts share a node's key, the one matching its `node_hashes` row
+/// is used.
+pub fn pending_texts(
+    pending: &[PendingNode],
+    texts: &HashMap<(String, String, i64), Vec<String>>,
+) -> Result<(Vec<i64>, Vec<String>)> {
+    let mut ids = Vec::with_capacity(pending.len());
+    let mut out = Vec::with_capacity(pending.len());
+    for node in pending {
+        let key = (node.source.clone(), node.source_id.clone(), node.chunk_idx);
+        let candidates = texts.get(&key).map(Vec::as_slice).unwrap_or_default();
+        let text = match node.text_hash {
+            Some(ref expected) => candidates.iter().find(|t| text_hash(t) == *expected),
+            None => candidates.last(),
+        };
+        let Some(text) = text else {
+            if candidates.is_empty() {
+                bail!(
+                    "Input has no text for pending node {} ({} {} chunk {}); it changed since the graph was built",
+                    node.id,
+                    node.source,
+                    node.source_id,
+                    node.chunk_idx
+                );
+            }
+            bail!(
+                "Text for {} {} chunk {} changed since the graph was built; rebuild the graph",
+                node.source,
+                node.source_id,
+                node.chunk_idx
+            );
+        };
+        ids.push(node.id);
+        out.push(text.clone());
+    }
+    Ok((ids, out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::{
+        create_output_db, mark_pending, sync_embedding_failures, write_embeddings_batch,
+    };
+
+    #[test]
+    fn test_resume_picks_up_pending_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.sqlite.db");
+        let conn = create_output_db(path.to_str().unwrap()).unwrap();
+        assert!(pending_nodes(&conn).is_err());
+
+        for (id, sid, text) in [(1, "1-1", "one"), (2, "1-2", "two")] {
+            conn.execute(
+                "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type)
+                 VALUES (?1, 'virginia_code', ?2, 0, 'section')",
+                rusqlite::params![id, sid],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO node_hashes (node_id, text_hash) VALUES (?1, ?2)",
+                rusqlite::params![id, text_hash(text)],
+            )
+            .unwrap();
+        }
+        mark_pending(&conn, &[1, 2]).unwrap();
+        write_embeddings_batch(&conn, &[1], &[vec![0.5, 0.5]]).unwrap();
+        // A node the model failed on twice stays pending.
+        let failure = [(2, "model error".to_string())];
+        assert_eq!(sync_embedding_failures(&conn, &failure).unwrap(), 1);
+        assert_eq!(sync_embedding_failures(&conn, &failure).unwrap(), 1);
+        let attempts: i64 = conn
+            .query_row("SELECT attempts FROM embedding_failures WHERE node_id = 2", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(attempts, 2);
+
+        let pending = pending_nodes(&conn).unwrap();
+        assert_eq!(pending.iter().map(|n| n.id).collect::<Vec<_>>(), vec![2]);
+        let all = embeddable_nodes(&conn).unwrap();
+        assert_eq!(all.iter().map(|n| n.id).collect::<Vec<_>>(), vec![1, 2]);
+
+        let key = ("virginia_code".to_string(), "1-2".to_string(), 0);
+        // Another node's text under the same key is skipped for the one
+        // whose hash matches.
+        let texts = HashMap::from([(key.clone(), vec!["two".to_string(), "deux".to_string()])]);
+        assert_eq!(
+            pending_texts(&pending, &texts).unwrap(),
+            (vec![2], vec!["two".to_string()])
+        );
+        let changed = HashMap::from([(key, vec!["TWO".to_string()])]);
+        assert!(pending_texts(&pending, &changed).is_err());
+        assert!(pending_texts(&pending, &HashMap::new()).is_err());
+
+        write_embeddings_batch(&conn, &[2], &[vec![0.5, 0.5]]).unwrap();
+        assert_eq!(sync_embedding_failures(&conn, &[]).unwrap(), 0);
+
+        let interrupted = |conn: &Connection| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM model_info WHERE key = 'interrupted_at'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap()
+        };
+        mark_interrupted(&conn).unwrap();
+        assert_eq!(interrupted(&conn), 1);
+        clear_interrupted(&conn).unwrap();
+        assert_eq!(interrupted(&conn), 0);
+    }
+}
@@ -0,0 +1,89 @@
+//! `section_history`: the enacting and amending acts each code section's
+//! history parenthetical lists, one row per act chapter, and the
+//! `nodes.enacted_year` / `nodes.amended_year` derived from it for temporal
+//! filtering ("sections amended since 2020").
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::etl::history::Enactment;
+
+/// Write `history` and set the years of every chunk of each section's nodes,
+/// which must already be written. Returns the rows written.
+pub fn write_section_history(
+    conn: &Connection,
+    history: &BTreeMap<String, Vec<Enactment>>,
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut written = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO section_history (section, seq, year, session, chapter)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (section, enactments) in history {
+            for e in enactments {
+                stmt.execute(rusqlite::params![
+                    section, e.seq, e.year, e.session, e.chapter
+                ])?;
+                written += 1;
+            }
+        }
+    }
+    tx.execute_batch(
+        "
+        UPDATE nodes SET
+            enacted_year = (SELECT MIN(year) FROM section_history h WHERE h.section = nodes.source_id),
+            amended_year = (SELECT MAX(year) FROM section_history h WHERE h.section = nodes.source_id)
+        WHERE source = 'virginia_code' AND node_type = 'section';
+        ",
+    )?;
+    tx.commit()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+    use crate::etl::history::parse_history;
+
+    #[test]
+    fn test_write_section_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("out.db").to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type) VALUES
+                 (1, 'virginia_code', '1-1', 0, 'section'),
+                 (2, 'virginia_code', '1-1', 1, 'section'),
+                 (3, 'virginia_code', '1-2', 0, 'section'),
+                 (4, 'acts', '2020:1', 0, 'act');",
+        )
+        .unwrap();
+
+        let history = BTreeMap::from([(
+            "1-1".to_string(),
+            parse_history("Code 1950, § 1-1; 1994, c. 432; 2020, cc. 1, 2"),
+        )]);
+        assert_eq!(write_section_history(&conn, &history).unwrap(), 4);
+
+        let years: Vec<(i64, Option<i64>, Option<i64>)> = conn
+            .prepare("SELECT id, enacted_year, amended_year FROM nodes ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            years,
+            [
+                (1, Some(1950), Some(2020)),
+                (2, Some(1950), Some(2020)),
+                (3, None, None),
+                (4, None, None),
+            ]
+        );
+    }
+}
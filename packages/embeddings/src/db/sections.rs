@@ -0,0 +1,201 @@
+//! Section-granularity view over the chunk-level graph.
+//!
+//! `section_nodes` has one row per `(source, source_id)`, keyed by the id of
+//! its first chunk so it still joins against `nodes`. `section_edges` is the
+//! union of the chunk edges re-pointed at those ids, without self-loops.
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+pub struct SectionCounts {
+    pub nodes: usize,
+    pub edges: usize,
+    pub embeddings: usize,
+}
+
+/// (Re)build `section_nodes` / `section_edges` from `nodes`, `edges` and
+/// `embeddings`. Safe to call again after embeddings change.
+pub fn materialize_sections(conn: &Connection) -> Result<SectionCounts> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        DROP TABLE IF EXISTS section_edges;
+        DROP TABLE IF EXISTS section_nodes;
+
+        CREATE TABLE section_nodes (
+            id          INTEGER PRIMARY KEY REFERENCES nodes(id),
+            source      TEXT NOT NULL,
+            source_id   TEXT NOT NULL,
+            node_type   TEXT NOT NULL,
+            chunk_count INTEGER NOT NULL,
+            embedding   BLOB
+        );
+
+        CREATE TABLE section_edges (
+            from_id  INTEGER NOT NULL REFERENCES section_nodes(id),
+            to_id    INTEGER NOT NULL REFERENCES section_nodes(id),
+            rel_type TEXT NOT NULL,
+            weight   REAL,
+            PRIMARY KEY (from_id, to_id, rel_type)
+        );
+
+        CREATE TEMP TABLE section_map AS
+            SELECT n.id AS node_id, s.section_id
+            FROM nodes n
+            JOIN (SELECT source, source_id, MIN(id) AS section_id
+                  FROM nodes GROUP BY source, source_id) s
+              ON s.source = n.source AND s.source_id = n.source_id;
+        CREATE INDEX temp.idx_section_map ON section_map(node_id);
+
+        INSERT INTO section_nodes (id, source, source_id, node_type, chunk_count)
+            SELECT MIN(id), source, source_id, node_type, COUNT(*)
+            FROM nodes GROUP BY source, source_id;
+
+        INSERT INTO section_edges (from_id, to_id, rel_type, weight)
+            SELECT f.section_id, t.section_id, e.rel_type, SUM(e.weight)
+            FROM edges e
+            JOIN section_map f ON f.node_id = e.from_id
+            JOIN section_map t ON t.node_id = e.to_id
+            WHERE f.section_id != t.section_id
+            GROUP BY f.section_id, t.section_id, e.rel_type;
+
+        CREATE INDEX idx_section_nodes_source ON section_nodes(source, source_id);
+        CREATE INDEX idx_section_edges_to ON section_edges(to_id, rel_type);
+        ",
+    )?;
+
+    let embeddings = average_embeddings(&tx)?;
+    tx.execute_batch("DROP TABLE temp.section_map;")?;
+
+    let nodes: usize = tx.query_row("SELECT COUNT(*) FROM section_nodes", [], |r| r.get(0))?;
+    let edges: usize = tx.query_row("SELECT COUNT(*) FROM section_edges", [], |r| r.get(0))?;
+    tx.commit()?;
+    Ok(SectionCounts {
+        nodes,
+        edges,
+        embeddings,
+    })
+}
+
+/// Mean of each section's chunk embeddings, L2-normalized so section vectors
+/// can be compared with cosine / dot product like chunk vectors.
+fn average_embeddings(conn: &Connection) -> Result<usize> {
+    let mut read = conn.prepare(
+        "SELECT m.section_id, e.embedding FROM embeddings e
+         JOIN section_map m ON m.node_id = e.node_id
+         ORDER BY m.section_id",
+    )?;
+    let mut write = conn.prepare("UPDATE section_nodes SET embedding = ?2 WHERE id = ?1")?;
+
+    let mut written = 0;
+    let mut current: Option<(i64, Vec<f32>)> = None;
+    let mut rows = read.query([])?;
+    while let Some(row) = rows.next()? {
+        let section_id: i64 = row.get(0)?;
+        let blob: Vec<u8> = row.get(1)?;
+        let vector = blob
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+        match current {
+            Some((id, ref mut sum)) if id == section_id => {
+                for (s, v) in sum.iter_mut().zip(vector) {
+                    *s += v;
+                }
+            }
+            _ => {
+                if let Some((id, sum)) = current.take() {
+                    write_normalized(&mut write, id, &sum)?;
+                    written += 1;
+                }
+                current = Some((section_id, vector.collect()));
+            }
+        }
+    }
+    if let Some((id, sum)) = current {
+        write_normalized(&mut write, id, &sum)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn write_normalized(stmt: &mut rusqlite::Statement, id: i64, sum: &[f32]) -> Result<()> {
+    let norm = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let bytes: Vec<u8> = sum
+        .iter()
+        .flat_map(|&x| (if norm > 0.0 { x / norm } else { x }).to_le_bytes())
+        .collect();
+    stmt.execute(rusqlite::params![id, bytes])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer;
+    use crate::graph::edges::Edge;
+    use crate::graph::nodes::Node;
+
+    fn node(id: i64, source_id: &str, chunk_idx: i64) -> Node {
+        Node {
+            id,
+            source: "virginia_code".into(),
+            source_id: source_id.into(),
+            chunk_idx,
+            node_type: "section".into(),
+            synthetic: false,
+            status: Default::default(),
+        }
+    }
+
+    fn blob(v: &[f32]) -> Vec<u8> {
+        v.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_materialize_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.db");
+        let conn = writer::create_output_db(path.to_str().unwrap()).unwrap();
+
+        // Section A has chunks 1 and 2, section B is node 3.
+        writer::write_nodes(&conn, &[node(1, "A", 0), node(2, "A", 1), node(3, "B", 0)]).unwrap();
+        let edge = |from_id, to_id, weight| Edge {
+            from_id,
+            to_id,
+            rel_type: "cites".into(),
+            weight,
+        };
+        writer::write_edges(
+            &conn,
+            &[edge(1, 3, Some(1.0)), edge(2, 3, Some(2.0)), edge(1, 2, Some(1.0))],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO embeddings VALUES (1, ?1)", [blob(&[1.0, 0.0])])
+            .unwrap();
+        conn.execute("INSERT INTO embeddings VALUES (2, ?1)", [blob(&[0.0, 1.0])])
+            .unwrap();
+
+        materialize_sections(&conn).unwrap();
+        // Rebuilding replaces the previous view.
+        let counts = materialize_sections(&conn).unwrap();
+        assert_eq!((counts.nodes, counts.edges, counts.embeddings), (2, 1, 1));
+
+        let (from, to, weight): (i64, i64, f64) = conn
+            .query_row("SELECT from_id, to_id, weight FROM section_edges", [], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((from, to, weight), (1, 3, 3.0));
+
+        let chunks: i64 = conn
+            .query_row("SELECT chunk_count FROM section_nodes WHERE id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(chunks, 2);
+        let avg: Vec<u8> = conn
+            .query_row("SELECT embedding FROM section_nodes WHERE id = 1", [], |r| r.get(0))
+            .unwrap();
+        let x = f32::from_le_bytes([avg[0], avg[1], avg[2], avg[3]]);
+        assert!((x - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+}
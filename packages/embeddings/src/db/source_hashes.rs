@@ -0,0 +1,396 @@
+//! `source_hashes`: a SHA-256 of each source row as read, before scrubbing
+//! and ETL, keyed by the `(source, source_id)` its nodes get. Comparing two
+//! builds' hashes tells an edited row from one whose text only changed
+//! because cleaning or chunking did.
+//!
+//! Rows sharing a key (e.g. duplicate code sections) are hashed together in
+//! row id order; rows without a key, which never become nodes, are skipped.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+
+use crate::db::reader::{
+    ActRow, AuthorityRow, ConstitutionRow, CourtRow, DocumentRow, FederalRow, PopularNameRow,
+    SourceRow, VirginiaCodeRow,
+};
+use crate::graph::nodes::{act_key, federal_key};
+
+/// A source row type's node key and raw content.
+pub trait SourceKey {
+    /// `Node::source` of the row's nodes.
+    const SOURCE: &'static str;
+
+    /// `Node::source_id` of the row's nodes; empty when it has none.
+    fn source_id(&self) -> String;
+
+    /// Names of the columns [`SourceKey::fields`] returns, in order.
+    const FIELD_NAMES: &'static [&'static str];
+
+    /// Every column as read, `id` excluded, so a renumbered row hashes the
+    /// same.
+    fn fields(&self) -> Vec<String>;
+
+    /// The text the row's `clean_text` is built from, for `--raw-texts`.
+    fn raw_text(&self) -> String;
+}
+
+/// The hash of one source row.
+#[derive(Debug, Clone)]
+pub struct SourceHash {
+    pub source: &'static str,
+    pub source_id: String,
+    pub row_id: i64,
+    pub hash: [u8; 32],
+}
+
+/// Length-prefixed, so no two field lists hash alike by shifting text
+/// between fields.
+fn digest(fields: &[String]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for field in fields {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The hash of every row of `rows` that has a key.
+pub fn hash_rows<T: SourceKey + SourceRow>(rows: &[T]) -> Vec<SourceHash> {
+    rows.iter()
+        .filter_map(|row| {
+            let source_id = row.source_id();
+            (!source_id.is_empty()).then(|| SourceHash {
+                source: T::SOURCE,
+                source_id,
+                row_id: row.id(),
+                hash: digest(&row.fields()),
+            })
+        })
+        .collect()
+}
+
+/// Write one `source_hashes` row per key, combining the hashes of rows that
+/// share it. Returns the rows written.
+pub fn write_source_hashes(conn: &Connection, hashes: &[SourceHash]) -> Result<usize> {
+    let mut by_key: BTreeMap<(&str, &str), Vec<&SourceHash>> = BTreeMap::new();
+    for h in hashes {
+        by_key
+            .entry((h.source, h.source_id.as_str()))
+            .or_default()
+            .push(h);
+    }
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO source_hashes (source, source_id, content_hash, row_count)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for ((source, source_id), rows) in &mut by_key {
+            let hash = match rows.as_slice() {
+                [row] => hex(&row.hash),
+                _ => {
+                    rows.sort_by_key(|row| row.row_id);
+                    let mut hasher = Sha256::new();
+                    for row in rows.iter() {
+                        hasher.update(row.hash);
+                    }
+                    hex(&hasher.finalize())
+                }
+            };
+            stmt.execute(rusqlite::params![source, source_id, hash, rows.len()])?;
+        }
+    }
+    tx.commit()?;
+    Ok(by_key.len())
+}
+
+impl SourceKey for VirginiaCodeRow {
+    const SOURCE: &'static str = "virginia_code";
+    const FIELD_NAMES: &'static [&'static str] = &[
+        "title_num",
+        "title_name",
+        "chapter_num",
+        "chapter_name",
+        "section",
+        "title",
+        "body",
+    ];
+
+    fn source_id(&self) -> String {
+        self.section.clone()
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.title_num.clone(),
+            self.title_name.clone(),
+            self.chapter_num.clone(),
+            self.chapter_name.clone(),
+            self.section.clone(),
+            self.title.clone(),
+            self.body.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.body.clone()
+    }
+}
+
+impl SourceKey for ConstitutionRow {
+    const SOURCE: &'static str = "constitution";
+    const FIELD_NAMES: &'static [&'static str] = &[
+        "article_id",
+        "article",
+        "article_name",
+        "section_name",
+        "section_title",
+        "section_text",
+        "section_count",
+    ];
+
+    fn source_id(&self) -> String {
+        format!("{}:{}", self.article_id, self.section_count)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.article_id.to_string(),
+            self.article.clone(),
+            self.article_name.clone(),
+            self.section_name.clone(),
+            self.section_title.clone(),
+            self.section_text.clone(),
+            self.section_count.to_string(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.section_text.clone()
+    }
+}
+
+impl SourceKey for AuthorityRow {
+    const SOURCE: &'static str = "authorities";
+    const FIELD_NAMES: &'static [&'static str] =
+        &["name", "short_name", "codified", "title", "section", "body"];
+
+    fn source_id(&self) -> String {
+        self.short_name.clone()
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.short_name.clone(),
+            self.codified.clone(),
+            self.title.clone(),
+            self.section.clone(),
+            self.body.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.body.clone()
+    }
+}
+
+impl SourceKey for CourtRow {
+    const SOURCE: &'static str = "courts";
+    const FIELD_NAMES: &'static [&'static str] = &[
+        "name",
+        "locality",
+        "court_type",
+        "district",
+        "address",
+        "city",
+        "state",
+        "zip",
+    ];
+
+    /// Courts are keyed by row id: they have no natural key.
+    fn source_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.locality.clone(),
+            self.court_type.clone(),
+            self.district.clone(),
+            self.address.clone(),
+            self.city.clone(),
+            self.state.clone(),
+            self.zip.clone(),
+        ]
+    }
+
+    /// Courts have no body; their text is built from these fields.
+    fn raw_text(&self) -> String {
+        self.fields()
+            .into_iter()
+            .filter(|f| !f.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl SourceKey for PopularNameRow {
+    const SOURCE: &'static str = "popular_names";
+    const FIELD_NAMES: &'static [&'static str] = &["name", "title_num", "section", "body"];
+
+    fn source_id(&self) -> String {
+        self.name.clone()
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.title_num.clone(),
+            self.section.clone(),
+            self.body.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.body.clone()
+    }
+}
+
+impl SourceKey for ActRow {
+    const SOURCE: &'static str = "acts";
+    const FIELD_NAMES: &'static [&'static str] = &["year", "chapter", "title", "body"];
+
+    fn source_id(&self) -> String {
+        act_key(self.year, &self.chapter)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.year.to_string(),
+            self.chapter.clone(),
+            self.title.clone(),
+            self.body.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.body.clone()
+    }
+}
+
+impl SourceKey for FederalRow {
+    const SOURCE: &'static str = "federal_code";
+    const FIELD_NAMES: &'static [&'static str] =
+        &["code", "title_num", "section", "heading", "body"];
+
+    fn source_id(&self) -> String {
+        federal_key(&self.code, &self.title_num, &self.section)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.code.clone(),
+            self.title_num.clone(),
+            self.section.clone(),
+            self.heading.clone(),
+            self.body.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.body.clone()
+    }
+}
+
+impl SourceKey for DocumentRow {
+    const SOURCE: &'static str = "documents";
+    const FIELD_NAMES: &'static [&'static str] = &["dataset", "filename", "title", "content"];
+
+    fn source_id(&self) -> String {
+        self.filename.clone()
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.dataset.clone(),
+            self.filename.clone(),
+            self.title.clone(),
+            self.content.clone(),
+        ]
+    }
+
+    fn raw_text(&self) -> String {
+        self.content.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::writer::create_output_db;
+
+    fn section(id: i64, section: &str, body: &str) -> VirginiaCodeRow {
+        VirginiaCodeRow {
+            id,
+            title_num: "1".into(),
+            title_name: "General Provisions".into(),
+            chapter_num: "1".into(),
+            chapter_name: "Definitions".into(),
+            section: section.into(),
+            title: "Heading".into(),
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn test_write_source_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = create_output_db(dir.path().join("out.db").to_str().unwrap()).unwrap();
+
+        let rows = [
+            section(1, "1-1", "Text"),
+            section(2, "1-2", "Other"),
+            section(3, "1-2", "Duplicate"),
+            section(4, "", "No section"),
+        ];
+        let hashes = hash_rows(&rows);
+        assert_eq!(hashes.len(), 3);
+        // The id is not content; moving text between fields is.
+        assert_eq!(
+            hashes[0].hash,
+            hash_rows(&[section(9, "1-1", "Text")])[0].hash
+        );
+        let mut shifted = section(1, "1-1", "Text");
+        shifted.title = "Heading Text".into();
+        shifted.body = String::new();
+        assert_ne!(hashes[0].hash, hash_rows(&[shifted])[0].hash);
+
+        assert_eq!(write_source_hashes(&conn, &hashes).unwrap(), 2);
+        let (hash, row_count): (String, i64) = conn
+            .query_row(
+                "SELECT content_hash, row_count FROM source_hashes
+                 WHERE source = 'virginia_code' AND source_id = '1-1'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((hash, row_count), (hex(&hashes[0].hash), 1));
+        let row_count: i64 = conn
+            .query_row(
+                "SELECT row_count FROM source_hashes WHERE source_id = '1-2'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(row_count, 2);
+    }
+}
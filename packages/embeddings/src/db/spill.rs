@@ -0,0 +1,112 @@
+//! Node texts parked in a temporary SQLite file when a build is over
+//! `--max-memory`. Pass 3 then reads them back a window at a time instead of
+//! holding every text, plus its sorted copy, in memory.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::Connection;
+use tempfile::NamedTempFile;
+
+pub struct TextSpill {
+    conn: Connection,
+    len: usize,
+    /// Deleted when the spill is dropped.
+    _file: NamedTempFile,
+}
+
+impl TextSpill {
+    /// Move `texts` into a new spill file in `dir`, freeing them as they go.
+    pub fn create(dir: &Path, texts: HashMap<i64, String>) -> Result<Self> {
+        let file = tempfile::Builder::new()
+            .prefix(".texts-")
+            .suffix(".spill.db")
+            .tempfile_in(dir)
+            .with_context(|| format!("Failed to create a text spill in {}", dir.display()))?;
+        let conn = Connection::open(file.path())?;
+        conn.execute_batch(
+            "
+            PRAGMA journal_mode = OFF;
+            PRAGMA synchronous = OFF;
+            CREATE TABLE texts (node_id INTEGER PRIMARY KEY, text TEXT NOT NULL);
+            ",
+        )?;
+        let len = texts.len();
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO texts (node_id, text) VALUES (?1, ?2)")?;
+            for (node_id, text) in texts {
+                stmt.execute(rusqlite::params![node_id, text])?;
+            }
+        }
+        tx.commit()?;
+        Ok(Self {
+            conn,
+            len,
+            _file: file,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Every spilled node id, in order.
+    pub fn ids(&self) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare("SELECT node_id FROM texts ORDER BY node_id")?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Byte length of each text, in the order of `ids`.
+    pub fn lengths(&self, ids: &[i64]) -> Result<Vec<usize>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT length(CAST(text AS BLOB)) FROM texts WHERE node_id = ?1")?;
+        ids.iter()
+            .map(|id| {
+                stmt.query_row([id], |r| r.get::<_, i64>(0))
+                    .map(|n| n as usize)
+                    .map_err(|_| anyhow!("Node {} is not in the text spill", id))
+            })
+            .collect()
+    }
+
+    /// The texts of `ids`, in order.
+    pub fn get(&self, ids: &[i64]) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT text FROM texts WHERE node_id = ?1")?;
+        ids.iter()
+            .map(|id| {
+                stmt.query_row([id], |r| r.get(0))
+                    .map_err(|_| anyhow!("Node {} is not in the text spill", id))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spill_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts = HashMap::from([(1, "first".to_string()), (2, "§ 2 — second".to_string())]);
+        let spill = TextSpill::create(dir.path(), texts.clone()).unwrap();
+        assert_eq!(spill.len(), 2);
+        assert_eq!(spill.get(&[2, 1]).unwrap(), ["§ 2 — second", "first"]);
+        assert_eq!(spill.lengths(&[2, 1]).unwrap(), [texts[&2].len(), 5]);
+        assert!(spill.get(&[3]).is_err());
+        assert_eq!(spill.ids().unwrap(), [1, 2]);
+
+        drop(spill);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
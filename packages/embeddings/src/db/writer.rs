@@ -1,11 +1,19 @@
+use std::collections::HashSet;
 use std::io::{BufRead, BufReader, Write};
 use anyhow::Result;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 
-use crate::graph::edges::Edge;
+use crate::bloom::SectionFilter;
+use crate::csr::Csr;
+use crate::db::reader::RowError;
+use crate::embed::ModelSpec;
+use crate::etl::{DroppedRow, HtmlLimitedRow};
+use crate::graph::edges::{Edge, EdgeProvenance};
 use crate::graph::nodes::{ChunkMeta, Node};
 
+/// Create an empty output DB at `path` with the full schema, replacing any
+/// existing file (and its WAL/SHM).
 pub fn create_output_db(path: &str) -> Result<Connection> {
     // Remove existing database and any stale WAL/SHM files if present
     let db_path = std::path::Path::new(path);
@@ -39,7 +47,11 @@ pub fn create_output_db(path: &str) -> Result<Connection> {
             source    TEXT NOT NULL,
             source_id TEXT NOT NULL,
             chunk_idx INTEGER NOT NULL DEFAULT 0,
-            node_type TEXT NOT NULL
+            node_type TEXT NOT NULL,
+            truncated INTEGER NOT NULL DEFAULT 0,
+            status    TEXT NOT NULL DEFAULT 'active',
+            enacted_year INTEGER,
+            amended_year INTEGER
         );
 
         CREATE TABLE edges (
@@ -50,6 +62,16 @@ pub fn create_output_db(path: &str) -> Result<Connection> {
             PRIMARY KEY (from_id, to_id, rel_type)
         );
 
+        CREATE TABLE edge_provenance (
+            from_id    INTEGER NOT NULL REFERENCES nodes(id),
+            to_id      INTEGER NOT NULL REFERENCES nodes(id),
+            rel_type   TEXT NOT NULL,
+            chunk_idx  INTEGER NOT NULL,
+            char_start INTEGER NOT NULL,
+            char_end   INTEGER NOT NULL,
+            matched    TEXT NOT NULL
+        );
+
         CREATE TABLE chunk_meta (
             node_id    INTEGER PRIMARY KEY REFERENCES nodes(id),
             char_start INTEGER NOT NULL,
@@ -61,33 +83,117 @@ pub fn create_output_db(path: &str) -> Result<Connection> {
             embedding BLOB NOT NULL
         );
 
+        CREATE TABLE node_hashes (
+            node_id   INTEGER PRIMARY KEY REFERENCES nodes(id),
+            text_hash TEXT NOT NULL
+        );
+
+        CREATE TABLE document_section_filters (
+            node_id    INTEGER PRIMARY KEY REFERENCES nodes(id),
+            num_bits   INTEGER NOT NULL,
+            num_hashes INTEGER NOT NULL,
+            bits       BLOB NOT NULL
+        );
+
+        CREATE TABLE adjacency (
+            rel_type  TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            offsets   BLOB NOT NULL,
+            targets   BLOB NOT NULL,
+            PRIMARY KEY (rel_type, direction)
+        );
+
+        CREATE TABLE dropped_rows (
+            source_table TEXT NOT NULL,
+            source_id    INTEGER NOT NULL,
+            reason       TEXT NOT NULL,
+            kept_id      INTEGER
+        );
+
+        CREATE TABLE document_datasets (
+            filename TEXT PRIMARY KEY,
+            dataset  TEXT NOT NULL
+        );
+
+        CREATE TABLE source_hashes (
+            source       TEXT NOT NULL,
+            source_id    TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            row_count    INTEGER NOT NULL,
+            PRIMARY KEY (source, source_id)
+        );
+
+        CREATE TABLE section_history (
+            section TEXT NOT NULL,
+            seq     INTEGER NOT NULL,
+            year    INTEGER NOT NULL,
+            session TEXT,
+            chapter TEXT
+        );
+
+        CREATE TABLE row_errors (
+            source_table TEXT NOT NULL,
+            source_id    INTEGER,
+            message      TEXT NOT NULL
+        );
+
+        CREATE TABLE html_limited_rows (
+            source_table TEXT NOT NULL,
+            source_id    INTEGER NOT NULL,
+            field        TEXT NOT NULL,
+            limit_hit    TEXT NOT NULL,
+            input_bytes  INTEGER NOT NULL
+        );
+
         CREATE INDEX idx_nodes_source ON nodes(source, source_id);
         CREATE INDEX idx_edges_to ON edges(to_id, rel_type);
         CREATE INDEX idx_edges_type ON edges(rel_type);
+        CREATE INDEX idx_edge_provenance ON edge_provenance(from_id, to_id, rel_type);
+        CREATE INDEX idx_section_history_section ON section_history(section);
         ",
     )?;
 
     Ok(conn)
 }
 
-pub fn write_model_info(conn: &Connection, model_name: &str, dimensions: usize) -> Result<()> {
-    conn.execute(
-        "INSERT INTO model_info (key, value) VALUES (?1, ?2)",
-        rusqlite::params!["model_name", model_name],
-    )?;
-    conn.execute(
-        "INSERT INTO model_info (key, value) VALUES (?1, ?2)",
-        rusqlite::params!["dimensions", dimensions.to_string()],
-    )?;
+/// The `model_info` rows of embeddings made by `model`. `model_name` and
+/// `dimensions` are what readers check; the rest documents the run.
+pub fn model_info(
+    model: &ModelSpec,
+    max_length: usize,
+    dimensions: usize,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("model_name", model.model_id.to_string()),
+        ("dimensions", dimensions.to_string()),
+        ("preset", model.name.to_string()),
+        ("max_length", max_length.to_string()),
+        ("pooling", model.pooling_name().to_string()),
+        ("document_prefix", model.document_prefix().to_string()),
+    ]
+}
+
+/// Record the model that produced the embeddings (see [`model_info`]).
+pub fn write_model_info(
+    conn: &Connection,
+    model: &ModelSpec,
+    max_length: usize,
+    dimensions: usize,
+) -> Result<()> {
+    let mut stmt = conn.prepare("INSERT OR REPLACE INTO model_info (key, value) VALUES (?1, ?2)")?;
+    for (key, value) in model_info(model, max_length, dimensions) {
+        stmt.execute(rusqlite::params![key, value])?;
+    }
     Ok(())
 }
 
+/// Write the Pass 1 nodes. Returns the number written.
 pub fn write_nodes(conn: &Connection, nodes: &[Node]) -> Result<usize> {
     let tx = conn.unchecked_transaction()?;
     {
         let mut stmt = tx.prepare(
-            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO nodes (id, source, source_id, chunk_idx, node_type, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         )?;
 
         for node in nodes {
@@ -97,6 +203,7 @@ pub fn write_nodes(conn: &Connection, nodes: &[Node]) -> Result<usize> {
                 node.source_id,
                 node.chunk_idx,
                 node.node_type,
+                node.status.as_str(),
             ])?;
         }
     }
@@ -104,6 +211,8 @@ pub fn write_nodes(conn: &Connection, nodes: &[Node]) -> Result<usize> {
     Ok(nodes.len())
 }
 
+/// Write the Pass 2 edges; duplicates of an existing `(from, to, type)` are
+/// ignored. Returns the number given.
 pub fn write_edges(conn: &Connection, edges: &[Edge]) -> Result<usize> {
     let tx = conn.unchecked_transaction()?;
     {
@@ -125,6 +234,160 @@ pub fn write_edges(conn: &Connection, edges: &[Edge]) -> Result<usize> {
     Ok(edges.len())
 }
 
+/// Write the matched citation spans behind `cites` / `references` edges.
+pub fn write_edge_provenance(conn: &Connection, provenance: &[EdgeProvenance]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO edge_provenance
+                 (from_id, to_id, rel_type, chunk_idx, char_start, char_end, matched)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for p in provenance {
+            stmt.execute(rusqlite::params![
+                p.from_id,
+                p.to_id,
+                p.rel_type,
+                p.chunk_idx,
+                p.char_start,
+                p.char_end,
+                p.matched,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(provenance.len())
+}
+
+/// Write the trimmed graph used by exports and the app alongside `edges`.
+pub fn write_presentation_edges(conn: &Connection, edges: &[Edge]) -> Result<usize> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS presentation_edges (
+            from_id   INTEGER NOT NULL REFERENCES nodes(id),
+            to_id     INTEGER NOT NULL REFERENCES nodes(id),
+            rel_type  TEXT NOT NULL,
+            weight    REAL,
+            PRIMARY KEY (from_id, to_id, rel_type)
+        );
+        DELETE FROM presentation_edges;
+        ",
+    )?;
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO presentation_edges (from_id, to_id, rel_type, weight)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for edge in edges {
+            stmt.execute(rusqlite::params![
+                edge.from_id,
+                edge.to_id,
+                edge.rel_type,
+                edge.weight,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(edges.len())
+}
+
+/// Write forward and reverse CSR adjacency for each edge type (see `csr.rs`).
+/// Returns the number of `(rel_type, direction)` rows written.
+pub fn write_adjacency(conn: &Connection, edges: &[Edge]) -> Result<usize> {
+    let mut rel_types: Vec<&str> = edges.iter().map(|e| e.rel_type.as_str()).collect();
+    rel_types.sort_unstable();
+    rel_types.dedup();
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM adjacency", [])?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO adjacency (rel_type, direction, offsets, targets) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for rel in &rel_types {
+            let of_type = || edges.iter().filter(|e| e.rel_type == *rel);
+            let forward = Csr::build(of_type().map(|e| (e.from_id, e.to_id)))?;
+            let reverse = Csr::build(of_type().map(|e| (e.to_id, e.from_id)))?;
+            for (direction, csr) in [("forward", forward), ("reverse", reverse)] {
+                let (offsets, targets) = csr.to_blobs();
+                stmt.execute(rusqlite::params![rel, direction, offsets, targets])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(rel_types.len() * 2)
+}
+
+/// Write one section-mention bloom filter per document (see `bloom.rs`).
+pub fn write_document_section_filters(
+    conn: &Connection,
+    mentions: &[(i64, Vec<String>)],
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO document_section_filters (node_id, num_bits, num_hashes, bits)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (node_id, sections) in mentions {
+            let filter = SectionFilter::from_keys(sections.iter().map(String::as_str));
+            stmt.execute(rusqlite::params![
+                node_id,
+                filter.num_bits as i64,
+                filter.num_hashes,
+                filter.to_bytes(),
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(mentions.len())
+}
+
+pub fn write_dropped_rows(conn: &Connection, dropped: &[DroppedRow]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO dropped_rows (source_table, source_id, reason, kept_id)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for d in dropped {
+            stmt.execute(rusqlite::params![d.table, d.id, d.reason, d.kept_id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(dropped.len())
+}
+
+pub fn write_row_errors(conn: &Connection, errors: &[RowError]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO row_errors (source_table, source_id, message) VALUES (?1, ?2, ?3)",
+        )?;
+        for e in errors {
+            stmt.execute(rusqlite::params![e.table, e.id, e.message])?;
+        }
+    }
+    tx.commit()?;
+    Ok(errors.len())
+}
+
+pub fn write_html_limited_rows(conn: &Connection, rows: &[HtmlLimitedRow]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO html_limited_rows (source_table, source_id, field, limit_hit, input_bytes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for r in rows {
+            stmt.execute(rusqlite::params![r.table, r.id, r.field, r.limit.as_str(), r.bytes])?;
+        }
+    }
+    tx.commit()?;
+    Ok(rows.len())
+}
+
 pub fn write_chunk_meta(conn: &Connection, meta: &[ChunkMeta]) -> Result<usize> {
     let tx = conn.unchecked_transaction()?;
     {
@@ -139,6 +402,7 @@ pub fn write_chunk_meta(conn: &Connection, meta: &[ChunkMeta]) -> Result<usize>
     Ok(meta.len())
 }
 
+/// Open an existing output DB for Pass 3.
 pub fn open_output_db(path: &str) -> Result<Connection> {
     if !std::path::Path::new(path).exists() {
         anyhow::bail!("Output database not found: {path}");
@@ -185,6 +449,149 @@ pub fn write_embeddings_jsonl_batch(
     Ok(())
 }
 
+/// Record `node_ids` as the work list for Pass 3, replacing any previous one.
+/// Each finished batch flips its rows to `done` (see [`write_embeddings_batch`]),
+/// so an interrupted run can be picked up with `--resume`.
+pub fn mark_pending(conn: &Connection, node_ids: &[i64]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS pending_embeddings (
+            node_id INTEGER PRIMARY KEY REFERENCES nodes(id),
+            status  TEXT NOT NULL
+        );
+        DELETE FROM pending_embeddings;
+        ",
+    )?;
+    {
+        let mut stmt =
+            tx.prepare("INSERT INTO pending_embeddings (node_id, status) VALUES (?1, 'pending')")?;
+        for id in node_ids {
+            stmt.execute([id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Store one batch of embeddings and mark those nodes done, atomically.
+pub fn write_embeddings_batch(
+    conn: &Connection,
+    node_ids: &[i64],
+    embeddings: &[Vec<f32>],
+) -> Result<()> {
+    assert_eq!(node_ids.len(), embeddings.len());
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO embeddings (node_id, embedding) VALUES (?1, ?2)",
+        )?;
+        let mut done =
+            tx.prepare("UPDATE pending_embeddings SET status = 'done' WHERE node_id = ?1")?;
+        for (node_id, embedding) in node_ids.iter().zip(embeddings) {
+            let bytes: Vec<u8> = embedding.iter().flat_map(|&f| f.to_le_bytes()).collect();
+            insert.execute(rusqlite::params![node_id, bytes])?;
+            done.execute([node_id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Track nodes the model failed on: add or bump `failures`, and drop entries
+/// for nodes that have since been embedded. Returns how many remain. Failed
+/// nodes stay `pending`, so `--resume` tries them again.
+pub fn sync_embedding_failures(conn: &Connection, failures: &[(i64, String)]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS embedding_failures (
+            node_id  INTEGER PRIMARY KEY REFERENCES nodes(id),
+            attempts INTEGER NOT NULL,
+            error    TEXT NOT NULL
+        );
+        DELETE FROM embedding_failures WHERE node_id IN (SELECT node_id FROM embeddings);
+        ",
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO embedding_failures (node_id, attempts, error) VALUES (?1, 1, ?2)
+             ON CONFLICT(node_id) DO UPDATE SET attempts = attempts + 1, error = excluded.error",
+        )?;
+        for (node_id, error) in failures {
+            stmt.execute(rusqlite::params![node_id, error])?;
+        }
+    }
+    let remaining = tx.query_row("SELECT COUNT(*) FROM embedding_failures", [], |r| r.get(0))?;
+    tx.commit()?;
+    Ok(remaining)
+}
+
+/// Set `nodes.truncated` on nodes whose text ran past the model's sequence
+/// length in Pass 3. DBs built before the column existed (e.g. when
+/// resuming) get it added.
+pub fn mark_truncated(conn: &Connection, node_ids: &[i64]) -> Result<()> {
+    let has_column: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('nodes') WHERE name = 'truncated'",
+        [],
+        |r| r.get(0),
+    )?;
+    let tx = conn.unchecked_transaction()?;
+    if !has_column {
+        tx.execute(
+            "ALTER TABLE nodes ADD COLUMN truncated INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+    {
+        let mut stmt = tx.prepare("UPDATE nodes SET truncated = 1 WHERE id = ?1")?;
+        for node_id in node_ids {
+            stmt.execute([node_id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Ids of nodes marked `repealed`, for `--skip-repealed`; none in a DB built
+/// before `nodes.status` existed.
+pub fn repealed_node_ids(conn: &Connection) -> Result<HashSet<i64>> {
+    let has_column: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('nodes') WHERE name = 'status'",
+        [],
+        |r| r.get(0),
+    )?;
+    if !has_column {
+        return Ok(HashSet::new());
+    }
+    let mut stmt = conn.prepare("SELECT id FROM nodes WHERE status = 'repealed'")?;
+    let ids = stmt.query_map([], |r| r.get(0))?;
+    Ok(ids.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Record `backend` as the one that embedded `node_ids`, when Pass 3 runs on
+/// an `[embedding]` backend chain.
+pub fn write_embedding_backends(conn: &Connection, node_ids: &[i64], backend: &str) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS embedding_backends (
+            node_id INTEGER PRIMARY KEY REFERENCES nodes(id),
+            backend TEXT NOT NULL
+        );
+        ",
+    )?;
+    {
+        let mut stmt =
+            tx.prepare("INSERT OR REPLACE INTO embedding_backends (node_id, backend) VALUES (?1, ?2)")?;
+        for node_id in node_ids {
+            stmt.execute(rusqlite::params![node_id, backend])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 pub fn load_embeddings_from_jsonl(conn: &Connection, jsonl_path: &std::path::Path) -> Result<usize> {
     let file = std::fs::File::open(jsonl_path)?;
     let reader = BufReader::new(file);
@@ -192,7 +599,10 @@ pub fn load_embeddings_from_jsonl(conn: &Connection, jsonl_path: &std::path::Pat
     let tx = conn.unchecked_transaction()?;
     let mut count = 0;
     {
-        let mut stmt = tx.prepare("INSERT INTO embeddings (node_id, embedding) VALUES (?1, ?2)")?;
+        // OR REPLACE: a resumed run may have re-embedded a batch that was
+        // already in the JSONL when the previous run died.
+        let mut stmt =
+            tx.prepare("INSERT OR REPLACE INTO embeddings (node_id, embedding) VALUES (?1, ?2)")?;
 
         for line in reader.lines() {
             let line = line?;
@@ -216,3 +626,24 @@ pub fn load_embeddings_from_jsonl(conn: &Connection, jsonl_path: &std::path::Pat
 
     Ok(count)
 }
+
+/// Write every embedding in the DB to `jsonl_path`, replacing the file, in the
+/// format [`load_embeddings_from_jsonl`] reads.
+pub fn export_embeddings_jsonl(conn: &Connection, jsonl_path: &std::path::Path) -> Result<usize> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(jsonl_path)?);
+    let mut stmt = conn.prepare("SELECT node_id, embedding FROM embeddings ORDER BY node_id")?;
+    let mut rows = stmt.query([])?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let bytes: Vec<u8> = row.get(1)?;
+        let embedding = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        write_embeddings_jsonl_batch(&mut writer, &[row.get(0)?], &[embedding])?;
+        count += 1;
+    }
+    writer.flush()?;
+
+    Ok(count)
+}
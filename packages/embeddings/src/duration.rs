@@ -0,0 +1,60 @@
+//! `--max-duration` values: `2h`, `90m`, `1h30m`, `45s`, or a bare number of
+//! minutes (what the flag's `--time-budget` alias took).
+
+use std::time::Duration;
+
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if let Ok(minutes) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(minutes * 60));
+    }
+    let mut secs = 0u64;
+    let mut digits = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("unknown unit {c:?} in {s:?} (use h, m or s)")),
+        };
+        let n: u64 = digits
+            .parse()
+            .map_err(|_| format!("expected a number before {c:?} in {s:?}"))?;
+        secs += n * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() || secs == 0 {
+        return Err(format!("{s:?} is not a duration like 2h, 90m or 1h30m"));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Serialize an optional duration as whole seconds, for the build report.
+pub fn serialize_secs<S: serde::Serializer>(
+    d: &Option<Duration>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.serialize_some(&d.map(|d| d.as_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let secs = |s| parse_duration(s).map(|d| d.as_secs());
+        assert_eq!(secs("2h"), Ok(7200));
+        assert_eq!(secs("1h30m"), Ok(5400));
+        assert_eq!(secs("45s"), Ok(45));
+        assert_eq!(secs("30"), Ok(1800));
+        assert!(secs("2d").is_err());
+        assert!(secs("h").is_err());
+        assert!(secs("1h30").is_err());
+        assert!(secs("0m").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! The `[embedding]` backend chain: where Pass 3 runs the model, in order of
+//! preference. Each backend runs the same `--model` preset, so vectors from
+//! any of them share one space; the chain only changes where they are
+//! computed. A backend is a local device, or a remote OpenAI-compatible
+//! `/v1/embeddings` endpoint serving the same model.
+//!
+//! ```toml
+//! [embedding]
+//! max_consecutive_failures = 3
+//!
+//! [[embedding.backends]]
+//! name = "gpu"
+//! device = "metal"
+//!
+//! [[embedding.backends]]
+//! device = "cpu"
+//!
+//! [[embedding.backends]]
+//! name = "gpu-box"
+//! url = "http://gpu-box:8000/v1/embeddings"
+//! api_key_env = "GPU_BOX_KEY"
+//! ```
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use super::{Device, ModelSpec};
+
+/// Longest a remote request may take when no `--batch-timeout` is set.
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainConfig {
+    pub backends: Vec<BackendSpec>,
+    /// Failed or hung batches in a row before moving to the next backend.
+    pub max_consecutive_failures: usize,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            max_consecutive_failures: 3,
+        }
+    }
+}
+
+impl ChainConfig {
+    pub fn check(&self) -> Result<()> {
+        if self.backends.is_empty() {
+            bail!("[embedding] needs at least one [[embedding.backends]] entry");
+        }
+        if self.max_consecutive_failures == 0 {
+            bail!("[embedding] max_consecutive_failures must be at least 1");
+        }
+        for backend in &self.backends {
+            backend.check()?;
+        }
+        Ok(())
+    }
+}
+
+/// One backend: a local `device`, or a remote `url`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackendSpec {
+    /// Recorded with each vector (default: the device, or the URL's host).
+    pub name: Option<String>,
+    pub device: Option<Device>,
+    pub url: Option<String>,
+    /// Environment variable holding the remote endpoint's bearer token.
+    pub api_key_env: Option<String>,
+}
+
+impl BackendSpec {
+    pub fn local(device: Device) -> Self {
+        Self {
+            device: Some(device),
+            ..Self::default()
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        match (&self.device, &self.url) {
+            (Some(_), None) => {}
+            (None, Some(url)) => {
+                url::Url::parse(url).with_context(|| format!("Invalid backend url {:?}", url))?;
+            }
+            _ => bail!("Each [[embedding.backends]] entry needs exactly one of device or url"),
+        }
+        if self.api_key_env.is_some() && self.url.is_none() {
+            bail!("api_key_env only applies to a backend with a url");
+        }
+        Ok(())
+    }
+
+    /// The configured name, else the device or the URL's host.
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match (&self.device, &self.url) {
+            (Some(device), _) => device.as_str().to_string(),
+            (_, Some(url)) => url::Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+                .unwrap_or_else(|| url.clone()),
+            _ => "unnamed".to_string(),
+        }
+    }
+}
+
+/// An OpenAI-compatible embeddings endpoint. Texts are sent with the model's
+/// document prefix already applied, so the endpoint must embed its input
+/// verbatim.
+pub struct Remote {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    model: &'static ModelSpec,
+}
+
+impl Remote {
+    pub fn new(spec: &BackendSpec, model: &'static ModelSpec) -> Result<Self> {
+        let url = spec.url.clone().context("A remote backend needs a url")?;
+        let api_key = match &spec.api_key_env {
+            Some(var) => Some(
+                std::env::var(var).with_context(|| format!("{} (api_key_env) is not set", var))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url,
+            api_key,
+            model,
+        })
+    }
+
+    pub async fn embed(
+        &self,
+        texts: Vec<String>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let count = texts.len();
+        let mut req = self
+            .client
+            .post(&self.url)
+            .timeout(timeout.unwrap_or(REMOTE_TIMEOUT))
+            .json(&serde_json::json!({ "model": self.model.model_id, "input": texts }));
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        let resp = req
+            .send()
+            .await
+            .with_context(|| format!("POST {}", self.url))?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!(
+                "POST {}: HTTP {}: {}",
+                self.url,
+                status,
+                resp.text().await.unwrap_or_default()
+            );
+        }
+        let json: serde_json::Value = resp.json().await?;
+        parse_embeddings(&json, count)
+    }
+}
+
+/// `data[].embedding` of an embeddings response, in `index` order.
+fn parse_embeddings(json: &serde_json::Value, count: usize) -> Result<Vec<Vec<f32>>> {
+    let data = json["data"]
+        .as_array()
+        .context("Embeddings response has no data array")?;
+    let mut vectors = vec![None; count];
+    for (i, item) in data.iter().enumerate() {
+        let index = item["index"].as_u64().map_or(i, |n| n as usize);
+        let vector = item["embedding"]
+            .as_array()
+            .context("Embeddings response item has no embedding")?
+            .iter()
+            .map(|v| v.as_f64().map(|f| f as f32))
+            .collect::<Option<Vec<f32>>>()
+            .context("Embedding holds a non-number")?;
+        match vectors.get_mut(index) {
+            Some(slot) => *slot = Some(vector),
+            None => bail!("Embeddings response index {} is out of range", index),
+        }
+    }
+    vectors
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .with_context(|| {
+            format!(
+                "Embeddings response has {} of {} vectors",
+                data.len(),
+                count
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_config() {
+        let config: ChainConfig = toml::from_str(
+            r#"
+            [[backends]]
+            device = "metal"
+            [[backends]]
+            name = "gpu-box"
+            url = "http://gpu-box:8000/v1/embeddings"
+            "#,
+        )
+        .unwrap();
+        config.check().unwrap();
+        assert_eq!(config.max_consecutive_failures, 3);
+        let labels: Vec<_> = config.backends.iter().map(|b| b.label()).collect();
+        assert_eq!(labels, ["metal", "gpu-box"]);
+        assert_eq!(
+            BackendSpec {
+                url: Some("http://10.0.0.5:8000/v1/embeddings".into()),
+                ..BackendSpec::default()
+            }
+            .label(),
+            "10.0.0.5"
+        );
+
+        let both: ChainConfig =
+            toml::from_str("[[backends]]\ndevice = \"cpu\"\nurl = \"http://x/\"").unwrap();
+        assert!(both.check().is_err());
+        assert!(ChainConfig::default().check().is_err());
+
+        let json = serde_json::json!({"data": [
+            {"index": 1, "embedding": [0.5, 1.0]},
+            {"index": 0, "embedding": [1.5, 2.0]},
+        ]});
+        assert_eq!(
+            parse_embeddings(&json, 2).unwrap(),
+            [vec![1.5, 2.0], vec![0.5, 1.0]]
+        );
+        assert!(parse_embeddings(&json, 3).is_err());
+    }
+}
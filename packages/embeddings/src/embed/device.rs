@@ -0,0 +1,79 @@
+//! `--device`: which ONNX Runtime execution provider runs the model.
+//!
+//! CUDA and CoreML ("metal") are only compiled into ONNX Runtime with the
+//! crate's `cuda` / `coreml` features. `auto` tries them in that order and
+//! falls back to the CPU, so one build command works on macOS and on Linux
+//! servers with or without a GPU.
+
+use fastembed::ExecutionProviderDispatch;
+use ort::ep::{self, ExecutionProvider};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    /// The first of cuda, metal, cpu that is available and loads the model
+    Auto,
+    Cpu,
+    Cuda,
+    /// Apple GPU / Neural Engine via CoreML
+    Metal,
+}
+
+impl Device {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Device::Auto => "auto",
+            Device::Cpu => "cpu",
+            Device::Cuda => "cuda",
+            Device::Metal => "metal",
+        }
+    }
+
+    /// Devices to try, in order.
+    pub fn candidates(self) -> Vec<Device> {
+        match self {
+            Device::Auto => vec![Device::Cuda, Device::Metal, Device::Cpu],
+            device => vec![device],
+        }
+    }
+
+    /// Whether this platform and the linked ONNX Runtime support the device.
+    pub fn is_available(self) -> bool {
+        fn check(ep: &impl ExecutionProvider) -> bool {
+            ep.supported_by_platform() && ep.is_available().unwrap_or(false)
+        }
+        match self {
+            Device::Auto | Device::Cpu => true,
+            Device::Cuda => check(&ep::CUDA::default()),
+            Device::Metal => check(&ep::CoreML::default()),
+        }
+    }
+
+    /// Execution providers for `InitOptions`. Registration errors are raised
+    /// rather than silently falling back to the CPU, so `auto` can move on to
+    /// the next candidate and an explicit device fails loudly.
+    pub fn execution_providers(self) -> Vec<ExecutionProviderDispatch> {
+        match self {
+            Device::Auto | Device::Cpu => vec![ep::CPU::default().build()],
+            Device::Cuda => vec![ep::CUDA::default().build().error_on_failure()],
+            Device::Metal => vec![ep::CoreML::default().build().error_on_failure()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_falls_back_to_cpu() {
+        assert_eq!(
+            Device::Auto.candidates(),
+            vec![Device::Cuda, Device::Metal, Device::Cpu]
+        );
+        assert_eq!(Device::Cuda.candidates(), vec![Device::Cuda]);
+        assert!(Device::Cpu.is_available());
+    }
+}
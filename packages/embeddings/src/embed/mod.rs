@@ -1,11 +1,29 @@
+//! Embedding with fastembed: a pool of model workers ([`EmbeddingPool`]) and
+//! the batching loop on top of it ([`Embedder`]).
+
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use anyhow::{Context, Result};
+use fastembed::{InitOptions, TextEmbedding};
 use indicatif::{ProgressBar, ProgressStyle};
+use tokenizers::Tokenizer;
 use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+pub mod backend;
+pub mod device;
+pub mod models;
+pub mod pacing;
+pub mod packing;
+pub mod truncation;
+
+pub use backend::{BackendSpec, ChainConfig};
+pub use device::Device;
+pub use models::ModelSpec;
+use pacing::{Pace, Pacer};
 
 /// Resolves the model cache directory. Respects `FASTEMBED_CACHE_DIR` if set;
 /// otherwise defaults to the Hugging Face cache directory (respecting `HF_HOME`).
@@ -29,24 +47,54 @@ struct EmbeddingJob {
     resp: oneshot::Sender<Result<Vec<Vec<f32>>>>,
 }
 
+/// Starts one worker: its job queue, and a receiver told once its model has
+/// loaded or failed to.
+type SpawnWorker = dyn Fn() -> (mpsc::Sender<EmbeddingJob>, std::sync::mpsc::Receiver<Result<()>>)
+    + Send
+    + Sync;
+
 pub struct EmbeddingPool {
-    senders: Vec<mpsc::Sender<EmbeddingJob>>,
+    /// Each worker's job queue. [`EmbeddingPool::embed_within`] swaps in a
+    /// fresh worker for one it gives up on, whose thread may be stuck inside
+    /// the model for good.
+    senders: Vec<Mutex<mpsc::Sender<EmbeddingJob>>>,
+    /// Workers that have gone away (their model failed to load, or their
+    /// thread died); no more jobs go to them.
+    stopped: Vec<AtomicBool>,
     next: AtomicUsize,
+    spawn_worker: Box<SpawnWorker>,
+    /// The device the workers run on, after `auto` fallback.
+    pub device: Device,
+    /// The model's tokenizer with truncation and padding off, so it counts
+    /// every token of a text.
+    tokenizer: Tokenizer,
 }
 
 impl EmbeddingPool {
-    fn new(pool_size: usize) -> Result<Self> {
+    fn new(
+        model: &'static ModelSpec,
+        max_length: usize,
+        device: Device,
+        pool_size: usize,
+    ) -> Result<Self> {
         let size = pool_size.max(1);
         let mut senders = Vec::with_capacity(size);
         let mut readiness_rxs = Vec::with_capacity(size);
 
-        let model_type = EmbeddingModel::EmbeddingGemma300M;
+        let init_options = move |device: Device, show_download_progress: bool| {
+            InitOptions::new(model.backend.clone())
+                .with_max_length(max_length)
+                .with_execution_providers(device.execution_providers())
+                .with_cache_dir(resolve_cache_dir())
+                .with_show_download_progress(show_download_progress)
+        };
 
         // Pass 0: Initialize one model instance first to ensure download/extraction
         // is complete before spawning many threads that would all try to acquire
-        // the same file locks.
-        {
-            eprintln!("  [init] Pre-loading model to ensure cache is ready...");
+        // the same file locks. This also picks the device: the first candidate
+        // that loads the model is used by every worker.
+        let (device, tokenizer) = {
+            info!("Pre-loading model to ensure cache is ready");
             let pb = ProgressBar::new_spinner();
             pb.set_style(
                 ProgressStyle::default_spinner()
@@ -56,20 +104,45 @@ impl EmbeddingPool {
             pb.set_message("Downloading/extracting model...");
             pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-            let _ = TextEmbedding::try_new(
-                InitOptions::new(model_type.clone())
-                    .with_cache_dir(resolve_cache_dir())
-                    .with_show_download_progress(true),
-            )
-            .map_err(|e| {
+            let mut chosen = None;
+            let mut last_error = None;
+            for candidate in device.candidates() {
+                if !candidate.is_available() {
+                    pb.suspend(|| info!(device = candidate.as_str(), "Device not available"));
+                    last_error = Some(anyhow::anyhow!(
+                        "device {} is not available on this machine or in this build",
+                        candidate.as_str()
+                    ));
+                    continue;
+                }
+                match TextEmbedding::try_new(init_options(candidate, true)) {
+                    Ok(loaded) => {
+                        chosen = Some((candidate, loaded.tokenizer));
+                        break;
+                    }
+                    Err(e) => {
+                        pb.suspend(|| {
+                            warn!(device = candidate.as_str(), "Device failed to load the model: {e}")
+                        });
+                        last_error = Some(e);
+                    }
+                }
+            }
+            let Some((chosen, mut tokenizer)) = chosen else {
                 pb.finish_and_clear();
-                anyhow::anyhow!("Initial model load failed: {e}")
-            })?;
-
-            pb.finish_with_message("Model ready.");
-        }
-
-        println!("  [init] Spawning {} worker threads...", size);
+                let e = last_error.unwrap_or_else(|| anyhow::anyhow!("no device to try"));
+                anyhow::bail!("Initial model load failed: {e}");
+            };
+            tokenizer
+                .with_truncation(None)
+                .map_err(|e| anyhow::anyhow!(e))?
+                .with_padding(None);
+
+            pb.finish_with_message(format!("Model ready on {}.", chosen.as_str()));
+            (chosen, tokenizer)
+        };
+
+        info!(workers = size, "Spawning worker threads");
         let pb = ProgressBar::new(size as u64);
         pb.set_style(
             ProgressStyle::default_bar()
@@ -77,56 +150,19 @@ impl EmbeddingPool {
                 .unwrap(),
         );
 
-        for _ in 0..size {
+        let spawn_worker = move || {
             let (tx, mut rx) = mpsc::channel::<EmbeddingJob>(32);
             let (ready_tx, ready_rx) = std::sync::mpsc::channel::<Result<()>>();
 
-            let model_type_clone = model_type.clone();
             std::thread::spawn(move || {
-                let mut text_embedding = {
-                    let try_init = |m: EmbeddingModel| {
-                        TextEmbedding::try_new(
-                            InitOptions::new(m)
-                                .with_cache_dir(resolve_cache_dir())
-                                .with_show_download_progress(false),
-                        )
-                    };
-
-                    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-                    {
-                        match try_init(model_type_clone.clone()) {
-                            Ok(ok) => {
-                                let _ = ready_tx.send(Ok(()));
-                                ok
-                            }
-                            Err(_) => {
-                                std::env::set_var("ORT_DISABLE_COREML", "1");
-                                match try_init(model_type_clone) {
-                                    Ok(ok) => {
-                                        let _ = ready_tx.send(Ok(()));
-                                        ok
-                                    }
-                                    Err(e) => {
-                                        let _ = ready_tx.send(Err(anyhow::anyhow!(e)));
-                                        return;
-                                    }
-                                }
-                            }
-                        }
+                let mut text_embedding = match TextEmbedding::try_new(init_options(device, false)) {
+                    Ok(ok) => {
+                        let _ = ready_tx.send(Ok(()));
+                        ok
                     }
-
-                    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
-                    {
-                        match try_init(model_type_clone) {
-                            Ok(ok) => {
-                                let _ = ready_tx.send(Ok(()));
-                                ok
-                            }
-                            Err(e) => {
-                                let _ = ready_tx.send(Err(anyhow::anyhow!(e)));
-                                return;
-                            }
-                        }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(anyhow::anyhow!(e)));
+                        return;
                     }
                 };
 
@@ -135,8 +171,12 @@ impl EmbeddingPool {
                     let _ = job.resp.send(result.map_err(|e| anyhow::anyhow!(e)));
                 }
             });
+            (tx, ready_rx)
+        };
 
-            senders.push(tx);
+        for _ in 0..size {
+            let (tx, ready_rx) = spawn_worker();
+            senders.push(Mutex::new(tx));
             readiness_rxs.push(ready_rx);
         }
 
@@ -148,99 +188,473 @@ impl EmbeddingPool {
         pb.finish_and_clear();
 
         Ok(Self {
+            stopped: senders.iter().map(|_| AtomicBool::new(false)).collect(),
             senders,
             next: AtomicUsize::new(0),
+            spawn_worker: Box::new(spawn_worker),
+            device,
+            tokenizer,
         })
     }
 
-    pub async fn embed(&self, texts: Vec<String>, batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+    /// Tokens in each of `texts`, special tokens included, before truncation.
+    pub fn token_counts(&self, texts: &[String]) -> Result<Vec<usize>> {
+        let inputs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let encodings = self
+            .tokenizer
+            .encode_batch(inputs, true)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(encodings.iter().map(|e| e.len()).collect())
+    }
+
+    /// Workers that haven't stopped.
+    pub fn live_workers(&self) -> usize {
+        self.stopped.iter().filter(|h| !h.load(Ordering::Relaxed)).count()
+    }
+
+    fn pick_worker(&self) -> Result<usize> {
         let workers = self.senders.len();
-        let idx = self.next.fetch_add(1, Ordering::Relaxed) % workers;
-        let (resp_tx, resp_rx) = oneshot::channel();
+        for _ in 0..workers {
+            let idx = self.next.fetch_add(1, Ordering::Relaxed) % workers;
+            if !self.stopped[idx].load(Ordering::Relaxed) {
+                return Ok(idx);
+            }
+        }
+        anyhow::bail!("Every embedding worker has stopped")
+    }
 
-        self.senders[idx]
-            .send(EmbeddingJob {
-                texts,
-                batch_size,
-                resp: resp_tx,
-            })
-            .await
-            .map_err(|_| anyhow::anyhow!("Failed to send job to worker"))?;
+    pub async fn embed(&self, texts: Vec<String>, batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+        let idx = self.pick_worker()?;
+        self.embed_on(idx, texts, batch_size).await
+    }
 
-        resp_rx.await?
+    /// [`Self::embed`] with a watchdog: if the worker hasn't answered within
+    /// `timeout`, `None` is returned so the caller can retry elsewhere, and
+    /// the worker is replaced by a fresh one. ONNX Runtime can't interrupt a
+    /// running session, so the stuck thread is left behind rather than killed.
+    pub async fn embed_within(
+        &self,
+        texts: Vec<String>,
+        timeout: Duration,
+    ) -> Result<Option<Vec<Vec<f32>>>> {
+        let idx = self.pick_worker()?;
+        match tokio::time::timeout(timeout, self.embed_on(idx, texts, None)).await {
+            Ok(result) => result.map(Some),
+            Err(_) => {
+                self.replace_worker(idx);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Start a worker in `idx`'s place. Its model loads on its own thread;
+    /// jobs queue until it is ready.
+    fn replace_worker(&self, idx: usize) {
+        let (tx, _ready) = (self.spawn_worker)();
+        *self.senders[idx].lock().unwrap() = tx;
+        warn!(worker = idx, "Replaced a hung embedding worker");
+    }
+
+    async fn embed_on(
+        &self,
+        idx: usize,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        let sender = self.senders[idx].lock().unwrap().clone();
+
+        let job = EmbeddingJob {
+            texts,
+            batch_size,
+            resp: resp_tx,
+        };
+        // A worker that drops its queue or a job has gone away for good.
+        let gone = || {
+            self.stopped[idx].store(true, Ordering::Relaxed);
+            anyhow::anyhow!("Embedding worker {} has stopped", idx)
+        };
+        sender.send(job).await.map_err(|_| gone())?;
+        resp_rx.await.map_err(|_| gone())?
     }
 }
 
-/// EmbeddingGemma prompt prefixes.
-/// See: https://huggingface.co/google/embeddinggemma-300m
-const DOCUMENT_PREFIX: &str = "title: none | text: ";
-const QUERY_PREFIX: &str = "task: search result | query: ";
+/// Where batches run: a pool of local model workers, or a remote endpoint.
+enum Engine {
+    Local(Arc<EmbeddingPool>),
+    Remote(backend::Remote),
+}
+
+impl Engine {
+    /// Start `spec` and check that it produces the preset's dimensions.
+    async fn start(
+        model: &'static ModelSpec,
+        spec: &BackendSpec,
+        max_length: usize,
+    ) -> Result<Self> {
+        let engine = match spec.device {
+            Some(device) => {
+                // Use more workers if available
+                let pool_size = std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1);
+                info!(model = model.name, pool_size, "Initializing embedding pool");
+                let pool = EmbeddingPool::new(model, max_length, device, pool_size)?;
+                Engine::Local(Arc::new(pool))
+            }
+            None => Engine::Remote(backend::Remote::new(spec, model)?),
+        };
 
-/// Apply the EmbeddingGemma document prefix to a text.
-pub fn format_document(text: &str) -> String {
-    format!("{DOCUMENT_PREFIX}{text}")
+        // Probe dimensions
+        let probe = engine.embed(vec![model.format_document("hello")], None).await?;
+        let dims = probe.first().map_or(0, |v| v.len());
+        if dims != model.dims {
+            anyhow::bail!(
+                "{} produced {}-dimensional embeddings, the registry expects {}",
+                model.name,
+                dims,
+                model.dims
+            );
+        }
+        Ok(engine)
+    }
+
+    /// Embed `texts`, or `None` if `timeout` passed first (see
+    /// [`EmbeddingPool::embed_within`]).
+    async fn embed_within(
+        &self,
+        texts: Vec<String>,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Vec<Vec<f32>>>> {
+        match (self, timeout) {
+            (Engine::Local(pool), Some(timeout)) => pool.embed_within(texts, timeout).await,
+            (Engine::Local(pool), None) => pool.embed(texts, None).await.map(Some),
+            (Engine::Remote(remote), _) => match remote.embed(texts, timeout).await {
+                Err(e) if is_timeout(&e) => Ok(None),
+                result => result.map(Some),
+            },
+        }
+    }
+
+    async fn embed(&self, texts: Vec<String>, timeout: Option<Duration>) -> Result<Vec<Vec<f32>>> {
+        match self {
+            Engine::Local(pool) => pool.embed(texts, None).await,
+            Engine::Remote(remote) => remote.embed(texts, timeout).await,
+        }
+    }
+
+    /// `(live, total)` workers; a remote endpoint counts as one that never stops.
+    fn workers(&self) -> (usize, usize) {
+        match self {
+            Engine::Local(pool) => (pool.live_workers(), pool.senders.len()),
+            Engine::Remote(_) => (1, 1),
+        }
+    }
+
+    fn device(&self) -> Option<Device> {
+        match self {
+            Engine::Local(pool) => Some(pool.device),
+            Engine::Remote(_) => None,
+        }
+    }
 }
 
-/// Apply the EmbeddingGemma query prefix to a text.
-pub fn format_query(text: &str) -> String {
-    format!("{QUERY_PREFIX}{text}")
+/// Whether a remote request ran past its timeout.
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| {
+        c.downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout())
+    })
 }
 
+/// Start backends from the front of `queue` until one initializes. Errors
+/// only if the last one tried failed; an empty queue gives `None`.
+async fn start_next(
+    model: &'static ModelSpec,
+    queue: &mut std::collections::VecDeque<BackendSpec>,
+    max_length: usize,
+) -> Result<Option<(Engine, String)>> {
+    while let Some(spec) = queue.pop_front() {
+        let load_start = Instant::now();
+        match Engine::start(model, &spec, max_length).await {
+            Ok(engine) => {
+                // `auto` is named after the device it picked
+                let name = match (&spec.name, engine.device()) {
+                    (None, Some(device)) => device.as_str().to_string(),
+                    _ => spec.label(),
+                };
+                info!(
+                    secs = load_start.elapsed().as_secs_f64(),
+                    dims = model.dims,
+                    backend = %name,
+                    max_seq_len = max_length,
+                    "Pool initialized"
+                );
+                return Ok(Some((engine, name)));
+            }
+            Err(e) if queue.is_empty() => return Err(e),
+            Err(e) => warn!(backend = %spec.label(), "Embedding backend failed to start: {e:#}"),
+        }
+    }
+    Ok(None)
+}
+
+type BatchObserver = dyn FnMut(&BatchStats) + Send + Sync;
+
+/// Batched document embedding over an [`EmbeddingPool`] or a chain of
+/// backends (see [`backend`]), with the recovery behaviour of Pass 3:
+/// hung-batch timeouts, throughput pacing, a deadline and a stop flag, each
+/// opted into with a `with_*` builder method.
 pub struct Embedder {
-    pub pool: Arc<EmbeddingPool>,
+    engine: Engine,
+    /// Name of the backend `engine` runs, recorded with its vectors.
+    backend: String,
+    /// Backends not tried yet, in order.
+    fallbacks: std::collections::VecDeque<BackendSpec>,
+    max_failures: usize,
+    model: &'static ModelSpec,
+    /// `--batch-size`, which a new backend starts from again.
+    configured_batch_size: usize,
     batch_size: usize,
+    batch_timeout: Option<Duration>,
+    cooldown: Option<Duration>,
+    packing: bool,
+    deadline: Option<Instant>,
+    /// Duration of the latest batch embedded, across calls, so the deadline
+    /// stops a pass fed in windows before a batch that would overrun it
+    /// rather than after.
+    last_batch: Duration,
+    stop: Option<Arc<AtomicBool>>,
+    observer: Option<Box<BatchObserver>>,
+    max_length: usize,
     dims: usize,
 }
 
 impl Embedder {
-    pub async fn new(batch_size: usize) -> Result<Self> {
-        let load_start = std::time::Instant::now();
+    /// `max_length` overrides the preset's tokens per text (`--max-seq-len`).
+    pub async fn new(
+        model: &'static ModelSpec,
+        device: Device,
+        batch_size: usize,
+        max_length: Option<usize>,
+    ) -> Result<Self> {
+        let chain = ChainConfig {
+            backends: vec![BackendSpec::local(device)],
+            ..ChainConfig::default()
+        };
+        Self::with_chain(model, &chain, batch_size, max_length).await
+    }
 
-        println!("  Initializing embedding pool (EmbeddingGemma300M)...");
+    /// Start the first backend of `chain` that initializes; the rest are
+    /// fallen back to, in order, if it keeps failing mid-run.
+    pub async fn with_chain(
+        model: &'static ModelSpec,
+        chain: &ChainConfig,
+        batch_size: usize,
+        max_length: Option<usize>,
+    ) -> Result<Self> {
+        let max_length = model.seq_len(max_length)?;
+        let mut fallbacks: std::collections::VecDeque<BackendSpec> =
+            chain.backends.iter().cloned().collect();
+        let (engine, backend) = start_next(model, &mut fallbacks, max_length)
+            .await?
+            .context("No embedding backend could be started")?;
+        Ok(Self {
+            engine,
+            backend,
+            fallbacks,
+            max_failures: chain.max_consecutive_failures,
+            model,
+            configured_batch_size: batch_size,
+            batch_size,
+            batch_timeout: None,
+            cooldown: None,
+            packing: false,
+            deadline: None,
+            last_batch: Duration::ZERO,
+            stop: None,
+            observer: None,
+            max_length,
+            dims: model.dims,
+        })
+    }
 
-        // Use more workers if available
-        let pool_size = std::thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or(1);
+    /// Abandon any batch that takes longer than `timeout` and carry on with
+    /// a smaller batch size (see [`EmbeddingPool::embed_within`]).
+    pub fn with_batch_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.batch_timeout = timeout;
+        self
+    }
 
-        println!("  Pool size: {}", pool_size);
+    /// Pause for `cooldown` when throughput collapses, and shrink the batch
+    /// size if it stays down (see [`pacing::Pacer`]).
+    pub fn with_pacing(mut self, cooldown: Option<Duration>) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
 
-        let pool = Arc::new(EmbeddingPool::new(pool_size)?);
+    /// Size [`Self::embed_batched`]'s batches by padded tokens within a length
+    /// bucket instead of by text count (see [`packing`]).
+    pub fn with_packing(mut self, packing: bool) -> Self {
+        self.packing = packing;
+        self
+    }
 
-        // Probe dimensions
-        let probe = pool.embed(vec![format_document("hello")], None).await?;
-        let dims = probe[0].len();
+    /// Stop starting new batches once the last batch's duration would take
+    /// the pass past `deadline`; the texts not reached are counted in
+    /// [`BatchOutcome::deferred`].
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
 
-        println!(
-            "  Pool initialized in {:.2}s (dims={dims})",
-            load_start.elapsed().as_secs_f64()
-        );
+    /// Stop starting new batches once `stop` is set (by Ctrl-C); the batch in
+    /// flight still finishes and is written.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
 
-        Ok(Self {
-            pool,
-            batch_size,
-            dims,
-        })
+    /// Call `observer` after every batch, embedded or not, e.g. to write
+    /// live telemetry.
+    pub fn with_observer(mut self, observer: impl FnMut(&BatchStats) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Embed texts, already prefixed, on the current backend, e.g. a
+    /// search query.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        self.engine.embed(texts, None).await
+    }
+
+    /// Tokens the current backend's model reads for each document in
+    /// `texts`, prompt prefix included, before truncation at
+    /// [`Self::max_length`]. `None` on a remote backend, whose tokenizer
+    /// isn't known here.
+    pub fn token_counts(&self, texts: &[String]) -> Result<Option<Vec<usize>>> {
+        let Engine::Local(pool) = &self.engine else {
+            return Ok(None);
+        };
+        let prefixed: Vec<String> = texts
+            .iter()
+            .map(|t| self.model.format_document(t))
+            .collect();
+        pool.token_counts(&prefixed).map(Some)
+    }
+
+    /// Embed a document of `tokens` tokens that is longer than
+    /// [`Self::max_length`] whole: split it into pieces that fit and pool
+    /// their vectors (see [`truncation`]). Returns the vector and the number
+    /// of pieces. Needs a local backend to count the pieces' tokens.
+    pub async fn embed_whole(&self, text: &str, tokens: usize) -> Result<(Vec<f32>, usize)> {
+        let count = |pieces: &[String]| {
+            self.token_counts(pieces)?
+                .context("Repairing a truncated text needs a local backend")
+        };
+        let pieces = truncation::split_to_fit(text, tokens, self.max_length, count)?;
+        let (texts, weights): (Vec<String>, Vec<usize>) = pieces
+            .into_iter()
+            .map(|(piece, n)| (self.model.format_document(&piece), n))
+            .unzip();
+        let vectors = self.engine.embed(texts, self.batch_timeout).await?;
+        Ok((truncation::pool(&vectors, &weights), weights.len()))
+    }
+
+    /// Name of the backend in use.
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+
+    /// The device the current backend runs on; `None` for a remote one.
+    pub fn device(&self) -> Option<Device> {
+        self.engine.device()
+    }
+
+    /// Move to the next backend that starts, if there is one.
+    async fn fall_back(&mut self, reason: &str) -> bool {
+        let from = std::mem::take(&mut self.backend);
+        let next = start_next(self.model, &mut self.fallbacks, self.max_length)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("No fallback embedding backend could be started: {e:#}");
+                None
+            });
+        match next {
+            Some((engine, backend)) => {
+                warn!(from = %from, to = %backend, "Falling back to the next embedding backend: {reason}");
+                self.engine = engine;
+                self.backend = backend;
+                true
+            }
+            None => {
+                self.backend = from;
+                false
+            }
+        }
+    }
+
+    pub fn model(&self) -> &'static ModelSpec {
+        self.model
+    }
+
+    /// Tokens per text the model sees; longer input is truncated.
+    pub fn max_length(&self) -> usize {
+        self.max_length
     }
 
     pub fn model_dimensions(&self) -> usize {
         self.dims
     }
 
-    /// Embed texts in batches, calling the callback with (node_ids, embeddings)
-    /// after each batch so results can be written incrementally.
+    /// Embed texts in batches, calling the callback with (node_ids,
+    /// embeddings, backend) after each batch so results can be written
+    /// incrementally. A batch the model fails on is skipped and its nodes
+    /// returned in [`BatchOutcome::failed`]; out-of-memory and callback errors
+    /// still abort, unless the chain has a backend left to fall back to.
     pub async fn embed_batched<F>(
         &mut self,
         node_ids: &[i64],
         texts: &[String],
+        on_batch: F,
+    ) -> Result<BatchOutcome>
+    where
+        F: FnMut(&[i64], &[Vec<f32>], &str) -> Result<()>,
+    {
+        self.embed_in_batches(node_ids, texts, self.batch_size, self.packing, on_batch)
+            .await
+    }
+
+    /// Re-embed texts one at a time, e.g. the failures of [`Self::embed_batched`],
+    /// so one bad text can't take a whole batch down with it.
+    pub async fn retry_singly<F>(
+        &mut self,
+        node_ids: &[i64],
+        texts: &[String],
+        on_batch: F,
+    ) -> Result<BatchOutcome>
+    where
+        F: FnMut(&[i64], &[Vec<f32>], &str) -> Result<()>,
+    {
+        self.embed_in_batches(node_ids, texts, 1, false, on_batch)
+            .await
+    }
+
+    async fn embed_in_batches<F>(
+        &mut self,
+        node_ids: &[i64],
+        texts: &[String],
+        batch_size: usize,
+        pack: bool,
         mut on_batch: F,
-    ) -> Result<usize>
+    ) -> Result<BatchOutcome>
     where
-        F: FnMut(&[i64], &[Vec<f32>]) -> Result<()>,
+        F: FnMut(&[i64], &[Vec<f32>], &str) -> Result<()>,
     {
         assert_eq!(node_ids.len(), texts.len());
+        let mut outcome = BatchOutcome::default();
         if texts.is_empty() {
-            return Ok(0);
+            return Ok(outcome);
         }
 
         let pb = ProgressBar::new(texts.len() as u64);
@@ -250,39 +664,326 @@ impl Embedder {
                 .unwrap(),
         );
 
-        let total_batches = (texts.len() + self.batch_size - 1) / self.batch_size;
-        let mut total_written = 0;
+        let max_length = self.max_length;
+        let tokens: Option<Vec<usize>> = pack.then(|| {
+            texts
+                .iter()
+                .map(|t| packing::estimated_tokens(t, max_length))
+                .collect()
+        });
+        // Batches left from `offset` at `batch_size`
+        let batches_from = |offset: usize, batch_size: usize| match &tokens {
+            Some(tokens) => packing::count_batches(tokens, offset, batch_size, max_length),
+            None => (texts.len() - offset).div_ceil(batch_size),
+        };
+
+        let requested_batch_size = batch_size;
+        let mut batch_size = batch_size;
+        let mut total_batches = batches_from(0, batch_size);
+        let mut pacer = self.cooldown.map(Pacer::new);
+        // Failed or hung batches in a row on the current backend
+        let mut failures = 0;
 
         let mut offset = 0;
         let mut batch_num = 0;
         while offset < texts.len() {
-            let end = (offset + self.batch_size).min(texts.len());
+            if self.deadline.is_some_and(|d| Instant::now() + self.last_batch >= d) {
+                outcome.deferred = texts.len() - offset;
+                pb.abandon_with_message("Time budget reached");
+                return Ok(outcome);
+            }
+            if self.stop.as_ref().is_some_and(|s| s.load(Ordering::SeqCst)) {
+                outcome.deferred = texts.len() - offset;
+                outcome.interrupted = true;
+                pb.abandon_with_message("Interrupted");
+                return Ok(outcome);
+            }
+            let all_stopped = self.engine.workers().0 == 0;
+            if all_stopped || (failures >= self.max_failures && !self.fallbacks.is_empty()) {
+                let reason = if all_stopped {
+                    "every worker has stopped".to_string()
+                } else {
+                    format!("{} failed batches in a row", failures)
+                };
+                if self.fall_back(&reason).await {
+                    outcome.switches += 1;
+                    // The new backend starts from the configured batch size and
+                    // a fresh throughput baseline
+                    batch_size = requested_batch_size;
+                    self.batch_size = self.configured_batch_size;
+                    total_batches = batch_num + batches_from(offset, batch_size);
+                    pacer = self.cooldown.map(Pacer::new);
+                    self.last_batch = Duration::ZERO;
+                } else if all_stopped {
+                    anyhow::bail!("Every embedding worker has stopped; giving up");
+                }
+                failures = 0;
+            }
+            let end = match &tokens {
+                Some(tokens) => packing::batch_end(tokens, offset, batch_size, max_length),
+                None => (offset + batch_size).min(texts.len()),
+            };
             let text_chunk = texts[offset..end].to_vec();
             let id_chunk = &node_ids[offset..end];
             batch_num += 1;
 
             pb.set_message(format!("Batch {}/{}", batch_num, total_batches));
 
-            let _batch_start = std::time::Instant::now();
-            // Apply EmbeddingGemma document prefix to each text
-            let prefixed: Vec<String> = text_chunk.iter().map(|t| format_document(t)).collect();
-            let embeddings = self
-                .pool
-                .embed(prefixed, None)
-                .await
-                .map_err(|e| anyhow::anyhow!("Embedding batch failed: {e}"))?;
-
-            let vecs: Vec<Vec<f32>> = embeddings;
-
-            on_batch(id_chunk, &vecs)?;
-            total_written += vecs.len();
+            // Apply the model's document prefix to each text
+            let prefixed: Vec<String> = text_chunk.iter().map(|t| self.model.format_document(t)).collect();
+            let batch_start = Instant::now();
+            let result = self.engine.embed_within(prefixed, self.batch_timeout).await;
+            match result {
+                Ok(Some(vecs)) => {
+                    failures = 0;
+                    on_batch(id_chunk, &vecs, &self.backend)?;
+                    outcome.written += vecs.len();
+                    self.last_batch = batch_start.elapsed();
+                    self.observe("ok", &text_chunk, self.last_batch, batch_size);
+                    debug!(
+                        batch = batch_num,
+                        batches = total_batches,
+                        texts = vecs.len(),
+                        secs = batch_start.elapsed().as_secs_f64(),
+                        "Batch embedded"
+                    );
+                    let chars = text_chunk.iter().map(|t| t.len()).sum();
+                    match pacer.as_mut().map(|p| p.record(chars, batch_start.elapsed())) {
+                        Some(Pace::Cooldown(pause)) => {
+                            outcome.cooldowns += 1;
+                            pb.suspend(|| {
+                                warn!(
+                                    pause_secs = pause.as_secs_f64(),
+                                    "Throughput collapsed (thermal throttling?); pausing"
+                                )
+                            });
+                            tokio::time::sleep(pause).await;
+                        }
+                        Some(Pace::Shrink) if batch_size > 1 => {
+                            batch_size = (batch_size / 2).max(1);
+                            self.batch_size = self.batch_size.min(batch_size);
+                            total_batches = batch_num + batches_from(end, batch_size);
+                            pb.suspend(|| {
+                                warn!(batch_size, "Throughput still down after cooling; shrinking batches")
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(None) => {
+                    outcome.hung += 1;
+                    failures += 1;
+                    self.observe("hung", &text_chunk, batch_start.elapsed(), batch_size);
+                    pb.suspend(|| {
+                        warn!(
+                            batch = batch_num,
+                            batches = total_batches,
+                            secs = batch_start.elapsed().as_secs_f64(),
+                            texts = id_chunk.len(),
+                            longest_chars = text_chunk.iter().map(|t| t.len()).max().unwrap_or(0),
+                            live_workers = self.engine.workers().0,
+                            workers = self.engine.workers().1,
+                            backend = %self.backend,
+                            "Batch hung: no result before the timeout"
+                        )
+                    });
+                    if failures >= self.max_failures && !self.fallbacks.is_empty() {
+                        // Retry the same texts on the next backend
+                        continue;
+                    }
+                    if batch_size > 1 {
+                        // Retry the same texts in smaller batches
+                        batch_size = (batch_size / 2).max(1);
+                        self.batch_size = self.batch_size.min(batch_size);
+                        total_batches = batch_num + batches_from(offset, batch_size);
+                        pb.suspend(|| info!(batch_size, "Continuing with a smaller batch size"));
+                        continue;
+                    }
+                    let error = "timed out".to_string();
+                    outcome
+                        .failed
+                        .extend(id_chunk.iter().map(|&id| (id, error.clone())));
+                }
+                Err(e) if is_out_of_memory(&e) => {
+                    if self.fallbacks.is_empty() {
+                        anyhow::bail!("Embedding batch failed: {e}");
+                    }
+                    self.observe("failed", &text_chunk, batch_start.elapsed(), batch_size);
+                    pb.suspend(|| warn!(backend = %self.backend, "Out of memory: {e}"));
+                    failures = self.max_failures;
+                    continue;
+                }
+                Err(e) => {
+                    failures += 1;
+                    self.observe("failed", &text_chunk, batch_start.elapsed(), batch_size);
+                    pb.suspend(|| {
+                        warn!(
+                            batch = batch_num,
+                            batches = total_batches,
+                            texts = id_chunk.len(),
+                            backend = %self.backend,
+                            "Batch failed, queued for retry: {e}"
+                        )
+                    });
+                    if failures >= self.max_failures && !self.fallbacks.is_empty() {
+                        continue;
+                    }
+                    let error = format!("{e:#}");
+                    outcome
+                        .failed
+                        .extend(id_chunk.iter().map(|&id| (id, error.clone())));
+                }
+            }
 
             pb.inc(text_chunk.len() as u64);
             offset = end;
         }
 
         pb.finish_with_message("Embedding complete");
-        Ok(total_written)
+        Ok(outcome)
+    }
+
+    fn observe(&mut self, status: &'static str, texts: &[String], elapsed: Duration, batch_size: usize) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer(&BatchStats {
+                status,
+                texts,
+                elapsed,
+                batch_size,
+                backend: &self.backend,
+                device: self.engine.device(),
+            });
+        }
     }
 }
 
+/// One batch, as seen by [`Embedder::with_observer`].
+pub struct BatchStats<'a> {
+    /// `ok`, `failed` (queued for retry) or `hung` (abandoned by the
+    /// watchdog; retried in smaller batches while the size allows).
+    pub status: &'static str,
+    /// The batch's texts, without the model's prefix.
+    pub texts: &'a [String],
+    pub elapsed: Duration,
+    /// The batch size in force, before any shrinking this batch caused.
+    pub batch_size: usize,
+    /// The backend that ran the batch.
+    pub backend: &'a str,
+    /// Its device; `None` for a remote backend.
+    pub device: Option<Device>,
+}
+
+/// What a run of [`Embedder::embed_batched`] got through.
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    pub written: usize,
+    /// `(node_id, error)` for every text in a batch the model failed on.
+    pub failed: Vec<(i64, String)>,
+    /// Batches abandoned by the watchdog.
+    pub hung: usize,
+    /// Pauses taken because throughput collapsed.
+    pub cooldowns: usize,
+    /// Texts not attempted because the deadline passed or the run was
+    /// interrupted.
+    pub deferred: usize,
+    /// Whether the stop flag ended the run early.
+    pub interrupted: bool,
+    /// Moves to the next backend of the chain.
+    pub switches: usize,
+}
+
+/// Allocation failures are a sign of batches too large for the machine, not
+/// of a bad text, so they abort rather than queue every batch for retry.
+fn is_out_of_memory(err: &anyhow::Error) -> bool {
+    let msg = format!("{err:#}").to_lowercase();
+    ["out of memory", "failed to allocate", "bad_alloc"]
+        .iter()
+        .any(|needle| msg.contains(needle))
+        || msg.split(|c: char| !c.is_alphanumeric()).any(|w| w == "oom")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pool of `size` workers that answer at once, except that a job for
+    /// the text "stuck" hangs its worker for good. `spawned` counts workers
+    /// started.
+    fn test_pool(size: usize, spawned: Arc<AtomicUsize>) -> EmbeddingPool {
+        let spawn_worker = move || {
+            spawned.fetch_add(1, Ordering::Relaxed);
+            let (tx, mut rx) = mpsc::channel::<EmbeddingJob>(1);
+            let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+            let _ = ready_tx.send(Ok(()));
+            tokio::spawn(async move {
+                while let Some(job) = rx.recv().await {
+                    if job.texts == ["stuck"] {
+                        // Hold the job, as a worker stuck inside the model does.
+                        std::future::pending::<()>().await;
+                        drop(job);
+                    } else {
+                        let _ = job.resp.send(Ok(vec![vec![1.0]; job.texts.len()]));
+                    }
+                }
+            });
+            (tx, ready_rx)
+        };
+        EmbeddingPool {
+            senders: (0..size).map(|_| Mutex::new(spawn_worker().0)).collect(),
+            stopped: (0..size).map(|_| AtomicBool::new(false)).collect(),
+            next: AtomicUsize::new(0),
+            spawn_worker: Box::new(spawn_worker),
+            device: Device::Cpu,
+            tokenizer: Tokenizer::new(tokenizers::models::wordlevel::WordLevel::default()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_replaces_hung_workers() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let pool = test_pool(2, spawned.clone());
+        let timeout = Duration::from_millis(50);
+        let text = |t: &str| vec![t.to_string()];
+
+        assert!(pool.embed_within(text("a"), timeout).await.unwrap().is_some());
+        // Lose more workers than the pool has; each is replaced.
+        for _ in 0..5 {
+            assert!(pool.embed_within(text("stuck"), timeout).await.unwrap().is_none());
+        }
+        assert_eq!(spawned.load(Ordering::Relaxed), 2 + 5);
+        assert_eq!(pool.live_workers(), 2);
+        for _ in 0..3 {
+            assert!(pool.embed_within(text("a"), timeout).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stopped_workers_are_skipped() {
+        let pool = test_pool(2, Arc::default());
+        // A worker whose queue is gone, as when its model fails to load.
+        *pool.senders[1].lock().unwrap() = mpsc::channel(1).0;
+        let timeout = Duration::from_millis(50);
+        let texts = || vec!["a".to_string()];
+        let results = [
+            pool.embed_within(texts(), timeout).await,
+            pool.embed_within(texts(), timeout).await,
+        ];
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+        assert_eq!(pool.live_workers(), 1);
+        for _ in 0..3 {
+            assert!(pool.embed_within(texts(), timeout).await.unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn test_out_of_memory_errors_abort() {
+        assert!(is_out_of_memory(&anyhow::anyhow!(
+            "Failed to allocate memory for requested buffer of size 4096"
+        )));
+        assert!(is_out_of_memory(&anyhow::anyhow!("CUDA error: out of memory")));
+        assert!(is_out_of_memory(&anyhow::anyhow!("worker killed (OOM)")));
+        assert!(!is_out_of_memory(&anyhow::anyhow!(
+            "Non-zero status code returned while running Gather node"
+        )));
+    }
+}
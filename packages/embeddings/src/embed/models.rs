@@ -0,0 +1,171 @@
+//! Registry of the embedding models a build can run. `--model` picks a preset
+//! by name; the preset's `model_id` is what lands in `model_info.model_name`,
+//! so query-time embedding can find the same preset again.
+
+use anyhow::{bail, Result};
+use fastembed::{EmbeddingModel, Pooling};
+
+/// One supported embedding model and how to feed it.
+#[derive(Debug)]
+pub struct ModelSpec {
+    /// `--model` value.
+    pub name: &'static str,
+    /// Hugging Face id, recorded as `model_info.model_name`.
+    pub model_id: &'static str,
+    pub backend: EmbeddingModel,
+    pub dims: usize,
+    /// Default tokens per text passed to the model (`--max-seq-len`); longer
+    /// input is truncated.
+    pub max_length: usize,
+    /// Longest input the model supports; `--max-seq-len` can't exceed it.
+    pub context_length: usize,
+    pub pooling: Pooling,
+    document_prefix: &'static str,
+    query_prefix: &'static str,
+}
+
+pub const DEFAULT_MODEL: &str = "embeddinggemma-300m";
+
+pub static MODELS: [ModelSpec; 5] = [
+    // See: https://huggingface.co/google/embeddinggemma-300m
+    ModelSpec {
+        name: "embeddinggemma-300m",
+        model_id: "onnx-community/embeddinggemma-300m-ONNX",
+        backend: EmbeddingModel::EmbeddingGemma300M,
+        dims: 768,
+        max_length: 512,
+        context_length: 2048,
+        pooling: Pooling::Mean,
+        document_prefix: "title: none | text: ",
+        query_prefix: "task: search result | query: ",
+    },
+    ModelSpec {
+        name: "nomic-embed-text-v1.5",
+        model_id: "nomic-ai/nomic-embed-text-v1.5",
+        backend: EmbeddingModel::NomicEmbedTextV15,
+        dims: 768,
+        max_length: 8192,
+        context_length: 8192,
+        pooling: Pooling::Mean,
+        document_prefix: "search_document: ",
+        query_prefix: "search_query: ",
+    },
+    ModelSpec {
+        name: "bge-small-en-v1.5",
+        model_id: "Xenova/bge-small-en-v1.5",
+        backend: EmbeddingModel::BGESmallENV15,
+        dims: 384,
+        max_length: 512,
+        context_length: 512,
+        pooling: Pooling::Cls,
+        document_prefix: "",
+        query_prefix: "Represent this sentence for searching relevant passages: ",
+    },
+    ModelSpec {
+        name: "all-minilm-l6-v2",
+        model_id: "Qdrant/all-MiniLM-L6-v2-onnx",
+        backend: EmbeddingModel::AllMiniLML6V2,
+        dims: 384,
+        max_length: 256,
+        context_length: 512,
+        pooling: Pooling::Mean,
+        document_prefix: "",
+        query_prefix: "",
+    },
+    ModelSpec {
+        name: "multilingual-e5-small",
+        model_id: "intfloat/multilingual-e5-small",
+        backend: EmbeddingModel::MultilingualE5Small,
+        dims: 384,
+        max_length: 512,
+        context_length: 512,
+        pooling: Pooling::Mean,
+        document_prefix: "passage: ",
+        query_prefix: "query: ",
+    },
+];
+
+/// Preset names, for `--model` help and possible values.
+pub fn names() -> Vec<&'static str> {
+    MODELS.iter().map(|m| m.name).collect()
+}
+
+/// Look up a preset by `--model` name or by recorded `model_id`.
+pub fn find(name: &str) -> Result<&'static ModelSpec> {
+    match MODELS.iter().find(|m| m.name == name || m.model_id == name) {
+        Some(spec) => Ok(spec),
+        None => bail!(
+            "Unknown embedding model {:?} (known: {})",
+            name,
+            names().join(", ")
+        ),
+    }
+}
+
+impl ModelSpec {
+    /// `--max-seq-len`, defaulting to the preset's and capped by its context.
+    pub fn seq_len(&self, requested: Option<usize>) -> Result<usize> {
+        match requested {
+            None => Ok(self.max_length),
+            Some(0) => bail!("--max-seq-len must be at least 1"),
+            Some(n) if n > self.context_length => bail!(
+                "--max-seq-len {} exceeds {}'s {}-token context",
+                n,
+                self.name,
+                self.context_length
+            ),
+            Some(n) => Ok(n),
+        }
+    }
+
+    pub fn document_prefix(&self) -> &'static str {
+        self.document_prefix
+    }
+
+    pub fn format_document(&self, text: &str) -> String {
+        format!("{}{}", self.document_prefix, text)
+    }
+
+    pub fn format_query(&self, text: &str) -> String {
+        format!("{}{}", self.query_prefix, text)
+    }
+
+    pub fn pooling_name(&self) -> &'static str {
+        match self.pooling {
+            Pooling::Cls => "cls",
+            Pooling::Mean => "mean",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastembed::TextEmbedding;
+
+    #[test]
+    fn test_presets_match_fastembed() {
+        for spec in &MODELS {
+            let info = TextEmbedding::get_model_info(&spec.backend).unwrap();
+            assert_eq!(info.model_code, spec.model_id, "{}", spec.name);
+            assert_eq!(info.dim, spec.dims, "{}", spec.name);
+            assert_eq!(
+                TextEmbedding::get_default_pooling_method(&spec.backend),
+                Some(spec.pooling.clone()),
+                "{}",
+                spec.name
+            );
+        }
+        assert_eq!(find(DEFAULT_MODEL).unwrap().name, DEFAULT_MODEL);
+        assert_eq!(
+            find("onnx-community/embeddinggemma-300m-ONNX").unwrap().name,
+            DEFAULT_MODEL
+        );
+        assert!(find("Octen-Embedding-0.6B-INT4-ONNX").is_err());
+
+        let gemma = find(DEFAULT_MODEL).unwrap();
+        assert_eq!(gemma.seq_len(None).unwrap(), 512);
+        assert_eq!(gemma.seq_len(Some(2048)).unwrap(), 2048);
+        assert!(gemma.seq_len(Some(4096)).is_err());
+    }
+}
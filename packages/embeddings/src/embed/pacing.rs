@@ -0,0 +1,149 @@
+//! Throughput-aware pacing for Pass 3. Laptops running the model on the GPU
+//! for long stretches throttle thermally and batch times can triple; pushing
+//! on at full load keeps them throttled. [`Pacer`] watches rolling throughput
+//! and, when it collapses, first pauses to let the machine cool and then, if
+//! that didn't help, asks for a smaller batch size.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Batches in the rolling throughput window.
+const WINDOW: usize = 8;
+/// Throughput below this fraction of the best window seen counts as collapsed.
+const COLLAPSE_RATIO: f64 = 0.5;
+
+/// What the embedding loop should do before its next batch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pace {
+    Steady,
+    /// Sleep this long, then carry on at the same batch size.
+    Cooldown(Duration),
+    /// A cooldown didn't restore throughput; halve the batch size.
+    Shrink,
+}
+
+pub struct Pacer {
+    cooldown: Duration,
+    /// `(chars, seconds)` of recent successful batches.
+    window: VecDeque<(usize, f64)>,
+    /// Best rolling throughput (chars/s) since the last shrink.
+    peak: f64,
+    /// Whether a cooldown was taken since throughput last looked healthy.
+    cooled: bool,
+}
+
+impl Pacer {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            window: VecDeque::with_capacity(WINDOW),
+            peak: 0.0,
+            cooled: false,
+        }
+    }
+
+    /// Rolling throughput in chars/s, once the window is full.
+    fn rate(&self) -> Option<f64> {
+        if self.window.len() < WINDOW {
+            return None;
+        }
+        let chars: usize = self.window.iter().map(|w| w.0).sum();
+        let secs: f64 = self.window.iter().map(|w| w.1).sum();
+        Some(chars as f64 / secs.max(1e-9))
+    }
+
+    /// Record a finished batch of `chars` input characters. Throughput is
+    /// measured in characters rather than texts so a run of long texts
+    /// isn't mistaken for throttling.
+    pub fn record(&mut self, chars: usize, elapsed: Duration) -> Pace {
+        if self.window.len() == WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back((chars, elapsed.as_secs_f64()));
+        let Some(rate) = self.rate() else {
+            return Pace::Steady;
+        };
+
+        if rate >= self.peak * COLLAPSE_RATIO {
+            self.peak = self.peak.max(rate);
+            self.cooled = false;
+            return Pace::Steady;
+        }
+
+        // Measure afresh after acting, rather than against pre-action batches.
+        self.window.clear();
+        if !self.cooled {
+            self.cooled = true;
+            Pace::Cooldown(self.cooldown)
+        } else {
+            // The smaller batch size sets its own baseline, so one collapse
+            // doesn't shrink the batch all the way to 1.
+            self.peak = rate;
+            self.cooled = false;
+            Pace::Shrink
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse_cools_down_then_shrinks() {
+        let cooldown = Duration::from_secs(30);
+        let mut pacer = Pacer::new(cooldown);
+        let batch =
+            |pacer: &mut Pacer, secs: f64| pacer.record(1000, Duration::from_secs_f64(secs));
+
+        for _ in 0..WINDOW * 2 {
+            assert_eq!(batch(&mut pacer, 1.0), Pace::Steady);
+        }
+        // Slower but not collapsed.
+        for _ in 0..WINDOW {
+            assert_eq!(batch(&mut pacer, 1.5), Pace::Steady);
+        }
+
+        // Thermal throttling: batches take three times as long.
+        let throttled = (0..WINDOW)
+            .map(|_| batch(&mut pacer, 3.0))
+            .find(|p| *p != Pace::Steady);
+        assert_eq!(throttled, Some(Pace::Cooldown(cooldown)));
+
+        // Still throttled after the pause: shrink.
+        let paces: Vec<Pace> = (0..WINDOW).map(|_| batch(&mut pacer, 3.0)).collect();
+        assert_eq!(paces.last(), Some(&Pace::Shrink));
+        // Same speed at the smaller batch size is the new normal.
+        for _ in 0..WINDOW * 4 {
+            assert_eq!(batch(&mut pacer, 3.0), Pace::Steady);
+        }
+    }
+
+    #[test]
+    fn test_recovery_after_cooldown_resets() {
+        let mut pacer = Pacer::new(Duration::from_secs(1));
+        let batch =
+            |pacer: &mut Pacer, secs: f64| pacer.record(1000, Duration::from_secs_f64(secs));
+        for _ in 0..WINDOW {
+            batch(&mut pacer, 1.0);
+        }
+        let throttled = |pacer: &mut Pacer| {
+            (0..WINDOW)
+                .map(|_| batch(pacer, 3.0))
+                .find(|p| *p != Pace::Steady)
+        };
+        assert_eq!(
+            throttled(&mut pacer),
+            Some(Pace::Cooldown(Duration::from_secs(1)))
+        );
+        // Cooled down and back to full speed.
+        for _ in 0..WINDOW {
+            assert_eq!(batch(&mut pacer, 1.0), Pace::Steady);
+        }
+        // A later collapse pauses again rather than shrinking.
+        assert_eq!(
+            throttled(&mut pacer),
+            Some(Pace::Cooldown(Duration::from_secs(1)))
+        );
+    }
+}
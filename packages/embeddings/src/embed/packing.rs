@@ -0,0 +1,89 @@
+//! Length-aware batching for Pass 3. The model pads every text of a batch to
+//! the batch's longest, so a fixed `--batch-size` spends most of its compute
+//! on padding for short sources (court rows, popular names) and leaves the
+//! sequence length unused. With packing, texts of one length bucket share a
+//! batch sized by a padded-token budget of `batch_size × max_length`: short
+//! texts go through many at a time, and no batch ever holds more padded
+//! tokens than a full batch of maximum-length texts.
+//!
+//! Each text still gets its own sequence; concatenating several into one
+//! would need a block-diagonal attention mask that the runtime doesn't take.
+
+use crate::text::chunker::approx_token_count;
+
+/// Smallest length bucket, in tokens.
+const MIN_BUCKET: usize = 32;
+
+/// Model tokens for `text`, estimated from its words: legal text runs about
+/// four subword tokens to three words, plus the two special tokens.
+pub fn estimated_tokens(text: &str, max_length: usize) -> usize {
+    (approx_token_count(text) * 4 / 3 + 2).min(max_length)
+}
+
+/// Power-of-two bucket of a token count; a batch stays in one bucket, so it
+/// pads its texts to at most twice their length.
+fn bucket(tokens: usize) -> usize {
+    tokens.next_power_of_two().max(MIN_BUCKET)
+}
+
+/// End of the batch starting at `offset`: texts of the first one's bucket,
+/// while the batch padded to its longest fits `batch_size × max_length`
+/// tokens. Always takes at least one text.
+pub fn batch_end(tokens: &[usize], offset: usize, batch_size: usize, max_length: usize) -> usize {
+    let budget = batch_size.max(1) * max_length;
+    let first = bucket(tokens[offset]);
+    let mut longest = tokens[offset];
+    let mut end = offset + 1;
+    while let Some(&next) = tokens.get(end) {
+        let width = longest.max(next);
+        if bucket(next) != first || (end - offset + 1) * width > budget {
+            break;
+        }
+        longest = width;
+        end += 1;
+    }
+    end
+}
+
+/// Batches [`batch_end`] makes of `tokens[offset..]`.
+pub fn count_batches(
+    tokens: &[usize],
+    mut offset: usize,
+    batch_size: usize,
+    max_length: usize,
+) -> usize {
+    let mut batches = 0;
+    while offset < tokens.len() {
+        offset = batch_end(tokens, offset, batch_size, max_length);
+        batches += 1;
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_texts_share_a_batch_within_their_bucket() {
+        assert_eq!(estimated_tokens("one two three", 512), 6);
+        assert_eq!(estimated_tokens(&"word ".repeat(1000), 512), 512);
+
+        // 100 court rows of ~40 tokens, then 10 full-length sections
+        let mut tokens = vec![40; 100];
+        tokens.extend([512; 10]);
+        // Budget 4 × 512: 51 rows fit padded to 40
+        assert_eq!(batch_end(&tokens, 0, 4, 512), 51);
+        assert_eq!(batch_end(&tokens, 51, 4, 512), 100);
+        // Long texts get the plain batch size
+        assert_eq!(batch_end(&tokens, 100, 4, 512), 104);
+        assert_eq!(count_batches(&tokens, 0, 4, 512), 5);
+
+        // A longer text in the next bucket starts a new batch
+        let mixed = [20, 30, 40, 60];
+        assert_eq!(batch_end(&mixed, 0, 64, 512), 2);
+        assert_eq!(batch_end(&mixed, 2, 64, 512), 4);
+        // Batch size 1 still packs short texts up to one full sequence
+        assert_eq!(batch_end(&[100, 100, 100, 100, 100, 100], 0, 1, 512), 5);
+    }
+}
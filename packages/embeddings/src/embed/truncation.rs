@@ -0,0 +1,75 @@
+//! Repair for texts longer than the model's sequence length. The tokenizer
+//! cuts such a text at `max_length` tokens, so whatever follows never reaches
+//! the vector. With `--repair-truncated`, Pass 3 instead splits the text into
+//! pieces that fit, embeds each, and stores their token-weighted mean: the
+//! node keeps its id and one vector, and that vector covers all of its text.
+
+use anyhow::Result;
+
+use crate::text::chunker::{approx_token_count, chunk_text};
+
+/// Split `text`, which `count` says is `tokens` tokens long, into pieces of at
+/// most `max_length` tokens each, at sentence boundaries where possible. The
+/// piece size starts from the text's words-per-token ratio and is halved
+/// until every piece fits. Returns each piece with its token count.
+pub fn split_to_fit(
+    text: &str,
+    tokens: usize,
+    max_length: usize,
+    count: impl Fn(&[String]) -> Result<Vec<usize>>,
+) -> Result<Vec<(String, usize)>> {
+    let words = approx_token_count(text).max(1);
+    // 10% headroom for pieces denser than the text as a whole
+    let mut budget = (words * max_length * 9 / 10 / tokens.max(1)).max(1);
+    loop {
+        let pieces: Vec<String> = chunk_text(text, budget, 0)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        let counts = count(&pieces)?;
+        if budget == 1 || counts.iter().all(|&n| n <= max_length) {
+            return Ok(pieces.into_iter().zip(counts).collect());
+        }
+        budget = (budget / 2).max(1);
+    }
+}
+
+/// Mean of `vectors` weighted by `weights`, scaled to unit length like the
+/// model's own output.
+pub fn pool(vectors: &[Vec<f32>], weights: &[usize]) -> Vec<f32> {
+    let dims = vectors.first().map_or(0, |v| v.len());
+    let mut mean = vec![0f32; dims];
+    for (v, &w) in vectors.iter().zip(weights) {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += x * w as f32;
+        }
+    }
+    let norm = mean.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        mean.iter_mut().for_each(|x| *x /= norm);
+    }
+    mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_to_fit_and_pool() {
+        // A "tokenizer" that reads two tokens per word
+        let count = |pieces: &[String]| -> Result<Vec<usize>> {
+            Ok(pieces.iter().map(|p| approx_token_count(p) * 2).collect())
+        };
+        let text = "One sentence of six words here. ".repeat(10);
+        let pieces = split_to_fit(&text, 120, 32, count).unwrap();
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|(_, n)| *n <= 32));
+        let words: usize = pieces.iter().map(|(p, _)| approx_token_count(p)).sum();
+        assert_eq!(words, 60);
+
+        let pooled = pool(&[vec![1.0, 0.0], vec![0.0, 1.0]], &[3, 1]);
+        assert!((pooled[0] - 0.9486833).abs() < 1e-6);
+        assert!((pooled[1] - 0.31622776).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,182 @@
+//! Which of several code sections with the same `clean_text` ETL keeps
+//! (`dedup` in `--config`). The rest are recorded in `dropped_rows` as
+//! `duplicate_text`, with the id of the row kept in their place.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use anyhow::Result;
+use polars::prelude::*;
+use serde::Deserialize;
+
+use crate::etl::DroppedRow;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupPolicy {
+    /// The first row read.
+    #[default]
+    KeepFirst,
+    /// The row with the longest raw title and body, i.e. the most markup
+    /// (tables, headings) behind the same text.
+    KeepLongest,
+    /// The row with the lowest section number, compared numerically part by
+    /// part (`1-2` before `1-10`).
+    KeepLowestSection,
+}
+
+impl DedupPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::KeepFirst => "keep_first",
+            Self::KeepLongest => "keep_longest",
+            Self::KeepLowestSection => "keep_lowest_section",
+        }
+    }
+
+    /// Whether row `a` should replace `b`, which was read first.
+    fn prefers(self, a: &Candidate, b: &Candidate) -> bool {
+        match self {
+            Self::KeepFirst => false,
+            Self::KeepLongest => a.raw_len > b.raw_len,
+            Self::KeepLowestSection => section_cmp(a.section, b.section) == Ordering::Less,
+        }
+    }
+}
+
+struct Candidate<'a> {
+    row: usize,
+    section: &'a str,
+    raw_len: i64,
+}
+
+/// Compare section numbers with their digit runs as numbers, so `1-2` comes
+/// before `1-10` and `8.01-1` before `8.01-1.1`.
+fn section_cmp(a: &str, b: &str) -> Ordering {
+    fn runs(s: &str) -> Vec<&str> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for (i, c) in s.char_indices().skip(1) {
+            let prev = s[..i].chars().next_back().unwrap();
+            if prev.is_ascii_digit() != c.is_ascii_digit() {
+                runs.push(&s[start..i]);
+                start = i;
+            }
+        }
+        if !s.is_empty() {
+            runs.push(&s[start..]);
+        }
+        runs
+    }
+    for (x, y) in runs(a).into_iter().zip(runs(b)) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    runs(a).len().cmp(&runs(b).len())
+}
+
+/// Keep one row per `clean_text` of `df` (with `id`, `section`, `raw_len` and
+/// `clean_text` columns) under `policy`, in the rows' original order, and
+/// record the others in `dropped`.
+pub(crate) fn dedup_code(
+    df: DataFrame,
+    policy: DedupPolicy,
+    dropped: &mut Vec<DroppedRow>,
+) -> Result<DataFrame> {
+    let ids = df.column("id")?.i64()?;
+    let sections = df.column("section")?.str()?;
+    let raw_lens = df.column("raw_len")?.i64()?;
+    let texts = df.column("clean_text")?.str()?;
+
+    let mut best: HashMap<&str, Candidate> = HashMap::new();
+    for row in 0..df.height() {
+        let candidate = Candidate {
+            row,
+            section: sections.get(row).unwrap_or(""),
+            raw_len: raw_lens.get(row).unwrap_or(0),
+        };
+        let text = texts.get(row).unwrap_or("");
+        match best.get(text) {
+            Some(current) if !policy.prefers(&candidate, current) => {}
+            _ => {
+                best.insert(text, candidate);
+            }
+        }
+    }
+
+    let mut keep = vec![false; df.height()];
+    for candidate in best.values() {
+        keep[candidate.row] = true;
+    }
+    for (row, kept) in keep.iter().enumerate() {
+        if !kept {
+            let winner = best[texts.get(row).unwrap_or("")].row;
+            dropped.push(DroppedRow {
+                table: "virginia_code",
+                id: ids.get(row).unwrap_or(0),
+                reason: "duplicate_text".into(),
+                kept_id: ids.get(winner),
+            });
+        }
+    }
+    let mask = BooleanChunked::from_slice("keep".into(), &keep);
+    Ok(df.filter(&mask)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> DataFrame {
+        DataFrame::new(vec![
+            Column::new("id".into(), [1i64, 2, 3, 4]),
+            Column::new("section".into(), ["1-10", "1-2", "2-1", "1-9"]),
+            Column::new("raw_len".into(), [10i64, 30, 5, 40]),
+            Column::new("clean_text".into(), ["same", "same", "other", "same"]),
+        ])
+        .unwrap()
+    }
+
+    fn kept(policy: DedupPolicy) -> (Vec<i64>, Vec<(i64, Option<i64>)>) {
+        let mut dropped = Vec::new();
+        let df = dedup_code(frame(), policy, &mut dropped).unwrap();
+        let ids = df
+            .column("id")
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        let dropped = dropped.iter().map(|d| (d.id, d.kept_id)).collect();
+        (ids, dropped)
+    }
+
+    #[test]
+    fn test_dedup_policies() {
+        assert_eq!(
+            kept(DedupPolicy::KeepFirst),
+            (vec![1, 3], vec![(2, Some(1)), (4, Some(1))])
+        );
+        assert_eq!(
+            kept(DedupPolicy::KeepLongest),
+            (vec![3, 4], vec![(1, Some(4)), (2, Some(4))])
+        );
+        assert_eq!(
+            kept(DedupPolicy::KeepLowestSection),
+            (vec![2, 3], vec![(1, Some(2)), (4, Some(2))])
+        );
+    }
+
+    #[test]
+    fn test_section_cmp() {
+        assert_eq!(section_cmp("1-2", "1-10"), Ordering::Less);
+        assert_eq!(section_cmp("8.01-1", "8.01-1.1"), Ordering::Less);
+        assert_eq!(section_cmp("46.2-862", "46.2-862"), Ordering::Equal);
+        assert_eq!(section_cmp("9.1-101", "19.2-1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,125 @@
+//! `[filters.<source>]` in `--config`: the length thresholds ETL drops rows
+//! by, so short but meaningful rows (one-line definitions, say) can be kept:
+//!
+//! ```toml
+//! [filters.virginia_code]
+//! short_text_chars = 5
+//!
+//! [filters.constitution]
+//! short_text_chars = 10
+//! ```
+//!
+//! A row whose `clean_text` has at most `short_text_chars` chars is dropped
+//! as `short_text`. A source left out keeps its built-in threshold; `0`
+//! drops only empty text.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// Each source and its built-in `short_text_chars`; `None` is no length
+/// filter.
+const DEFAULTS: &[(&str, Option<usize>)] = &[
+    ("virginia_code", Some(20)),
+    ("constitution", None),
+    ("authorities", Some(10)),
+    ("courts", None),
+    ("popular_names", Some(10)),
+    ("acts", None),
+    ("federal_code", None),
+    ("documents", None),
+];
+
+/// One source's `[filters.<source>]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceFilters {
+    /// Drop rows whose `clean_text` has at most this many chars.
+    pub short_text_chars: Option<usize>,
+}
+
+/// The filter thresholds of each source: the configured ones over the
+/// built-in defaults.
+#[derive(Debug, Clone)]
+pub struct Filters {
+    by_source: BTreeMap<&'static str, SourceFilters>,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        let by_source = DEFAULTS
+            .iter()
+            .map(|&(source, short_text_chars)| (source, SourceFilters { short_text_chars }))
+            .collect();
+        Self { by_source }
+    }
+}
+
+impl Filters {
+    /// Apply `[filters]` over the defaults, failing on an unknown source.
+    pub fn new(config: &BTreeMap<String, SourceFilters>) -> Result<Self> {
+        let mut filters = Self::default();
+        for (source, configured) in config {
+            let Some(filter) = filters.by_source.get_mut(source.as_str()) else {
+                bail!(
+                    "Unknown source {:?} in [filters] (sources: {})",
+                    source,
+                    DEFAULTS
+                        .iter()
+                        .map(|(s, _)| *s)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            };
+            if configured.short_text_chars.is_some() {
+                filter.short_text_chars = configured.short_text_chars;
+            }
+        }
+        Ok(filters)
+    }
+
+    /// `source`'s `short_text` rule for [`super::drop_reason`], if it has one.
+    pub(crate) fn short_text(&self, source: &str) -> Option<(Expr, &'static str)> {
+        let chars = self.by_source.get(source)?.short_text_chars?;
+        Some((
+            col("clean_text").str().len_chars().lt_eq(lit(chars as u32)),
+            "short_text",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_override_defaults() {
+        let config = BTreeMap::from([
+            (
+                "virginia_code".to_string(),
+                SourceFilters {
+                    short_text_chars: Some(5),
+                },
+            ),
+            ("authorities".to_string(), SourceFilters::default()),
+            (
+                "constitution".to_string(),
+                SourceFilters {
+                    short_text_chars: Some(0),
+                },
+            ),
+        ]);
+        let filters = Filters::new(&config).unwrap();
+        let chars = |source: &str| filters.by_source[source].short_text_chars;
+        assert_eq!(chars("virginia_code"), Some(5));
+        assert_eq!(chars("authorities"), Some(10));
+        assert_eq!(chars("constitution"), Some(0));
+        assert_eq!(chars("acts"), None);
+        assert!(filters.short_text("acts").is_none());
+
+        let unknown = BTreeMap::from([("statutes".to_string(), SourceFilters::default())]);
+        assert!(Filters::new(&unknown).is_err());
+    }
+}
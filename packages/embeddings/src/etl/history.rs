@@ -0,0 +1,123 @@
+//! The amendment history a code section's body ends with, e.g.
+//! "(Code 1950, § 46.1-1; 1994, c. 432; 2020, cc. 1, 2.)": one entry per
+//! enacting or amending act, oldest first.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// A trailing parenthetical that mentions a year.
+static TRAILING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\(([^()]*\b(?:1[6-9]|20)\d{2}\b[^()]*)\)\.?\s*$").unwrap());
+
+static ENTRY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:Code\s+)?((?:1[6-9]|20)\d{2})\b(.*)$").unwrap());
+
+static SESSION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(Sp\.\s*Sess\.\s*[IVX]+|Ex\.\s*Sess\.)").unwrap());
+
+static CHAPTERS_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bcc?\.\s*(.+)$").unwrap());
+
+static CHAPTER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+[A-Za-z]?$").unwrap());
+
+/// One act in a section's history. An entry naming several chapters
+/// ("2020, cc. 1, 2") yields one per chapter; a Code citation
+/// ("Code 1950, § 46.1-1") has none.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Enactment {
+    /// Position of the entry in the history, from 0.
+    pub seq: i64,
+    pub year: i64,
+    /// Special session, e.g. `Sp. Sess. I`; `None` for a regular session.
+    pub session: Option<String>,
+    pub chapter: Option<String>,
+}
+
+/// The history parenthetical `clean_text` ends with, without its
+/// parentheses; empty when it has none.
+pub fn trailing_history(clean_text: &str) -> &str {
+    TRAILING_RE
+        .captures(clean_text)
+        .and_then(|cap| cap.get(1))
+        .map_or("", |m| m.as_str())
+}
+
+/// The enactments of a history from [`trailing_history`]. Entries without a
+/// leading year are skipped.
+pub fn parse_history(history: &str) -> Vec<Enactment> {
+    let mut enactments = Vec::new();
+    let entries = history
+        .split(';')
+        .map(|e| e.trim().trim_end_matches('.').trim());
+    for (seq, entry) in entries.filter(|e| !e.is_empty()).enumerate() {
+        let Some(cap) = ENTRY_RE.captures(entry) else {
+            continue;
+        };
+        let year: i64 = cap[1].parse().unwrap_or(0);
+        let rest = &cap[2];
+        let session = SESSION_RE
+            .captures(rest)
+            .map(|s| s[1].split_whitespace().collect::<Vec<_>>().join(" "));
+        let chapters: Vec<String> = CHAPTERS_RE
+            .captures(rest)
+            .map(|c| {
+                c[1].split([',', '&'])
+                    .flat_map(|part| part.split(" and "))
+                    .map(str::trim)
+                    .filter(|ch| CHAPTER_RE.is_match(ch))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let enactment = |chapter| Enactment {
+            seq: seq as i64,
+            year,
+            session: session.clone(),
+            chapter,
+        };
+        if chapters.is_empty() {
+            enactments.push(enactment(None));
+        } else {
+            enactments.extend(chapters.into_iter().map(|ch| enactment(Some(ch))));
+        }
+    }
+    enactments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn act(seq: i64, year: i64, session: Option<&str>, chapter: Option<&str>) -> Enactment {
+        Enactment {
+            seq,
+            year,
+            session: session.map(String::from),
+            chapter: chapter.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let text = "Title | Chapter | Heading Text of the section. (Code 1950, § 46.1-1; 1994, c. 432; 2004, Sp. Sess. I, c. 4; 2020, cc. 1, 2.)";
+        let history = trailing_history(text);
+        assert_eq!(
+            history,
+            "Code 1950, § 46.1-1; 1994, c. 432; 2004, Sp. Sess. I, c. 4; 2020, cc. 1, 2."
+        );
+        assert_eq!(
+            parse_history(history),
+            [
+                act(0, 1950, None, None),
+                act(1, 1994, None, Some("432")),
+                act(2, 2004, Some("Sp. Sess. I"), Some("4")),
+                act(3, 2020, None, Some("1")),
+                act(3, 2020, None, Some("2")),
+            ]
+        );
+
+        // A parenthetical mid-text, or one without a year, is not a history.
+        assert_eq!(trailing_history("See (1994, c. 432) above."), "");
+        assert_eq!(trailing_history("Text (see subsection A)."), "");
+    }
+}
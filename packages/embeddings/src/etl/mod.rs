@@ -1,10 +1,40 @@
+//! Clean the raw source rows into DataFrames with a `clean_text` column.
+//!
+//! Each batch of a table is cleaned by one lazy query, stripping and
+//! concatenation included, collected on polars' streaming engine. The raw,
+//! stripped and concatenated columns of a batch are then processed a slice
+//! at a time, so only a batch's rows and its cleaned output are ever whole in
+//! memory, however large its documents.
+
+pub mod dedup;
+pub mod filters;
+pub mod history;
+pub mod quality;
+pub mod templates;
+pub mod transforms;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, LazyLock, Mutex};
+
 use anyhow::Result;
 use polars::prelude::*;
+use regex::Regex;
 
 use crate::db::reader::{
-    AuthorityRow, ConstitutionRow, CourtRow, DocumentRow, PopularNameRow, VirginiaCodeRow,
+    ActRow, AuthorityRow, ConstitutionRow, CourtRow, DocumentRow, FederalRow, PopularNameRow,
+    RowError, VirginiaCodeRow,
 };
-use crate::text::html::strip_html;
+use crate::db::raw_texts::RawText;
+use crate::db::source_hashes::{SourceHash, SourceKey};
+use crate::etl::dedup::{dedup_code, DedupPolicy};
+use crate::etl::filters::Filters;
+use crate::etl::history::{parse_history, trailing_history, Enactment};
+use crate::etl::quality::{FieldCounts, QualityReport};
+use crate::etl::templates::TextTemplates;
+use crate::etl::transforms::TitleTransforms;
+use crate::text::dedup::collapse_repeats;
+use crate::text::html::{strip_html, strip_html_limited, HtmlLimit};
+use crate::text::normalize::normalize;
 
 /// Cleaned DataFrames ready for node building.
 /// Each DataFrame has at minimum an `id` column and a `clean_text` column.
@@ -14,48 +44,456 @@ pub struct CleanedData {
     pub authorities: DataFrame,
     pub courts: DataFrame,
     pub popular_names: DataFrame,
+    pub acts: DataFrame,
+    /// U.S.C. and CFR sections, empty when the input has no `federal_code`.
+    pub federal_code: DataFrame,
     pub documents: DataFrame,
+    /// Byte offsets into each document's `clean_text` where an HTML heading
+    /// (`<h1>`-`<h6>`) starts, by document id. Chunking splits there first.
+    pub document_headings: HashMap<i64, Vec<usize>>,
+    /// Source rows excluded by an ETL filter, with the filter that excluded them.
+    pub dropped: Vec<DroppedRow>,
+    /// Source fields whose HTML hit a size or parse-time limit.
+    pub html_limited: Vec<HtmlLimitedRow>,
+    /// Source rows the reader couldn't read, which never reached ETL.
+    pub row_errors: Vec<RowError>,
+    /// The hash of every source row as read, for `source_hashes`.
+    pub source_hashes: Vec<SourceHash>,
+    /// Each source row's text after scrubbing; only with `--raw-texts`.
+    pub raw_texts: Vec<RawText>,
+    /// The amendment history each code section ends with, by section, for
+    /// `section_history`. A section with no history has no entry.
+    pub section_history: BTreeMap<String, Vec<Enactment>>,
+    /// Drop, empty-field, HTML and length statistics per source.
+    pub quality: QualityReport,
+}
+
+/// A source row excluded during ETL, kept so the exclusion can be audited.
+#[derive(Debug, Clone)]
+pub struct DroppedRow {
+    pub table: &'static str,
+    pub id: i64,
+    pub reason: String,
+    /// For a `duplicate_text` row, the id of the row kept instead.
+    pub kept_id: Option<i64>,
+}
+
+/// A source field whose HTML was truncated or stripped with the regex
+/// fallback instead of a DOM parse.
+#[derive(Debug, Clone)]
+pub struct HtmlLimitedRow {
+    pub table: &'static str,
+    pub id: i64,
+    pub field: &'static str,
+    pub limit: HtmlLimit,
+    /// Size of the raw field.
+    pub bytes: usize,
 }
 
 /// Run the full ETL pipeline on raw rows from virginia.db.
+#[allow(clippy::too_many_arguments)]
 pub fn run_etl(
     code_rows: &[VirginiaCodeRow],
     constitution_rows: &[ConstitutionRow],
     authority_rows: &[AuthorityRow],
     court_rows: &[CourtRow],
     popular_name_rows: &[PopularNameRow],
+    act_rows: &[ActRow],
+    federal_rows: &[FederalRow],
     document_rows: &[DocumentRow],
 ) -> Result<CleanedData> {
-    let virginia_code = clean_virginia_code(code_rows)?;
-    let constitution = clean_constitution(constitution_rows)?;
-    let authorities = clean_authorities(authority_rows)?;
-    let courts = clean_courts(court_rows)?;
-    let popular_names = clean_popular_names(popular_name_rows)?;
-    let documents = clean_documents(document_rows)?;
-
-    Ok(CleanedData {
-        virginia_code,
-        constitution,
-        authorities,
-        courts,
-        popular_names,
-        documents,
-    })
+    let mut etl = Etl::default();
+    etl.virginia_code(code_rows)?;
+    etl.constitution(constitution_rows)?;
+    etl.authorities(authority_rows)?;
+    etl.courts(court_rows)?;
+    etl.popular_names(popular_name_rows)?;
+    etl.acts(act_rows)?;
+    etl.federal_code(federal_rows)?;
+    etl.documents(document_rows)?;
+    etl.finish()
 }
 
-/// Apply strip_html to every element of a string Column.
-fn strip_html_column(col: &Column) -> PolarsResult<Option<Column>> {
-    let ca = col.str()?;
-    let out: StringChunked = ca
+/// ETL fed a batch of rows at a time, e.g. from [`crate::db::reader::stream`],
+/// so the raw rows of a table never have to be in memory at once; only their
+/// cleaned frames are kept until [`Etl::finish`].
+#[derive(Default)]
+pub struct Etl {
+    virginia_code: Vec<DataFrame>,
+    constitution: Vec<DataFrame>,
+    authorities: Vec<DataFrame>,
+    courts: Vec<DataFrame>,
+    popular_names: Vec<DataFrame>,
+    acts: Vec<DataFrame>,
+    federal_code: Vec<DataFrame>,
+    documents: Vec<DataFrame>,
+    document_headings: HashMap<i64, Vec<usize>>,
+    dropped: Vec<DroppedRow>,
+    html_limited: Vec<HtmlLimitedRow>,
+    row_errors: Vec<RowError>,
+    source_hashes: Vec<SourceHash>,
+    raw_texts: Vec<RawText>,
+    field_counts: BTreeMap<&'static str, FieldCounts>,
+    limits: LimitHits,
+    options: EtlOptions,
+}
+
+/// How rows are cleaned, from `--config`.
+#[derive(Clone, Default)]
+pub struct EtlOptions {
+    /// `[[transforms]]`, or the built-in defaults.
+    pub transforms: Arc<TitleTransforms>,
+    /// `dedup`: which of several sections with the same text is kept.
+    pub dedup: DedupPolicy,
+    /// `[templates]`: how each source's `clean_text` is assembled.
+    pub templates: Arc<TextTemplates>,
+    /// `[filters]`: the length thresholds rows are dropped by.
+    pub filters: Arc<Filters>,
+}
+
+impl Etl {
+    /// An `Etl` cleaning code sections with `options` instead of the
+    /// defaults.
+    pub fn new(options: EtlOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    pub fn virginia_code(&mut self, rows: &[VirginiaCodeRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_virginia_code(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("virginia_code"));
+        self.virginia_code.push(df);
+        Ok(())
+    }
+
+    pub fn constitution(&mut self, rows: &[ConstitutionRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_constitution(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("constitution"));
+        self.constitution.push(df);
+        Ok(())
+    }
+
+    pub fn authorities(&mut self, rows: &[AuthorityRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_authorities(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("authorities"));
+        self.authorities.push(df);
+        Ok(())
+    }
+
+    pub fn courts(&mut self, rows: &[CourtRow]) -> Result<()> {
+        self.observe(rows);
+        self.courts
+            .push(clean_courts(rows, &self.options, &mut self.dropped)?);
+        Ok(())
+    }
+
+    pub fn popular_names(&mut self, rows: &[PopularNameRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_popular_names(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("popular_names"));
+        self.popular_names.push(df);
+        Ok(())
+    }
+
+    pub fn acts(&mut self, rows: &[ActRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_acts(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("acts"));
+        self.acts.push(df);
+        Ok(())
+    }
+
+    pub fn federal_code(&mut self, rows: &[FederalRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_federal_code(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("federal_code"));
+        self.federal_code.push(df);
+        Ok(())
+    }
+
+    pub fn documents(&mut self, rows: &[DocumentRow]) -> Result<()> {
+        self.observe(rows);
+        let df = clean_documents(rows, &self.options, &mut self.dropped, &self.limits)?;
+        self.html_limited.extend(self.limits.take("documents"));
+        let contents: HashMap<i64, &str> =
+            rows.iter().map(|r| (r.id, r.content.as_str())).collect();
+        let ids = df.column("id")?.i64()?;
+        let clean_texts = df.column("clean_text")?.str()?;
+        for (id, clean_text) in ids.into_iter().zip(clean_texts) {
+            let (Some(id), Some(clean_text)) = (id, clean_text) else {
+                continue;
+            };
+            let starts = heading_offsets(contents.get(&id).copied().unwrap_or(""), clean_text);
+            if !starts.is_empty() {
+                self.document_headings.insert(id, starts);
+            }
+        }
+        self.documents.push(df);
+        Ok(())
+    }
+
+    /// Record rows the reader skipped, for the audit trail.
+    pub fn row_errors(&mut self, errors: Vec<RowError>) {
+        self.row_errors.extend(errors);
+    }
+
+    /// Record the hashes of rows as read, before scrubbing.
+    pub fn source_hashes(&mut self, hashes: Vec<SourceHash>) {
+        self.source_hashes.extend(hashes);
+    }
+
+    /// Record the text of rows after scrubbing, for `--raw-texts`.
+    pub fn raw_texts(&mut self, texts: Vec<RawText>) {
+        self.raw_texts.extend(texts);
+    }
+
+    /// Append `other`'s cleaned batches and audit rows, e.g. from a table
+    /// cleaned on another thread. Merging in table order keeps the result the
+    /// same as feeding one `Etl` table by table.
+    pub fn merge(&mut self, other: Etl) {
+        self.virginia_code.extend(other.virginia_code);
+        self.constitution.extend(other.constitution);
+        self.authorities.extend(other.authorities);
+        self.courts.extend(other.courts);
+        self.popular_names.extend(other.popular_names);
+        self.acts.extend(other.acts);
+        self.federal_code.extend(other.federal_code);
+        self.documents.extend(other.documents);
+        self.document_headings.extend(other.document_headings);
+        self.dropped.extend(other.dropped);
+        self.html_limited.extend(other.html_limited);
+        self.row_errors.extend(other.row_errors);
+        self.source_hashes.extend(other.source_hashes);
+        self.raw_texts.extend(other.raw_texts);
+        for (source, counts) in other.field_counts {
+            self.field_counts.entry(source).or_default().merge(counts);
+        }
+    }
+
+    /// Count empty and HTML-bearing fields of a batch for the quality report.
+    fn observe<T: SourceKey>(&mut self, rows: &[T]) {
+        self.field_counts.entry(T::SOURCE).or_default().observe(rows);
+    }
+
+    /// Concatenate each table's batches. Code sections are deduplicated within
+    /// each batch and again across batches here, so the first of a set of
+    /// identical sections is kept however the rows were batched.
+    pub fn finish(mut self) -> Result<CleanedData> {
+        // A table fed no batches still needs its columns
+        if self.virginia_code.is_empty() {
+            self.virginia_code(&[])?;
+        }
+        if self.constitution.is_empty() {
+            self.constitution(&[])?;
+        }
+        if self.authorities.is_empty() {
+            self.authorities(&[])?;
+        }
+        if self.courts.is_empty() {
+            self.courts(&[])?;
+        }
+        if self.popular_names.is_empty() {
+            self.popular_names(&[])?;
+        }
+        if self.acts.is_empty() {
+            self.acts(&[])?;
+        }
+        if self.federal_code.is_empty() {
+            self.federal_code(&[])?;
+        }
+        if self.documents.is_empty() {
+            self.documents(&[])?;
+        }
+        let code = concat_batches(self.virginia_code)?;
+        let virginia_code = dedup_code(code, self.options.dedup, &mut self.dropped)?;
+        let section_history = section_history(&virginia_code);
+        let constitution = concat_batches(self.constitution)?;
+        let authorities = concat_batches(self.authorities)?;
+        let courts = concat_batches(self.courts)?;
+        let popular_names = concat_batches(self.popular_names)?;
+        let acts = concat_batches(self.acts)?;
+        let federal_code = concat_batches(self.federal_code)?;
+        let documents = concat_batches(self.documents)?;
+        let quality = QualityReport::build(
+            &self.field_counts,
+            &[
+                ("virginia_code", &virginia_code),
+                ("constitution", &constitution),
+                ("authorities", &authorities),
+                ("courts", &courts),
+                ("popular_names", &popular_names),
+                ("acts", &acts),
+                ("federal_code", &federal_code),
+                ("documents", &documents),
+            ],
+            &self.dropped,
+        )?;
+
+        Ok(CleanedData {
+            virginia_code,
+            constitution,
+            authorities,
+            courts,
+            popular_names,
+            acts,
+            federal_code,
+            documents,
+            document_headings: self.document_headings,
+            dropped: self.dropped,
+            html_limited: self.html_limited,
+            row_errors: self.row_errors,
+            source_hashes: self.source_hashes,
+            raw_texts: self.raw_texts,
+            section_history,
+            quality,
+        })
+    }
+}
+
+/// One frame from a table's cleaned batches (there is always at least one),
+/// concatenated lazily so the batches are moved into the result rather than
+/// copied next to it.
+fn concat_batches(batches: Vec<DataFrame>) -> Result<DataFrame> {
+    assert!(!batches.is_empty(), "finish feeds every table a batch");
+    let frames: Vec<LazyFrame> = batches.into_iter().map(DataFrame::lazy).collect();
+    let args = UnionArgs {
+        rechunk: true,
+        ..UnionArgs::default()
+    };
+    Ok(concat(frames, args)?.with_streaming(true).collect()?)
+}
+
+/// Label each row with the first rule it matches, or null if it matches none.
+/// Rules are `(drop_if, reason)` pairs checked in order.
+fn drop_reason<'a>(rules: impl IntoIterator<Item = (Expr, &'a str)>) -> Expr {
+    let rules: Vec<_> = rules.into_iter().collect();
+    rules
         .into_iter()
-        .map(|opt_val| opt_val.map(|v| strip_html(v)))
-        .collect();
-    Ok(Some(out.into_column()))
+        .rev()
+        .fold(lit(NULL).cast(DataType::String), |acc, (drop_if, reason)| {
+            when(drop_if).then(lit(reason)).otherwise(acc)
+        })
+        .alias("drop_reason")
+}
+
+/// Move rows with a `drop_reason` into `dropped` and return the rest, without
+/// the `drop_reason` column.
+fn split_dropped(
+    df: DataFrame,
+    table: &'static str,
+    dropped: &mut Vec<DroppedRow>,
+) -> Result<DataFrame> {
+    let reasons = df.column("drop_reason")?.str()?;
+    let ids = df.column("id")?.i64()?;
+    for (id, reason) in ids.into_iter().zip(reasons) {
+        if let (Some(id), Some(reason)) = (id, reason) {
+            dropped.push(DroppedRow {
+                table,
+                id,
+                reason: reason.to_string(),
+                kept_id: None,
+            });
+        }
+    }
+    let keep = reasons.is_null();
+    Ok(df.filter(&keep)?.drop("drop_reason")?)
+}
+
+/// An HTML limit hit in one column of row `id`.
+struct LimitHit {
+    id: i64,
+    field: &'static str,
+    limit: HtmlLimit,
+    bytes: usize,
+}
+
+/// Limits hit while stripping one table's columns, shared with the column
+/// closures (which see no row ids).
+#[derive(Clone, Default)]
+struct LimitHits(Arc<Mutex<Vec<LimitHit>>>);
+
+impl LimitHits {
+    /// Drain the hits recorded so far, in row id order (a streaming collect
+    /// strips batches in parallel).
+    fn take(&self, table: &'static str) -> Vec<HtmlLimitedRow> {
+        let mut hits = std::mem::take(&mut *self.0.lock().unwrap());
+        hits.sort_by_key(|h| (h.id, h.field));
+        hits.into_iter()
+            .map(|h| HtmlLimitedRow {
+                table,
+                id: h.id,
+                field: h.field,
+                limit: h.limit,
+                bytes: h.bytes,
+            })
+            .collect()
+    }
+}
+
+/// Apply strip_html and normalize to every element of the string column
+/// `name`, recording any HTML limits hit in `limits` against the row's `id`.
+/// The id travels with the text because a streaming collect hands the closure
+/// one slice of the column at a time.
+fn strip_html_column(name: &'static str, limits: &LimitHits) -> Expr {
+    strip_column(name, limits, false)
+}
+
+/// [`strip_html_column`] plus collapse_repeats, for body text. Titles and
+/// headings are left alone.
+fn strip_body_column(name: &'static str, limits: &LimitHits) -> Expr {
+    strip_column(name, limits, true)
+}
+
+fn strip_column(name: &'static str, limits: &LimitHits, collapse: bool) -> Expr {
+    let limits = limits.clone();
+    col(name).map_many(
+        move |columns| {
+            let texts = columns[0].str()?;
+            let ids = columns[1].i64()?;
+            let out: StringChunked = texts
+                .into_iter()
+                .zip(ids)
+                .map(|(opt_val, id)| {
+                    opt_val.map(|v| {
+                        let (text, limit) = strip_html_limited(v);
+                        if let Some(limit) = limit {
+                            let field = name.trim_end_matches("_raw");
+                            limits.0.lock().unwrap().push(LimitHit {
+                                id: id.unwrap_or(0),
+                                field,
+                                limit,
+                                bytes: v.len(),
+                            });
+                        }
+                        let text = normalize(&text);
+                        if collapse {
+                            collapse_repeats(&text).0
+                        } else {
+                            text
+                        }
+                    })
+                })
+                .collect();
+            Ok(Some(out.into_column()))
+        },
+        &[col("id")],
+        GetOutput::from_type(DataType::String),
+    )
 }
 
 // --- Virginia Code ---
 
-fn clean_virginia_code(rows: &[VirginiaCodeRow]) -> Result<DataFrame> {
+fn clean_virginia_code(
+    rows: &[VirginiaCodeRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let sections: Vec<&str> = rows.iter().map(|r| r.section.as_str()).collect();
     let title_nums: Vec<&str> = rows.iter().map(|r| r.title_num.as_str()).collect();
@@ -63,7 +501,21 @@ fn clean_virginia_code(rows: &[VirginiaCodeRow]) -> Result<DataFrame> {
     let chapter_nums: Vec<&str> = rows.iter().map(|r| r.chapter_num.as_str()).collect();
     let chapter_names: Vec<&str> = rows.iter().map(|r| r.chapter_name.as_str()).collect();
     let titles: Vec<&str> = rows.iter().map(|r| r.title.as_str()).collect();
-    let bodies: Vec<&str> = rows.iter().map(|r| r.body.as_str()).collect();
+    let transformed: Vec<std::borrow::Cow<str>> = rows
+        .iter()
+        .map(|r| options.transforms.apply(&r.title_num, &r.body))
+        .collect();
+    let bodies: Vec<&str> = transformed.iter().map(|b| b.as_ref()).collect();
+    let raw_lens: Vec<i64> = rows
+        .iter()
+        .zip(&bodies)
+        .map(|(r, body)| (r.title.len() + body.len()) as i64)
+        .collect();
+    let today = chrono::Utc::now().date_naive();
+    let statuses: Vec<&str> = rows
+        .iter()
+        .map(|r| section_status(&r.title, &r.body, today))
+        .collect();
 
     let df = DataFrame::new(vec![
         Column::new("id".into(), ids),
@@ -74,31 +526,39 @@ fn clean_virginia_code(rows: &[VirginiaCodeRow]) -> Result<DataFrame> {
         Column::new("chapter_name".into(), chapter_names),
         Column::new("title_raw".into(), titles),
         Column::new("body_raw".into(), bodies),
+        Column::new("status".into(), statuses),
+        Column::new("raw_len".into(), raw_lens),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
         .with_columns([
-            col("title_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_html_column("title_raw", limits)
                 .alias("title_clean"),
-            col("body_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_body_column("body_raw", limits)
                 .alias("body_clean"),
         ])
-        .with_column(
-            (col("title_name")
+        .with_column(options.templates.clean_text(
+            "virginia_code",
+            col("title_name")
                 + lit(" | ")
                 + col("chapter_name")
                 + lit(" | ")
                 + col("title_clean")
                 + lit(" ")
-                + col("body_clean"))
-            .alias("clean_text"),
-        )
-        .filter(col("section").str().len_chars().gt(lit(0)))
-        .filter(col("clean_text").str().len_chars().gt(lit(20)))
-        .unique(Some(vec!["clean_text".into()]), UniqueKeepStrategy::First)
+                + col("body_clean"),
+        ))
+        .with_column(drop_reason(
+            [(col("section").str().len_chars().eq(lit(0)), "empty_section")]
+                .into_iter()
+                .chain(options.filters.short_text("virginia_code")),
+        ))
+        .with_streaming(true)
+        .collect()?;
+    let filtered = split_dropped(labelled, "virginia_code", dropped)?;
+
+    let mut result = dedup_code(filtered, options.dedup, dropped)?
+        .lazy()
         .select([
             col("id"),
             col("section"),
@@ -107,15 +567,82 @@ fn clean_virginia_code(rows: &[VirginiaCodeRow]) -> Result<DataFrame> {
             col("title_name"),
             col("chapter_name"),
             col("clean_text"),
+            col("title_clean").alias("heading"),
+            col("status"),
+            col("raw_len"),
         ])
         .collect()?;
 
+    let histories: Vec<String> = result
+        .column("clean_text")?
+        .str()?
+        .into_iter()
+        .map(|text| trailing_history(text.unwrap_or("")).to_string())
+        .collect();
+    result.with_column(Column::new("history".into(), histories))?;
     Ok(result)
 }
 
+/// Parse each section's `history` column, keeping the first row's for a
+/// section that appears twice.
+fn section_history(code: &DataFrame) -> BTreeMap<String, Vec<Enactment>> {
+    let mut by_section = BTreeMap::new();
+    let (Ok(sections), Ok(histories)) = (code.column("section"), code.column("history")) else {
+        return by_section;
+    };
+    let (Ok(sections), Ok(histories)) = (sections.str(), histories.str()) else {
+        return by_section;
+    };
+    for (section, history) in sections.into_iter().zip(histories) {
+        let (Some(section), Some(history)) = (section, history) else {
+            continue;
+        };
+        let enactments = parse_history(history);
+        if !enactments.is_empty() {
+            by_section.entry(section.to_string()).or_insert(enactments);
+        }
+    }
+    by_section
+}
+
+static REPEALED_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^\W*(?:<[^>]*>\W*)*repealed\b").unwrap());
+
+static EXPIRES_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\(expires?\s+(?:on\s+)?([a-z]+\s+\d{1,2},\s*\d{4})\)").unwrap()
+});
+
+/// `status` of a code section: `repealed` when its title or body opens with
+/// "Repealed" (a stub left in place of the text), `expired` when it carries
+/// an "(Expires July 1, 2024)" note dated on or before `today`, otherwise
+/// `active`. A note with a later date is still in force.
+fn section_status(title: &str, body: &str, today: chrono::NaiveDate) -> &'static str {
+    if REPEALED_RE.is_match(title) || REPEALED_RE.is_match(body) {
+        return "repealed";
+    }
+    let expired = [title, body]
+        .iter()
+        .flat_map(|text| EXPIRES_RE.captures_iter(text))
+        .filter_map(|cap| {
+            let date = cap[1].split_whitespace().collect::<Vec<_>>().join(" ");
+            chrono::NaiveDate::parse_from_str(&date, "%B %d, %Y").ok()
+        })
+        .any(|date| date <= today);
+    if expired {
+        "expired"
+    } else {
+        "active"
+    }
+}
+
 // --- Constitution ---
 
-fn clean_constitution(rows: &[ConstitutionRow]) -> Result<DataFrame> {
+fn clean_constitution(
+    rows: &[ConstitutionRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let article_ids: Vec<i64> = rows.iter().map(|r| r.article_id).collect();
     let article_names: Vec<&str> = rows.iter().map(|r| r.article_name.as_str()).collect();
@@ -134,45 +661,54 @@ fn clean_constitution(rows: &[ConstitutionRow]) -> Result<DataFrame> {
         Column::new("section_count".into(), section_counts),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
         .with_columns([
-            col("section_name_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_html_column("section_name_raw", limits)
                 .alias("section_name_clean"),
-            col("section_title_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_html_column("section_title_raw", limits)
                 .alias("section_title_clean"),
-            col("section_text_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_body_column("section_text_raw", limits)
                 .alias("section_text_clean"),
         ])
-        .with_column(
-            (col("article_name")
+        .with_column(options.templates.clean_text(
+            "constitution",
+            col("article_name")
                 + lit(" | ")
                 + col("section_name_clean")
                 + lit(" ")
                 + col("section_title_clean")
                 + lit(" ")
-                + col("section_text_clean"))
-            .alias("clean_text"),
-        )
-        .filter(col("section_text_clean").str().len_chars().gt(lit(0)))
+                + col("section_text_clean"),
+        ))
+        .with_column(drop_reason(
+            [(col("section_text_clean").str().len_chars().eq(lit(0)), "empty_text")]
+                .into_iter()
+                .chain(options.filters.short_text("constitution")),
+        ))
         .select([
             col("id"),
             col("article_id"),
             col("article_name"),
             col("section_count"),
             col("clean_text"),
+            (col("section_name_clean") + lit(" ") + col("section_title_clean")).alias("heading"),
+            col("drop_reason"),
         ])
+        .with_streaming(true)
         .collect()?;
 
-    Ok(result)
+    split_dropped(labelled, "constitution", dropped)
 }
 
 // --- Authorities ---
 
-fn clean_authorities(rows: &[AuthorityRow]) -> Result<DataFrame> {
+fn clean_authorities(
+    rows: &[AuthorityRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let short_names: Vec<&str> = rows.iter().map(|r| r.short_name.as_str()).collect();
     let titles: Vec<&str> = rows.iter().map(|r| r.title.as_str()).collect();
@@ -185,30 +721,37 @@ fn clean_authorities(rows: &[AuthorityRow]) -> Result<DataFrame> {
         Column::new("body_raw".into(), bodies),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
         .with_columns([
-            col("title_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_html_column("title_raw", limits)
                 .alias("title_clean"),
-            col("body_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_body_column("body_raw", limits)
                 .alias("body_clean"),
         ])
-        .with_column(
-            (col("title_clean") + lit(" ") + col("body_clean")).alias("clean_text"),
-        )
-        .filter(col("short_name").str().len_chars().gt(lit(0)))
-        .filter(col("clean_text").str().len_chars().gt(lit(10)))
-        .select([col("id"), col("short_name"), col("clean_text")])
+        .with_column(options.templates.clean_text(
+            "authorities",
+            col("title_clean") + lit(" ") + col("body_clean"),
+        ))
+        .with_column(drop_reason(
+            [(col("short_name").str().len_chars().eq(lit(0)), "empty_short_name")]
+                .into_iter()
+                .chain(options.filters.short_text("authorities")),
+        ))
+        .select([col("id"), col("short_name"), col("clean_text"), col("drop_reason")])
+        .with_streaming(true)
         .collect()?;
 
-    Ok(result)
+    split_dropped(labelled, "authorities", dropped)
 }
 
 // --- Courts ---
 
-fn clean_courts(rows: &[CourtRow]) -> Result<DataFrame> {
+fn clean_courts(
+    rows: &[CourtRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
     let localities: Vec<&str> = rows.iter().map(|r| r.locality.as_str()).collect();
@@ -225,10 +768,11 @@ fn clean_courts(rows: &[CourtRow]) -> Result<DataFrame> {
         Column::new("city".into(), cities),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
-        .with_column(
-            (col("name")
+        .with_column(options.templates.clean_text(
+            "courts",
+            col("name")
                 + lit(" ")
                 + col("locality")
                 + lit(" ")
@@ -236,18 +780,24 @@ fn clean_courts(rows: &[CourtRow]) -> Result<DataFrame> {
                 + lit(" ")
                 + col("district")
                 + lit(" ")
-                + col("city"))
-            .alias("clean_text"),
-        )
-        .select([col("id"), col("clean_text")])
+                + col("city"),
+        ))
+        .with_column(drop_reason(options.filters.short_text("courts")))
+        .select([col("id"), col("clean_text"), col("drop_reason")])
+        .with_streaming(true)
         .collect()?;
 
-    Ok(result)
+    split_dropped(labelled, "courts", dropped)
 }
 
 // --- Popular Names ---
 
-fn clean_popular_names(rows: &[PopularNameRow]) -> Result<DataFrame> {
+fn clean_popular_names(
+    rows: &[PopularNameRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
     let bodies: Vec<&str> = rows.iter().map(|r| r.body.as_str()).collect();
@@ -258,27 +808,179 @@ fn clean_popular_names(rows: &[PopularNameRow]) -> Result<DataFrame> {
         Column::new("body_raw".into(), bodies),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
         .with_column(
-            col("body_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_body_column("body_raw", limits)
                 .alias("body_clean"),
         )
-        .with_column(
-            (col("name") + lit(" ") + col("body_clean")).alias("clean_text"),
-        )
-        .filter(col("name").str().len_chars().gt(lit(0)))
-        .filter(col("clean_text").str().len_chars().gt(lit(10)))
-        .select([col("id"), col("name"), col("clean_text")])
+        .with_column(options.templates.clean_text(
+            "popular_names",
+            col("name") + lit(" ") + col("body_clean"),
+        ))
+        .with_column(drop_reason(
+            [(col("name").str().len_chars().eq(lit(0)), "empty_name")]
+                .into_iter()
+                .chain(options.filters.short_text("popular_names")),
+        ))
+        .select([col("id"), col("name"), col("clean_text"), col("drop_reason")])
+        .with_streaming(true)
         .collect()?;
 
-    Ok(result)
+    split_dropped(labelled, "popular_names", dropped)
+}
+
+// --- Acts ---
+
+fn clean_acts(
+    rows: &[ActRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
+    let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
+    let years: Vec<i64> = rows.iter().map(|r| r.year).collect();
+    let chapters: Vec<&str> = rows.iter().map(|r| r.chapter.trim()).collect();
+    let titles: Vec<&str> = rows.iter().map(|r| r.title.as_str()).collect();
+    let bodies: Vec<&str> = rows.iter().map(|r| r.body.as_str()).collect();
+
+    let df = DataFrame::new(vec![
+        Column::new("id".into(), ids),
+        Column::new("year".into(), years),
+        Column::new("chapter".into(), chapters),
+        Column::new("title_raw".into(), titles),
+        Column::new("body_raw".into(), bodies),
+    ])?;
+
+    let labelled = df
+        .lazy()
+        .with_columns([
+            strip_html_column("title_raw", limits)
+                .alias("title_clean"),
+            strip_body_column("body_raw", limits)
+                .alias("body_clean"),
+        ])
+        .with_column(options.templates.clean_text(
+            "acts",
+            lit("Acts ")
+                + col("year").cast(DataType::String)
+                + lit(" c. ")
+                + col("chapter")
+                + lit(" ")
+                + col("title_clean")
+                + lit(" ")
+                + col("body_clean"),
+        ))
+        .with_column(drop_reason(
+            [
+                (col("year").lt_eq(lit(0)), "missing_year"),
+                (col("chapter").str().len_chars().eq(lit(0)), "empty_chapter"),
+                (col("body_clean").str().len_chars().eq(lit(0)), "empty_body"),
+            ]
+            .into_iter()
+            .chain(options.filters.short_text("acts")),
+        ))
+        .select([
+            col("id"),
+            col("year"),
+            col("chapter"),
+            col("clean_text"),
+            col("drop_reason"),
+        ])
+        .with_streaming(true)
+        .collect()?;
+
+    split_dropped(labelled, "acts", dropped)
+}
+
+// --- Federal code ---
+
+/// Citation form of a federal section, e.g. `42 U.S.C. § 1983` or
+/// `29 C.F.R. § 1910.1200`; `None` for a code other than `usc` or `cfr`.
+fn federal_citation(code: &str, title: &str, section: &str) -> Option<String> {
+    let abbreviation = match code {
+        "usc" => "U.S.C.",
+        "cfr" => "C.F.R.",
+        _ => return None,
+    };
+    Some(format!("{} {} § {}", title, abbreviation, section))
+}
+
+fn clean_federal_code(
+    rows: &[FederalRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
+    let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
+    let codes: Vec<String> = rows.iter().map(|r| r.code.trim().to_lowercase()).collect();
+    let titles: Vec<&str> = rows.iter().map(|r| r.title_num.trim()).collect();
+    let sections: Vec<&str> = rows.iter().map(|r| r.section.trim()).collect();
+    let citations: Vec<Option<String>> = codes
+        .iter()
+        .zip(&titles)
+        .zip(&sections)
+        .map(|((code, title), section)| federal_citation(code, title, section))
+        .collect();
+    let headings: Vec<&str> = rows.iter().map(|r| r.heading.as_str()).collect();
+    let bodies: Vec<&str> = rows.iter().map(|r| r.body.as_str()).collect();
+
+    let df = DataFrame::new(vec![
+        Column::new("id".into(), ids),
+        Column::new("code".into(), codes),
+        Column::new("title_num".into(), titles),
+        Column::new("section".into(), sections),
+        Column::new("citation".into(), citations),
+        Column::new("heading_raw".into(), headings),
+        Column::new("body_raw".into(), bodies),
+    ])?;
+
+    let labelled = df
+        .lazy()
+        .with_columns([
+            strip_html_column("heading_raw", limits).alias("heading_clean"),
+            strip_body_column("body_raw", limits).alias("body_clean"),
+        ])
+        .with_column(options.templates.clean_text(
+            "federal_code",
+            col("citation").fill_null(lit(""))
+                + lit(" ")
+                + col("heading_clean")
+                + lit(" ")
+                + col("body_clean"),
+        ))
+        .with_column(drop_reason(
+            [
+                (col("citation").is_null(), "unknown_code"),
+                (col("title_num").str().len_chars().eq(lit(0)), "empty_title"),
+                (col("section").str().len_chars().eq(lit(0)), "empty_section"),
+                (col("body_clean").str().len_chars().eq(lit(0)), "empty_body"),
+            ]
+            .into_iter()
+            .chain(options.filters.short_text("federal_code")),
+        ))
+        .select([
+            col("id"),
+            col("code"),
+            col("title_num"),
+            col("section"),
+            col("clean_text"),
+            col("drop_reason"),
+        ])
+        .with_streaming(true)
+        .collect()?;
+
+    split_dropped(labelled, "federal_code", dropped)
 }
 
 // --- Documents ---
 
-fn clean_documents(rows: &[DocumentRow]) -> Result<DataFrame> {
+fn clean_documents(
+    rows: &[DocumentRow],
+    options: &EtlOptions,
+    dropped: &mut Vec<DroppedRow>,
+    limits: &LimitHits,
+) -> Result<DataFrame> {
     let ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
     let filenames: Vec<&str> = rows.iter().map(|r| r.filename.as_str()).collect();
     let titles: Vec<&str> = rows.iter().map(|r| r.title.as_str()).collect();
@@ -291,24 +993,50 @@ fn clean_documents(rows: &[DocumentRow]) -> Result<DataFrame> {
         Column::new("content_raw".into(), contents),
     ])?;
 
-    let result = df
+    let labelled = df
         .lazy()
         .with_columns([
-            col("title_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_html_column("title_raw", limits)
                 .alias("title_clean"),
-            col("content_raw")
-                .map(|s| strip_html_column(&s), GetOutput::from_type(DataType::String))
+            strip_body_column("content_raw", limits)
                 .alias("content_clean"),
         ])
-        .with_column(
-            (col("title_clean") + lit(" ") + col("content_clean")).alias("clean_text"),
-        )
-        .filter(col("filename").str().len_chars().gt(lit(0)))
-        .select([col("id"), col("filename"), col("clean_text")])
+        .with_column(options.templates.clean_text(
+            "documents",
+            col("title_clean") + lit(" ") + col("content_clean"),
+        ))
+        .with_column(drop_reason(
+            [(col("filename").str().len_chars().eq(lit(0)), "empty_filename")]
+                .into_iter()
+                .chain(options.filters.short_text("documents")),
+        ))
+        .select([col("id"), col("filename"), col("clean_text"), col("drop_reason")])
+        .with_streaming(true)
         .collect()?;
 
-    Ok(result)
+    split_dropped(labelled, "documents", dropped)
+}
+
+static HEADING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<h[1-6]\b[^>]*>(.*?)</h[1-6]\s*>").unwrap());
+
+/// Where each of `raw`'s HTML headings starts in `clean`, its cleaned text.
+/// Headings are cleaned the same way and found in order; one that cleaning
+/// changed beyond recognition is skipped.
+fn heading_offsets(raw: &str, clean: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut from = 0;
+    for cap in HEADING_RE.captures_iter(raw) {
+        let heading = normalize(&strip_html(&cap[1]));
+        if heading.is_empty() {
+            continue;
+        }
+        if let Some(at) = clean[from..].find(heading.as_str()) {
+            starts.push(from + at);
+            from += at + heading.len();
+        }
+    }
+    starts
 }
 
 #[cfg(test)]
@@ -350,9 +1078,120 @@ mod tests {
             },
         ];
 
-        let result = clean_virginia_code(&rows).unwrap();
+        let mut dropped = Vec::new();
+        let result = clean_virginia_code(&rows, &EtlOptions::default(), &mut dropped, &LimitHits::default()).unwrap();
         assert!(result.height() <= rows.len());
         assert!(result.height() >= 1);
+        assert_eq!(result.height() + dropped.len(), rows.len());
+        assert!(dropped.iter().all(|d| d.table == "virginia_code"));
+    }
+
+    #[test]
+    fn test_section_status() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        assert_eq!(section_status("<b>[Repealed]</b>", "", today), "repealed");
+        assert_eq!(section_status("Heading", "Repealed by Acts 2005, c. 123.", today), "repealed");
+        assert_eq!(section_status("Heading", "Not repealed by this act.", today), "active");
+        assert_eq!(section_status("Heading (Expires July 1, 2025)", "Text", today), "expired");
+        assert_eq!(section_status("Heading", "<p>(Expires\nJanuary 1,\n2030)</p>", today), "active");
+        assert_eq!(section_status("Heading (Effective July 1, 2025)", "Text", today), "active");
+    }
+
+    #[test]
+    fn test_dropped_rows_record_first_failing_filter() {
+        let row = |id: i64, section: &str, body: &str| VirginiaCodeRow {
+            id,
+            title_num: "1".into(),
+            title_name: "T".into(),
+            chapter_num: "1".into(),
+            chapter_name: "C".into(),
+            section: section.into(),
+            title: "Title".into(),
+            body: body.into(),
+        };
+        let rows = vec![
+            row(1, "1-1", "A body long enough to pass the length filter."),
+            row(2, "", "A body long enough to pass the length filter."),
+            row(3, "1-3", ""),
+            row(4, "1-1", "A body long enough to pass the length filter."),
+        ];
+
+        let mut dropped = Vec::new();
+        let result = clean_virginia_code(&rows, &EtlOptions::default(), &mut dropped, &LimitHits::default()).unwrap();
+        assert_eq!(result.height(), 1);
+
+        let reason = |id: i64| {
+            dropped
+                .iter()
+                .find(|d| d.id == id)
+                .map(|d| d.reason.as_str())
+        };
+        assert_eq!(reason(2), Some("empty_section"));
+        assert_eq!(reason(3), Some("short_text"));
+        // Row 1 or 4 is kept; the other is a duplicate.
+        assert_eq!(dropped.len(), 3);
+        assert!(reason(1) == Some("duplicate_text") || reason(4) == Some("duplicate_text"));
+    }
+
+    #[test]
+    fn test_batches_dedup_like_one_pass() {
+        let row = |id: i64, body: &str| VirginiaCodeRow {
+            id,
+            title_num: "1".into(),
+            title_name: "T".into(),
+            chapter_num: "1".into(),
+            chapter_name: "C".into(),
+            section: format!("1-{id}"),
+            title: "Title".into(),
+            body: body.into(),
+        };
+        let same = "A body long enough to pass the length filter.";
+        let rows = [row(1, same), row(2, "Another body of reasonable length."), row(3, same)];
+
+        let mut etl = Etl::default();
+        etl.virginia_code(&rows[..2]).unwrap();
+        etl.virginia_code(&rows[2..]).unwrap();
+        let cleaned = etl.finish().unwrap();
+        let mut ids: Vec<i64> = cleaned
+            .virginia_code
+            .column("id")
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        ids.sort();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(cleaned.dropped.len(), 1);
+        assert_eq!((cleaned.dropped[0].id, cleaned.dropped[0].reason.as_str()), (3, "duplicate_text"));
+        // Tables fed nothing still have their columns.
+        assert_eq!(cleaned.acts.height(), 0);
+        assert!(cleaned.acts.column("clean_text").is_ok());
+    }
+
+    #[test]
+    fn test_html_limits_are_reported_by_row() {
+        let row = |id: i64, body: String| AuthorityRow {
+            id,
+            name: "Authority".into(),
+            short_name: "A".into(),
+            codified: String::new(),
+            title: "<b>Title</b>".into(),
+            section: String::new(),
+            body,
+        };
+        let huge = format!("<p>{}</p>", "text ".repeat(crate::text::html::MAX_PARSE_BYTES / 4));
+        let rows = vec![row(10, "<p>small body text</p>".into()), row(20, huge)];
+
+        let limits = LimitHits::default();
+        let result = clean_authorities(&rows, &EtlOptions::default(), &mut Vec::new(), &limits).unwrap();
+        assert_eq!(result.height(), 2);
+
+        let limited = limits.take("authorities");
+        assert_eq!(limited.len(), 1);
+        assert_eq!((limited[0].id, limited[0].field), (20, "body"));
+        assert_eq!(limited[0].limit, HtmlLimit::TooLarge);
+        assert!(limits.take("authorities").is_empty());
     }
 
     #[test]
@@ -369,7 +1208,7 @@ mod tests {
             zip: "22030".into(),
         }];
 
-        let result = clean_courts(&rows).unwrap();
+        let result = clean_courts(&rows, &EtlOptions::default(), &mut Vec::new()).unwrap();
         assert_eq!(result.height(), 1);
         let text = result
             .column("clean_text")
@@ -381,4 +1220,71 @@ mod tests {
         assert!(text.contains("Circuit Court"));
         assert!(text.contains("Fairfax"));
     }
+
+    #[test]
+    fn test_clean_federal_code() {
+        let row = |id: i64, code: &str, section: &str, body: &str| FederalRow {
+            id,
+            code: code.into(),
+            title_num: "42".into(),
+            section: section.into(),
+            heading: "<b>Civil action for deprivation of rights</b>".into(),
+            body: body.into(),
+        };
+        let rows = vec![
+            row(
+                1,
+                " USC ",
+                "1983",
+                "<p>Every person who, under color of any statute</p>",
+            ),
+            row(2, "ucc", "2-207", "Additional terms in acceptance"),
+            row(3, "usc", "", "A body without a section"),
+            row(4, "cfr", "35.130", ""),
+        ];
+
+        let mut dropped = Vec::new();
+        let result = clean_federal_code(&rows, &EtlOptions::default(), &mut dropped, &LimitHits::default()).unwrap();
+        assert_eq!(result.height(), 1);
+        let text = result
+            .column("clean_text")
+            .unwrap()
+            .str()
+            .unwrap()
+            .get(0)
+            .unwrap();
+        assert!(text.starts_with("42 U.S.C. § 1983 Civil action"));
+        let codes = result.column("code").unwrap().str().unwrap();
+        assert_eq!(codes.get(0), Some("usc"));
+
+        let reasons: Vec<_> = dropped.iter().map(|d| (d.id, d.reason.as_str())).collect();
+        assert_eq!(
+            reasons,
+            [(2, "unknown_code"), (3, "empty_section"), (4, "empty_body")]
+        );
+    }
+    #[test]
+    fn test_document_heading_offsets() {
+        let rows = vec![DocumentRow {
+            id: -1,
+            dataset: "memo.docx".into(),
+            filename: "memo.docx".into(),
+            title: "Memo".into(),
+            content: "<h1>Memo</h1>\n<p>Intro.</p>\n<h2>Sec. 18.2-57</h2>\n<p>Assault.</p>".into(),
+        }];
+        let mut etl = Etl::default();
+        etl.documents(&rows).unwrap();
+        let cleaned = etl.finish().unwrap();
+        let text = cleaned
+            .documents
+            .column("clean_text")
+            .unwrap()
+            .str()
+            .unwrap()
+            .get(0)
+            .unwrap();
+        let starts = &cleaned.document_headings[&-1];
+        assert_eq!(starts.len(), 2);
+        assert!(text[starts[1]..].starts_with("§ 18.2-57 Assault."));
+    }
 }
@@ -0,0 +1,261 @@
+//! The data-quality report ETL produces, written beside the output DB as
+//! `etl-quality.json`: per source, the rows read and kept, the rows each
+//! filter dropped, the share of rows with each field empty or holding HTML,
+//! and `clean_text` length percentiles; plus every cluster of code sections
+//! deduplicated into one.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use polars::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::db::source_hashes::SourceKey;
+use crate::etl::DroppedRow;
+
+/// An opening or closing tag, e.g. `<p class="x">` or `</td>`; a bare `<` in
+/// "a < b" is not HTML.
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"</?[A-Za-z][A-Za-z0-9]*(?:\s[^<>]*)?/?>").unwrap());
+
+/// Field counts of one source's rows, accumulated batch by batch.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FieldCounts {
+    rows: usize,
+    empty: BTreeMap<&'static str, usize>,
+    html: BTreeMap<&'static str, usize>,
+}
+
+impl FieldCounts {
+    pub(crate) fn observe<T: SourceKey>(&mut self, rows: &[T]) {
+        for name in T::FIELD_NAMES {
+            self.empty.entry(name).or_default();
+            self.html.entry(name).or_default();
+        }
+        for row in rows {
+            self.rows += 1;
+            for (name, value) in T::FIELD_NAMES.iter().zip(row.fields()) {
+                if value.trim().is_empty() {
+                    *self.empty.entry(name).or_default() += 1;
+                } else if value.contains('<') && TAG_RE.is_match(&value) {
+                    *self.html.entry(name).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: FieldCounts) {
+        self.rows += other.rows;
+        for (name, n) in other.empty {
+            *self.empty.entry(name).or_default() += n;
+        }
+        for (name, n) in other.html {
+            *self.html.entry(name).or_default() += n;
+        }
+    }
+}
+
+/// `clean_text` length in chars.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub min: usize,
+    pub p10: usize,
+    pub p50: usize,
+    pub p90: usize,
+    pub p99: usize,
+    pub max: usize,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles of `lengths`; `None` when it is empty.
+    fn of(mut lengths: Vec<usize>) -> Option<Self> {
+        if lengths.is_empty() {
+            return None;
+        }
+        lengths.sort_unstable();
+        let at = |p: f64| {
+            lengths[((p * lengths.len() as f64).ceil() as usize).clamp(1, lengths.len()) - 1]
+        };
+        Some(Self {
+            min: lengths[0],
+            p10: at(0.10),
+            p50: at(0.50),
+            p90: at(0.90),
+            p99: at(0.99),
+            max: lengths[lengths.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SourceQuality {
+    /// Rows fed to ETL.
+    pub rows: usize,
+    /// Rows left after every filter and dedup.
+    pub kept: usize,
+    /// Rows dropped, by filter.
+    pub dropped: BTreeMap<String, usize>,
+    /// Share of rows with each field empty.
+    pub empty_rate: BTreeMap<&'static str, f64>,
+    /// Share of rows with HTML tags in each field.
+    pub html_rate: BTreeMap<&'static str, f64>,
+    /// Lengths of the kept rows' `clean_text`; absent when none were kept.
+    pub length: Option<Percentiles>,
+}
+
+/// Code sections with the same `clean_text`: the one kept and the ones
+/// dropped as its duplicates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateCluster {
+    pub table: &'static str,
+    pub kept_id: i64,
+    pub dropped_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityReport {
+    pub sources: BTreeMap<&'static str, SourceQuality>,
+    /// Largest first.
+    pub duplicate_clusters: Vec<DuplicateCluster>,
+}
+
+impl QualityReport {
+    /// Build the report from each source's field counts and cleaned frame
+    /// and the rows ETL dropped.
+    pub(crate) fn build(
+        counts: &BTreeMap<&'static str, FieldCounts>,
+        frames: &[(&'static str, &DataFrame)],
+        dropped: &[DroppedRow],
+    ) -> Result<Self> {
+        let mut sources: BTreeMap<&'static str, SourceQuality> = BTreeMap::new();
+        for (table, df) in frames {
+            let source = sources.entry(table).or_default();
+            source.kept = df.height();
+            let lengths = df
+                .column("clean_text")?
+                .str()?
+                .into_iter()
+                .map(|t| t.map_or(0, |t| t.chars().count()))
+                .collect();
+            source.length = Percentiles::of(lengths);
+            if let Some(counts) = counts.get(table) {
+                let rate = |n: &usize| match counts.rows {
+                    0 => 0.0,
+                    rows => *n as f64 / rows as f64,
+                };
+                source.rows = counts.rows;
+                source.empty_rate = counts.empty.iter().map(|(f, n)| (*f, rate(n))).collect();
+                source.html_rate = counts.html.iter().map(|(f, n)| (*f, rate(n))).collect();
+            }
+        }
+
+        let mut clusters: BTreeMap<(&'static str, i64), Vec<i64>> = BTreeMap::new();
+        for d in dropped {
+            *sources
+                .entry(d.table)
+                .or_default()
+                .dropped
+                .entry(d.reason.clone())
+                .or_default() += 1;
+            if let Some(kept_id) = d.kept_id {
+                clusters.entry((d.table, kept_id)).or_default().push(d.id);
+            }
+        }
+        let mut duplicate_clusters: Vec<DuplicateCluster> = clusters
+            .into_iter()
+            .map(|((table, kept_id), mut dropped_ids)| {
+                dropped_ids.sort_unstable();
+                DuplicateCluster {
+                    table,
+                    kept_id,
+                    dropped_ids,
+                }
+            })
+            .collect();
+        duplicate_clusters.sort_by_key(|c| std::cmp::Reverse(c.dropped_ids.len()));
+
+        Ok(Self {
+            sources,
+            duplicate_clusters,
+        })
+    }
+}
+
+/// Where the report for the output DB `output` goes.
+pub fn json_path(output: &Path) -> PathBuf {
+    output.with_file_name("etl-quality.json")
+}
+
+/// Write the report as pretty-printed JSON.
+pub fn write_json(path: &Path, report: &QualityReport) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::reader::PopularNameRow;
+
+    #[test]
+    fn test_quality_report() {
+        let row = |id: i64, name: &str, body: &str| PopularNameRow {
+            id,
+            name: name.into(),
+            title_num: "18.2".into(),
+            section: String::new(),
+            body: body.into(),
+        };
+        let mut counts = FieldCounts::default();
+        counts.observe(&[row(1, "Act A", "<p>Text</p>"), row(2, "", "a < b")]);
+        counts.observe(&[row(3, "Act C", "plain"), row(4, "Act D", "")]);
+        let counts = BTreeMap::from([("popular_names", counts)]);
+
+        let df = df!("id" => [1i64, 3], "clean_text" => ["Act A Text", "Act C plain"]).unwrap();
+        let dropped = vec![
+            DroppedRow {
+                table: "popular_names",
+                id: 2,
+                reason: "empty_name".into(),
+                kept_id: None,
+            },
+            DroppedRow {
+                table: "popular_names",
+                id: 4,
+                reason: "duplicate_text".into(),
+                kept_id: Some(3),
+            },
+        ];
+        let report = QualityReport::build(&counts, &[("popular_names", &df)], &dropped).unwrap();
+
+        let source = &report.sources["popular_names"];
+        assert_eq!((source.rows, source.kept), (4, 2));
+        assert_eq!(source.dropped["empty_name"], 1);
+        assert_eq!(source.empty_rate["name"], 0.25);
+        assert_eq!(source.empty_rate["section"], 1.0);
+        // "a < b" is not HTML.
+        assert_eq!(source.html_rate["body"], 0.25);
+        assert_eq!(source.length.as_ref().unwrap().max, 11);
+        assert_eq!(
+            report.duplicate_clusters,
+            [DuplicateCluster {
+                table: "popular_names",
+                kept_id: 3,
+                dropped_ids: vec![4],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let p = Percentiles::of((1..=100).collect()).unwrap();
+        assert_eq!(
+            (p.min, p.p10, p.p50, p.p90, p.p99, p.max),
+            (1, 10, 50, 90, 99, 100)
+        );
+        assert_eq!(Percentiles::of(Vec::new()), None);
+    }
+}
@@ -0,0 +1,250 @@
+//! `[templates]` in `--config`: how each source's fields are assembled into
+//! `clean_text`, so embedding-input formats can be tried without a rebuild:
+//!
+//! ```toml
+//! [templates]
+//! virginia_code = "{title_name} › {chapter_name} › § {section}: {title}. {body}"
+//! documents = "{title}\n\n{content}"
+//! ```
+//!
+//! A placeholder names a source field; HTML fields are filled in stripped and
+//! normalized. `{{` and `}}` are literal braces. A source without a template
+//! keeps the built-in format.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Result};
+use polars::prelude::*;
+
+/// Each source's placeholders and the ETL column that fills them.
+const FIELDS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "virginia_code",
+        &[
+            ("title_num", "title_num"),
+            ("title_name", "title_name"),
+            ("chapter_num", "chapter_num"),
+            ("chapter_name", "chapter_name"),
+            ("section", "section"),
+            ("title", "title_clean"),
+            ("body", "body_clean"),
+        ],
+    ),
+    (
+        "constitution",
+        &[
+            ("article_id", "article_id"),
+            ("article_name", "article_name"),
+            ("section_count", "section_count"),
+            ("section_name", "section_name_clean"),
+            ("section_title", "section_title_clean"),
+            ("section_text", "section_text_clean"),
+        ],
+    ),
+    (
+        "authorities",
+        &[
+            ("short_name", "short_name"),
+            ("title", "title_clean"),
+            ("body", "body_clean"),
+        ],
+    ),
+    (
+        "courts",
+        &[
+            ("name", "name"),
+            ("locality", "locality"),
+            ("court_type", "court_type"),
+            ("district", "district"),
+            ("city", "city"),
+        ],
+    ),
+    ("popular_names", &[("name", "name"), ("body", "body_clean")]),
+    (
+        "acts",
+        &[
+            ("year", "year"),
+            ("chapter", "chapter"),
+            ("title", "title_clean"),
+            ("body", "body_clean"),
+        ],
+    ),
+    (
+        "federal_code",
+        &[
+            ("code", "code"),
+            ("title_num", "title_num"),
+            ("section", "section"),
+            ("citation", "citation"),
+            ("heading", "heading_clean"),
+            ("body", "body_clean"),
+        ],
+    ),
+    (
+        "documents",
+        &[
+            ("filename", "filename"),
+            ("title", "title_clean"),
+            ("content", "content_clean"),
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// The ETL column a placeholder reads.
+    Column(&'static str),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextTemplate {
+    parts: Vec<Part>,
+}
+
+impl TextTemplate {
+    /// Parse `template` for `source`, failing on an unknown source or
+    /// placeholder, or an unbalanced brace.
+    pub fn parse(source: &str, template: &str) -> Result<Self> {
+        let Some((_, fields)) = FIELDS.iter().find(|(s, _)| *s == source) else {
+            bail!(
+                "Unknown source {:?} in [templates] (sources: {})",
+                source,
+                FIELDS
+                    .iter()
+                    .map(|(s, _)| *s)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Unclosed '{{' in the {} template", source),
+                        }
+                    }
+                    let Some((_, column)) = fields.iter().find(|(f, _)| *f == name.trim()) else {
+                        bail!(
+                            "Unknown field {{{}}} in the {} template (fields: {})",
+                            name,
+                            source,
+                            fields
+                                .iter()
+                                .map(|(f, _)| *f)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Column(column));
+                }
+                '}' => bail!("Unmatched '}}' in the {} template", source),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The template as a polars expression over the ETL columns.
+    fn expr(&self) -> Expr {
+        self.parts.iter().fold(lit(""), |acc, part| match part {
+            Part::Text(text) => acc + lit(text.clone()),
+            Part::Column(column) => acc + col(*column).cast(DataType::String).fill_null(lit("")),
+        })
+    }
+}
+
+/// The configured template of each source.
+#[derive(Debug, Default)]
+pub struct TextTemplates {
+    by_source: HashMap<String, TextTemplate>,
+}
+
+impl TextTemplates {
+    /// Parse `[templates]`.
+    pub fn new(templates: &BTreeMap<String, String>) -> Result<Self> {
+        let by_source = templates
+            .iter()
+            .map(|(source, template)| Ok((source.clone(), TextTemplate::parse(source, template)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self { by_source })
+    }
+
+    /// `source`'s `clean_text` expression: its template, or `default`.
+    pub(crate) fn clean_text(&self, source: &str, default: Expr) -> Expr {
+        match self.by_source.get(source) {
+            Some(template) => template.expr(),
+            None => default,
+        }
+        .alias("clean_text")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_parse() {
+        let template = TextTemplate::parse("acts", "{{Acts}} {year} c. {chapter}: {body}").unwrap();
+        assert_eq!(
+            template.parts,
+            [
+                Part::Text("{Acts} ".into()),
+                Part::Column("year"),
+                Part::Text(" c. ".into()),
+                Part::Column("chapter"),
+                Part::Text(": ".into()),
+                Part::Column("body_clean"),
+            ]
+        );
+        assert!(TextTemplate::parse("acts", "{section}").is_err());
+        assert!(TextTemplate::parse("statutes", "{body}").is_err());
+        assert!(TextTemplate::parse("acts", "{body}}").is_err());
+        assert!(TextTemplate::parse("acts", "{body").is_err());
+    }
+
+    #[test]
+    fn test_template_expr() {
+        let df = df!(
+            "year" => [2023i64],
+            "chapter" => ["12"],
+            "body_clean" => [None::<&str>],
+        )
+        .unwrap();
+        let templates = TextTemplates::new(&BTreeMap::from([(
+            "acts".to_string(),
+            "Acts {year}, c. {chapter}: {body}".to_string(),
+        )]))
+        .unwrap();
+        let out = df
+            .lazy()
+            .select([templates.clean_text("acts", lit("default"))])
+            .collect()
+            .unwrap();
+        let text = out.column("clean_text").unwrap().str().unwrap().get(0);
+        assert_eq!(text, Some("Acts 2023, c. 12: "));
+    }
+}
@@ -0,0 +1,265 @@
+//! Per-title cleanup of code section bodies whose formatting generic HTML
+//! stripping mangles, e.g. Title 58.1's tax tables and Title 8.01's forms.
+//! Transforms run on a section's raw body before ETL strips its HTML, picked
+//! by the section's `title_num`, so title-specific quirks stay out of
+//! `clean_virginia_code`.
+//!
+//! Built-ins implement [`TitleTransform`]. `[[transforms]]` in `--config`
+//! replaces the default assignments ([`TitleTransforms::default`]) and can add
+//! regex replacements:
+//!
+//! ```toml
+//! [[transforms]]
+//! title = "58.1"
+//! apply = ["tax_tables"]
+//!
+//! [[transforms]]
+//! title = "46.2"
+//! replace = [{ pattern = '\[Effective until [^\]]*\]', with = "" }]
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+/// A cleanup applied to the raw body of every code section in a title.
+pub trait TitleTransform: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `body` rewritten, or `None` when there was nothing to change.
+    fn apply(&self, body: &str) -> Option<String>;
+}
+
+static ROW_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<tr\b[^>]*>(.*?)</tr\s*>").unwrap());
+
+static CELL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<t[dh]\b[^>]*>(.*?)</t[dh]\s*>").unwrap());
+
+/// Table rows as `cell | cell;` paragraphs. Stripped as-is, a rate table's
+/// cells run together into one line of numbers.
+pub struct TaxTables;
+
+impl TitleTransform for TaxTables {
+    fn name(&self) -> &'static str {
+        "tax_tables"
+    }
+
+    fn apply(&self, body: &str) -> Option<String> {
+        if !ROW_RE.is_match(body) {
+            return None;
+        }
+        let rows = ROW_RE.replace_all(body, |row: &regex::Captures| {
+            let cells: Vec<&str> = CELL_RE
+                .captures_iter(&row[1])
+                .map(|cell| cell.get(1).map_or("", |m| m.as_str().trim()))
+                .collect();
+            format!("<p>{};</p>", cells.join(" | "))
+        });
+        Some(rows.into_owned())
+    }
+}
+
+static BLANK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"_{3,}").unwrap());
+
+static LEADER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:\. ?){5,}").unwrap());
+
+/// Form blanks (`__________`) as `[blank]` and dot leaders dropped, so a
+/// form's chunks aren't mostly punctuation.
+pub struct FormBlanks;
+
+impl TitleTransform for FormBlanks {
+    fn name(&self) -> &'static str {
+        "form_blanks"
+    }
+
+    fn apply(&self, body: &str) -> Option<String> {
+        if !BLANK_RE.is_match(body) && !LEADER_RE.is_match(body) {
+            return None;
+        }
+        let body = BLANK_RE.replace_all(body, "[blank]");
+        Some(LEADER_RE.replace_all(&body, " ").into_owned())
+    }
+}
+
+/// A configured regex replacement.
+struct Replace {
+    re: Regex,
+    with: String,
+}
+
+impl TitleTransform for Replace {
+    fn name(&self) -> &'static str {
+        "replace"
+    }
+
+    fn apply(&self, body: &str) -> Option<String> {
+        match self.re.replace_all(body, self.with.as_str()) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(body) => Some(body),
+        }
+    }
+}
+
+/// A built-in transform by name.
+fn builtin(name: &str) -> Option<Box<dyn TitleTransform>> {
+    match name {
+        "tax_tables" => Some(Box::new(TaxTables)),
+        "form_blanks" => Some(Box::new(FormBlanks)),
+        _ => None,
+    }
+}
+
+const BUILTIN_NAMES: &[&str] = &["tax_tables", "form_blanks"];
+
+/// One `[[transforms]]` entry: what to run on a title's sections, built-ins
+/// first, then replacements, in order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformRule {
+    /// `title_num`, e.g. `58.1`.
+    pub title: String,
+    /// Built-in transforms by name.
+    #[serde(default)]
+    pub apply: Vec<String>,
+    #[serde(default)]
+    pub replace: Vec<Replacement>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replacement {
+    pub pattern: String,
+    /// Replacement text; `$1` etc. refer to the pattern's groups.
+    #[serde(default)]
+    pub with: String,
+}
+
+/// The transforms to run on each title's sections.
+pub struct TitleTransforms {
+    by_title: HashMap<String, Vec<Box<dyn TitleTransform>>>,
+}
+
+impl TitleTransforms {
+    /// Compile `[[transforms]]`, failing on an unknown built-in or a bad
+    /// pattern.
+    pub fn new(rules: &[TransformRule]) -> Result<Self> {
+        let mut by_title: HashMap<String, Vec<Box<dyn TitleTransform>>> = HashMap::new();
+        for rule in rules {
+            let transforms = by_title.entry(rule.title.clone()).or_default();
+            for name in &rule.apply {
+                let Some(transform) = builtin(name) else {
+                    bail!(
+                        "Unknown transform {:?} for title {} (built-ins: {})",
+                        name,
+                        rule.title,
+                        BUILTIN_NAMES.join(", ")
+                    );
+                };
+                transforms.push(transform);
+            }
+            for r in &rule.replace {
+                let re = Regex::new(&r.pattern).with_context(|| {
+                    format!(
+                        "Invalid transform pattern {:?} for title {}",
+                        r.pattern, rule.title
+                    )
+                })?;
+                transforms.push(Box::new(Replace {
+                    re,
+                    with: r.with.clone(),
+                }));
+            }
+        }
+        Ok(Self { by_title })
+    }
+
+    /// `[[transforms]]` when configured, otherwise the defaults.
+    pub fn from_config(rules: Option<&[TransformRule]>) -> Result<Self> {
+        match rules {
+            Some(rules) => Self::new(rules),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// `body` after every transform for `title_num`, in order.
+    pub fn apply<'a>(&self, title_num: &str, body: &'a str) -> Cow<'a, str> {
+        let mut body = Cow::Borrowed(body);
+        for transform in self.by_title.get(title_num).into_iter().flatten() {
+            if let Some(changed) = transform.apply(&body) {
+                body = Cow::Owned(changed);
+            }
+        }
+        body
+    }
+}
+
+/// `tax_tables` on Title 58.1 and `form_blanks` on Title 8.01.
+impl Default for TitleTransforms {
+    fn default() -> Self {
+        let by_title = [("58.1", TaxTables.name()), ("8.01", FormBlanks.name())]
+            .into_iter()
+            .map(|(title, name)| (title.to_string(), builtin(name).into_iter().collect()))
+            .collect();
+        Self { by_title }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::html::strip_html;
+
+    #[test]
+    fn test_default_transforms() {
+        let transforms = TitleTransforms::default();
+        let table = "<table><tr><th>Income</th><th>Rate</th></tr>\
+                     <tr><td>$0 - $3,000</td><td>2%</td></tr></table>";
+        let body = transforms.apply("58.1", table);
+        assert_eq!(strip_html(&body), "Income | Rate; $0 - $3,000 | 2%;");
+        // Other titles are untouched.
+        assert!(matches!(transforms.apply("46.2", table), Cow::Borrowed(_)));
+
+        let form = "Plaintiff: __________ Date . . . . . . 20__";
+        assert_eq!(
+            transforms.apply("8.01", form),
+            "Plaintiff: [blank] Date  20__"
+        );
+    }
+
+    #[test]
+    fn test_configured_transforms() {
+        let rules: Vec<TransformRule> = toml::from_str::<HashMap<String, Vec<TransformRule>>>(
+            r#"
+            [[transforms]]
+            title = "46.2"
+            apply = ["form_blanks"]
+            replace = [{ pattern = '\[Effective until [^\]]*\]\s*', with = "" }]
+            "#,
+        )
+        .unwrap()
+        .remove("transforms")
+        .unwrap();
+        let transforms = TitleTransforms::new(&rules).unwrap();
+        assert_eq!(
+            transforms.apply("46.2", "[Effective until July 1, 2026] Fee: _____"),
+            "Fee: [blank]"
+        );
+        // Configuring transforms replaces the defaults.
+        assert!(matches!(
+            transforms.apply("58.1", "<tr><td>a</td></tr>"),
+            Cow::Borrowed(_)
+        ));
+
+        let unknown = TransformRule {
+            title: "1".into(),
+            apply: vec!["nope".into()],
+            replace: Vec::new(),
+        };
+        assert!(TitleTransforms::new(&[unknown]).is_err());
+    }
+}
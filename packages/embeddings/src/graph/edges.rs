@@ -1,18 +1,66 @@
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::LazyLock;
 
 use regex::Regex;
 
 use crate::db::reader::{ConstitutionRow, DocumentRow, VirginiaCodeRow};
-use crate::graph::nodes::Node;
+use crate::graph::lookup_key;
+use crate::graph::nodes::{federal_key, Node};
+use crate::text::normalize::normalize;
 
+/// A directed edge between two nodes: `contains`, `cites` (to a Virginia or,
+/// from `federal_code`, a federal section), `references`, `amends` or
+/// `enacts`.
 #[derive(Debug, Clone)]
 pub struct Edge {
     pub from_id: i64,
     pub to_id: i64,
     pub rel_type: String,
+    /// How many times the source text mentions the target, for `cites`,
+    /// `references`, `amends` and `enacts` (one edge per distinct target, not
+    /// per mention); `None` for `contains`.
     pub weight: Option<f64>,
 }
 
+/// A section reference found in text that matched no node in the lookup.
+#[derive(Debug, Clone)]
+pub struct UnresolvedCitation {
+    pub from_id: i64,
+    pub section_ref: String,
+}
+
+/// One matched citation behind a `cites` / `references` / `amends` / `enacts`
+/// edge. The offsets are byte offsets into the source node's text (the chunk
+/// that was embedded), except for `references`, where they are into the
+/// document's normalized `content`, which is extracted before HTML stripping
+/// so hrefs count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeProvenance {
+    pub from_id: i64,
+    pub to_id: i64,
+    pub rel_type: String,
+    pub chunk_idx: i64,
+    pub char_start: usize,
+    pub char_end: usize,
+    /// The text the extraction regex matched, e.g. `§ 8.01-230`.
+    pub matched: String,
+}
+
+/// Result of building edges: the deduplicated edge list plus every citation
+/// that could not be resolved (useful for spotting extraction bugs).
+pub struct EdgeBuildResult {
+    pub edges: Vec<Edge>,
+    pub unresolved: Vec<UnresolvedCitation>,
+    /// Where each citation edge's mentions were found.
+    pub provenance: Vec<EdgeProvenance>,
+    /// Every distinct section number each document mentions (resolved or
+    /// not), keyed by the document's first chunk node.
+    pub document_mentions: Vec<(i64, Vec<String>)>,
+}
+
+/// Pass 2: hierarchy edges from the source rows, and citation edges from
+/// section references in node texts and document content.
 pub fn build_edges(
     nodes: &[Node],
     lookup: &HashMap<(String, String), Vec<i64>>,
@@ -20,17 +68,55 @@ pub fn build_edges(
     constitution_rows: &[ConstitutionRow],
     document_rows: &[DocumentRow],
     texts: &HashMap<i64, String>,
-) -> Vec<Edge> {
+) -> EdgeBuildResult {
     let mut edges = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut provenance = Vec::new();
+    let mut document_mentions = Vec::new();
 
     // --- Structural hierarchy edges ---
     build_hierarchy_edges(nodes, lookup, code_rows, constitution_rows, &mut edges);
 
     // --- Citation edges ---
-    build_citation_edges(nodes, lookup, texts, &mut edges);
+    build_citation_edges(
+        nodes,
+        lookup,
+        texts,
+        &mut edges,
+        &mut unresolved,
+        &mut provenance,
+    );
+
+    // --- Session-law edges ---
+    build_act_edges(
+        nodes,
+        lookup,
+        texts,
+        &mut edges,
+        &mut unresolved,
+        &mut provenance,
+    );
+
+    // --- Federal citation edges ---
+    build_federal_edges(
+        nodes,
+        lookup,
+        texts,
+        &mut edges,
+        &mut unresolved,
+        &mut provenance,
+    );
 
     // --- Document reference edges ---
-    build_document_reference_edges(nodes, lookup, document_rows, &mut edges);
+    build_document_reference_edges(
+        nodes,
+        lookup,
+        document_rows,
+        &mut edges,
+        &mut unresolved,
+        &mut provenance,
+        &mut document_mentions,
+    );
 
     // Deduplicate edges
     edges.sort_by(|a, b| {
@@ -41,7 +127,12 @@ pub fn build_edges(
     });
     edges.dedup_by(|a, b| a.from_id == b.from_id && a.to_id == b.to_id && a.rel_type == b.rel_type);
 
-    edges
+    EdgeBuildResult {
+        edges,
+        unresolved,
+        provenance,
+        document_mentions,
+    }
 }
 
 fn build_hierarchy_edges(
@@ -53,12 +144,12 @@ fn build_hierarchy_edges(
 ) {
     // title -> chapter -> section hierarchy
     for row in code_rows {
-        let title_key = ("virginia_code".to_string(), row.title_num.clone());
-        let ch_key = (
-            "virginia_code".to_string(),
-            format!("{}:{}", row.title_num, row.chapter_num),
+        let title_key = lookup_key("virginia_code", &row.title_num);
+        let ch_key = lookup_key(
+            "virginia_code",
+            &format!("{}:{}", row.title_num, row.chapter_num),
         );
-        let section_key = ("virginia_code".to_string(), row.section.clone());
+        let section_key = lookup_key("virginia_code", &row.section);
 
         // title contains chapter
         if let (Some(title_ids), Some(ch_ids)) = (lookup.get(&title_key), lookup.get(&ch_key)) {
@@ -91,13 +182,10 @@ fn build_hierarchy_edges(
 
     // Constitution: article -> section
     for row in constitution_rows {
-        let article_key = (
-            "constitution".to_string(),
-            format!("article:{}", row.article_id),
-        );
-        let section_key = (
-            "constitution".to_string(),
-            format!("{}:{}", row.article_id, row.section_count),
+        let article_key = lookup_key("constitution", &format!("article:{}", row.article_id));
+        let section_key = lookup_key(
+            "constitution",
+            &format!("{}:{}", row.article_id, row.section_count),
         );
 
         if let (Some(art_ids), Some(sec_ids)) =
@@ -122,6 +210,8 @@ fn build_citation_edges(
     lookup: &HashMap<(String, String), Vec<i64>>,
     texts: &HashMap<i64, String>,
     edges: &mut Vec<Edge>,
+    unresolved: &mut Vec<UnresolvedCitation>,
+    provenance: &mut Vec<EdgeProvenance>,
 ) {
     let re_href = Regex::new(r#"href.*?/vacode/([^/'"]+)"#).unwrap();
     let re_section = Regex::new(r"§\s*(\d+(?:\.\d+)*-\d+(?:\.\d+)*)").unwrap();
@@ -141,21 +231,137 @@ fn build_citation_edges(
             None => continue,
         };
 
-        let cited_sections = extract_section_refs(text, &re_href, &re_section, &re_sections_plural);
+        let cited_sections =
+            count_section_refs(text, &re_href, &re_section, &re_sections_plural);
 
-        for section_ref in cited_sections {
-            let target_key = ("virginia_code".to_string(), section_ref);
-            if let Some(target_ids) = lookup.get(&target_key) {
+        for (section_ref, spans) in cited_sections {
+            if let Some(target_ids) = lookup.get(&lookup_key("virginia_code", &section_ref)) {
                 for &tid in target_ids {
                     if tid != node.id {
                         edges.push(Edge {
                             from_id: node.id,
                             to_id: tid,
                             rel_type: "cites".into(),
-                            weight: None,
+                            weight: Some(spans.len() as f64),
                         });
+                        push_provenance(
+                            provenance,
+                            (node.id, tid),
+                            "cites",
+                            node.chunk_idx,
+                            text,
+                            &spans,
+                        );
                     }
                 }
+            } else {
+                unresolved.push(UnresolvedCitation {
+                    from_id: node.id,
+                    section_ref,
+                });
+            }
+        }
+    }
+}
+
+/// `amends` / `enacts` edges from each act chunk to the sections its enacting
+/// clauses name, weighted by how often the act names them.
+fn build_act_edges(
+    nodes: &[Node],
+    lookup: &HashMap<(String, String), Vec<i64>>,
+    texts: &HashMap<i64, String>,
+    edges: &mut Vec<Edge>,
+    unresolved: &mut Vec<UnresolvedCitation>,
+    provenance: &mut Vec<EdgeProvenance>,
+) {
+    for node in nodes.iter().filter(|n| n.node_type == "act") {
+        let Some(text) = texts.get(&node.id) else {
+            continue;
+        };
+        for (rel_type, section_ref, spans) in extract_act_refs(text) {
+            let Some(target_ids) = lookup.get(&lookup_key("virginia_code", &section_ref)) else {
+                unresolved.push(UnresolvedCitation {
+                    from_id: node.id,
+                    section_ref,
+                });
+                continue;
+            };
+            for &tid in target_ids {
+                edges.push(Edge {
+                    from_id: node.id,
+                    to_id: tid,
+                    rel_type: rel_type.into(),
+                    weight: Some(spans.len() as f64),
+                });
+                push_provenance(
+                    provenance,
+                    (node.id, tid),
+                    rel_type,
+                    node.chunk_idx,
+                    text,
+                    &spans,
+                );
+            }
+        }
+    }
+}
+
+/// `cites` edges from any chunk that cites the U.S.C. or the CFR to the
+/// federal section it names. Skipped when the input has no `federal_code`
+/// table, so federal citations aren't all reported as unresolved.
+fn build_federal_edges(
+    nodes: &[Node],
+    lookup: &HashMap<(String, String), Vec<i64>>,
+    texts: &HashMap<i64, String>,
+    edges: &mut Vec<Edge>,
+    unresolved: &mut Vec<UnresolvedCitation>,
+    provenance: &mut Vec<EdgeProvenance>,
+) {
+    if !nodes.iter().any(|n| n.node_type == "federal_section") {
+        return;
+    }
+    for node in nodes {
+        if !matches!(
+            node.node_type.as_str(),
+            "section"
+                | "constitution_section"
+                | "authority"
+                | "popular_name"
+                | "act"
+                | "manual_chunk"
+                | "federal_section"
+        ) {
+            continue;
+        }
+        let Some(text) = texts.get(&node.id) else {
+            continue;
+        };
+        for (key, spans) in extract_federal_refs(text) {
+            let Some(target_ids) = lookup.get(&lookup_key("federal_code", &key)) else {
+                unresolved.push(UnresolvedCitation {
+                    from_id: node.id,
+                    section_ref: key,
+                });
+                continue;
+            };
+            for &tid in target_ids {
+                if tid == node.id {
+                    continue;
+                }
+                edges.push(Edge {
+                    from_id: node.id,
+                    to_id: tid,
+                    rel_type: "cites".into(),
+                    weight: Some(spans.len() as f64),
+                });
+                push_provenance(
+                    provenance,
+                    (node.id, tid),
+                    "cites",
+                    node.chunk_idx,
+                    text,
+                    &spans,
+                );
             }
         }
     }
@@ -166,25 +372,33 @@ fn build_document_reference_edges(
     lookup: &HashMap<(String, String), Vec<i64>>,
     document_rows: &[DocumentRow],
     edges: &mut Vec<Edge>,
+    unresolved: &mut Vec<UnresolvedCitation>,
+    provenance: &mut Vec<EdgeProvenance>,
+    document_mentions: &mut Vec<(i64, Vec<String>)>,
 ) {
     let re_href = Regex::new(r#"href.*?/vacode/([^/'"]+)"#).unwrap();
     let re_section = Regex::new(r"§\s*(\d+(?:\.\d+)*-\d+(?:\.\d+)*)").unwrap();
     let re_sections_plural = Regex::new(r"§§\s*([\d.,\s\-and]+)").unwrap();
 
     for row in document_rows {
-        let doc_key = ("documents".to_string(), row.filename.clone());
-        let doc_node_ids = match lookup.get(&doc_key) {
+        let doc_node_ids = match lookup.get(&lookup_key("documents", &row.filename)) {
             Some(ids) => ids.clone(),
             None => continue,
         };
 
         // Extract citations from the raw content (before stripping, to capture hrefs)
+        let content = normalize(&row.content);
         let cited_sections =
-            extract_section_refs(&row.content, &re_href, &re_section, &re_sections_plural);
+            count_section_refs(&content, &re_href, &re_section, &re_sections_plural);
+        if let Some(&first_doc_id) = doc_node_ids.first() {
+            document_mentions.push((
+                first_doc_id,
+                cited_sections.iter().map(|(r, _)| r.clone()).collect(),
+            ));
+        }
 
-        for section_ref in cited_sections {
-            let target_key = ("virginia_code".to_string(), section_ref);
-            if let Some(target_ids) = lookup.get(&target_key) {
+        for (section_ref, spans) in cited_sections {
+            if let Some(target_ids) = lookup.get(&lookup_key("virginia_code", &section_ref)) {
                 // Only create edge from the first chunk of the document
                 if let Some(&first_doc_id) = doc_node_ids.first() {
                     for &tid in target_ids {
@@ -192,10 +406,23 @@ fn build_document_reference_edges(
                             from_id: first_doc_id,
                             to_id: tid,
                             rel_type: "references".into(),
-                            weight: None,
+                            weight: Some(spans.len() as f64),
                         });
+                        push_provenance(
+                            provenance,
+                            (first_doc_id, tid),
+                            "references",
+                            0,
+                            &content,
+                            &spans,
+                        );
                     }
                 }
+            } else if let Some(&first_doc_id) = doc_node_ids.first() {
+                unresolved.push(UnresolvedCitation {
+                    from_id: first_doc_id,
+                    section_ref,
+                });
             }
         }
     }
@@ -209,25 +436,172 @@ fn build_document_reference_edges(
     }
 }
 
+fn push_provenance(
+    provenance: &mut Vec<EdgeProvenance>,
+    (from_id, to_id): (i64, i64),
+    rel_type: &str,
+    chunk_idx: i64,
+    text: &str,
+    spans: &[Range<usize>],
+) {
+    provenance.extend(spans.iter().map(|span| EdgeProvenance {
+        from_id,
+        to_id,
+        rel_type: rel_type.to_string(),
+        chunk_idx,
+        char_start: span.start,
+        char_end: span.end,
+        matched: text[span.clone()].to_string(),
+    }));
+}
+
+/// The Code section reference patterns `build_citation_edges` uses, compiled
+/// once for [`cited_statutes`], which runs on every answer and passage.
+static HREF_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"href.*?/vacode/([^/'"]+)"#).unwrap());
+static SECTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"§\s*(\d+(?:\.\d+)*-\d+(?:\.\d+)*)").unwrap());
+static SECTIONS_PLURAL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"§§\s*([\d.,\s\-and]+)").unwrap());
+
+/// Bare section number inside a `§§` list, e.g. `8.01-230`.
+static SECTION_NUMBER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d+(?:\.\d+)*-\d+(?:\.\d+)*").unwrap());
+
+/// A list of section numbers in an enacting clause: `8.01-230`,
+/// `8.01-230 and 8.01-243`, `8.01-229.1 through 8.01-229.4` (a range
+/// contributes its two ends).
+const ACT_SECTION_LIST: &str = r"((?:[\d.,\s-]|and\b|through\b)+)";
+
+/// Enacting-clause forms and the edge each produces: the title's "to amend
+/// and reenact § X", the clause "That §§ X and Y of the Code of Virginia are
+/// amended and reenacted", and "by adding in Chapter 3 ... a section numbered
+/// X" for new sections.
+static ACT_CLAUSE_RES: LazyLock<[(&str, Regex); 3]> = LazyLock::new(|| {
+    let re = |prefix: &str, suffix: &str| {
+        Regex::new(&format!("(?i){prefix}{ACT_SECTION_LIST}{suffix}")).unwrap()
+    };
+    [
+        ("amends", re(r"\bto amend and reenact\s+§§?\s*", "")),
+        (
+            "amends",
+            re(
+                r"\bthat\s+§§?\s*",
+                r"(?:of the Code of Virginia\s+)?(?:is|are)\s+amended and reenacted",
+            ),
+        ),
+        ("enacts", re(r"\bby adding\b[^;:]*?\bsections?\s+numbered\s+", "")),
+    ]
+});
+
+/// Federal citation forms, with the title and section captured: `42 U.S.C.
+/// § 1983`, `42 USC 2000e-2`, `42 U.S.C.A. § 1983`, and `29 C.F.R. §
+/// 1910.1200` / `29 CFR 1910.1200`. A bare CFR part (`29 CFR Part 1910`)
+/// names no section and isn't matched.
+static FEDERAL_CITATION_RES: LazyLock<[(&str, Regex); 2]> = LazyLock::new(|| {
+    [
+        (
+            "usc",
+            Regex::new(
+                r"\b(\d+)\s+U\.?\s?S\.?\s?C\.?(?:\s?A\.?)?\s*(?:§§?\s*)?(\d+[a-zA-Z]?(?:-\d+[a-zA-Z]?)*)\b",
+            )
+            .unwrap(),
+        ),
+        (
+            "cfr",
+            Regex::new(r"\b(\d+)\s+C\.?\s?F\.?\s?R\.?\s*(?:§§?\s*)?(\d+\.\d+[a-z]?)\b").unwrap(),
+        ),
+    ]
+});
+
+/// Federal sections cited in `text` as [`federal_key`]s, each with the span of
+/// every citation of it, sorted.
+fn extract_federal_refs(text: &str) -> Vec<(String, Vec<Range<usize>>)> {
+    let mut refs: Vec<(String, Range<usize>)> = Vec::new();
+    for (code, re) in FEDERAL_CITATION_RES.iter() {
+        for cap in re.captures_iter(text) {
+            let whole = cap.get(0).unwrap();
+            refs.push((federal_key(code, &cap[1], &cap[2]), whole.range()));
+        }
+    }
+    refs.sort_by(|a, b| (&a.0, a.1.start).cmp(&(&b.0, b.1.start)));
+
+    let mut grouped: Vec<(String, Vec<Range<usize>>)> = Vec::new();
+    for (key, span) in refs {
+        match grouped.last_mut() {
+            Some((k, spans)) if *k == key => spans.push(span),
+            _ => grouped.push((key, vec![span])),
+        }
+    }
+    grouped
+}
+
+/// Distinct statutes cited in `text`, in order of first mention, each as
+/// `(source, source_id, span of its first mention)`: Code sections cited by
+/// `§` or `§§` and U.S.C. / CFR sections keyed by [`federal_key`]. These are
+/// the references `cites` edges are built from.
+pub fn cited_statutes(text: &str) -> Vec<(&'static str, String, Range<usize>)> {
+    let mut refs: Vec<(&'static str, String, Range<usize>)> =
+        count_section_refs(text, &HREF_RE, &SECTION_RE, &SECTIONS_PLURAL_RE)
+            .into_iter()
+            .map(|(section, spans)| ("virginia_code", section, spans[0].clone()))
+            .collect();
+    refs.extend(
+        extract_federal_refs(text)
+            .into_iter()
+            .map(|(key, spans)| ("federal_code", key, spans[0].clone())),
+    );
+    refs.sort_by_key(|r| r.2.start);
+    refs
+}
+
+/// Sections an act amends or enacts, grouped by `(rel_type, section)` with
+/// the span of each mention, sorted.
+fn extract_act_refs(text: &str) -> Vec<(&'static str, String, Vec<Range<usize>>)> {
+    let mut refs: Vec<(&'static str, String, Range<usize>)> = Vec::new();
+    for (rel_type, re) in ACT_CLAUSE_RES.iter() {
+        for cap in re.captures_iter(text) {
+            let Some(list) = cap.get(1) else { continue };
+            for sec in SECTION_NUMBER_RE.find_iter(list.as_str()) {
+                let start = list.start() + sec.start();
+                refs.push((rel_type, sec.as_str().to_string(), start..start + sec.len()));
+            }
+        }
+    }
+    refs.sort_by(|a, b| (a.0, &a.1, a.2.start).cmp(&(b.0, &b.1, b.2.start)));
+
+    let mut grouped: Vec<(&'static str, String, Vec<Range<usize>>)> = Vec::new();
+    for (rel_type, section, span) in refs {
+        match grouped.last_mut() {
+            Some((r, s, spans)) if *r == rel_type && *s == section => spans.push(span),
+            _ => grouped.push((rel_type, section, vec![span])),
+        }
+    }
+    grouped
+}
+
+/// Every section reference in `text` with the span that matched it, sorted,
+/// with repeated mentions kept. A `§` or href mention spans the whole regex
+/// match; a number in a `§§` list spans just that number.
 fn extract_section_refs(
     text: &str,
     re_href: &Regex,
     re_section: &Regex,
     re_sections_plural: &Regex,
-) -> Vec<String> {
+) -> Vec<(String, Range<usize>)> {
     let mut refs = Vec::new();
 
     // href-based references
     for cap in re_href.captures_iter(text) {
-        if let Some(m) = cap.get(1) {
-            refs.push(m.as_str().to_string());
+        if let (Some(whole), Some(m)) = (cap.get(0), cap.get(1)) {
+            refs.push((m.as_str().to_string(), whole.range()));
         }
     }
 
     // § X.Y-Z references
     for cap in re_section.captures_iter(text) {
-        if let Some(m) = cap.get(1) {
-            refs.push(m.as_str().to_string());
+        if let (Some(whole), Some(m)) = (cap.get(0), cap.get(1)) {
+            refs.push((m.as_str().to_string(), whole.range()));
         }
     }
 
@@ -236,18 +610,36 @@ fn extract_section_refs(
         if let Some(m) = cap.get(1) {
             let list = m.as_str();
             // Split on comma, "and", spaces to extract individual section numbers
-            let section_re = Regex::new(r"\d+(?:\.\d+)*-\d+(?:\.\d+)*").unwrap();
-            for sec_match in section_re.find_iter(list) {
-                refs.push(sec_match.as_str().to_string());
+            for sec_match in SECTION_NUMBER_RE.find_iter(list) {
+                let start = m.start() + sec_match.start();
+                let span = start..start + sec_match.len();
+                refs.push((sec_match.as_str().to_string(), span));
             }
         }
     }
 
-    refs.sort();
-    refs.dedup();
+    refs.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.start.cmp(&b.1.start)));
     refs
 }
 
+/// Distinct section references in `text` with the span of each mention; the
+/// mention count is the weight of `cites` / `references` edges.
+fn count_section_refs(
+    text: &str,
+    re_href: &Regex,
+    re_section: &Regex,
+    re_sections_plural: &Regex,
+) -> Vec<(String, Vec<Range<usize>>)> {
+    let mut counts: Vec<(String, Vec<Range<usize>>)> = Vec::new();
+    for (r, span) in extract_section_refs(text, re_href, re_section, re_sections_plural) {
+        match counts.last_mut() {
+            Some((last, spans)) if *last == r => spans.push(span),
+            _ => counts.push((r, vec![span])),
+        }
+    }
+    counts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -260,8 +652,54 @@ mod tests {
 
         let text = "See § 1-200 and § 2.2-3700 for details.";
         let refs = extract_section_refs(text, &re_href, &re_section, &re_plural);
-        assert!(refs.contains(&"1-200".to_string()));
-        assert!(refs.contains(&"2.2-3700".to_string()));
+        let sections: Vec<&str> = refs.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(sections, ["1-200", "2.2-3700"]);
+        assert_eq!(&text[refs[0].1.clone()], "§ 1-200");
+    }
+
+    #[test]
+    fn test_extract_act_refs() {
+        let text = "Acts 2023 c. 123 An Act to amend and reenact §§ 8.01-230 and 8.01-243 \
+            of the Code of Virginia and to amend the Code of Virginia by adding in \
+            Chapter 4 of Title 8.01 a section numbered 8.01-243.3, relating to \
+            limitations. 1. That §§ 8.01-230 and 8.01-243 of the Code of Virginia \
+            are amended and reenacted and that the Code of Virginia is amended by \
+            adding in Chapter 4 of Title 8.01 a section numbered 8.01-243.3 as \
+            follows: § 8.01-243.3. See § 8.01-229. 2. That § 8.01-231 is repealed.";
+        let refs = extract_act_refs(text);
+        let counts: Vec<(&str, &str, usize)> = refs
+            .iter()
+            .map(|(rel, sec, spans)| (*rel, sec.as_str(), spans.len()))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("amends", "8.01-230", 2),
+                ("amends", "8.01-243", 2),
+                ("enacts", "8.01-243.3", 2),
+            ]
+        );
+        assert_eq!(&text[refs[0].2[0].clone()], "8.01-230");
+    }
+
+    #[test]
+    fn test_extract_federal_refs() {
+        let text = "Actions under 42 U.S.C. § 1983 and 42 USC 2000e-2(a), see also \
+            42 U.S.C.A. §1983; OSHA's rule at 29 C.F.R. § 1910.1200 (not 29 CFR Part 1910).";
+        let refs = extract_federal_refs(text);
+        let keys: Vec<(&str, usize)> = refs.iter().map(|(k, s)| (k.as_str(), s.len())).collect();
+        assert_eq!(
+            keys,
+            [
+                ("cfr:29:1910.1200", 1),
+                ("usc:42:1983", 2),
+                ("usc:42:2000e-2", 1)
+            ]
+        );
+        assert_eq!(&text[refs[1].1[0].clone()], "42 U.S.C. § 1983");
+        assert_eq!(&text[refs[2].1[0].clone()], "42 USC 2000e-2");
+        // Virginia section citations aren't federal
+        assert!(extract_federal_refs("Code § 8.01-230").is_empty());
     }
 
     #[test]
@@ -272,6 +710,71 @@ mod tests {
 
         let text = r#"<a href="https://law.lis.virginia.gov/vacode/19.2-392">link</a>"#;
         let refs = extract_section_refs(text, &re_href, &re_section, &re_plural);
-        assert!(refs.contains(&"19.2-392".to_string()));
+        assert!(refs.iter().any(|(s, _)| s == "19.2-392"));
+    }
+
+    #[test]
+    fn test_count_section_refs() {
+        let re_href = Regex::new(r#"href.*?/vacode/([^/'"]+)"#).unwrap();
+        let re_section = Regex::new(r"§\s*(\d+(?:\.\d+)*-\d+(?:\.\d+)*)").unwrap();
+        let re_plural = Regex::new(r"§§\s*([\d.,\s\-and]+)").unwrap();
+
+        let text = "Under § 1-200, and again § 1-200; see also § 2.2-3700.";
+        let counts = count_section_refs(text, &re_href, &re_section, &re_plural);
+        let counts: Vec<(&str, usize)> =
+            counts.iter().map(|(s, spans)| (s.as_str(), spans.len())).collect();
+        assert_eq!(counts, vec![("1-200", 2), ("2.2-3700", 1)]);
+    }
+
+    #[test]
+    fn test_plural_refs_span_each_number() {
+        let re_href = Regex::new(r#"href.*?/vacode/([^/'"]+)"#).unwrap();
+        let re_section = Regex::new(r"§\s*(\d+(?:\.\d+)*-\d+(?:\.\d+)*)").unwrap();
+        let re_plural = Regex::new(r"§§\s*([\d.,\s\-and]+)").unwrap();
+
+        let text = "See §§ 8.01-230 and 8.01-231.";
+        let refs = extract_section_refs(text, &re_href, &re_section, &re_plural);
+        let matched: Vec<&str> = refs
+            .iter()
+            .filter(|(s, _)| s == "8.01-231")
+            .map(|(_, span)| &text[span.clone()])
+            .collect();
+        assert_eq!(matched, ["8.01-231"]);
+    }
+
+    #[test]
+    fn test_citation_weight_is_mention_count() {
+        let section = |id: i64, source_id: &str| Node {
+            id,
+            source: "virginia_code".into(),
+            source_id: source_id.into(),
+            chunk_idx: 0,
+            node_type: "section".into(),
+            synthetic: false,
+            status: Default::default(),
+        };
+        let nodes = vec![section(1, "1-100"), section(2, "1-200"), section(3, "2.2-3700")];
+        let lookup = nodes
+            .iter()
+            .map(|n| (lookup_key(&n.source, &n.source_id), vec![n.id]))
+            .collect();
+        let texts = HashMap::from([(
+            1,
+            "Under § 1-200, and again § 1-200; see also § 2.2-3700.".to_string(),
+        )]);
+        let (mut edges, mut unresolved, mut provenance) = (Vec::new(), Vec::new(), Vec::new());
+        build_citation_edges(
+            &nodes,
+            &lookup,
+            &texts,
+            &mut edges,
+            &mut unresolved,
+            &mut provenance,
+        );
+        let weights: Vec<(i64, &str, Option<f64>)> =
+            edges.iter().map(|e| (e.to_id, e.rel_type.as_str(), e.weight)).collect();
+        assert_eq!(weights, vec![(2, "cites", Some(2.0)), (3, "cites", Some(1.0))]);
+        // One provenance row per mention, so a weight can be traced.
+        assert_eq!(provenance.len(), 3);
     }
 }
@@ -1,2 +1,20 @@
+//! The knowledge graph: nodes (Pass 1), edges (Pass 2), and what reads or
+//! reshapes them afterwards (paths, pruning, subgraphs, weights).
+
 pub mod edges;
 pub mod nodes;
+pub mod path;
+pub mod prune;
+pub mod store;
+pub mod subgraph;
+pub mod weights;
+
+use crate::text::normalize::fold_key;
+
+/// Key into the node lookup map built in Pass 1 and resolved in Pass 2.
+///
+/// Identifiers are folded, so ids that differ only in case, punctuation or
+/// diacritics across tables resolve to the same nodes.
+pub fn lookup_key(source: &str, id: &str) -> (String, String) {
+    (source.to_string(), fold_key(id))
+}
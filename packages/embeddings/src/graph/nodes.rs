@@ -4,8 +4,34 @@ use anyhow::Result;
 use polars::prelude::*;
 
 use crate::etl::CleanedData;
-use crate::text::chunker::chunk_text;
+use crate::graph::lookup_key;
+use crate::text::chunker::{chunk_sections, chunk_text, ChunkConfig};
+
+/// Every value of `Node::source`.
+pub const SOURCES: [&str; 8] = [
+    "virginia_code",
+    "constitution",
+    "authorities",
+    "courts",
+    "popular_names",
+    "documents",
+    "acts",
+    "federal_code",
+];
+
+/// Pass 3 embeds nodes in ascending tier, so a run that is interrupted or
+/// stopped by `--max-duration` has the statutes searchable first and the
+/// court directory last.
+pub fn embed_priority(node_type: &str) -> u8 {
+    match node_type {
+        "section" | "constitution_section" => 0,
+        "court" => 2,
+        _ => 1,
+    }
+}
 
+/// A graph node: one chunk of a source row, or a synthetic structural node
+/// (title, chapter, article) that has no text of its own.
 #[derive(Debug, Clone)]
 pub struct Node {
     pub id: i64,
@@ -14,6 +40,38 @@ pub struct Node {
     pub chunk_idx: i64,
     pub node_type: String,
     pub synthetic: bool,
+    pub status: NodeStatus,
+}
+
+/// Whether a node's source text is still in force. Only code sections are
+/// ever anything but `Active`; see `etl::section_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeStatus {
+    #[default]
+    Active,
+    /// A "Repealed" stub.
+    Repealed,
+    /// Past the date of its "(Expires ...)" note.
+    Expired,
+}
+
+impl NodeStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Repealed => "repealed",
+            Self::Expired => "expired",
+        }
+    }
+
+    /// The status ETL wrote; anything unrecognized is `Active`.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "repealed" => Self::Repealed,
+            "expired" => Self::Expired,
+            _ => Self::Active,
+        }
+    }
 }
 
 /// Byte-offset metadata for a chunk node, used to slice source text at query time.
@@ -25,15 +83,30 @@ pub struct ChunkMeta {
 }
 
 /// Result of building nodes: the node list, a lookup map, cleaned text per node_id,
-/// and chunk offset metadata for document chunks.
+/// chunk offset metadata for document chunks, and the chunking that produced them.
 pub struct NodeBuildResult {
     pub nodes: Vec<Node>,
     pub lookup: HashMap<(String, String), Vec<i64>>,
     pub texts: HashMap<i64, String>,
+    /// Heading of each code and constitution section, keyed by the node id
+    /// of its first chunk; embedded separately by `--title-embeddings`.
+    pub headings: HashMap<i64, String>,
     pub chunk_meta: Vec<ChunkMeta>,
+    pub chunking: ChunkConfig,
 }
 
 /// Helper: get a string column from a DataFrame as a StringChunked.
+/// `source_id` of an act node: `2023:123` for 2023 Acts, chapter 123.
+pub fn act_key(year: i64, chapter: &str) -> String {
+    format!("{year}:{chapter}")
+}
+
+/// `source_id` of a federal section: `usc:42:1983` for 42 U.S.C. § 1983,
+/// `cfr:29:1910.1200` for 29 C.F.R. § 1910.1200.
+pub fn federal_key(code: &str, title: &str, section: &str) -> String {
+    format!("{code}:{title}:{section}")
+}
+
 fn str_col<'a>(df: &'a DataFrame, name: &str) -> &'a StringChunked {
     df.column(name).unwrap().str().unwrap()
 }
@@ -43,10 +116,13 @@ fn i64_col<'a>(df: &'a DataFrame, name: &str) -> &'a Int64Chunked {
     df.column(name).unwrap().i64().unwrap()
 }
 
-pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
+/// Pass 1: turn cleaned rows into nodes, chunking long texts with `chunking`,
+/// and build the `(source, id)` lookup Pass 2 resolves citations against.
+pub fn build_nodes(cleaned: &CleanedData, chunking: ChunkConfig) -> Result<NodeBuildResult> {
     let mut nodes = Vec::new();
     let mut lookup: HashMap<(String, String), Vec<i64>> = HashMap::new();
     let mut texts: HashMap<i64, String> = HashMap::new();
+    let mut headings: HashMap<i64, String> = HashMap::new();
     let mut chunk_meta: Vec<ChunkMeta> = Vec::new();
     let mut next_id: i64 = 1;
 
@@ -59,6 +135,8 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
         let chapter_nums = str_col(df, "chapter_num");
         let chapter_names = str_col(df, "chapter_name");
         let clean_texts = str_col(df, "clean_text");
+        let section_headings = str_col(df, "heading");
+        let statuses = str_col(df, "status");
 
         // Collect unique titles and chapters from cleaned data
         let mut titles_seen: HashMap<String, String> = HashMap::new();
@@ -88,9 +166,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 chunk_idx: 0,
                 node_type: "title".into(),
                 synthetic: true,
+                status: NodeStatus::Active,
             };
             lookup
-                .entry(("virginia_code".into(), title_num.clone()))
+                .entry(lookup_key("virginia_code", title_num))
                 .or_default()
                 .push(next_id);
             texts.insert(next_id, title_name.clone());
@@ -107,9 +186,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 chunk_idx: 0,
                 node_type: "chapter".into(),
                 synthetic: true,
+                status: NodeStatus::Active,
             };
             lookup
-                .entry(("virginia_code".into(), ch_key.clone()))
+                .entry(lookup_key("virginia_code", ch_key))
                 .or_default()
                 .push(next_id);
             texts.insert(next_id, ch_name.clone());
@@ -126,7 +206,12 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 continue;
             }
 
-            let chunks = chunk_text(clean_text, 500, 50);
+            let status = NodeStatus::parse(statuses.get(i).unwrap_or(""));
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
+            let heading = section_headings.get(i).unwrap_or("").trim();
+            if !heading.is_empty() && !chunks.is_empty() {
+                headings.insert(next_id, format!("§ {section} {heading}"));
+            }
             for (idx, chunk) in chunks.iter().enumerate() {
                 let node = Node {
                     id: next_id,
@@ -135,9 +220,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                     chunk_idx: idx as i64,
                     node_type: "section".into(),
                     synthetic: false,
+                    status,
                 };
                 lookup
-                    .entry(("virginia_code".into(), section.to_string()))
+                    .entry(lookup_key("virginia_code", section))
                     .or_default()
                     .push(next_id);
                 texts.insert(next_id, chunk.text.clone());
@@ -161,6 +247,7 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
         let article_names = str_col(df, "article_name");
         let section_counts = i64_col(df, "section_count");
         let clean_texts = str_col(df, "clean_text");
+        let section_headings = str_col(df, "heading");
 
         // Collect unique articles (synthetic)
         let mut articles_seen: HashMap<i64, String> = HashMap::new();
@@ -178,9 +265,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 chunk_idx: 0,
                 node_type: "article".into(),
                 synthetic: true,
+                status: NodeStatus::Active,
             };
             lookup
-                .entry(("constitution".into(), format!("article:{article_id}")))
+                .entry(lookup_key("constitution", &format!("article:{article_id}")))
                 .or_default()
                 .push(next_id);
             texts.insert(next_id, article_name.clone());
@@ -195,7 +283,11 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
             let clean_text = clean_texts.get(i).unwrap_or("");
 
             let source_id = format!("{article_id}:{section_count}");
-            let chunks = chunk_text(clean_text, 500, 50);
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
+            let heading = section_headings.get(i).unwrap_or("").trim();
+            if !heading.is_empty() && !chunks.is_empty() {
+                headings.insert(next_id, heading.to_string());
+            }
             for (idx, chunk) in chunks.iter().enumerate() {
                 let node = Node {
                     id: next_id,
@@ -204,9 +296,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                     chunk_idx: idx as i64,
                     node_type: "constitution_section".into(),
                     synthetic: false,
+                    status: NodeStatus::Active,
                 };
                 lookup
-                    .entry(("constitution".into(), source_id.clone()))
+                    .entry(lookup_key("constitution", &source_id))
                     .or_default()
                     .push(next_id);
                 texts.insert(next_id, chunk.text.clone());
@@ -237,7 +330,7 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 continue;
             }
 
-            let chunks = chunk_text(clean_text, 500, 50);
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
             for (idx, chunk) in chunks.iter().enumerate() {
                 let node = Node {
                     id: next_id,
@@ -246,9 +339,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                     chunk_idx: idx as i64,
                     node_type: "authority".into(),
                     synthetic: false,
+                    status: NodeStatus::Active,
                 };
                 lookup
-                    .entry(("authorities".into(), short_name.to_string()))
+                    .entry(lookup_key("authorities", short_name))
                     .or_default()
                     .push(next_id);
                 texts.insert(next_id, chunk.text.clone());
@@ -282,9 +376,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 chunk_idx: 0,
                 node_type: "court".into(),
                 synthetic: false,
+                status: NodeStatus::Active,
             };
             lookup
-                .entry(("courts".into(), court_id.to_string()))
+                .entry(lookup_key("courts", &court_id.to_string()))
                 .or_default()
                 .push(next_id);
             texts.insert(next_id, clean_text.to_string());
@@ -307,7 +402,7 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 continue;
             }
 
-            let chunks = chunk_text(clean_text, 500, 50);
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
             for (idx, chunk) in chunks.iter().enumerate() {
                 let node = Node {
                     id: next_id,
@@ -316,9 +411,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                     chunk_idx: idx as i64,
                     node_type: "popular_name".into(),
                     synthetic: false,
+                    status: NodeStatus::Active,
                 };
                 lookup
-                    .entry(("popular_names".into(), name.to_string()))
+                    .entry(lookup_key("popular_names", name))
                     .or_default()
                     .push(next_id);
                 texts.insert(next_id, chunk.text.clone());
@@ -338,6 +434,7 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
     // --- Documents ---
     {
         let df = &cleaned.documents;
+        let ids = i64_col(df, "id");
         let filenames = str_col(df, "filename");
         let clean_texts = str_col(df, "clean_text");
 
@@ -349,7 +446,16 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                 continue;
             }
 
-            let chunks = chunk_text(clean_text, 500, 50);
+            // Documents with headings split at them, so a chunk is one section
+            let chunks = match ids.get(i).and_then(|id| cleaned.document_headings.get(&id)) {
+                Some(starts) => chunk_sections(
+                    clean_text,
+                    starts,
+                    chunking.max_tokens,
+                    chunking.overlap_tokens,
+                ),
+                None => chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens),
+            };
 
             for (idx, chunk) in chunks.iter().enumerate() {
                 let node = Node {
@@ -359,9 +465,10 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
                     chunk_idx: idx as i64,
                     node_type: "manual_chunk".into(),
                     synthetic: false,
+                    status: NodeStatus::Active,
                 };
                 lookup
-                    .entry(("documents".into(), filename.to_string()))
+                    .entry(lookup_key("documents", filename))
                     .or_default()
                     .push(next_id);
                 texts.insert(next_id, chunk.text.clone());
@@ -376,10 +483,101 @@ pub fn build_nodes(cleaned: &CleanedData) -> Result<NodeBuildResult> {
         }
     }
 
+    // --- Acts ---
+    // Keyed `{year}:{chapter}`, the way session laws are cited ("2023 Acts,
+    // c. 123"). Built last so adding the table leaves earlier node ids as
+    // they were.
+    {
+        let df = &cleaned.acts;
+        let years = i64_col(df, "year");
+        let chapters = str_col(df, "chapter");
+        let clean_texts = str_col(df, "clean_text");
+
+        for i in 0..df.height() {
+            let key = act_key(years.get(i).unwrap_or(0), chapters.get(i).unwrap_or(""));
+            let clean_text = clean_texts.get(i).unwrap_or("");
+
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
+            for (idx, chunk) in chunks.iter().enumerate() {
+                let node = Node {
+                    id: next_id,
+                    source: "acts".into(),
+                    source_id: key.clone(),
+                    chunk_idx: idx as i64,
+                    node_type: "act".into(),
+                    synthetic: false,
+                    status: NodeStatus::Active,
+                };
+                lookup
+                    .entry(lookup_key("acts", &key))
+                    .or_default()
+                    .push(next_id);
+                texts.insert(next_id, chunk.text.clone());
+                if chunks.len() > 1 {
+                    chunk_meta.push(ChunkMeta {
+                        node_id: next_id,
+                        char_start: chunk.char_start,
+                        char_end: chunk.char_end,
+                    });
+                }
+                nodes.push(node);
+                next_id += 1;
+            }
+        }
+    }
+
+    // --- Federal code ---
+    // Keyed by `federal_key`; built after acts for the same reason.
+    {
+        let df = &cleaned.federal_code;
+        let codes = str_col(df, "code");
+        let titles = str_col(df, "title_num");
+        let sections = str_col(df, "section");
+        let clean_texts = str_col(df, "clean_text");
+
+        for i in 0..df.height() {
+            let key = federal_key(
+                codes.get(i).unwrap_or(""),
+                titles.get(i).unwrap_or(""),
+                sections.get(i).unwrap_or(""),
+            );
+            let clean_text = clean_texts.get(i).unwrap_or("");
+
+            let chunks = chunk_text(clean_text, chunking.max_tokens, chunking.overlap_tokens);
+            for (idx, chunk) in chunks.iter().enumerate() {
+                let node = Node {
+                    id: next_id,
+                    source: "federal_code".into(),
+                    source_id: key.clone(),
+                    chunk_idx: idx as i64,
+                    node_type: "federal_section".into(),
+                    synthetic: false,
+                    status: NodeStatus::Active,
+                };
+                lookup
+                    .entry(lookup_key("federal_code", &key))
+                    .or_default()
+                    .push(next_id);
+                texts.insert(next_id, chunk.text.clone());
+                if chunks.len() > 1 {
+                    chunk_meta.push(ChunkMeta {
+                        node_id: next_id,
+                        char_start: chunk.char_start,
+                        char_end: chunk.char_end,
+                    });
+                }
+                nodes.push(node);
+                next_id += 1;
+            }
+        }
+    }
+
     Ok(NodeBuildResult {
         nodes,
         lookup,
         texts,
+        headings,
         chunk_meta,
+        chunking,
     })
 }
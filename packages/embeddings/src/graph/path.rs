@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::graph::store::Adjacency;
+
+/// One hop of a path: the edge taken and whether it was followed forwards
+/// (`from_id` → `to_id`) or backwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hop {
+    pub edge: usize,
+    pub forward: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphPath {
+    /// Node ids from start to end; `hops.len() == nodes.len() - 1`.
+    pub nodes: Vec<i64>,
+    pub hops: Vec<Hop>,
+}
+
+/// Up to `max_paths` shortest paths from any node in `from` to any node in
+/// `to`, ignoring edge direction and with at most `max_hops` hops.
+pub fn shortest_paths(
+    adj: &Adjacency,
+    from: &[i64],
+    to: &[i64],
+    max_hops: usize,
+    max_paths: usize,
+) -> Vec<GraphPath> {
+    let targets: HashSet<i64> = to.iter().copied().collect();
+    let mut depth: HashMap<i64, usize> = HashMap::new();
+    // Every (parent, edge) that reaches a node at its shortest depth.
+    let mut parents: HashMap<i64, Vec<(i64, usize)>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for &id in from {
+        depth.insert(id, 0);
+        queue.push_back(id);
+    }
+
+    let mut found_depth = None;
+    while let Some(id) = queue.pop_front() {
+        let d = depth[&id];
+        if found_depth.is_some_and(|f| d >= f) || d >= max_hops {
+            continue;
+        }
+        for &(next, edge) in adj.neighbours(id) {
+            match depth.get(&next) {
+                None => {
+                    depth.insert(next, d + 1);
+                    parents.entry(next).or_default().push((id, edge));
+                    if targets.contains(&next) {
+                        found_depth.get_or_insert(d + 1);
+                    }
+                    queue.push_back(next);
+                }
+                Some(&nd) if nd == d + 1 => {
+                    parents.entry(next).or_default().push((id, edge));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let Some(length) = found_depth else {
+        // Already there: a node in both sets is a zero-hop path.
+        return from
+            .iter()
+            .find(|id| targets.contains(id))
+            .map(|&id| {
+                vec![GraphPath {
+                    nodes: vec![id],
+                    hops: Vec::new(),
+                }]
+            })
+            .unwrap_or_default();
+    };
+
+    let mut paths = Vec::new();
+    let mut ends: Vec<i64> = to
+        .iter()
+        .copied()
+        .filter(|id| depth.get(id) == Some(&length))
+        .collect();
+    ends.sort();
+    for end in ends {
+        let mut partial = vec![(end, Hop { edge: 0, forward: true })];
+        walk_back(adj, &parents, &mut partial, max_paths, &mut paths);
+        if paths.len() >= max_paths {
+            break;
+        }
+    }
+    paths
+}
+
+/// Depth-first enumeration of parent chains back to a start node.
+fn walk_back(
+    adj: &Adjacency,
+    parents: &HashMap<i64, Vec<(i64, usize)>>,
+    partial: &mut Vec<(i64, Hop)>,
+    max_paths: usize,
+    out: &mut Vec<GraphPath>,
+) {
+    if out.len() >= max_paths {
+        return;
+    }
+    let (node, _) = *partial.last().unwrap();
+    match parents.get(&node) {
+        None => {
+            // Reached a start node: `partial` is end → start.
+            let nodes = partial.iter().rev().map(|(n, _)| *n).collect();
+            let hops = partial[..partial.len() - 1]
+                .iter()
+                .rev()
+                .map(|(_, hop)| *hop)
+                .collect();
+            out.push(GraphPath { nodes, hops });
+        }
+        Some(options) => {
+            for &(parent, edge) in options {
+                let forward = adj.edges[edge].from_id == parent;
+                partial.last_mut().unwrap().1 = Hop { edge, forward };
+                partial.push((parent, Hop { edge: 0, forward: true }));
+                walk_back(adj, parents, partial, max_paths, out);
+                partial.pop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    /// 1 -cites-> 2 -cites-> 4, 1 -cites-> 3 -cites-> 4, chapter 5 contains 1.
+    fn adjacency() -> Adjacency {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE edges (from_id INTEGER, to_id INTEGER, rel_type TEXT, weight REAL);
+            INSERT INTO edges VALUES
+                (1, 2, 'cites', NULL), (2, 4, 'cites', NULL),
+                (1, 3, 'cites', NULL), (3, 4, 'cites', NULL),
+                (5, 1, 'contains', NULL);
+            ",
+        )
+        .unwrap();
+        Adjacency::load(&conn, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_finds_all_shortest_paths() {
+        let adj = adjacency();
+        let paths = shortest_paths(&adj, &[1], &[4], 10, 10);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.nodes.len() == 3 && p.hops.len() == 2));
+        assert!(paths.iter().all(|p| p.hops.iter().all(|h| h.forward)));
+    }
+
+    #[test]
+    fn test_reverse_hops_and_limits() {
+        let adj = adjacency();
+        let paths = shortest_paths(&adj, &[4], &[5], 10, 1);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].nodes.first(), Some(&4));
+        assert_eq!(paths[0].nodes.last(), Some(&5));
+        assert!(paths[0].hops.iter().all(|h| !h.forward));
+        assert!(shortest_paths(&adj, &[4], &[5], 2, 10).is_empty());
+    }
+}
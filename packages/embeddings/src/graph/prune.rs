@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+use crate::graph::edges::Edge;
+
+/// How to trim the chunk-level graph into a smaller presentation graph.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PruneOptions {
+    /// Edge types to keep; empty keeps all.
+    pub rel_types: Vec<String>,
+    /// Drop `cites` edges whose weight (mention count) is below this.
+    pub min_cites_weight: f64,
+    /// Re-point edges at the first chunk of their section and drop the
+    /// resulting self-loops.
+    pub collapse_chunks: bool,
+    /// Merge edges with the same endpoints and type, summing their weights.
+    pub merge_parallel: bool,
+}
+
+impl Default for PruneOptions {
+    fn default() -> Self {
+        Self {
+            rel_types: Vec::new(),
+            min_cites_weight: 0.0,
+            collapse_chunks: true,
+            merge_parallel: true,
+        }
+    }
+}
+
+/// Map every node id to the id of chunk 0 of its `(source, source_id)`.
+/// Input items are `(id, source, source_id, chunk_idx)`.
+pub fn section_representatives<'a>(
+    nodes: impl IntoIterator<Item = (i64, &'a str, &'a str, i64)>,
+) -> HashMap<i64, i64> {
+    let nodes: Vec<_> = nodes.into_iter().collect();
+    let mut first: HashMap<(&str, &str), (i64, i64)> = HashMap::new();
+    for &(id, source, source_id, chunk_idx) in &nodes {
+        let entry = first.entry((source, source_id)).or_insert((chunk_idx, id));
+        if chunk_idx < entry.0 {
+            *entry = (chunk_idx, id);
+        }
+    }
+    nodes
+        .iter()
+        .map(|&(id, source, source_id, _)| (id, first[&(source, source_id)].1))
+        .collect()
+}
+
+/// Apply `opts` to `edges`. `representatives` is only consulted when
+/// collapsing chunks (see [`section_representatives`]).
+pub fn prune_edges(
+    edges: &[Edge],
+    representatives: &HashMap<i64, i64>,
+    opts: &PruneOptions,
+) -> Vec<Edge> {
+    let kept = edges.iter().filter(|e| {
+        (opts.rel_types.is_empty() || opts.rel_types.contains(&e.rel_type))
+            && (e.rel_type != "cites" || e.weight.unwrap_or(1.0) >= opts.min_cites_weight)
+    });
+
+    let mapped = kept.filter_map(|e| {
+        let mut e = e.clone();
+        if opts.collapse_chunks {
+            e.from_id = *representatives.get(&e.from_id).unwrap_or(&e.from_id);
+            e.to_id = *representatives.get(&e.to_id).unwrap_or(&e.to_id);
+            if e.from_id == e.to_id {
+                return None;
+            }
+        }
+        Some(e)
+    });
+
+    if !opts.merge_parallel {
+        return mapped.collect();
+    }
+
+    // NULL weights count as 1 when merged, so merged counts stay meaningful.
+    let mut merged: BTreeMap<(i64, i64, String), Option<f64>> = BTreeMap::new();
+    for e in mapped {
+        merged
+            .entry((e.from_id, e.to_id, e.rel_type))
+            .and_modify(|w| *w = Some(w.unwrap_or(1.0) + e.weight.unwrap_or(1.0)))
+            .or_insert(e.weight);
+    }
+    merged
+        .into_iter()
+        .map(|((from_id, to_id, rel_type), weight)| Edge {
+            from_id,
+            to_id,
+            rel_type,
+            weight,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from_id: i64, to_id: i64, rel_type: &str, weight: Option<f64>) -> Edge {
+        Edge {
+            from_id,
+            to_id,
+            rel_type: rel_type.into(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_collapse_and_merge() {
+        // Section A has chunks 1 and 2; section B has chunks 3 and 4.
+        let reps = section_representatives([
+            (1, "virginia_code", "A", 0),
+            (2, "virginia_code", "A", 1),
+            (3, "virginia_code", "B", 0),
+            (4, "virginia_code", "B", 1),
+        ]);
+        let edges = vec![
+            edge(1, 3, "cites", Some(1.0)),
+            edge(2, 4, "cites", Some(2.0)),
+            edge(1, 2, "cites", Some(1.0)),
+        ];
+
+        let pruned = prune_edges(&edges, &reps, &PruneOptions::default());
+        assert_eq!(pruned.len(), 1);
+        assert_eq!((pruned[0].from_id, pruned[0].to_id), (1, 3));
+        assert_eq!(pruned[0].weight, Some(3.0));
+    }
+
+    #[test]
+    fn test_weight_and_type_filters() {
+        let edges = vec![
+            edge(1, 2, "cites", Some(1.0)),
+            edge(1, 3, "cites", Some(4.0)),
+            edge(5, 1, "contains", None),
+        ];
+        let opts = PruneOptions {
+            min_cites_weight: 2.0,
+            collapse_chunks: false,
+            merge_parallel: false,
+            ..Default::default()
+        };
+        let pruned = prune_edges(&edges, &HashMap::new(), &opts);
+        assert_eq!(pruned.len(), 2);
+
+        let opts = PruneOptions {
+            rel_types: vec!["contains".into()],
+            ..opts
+        };
+        let pruned = prune_edges(&edges, &HashMap::new(), &opts);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].rel_type, "contains");
+    }
+}
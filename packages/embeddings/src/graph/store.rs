@@ -0,0 +1,306 @@
+//! Read side of the output graph: node resolution, adjacency, and node texts
+//! for the research subcommands (`path`, `subgraph`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::extra_documents::read_extra_documents;
+use crate::db::reader::{read_input, ConstitutionRow, DocumentRow, SourceMapping, VirginiaCodeRow};
+use crate::etl::{CleanedData, Etl, EtlOptions};
+use crate::graph::nodes::{build_nodes, SOURCES};
+use crate::text::chunker::ChunkConfig;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode {
+    pub id: i64,
+    pub source: String,
+    pub source_id: String,
+    pub chunk_idx: i64,
+    pub node_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdge {
+    pub from_id: i64,
+    pub to_id: i64,
+    pub rel_type: String,
+    pub weight: Option<f64>,
+}
+
+/// Split a user-supplied node spec into `(source, source_id)`.
+///
+/// A bare id (optionally written with a leading `§`) is a Code section:
+/// `18.2-31`, `§ 8.01-243`. Other sources are prefixed: `documents:manual.pdf`.
+pub fn parse_node_spec(spec: &str) -> (String, String) {
+    let spec = spec.trim();
+    if let Some((source, id)) = spec.split_once(':') {
+        if SOURCES.contains(&source) {
+            return (source.to_string(), id.trim().to_string());
+        }
+    }
+    let id = spec.trim_start_matches('§').trim();
+    ("virginia_code".to_string(), id.to_string())
+}
+
+/// All nodes (every chunk) for a node spec. Fails if nothing matches.
+pub fn resolve(conn: &Connection, spec: &str) -> Result<Vec<GraphNode>> {
+    let (source, source_id) = parse_node_spec(spec);
+    let mut stmt = conn.prepare(
+        "SELECT id, source, source_id, chunk_idx, node_type FROM nodes
+         WHERE source = ?1 AND source_id = ?2 ORDER BY chunk_idx",
+    )?;
+    let nodes = stmt
+        .query_map([&source, &source_id], node_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if nodes.is_empty() {
+        anyhow::bail!("No node found for {} {}", source, source_id);
+    }
+    Ok(nodes)
+}
+
+pub fn load_nodes(conn: &Connection) -> Result<HashMap<i64, GraphNode>> {
+    let mut stmt = conn.prepare("SELECT id, source, source_id, chunk_idx, node_type FROM nodes")?;
+    let nodes = stmt
+        .query_map([], node_from_row)?
+        .map(|r| r.map(|n| (n.id, n)))
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(nodes)
+}
+
+fn node_from_row(r: &rusqlite::Row) -> rusqlite::Result<GraphNode> {
+    Ok(GraphNode {
+        id: r.get(0)?,
+        source: r.get(1)?,
+        source_id: r.get(2)?,
+        chunk_idx: r.get(3)?,
+        node_type: r.get(4)?,
+    })
+}
+
+/// Undirected view of the edges of the given types (all types when empty).
+/// Each entry keeps the original edge so callers can print its direction.
+pub struct Adjacency {
+    neighbours: HashMap<i64, Vec<(i64, usize)>>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl Adjacency {
+    pub fn load(conn: &Connection, rel_types: &[String]) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT from_id, to_id, rel_type, weight FROM edges")?;
+        let edges: Vec<GraphEdge> = stmt
+            .query_map([], |r| {
+                Ok(GraphEdge {
+                    from_id: r.get(0)?,
+                    to_id: r.get(1)?,
+                    rel_type: r.get(2)?,
+                    weight: r.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|e| rel_types.is_empty() || rel_types.contains(&e.rel_type))
+            .collect();
+
+        let mut neighbours: HashMap<i64, Vec<(i64, usize)>> = HashMap::new();
+        for (i, e) in edges.iter().enumerate() {
+            neighbours.entry(e.from_id).or_default().push((e.to_id, i));
+            neighbours.entry(e.to_id).or_default().push((e.from_id, i));
+        }
+        Ok(Self { neighbours, edges })
+    }
+
+    /// `(neighbour, edge index)` pairs in either direction.
+    pub fn neighbours(&self, id: i64) -> &[(i64, usize)] {
+        self.neighbours.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// A node's `(source, source_id, chunk_idx)`.
+pub type TextKey = (String, String, i64);
+
+/// Node texts keyed by `(source, source_id, chunk_idx)`.
+///
+/// The output graph doesn't store text, so this re-runs ETL and node building
+/// over the input DB. Keys (not node ids) are matched, so it works against any
+/// graph built from the same input with the same `chunking`. The input is read
+/// with Virginia's schema.
+pub fn rebuild_texts(input: &Path, chunking: ChunkConfig) -> Result<HashMap<TextKey, String>> {
+    rebuild(input, chunking, None)
+}
+
+/// Like [`rebuild_texts`], for `keys` only: sources without a key aren't read
+/// and code sections without one aren't cleaned or chunked, so a handful of
+/// search hits doesn't cost a rebuild of the whole corpus.
+pub fn rebuild_texts_for(
+    input: &Path,
+    chunking: ChunkConfig,
+    keys: &HashSet<TextKey>,
+) -> Result<HashMap<TextKey, String>> {
+    let mut texts = rebuild(input, chunking, Some(keys))?;
+    texts.retain(|key, _| keys.contains(key));
+    Ok(texts)
+}
+
+fn rebuild(
+    input: &Path,
+    chunking: ChunkConfig,
+    keys: Option<&HashSet<TextKey>>,
+) -> Result<HashMap<TextKey, String>> {
+    let wanted = |source: &str| keys.is_none_or(|keys| keys.iter().any(|k| k.0 == source));
+    let sources = &SourceMapping::new();
+    let mut etl = Etl::new(EtlOptions::default());
+    if wanted("virginia_code") {
+        let mut rows: Vec<VirginiaCodeRow> = read_input(input, sources)?;
+        if let Some(keys) = keys {
+            let sections: HashSet<&str> = keys
+                .iter()
+                .filter(|k| k.0 == "virginia_code")
+                .map(|k| k.1.as_str())
+                .collect();
+            rows.retain(|r| sections.contains(r.section.as_str()));
+        }
+        etl.virginia_code(&rows)?;
+    }
+    if wanted("constitution") {
+        etl.constitution(&read_input(input, sources)?)?;
+    }
+    if wanted("authorities") {
+        etl.authorities(&read_input(input, sources)?)?;
+    }
+    if wanted("courts") {
+        etl.courts(&read_input(input, sources)?)?;
+    }
+    if wanted("popular_names") {
+        etl.popular_names(&read_input(input, sources)?)?;
+    }
+    if wanted("acts") {
+        etl.acts(&read_input(input, sources)?)?;
+    }
+    if wanted("federal_code") {
+        etl.federal_code(&read_input(input, sources)?)?;
+    }
+    if wanted("documents") {
+        etl.documents(&read_input(input, sources)?)?;
+    }
+    Ok(node_texts(&etl.finish()?, chunking)?
+        .into_iter()
+        .filter_map(|(key, mut texts)| Some((key, texts.pop()?)))
+        .collect())
+}
+
+/// Like [`rebuild_texts`], but keeps every text under a key. Keys aren't
+/// unique (constitution sections are keyed by article and section count), so
+/// callers with a `node_hashes` row can pick the text that matches it.
+/// `sources` maps the input's tables, `options` cleans code sections, and
+/// `extra_documents` adds documents, as they did for the build.
+pub fn rebuild_text_candidates(
+    input: &Path,
+    chunking: ChunkConfig,
+    sources: &SourceMapping,
+    options: &EtlOptions,
+    extra_documents: Option<&Path>,
+) -> Result<HashMap<TextKey, Vec<String>>> {
+    let code_rows: Vec<VirginiaCodeRow> = read_input(input, sources)?;
+    let constitution_rows: Vec<ConstitutionRow> = read_input(input, sources)?;
+    let mut document_rows: Vec<DocumentRow> = read_input(input, sources)?;
+    if let Some(path) = extra_documents {
+        document_rows.extend(read_extra_documents(path)?);
+    }
+    let mut etl = Etl::new(options.clone());
+    etl.virginia_code(&code_rows)?;
+    etl.constitution(&constitution_rows)?;
+    etl.authorities(&read_input(input, sources)?)?;
+    etl.courts(&read_input(input, sources)?)?;
+    etl.popular_names(&read_input(input, sources)?)?;
+    etl.acts(&read_input(input, sources)?)?;
+    etl.federal_code(&read_input(input, sources)?)?;
+    etl.documents(&document_rows)?;
+    node_texts(&etl.finish()?, chunking)
+}
+
+/// Every text of the nodes built from `cleaned`, by key.
+fn node_texts(
+    cleaned: &CleanedData,
+    chunking: ChunkConfig,
+) -> Result<HashMap<TextKey, Vec<String>>> {
+    let mut built = build_nodes(cleaned, chunking)?;
+    let mut texts: HashMap<TextKey, Vec<String>> = HashMap::new();
+    for n in built.nodes {
+        if let Some(text) = built.texts.remove(&n.id) {
+            texts
+                .entry((n.source, n.source_id, n.chunk_idx))
+                .or_default()
+                .push(text);
+        }
+    }
+    Ok(texts)
+}
+
+/// First `max_chars` characters of `text`, on a char boundary, with an ellipsis.
+pub fn summarize(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_node_spec() {
+        assert_eq!(
+            parse_node_spec("§ 8.01-243"),
+            ("virginia_code".into(), "8.01-243".into())
+        );
+        assert_eq!(
+            parse_node_spec("18.2-31"),
+            ("virginia_code".into(), "18.2-31".into())
+        );
+        assert_eq!(
+            parse_node_spec("documents:manual.pdf"),
+            ("documents".into(), "manual.pdf".into())
+        );
+        // A chapter key is not a source prefix.
+        assert_eq!(
+            parse_node_spec("46.2:8"),
+            ("virginia_code".into(), "46.2:8".into())
+        );
+    }
+
+    #[test]
+    fn test_rebuild_texts_for_matches_full_rebuild() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("virginia.db");
+        Connection::open(&input)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE virginia_code (
+                     id INTEGER, title_num TEXT, title_name TEXT, chapter_num TEXT,
+                     chapter_name TEXT, section TEXT, title TEXT, body TEXT
+                 );
+                 INSERT INTO virginia_code VALUES
+                     (1, '1', 'General', '1', 'Ch', '1-1', 'One', 'The first section, kept.'),
+                     (2, '1', 'General', '1', 'Ch', '1-2', 'Two', 'The second section, kept.');",
+            )
+            .unwrap();
+        let chunking = ChunkConfig::default();
+        let all = rebuild_texts(&input, chunking).unwrap();
+        let key: TextKey = ("virginia_code".into(), "1-2".into(), 0);
+        let keys = HashSet::from([key.clone(), ("courts".into(), "va-sc".into(), 0)]);
+        let some = rebuild_texts_for(&input, chunking, &keys).unwrap();
+        assert_eq!(some.len(), 1);
+        assert_eq!(some[&key], all[&key]);
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize("short", 10), "short");
+        assert_eq!(summarize("§ one two three", 7), "§ one t…");
+    }
+}
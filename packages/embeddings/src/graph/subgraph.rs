@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+
+use crate::graph::store::Adjacency;
+
+/// Nodes within `hops` undirected hops of `center`, and the indices of every
+/// loaded edge with both endpoints in that set (the induced subgraph).
+pub fn neighbourhood(adj: &Adjacency, center: &[i64], hops: usize) -> (BTreeSet<i64>, Vec<usize>) {
+    let mut nodes: BTreeSet<i64> = center.iter().copied().collect();
+    let mut frontier: Vec<i64> = center.to_vec();
+    for _ in 0..hops {
+        let mut next = Vec::new();
+        for id in frontier {
+            for &(n, _) in adj.neighbours(id) {
+                if nodes.insert(n) {
+                    next.push(n);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+
+    let edges = adj
+        .edges
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| nodes.contains(&e.from_id) && nodes.contains(&e.to_id))
+        .map(|(i, _)| i)
+        .collect();
+    (nodes, edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_neighbourhood_is_induced() {
+        // Chain 1-2-3-4 plus a chord 1-3.
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE edges (from_id INTEGER, to_id INTEGER, rel_type TEXT, weight REAL);
+            INSERT INTO edges VALUES
+                (1, 2, 'cites', NULL), (2, 3, 'cites', NULL),
+                (3, 4, 'cites', NULL), (1, 3, 'contains', NULL);
+            ",
+        )
+        .unwrap();
+        let adj = Adjacency::load(&conn, &[]).unwrap();
+
+        let (nodes, edges) = neighbourhood(&adj, &[2], 1);
+        assert_eq!(nodes.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        // 1-2, 2-3 and the chord 1-3; 3-4 leaves the set.
+        assert_eq!(edges.len(), 3);
+
+        let (nodes, _) = neighbourhood(&adj, &[2], 0);
+        assert_eq!(nodes.len(), 1);
+    }
+}
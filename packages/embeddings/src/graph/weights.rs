@@ -0,0 +1,302 @@
+//! `[weights]` config: a formula per edge type for the `weight` column, e.g.
+//! `cites = "log(1 + count)"` or `contains = "1"`. Formulas are applied once,
+//! when edges are written, so `edges`, `adjacency`, `presentation_edges` and
+//! `section_edges` all carry the same numbers. Edge types without a formula
+//! keep the built-in weight (mention count, NULL for `contains`).
+//!
+//! A formula is arithmetic (`+ - * / ^`, parentheses) over numbers and
+//! `count`, the edge's mention count (1 for structural edges), with the
+//! functions `log` (natural), `log2`, `log10`, `sqrt`, `exp`, `abs`, `min`
+//! and `max`.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::graph::edges::Edge;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Formula {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(f64),
+    Count,
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+    Call(&'static str, Vec<Expr>),
+}
+
+/// `(name, arity)` of the functions a formula may call.
+const FUNCTIONS: [(&str, usize); 8] = [
+    ("log", 1),
+    ("log2", 1),
+    ("log10", 1),
+    ("sqrt", 1),
+    ("exp", 1),
+    ("abs", 1),
+    ("min", 2),
+    ("max", 2),
+];
+
+impl TryFrom<String> for Formula {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_ws();
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected {c:?} in weight formula {source:?}"));
+        }
+        Ok(Self { source, expr })
+    }
+}
+
+impl Formula {
+    pub fn eval(&self, count: f64) -> f64 {
+        eval(&self.expr, count)
+    }
+}
+
+fn eval(expr: &Expr, count: f64) -> f64 {
+    match expr {
+        Expr::Num(n) => *n,
+        Expr::Count => count,
+        Expr::Neg(e) => -eval(e, count),
+        Expr::Bin(op, a, b) => {
+            let (a, b) = (eval(a, count), eval(b, count));
+            match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' => a / b,
+                _ => a.powf(b),
+            }
+        }
+        Expr::Call(name, args) => {
+            let x = eval(&args[0], count);
+            match *name {
+                "log" => x.ln(),
+                "log2" => x.log2(),
+                "log10" => x.log10(),
+                "sqrt" => x.sqrt(),
+                "exp" => x.exp(),
+                "abs" => x.abs(),
+                "min" => x.min(eval(&args[1], count)),
+                _ => x.max(eval(&args[1], count)),
+            }
+        }
+    }
+}
+
+/// Copy of `edges` with the configured formula applied to each edge's weight.
+/// Fails if a formula gives a non-finite number (e.g. `log(count - 1)` for a
+/// single mention), rather than writing NaN into the graph.
+pub fn apply(edges: &[Edge], formulas: &BTreeMap<String, Formula>) -> Result<Vec<Edge>> {
+    edges
+        .iter()
+        .map(|edge| {
+            let Some(formula) = formulas.get(&edge.rel_type) else {
+                return Ok(edge.clone());
+            };
+            let count = edge.weight.unwrap_or(1.0);
+            let weight = formula.eval(count);
+            if !weight.is_finite() {
+                bail!(
+                    "Weight formula for {} ({:?}) gives {} for count = {}",
+                    edge.rel_type,
+                    formula.source,
+                    weight,
+                    count
+                );
+            }
+            Ok(Edge {
+                weight: Some(weight),
+                ..edge.clone()
+            })
+        })
+        .collect()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `c` if it is the next non-space character.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match () {
+                _ if self.eat('+') => '+',
+                _ if self.eat('-') => '-',
+                _ => return Ok(lhs),
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.factor()?;
+        loop {
+            let op = match () {
+                _ if self.eat('*') => '*',
+                _ if self.eat('/') => '/',
+                _ => return Ok(lhs),
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.factor()?));
+        }
+    }
+
+    /// `^` binds tighter than unary minus on its left and is right-associative.
+    fn factor(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+        let base = self.primary()?;
+        if self.eat('^') {
+            return Ok(Expr::Bin('^', Box::new(base), Box::new(self.factor()?)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_ws();
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing ')' in weight formula".into());
+                }
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                text.parse()
+                    .map(Expr::Num)
+                    .map_err(|_| format!("bad number {text:?} in weight formula"))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                if name == "count" {
+                    return Ok(Expr::Count);
+                }
+                let Some(&(name, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    return Err(format!(
+                        "unknown name {name:?} in weight formula (use count or a function)"
+                    ));
+                };
+                if !self.eat('(') {
+                    return Err(format!("expected '(' after {name}"));
+                }
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                if !self.eat(')') {
+                    return Err(format!("missing ')' after {name}(...)"));
+                }
+                if args.len() != arity {
+                    return Err(format!(
+                        "{name} takes {arity} argument(s), got {}",
+                        args.len()
+                    ));
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(format!("unexpected {c:?} in weight formula")),
+            None => Err("weight formula ended early".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formula(s: &str) -> Result<Formula, String> {
+        Formula::try_from(s.to_string())
+    }
+
+    #[test]
+    fn test_formula_eval() {
+        let eval = |s: &str, count| formula(s).unwrap().eval(count);
+        assert_eq!(eval("log(1 + count)", std::f64::consts::E - 1.0), 1.0);
+        assert_eq!(eval("1", 7.0), 1.0);
+        assert_eq!(eval("2 * count ^ 2 - 1", 3.0), 17.0);
+        assert_eq!(eval("-2 ^ 2", 0.0), -4.0);
+        assert_eq!(eval("min(count, 5) / (1 + 1)", 9.0), 2.5);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.0), 512.0);
+
+        assert!(formula("log(count").is_err());
+        assert!(formula("cosine").is_err());
+        assert!(formula("max(count)").is_err());
+        assert!(formula("count count").is_err());
+    }
+
+    #[test]
+    fn test_apply_by_rel_type() {
+        let edge = |rel_type: &str, weight| Edge {
+            from_id: 1,
+            to_id: 2,
+            rel_type: rel_type.into(),
+            weight,
+        };
+        let formulas: BTreeMap<String, Formula> = [
+            ("cites".to_string(), formula("count * 10").unwrap()),
+            ("contains".to_string(), formula("0.5").unwrap()),
+        ]
+        .into();
+        let edges = [
+            edge("cites", Some(2.0)),
+            edge("contains", None),
+            edge("references", Some(3.0)),
+        ];
+        let weights: Vec<Option<f64>> = apply(&edges, &formulas)
+            .unwrap()
+            .iter()
+            .map(|e| e.weight)
+            .collect();
+        assert_eq!(weights, [Some(20.0), Some(0.5), Some(3.0)]);
+
+        let formulas = [("cites".to_string(), formula("log(count - 2)").unwrap())].into();
+        assert!(apply(&edges, &formulas).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Int8 vectors for the in-memory search index (`--precision int8`): a
+//! quarter of the memory of f32 rows, scored with integer dot products.
+//!
+//! Each unit-normalized vector is quantized symmetrically on its own: its
+//! largest component maps to ±127 and the rest scale with it, so a short
+//! vector keeps its full int8 range. The quantization scale cancels out of
+//! cosine similarity, so a row keeps only its codes and the norm of its codes,
+//! computed once at load; a score is `dot(q, r) / (|q| |r|)`.
+//!
+//! [`dot`] uses AVX2 when the CPU has it, detected at runtime. Elsewhere the
+//! scalar loop, which the compiler vectorizes on its own (NEON on aarch64).
+
+/// One quantized vector: its codes and their Euclidean norm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantized {
+    pub codes: Vec<i8>,
+    pub norm: f32,
+}
+
+impl Quantized {
+    pub fn new(v: &[f32]) -> Self {
+        let max = v.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let scale = if max > 0.0 { 127.0 / max } else { 0.0 };
+        let codes: Vec<i8> = v
+            .iter()
+            .map(|x| (x * scale).round().clamp(-127.0, 127.0) as i8)
+            .collect();
+        let norm = (dot(&codes, &codes) as f32).sqrt();
+        Self { codes, norm }
+    }
+}
+
+/// Row-major int8 vectors of `dims` components each.
+#[derive(Debug, Clone, Default)]
+pub struct Int8Vectors {
+    dims: usize,
+    codes: Vec<i8>,
+    norms: Vec<f32>,
+}
+
+impl Int8Vectors {
+    pub fn new(dims: usize) -> Self {
+        Self {
+            dims,
+            ..Self::default()
+        }
+    }
+
+    /// Quantize `v`, which must have `dims` components, onto the end.
+    pub fn push(&mut self, v: &[f32]) {
+        debug_assert_eq!(v.len(), self.dims);
+        let quantized = Quantized::new(v);
+        self.codes.extend(quantized.codes);
+        self.norms.push(quantized.norm);
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    pub fn len(&self) -> usize {
+        self.norms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.norms.is_empty()
+    }
+
+    /// Approximate cosine similarity of row `row` and `query`; 0 when either
+    /// is the zero vector.
+    pub fn cosine(&self, row: usize, query: &Quantized) -> f32 {
+        let norm = self.norms[row] * query.norm;
+        if norm == 0.0 {
+            return 0.0;
+        }
+        let codes = &self.codes[row * self.dims..(row + 1) * self.dims];
+        dot(codes, &query.codes) as f32 / norm
+    }
+}
+
+/// Dot product of two int8 slices, over the shorter one's length.
+pub fn dot(a: &[i8], b: &[i8]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2, checked just above.
+        return unsafe { dot_avx2(a, b) };
+    }
+    dot_scalar(a, b)
+}
+
+fn dot_scalar(a: &[i8], b: &[i8]) -> i32 {
+    a.iter().zip(b).map(|(&x, &y)| x as i32 * y as i32).sum()
+}
+
+/// 16 components per step: sign-extend to i16, then `madd` multiplies pairs
+/// and sums adjacent products into i32 lanes, which can't overflow for int8
+/// inputs.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn dot_avx2(a: &[i8], b: &[i8]) -> i32 {
+    use std::arch::x86_64::*;
+
+    let n = a.len().min(b.len());
+    let mut acc = _mm256_setzero_si256();
+    let mut i = 0;
+    while i + 16 <= n {
+        let va = _mm256_cvtepi8_epi16(_mm_loadu_si128(a.as_ptr().add(i) as *const __m128i));
+        let vb = _mm256_cvtepi8_epi16(_mm_loadu_si128(b.as_ptr().add(i) as *const __m128i));
+        acc = _mm256_add_epi32(acc, _mm256_madd_epi16(va, vb));
+        i += 16;
+    }
+    let mut lanes = [0i32; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, acc);
+    lanes.iter().sum::<i32>() + dot_scalar(&a[i..n], &b[i..n])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_matches_scalar() {
+        // 37 components: two SIMD steps and a scalar tail.
+        let a: Vec<i8> = (0..37).map(|i| ((i * 53) % 255 - 127) as i8).collect();
+        let b: Vec<i8> = (0..37).map(|i| ((i * 91 + 7) % 255 - 127) as i8).collect();
+        assert_eq!(dot(&a, &b), dot_scalar(&a, &b));
+        let extremes = vec![-127i8; 64];
+        assert_eq!(dot(&extremes, &extremes), 64 * 127 * 127);
+    }
+
+    #[test]
+    fn test_cosine_approximates_f32() {
+        let unit = |v: &[f32]| {
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            v.iter().map(|x| x / norm).collect::<Vec<f32>>()
+        };
+        let rows: Vec<Vec<f32>> = (0..20)
+            .map(|r| {
+                unit(
+                    &(0..64)
+                        .map(|i| ((r * 64 + i) as f32 * 0.37).sin())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let mut vectors = Int8Vectors::new(64);
+        rows.iter().for_each(|r| vectors.push(r));
+        assert_eq!(vectors.len(), 20);
+
+        let query = unit(&(0..64).map(|i| (i as f32 * 0.11).cos()).collect::<Vec<_>>());
+        let quantized = Quantized::new(&query);
+        for (i, row) in rows.iter().enumerate() {
+            let exact: f32 = row.iter().zip(&query).map(|(a, b)| a * b).sum();
+            assert!((vectors.cosine(i, &quantized) - exact).abs() < 0.01);
+        }
+        assert_eq!(vectors.cosine(0, &Quantized::new(&[0.0; 64])), 0.0);
+    }
+}
@@ -0,0 +1,70 @@
+//! Ctrl-C during Pass 3. Killing the process mid-batch can leave a torn line
+//! at the end of the JSONL file, so while an [`Interrupt`] is held the first
+//! SIGINT only asks the embedding loop to stop after the in-flight batch; the
+//! pass then records its work list and exits. A second Ctrl-C, or one outside
+//! Pass 3, exits at once, as SIGINT would have.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tracing::warn;
+
+/// Exit status of a process killed by SIGINT (128 + 2).
+const SIGINT_EXIT: i32 = 130;
+
+pub struct Interrupt {
+    requested: Arc<AtomicBool>,
+    armed: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    /// Start catching Ctrl-C. Tokio can't hand SIGINT back to the default
+    /// handler, so the listener stays for the life of the process and exits
+    /// on Ctrl-C whenever no `Interrupt` is armed.
+    pub fn arm() -> Self {
+        let requested = Arc::new(AtomicBool::new(false));
+        let armed = Arc::new(AtomicBool::new(true));
+        let (r, a) = (requested.clone(), armed.clone());
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if !a.load(Ordering::SeqCst) || r.swap(true, Ordering::SeqCst) {
+                    eprintln!("Interrupted");
+                    std::process::exit(SIGINT_EXIT);
+                }
+                warn!("Interrupt: finishing the in-flight batch; press Ctrl-C again to abort");
+            }
+        });
+        Self { requested, armed }
+    }
+
+    /// Flag for [`crate::embed::Embedder::with_stop`].
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+}
+
+/// What Pass 3 fails with after stopping on Ctrl-C, so `--watch` can tell it
+/// from a failed rebuild.
+#[derive(Debug)]
+pub struct Interrupted {
+    pub embedded: usize,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Interrupted after {} embeddings; the rest stay pending, finish with --resume",
+            self.embedded
+        )
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        self.armed.store(false, Ordering::SeqCst);
+    }
+}
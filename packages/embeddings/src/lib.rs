@@ -0,0 +1,77 @@
+//! The Virginia legal knowledge-graph pipeline as a library: read the source
+//! tables from `virginia.db`, clean them, build graph nodes and citation
+//! edges, embed node texts, and write the output DB that `proseva-embeddings`,
+//! `graph-query` and `embedding-server` read.
+//!
+//! The `proseva-embeddings` binary is a CLI over these stages; a service that
+//! wants the same graph can call them directly:
+//!
+//! ```no_run
+//! use proseva_embeddings::db::{reader, writer};
+//! use proseva_embeddings::embed::Device;
+//! use proseva_embeddings::text::chunker::ChunkConfig;
+//! use proseva_embeddings::{build_edges, build_nodes, run_etl, Embedder};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let input = rusqlite::Connection::open("virginia.db")?;
+//! let code = reader::read_virginia_code(&input)?;
+//! let constitution = reader::read_constitution(&input)?;
+//! let documents = reader::read_documents(&input)?;
+//! let cleaned = run_etl(
+//!     &code,
+//!     &constitution,
+//!     &reader::read_authorities(&input)?,
+//!     &reader::read_courts(&input)?,
+//!     &reader::read_popular_names(&input)?,
+//!     &reader::read_acts(&input)?,
+//!     &reader::read_federal_code(&input)?,
+//!     &documents,
+//! )?;
+//!
+//! let model = proseva_embeddings::embed::models::find("embeddinggemma-300m")?;
+//! let chunking = ChunkConfig::new(ChunkConfig::budget(model.seq_len(None)?), 50)?;
+//! let nodes = build_nodes(&cleaned, chunking)?;
+//! let edges = build_edges(
+//!     &nodes.nodes,
+//!     &nodes.lookup,
+//!     &code,
+//!     &constitution,
+//!     &documents,
+//!     &nodes.texts,
+//! );
+//!
+//! let out = writer::create_output_db("graph.sqlite.db")?;
+//! writer::write_nodes(&out, &nodes.nodes)?;
+//! writer::write_edges(&out, &edges.edges)?;
+//!
+//! let mut embedder = Embedder::new(model, Device::Auto, 64, None).await?;
+//! let (ids, texts): (Vec<i64>, Vec<String>) = nodes.texts.into_iter().unzip();
+//! embedder
+//!     .embed_batched(&ids, &texts, |ids, vectors, _backend| {
+//!         writer::write_embeddings_batch(&out, ids, vectors)
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod access_log;
+pub mod binary;
+pub mod bloom;
+pub mod csr;
+pub mod db;
+pub mod embed;
+pub mod etl;
+pub mod graph;
+pub mod int8;
+pub mod logging;
+pub mod query;
+pub mod query_log;
+pub mod rate_limit;
+pub mod search;
+pub mod text;
+
+pub use embed::Embedder;
+pub use etl::run_etl;
+pub use graph::edges::build_edges;
+pub use graph::nodes::build_nodes;
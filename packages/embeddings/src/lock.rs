@@ -0,0 +1,106 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+/// Advisory lock on `<output>.lock`, held for the lifetime of a run so two
+/// pipelines launched against the same output (cron overlap, double-clicked
+/// scripts) can't interleave writes. The OS releases the lock if the process
+/// dies, so a stale lock file never blocks the next run.
+pub struct BuildLock {
+    _file: File,
+}
+
+/// Acquire the lock for `output`.
+///
+/// If another run holds it: with `wait`, block until it is released; with
+/// `force`, warn and proceed unlocked; otherwise fail with the holder's pid.
+pub fn acquire(output: &Path, wait: bool, force: bool) -> Result<Option<BuildLock>> {
+    let path = lock_path(output);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = read_holder(&mut file);
+            if force {
+                warn!(
+                    lock = %path.display(),
+                    holder = %holder,
+                    "Lock is held by another build; continuing anyway (--force)"
+                );
+                return Ok(None);
+            }
+            if !wait {
+                anyhow::bail!(
+                    "Another build ({}) holds {}. Use --wait to queue behind it or --force to ignore it.",
+                    holder,
+                    path.display()
+                );
+            }
+            info!(lock = %path.display(), holder = %holder, "Waiting for lock");
+            file.lock()?;
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+        }
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    writeln!(
+        file,
+        "pid {} since {}",
+        std::process::id(),
+        chrono::Utc::now().to_rfc3339()
+    )?;
+    file.flush()?;
+
+    Ok(Some(BuildLock { _file: file }))
+}
+
+fn lock_path(output: &Path) -> PathBuf {
+    let mut s = output.as_os_str().to_owned();
+    s.push(".lock");
+    PathBuf::from(s)
+}
+
+fn read_holder(file: &mut File) -> String {
+    let mut contents = String::new();
+    let _ = file.read_to_string(&mut contents);
+    let contents = contents.trim();
+    if contents.is_empty() {
+        "an unknown process".to_string()
+    } else {
+        contents.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_acquire_fails_while_held() {
+        let dir = std::env::temp_dir().join(format!("proseva-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("graph.sqlite.db");
+
+        let first = acquire(&output, false, false).unwrap();
+        assert!(first.is_some());
+        assert!(acquire(&output, false, false).is_err());
+        assert!(acquire(&output, false, true).unwrap().is_none());
+
+        drop(first);
+        assert!(acquire(&output, false, false).unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! `--log-level` / `--log-json`: route build and server telemetry through
+//! `tracing`. The console format is for people; `--log-json` writes one JSON
+//! object per event (with the enclosing pass spans) for log pipelines.
+//!
+//! `RUST_LOG`, when set, replaces the filter entirely, e.g. to turn on
+//! dependency logs (`RUST_LOG=info,ort=debug`).
+
+use std::io::IsTerminal;
+
+use anyhow::{anyhow, Result};
+use tracing_subscriber::EnvFilter;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct LogArgs {
+    /// Most verbose log level shown: error, warn, info, debug or trace
+    #[arg(long, global = true, default_value = "info")]
+    pub log_level: tracing::Level,
+
+    /// Log one JSON object per line instead of human-readable text
+    #[arg(long, global = true, default_value_t = false)]
+    pub log_json: bool,
+}
+
+/// Install the global subscriber. Dependencies log at `warn` and above; this
+/// library and the calling binary (its `CARGO_CRATE_NAME`) at `--log-level`.
+pub fn init(args: &LogArgs, binary: &str) -> Result<()> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) => EnvFilter::try_new(directives)?,
+        Err(_) => EnvFilter::new(directives(args.log_level, binary)),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let installed = if args.log_json {
+        builder
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .try_init()
+    } else {
+        builder
+            .with_target(false)
+            .with_ansi(std::io::stdout().is_terminal())
+            .try_init()
+    };
+    installed.map_err(|e| anyhow!("Failed to install logger: {e}"))
+}
+
+fn directives(level: tracing::Level, binary: &str) -> String {
+    let level = level.as_str().to_lowercase();
+    let mut directives = format!("warn,{}={level}", env!("CARGO_CRATE_NAME"));
+    if binary != env!("CARGO_CRATE_NAME") {
+        directives.push_str(&format!(",{binary}={level}"));
+    }
+    directives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives_scope_level_to_this_crate() {
+        let d = directives(tracing::Level::DEBUG, "proseva_embeddings");
+        assert_eq!(d, "warn,proseva_embeddings=debug");
+        assert!(EnvFilter::try_new(d).is_ok());
+        let d = directives(tracing::Level::DEBUG, "embedding_server");
+        assert_eq!(d, "warn,proseva_embeddings=debug,embedding_server=debug");
+        assert!(EnvFilter::try_new(d).is_ok());
+    }
+}
@@ -1,27 +1,76 @@
-mod db;
-mod embed;
-mod etl;
-mod graph;
-mod text;
-
-use std::path::PathBuf;
+mod commands;
+mod config;
+mod duration;
+mod interrupt;
+mod lock;
+mod memory;
+mod progress;
+mod publish;
+mod quality;
+mod report;
+mod sample;
+mod scrub;
+mod stream;
+mod summarize;
+mod verify;
+mod watch;
+
+use proseva_embeddings::{db, embed, etl, graph, logging, query, query_log, search, text};
+
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use polars::prelude::*;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use tracing::{info, info_span, warn, Instrument};
 
 #[derive(Parser, Debug)]
 #[command(name = "proseva-embeddings")]
 #[command(about = "Build knowledge graph and embeddings from virginia.db")]
-struct Args {
-    /// Path to virginia.db (input)
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Build flags given without a subcommand run `build`, as before
+    /// subcommands existed
+    #[command(flatten)]
+    build: BuildArgs,
+
+    #[command(flatten)]
+    log: logging::LogArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build the graph and embeddings from virginia.db
+    Build(BuildArgs),
+    /// Passes 1-2 only: write the graph DB without embeddings (`build
+    /// --skip-embeddings`)
+    BuildGraph(BuildArgs),
+    /// Pass 3 only: embed every node of a graph DB written by `build-graph`
+    /// (`--db`), with texts rebuilt from `--input`
+    BuildEmbeddings(BuildArgs),
+    /// The `[summaries]` stage only: summarize the nodes of a graph DB
+    /// (`--db`) that have no summary yet, with texts rebuilt from `--input`
+    BuildSummaries(BuildArgs),
+    #[command(flatten)]
+    Tool(commands::Command),
+}
+
+#[derive(clap::Args, Debug, Clone, serde::Serialize)]
+struct BuildArgs {
+    /// Path to virginia.db (input), `-` to read the database bytes from stdin,
+    /// or a `postgres://` URL (needs the `postgres` feature)
     #[arg(long)]
+    #[serde(serialize_with = "serialize_input")]
     input: Option<PathBuf>,
 
-    /// Path to write graph.sqlite.db (output)
-    #[arg(long)]
+    /// Path to write graph.sqlite.db (output), or `-` / a named pipe to stream
+    /// the finished database out (console output then goes to stderr)
+    #[arg(long, visible_alias = "db")]
     output: Option<PathBuf>,
 
     /// Path to write embeddings.jsonl (output)
@@ -32,10 +81,123 @@ struct Args {
     #[arg(long, default_value_t = false)]
     skip_embeddings: bool,
 
+    /// Set by `build-embeddings`: embed the existing --output graph
+    #[arg(skip)]
+    embed_existing: bool,
+
+    /// Set by `build-summaries`: summarize the existing --output graph
+    #[arg(skip)]
+    summarize_existing: bool,
+
+    /// Embed only nodes from these sources (comma-separated); the graph still
+    /// covers every source
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = clap::builder::PossibleValuesParser::new(graph::nodes::SOURCES),
+        conflicts_with_all = ["skip_embeddings", "embed_skip", "embed_from", "load_jsonl"]
+    )]
+    embed_only: Vec<String>,
+
+    /// Embed every source except these (comma-separated)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = clap::builder::PossibleValuesParser::new(graph::nodes::SOURCES),
+        conflicts_with_all = ["skip_embeddings", "embed_from", "load_jsonl"]
+    )]
+    embed_skip: Vec<String>,
+
+    /// Leave code sections ETL marked repealed unembedded; they stay in the
+    /// graph with `nodes.status = 'repealed'`
+    #[arg(long, default_value_t = false, conflicts_with_all = ["skip_embeddings", "embed_from", "load_jsonl"])]
+    skip_repealed: bool,
+
+    /// Also embed each code and constitution section's heading on its own,
+    /// into `title_embeddings`; search blends the two scores
+    #[arg(long, default_value_t = false, conflicts_with_all = ["embed_from", "load_jsonl"])]
+    title_embeddings: bool,
+
+    /// Keep each node's source text as read (after scrubbing) in
+    /// `raw_texts`, with the input row it came from
+    #[arg(long, default_value_t = false, conflicts_with_all = ["embed_from", "load_jsonl"])]
+    raw_texts: bool,
+
+    /// Index node texts in a full-text `node_fts` table, so searches can
+    /// exclude terms (`--exclude-terms`, `exclude_terms`)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["embed_from", "load_jsonl"])]
+    fts: bool,
+
+    /// Embedding model preset; recorded in model_info
+    #[arg(
+        long,
+        default_value = embed::models::DEFAULT_MODEL,
+        value_parser = clap::builder::PossibleValuesParser::new(embed::models::names())
+    )]
+    model: String,
+
+    /// Device to run the embedding model on
+    #[arg(long, value_enum, default_value_t = embed::Device::Auto)]
+    device: embed::Device,
+
     /// Batch size for embedding computation
     #[arg(long, default_value_t = 64)]
     batch_size: usize,
 
+    /// Batch texts of similar length by padded tokens (up to --batch-size
+    /// texts of the full sequence length) instead of by count, so short
+    /// texts go through many per batch
+    #[arg(long, default_value_t = false)]
+    pack_batches: bool,
+
+    /// Abandon an embedding batch with no result after this many seconds and
+    /// continue with half the batch size (0 disables the watchdog)
+    #[arg(long, default_value_t = 600)]
+    batch_timeout: u64,
+
+    /// Seconds to pause when embedding throughput collapses (e.g. thermal
+    /// throttling) before shrinking the batch size; 0 disables pacing
+    #[arg(long, default_value_t = 30)]
+    cooldown: u64,
+
+    /// Wall-time budget for the whole build (e.g. 2h, 90m, 1h30m; a bare
+    /// number is minutes). Embedding stops before a batch would overrun it,
+    /// the rest stays pending for --resume, and the build exits successfully
+    #[arg(long, alias = "time-budget", value_parser = duration::parse_duration)]
+    #[serde(serialize_with = "duration::serialize_secs")]
+    max_duration: Option<std::time::Duration>,
+
+    /// Resident memory budget, e.g. `8G`. A build over it before Pass 3 or
+    /// the summaries stage spills node texts to a temporary SQLite file
+    /// beside the output
+    #[arg(long, value_parser = memory::parse_size, value_name = "SIZE")]
+    max_memory: Option<u64>,
+
+    /// Rewrite this JSON file after every embedding batch with Pass 3's
+    /// progress and per-batch telemetry, for live dashboards
+    #[arg(long, value_name = "PATH")]
+    progress_file: Option<PathBuf>,
+
+    /// Tokens per text the embedding model reads; longer input is truncated
+    /// (default: the model preset's, up to its context length)
+    #[arg(long)]
+    max_seq_len: Option<usize>,
+
+    /// Embed a text longer than --max-seq-len whole instead of truncated:
+    /// split it into pieces that fit and store the mean of their vectors
+    #[arg(long, default_value_t = false)]
+    repair_truncated: bool,
+
+    /// Maximum approximate tokens per chunk when splitting long texts
+    /// (default: --max-seq-len less room for the prompt prefix, 500 at 512)
+    #[arg(long)]
+    chunk_tokens: Option<usize>,
+
+    /// Approximate tokens repeated between consecutive chunks (must be below
+    /// --chunk-tokens)
+    #[arg(long, default_value_t = 50)]
+    chunk_overlap: usize,
+
     /// Run ETL + graph only, write embeddable texts to Parquet, skip embedding
     #[arg(long)]
     prepare: Option<PathBuf>,
@@ -47,17 +209,379 @@ struct Args {
     /// Load embeddings from JSONL into an existing graph DB (no model needed)
     #[arg(long)]
     load_jsonl: Option<PathBuf>,
+
+    /// Upload output artifacts to an object store after a successful build
+    /// (e.g. s3://bucket/prefix). Credentials are read from AWS_* env vars.
+    #[arg(long)]
+    publish: Option<String>,
+
+    /// POST a JSON build report to this URL when the run finishes (success or failure)
+    #[arg(long)]
+    notify_url: Option<String>,
+
+    /// If another build holds the output lock, wait for it instead of failing
+    #[arg(long, default_value_t = false)]
+    wait: bool,
+
+    /// Ignore another build's output lock and proceed anyway
+    #[arg(long, default_value_t = false, conflicts_with = "wait")]
+    force: bool,
+
+    /// Write a human-readable build report (HTML, or Markdown if the path ends in .md)
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Pipeline config (TOML), e.g. data-quality rules checked after each pass
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Reuse embeddings from the existing --output DB for nodes whose text is
+    /// unchanged, and only embed new or edited ones
+    #[arg(long, default_value_t = false, conflicts_with_all = ["prepare", "embed_from", "load_jsonl"])]
+    incremental: bool,
+
+    /// Finish an interrupted embedding pass in the existing --output DB,
+    /// embedding only the nodes still marked pending (texts are rebuilt from --input)
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["prepare", "embed_from", "load_jsonl", "incremental", "skip_embeddings"]
+    )]
+    resume: bool,
+
+    /// Run ETL, node and edge building, print statistics and an embedding-time
+    /// estimate, then exit without writing the output DB or loading the model
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["prepare", "embed_from", "load_jsonl", "resume", "incremental", "publish"]
+    )]
+    dry_run: bool,
+
+    /// Embedding throughput assumed by --dry-run's time estimate
+    #[arg(long, default_value_t = 1000.0)]
+    tokens_per_sec: f64,
+
+    /// Keep at most N rows per source table (after --sample-rate), for fast
+    /// iteration on a small slice of the corpus
+    #[arg(long, conflicts_with_all = ["embed_from", "load_jsonl"])]
+    limit: Option<usize>,
+
+    /// Keep this fraction (0, 1] of each source table's rows; the same rate
+    /// and seed always pick the same rows
+    #[arg(
+        long,
+        visible_alias = "sample",
+        value_parser = sample::parse_rate,
+        conflicts_with_all = ["embed_from", "load_jsonl"]
+    )]
+    sample_rate: Option<f64>,
+
+    /// Seed for --sample-rate; another seed draws another sample of the
+    /// same size
+    #[arg(long, default_value_t = 0, requires = "sample_rate")]
+    sample_seed: u64,
+
+    /// Source rows read per batch; Pass 1 holds a few raw batches per table
+    /// in memory rather than the whole table
+    #[arg(long, default_value_t = 2000, value_name = "ROWS")]
+    read_batch: usize,
+
+    /// Fail the build when more than this fraction [0, 1] of any source
+    /// table's rows can't be read; they are skipped and recorded either way
+    #[arg(long, value_parser = parse_error_rate, value_name = "RATE")]
+    max_row_error_rate: Option<f64>,
+
+    /// A folder (or one file) of documents to build alongside the input's:
+    /// CSV or JSONL records with filename, title and content fields, and
+    /// plain-text or Markdown files, one document each
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["embed_from", "load_jsonl"])]
+    extra_documents: Option<PathBuf>,
+
+    /// Copy --input with VACUUM INTO beside the output and read the copy, so
+    /// a scraper writing the input mid-build can't change what later tables see
+    #[arg(long, default_value_t = false, conflicts_with_all = ["embed_from", "load_jsonl"])]
+    snapshot_input: bool,
+
+    /// Keep running and rebuild whenever --input changes, reusing embeddings
+    /// for unchanged texts; each rebuild replaces --output atomically
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["prepare", "embed_from", "load_jsonl", "resume", "dry_run", "publish"]
+    )]
+    watch: bool,
+
+    /// Set by --watch: the live output, read for embeddings to reuse but left in place
+    #[arg(skip)]
+    #[serde(skip)]
+    reuse_from: Option<PathBuf>,
+
+    /// Set for a streamed --output: the build lock is already held on the real
+    /// destination, so --output (the staging file) isn't locked again
+    #[arg(skip)]
+    #[serde(skip)]
+    output_locked: bool,
+}
+
+impl BuildArgs {
+    /// `--output`, or `graph.sqlite.db` beside an input file.
+    fn output_path(&self) -> Option<PathBuf> {
+        self.output.clone().or_else(|| {
+            let input = self.input.as_deref()?;
+            if db::reader::postgres_url(input).is_some() {
+                return None;
+            }
+            Some(input.parent()?.join("graph.sqlite.db"))
+        })
+    }
+
+    /// Whether `--embed-only` / `--embed-skip` let nodes from `source` be embedded.
+    fn embeds_source(&self, source: &str) -> bool {
+        (self.embed_only.is_empty() || self.embed_only.iter().any(|s| s == source))
+            && !self.embed_skip.iter().any(|s| s == source)
+    }
+}
+
+/// A fraction in [0, 1], for `--max-row-error-rate`.
+fn parse_error_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("{rate} is not in [0, 1]"))
+    }
+}
+
+/// `--input` in the build report's params, without a database password.
+fn serialize_input<S: serde::Serializer>(
+    input: &Option<PathBuf>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match input {
+        Some(input) => s.serialize_some(&db::reader::display_input(input)),
+        None => s.serialize_none(),
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let cli = Cli::parse();
+    logging::init(&cli.log, env!("CARGO_CRATE_NAME"))?;
+    match cli.command {
+        Some(Command::Build(args)) if args.watch => watch(args).await,
+        Some(Command::Build(args)) => build(args).await,
+        Some(Command::BuildGraph(mut args)) => {
+            args.skip_embeddings = true;
+            match args.watch {
+                true => watch(args).await,
+                false => build(args).await,
+            }
+        }
+        Some(Command::BuildEmbeddings(mut args)) => {
+            if args.watch {
+                anyhow::bail!("--watch rebuilds the graph too; use `build --watch`");
+            }
+            args.embed_existing = true;
+            build(args).await
+        }
+        Some(Command::BuildSummaries(mut args)) => {
+            if args.watch {
+                anyhow::bail!("--watch rebuilds the graph too; use `build --watch`");
+            }
+            args.summarize_existing = true;
+            build(args).await
+        }
+        Some(Command::Tool(command)) => commands::run(command).await,
+        None => build(cli.build).await,
+    }
+}
+
+async fn build(mut args: BuildArgs) -> Result<()> {
+    let mut report = report::BuildReport::new();
+    report.params = serde_json::to_value(&args).ok();
+
+    // Streaming mode: divert stdout before anything is printed, then stage stdin
+    let stream_output = stream::StreamOutput::detect(args.output.as_deref())?;
+    if let Some(ref out) = stream_output {
+        if args.load_jsonl.is_some() || args.embed_from.is_some() {
+            anyhow::bail!("Streaming --output only applies to full builds and --prepare");
+        }
+        args.output = Some(out.staging_path().to_path_buf());
+        args.output_locked = true;
+    }
+    let _lock = match (&stream_output, args.dry_run) {
+        (Some(out), false) => match out.lock_target() {
+            Some(path) => lock::acquire(path, args.wait, args.force)?,
+            None => None,
+        },
+        _ => None,
+    };
+    let staged_input = match args.input.as_deref() {
+        Some(path) if stream::is_stdio(path) => {
+            if args.output.is_none() {
+                anyhow::bail!("--output is required with --input -");
+            }
+            Some(stream::stage_stdin()?)
+        }
+        _ => None,
+    };
+    if let Some(ref staged) = staged_input {
+        args.input = Some(staged.path().to_path_buf());
+    }
+
+    let streaming = stream_output.is_some();
+    let mut result = run(&args, &mut report)
+        .instrument(info_span!("build"))
+        .await;
+    if let (Ok(()), Some(out)) = (&result, stream_output) {
+        let dest = out.describe();
+        match out.emit() {
+            Ok(bytes) => {
+                info!(bytes, dest = %dest, "Streamed output");
+                report.output = Some(dest);
+            }
+            Err(e) => result = Err(e),
+        }
+    }
+    report.finish(&result);
+
+    if let Some(ref path) = args.report {
+        match report::render::write_report(path, &report) {
+            Ok(()) => info!(path = %path.display(), "Wrote report"),
+            Err(e) => warn!(path = %path.display(), "Failed to write report: {:#}", e),
+        }
+    }
+
+    // The machine-readable report always sits beside the output DB.
+    if let Some(path) = args.output_path().filter(|_| !streaming && !args.dry_run) {
+        let path = report::json_path(&path);
+        match report::write_json(&path, &report) {
+            Ok(()) => info!(path = %path.display(), "Wrote build report"),
+            Err(e) => warn!(path = %path.display(), "Failed to write build report: {:#}", e),
+        }
+        if let Some(ref quality) = report.etl_quality {
+            let path = etl::quality::json_path(&path);
+            match etl::quality::write_json(&path, quality) {
+                Ok(()) => info!(path = %path.display(), "Wrote ETL quality report"),
+                Err(e) => warn!(path = %path.display(), "Failed to write ETL quality report: {:#}", e),
+            }
+        }
+    }
+
+    if let Some(ref url) = args.notify_url {
+        match report::notify(url, &report).await {
+            Ok(()) => info!(url = %url, "Sent build notification"),
+            Err(e) => warn!(url = %url, "Build notification failed: {:#}", e),
+        }
+    }
+
+    result
+}
+
+/// `--watch`: build, then rebuild each time the input changes. Every build is
+/// staged beside the output and only replaces it on success; a failed one is
+/// logged and the previous output stays live.
+async fn watch(args: BuildArgs) -> Result<()> {
+    let input = args
+        .input
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("--input is required with --watch"))?;
+    let output = args
+        .output_path()
+        .ok_or_else(|| anyhow::anyhow!("--output is required with --watch"))?;
+    if stream::is_stdio(&input) || stream::is_stdio(&output) {
+        anyhow::bail!("--watch needs file paths for --input and --output, not `-`");
+    }
+    if db::reader::postgres_url(&input).is_some() {
+        anyhow::bail!("--watch watches an input file; it can't watch a PostgreSQL database");
+    }
+    let jsonl = args
+        .jsonl
+        .clone()
+        .unwrap_or_else(|| default_jsonl_path(&output));
+    let _lock = lock::acquire(&output, args.wait, args.force)?;
+
+    let mut seen = watch::signature(&input)?;
+    loop {
+        match watch_cycle(&args, &output, &jsonl).await {
+            Ok(()) => info!(output = %output.display(), "Replaced output"),
+            Err(e) if e.is::<interrupt::Interrupted>() => {
+                anyhow::bail!("Stopped watching; {} is unchanged", output.display());
+            }
+            Err(e) => warn!(
+                output = %output.display(),
+                "Rebuild failed; keeping the current output: {:#}",
+                e
+            ),
+        }
+
+        info!(input = %input.display(), "Watching for changes");
+        seen = watch::wait_for_change(&input, seen).await?;
+        info!(input = %input.display(), "Input changed; rebuilding");
+    }
+}
+
+/// One `--watch` build, staged beside `output` and `jsonl` and moved over
+/// them only if it succeeds.
+async fn watch_cycle(args: &BuildArgs, output: &Path, jsonl: &Path) -> Result<()> {
+    let (staged, staged_jsonl) = (watch::staging_path(output), watch::staging_path(jsonl));
+    let mut cycle = args.clone();
+    cycle.output = Some(staged.clone());
+    cycle.jsonl = Some(staged_jsonl.clone());
+    cycle.reuse_from = Some(output.to_path_buf());
+    let result = async {
+        build(cycle).await?;
+        if !args.skip_embeddings {
+            // Pass 3 only wrote the vectors it computed to the JSONL; the
+            // ones reused from the live output are in the staged DB alone.
+            let conn = Connection::open(&staged)?;
+            let count = db::writer::export_embeddings_jsonl(&conn, &staged_jsonl)?;
+            drop(conn);
+            info!(jsonl = %jsonl.display(), embeddings = count, "Exported embeddings");
+            watch::promote(&staged_jsonl, jsonl)?;
+        }
+        watch::promote(&staged, output)
+    }
+    .await;
+    watch::discard(&staged);
+    let _ = std::fs::remove_file(&staged_jsonl);
+    result
+}
+
+async fn run(args: &BuildArgs, report: &mut report::BuildReport) -> Result<()> {
     let total_start = Instant::now();
 
     // Validate mutually exclusive flags
     if args.prepare.is_some() && args.embed_from.is_some() {
         anyhow::bail!("--prepare and --embed-from are mutually exclusive");
     }
+    let max_seq_len = embed::models::find(&args.model)?.seq_len(args.max_seq_len)?;
+    let chunking = text::chunker::ChunkConfig::new(
+        args.chunk_tokens
+            .unwrap_or_else(|| text::chunker::ChunkConfig::budget(max_seq_len)),
+        args.chunk_overlap,
+    )?;
+
+    // Fail fast on a bad --publish target rather than after a multi-hour build
+    if let Some(ref target) = args.publish {
+        publish::validate_target(target)?;
+    }
+
+    let config = match args.config {
+        Some(ref path) => config::load(path)?,
+        None => config::Config::default(),
+    };
+    let etl_options = etl::EtlOptions {
+        transforms: std::sync::Arc::new(etl::transforms::TitleTransforms::from_config(
+            config.transforms.as_deref(),
+        )?),
+        dedup: config.dedup,
+        templates: std::sync::Arc::new(etl::templates::TextTemplates::new(&config.templates)?),
+        filters: std::sync::Arc::new(etl::filters::Filters::new(&config.filters)?),
+    };
+    let mut quality = quality::QualityGate::new(config.quality);
+    let monitor = memory::Monitor::start(args.max_memory);
 
     // --load-jsonl mode: load pre-computed embeddings from JSONL into existing DB
     if let Some(ref jsonl_path) = args.load_jsonl {
@@ -68,10 +592,14 @@ async fn main() -> Result<()> {
             .output
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("--output is required with --load-jsonl"))?;
+        let _lock = lock::acquire(output_path, args.wait, args.force)?;
 
-        println!("JSONL:   {}", jsonl_path.display());
-        println!("Output:  {}", output_path.display());
-        println!();
+        info!(
+            jsonl = %jsonl_path.display(),
+            output = %output_path.display(),
+            "Loading embeddings from JSONL"
+        );
+        report.mode = "load_jsonl";
 
         let out_conn = db::writer::open_output_db(output_path.to_str().unwrap())?;
         db::writer::clear_embeddings(&out_conn)?;
@@ -91,17 +619,30 @@ async fn main() -> Result<()> {
             .ok_or_else(|| anyhow::anyhow!("First JSONL line has no 'embedding' array"))?
             .len();
 
-        println!("  Inferred dimensions: {}", dims);
-        db::writer::write_model_info(&out_conn, "onnx-community/embeddinggemma-300m-ONNX", dims)?;
+        info!(dims, "Inferred dimensions");
+        let model = embed::models::find(&args.model)?;
+        if dims != model.dims {
+            anyhow::bail!(
+                "JSONL embeddings have {} dimensions, but {} produces {} (pass the --model that wrote them)",
+                dims,
+                model.name,
+                model.dims
+            );
+        }
+        db::writer::write_model_info(&out_conn, model, model.seq_len(args.max_seq_len)?, dims)?;
 
-        println!("  Loading embeddings from JSONL...");
         let count = db::writer::load_embeddings_from_jsonl(&out_conn, jsonl_path)?;
-        println!("  Loaded {} embeddings", count);
+        info!(embeddings = count, "Loaded embeddings");
+        report.count("embeddings", count);
+        materialize_views(&out_conn, report)?;
+        quality.check("load", report)?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(output_path)?;
 
-        println!(
-            "\n=== Done in {:.2}s ===",
-            total_start.elapsed().as_secs_f64()
-        );
+        publish_if_requested(args.publish.as_deref(), &[output_path]).await?;
+
+        info!(secs = total_start.elapsed().as_secs_f64(), "Done");
         return Ok(());
     }
 
@@ -114,21 +655,25 @@ async fn main() -> Result<()> {
             .output
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("--output is required with --embed-from"))?;
+        let _lock = lock::acquire(output_path, args.wait, args.force)?;
 
-        let jsonl_path = args.jsonl.unwrap_or_else(|| {
+        let jsonl_path = args.jsonl.clone().unwrap_or_else(|| {
             output_path
                 .parent()
                 .unwrap()
                 .join("embeddings.jsonl")
         });
 
-        println!("Parquet: {}", parquet_path.display());
-        println!("Output:  {}", output_path.display());
-        println!("JSONL:   {}", jsonl_path.display());
-        println!();
+        report.mode = "embed_from";
+        report.input = Some(parquet_path.display().to_string());
+        info!(
+            parquet = %parquet_path.display(),
+            output = %output_path.display(),
+            jsonl = %jsonl_path.display(),
+            "Embedding texts from Parquet"
+        );
 
         // Read (node_id, text) pairs from Parquet
-        println!("=== Reading texts from Parquet ===");
         let read_start = Instant::now();
 
         let df = LazyFrame::scan_parquet(parquet_path, Default::default())?
@@ -146,12 +691,14 @@ async fn main() -> Result<()> {
             .map(|s| s.to_string())
             .collect();
 
-        println!(
-            "  Loaded {} texts in {:.2}s",
-            texts.len(),
-            read_start.elapsed().as_secs_f64()
+        info!(
+            texts = texts.len(),
+            secs = read_start.elapsed().as_secs_f64(),
+            "Read texts from Parquet"
         );
-        println!();
+        report.count("texts", texts.len());
+        let lengths: Vec<usize> = texts.iter().map(|t| t.len()).collect();
+        report.histogram("text_length_chars", report::text_length_histogram(&lengths));
 
         // Open existing DB
         let out_conn = db::writer::open_output_db(output_path.to_str().unwrap())?;
@@ -160,14 +707,240 @@ async fn main() -> Result<()> {
         db::writer::clear_embeddings(&out_conn)?;
 
         // Run embedding
-        run_embedding(&out_conn, &jsonl_path, &node_ids, &texts, args.batch_size).await?;
+        let pass3_start = Instant::now();
+        let embedded =
+            run_embedding(&out_conn, &jsonl_path, &node_ids, EmbedTexts::List(&texts), args, false, report)
+                .await?;
+        report.count("embeddings", embedded);
+        report.duration("pass3", pass3_start);
+        monitor.pass_done("pass3", report);
+        materialize_views(&out_conn, report)?;
+        quality.check("pass3", report)?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(output_path)?;
+
+        publish_if_requested(args.publish.as_deref(), &[output_path, &jsonl_path]).await?;
+
+        info!(
+            secs = total_start.elapsed().as_secs_f64(),
+            output = %output_path.display(),
+            jsonl = %jsonl_path.display(),
+            "Done"
+        );
+        return Ok(());
+    }
 
-        println!(
-            "\n=== Done in {:.2}s ===",
-            total_start.elapsed().as_secs_f64()
+    // --resume mode: finish an interrupted Pass 3 in an existing output DB
+    if args.resume {
+        let input_path = args
+            .input
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--input is required with --resume"))?;
+        let output_path = args
+            .output
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--output is required with --resume"))?;
+        if !output_path.exists() {
+            anyhow::bail!("Output DB not found: {}", output_path.display());
+        }
+        let _lock = lock::acquire(output_path, args.wait, args.force)?;
+        let jsonl_path = args
+            .jsonl
+            .clone()
+            .unwrap_or_else(|| default_jsonl_path(output_path));
+
+        report.mode = "resume";
+        report.input = Some(db::reader::display_input(input_path));
+        info!(
+            input = %db::reader::display_input(input_path),
+            output = %output_path.display(),
+            jsonl = %jsonl_path.display(),
+            "Resuming build"
         );
-        println!("Output: {}", output_path.display());
-        println!("JSONL:  {}", jsonl_path.display());
+
+        let out_conn = db::writer::open_output_db(output_path.to_str().unwrap())?;
+        let model = embed::models::find(&args.model)?;
+        let recorded: Option<String> = out_conn
+            .query_row("SELECT value FROM model_info WHERE key = 'model_name'", [], |r| r.get(0))
+            .optional()?;
+        if let Some(recorded) = recorded.filter(|m| m != model.model_id) {
+            anyhow::bail!(
+                "{} was embedded with {}, not {}; resume with the same --model",
+                output_path.display(),
+                recorded,
+                model.name
+            );
+        }
+        let pending = db::resume::pending_nodes(&out_conn)?;
+        info!(pending = pending.len(), "Resuming Pass 3");
+
+        if !pending.is_empty() {
+            let texts = graph::store::rebuild_text_candidates(
+                input_path,
+                chunking,
+                &config.sources,
+                &etl_options,
+                args.extra_documents.as_deref(),
+            )?;
+            let (node_ids, texts) = db::resume::pending_texts(&pending, &texts)?;
+            report.count("texts", texts.len());
+
+            let pass3_start = Instant::now();
+            run_embedding(&out_conn, &jsonl_path, &node_ids, EmbedTexts::List(&texts), args, true, report)
+                .await?;
+            report.duration("pass3", pass3_start);
+            monitor.pass_done("pass3", report);
+        }
+        let total: usize = out_conn.query_row("SELECT COUNT(*) FROM embeddings", [], |r| r.get(0))?;
+        report.count("embeddings", total);
+        materialize_views(&out_conn, report)?;
+        quality.check("pass3", report)?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(output_path)?;
+
+        publish_if_requested(args.publish.as_deref(), &[output_path, &jsonl_path]).await?;
+
+        info!(secs = total_start.elapsed().as_secs_f64(), "Done");
+        return Ok(());
+    }
+
+    // build-embeddings: Pass 3 alone over a graph written by build-graph
+    if args.embed_existing {
+        if args.resume || args.prepare.is_some() || args.embed_from.is_some() || args.load_jsonl.is_some() {
+            anyhow::bail!("build-embeddings can't be combined with --resume, --prepare, --embed-from or --load-jsonl");
+        }
+        let input_path = args
+            .input
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--input is required with build-embeddings"))?;
+        let output_path = args
+            .output
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--db is required with build-embeddings"))?;
+        if !output_path.exists() {
+            anyhow::bail!("Graph DB not found: {}", output_path.display());
+        }
+        let _lock = lock::acquire(output_path, args.wait, args.force)?;
+        let jsonl_path = args
+            .jsonl
+            .clone()
+            .unwrap_or_else(|| default_jsonl_path(output_path));
+
+        report.mode = "build_embeddings";
+        report.input = Some(db::reader::display_input(input_path));
+        info!(
+            input = %db::reader::display_input(input_path),
+            db = %output_path.display(),
+            jsonl = %jsonl_path.display(),
+            "Embedding existing graph"
+        );
+
+        let out_conn = db::writer::open_output_db(output_path.to_str().unwrap())?;
+        let nodes = db::resume::embeddable_nodes(&out_conn)?;
+        let texts = graph::store::rebuild_text_candidates(
+            input_path,
+            chunking,
+            &config.sources,
+            &etl_options,
+            args.extra_documents.as_deref(),
+        )?;
+        let (node_ids, texts): (Vec<i64>, Vec<String>) = {
+            let (ids, texts) = db::resume::pending_texts(&nodes, &texts)?;
+            let sources: std::collections::HashMap<i64, &str> =
+                nodes.iter().map(|n| (n.id, n.source.as_str())).collect();
+            let repealed = if args.skip_repealed {
+                db::writer::repealed_node_ids(&out_conn)?
+            } else {
+                std::collections::HashSet::new()
+            };
+            ids.into_iter()
+                .zip(texts)
+                .filter(|(id, text)| {
+                    !text.is_empty() && args.embeds_source(sources[id]) && !repealed.contains(id)
+                })
+                .unzip()
+        };
+        report.count("texts", texts.len());
+        let lengths: Vec<usize> = texts.iter().map(|t| t.len()).collect();
+        report.histogram("text_length_chars", report::text_length_histogram(&lengths));
+
+        db::writer::clear_embeddings(&out_conn)?;
+        let pass3_start = Instant::now();
+        let embedded =
+            run_embedding(&out_conn, &jsonl_path, &node_ids, EmbedTexts::List(&texts), args, false, report)
+                .await?;
+        report.count("embeddings", embedded);
+        report.duration("pass3", pass3_start);
+        monitor.pass_done("pass3", report);
+        materialize_views(&out_conn, report)?;
+        quality.check("pass3", report)?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(output_path)?;
+
+        publish_if_requested(args.publish.as_deref(), &[output_path, &jsonl_path]).await?;
+
+        info!(secs = total_start.elapsed().as_secs_f64(), "Done");
+        return Ok(());
+    }
+
+    // build-summaries: the [summaries] stage alone over an existing graph
+    if args.summarize_existing {
+        let summaries = config
+            .summaries
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("build-summaries needs a --config with [summaries]"))?;
+        summaries.validate()?;
+        let input_path = args
+            .input
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--input is required with build-summaries"))?;
+        let output_path = args
+            .output
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--db is required with build-summaries"))?;
+        if !output_path.exists() {
+            anyhow::bail!("Graph DB not found: {}", output_path.display());
+        }
+        let _lock = lock::acquire(output_path, args.wait, args.force)?;
+
+        report.mode = "build_summaries";
+        report.input = Some(db::reader::display_input(input_path));
+        info!(
+            input = %db::reader::display_input(input_path),
+            db = %output_path.display(),
+            "Summarizing existing graph"
+        );
+
+        let out_conn = db::writer::open_output_db(output_path.to_str().unwrap())?;
+        let nodes = db::resume::embeddable_nodes(&out_conn)?;
+        let candidates = graph::store::rebuild_text_candidates(
+            input_path,
+            chunking,
+            &config.sources,
+            &etl_options,
+            args.extra_documents.as_deref(),
+        )?;
+        let (ids, texts) = db::resume::pending_texts(&nodes, &candidates)?;
+        let texts: std::collections::HashMap<i64, String> = ids.into_iter().zip(texts).collect();
+        run_summaries(
+            &out_conn,
+            summaries,
+            output_path,
+            summarize::Texts::Map(&texts),
+            args,
+            report,
+        )
+        .await?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(output_path)?;
+
+        publish_if_requested(args.publish.as_deref(), &[output_path]).await?;
+
+        info!(secs = total_start.elapsed().as_secs_f64(), "Done");
         return Ok(());
     }
 
@@ -177,98 +950,287 @@ async fn main() -> Result<()> {
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("--input is required (unless using --embed-from)"))?;
 
-    if !input_path.exists() {
+    if db::reader::postgres_url(input_path).is_none() && !input_path.exists() {
         anyhow::bail!("Input file not found: {}", input_path.display());
     }
 
-    let output_path = args.output.unwrap_or_else(|| {
-        input_path
-            .parent()
-            .unwrap()
-            .join("graph.sqlite.db")
-    });
+    let output_path = args
+        .output_path()
+        .ok_or_else(|| anyhow::anyhow!("--output is required"))?;
+
+    let jsonl_path = args
+        .jsonl
+        .clone()
+        .unwrap_or_else(|| default_jsonl_path(&output_path));
+
+    let _lock = match args.dry_run || args.output_locked {
+        true => None,
+        false => lock::acquire(&output_path, args.wait, args.force)?,
+    };
+    // Fail fast on a [summaries] stage that can't make a request
+    if let (Some(summaries), false, None) = (&config.summaries, args.dry_run, &args.prepare) {
+        summaries.validate()?;
+    }
 
-    let jsonl_path = args.jsonl.clone().unwrap_or_else(|| {
-        let mut s = output_path.to_str().unwrap().to_string();
-        if let Some(pos) = s.rfind('.') {
-            s.truncate(pos);
+    if args.prepare.is_some() {
+        report.mode = "prepare";
+    }
+    if args.dry_run {
+        report.mode = "dry_run";
+    }
+    report.input = Some(db::reader::display_input(input_path));
+    info!(
+        input = %db::reader::display_input(input_path),
+        output = %output_path.display(),
+        jsonl = %jsonl_path.display(),
+        "Starting build"
+    );
+    let freshness = db::build_info::source_freshness(input_path)?;
+    match freshness {
+        Some(ref f) => info!(scraped_at = %f.scraped_at.to_rfc3339(), from = f.from, "Source age"),
+        None => warn!("The input has no metadata.scraped_at; its age is unknown"),
+    }
+    let snapshot = match args.snapshot_input {
+        true => {
+            let start = Instant::now();
+            let dir = output_path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let snapshot = db::reader::snapshot_input(input_path, dir)?;
+            let (bytes, sha256) = report::sha256_file(snapshot.path())?;
+            info!(
+                path = %snapshot.path().display(),
+                bytes,
+                sha256 = %sha256,
+                secs = start.elapsed().as_secs_f64(),
+                "Snapshotted input"
+            );
+            report.duration("snapshot", start);
+            report.input_sha256 = Some(sha256);
+            Some(snapshot)
         }
-        PathBuf::from(format!("{}.jsonl", s))
-    });
-
-    println!("Input:  {}", input_path.display());
-    println!("Output: {}", output_path.display());
-    println!("JSONL:  {}", jsonl_path.display());
-    println!();
-
-    // Open input database
-    let input_conn =
-        Connection::open_with_flags(input_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        false => None,
+    };
+    let input_path = snapshot.as_ref().map_or(input_path.as_path(), |s| s.path());
+    report.sources = db::reader::detect_tables(input_path, &config.sources)?;
 
     // ========== Pass 1: Parse — Build Nodes ==========
-    println!("=== Pass 1: Building nodes ===");
+    let pass1 = info_span!("pass1").entered();
+    info!("Building nodes");
     let pass1_start = Instant::now();
 
-    let code_rows = db::reader::read_virginia_code(&input_conn)?;
-    println!("  virginia_code:  {} rows", code_rows.len());
-
-    let constitution_rows = db::reader::read_constitution(&input_conn)?;
-    println!("  constitution:   {} rows", constitution_rows.len());
-
-    let authority_rows = db::reader::read_authorities(&input_conn)?;
-    println!("  authorities:    {} rows", authority_rows.len());
-
-    let court_rows = db::reader::read_courts(&input_conn)?;
-    println!("  courts:         {} rows", court_rows.len());
-
-    let popular_name_rows = db::reader::read_popular_names(&input_conn)?;
-    println!("  popular_names:  {} rows", popular_name_rows.len());
-
-    let document_rows = db::reader::read_documents(&input_conn)?;
-    println!("  documents:      {} rows", document_rows.len());
-
     // --- ETL: clean, enrich, filter, dedup ---
-    println!("\n  Running ETL pipeline...");
+    // Each table streams through sampling, scrubbing and ETL a batch at a
+    // time on its own thread, with its own read-only connection and `Etl`,
+    // so only cleaned text and the few columns Pass 2 needs stay in memory,
+    // not every raw row. Over --max-memory the threads take turns, a batch
+    // at a time.
+    info!(dedup = etl_options.dedup.as_str(), "Running ETL pipeline");
     let etl_start = Instant::now();
-    let cleaned = etl::run_etl(
-        &code_rows,
-        &constitution_rows,
-        &authority_rows,
-        &court_rows,
-        &popular_name_rows,
-        &document_rows,
-    )?;
+    let source = SourceReader {
+        input: input_path,
+        batch_rows: args.read_batch,
+        sources: &config.sources,
+        sampling: sample::Sampling {
+            rate: args.sample_rate,
+            limit: args.limit,
+            seed: args.sample_seed,
+        },
+        scrubber: config
+            .scrub
+            .as_ref()
+            .map(scrub::Scrubber::new)
+            .transpose()?,
+        raw_texts: args.raw_texts,
+        etl_options: etl_options.clone(),
+        governor: memory::Governor::new(&monitor),
+        span: tracing::Span::current(),
+    };
+    let tables = std::thread::scope(|s| -> Result<_> {
+        let source = &source;
+        // Pass 2 needs the code and constitution hierarchy, not the text.
+        let code = s.spawn(move || {
+            source.clean(
+                etl::Etl::virginia_code,
+                |mut r: db::reader::VirginiaCodeRow| {
+                    r.title = String::new();
+                    r.body = String::new();
+                    Some(r)
+                },
+            )
+        });
+        let constitution = s.spawn(move || {
+            source.clean(
+                etl::Etl::constitution,
+                |mut r: db::reader::ConstitutionRow| {
+                    r.section_title = String::new();
+                    r.section_text = String::new();
+                    Some(r)
+                },
+            )
+        });
+        let authorities = s
+            .spawn(move || source.clean(etl::Etl::authorities, |_: db::reader::AuthorityRow| None));
+        let courts =
+            s.spawn(move || source.clean(etl::Etl::courts, |_: db::reader::CourtRow| None));
+        let popular_names = s.spawn(move || {
+            source.clean(etl::Etl::popular_names, |_: db::reader::PopularNameRow| {
+                None
+            })
+        });
+        let acts = s.spawn(move || source.clean(etl::Etl::acts, |_: db::reader::ActRow| None));
+        let federal_code =
+            s.spawn(move || source.clean(etl::Etl::federal_code, |_: db::reader::FederalRow| None));
+        // Documents are kept whole: Pass 2 extracts citations from their content.
+        let documents = s.spawn(move || source.clean(etl::Etl::documents, Some));
+        Ok((
+            joined(code)?,
+            joined(constitution)?,
+            joined(authorities)?,
+            joined(courts)?,
+            joined(popular_names)?,
+            joined(acts)?,
+            joined(federal_code)?,
+            joined(documents)?,
+        ))
+    })?;
+
+    // Merged in table order, so the result is the same as a sequential read.
+    let mut etl = etl::Etl::default();
+    let mut summary = scrub::ScrubSummary::new();
+    let (code, constitution, authorities, courts, popular_names, acts, federal_code, documents) =
+        tables;
+    let code_rows = source.merge(code, &mut etl, &mut summary, report);
+    let constitution_rows = source.merge(constitution, &mut etl, &mut summary, report);
+    source.merge(authorities, &mut etl, &mut summary, report);
+    source.merge(courts, &mut etl, &mut summary, report);
+    source.merge(popular_names, &mut etl, &mut summary, report);
+    source.merge(acts, &mut etl, &mut summary, report);
+    source.merge(federal_code, &mut etl, &mut summary, report);
+    let mut document_rows = source.merge(documents, &mut etl, &mut summary, report);
+    if let Some(path) = &args.extra_documents {
+        let extra = source.clean_extra_documents(path)?;
+        let filenames: std::collections::HashSet<&str> =
+            document_rows.iter().map(|r| r.filename.as_str()).collect();
+        if let Some(row) = extra
+            .rows
+            .iter()
+            .find(|r| filenames.contains(r.filename.as_str()))
+        {
+            anyhow::bail!(
+                "--extra-documents filename {:?} is already in the input's documents",
+                row.filename
+            );
+        }
+        document_rows.extend(source.merge(extra, &mut etl, &mut summary, report));
+    }
+
+    if source.scrubber.is_some() {
+        let total: usize = summary.values().sum();
+        info!(redactions = total, "Scrubbed");
+        let mut by_rule: std::collections::BTreeMap<&str, usize> = Default::default();
+        for ((column, rule), count) in &summary {
+            info!(column = %column, rule = %rule, count, "Scrub redactions");
+            *by_rule.entry(rule.as_str()).or_default() += count;
+        }
+        report.count("scrub.redactions", total);
+        for (rule, count) in by_rule {
+            report.count(&format!("scrub.{}", rule), count);
+        }
+    }
 
-    println!(
-        "  ETL output: virginia_code={}, constitution={}, authorities={}, courts={}, popular_names={}, documents={}",
-        cleaned.virginia_code.height(),
-        cleaned.constitution.height(),
-        cleaned.authorities.height(),
-        cleaned.courts.height(),
-        cleaned.popular_names.height(),
-        cleaned.documents.height(),
+    let cleaned = etl.finish()?;
+
+    info!(
+        virginia_code = cleaned.virginia_code.height(),
+        constitution = cleaned.constitution.height(),
+        authorities = cleaned.authorities.height(),
+        courts = cleaned.courts.height(),
+        popular_names = cleaned.popular_names.height(),
+        acts = cleaned.acts.height(),
+        federal_code = cleaned.federal_code.height(),
+        documents = cleaned.documents.height(),
+        secs = etl_start.elapsed().as_secs_f64(),
+        "ETL done"
     );
-    println!("  ETL took:       {:.2}s", etl_start.elapsed().as_secs_f64());
+    report.count("etl.virginia_code", cleaned.virginia_code.height());
+    report.count("etl.constitution", cleaned.constitution.height());
+    report.count("etl.authorities", cleaned.authorities.height());
+    report.count("etl.courts", cleaned.courts.height());
+    report.count("etl.popular_names", cleaned.popular_names.height());
+    report.count("etl.acts", cleaned.acts.height());
+    report.count("etl.federal_code", cleaned.federal_code.height());
+    report.count("etl.documents", cleaned.documents.height());
+    report_row_errors(&cleaned.row_errors, args.max_row_error_rate, report)?;
+    let mut drop_counts: std::collections::BTreeMap<(&str, &str), usize> = Default::default();
+    for d in &cleaned.dropped {
+        *drop_counts.entry((d.table, d.reason.as_str())).or_default() += 1;
+    }
+    if !drop_counts.is_empty() {
+        for ((table, reason), count) in &drop_counts {
+            info!(table, reason, count, "Dropped rows");
+            report.count(&format!("dropped.{}.{}", table, reason), *count);
+        }
+    }
+    if !cleaned.html_limited.is_empty() {
+        warn!(fields = cleaned.html_limited.len(), "HTML limits hit");
+        let mut limit_counts: std::collections::BTreeMap<&str, usize> = Default::default();
+        for r in &cleaned.html_limited {
+            warn!(
+                table = r.table,
+                id = r.id,
+                field = %r.field,
+                limit = r.limit.as_str(),
+                bytes = r.bytes,
+                "HTML limit hit"
+            );
+            *limit_counts.entry(r.limit.as_str()).or_default() += 1;
+        }
+        for (limit, count) in limit_counts {
+            report.count(&format!("html_limit.{}", limit), count);
+        }
+    }
+    report.etl_quality = Some(cleaned.quality.clone());
+    report.duration("etl", etl_start);
 
-    let node_result = graph::nodes::build_nodes(&cleaned)?;
+    let mut node_result = graph::nodes::build_nodes(&cleaned, chunking)?;
 
     let synthetic_count = node_result.nodes.iter().filter(|n| n.synthetic).count();
     let embeddable_count = node_result.nodes.len() - synthetic_count;
 
-    println!(
-        "  Total nodes:    {} ({} embeddable, {} synthetic)",
-        node_result.nodes.len(),
-        embeddable_count,
-        synthetic_count
+    info!(
+        nodes = node_result.nodes.len(),
+        embeddable = embeddable_count,
+        synthetic = synthetic_count,
+        secs = pass1_start.elapsed().as_secs_f64(),
+        "Pass 1 done"
     );
-    println!("  Pass 1 took:    {:.2}s", pass1_start.elapsed().as_secs_f64());
-    println!();
+    report.count("nodes", node_result.nodes.len());
+    report.count("nodes.embeddable", embeddable_count);
+    report.count("nodes.synthetic", synthetic_count);
+    let mut type_counts: std::collections::BTreeMap<&str, usize> = Default::default();
+    for node in &node_result.nodes {
+        *type_counts.entry(node.node_type.as_str()).or_default() += 1;
+    }
+    for (node_type, count) in type_counts {
+        report.count(&format!("nodes.type.{}", node_type), count);
+    }
+    for status in [graph::nodes::NodeStatus::Repealed, graph::nodes::NodeStatus::Expired] {
+        let count = node_result.nodes.iter().filter(|n| n.status == status).count();
+        report.count(&format!("nodes.status.{}", status.as_str()), count);
+    }
+    report.duration("pass1", pass1_start);
+    monitor.pass_done("pass1", report);
+    quality.check("pass1", report)?;
+    drop(pass1);
 
     // ========== Pass 2: Extract — Build Edges ==========
-    println!("=== Pass 2: Building edges ===");
+    let pass2 = info_span!("pass2").entered();
+    info!("Building edges");
     let pass2_start = Instant::now();
 
-    let edges = graph::edges::build_edges(
+    let edge_result = graph::edges::build_edges(
         &node_result.nodes,
         &node_result.lookup,
         &code_rows,
@@ -276,64 +1238,265 @@ async fn main() -> Result<()> {
         &document_rows,
         &node_result.texts,
     );
+    let edges = edge_result.edges;
 
     // Count by type
     let mut cites_count = 0;
     let mut contains_count = 0;
     let mut references_count = 0;
+    let mut amends_count = 0;
+    let mut enacts_count = 0;
     for edge in &edges {
         match edge.rel_type.as_str() {
             "cites" => cites_count += 1,
             "contains" => contains_count += 1,
             "references" => references_count += 1,
+            "amends" => amends_count += 1,
+            "enacts" => enacts_count += 1,
             _ => {}
         }
     }
 
-    println!("  Total edges:    {}", edges.len());
-    println!("    contains:     {}", contains_count);
-    println!("    cites:        {}", cites_count);
-    println!("    references:   {}", references_count);
-    println!("  Pass 2 took:    {:.2}s", pass2_start.elapsed().as_secs_f64());
-    println!();
-
-    // Close input connection — we're done reading
-    drop(input_conn);
+    info!(
+        edges = edges.len(),
+        contains = contains_count,
+        cites = cites_count,
+        references = references_count,
+        amends = amends_count,
+        enacts = enacts_count,
+        secs = pass2_start.elapsed().as_secs_f64(),
+        "Pass 2 done"
+    );
+    report.count("edges", edges.len());
+    report.count("edges.contains", contains_count);
+    report.count("edges.cites", cites_count);
+    report.count("edges.references", references_count);
+    report.count("edges.amends", amends_count);
+    report.count("edges.enacts", enacts_count);
+    report.count("unresolved_citations", edge_result.unresolved.len());
+    let nodes_by_id: std::collections::HashMap<i64, &graph::nodes::Node> =
+        node_result.nodes.iter().map(|n| (n.id, n)).collect();
+    for citation in &edge_result.unresolved {
+        if let Some(node) = nodes_by_id.get(&citation.from_id) {
+            report.unresolved_sample(
+                format!("{} {}", node.source, node.source_id),
+                citation.section_ref.clone(),
+            );
+        }
+    }
+    report.duration("pass2", pass2_start);
+    monitor.pass_done("pass2", report);
+    quality.check("pass2", report)?;
+    drop(pass2);
+
+    // ========== --dry-run: statistics only, nothing written ==========
+    if args.dry_run {
+        let model = embed::models::find(&args.model)?;
+        info_span!("dry_run").in_scope(|| {
+            dry_run_summary(&node_result, model, max_seq_len, args.tokens_per_sec, report)
+        });
+        quality.finish(report);
+        info!(
+            secs = total_start.elapsed().as_secs_f64(),
+            "Dry run done; nothing written"
+        );
+        return Ok(());
+    }
 
     // ========== Write graph to output DB ==========
-    println!("=== Writing output database ===");
+    let write = info_span!("write").entered();
+    info!("Writing output database");
     let write_start = Instant::now();
 
+    let previous = match (&args.reuse_from, args.incremental) {
+        (Some(live), _) => live.exists().then(|| live.clone()),
+        (None, true) => db::incremental::stash_previous(&output_path)?,
+        (None, false) => None,
+    };
+    if args.incremental && previous.is_none() {
+        info!(output = %output_path.display(), "No previous build; building in full");
+    }
+
     let out_conn = db::writer::create_output_db(output_path.to_str().unwrap())?;
     let nodes_written = db::writer::write_nodes(&out_conn, &node_result.nodes)?;
-    let edges_written = db::writer::write_edges(&out_conn, &edges)?;
+    // Pruning works on mention counts; formulas apply to what is written.
+    let weighted = graph::weights::apply(&edges, &config.weights)?;
+    let edges_written = db::writer::write_edges(&out_conn, &weighted)?;
+    let provenance_written =
+        db::writer::write_edge_provenance(&out_conn, &edge_result.provenance)?;
+    db::writer::write_adjacency(&out_conn, &weighted)?;
     let chunk_meta_written = db::writer::write_chunk_meta(&out_conn, &node_result.chunk_meta)?;
-    println!(
-        "  Wrote {} nodes, {} edges, {} chunk_meta entries",
-        nodes_written, edges_written, chunk_meta_written
+    if args.title_embeddings {
+        let sources: std::collections::HashMap<i64, &str> = node_result
+            .nodes
+            .iter()
+            .map(|n| (n.id, n.source.as_str()))
+            .collect();
+        let headings: std::collections::HashMap<i64, String> = node_result
+            .headings
+            .iter()
+            .filter(|(id, _)| args.embeds_source(sources[id]))
+            .map(|(&id, heading)| (id, heading.clone()))
+            .collect();
+        let written = db::headings::write_headings(&out_conn, &headings)?;
+        info!(headings = written, "Wrote headings");
+        report.count("headings", written);
+    }
+    if args.fts {
+        let indexed = db::fts::write_fts(&out_conn, &node_result.texts)?;
+        info!(nodes = indexed, "Wrote full-text index");
+        report.count("fts", indexed);
+    }
+    db::incremental::write_node_hashes(&out_conn, &node_result.texts)?;
+    db::build_info::write_build_info(
+        &out_conn,
+        chrono::Utc::now(),
+        freshness.as_ref(),
+        report.input_sha256.as_deref(),
+        &report.sources,
+        node_result.chunking,
+    )?;
+    let dropped_written = db::writer::write_dropped_rows(&out_conn, &cleaned.dropped)?;
+    db::writer::write_row_errors(&out_conn, &cleaned.row_errors)?;
+    let hashes_written =
+        db::source_hashes::write_source_hashes(&out_conn, &cleaned.source_hashes)?;
+    report.count("source_hashes", hashes_written);
+    let history_written =
+        db::section_history::write_section_history(&out_conn, &cleaned.section_history)?;
+    report.count("section_history", history_written);
+    if args.raw_texts {
+        let written =
+            db::raw_texts::write_raw_texts(&out_conn, &node_result.nodes, &cleaned.raw_texts)?;
+        info!(rows = written, "Wrote raw texts");
+        report.count("raw_texts", written);
+    }
+    db::corpus_stats::write_document_datasets(
+        &out_conn,
+        document_rows
+            .iter()
+            .map(|r| (r.filename.as_str(), r.dataset.as_str())),
+    )?;
+    db::writer::write_html_limited_rows(&out_conn, &cleaned.html_limited)?;
+    let filters_written =
+        db::writer::write_document_section_filters(&out_conn, &edge_result.document_mentions)?;
+    info!(
+        nodes = nodes_written,
+        edges = edges_written,
+        edge_provenance = provenance_written,
+        chunk_meta = chunk_meta_written,
+        dropped_rows = dropped_written,
+        document_filters = filters_written,
+        "Wrote graph"
     );
+    if let Some(ref opts) = config.presentation {
+        let representatives = graph::prune::section_representatives(
+            node_result
+                .nodes
+                .iter()
+                .map(|n| (n.id, n.source.as_str(), n.source_id.as_str(), n.chunk_idx)),
+        );
+        let presentation = graph::weights::apply(
+            &graph::prune::prune_edges(&edges, &representatives, opts),
+            &config.weights,
+        )?;
+        let written = db::writer::write_presentation_edges(&out_conn, &presentation)?;
+        info!(presentation_edges = written, "Wrote presentation edges");
+        report.count("presentation_edges", written);
+    }
+    report.count("edge_provenance", provenance_written);
+    report.count("chunk_meta", chunk_meta_written);
+    report.count("document_filters", filters_written);
 
-    // Collect embeddable texts (used by both --prepare and Pass 3)
+    // Collect embeddable nodes (used by both --prepare and Pass 3)
     let mut embed_node_ids = Vec::new();
-    let mut embed_texts = Vec::new();
+    let mut lengths = Vec::new();
+    let mut empty_text_count = 0;
 
     for node in &node_result.nodes {
         if node.synthetic {
             continue;
         }
-        if let Some(text) = node_result.texts.get(&node.id) {
-            if !text.is_empty() {
+        match node_result.texts.get(&node.id) {
+            Some(text) if !text.is_empty() => {
                 embed_node_ids.push(node.id);
-                embed_texts.push(text.clone());
+                lengths.push(text.len());
             }
+            _ => empty_text_count += 1,
         }
     }
+    report.count("nodes.empty_text", empty_text_count);
+    report.count("texts", embed_node_ids.len());
+    quality.check("write", report)?;
+    report.histogram("text_length_chars", report::text_length_histogram(&lengths));
+
+    let mut reused_count = 0;
+    if let Some(ref prev) = previous {
+        let model = embed::models::find(&args.model)?;
+        let model_info =
+            db::writer::model_info(model, model.seq_len(args.max_seq_len)?, model.dims);
+        let reused = db::incremental::reuse_embeddings(&out_conn, prev, &model_info)?;
+        reused_count = reused.len();
+        embed_node_ids.retain(|id| !reused.contains(id));
+        info!(
+            reused = reused_count,
+            previous = %prev.display(),
+            changed = embed_node_ids.len(),
+            "Incremental: reusing embeddings for unchanged texts"
+        );
+        report.count("embeddings.reused", reused_count);
+    }
+
+    if !args.embed_only.is_empty() || !args.embed_skip.is_empty() {
+        let sources: std::collections::HashMap<i64, &str> = node_result
+            .nodes
+            .iter()
+            .map(|n| (n.id, n.source.as_str()))
+            .collect();
+        let before = embed_node_ids.len();
+        embed_node_ids.retain(|id| args.embeds_source(sources[id]));
+        info!(
+            selected = embed_node_ids.len(),
+            texts = before,
+            "Source filter: the rest stay unembedded"
+        );
+        report.count("texts.source_filtered", before - embed_node_ids.len());
+    }
+
+    if args.skip_repealed {
+        let repealed: std::collections::HashSet<i64> = node_result
+            .nodes
+            .iter()
+            .filter(|n| n.status == graph::nodes::NodeStatus::Repealed)
+            .map(|n| n.id)
+            .collect();
+        let before = embed_node_ids.len();
+        embed_node_ids.retain(|id| !repealed.contains(id));
+        info!(
+            skipped = before - embed_node_ids.len(),
+            "--skip-repealed: repealed sections stay unembedded"
+        );
+        report.count("texts.repealed_skipped", before - embed_node_ids.len());
+    }
+
+    // Over --max-memory, park the node texts on disk for Pass 3 to read back
+    // a window at a time
+    let mut spill = None;
+    if args.prepare.is_none() && !args.skip_embeddings {
+        spill_if_over_budget(&monitor, &mut node_result.texts, &mut spill, &output_path, report)?;
+    }
+    monitor.pass_done("write", report);
+
+    drop(write);
 
     // ========== --prepare: write Parquet and exit ==========
     if let Some(ref parquet_path) = args.prepare {
-        println!("\n=== Writing Parquet ===");
+        let prepare = info_span!("prepare").entered();
         let parquet_start = Instant::now();
 
+        let embed_texts: Vec<String> = embed_node_ids
+            .iter()
+            .map(|id| node_result.texts[id].clone())
+            .collect();
         let id_series = Column::new("node_id".into(), &embed_node_ids);
         let text_series = Column::new("text".into(), &embed_texts);
         let mut df = DataFrame::new(vec![id_series, text_series])?;
@@ -341,143 +1504,924 @@ async fn main() -> Result<()> {
         let file = std::fs::File::create(parquet_path)?;
         ParquetWriter::new(file).finish(&mut df)?;
 
-        println!(
-            "  Wrote {} rows to {}",
-            embed_node_ids.len(),
-            parquet_path.display()
-        );
-        println!(
-            "  Parquet write took: {:.2}s",
-            parquet_start.elapsed().as_secs_f64()
-        );
-        println!("\n  Skipping embeddings (--prepare)");
-        println!(
-            "  Write took:     {:.2}s",
-            write_start.elapsed().as_secs_f64()
+        info!(
+            rows = embed_node_ids.len(),
+            parquet = %parquet_path.display(),
+            secs = parquet_start.elapsed().as_secs_f64(),
+            "Wrote Parquet; skipping embeddings"
         );
-        println!();
-        println!(
-            "=== Done in {:.2}s ===",
-            total_start.elapsed().as_secs_f64()
+        materialize_views(&out_conn, report)?;
+        quality.finish(report);
+        drop(out_conn);
+        report.set_output(&output_path)?;
+        drop(prepare);
+        publish_if_requested(args.publish.as_deref(), &[&output_path, parquet_path]).await?;
+
+        info!(
+            secs = total_start.elapsed().as_secs_f64(),
+            write_secs = write_start.elapsed().as_secs_f64(),
+            output = %output_path.display(),
+            parquet = %parquet_path.display(),
+            "Done"
         );
-        println!("Output: {}", output_path.display());
-        println!("Parquet: {}", parquet_path.display());
         return Ok(());
     }
 
     // ========== Pass 3: Embed — Compute Vectors ==========
     if args.skip_embeddings {
-        println!("\n  Skipping embeddings (--skip-embeddings)");
+        info!("Skipping embeddings (--skip-embeddings)");
+        if reused_count > 0 {
+            report.count("embeddings", reused_count);
+        }
+    } else if embed_node_ids.is_empty() && previous.is_some() {
+        info!("No new or changed texts; nothing to embed");
+        report.count("embeddings", reused_count);
     } else {
-        run_embedding(&out_conn, &jsonl_path, &embed_node_ids, &embed_texts, args.batch_size).await?;
+        let pass3_start = Instant::now();
+        let texts = match &spill {
+            Some(spill) => EmbedTexts::Spilled(spill),
+            None => EmbedTexts::Map(&node_result.texts),
+        };
+        let embedded = run_embedding(
+            &out_conn,
+            &jsonl_path,
+            &embed_node_ids,
+            texts,
+            args,
+            false,
+            report,
+        )
+        .await?;
+        report.count("embeddings", reused_count + embedded);
+        report.duration("pass3", pass3_start);
+        monitor.pass_done("pass3", report);
+        quality.check("pass3", report)?;
+    }
+    if args.title_embeddings && !args.skip_embeddings {
+        run_title_embeddings(&out_conn, args, report).await?;
+    }
+    if let Some(ref summaries) = config.summaries {
+        // Pass 3 may have grown the process past --max-memory since
+        spill_if_over_budget(&monitor, &mut node_result.texts, &mut spill, &output_path, report)?;
+        let texts = match &spill {
+            Some(spill) => summarize::Texts::Spilled(spill),
+            None => summarize::Texts::Map(&node_result.texts),
+        };
+        run_summaries(&out_conn, summaries, &output_path, texts, args, report).await?;
+    }
+    materialize_views(&out_conn, report)?;
+    quality.finish(report);
+
+    info!(secs = write_start.elapsed().as_secs_f64(), "Write done");
+    report.duration("write", write_start);
+    drop(out_conn);
+    if let (Some(prev), None) = (&previous, &args.reuse_from) {
+        db::incremental::discard_previous(prev)?;
+    }
+    report.set_output(&output_path)?;
+
+    if args.skip_embeddings {
+        publish_if_requested(args.publish.as_deref(), &[&output_path]).await?;
+    } else {
+        publish_if_requested(args.publish.as_deref(), &[&output_path, &jsonl_path]).await?;
+    }
+
+    info!(
+        secs = total_start.elapsed().as_secs_f64(),
+        output = %output_path.display(),
+        jsonl = (!args.skip_embeddings).then(|| jsonl_path.display().to_string()),
+        "Done"
+    );
+
+    Ok(())
+}
+
+/// Log what Pass 3 would do: embeddable texts, chunking, and an estimated
+/// embedding time from approximate token counts (the model is not loaded).
+fn dry_run_summary(
+    node_result: &graph::nodes::NodeBuildResult,
+    model: &embed::ModelSpec,
+    max_seq_len: usize,
+    tokens_per_sec: f64,
+    report: &mut report::BuildReport,
+) {
+    let prefix_tokens = text::chunker::approx_token_count(&model.format_document(""));
+    let mut tokens: Vec<usize> = node_result
+        .nodes
+        .iter()
+        .filter(|n| !n.synthetic)
+        .filter_map(|n| node_result.texts.get(&n.id))
+        .filter(|t| !t.is_empty())
+        .map(|t| text::chunker::approx_token_count(t) + prefix_tokens)
+        .collect();
+    tokens.sort_unstable();
+    let total: usize = tokens.iter().sum();
+    let estimate = (total as f64 / tokens_per_sec.max(1.0)).round() as u64;
+
+    info!(texts = tokens.len(), "Embeddable texts");
+    info!(
+        chunked_nodes = node_result.chunk_meta.len(),
+        chunk_tokens = node_result.chunking.max_tokens,
+        chunk_overlap = node_result.chunking.overlap_tokens,
+        "Chunking"
+    );
+    if let (Some(min), Some(max)) = (tokens.first(), tokens.last()) {
+        info!(
+            total,
+            min,
+            median = tokens[tokens.len() / 2],
+            mean = (total as f64 / tokens.len() as f64).round(),
+            max,
+            "Tokens (approx)"
+        );
+    }
+    let over = tokens.iter().filter(|&&t| t > max_seq_len).count();
+    info!(texts = over, max_seq_len, "Over max_seq_len (truncated)");
+    info!(
+        secs = estimate,
+        tokens_per_sec,
+        "Estimated Pass 3: {}h {:02}m {:02}s",
+        estimate / 3600,
+        estimate / 60 % 60,
+        estimate % 60
+    );
+
+    report.count("texts", tokens.len());
+    report.count("chunk_meta", node_result.chunk_meta.len());
+    report.count("texts.over_max_seq_len", over);
+    report.count("tokens.estimated", total);
+}
+
+/// `graph.sqlite.db` → `graph.sqlite.jsonl`.
+fn default_jsonl_path(output_path: &Path) -> PathBuf {
+    let mut s = output_path.to_str().unwrap().to_string();
+    if let Some(pos) = s.rfind('.') {
+        s.truncate(pos);
+    }
+    PathBuf::from(format!("{}.jsonl", s))
+}
+
+/// Roll the chunk-level graph up into `section_nodes` / `section_edges`, rank
+/// sections into `section_leaderboards`, and recompute `corpus_stats` over
+/// the finished graph and embeddings.
+fn materialize_views(conn: &Connection, report: &mut report::BuildReport) -> Result<()> {
+    let start = Instant::now();
+    let counts = db::sections::materialize_sections(conn)?;
+    info!(
+        section_nodes = counts.nodes,
+        with_embeddings = counts.embeddings,
+        section_edges = counts.edges,
+        secs = start.elapsed().as_secs_f64(),
+        "Materialized section view"
+    );
+    report.count("section_nodes", counts.nodes);
+    report.count("section_edges", counts.edges);
+    report.duration("sections", start);
+
+    let start = Instant::now();
+    let boards = db::leaderboards::materialize_leaderboards(conn)?;
+    info!(
+        orphans = boards.orphans.len(),
+        secs = start.elapsed().as_secs_f64(),
+        "Ranked sections by citations"
+    );
+    report.leaderboards(&boards.most_cited, &boards.orphans);
+    report.duration("leaderboards", start);
+
+    let start = Instant::now();
+    let stats = db::corpus_stats::refresh(conn)?;
+    info!(
+        titles = stats.sections_per_title.len(),
+        datasets = stats.documents_per_dataset.len(),
+        secs = start.elapsed().as_secs_f64(),
+        "Computed corpus stats"
+    );
+    report.duration("corpus_stats", start);
+    Ok(())
+}
+
+/// Pass 1's view of the input DB: each table is found through `sources`,
+/// streamed in batches of `batch_rows`, sampled and scrubbed, then handed to
+/// the caller.
+struct SourceReader<'a> {
+    input: &'a Path,
+    batch_rows: usize,
+    sources: &'a db::reader::SourceMapping,
+    sampling: sample::Sampling,
+    scrubber: Option<scrub::Scrubber>,
+    /// Keep each row's scrubbed text for `raw_texts`.
+    raw_texts: bool,
+    /// Per-title cleanup and dedup policy for code sections.
+    etl_options: etl::EtlOptions,
+    governor: memory::Governor<'a>,
+    /// Pass 1's span, entered by the table threads so their logs nest under it.
+    span: tracing::Span,
+}
+
+/// One table as [`SourceReader::clean`] left it: its cleaned batches, the
+/// rows kept for Pass 2, and what to report.
+struct CleanedTable<T> {
+    table: &'static str,
+    etl: etl::Etl,
+    rows: Vec<T>,
+    read: usize,
+    kept: usize,
+    summary: scrub::ScrubSummary,
+}
+
+impl SourceReader<'_> {
+    /// Stream `T`'s table through sampling, scrubbing and `clean`, keeping
+    /// whatever `keep` returns of each row.
+    fn clean<T>(
+        &self,
+        clean: impl Fn(&mut etl::Etl, &[T]) -> Result<()>,
+        mut keep: impl FnMut(T) -> Option<T>,
+    ) -> Result<CleanedTable<T>>
+    where
+        T: db::reader::SourceRow + db::source_hashes::SourceKey + scrub::Scrubbable,
+    {
+        let _span = self.span.enter();
+        let mut out = CleanedTable::new(
+            <T as db::reader::SourceRow>::TABLE,
+            etl::Etl::new(self.etl_options.clone()),
+        );
+        let mut rows = db::reader::stream::<T>(self.input, self.batch_rows, self.sources);
+        for batch in &mut rows {
+            let batch = batch?;
+            let _turn = self.governor.admit();
+            self.feed(&mut out, batch, &clean, &mut keep)?;
+        }
+        out.etl.row_errors(rows.take_errors());
+        Ok(out)
+    }
+
+    /// `--extra-documents`, sampled and scrubbed like a source table and
+    /// reported as `extra_documents`.
+    fn clean_extra_documents(&self, path: &Path) -> Result<CleanedTable<db::reader::DocumentRow>> {
+        let mut out = CleanedTable::new("extra_documents", etl::Etl::new(self.etl_options.clone()));
+        let batch = db::extra_documents::read_extra_documents(path)?;
+        self.feed(&mut out, batch, &etl::Etl::documents, &mut Some)?;
+        Ok(out)
+    }
+
+    fn feed<T>(
+        &self,
+        out: &mut CleanedTable<T>,
+        mut batch: Vec<T>,
+        clean: &impl Fn(&mut etl::Etl, &[T]) -> Result<()>,
+        keep: &mut impl FnMut(T) -> Option<T>,
+    ) -> Result<()>
+    where
+        T: db::reader::SourceRow + db::source_hashes::SourceKey + scrub::Scrubbable,
+    {
+        out.read += batch.len();
+        self.sampling.apply_batch(
+            out.table,
+            &mut batch,
+            &mut out.kept,
+            db::reader::SourceRow::id,
+        );
+        out.etl.source_hashes(db::source_hashes::hash_rows(&batch));
+        if let Some(scrubber) = &self.scrubber {
+            scrubber.scrub(&mut batch, &mut out.summary);
+        }
+        if self.raw_texts {
+            out.etl.raw_texts(db::raw_texts::raw_texts(&batch));
+        }
+        clean(&mut out.etl, &batch)?;
+        out.rows.extend(batch.into_iter().filter_map(keep));
+        Ok(())
+    }
+
+    /// Log and report a cleaned table's row counts and fold it into `etl` and
+    /// `summary`, returning the rows it kept.
+    fn merge<T>(
+        &self,
+        table: CleanedTable<T>,
+        etl: &mut etl::Etl,
+        summary: &mut scrub::ScrubSummary,
+        report: &mut report::BuildReport,
+    ) -> Vec<T> {
+        let name = table.table;
+        info!(table = name, rows = table.read, "Read rows");
+        report.count(&format!("rows.{}", name), table.read);
+        if self.sampling.is_active() {
+            info!(
+                table = name,
+                rows = table.kept,
+                seed = self.sampling.seed,
+                "Sampled rows"
+            );
+            report.count(&format!("sampled.{}", name), table.kept);
+        }
+        for (key, count) in table.summary {
+            *summary.entry(key).or_default() += count;
+        }
+        etl.merge(table.etl);
+        table.rows
+    }
+}
+
+impl<T> CleanedTable<T> {
+    fn new(table: &'static str, etl: etl::Etl) -> Self {
+        Self {
+            table,
+            etl,
+            rows: Vec::new(),
+            read: 0,
+            kept: 0,
+            summary: scrub::ScrubSummary::new(),
+        }
+    }
+}
+
+/// Log and count the source rows that couldn't be read, by table, and fail
+/// when a table's share of them is over `max_rate`.
+fn report_row_errors(
+    errors: &[db::reader::RowError],
+    max_rate: Option<f64>,
+    report: &mut report::BuildReport,
+) -> Result<()> {
+    let mut by_table: std::collections::BTreeMap<&str, Vec<&db::reader::RowError>> =
+        Default::default();
+    for e in errors {
+        by_table.entry(e.table).or_default().push(e);
+    }
+    report.count("row_errors", errors.len());
+    for (table, errors) in by_table {
+        let read = report.counts.get(&format!("rows.{}", table)).copied();
+        let total = read.unwrap_or(0) + errors.len();
+        warn!(
+            table,
+            rows = errors.len(),
+            of = total,
+            id = ?errors[0].id,
+            error = %errors[0].message,
+            "Skipped unreadable rows"
+        );
+        report.count(&format!("row_errors.{}", table), errors.len());
+        let rate = errors.len() as f64 / total as f64;
+        if let Some(max) = max_rate.filter(|&max| rate > max) {
+            anyhow::bail!(
+                "{} of {} {} rows are unreadable ({:.2}%), over --max-row-error-rate {}",
+                errors.len(),
+                total,
+                table,
+                rate * 100.0,
+                max
+            );
+        }
     }
+    Ok(())
+}
+
+/// A scoped thread's result, re-raising its panic on the caller.
+fn joined<T>(handle: std::thread::ScopedJoinHandle<'_, Result<T>>) -> Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
-    println!(
-        "  Write took:     {:.2}s",
-        write_start.elapsed().as_secs_f64()
+/// Upload finished artifacts when `--publish` is set. The output connection must
+/// already be closed so the WAL has been checkpointed into the main DB file.
+#[tracing::instrument(name = "publish", skip_all)]
+async fn publish_if_requested(target: Option<&str>, artifacts: &[&Path]) -> Result<()> {
+    let Some(target) = target else {
+        return Ok(());
+    };
+    let manifest = publish::publish_artifacts(target, artifacts).await?;
+    info!(
+        build_id = %manifest.build_id,
+        artifacts = manifest.artifacts.len(),
+        target,
+        "Published artifacts"
     );
-    println!();
+    Ok(())
+}
+
+/// `embed_priority` tier of every node in the output DB.
+fn node_priorities(conn: &rusqlite::Connection) -> Result<std::collections::HashMap<i64, u8>> {
+    let mut stmt = conn.prepare("SELECT id, node_type FROM nodes")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+    let mut priority = std::collections::HashMap::new();
+    for row in rows {
+        let (id, node_type) = row?;
+        priority.insert(id, graph::nodes::embed_priority(&node_type));
+    }
+    Ok(priority)
+}
 
-    println!(
-        "=== Done in {:.2}s ===",
-        total_start.elapsed().as_secs_f64()
+/// The `[summaries]` stage, then embedding the summaries if asked to (and
+/// embeddings aren't skipped).
+#[tracing::instrument(name = "summaries", skip_all)]
+async fn run_summaries(
+    out_conn: &Connection,
+    config: &summarize::SummaryConfig,
+    output_path: &Path,
+    texts: summarize::Texts<'_>,
+    args: &BuildArgs,
+    report: &mut report::BuildReport,
+) -> Result<()> {
+    let start = Instant::now();
+    let counts = summarize::run(out_conn, config, &config.cache_path(output_path), texts).await?;
+    info!(
+        summaries = counts.summaries,
+        generated = counts.generated,
+        cached = counts.cached,
+        failed = counts.failed,
+        "Summaries done"
     );
-    println!("Output:  {}", output_path.display());
-    if !args.skip_embeddings {
-        println!("JSONL:   {}", jsonl_path.display());
+    report.count("summaries", counts.summaries);
+    report.count("summaries.generated", counts.generated);
+    report.count("summaries.cached", counts.cached);
+    report.count("summaries.failed", counts.failed);
+    if config.embed && args.skip_embeddings {
+        info!("Skipping summary embeddings (--skip-embeddings)");
+    } else if config.embed {
+        let mut embedder = start_embedder(args, embedding_chain(args)?.as_ref()).await?;
+        let embedded = summarize::embed_summaries(out_conn, &mut embedder).await?;
+        info!(summary_embeddings = embedded, "Embedded summaries");
+        report.count("summary_embeddings", embedded);
     }
+    report.duration("summaries", start);
+    Ok(())
+}
 
+/// Embed the headings `--title-embeddings` wrote into `title_embeddings`.
+#[tracing::instrument(name = "title_embeddings", skip_all)]
+async fn run_title_embeddings(
+    out_conn: &Connection,
+    args: &BuildArgs,
+    report: &mut report::BuildReport,
+) -> Result<()> {
+    let start = Instant::now();
+    let (ids, texts) = db::headings::unembedded(out_conn)?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut embedder = start_embedder(args, embedding_chain(args)?.as_ref()).await?;
+    let outcome = embedder
+        .embed_batched(&ids, &texts, |ids, vectors, _| {
+            db::headings::write_title_embeddings_batch(out_conn, ids, vectors)
+        })
+        .await?;
+    if !outcome.failed.is_empty() {
+        warn!(
+            headings = outcome.failed.len(),
+            "Headings could not be embedded; search uses their full-text score alone"
+        );
+    }
+    info!(title_embeddings = outcome.written, "Embedded headings");
+    report.count("title_embeddings", outcome.written);
+    report.duration("title_embeddings", start);
     Ok(())
 }
 
+/// The `[embedding]` backend chain of `--config`, if it has one.
+fn embedding_chain(args: &BuildArgs) -> Result<Option<embed::ChainConfig>> {
+    match args.config {
+        Some(ref path) => Ok(config::load(path)?.embedding),
+        None => Ok(None),
+    }
+}
+
+/// Start the `--model` embedder on `chain`, else on `--device`.
+async fn start_embedder(
+    args: &BuildArgs,
+    chain: Option<&embed::ChainConfig>,
+) -> Result<embed::Embedder> {
+    let model = embed::models::find(&args.model)?;
+    let embedder = match chain {
+        Some(chain) => {
+            embed::Embedder::with_chain(model, chain, args.batch_size, args.max_seq_len).await?
+        }
+        None => embed::Embedder::new(model, args.device, args.batch_size, args.max_seq_len).await?,
+    };
+    info!(backend = embedder.backend(), "Embedding backend ready");
+    Ok(embedder.with_packing(args.pack_batches))
+}
+
+/// Batches of spilled texts read back per window in Pass 3.
+const SPILL_WINDOW_BATCHES: usize = 16;
+
+/// Over `--max-memory`, move `texts` into a spill file beside the output,
+/// unless they are already spilled.
+fn spill_if_over_budget(
+    monitor: &memory::Monitor,
+    texts: &mut std::collections::HashMap<i64, String>,
+    spill: &mut Option<db::spill::TextSpill>,
+    output_path: &Path,
+    report: &mut report::BuildReport,
+) -> Result<()> {
+    let Some(rss) = monitor.over_budget().filter(|_| spill.is_none()) else {
+        return Ok(());
+    };
+    let dir = output_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    info!(
+        rss_mb = rss >> 20,
+        texts = texts.len(),
+        "Over --max-memory; spilling node texts to disk"
+    );
+    let created = db::spill::TextSpill::create(dir, std::mem::take(texts))?;
+    report.count("memory.spilled_texts", created.len());
+    *spill = Some(created);
+    Ok(())
+}
+
+/// Where Pass 3 reads the texts it embeds.
+#[derive(Clone, Copy)]
+enum EmbedTexts<'a> {
+    /// Aligned with the node ids passed alongside.
+    List(&'a [String]),
+    /// Keyed by node id.
+    Map(&'a std::collections::HashMap<i64, String>),
+    /// Parked on disk by `--max-memory`.
+    Spilled(&'a db::spill::TextSpill),
+}
+
+impl EmbedTexts<'_> {
+    /// Byte length of each text of `ids`.
+    fn lengths(self, ids: &[i64]) -> Result<Vec<usize>> {
+        match self {
+            EmbedTexts::List(texts) => Ok(texts.iter().map(|t| t.len()).collect()),
+            EmbedTexts::Map(texts) => Ok(ids.iter().map(|id| texts[id].len()).collect()),
+            EmbedTexts::Spilled(spill) => spill.lengths(ids),
+        }
+    }
+
+    /// The texts at `positions` in `ids`.
+    fn fetch(self, ids: &[i64], positions: &[usize]) -> Result<Vec<String>> {
+        match self {
+            EmbedTexts::List(texts) => Ok(positions.iter().map(|&i| texts[i].clone()).collect()),
+            EmbedTexts::Map(texts) => Ok(positions.iter().map(|&i| texts[&ids[i]].clone()).collect()),
+            EmbedTexts::Spilled(spill) => {
+                spill.get(&positions.iter().map(|&i| ids[i]).collect::<Vec<_>>())
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "pass3", skip_all, fields(texts = embed_node_ids.len()))]
 async fn run_embedding(
     out_conn: &Connection,
     jsonl_path: &std::path::Path,
     embed_node_ids: &[i64],
-    embed_texts: &[String],
-    batch_size: usize,
-) -> Result<()> {
-    println!("\n=== Pass 3: Computing embeddings ===");
+    texts: EmbedTexts<'_>,
+    args: &BuildArgs,
+    append_jsonl: bool,
+    report: &mut report::BuildReport,
+) -> Result<usize> {
+    // --embed-only / --embed-skip can leave nothing, e.g. a source without rows
+    if embed_node_ids.is_empty() {
+        info!("No texts to embed");
+        return Ok(0);
+    }
+    info!("Computing embeddings");
     let pass3_start = Instant::now();
 
-    let mut embedder = embed::Embedder::new(batch_size).await?;
+    let batch_timeout = (args.batch_timeout > 0).then(|| std::time::Duration::from_secs(args.batch_timeout));
+    let chain = embedding_chain(args)?;
+    let mut embedder = start_embedder(args, chain.as_ref())
+        .await?
+        .with_batch_timeout(batch_timeout)
+        .with_pacing((args.cooldown > 0).then(|| std::time::Duration::from_secs(args.cooldown)))
+        .with_deadline(args.max_duration.map(|d| report.started() + d));
+    let progress = args.progress_file.as_deref().map(|path| {
+        let file = progress::ProgressFile::new(path, embed_node_ids.len());
+        std::sync::Arc::new(std::sync::Mutex::new(file))
+    });
+    if let Some(progress) = &progress {
+        let progress = progress.clone();
+        embedder = embedder.with_observer(move |stats| progress.lock().unwrap().batch(stats));
+    }
     let dims = embedder.model_dimensions();
 
-    db::writer::write_model_info(out_conn, "onnx-community/embeddinggemma-300m-ONNX", dims)?;
+    db::writer::write_model_info(out_conn, embedder.model(), embedder.max_length(), dims)?;
 
-    println!("  Embedding {} texts...", embed_texts.len());
+    // Fresh runs start a new work list; a resumed run keeps the one it is finishing
+    if !append_jsonl {
+        db::writer::mark_pending(out_conn, embed_node_ids)?;
+    }
 
-    // Create JSONL file
-    let jsonl_file = std::fs::File::create(jsonl_path)?;
+    // Create (or, when resuming, extend) the JSONL file
+    let jsonl_file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append_jsonl)
+        .truncate(!append_jsonl)
+        .open(jsonl_path)?;
     let mut writer = std::io::BufWriter::new(jsonl_file);
 
-    // Sort texts by length (proxy for token count) so similar-length texts
-    // are grouped together — gives more predictable batch timing and better
-    // progress estimates.
-    let mut order: Vec<usize> = (0..embed_texts.len()).collect();
-    order.sort_by_key(|&i| embed_texts[i].len());
+    // High-value nodes first (see graph::nodes::embed_priority), so a run cut
+    // short still leaves a usable DB. Within a tier, sort by length (proxy
+    // for token count) so similar-length texts are grouped together — gives
+    // more predictable batch timing and better progress estimates.
+    let priority = node_priorities(out_conn)?;
+    let text_lengths = texts.lengths(embed_node_ids)?;
+    let mut order: Vec<usize> = (0..embed_node_ids.len()).collect();
+    order.sort_by_key(|&i| (priority.get(&embed_node_ids[i]).copied().unwrap_or(1), text_lengths[i]));
 
     let sorted_ids: Vec<i64> = order.iter().map(|&i| embed_node_ids[i]).collect();
-    let sorted_texts: Vec<String> = order.iter().map(|&i| embed_texts[i].clone()).collect();
 
     // Report text-length distribution
     {
-        let lengths: Vec<usize> = sorted_texts.iter().map(|t| t.len()).collect();
+        let lengths: Vec<usize> = order.iter().map(|&i| text_lengths[i]).collect();
         let total_chars: usize = lengths.iter().sum();
         let min_len = lengths.first().copied().unwrap_or(0);
         let max_len = lengths.last().copied().unwrap_or(0);
         let median_len = lengths[lengths.len() / 2];
         let avg_len = total_chars as f64 / lengths.len() as f64;
 
-        let buckets = [
-            (0, 100, "< 100"),
-            (100, 500, "100-500"),
-            (500, 1000, "500-1k"),
-            (1000, 2000, "1k-2k"),
-            (2000, usize::MAX, "2k+"),
-        ];
-        let mut counts = vec![0usize; buckets.len()];
-        for &l in &lengths {
-            for (i, &(lo, hi, _)) in buckets.iter().enumerate() {
-                if l >= lo && l < hi {
-                    counts[i] += 1;
-                    break;
+        let buckets = report::text_length_histogram(&lengths);
+        let bucket_str: Vec<String> = buckets
+            .iter()
+            .filter(|b| b.count > 0)
+            .map(|b| format!("{}={}", b.label, b.count))
+            .collect();
+        info!(
+            min = min_len,
+            median = median_len,
+            mean = avg_len.round(),
+            max = max_len,
+            buckets = %bucket_str.join(", "),
+            "Text length distribution (chars)"
+        );
+    }
+
+    let mut batch_start = Instant::now();
+    let mut by_backend: std::collections::BTreeMap<String, usize> = Default::default();
+    let mut write_batch = |ids: &[i64], vecs: &[Vec<f32>], backend: &str| {
+        report.batch(ids.len(), batch_start.elapsed().as_secs_f64());
+        batch_start = Instant::now();
+        // JSONL first, then the DB batch that marks these nodes done: a
+        // crash in between only means the batch is re-embedded on resume.
+        db::writer::write_embeddings_jsonl_batch(&mut writer, ids, vecs)?;
+        std::io::Write::flush(&mut writer)?;
+        if chain.is_some() {
+            db::writer::write_embedding_backends(out_conn, ids, backend)?;
+            *by_backend.entry(backend.to_string()).or_default() += ids.len();
+        }
+        db::writer::write_embeddings_batch(out_conn, ids, vecs)
+    };
+    // From here on, Ctrl-C stops after the in-flight batch instead of killing
+    // the process partway through a JSONL write
+    let interrupt = interrupt::Interrupt::arm();
+    embedder = embedder.with_stop(interrupt.flag());
+    // Spilled texts come back from disk a window at a time; otherwise the
+    // whole sorted list is one window.
+    let window = match texts {
+        EmbedTexts::Spilled(_) => args.batch_size.max(1) * SPILL_WINDOW_BATCHES,
+        _ => order.len().max(1),
+    };
+    let mut outcome = embed::BatchOutcome::default();
+    let mut attempted = 0;
+    let mut truncated = 0;
+    let mut repaired = 0;
+    for (positions, ids) in order.chunks(window).zip(sorted_ids.chunks(window)) {
+        let mut window_texts = texts.fetch(embed_node_ids, positions)?;
+        let mut ids = ids.to_vec();
+        // Texts the tokenizer would cut off; a remote backend can't be checked
+        let mut over = Vec::new();
+        if let Some(counts) = embedder.token_counts(&window_texts)? {
+            let max_length = embedder.max_length();
+            for (i, &tokens) in counts.iter().enumerate().filter(|(_, &n)| n > max_length) {
+                warn!(
+                    node_id = ids[i],
+                    tokens,
+                    max_seq_len = max_length,
+                    "Text truncated to max_seq_len"
+                );
+                over.push((i, tokens));
+            }
+            let over_ids: Vec<i64> = over.iter().map(|&(i, _)| ids[i]).collect();
+            db::writer::mark_truncated(out_conn, &over_ids)?;
+            truncated += over.len();
+        }
+        // With --repair-truncated, those are embedded whole, after the batches
+        let mut repairs = Vec::new();
+        if args.repair_truncated && !over.is_empty() {
+            for &(i, tokens) in over.iter().rev() {
+                repairs.push((ids.remove(i), window_texts.remove(i), tokens));
+            }
+        }
+        let mut part = embedder
+            .embed_batched(&ids, &window_texts, &mut write_batch)
+            .await?;
+        if !part.interrupted && part.deferred == 0 {
+            for (id, text, tokens) in repairs.into_iter().rev() {
+                match embedder.embed_whole(&text, tokens).await {
+                    Ok((vector, pieces)) => {
+                        info!(node_id = id, tokens, pieces, "Repaired truncated text");
+                        write_batch(&[id], &[vector], embedder.backend())?;
+                        part.written += 1;
+                        repaired += 1;
+                    }
+                    Err(e) => part.failed.push((id, format!("{e:#}"))),
                 }
             }
+        } else {
+            part.deferred += repairs.len();
+        }
+        attempted += positions.len();
+        outcome.written += part.written;
+        outcome.failed.extend(part.failed);
+        outcome.hung += part.hung;
+        outcome.cooldowns += part.cooldowns;
+        outcome.switches += part.switches;
+        outcome.deferred += part.deferred;
+        outcome.interrupted |= part.interrupted;
+        if part.interrupted || part.deferred > 0 {
+            outcome.deferred += sorted_ids.len() - attempted;
+            break;
+        }
+    }
+    let mut embeds_written = outcome.written;
+    let mut hung = outcome.hung;
+    let mut cooldowns = outcome.cooldowns;
+    let mut switches = outcome.switches;
+    let mut interrupted = outcome.interrupted;
+    db::writer::sync_embedding_failures(out_conn, &outcome.failed)?;
+
+    // Failed batches are retried once the rest is done, one text at a time
+    // (unless the time budget is already spent or the run was interrupted)
+    let mut failed = outcome.failed.len();
+    if failed > 0 && outcome.deferred == 0 {
+        info!(texts = failed, "Retrying texts from failed batches one at a time");
+        let position: std::collections::HashMap<i64, usize> =
+            embed_node_ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let retry_ids: Vec<i64> = outcome.failed.iter().map(|(id, _)| *id).collect();
+        let retry_positions: Vec<usize> = retry_ids.iter().map(|id| position[id]).collect();
+        let retry_texts = texts.fetch(embed_node_ids, &retry_positions)?;
+        let retry = embedder
+            .retry_singly(&retry_ids, &retry_texts, &mut write_batch)
+            .await?;
+        embeds_written += retry.written;
+        hung += retry.hung;
+        cooldowns += retry.cooldowns;
+        switches += retry.switches;
+        interrupted |= retry.interrupted;
+        failed = db::writer::sync_embedding_failures(out_conn, &retry.failed)?;
+        report.count("embeddings.retried", retry_ids.len());
+    }
+    report.count("embeddings.failed", failed);
+    report.count("embeddings.truncated", truncated);
+    if args.repair_truncated {
+        report.count("embeddings.repaired", repaired);
+    }
+    if truncated > repaired {
+        warn!(
+            texts = truncated - repaired,
+            "Texts were truncated to max_seq_len (see nodes.truncated); --repair-truncated embeds them whole"
+        );
+    }
+    report.count("embeddings.hung_batches", hung);
+    report.count("embeddings.cooldowns", cooldowns);
+    report.count("embeddings.deferred", outcome.deferred);
+    if chain.is_some() {
+        report.count("embeddings.backend_switches", switches);
+        for (backend, count) in &by_backend {
+            report.count(&format!("embeddings.by_backend.{}", backend), *count);
+        }
+    }
+    drop(interrupt);
+    std::io::Write::flush(&mut writer)?;
+    if let Some(progress) = &progress {
+        let state = if interrupted {
+            "interrupted"
+        } else if outcome.deferred > 0 {
+            "deferred"
+        } else {
+            "done"
+        };
+        progress.lock().unwrap().finish(state);
+    }
+    if interrupted {
+        db::resume::mark_interrupted(out_conn)?;
+        return Err(interrupt::Interrupted {
+            embedded: embeds_written,
         }
+        .into());
+    }
+    if failed == 0 && outcome.deferred == 0 {
+        db::resume::clear_interrupted(out_conn)?;
+    }
+    info!(
+        embeddings = embeds_written,
+        jsonl = %jsonl_path.display(),
+        "Wrote embeddings to JSONL and the database"
+    );
+    if failed > 0 {
+        warn!(
+            texts = failed,
+            "Texts could not be embedded (see embedding_failures); they stay pending for --resume"
+        );
+    }
+    if outcome.deferred > 0 {
+        info!(
+            texts = outcome.deferred,
+            "Time budget reached; texts left pending, finish with --resume"
+        );
+    }
+
+    info!(secs = pass3_start.elapsed().as_secs_f64(), "Pass 3 done");
+
+    Ok(embeds_written)
+}
 
-        println!("  Text length distribution (chars):");
-        println!(
-            "    min={}, median={}, mean={:.0}, max={}",
-            min_len, median_len, avg_len, max_len,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A `/v1/embeddings` stand-in that returns vectors the width of the
+    /// default model and counts the texts it embeds.
+    async fn serve(texts: Arc<AtomicUsize>) -> String {
+        let dims = embed::models::find(embed::models::DEFAULT_MODEL).unwrap().dims;
+        let app = axum::Router::new().route(
+            "/v1/embeddings",
+            axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+                let count = body["input"].as_array().map_or(0, |a| a.len());
+                texts.fetch_add(count, Ordering::SeqCst);
+                let data: Vec<_> = (0..count)
+                    .map(|i| serde_json::json!({"index": i, "embedding": vec![0.5; dims]}))
+                    .collect();
+                async move { axum::Json(serde_json::json!({ "data": data })) }
+            }),
         );
-        let bucket_str: Vec<String> = buckets
-            .iter()
-            .zip(counts.iter())
-            .filter(|(_, &c)| c > 0)
-            .map(|(&(_, _, label), &c)| format!("{}={}", label, c))
-            .collect();
-        println!("    buckets: {}", bucket_str.join(", "));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/v1/embeddings")
     }
 
-    let embeds_written = embedder.embed_batched(
-        &sorted_ids,
-        &sorted_texts,
-        |ids, vecs| db::writer::write_embeddings_jsonl_batch(&mut writer, ids, vecs),
-    ).await?;
-    println!("  Wrote {} embeddings to {}", embeds_written, jsonl_path.display());
+    /// `build` over a copy of the test fixture in `dir`, embedding on `url`.
+    fn build_args(dir: &Path, url: &str, flags: &[&str]) -> BuildArgs {
+        let input = dir.join("virginia.db");
+        if !input.exists() {
+            let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/test-virginia.db");
+            std::fs::copy(fixture, &input).unwrap();
+        }
+        let config = dir.join("config.toml");
+        std::fs::write(&config, format!("[[embedding.backends]]\nurl = \"{url}\"\n")).unwrap();
+        let (input, output) = (input.display().to_string(), dir.join("graph.sqlite.db"));
+        let (output, config) = (output.display().to_string(), config.display().to_string());
+        let mut argv = vec!["proseva-embeddings", "build", "--input", &input];
+        argv.extend(["--output", &output, "--config", &config]);
+        argv.extend(flags);
+        match Cli::try_parse_from(argv).unwrap().command {
+            Some(Command::Build(args)) => args,
+            _ => unreachable!(),
+        }
+    }
 
-    // Flush writer before reading back
-    drop(writer);
+    fn lines(path: &Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
 
-    println!("  Loading embeddings into SQLite for backwards compatibility...");
-    let db_written = db::writer::load_embeddings_from_jsonl(out_conn, jsonl_path)?;
-    println!("  Wrote {} embeddings to database", db_written);
+    fn embeddings(db: &Path) -> usize {
+        let conn = Connection::open(db).unwrap();
+        conn.query_row("SELECT COUNT(*) FROM embeddings", [], |r| r.get(0)).unwrap()
+    }
 
-    println!(
-        "  Pass 3 took:    {:.2}s",
-        pass3_start.elapsed().as_secs_f64()
-    );
+    #[tokio::test]
+    async fn test_watch_jsonl_keeps_reused_embeddings() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts = Arc::new(AtomicUsize::new(0));
+        let args = build_args(dir.path(), &serve(texts.clone()).await, &[]);
+        let output = dir.path().join("graph.sqlite.db");
+        let jsonl = default_jsonl_path(&output);
+
+        watch_cycle(&args, &output, &jsonl).await.unwrap();
+        let embedded = embeddings(&output);
+        assert!(embedded > 0);
+        assert_eq!(lines(&jsonl), embedded);
+        texts.store(0, Ordering::SeqCst);
+
+        let input = Connection::open(dir.path().join("virginia.db")).unwrap();
+        input
+            .execute("UPDATE virginia_code SET body = body || ' Amended.' WHERE id = 1", [])
+            .unwrap();
+        drop(input);
+        watch_cycle(&args, &output, &jsonl).await.unwrap();
+        let reembedded = texts.load(Ordering::SeqCst);
+        assert!(reembedded > 0 && reembedded < embedded);
+        assert_eq!(embeddings(&output), embedded);
+        assert_eq!(lines(&jsonl), embedded);
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn test_embed_only_source_without_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts = Arc::new(AtomicUsize::new(0));
+        let url = serve(texts.clone()).await;
+        let args = build_args(dir.path(), &url, &["--embed-only", "federal_code"]);
+
+        build(args).await.unwrap();
+        assert_eq!(texts.load(Ordering::SeqCst), 0);
+        assert_eq!(embeddings(&dir.path().join("graph.sqlite.db")), 0);
+    }
 }
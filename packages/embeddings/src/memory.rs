@@ -0,0 +1,216 @@
+//! `--max-memory` and RSS reporting. A sampler thread reads the process's
+//! resident set size once a second, logs it every [`LOG_EVERY`], and keeps
+//! the peak; [`Monitor::pass_done`] records each pass's peak in the build
+//! report. Before Pass 3 the build asks [`Monitor::over_budget`] whether to
+//! spill node texts to disk (see `db::spill`), and Pass 1's table workers
+//! go through a [`Governor`] that runs them one batch at a time while RSS is
+//! over budget.
+//!
+//! RSS is read from `/proc/self/statm`, so on other platforms nothing is
+//! reported and the budget never triggers a spill.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::report::BuildReport;
+
+const SAMPLE: Duration = Duration::from_secs(1);
+/// How often a waiting worker rechecks RSS, in case memory was freed without
+/// another worker finishing a batch.
+const RECHECK: Duration = Duration::from_millis(100);
+const LOG_EVERY: Duration = Duration::from_secs(30);
+const MB: u64 = 1024 * 1024;
+
+/// Parse a `--max-memory` value: bytes, or a number with a `K`, `M`, `G` or
+/// `T` suffix (binary units; a trailing `B` or `iB` is allowed), e.g. `8G`.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let upper = s.to_ascii_uppercase();
+    let digits = upper
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+        .trim_end();
+    let (number, shift) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 10),
+        Some('M') => (&digits[..digits.len() - 1], 20),
+        Some('G') => (&digits[..digits.len() - 1], 30),
+        Some('T') => (&digits[..digits.len() - 1], 40),
+        _ => (digits, 0),
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("{s:?} is not a size like 8G or 512M"))?;
+    if value <= 0.0 {
+        return Err(format!("{s:?} must be more than zero"));
+    }
+    Ok((value * (1u64 << shift) as f64) as u64)
+}
+
+/// Current resident set size in bytes, where the platform exposes it.
+pub fn rss() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+pub struct Monitor {
+    max: Option<u64>,
+    peak: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    /// Start sampling; `max` is the `--max-memory` budget in bytes.
+    pub fn start(max: Option<u64>) -> Self {
+        let peak = Arc::new(AtomicU64::new(rss().unwrap_or(0)));
+        let stop = Arc::new(AtomicBool::new(false));
+        if max.is_some() && rss().is_none() {
+            warn!("RSS is not available on this platform; --max-memory has no effect");
+        }
+        let sampler = {
+            let (peak, stop) = (peak.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let mut logged = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(rss) = rss() else { return };
+                    peak.fetch_max(rss, Ordering::Relaxed);
+                    if logged.elapsed() >= LOG_EVERY {
+                        info!(rss_mb = rss / MB, "Memory");
+                        logged = Instant::now();
+                    }
+                    std::thread::park_timeout(SAMPLE);
+                }
+            })
+        };
+        Self {
+            max,
+            peak,
+            stop,
+            sampler: Some(sampler),
+        }
+    }
+
+    /// Log and report the peak RSS since the previous pass ended, as
+    /// `memory.<pass>.peak_mb`, then start tracking the next pass's peak.
+    pub fn pass_done(&self, pass: &str, report: &mut BuildReport) {
+        let Some(now) = rss() else { return };
+        let peak = self.peak.swap(now, Ordering::Relaxed).max(now);
+        info!(pass, rss_mb = now / MB, peak_mb = peak / MB, "Memory");
+        report.count(&format!("memory.{}.peak_mb", pass), (peak / MB) as usize);
+        if let Some(max) = self.max.filter(|&max| peak > max) {
+            warn!(
+                pass,
+                peak_mb = peak / MB,
+                max_mb = max / MB,
+                "Peak memory was over --max-memory"
+            );
+        }
+    }
+
+    /// Current RSS, when it is over the budget.
+    pub fn over_budget(&self) -> Option<u64> {
+        let max = self.max?;
+        rss().filter(|&rss| rss > max)
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(sampler) = self.sampler.take() {
+            sampler.thread().unpark();
+            let _ = sampler.join();
+        }
+    }
+}
+
+/// Admits concurrent workers freely while RSS is within `--max-memory`, and
+/// one at a time while it is over, so parallel work degrades to sequential
+/// instead of growing further.
+pub struct Governor<'a> {
+    monitor: &'a Monitor,
+    active: Mutex<usize>,
+    done: Condvar,
+}
+
+/// A worker's turn; dropping it lets waiting workers in.
+pub struct Admission<'g, 'a> {
+    governor: &'g Governor<'a>,
+}
+
+impl<'a> Governor<'a> {
+    pub fn new(monitor: &'a Monitor) -> Self {
+        Self {
+            monitor,
+            active: Mutex::new(0),
+            done: Condvar::new(),
+        }
+    }
+
+    /// Wait until no other worker is active or RSS is within budget. A lone
+    /// worker is always admitted, so work always progresses.
+    pub fn admit(&self) -> Admission<'_, 'a> {
+        let mut active = self.active.lock().unwrap();
+        while *active > 0 && self.monitor.over_budget().is_some() {
+            active = self.done.wait_timeout(active, RECHECK).unwrap().0;
+        }
+        *active += 1;
+        Admission { governor: self }
+    }
+}
+
+impl Drop for Admission<'_, '_> {
+    fn drop(&mut self) {
+        *self.governor.active.lock().unwrap() -= 1;
+        self.governor.done.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512M"), Ok(512 * MB));
+        assert_eq!(parse_size("8G"), Ok(8 << 30));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("2 kb"), Ok(2048));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_governor_serializes_over_budget() {
+        // A 1-byte budget is always exceeded (where RSS is available).
+        let monitor = Monitor::start(Some(1));
+        let governor = Governor::new(&monitor);
+        let running = AtomicU64::new(0);
+        let most = AtomicU64::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..5 {
+                        let _turn = governor.admit();
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(2));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        if rss().is_some() {
+            assert_eq!(most.load(Ordering::SeqCst), 1);
+        }
+    }
+}
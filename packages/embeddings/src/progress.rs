@@ -0,0 +1,270 @@
+//! `--progress-file`: Pass 3 telemetry as a JSON file rewritten after every
+//! embedding batch, so a dashboard can chart throughput while a long build
+//! runs instead of waiting for the build report. Each write goes to a
+//! temporary file renamed over the old one, so readers never see half a
+//! file.
+//!
+//! Token counts are the chunker's whitespace approximation, not the model
+//! tokenizer's. Device memory is the whole GPU's, from `nvidia-smi`, and only
+//! for CUDA; on the CPU and on Apple's unified memory, `rss_mb` is the figure
+//! to watch.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::warn;
+
+use proseva_embeddings::embed::{BatchStats, Device};
+use proseva_embeddings::text::chunker::approx_token_count;
+
+/// Batches kept in `recent_batches`.
+const RECENT: usize = 100;
+/// How often `nvidia-smi` is asked for device memory.
+const DEVICE_SAMPLE: Duration = Duration::from_secs(10);
+const MB: u64 = 1024 * 1024;
+
+/// One batch's line in `recent_batches`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchTelemetry {
+    pub batch: usize,
+    pub at: String,
+    /// `ok`, `failed` (queued for retry) or `hung` (abandoned by the watchdog).
+    pub status: &'static str,
+    pub texts: usize,
+    pub tokens: usize,
+    pub chars: usize,
+    pub secs: f64,
+    pub tokens_per_sec: f64,
+    /// The batch size in force, after any shrinking.
+    pub batch_size: usize,
+    /// The backend that ran the batch.
+    pub backend: String,
+    pub rss_mb: Option<u64>,
+    pub device_memory_mb: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    state: &'a str,
+    started_at: &'a str,
+    updated_at: String,
+    elapsed_secs: f64,
+    /// The backend of the last batch.
+    backend: Option<&'a str>,
+    texts_total: usize,
+    texts_embedded: usize,
+    /// Texts in failed or hung batches, counted once per attempt; a text
+    /// retried successfully is also counted as embedded.
+    texts_failed: usize,
+    tokens_embedded: usize,
+    batches: usize,
+    tokens_per_sec: f64,
+    eta_secs: Option<f64>,
+    batch_size: Option<usize>,
+    recent_batches: &'a VecDeque<BatchTelemetry>,
+}
+
+pub struct ProgressFile {
+    path: PathBuf,
+    started: Instant,
+    started_at: String,
+    texts: usize,
+    embedded: usize,
+    failed: usize,
+    tokens: usize,
+    batches: usize,
+    recent: VecDeque<BatchTelemetry>,
+    /// Last `nvidia-smi` reading and when it was taken.
+    device_memory: Option<(Instant, Option<u64>)>,
+    /// Whether a failed write was already logged.
+    warned: bool,
+}
+
+impl ProgressFile {
+    /// Start a progress file for `texts` texts, writing a first snapshot
+    /// straight away.
+    pub fn new(path: &Path, texts: usize) -> Self {
+        let mut progress = Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            texts,
+            embedded: 0,
+            failed: 0,
+            tokens: 0,
+            batches: 0,
+            recent: VecDeque::with_capacity(RECENT),
+            device_memory: None,
+            warned: false,
+        };
+        progress.write("running");
+        progress
+    }
+
+    /// Record a finished batch and rewrite the file.
+    pub fn batch(&mut self, stats: &BatchStats) {
+        let BatchStats {
+            status,
+            texts,
+            elapsed,
+            batch_size,
+            backend,
+            device,
+        } = *stats;
+        self.batches += 1;
+        let tokens = texts.iter().map(|t| approx_token_count(t)).sum();
+        if status == "ok" {
+            self.embedded += texts.len();
+            self.tokens += tokens;
+        } else {
+            self.failed += texts.len();
+        }
+        let secs = elapsed.as_secs_f64();
+        let device_memory_mb = self.device_memory_mb(device);
+        if self.recent.len() == RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(BatchTelemetry {
+            batch: self.batches,
+            at: chrono::Utc::now().to_rfc3339(),
+            status,
+            texts: texts.len(),
+            tokens,
+            chars: texts.iter().map(|t| t.len()).sum(),
+            secs,
+            tokens_per_sec: tokens as f64 / secs.max(1e-9),
+            batch_size,
+            backend: backend.to_string(),
+            rss_mb: crate::memory::rss().map(|b| b / MB),
+            device_memory_mb,
+        });
+        self.write("running");
+    }
+
+    /// Write the final snapshot: `done`, `interrupted` or `deferred`.
+    pub fn finish(&mut self, state: &str) {
+        self.write(state);
+    }
+
+    fn device_memory_mb(&mut self, device: Option<Device>) -> Option<u64> {
+        if device != Some(Device::Cuda) {
+            return None;
+        }
+        match self.device_memory {
+            Some((at, mb)) if at.elapsed() < DEVICE_SAMPLE => mb,
+            _ => {
+                let mb = cuda_memory_used_mb();
+                self.device_memory = Some((Instant::now(), mb));
+                mb
+            }
+        }
+    }
+
+    fn write(&mut self, state: &str) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let snapshot = Snapshot {
+            state,
+            started_at: &self.started_at,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            elapsed_secs: elapsed,
+            backend: self.recent.back().map(|b| b.backend.as_str()),
+            texts_total: self.texts,
+            texts_embedded: self.embedded,
+            texts_failed: self.failed,
+            tokens_embedded: self.tokens,
+            batches: self.batches,
+            tokens_per_sec: self.tokens as f64 / elapsed.max(1e-9),
+            // At the embedded texts' rate so far
+            eta_secs: (self.embedded > 0 && state == "running").then(|| {
+                elapsed / self.embedded as f64 * self.texts.saturating_sub(self.embedded) as f64
+            }),
+            batch_size: self.recent.back().map(|b| b.batch_size),
+            recent_batches: &self.recent,
+        };
+        let tmp = PathBuf::from(format!("{}.tmp", self.path.display()));
+        let written = serde_json::to_vec_pretty(&snapshot)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&tmp, json)?))
+            .and_then(|()| Ok(std::fs::rename(&tmp, &self.path)?));
+        if let Err(e) = written {
+            // Telemetry isn't worth failing a long run over
+            if !self.warned {
+                warn!(path = %self.path.display(), "Failed to write --progress-file: {e:#}");
+                self.warned = true;
+            }
+        }
+    }
+}
+
+/// Memory in use on every GPU, in MiB, from `nvidia-smi`.
+fn cuda_memory_used_mb() -> Option<u64> {
+    let out = std::process::Command::new("nvidia-smi")
+        .args(["--query-gpu=memory.used", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let used: Vec<u64> = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| l.trim().parse().ok())
+        .collect();
+    (!used.is_empty()).then(|| used.iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_file_tracks_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.json");
+        let read = || -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+        };
+
+        let mut progress = ProgressFile::new(&path, 5);
+        assert_eq!(read()["state"], "running");
+        assert_eq!(read()["texts_embedded"], 0);
+
+        let texts = vec!["one two three".to_string(), "four".to_string()];
+        progress.batch(&BatchStats {
+            status: "ok",
+            texts: &texts,
+            elapsed: Duration::from_millis(500),
+            batch_size: 2,
+            backend: "cpu",
+            device: Some(Device::Cpu),
+        });
+        progress.batch(&BatchStats {
+            status: "hung",
+            texts: &texts[1..],
+            elapsed: Duration::from_secs(1),
+            batch_size: 1,
+            backend: "gpu-box",
+            device: None,
+        });
+        let json = read();
+        assert_eq!(json["texts_embedded"], 2);
+        assert_eq!(json["texts_failed"], 1);
+        assert_eq!(json["tokens_embedded"], 4);
+        assert_eq!(json["batch_size"], 1);
+        assert_eq!(json["backend"], "gpu-box");
+        assert!(json["eta_secs"].as_f64().unwrap() > 0.0);
+        let first = &json["recent_batches"][0];
+        assert_eq!(
+            (first["status"].as_str(), first["tokens"].as_u64()),
+            (Some("ok"), Some(4))
+        );
+        assert_eq!(first["tokens_per_sec"], 8.0);
+        assert!(first["device_memory_mb"].is_null());
+
+        progress.finish("done");
+        assert_eq!(read()["state"], "done");
+        assert!(read()["eta_secs"].is_null());
+        assert!(!dir.path().join("progress.json.tmp").exists());
+    }
+}
@@ -0,0 +1,259 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use object_store::path::Path as ObjectPath;
+use object_store::{MultipartUpload, ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+use url::Url;
+
+/// Size of each multipart chunk read from disk and uploaded.
+const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Number of multipart chunks allowed in flight at once.
+const UPLOAD_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedArtifact {
+    pub name: String,
+    pub key: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Manifest written next to each build and copied to `<prefix>/latest.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishManifest {
+    pub build_id: String,
+    pub created_at: String,
+    pub artifacts: Vec<PublishedArtifact>,
+}
+
+/// Check that a `--publish` target parses and names a supported store.
+/// No network requests are made.
+pub fn validate_target(target: &str) -> Result<()> {
+    open_store(target).map(|_| ())
+}
+
+fn open_store(target: &str) -> Result<(Box<dyn ObjectStore>, ObjectPath)> {
+    open_store_with(target, std::env::vars())
+}
+
+/// [`open_store`] with explicit `(option, value)` pairs, e.g. `AWS_ENDPOINT`.
+fn open_store_with(
+    target: &str,
+    options: impl IntoIterator<Item = (String, String)>,
+) -> Result<(Box<dyn ObjectStore>, ObjectPath)> {
+    let url = Url::parse(target).with_context(|| format!("Invalid --publish URL: {target}"))?;
+    let options = options.into_iter().map(|(k, v)| (k.to_ascii_lowercase(), v));
+    object_store::parse_url_opts(&url, options)
+        .with_context(|| format!("Unsupported --publish URL: {target}"))
+}
+
+/// Upload build artifacts to an object store URL (e.g. `s3://bucket/prefix`).
+///
+/// Each artifact lands under `<prefix>/<build_id>/<filename>`. Once every upload
+/// has succeeded, a `manifest.json` with sizes and SHA-256 checksums is written
+/// into the build directory and copied to `<prefix>/latest.json`, so consumers
+/// only ever see a pointer to a complete build.
+///
+/// Credentials and region are taken from the environment (`AWS_*` variables for S3).
+pub async fn publish_artifacts(target: &str, artifacts: &[&Path]) -> Result<PublishManifest> {
+    let (store, prefix) = open_store(target)?;
+    publish_to(store.as_ref(), &prefix, artifacts).await
+}
+
+async fn publish_to(
+    store: &dyn ObjectStore,
+    prefix: &ObjectPath,
+    artifacts: &[&Path],
+) -> Result<PublishManifest> {
+    let now = chrono::Utc::now();
+    let build_id = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let build_prefix = prefix.child(build_id.as_str());
+
+    let mut published = Vec::with_capacity(artifacts.len());
+    for path in artifacts {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Artifact has no file name: {}", path.display()))?
+            .to_string();
+        let key = build_prefix.child(name.as_str());
+
+        info!(path = %path.display(), key = %key, "Uploading artifact");
+        let (size, sha256) = upload_file(store, &key, path).await?;
+        info!(key = %key, bytes = size, sha256 = %sha256, "Uploaded artifact");
+
+        published.push(PublishedArtifact {
+            name,
+            key: key.to_string(),
+            size,
+            sha256,
+        });
+    }
+
+    let manifest = PublishManifest {
+        build_id,
+        created_at: now.to_rfc3339(),
+        artifacts: published,
+    };
+    let body = serde_json::to_vec_pretty(&manifest)?;
+
+    store
+        .put(&build_prefix.child("manifest.json"), PutPayload::from(body.clone()))
+        .await?;
+    // Written last: the latest pointer only moves once the build is fully uploaded.
+    let latest = prefix.child("latest.json");
+    store.put(&latest, PutPayload::from(body)).await?;
+    info!(key = %latest, "Updated latest pointer");
+
+    Ok(manifest)
+}
+
+/// Stream a file into the store with a multipart upload, hashing as it goes.
+/// A failed upload is aborted, so its parts don't linger in the bucket.
+async fn upload_file(
+    store: &dyn ObjectStore,
+    key: &ObjectPath,
+    path: &Path,
+) -> Result<(u64, String)> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open artifact {}", path.display()))?;
+    let mut upload = store.put_multipart(key).await?;
+    match upload_parts(upload.as_mut(), file).await {
+        Ok(uploaded) => Ok(uploaded),
+        Err(e) => {
+            if let Err(abort) = upload.abort().await {
+                warn!(key = %key, error = %abort, "Failed to abort multipart upload");
+            }
+            Err(e.context(format!("Failed to upload {}", path.display())))
+        }
+    }
+}
+
+/// Upload `file` in `UPLOAD_CHUNK_BYTES` parts, at most `UPLOAD_CONCURRENCY`
+/// at a time, and complete the upload.
+async fn upload_parts(
+    upload: &mut dyn MultipartUpload,
+    mut file: std::fs::File,
+) -> Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut parts = JoinSet::new();
+    loop {
+        let mut buf = Vec::with_capacity(UPLOAD_CHUNK_BYTES);
+        (&mut file).take(UPLOAD_CHUNK_BYTES as u64).read_to_end(&mut buf)?;
+        if buf.is_empty() {
+            break;
+        }
+        hasher.update(&buf);
+        size += buf.len() as u64;
+        while parts.len() >= UPLOAD_CONCURRENCY {
+            parts.join_next().await.expect("parts in flight")??;
+        }
+        parts.spawn(upload.put_part(PutPayload::from(buf)));
+    }
+    while let Some(part) = parts.join_next().await {
+        part??;
+    }
+    upload.complete().await?;
+
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, Method, StatusCode, Uri};
+    use axum::response::IntoResponse;
+    use std::sync::{Arc, Mutex};
+
+    /// A minimal S3 stand-in that records `METHOD path?query` for every request
+    /// and rejects part uploads when `fail_parts` is set.
+    async fn serve(fail_parts: bool, requests: Arc<Mutex<Vec<String>>>) -> String {
+        let app = axum::Router::new().fallback(move |method: Method, uri: Uri| {
+            let query = uri.query().unwrap_or("").to_string();
+            requests.lock().unwrap().push(format!("{method} {}?{query}", uri.path()));
+            async move {
+                let etag = [(header::ETAG, "\"etag\"")];
+                match method {
+                    Method::POST if query.contains("uploads") => (
+                        StatusCode::OK,
+                        "<InitiateMultipartUploadResult><Bucket>bucket</Bucket><Key>k</Key>\
+                         <UploadId>upload-1</UploadId></InitiateMultipartUploadResult>",
+                    )
+                        .into_response(),
+                    Method::POST => (
+                        StatusCode::OK,
+                        "<CompleteMultipartUploadResult><Bucket>bucket</Bucket><Key>k</Key>\
+                         <ETag>\"etag\"</ETag></CompleteMultipartUploadResult>",
+                    )
+                        .into_response(),
+                    Method::PUT if fail_parts && query.contains("partNumber") => {
+                        StatusCode::BAD_REQUEST.into_response()
+                    }
+                    Method::PUT => (StatusCode::OK, etag).into_response(),
+                    Method::DELETE => StatusCode::NO_CONTENT.into_response(),
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn store(endpoint: &str) -> (Box<dyn ObjectStore>, ObjectPath) {
+        let options = [
+            ("AWS_ENDPOINT", endpoint),
+            ("AWS_ALLOW_HTTP", "true"),
+            ("AWS_REGION", "us-east-1"),
+            ("AWS_ACCESS_KEY_ID", "test"),
+            ("AWS_SECRET_ACCESS_KEY", "test"),
+            ("AWS_VIRTUAL_HOSTED_STYLE_REQUEST", "false"),
+        ];
+        let options = options.map(|(k, v)| (k.to_string(), v.to_string()));
+        open_store_with("s3://bucket/builds", options).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_publish_uploads_artifacts_then_latest() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (store, prefix) = store(&serve(false, requests.clone()).await);
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = dir.path().join("embeddings.db");
+        std::fs::write(&artifact, b"abc").unwrap();
+
+        let manifest = publish_to(store.as_ref(), &prefix, &[&artifact]).await.unwrap();
+        assert_eq!(manifest.artifacts.len(), 1);
+        assert_eq!(manifest.artifacts[0].size, 3);
+        assert_eq!(
+            manifest.artifacts[0].sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|r| !r.starts_with("DELETE")));
+        assert_eq!(requests.last().unwrap(), "PUT /bucket/builds/latest.json?");
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_is_aborted() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (store, prefix) = store(&serve(true, requests.clone()).await);
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = dir.path().join("embeddings.db");
+        std::fs::write(&artifact, b"abc").unwrap();
+
+        assert!(publish_to(store.as_ref(), &prefix, &[&artifact]).await.is_err());
+
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().any(|r| r.starts_with("DELETE") && r.contains("uploadId")));
+        assert!(requests.iter().all(|r| !r.contains("latest.json")));
+    }
+}
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::report::BuildReport;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Abort the build when the rule fails.
+    #[default]
+    Error,
+    /// Log and record the failure, but keep going.
+    Warn,
+}
+
+/// A data-quality expectation on a build metric, e.g.
+///
+/// ```toml
+/// [[quality]]
+/// metric = "rows.virginia_code"
+/// min = 70000
+///
+/// [[quality]]
+/// metric = "nodes.empty_text"
+/// per = "nodes.embeddable"   # compare the ratio, not the raw count
+/// max = 0.01
+/// severity = "warn"
+/// ```
+///
+/// Metrics are the keys of [`BuildReport::counts`]. A rule is evaluated after
+/// the first pass that produces its metric.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityRule {
+    pub metric: String,
+    /// Divide the metric by this one before comparing.
+    pub per: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl QualityRule {
+    fn describe(&self) -> String {
+        let name = match self.per {
+            Some(ref per) => format!("{} / {}", self.metric, per),
+            None => self.metric.clone(),
+        };
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{min} <= {name} <= {max}"),
+            (Some(min), None) => format!("{name} >= {min}"),
+            (None, Some(max)) => format!("{name} <= {max}"),
+            (None, None) => name,
+        }
+    }
+
+    fn value(&self, report: &BuildReport) -> Option<f64> {
+        let value = *report.counts.get(&self.metric)? as f64;
+        match self.per {
+            Some(ref per) => {
+                let denom = *report.counts.get(per)? as f64;
+                Some(if denom == 0.0 { 0.0 } else { value / denom })
+            }
+            None => Some(value),
+        }
+    }
+
+    fn passes(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityResult {
+    pub rule: String,
+    pub stage: String,
+    pub value: Option<f64>,
+    pub passed: bool,
+    pub severity: Severity,
+}
+
+/// Evaluates configured rules incrementally as metrics become available.
+pub struct QualityGate {
+    rules: Vec<QualityRule>,
+    evaluated: Vec<bool>,
+}
+
+impl QualityGate {
+    pub fn new(rules: Vec<QualityRule>) -> Self {
+        let evaluated = vec![false; rules.len()];
+        Self { rules, evaluated }
+    }
+
+    /// Check every not-yet-evaluated rule whose metric now exists in `report`.
+    /// Results are recorded on the report; an error-severity failure aborts.
+    pub fn check(&mut self, stage: &str, report: &mut BuildReport) -> anyhow::Result<()> {
+        let mut failures = Vec::new();
+        for (rule, done) in self.rules.iter().zip(self.evaluated.iter_mut()) {
+            if *done {
+                continue;
+            }
+            let Some(value) = rule.value(report) else {
+                continue;
+            };
+            *done = true;
+            let passed = rule.passes(value);
+            let result = QualityResult {
+                rule: rule.describe(),
+                stage: stage.to_string(),
+                value: Some(value),
+                passed,
+                severity: rule.severity,
+            };
+            if passed {
+                info!(rule = %result.rule, stage, value, "Quality check passed");
+            } else {
+                match rule.severity {
+                    Severity::Warn => {
+                        warn!(rule = %result.rule, stage, value, "Quality check failed")
+                    }
+                    Severity::Error => failures.push(format!("{} (value {})", result.rule, value)),
+                }
+            }
+            report.quality.push(result);
+        }
+
+        if !failures.is_empty() {
+            anyhow::bail!(
+                "Data-quality check failed after {}: {}",
+                stage,
+                failures.join("; ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Report rules whose metric never appeared (e.g. embedding counts when
+    /// embeddings were skipped). These are recorded but never abort the build.
+    pub fn finish(&mut self, report: &mut BuildReport) {
+        for (rule, done) in self.rules.iter().zip(self.evaluated.iter_mut()) {
+            if *done {
+                continue;
+            }
+            *done = true;
+            info!(
+                rule = %rule.describe(),
+                "Quality check skipped; its metric wasn't produced by this run"
+            );
+            report.quality.push(QualityResult {
+                rule: rule.describe(),
+                stage: "never".to_string(),
+                value: None,
+                passed: false,
+                severity: Severity::Warn,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(metric: &str, per: Option<&str>, min: Option<f64>, max: Option<f64>) -> QualityRule {
+        QualityRule {
+            metric: metric.into(),
+            per: per.map(Into::into),
+            min,
+            max,
+            severity: Severity::Error,
+        }
+    }
+
+    #[test]
+    fn test_rule_waits_for_metric() {
+        let mut report = BuildReport::new();
+        let mut gate = QualityGate::new(vec![rule("edges.cites", None, Some(10.0), None)]);
+        gate.check("pass1", &mut report).unwrap();
+        assert!(report.quality.is_empty());
+
+        report.count("edges.cites", 3);
+        assert!(gate.check("pass2", &mut report).is_err());
+        assert_eq!(report.quality.len(), 1);
+        assert!(!report.quality[0].passed);
+    }
+
+    #[test]
+    fn test_ratio_rule_and_warning() {
+        let mut report = BuildReport::new();
+        report.count("nodes.empty_text", 5);
+        report.count("nodes.embeddable", 100);
+        let mut warn = rule("nodes.empty_text", Some("nodes.embeddable"), None, Some(0.01));
+        warn.severity = Severity::Warn;
+        let mut gate = QualityGate::new(vec![warn]);
+        gate.check("pass1", &mut report).unwrap();
+        assert_eq!(report.quality[0].value, Some(0.05));
+        assert!(!report.quality[0].passed);
+    }
+}
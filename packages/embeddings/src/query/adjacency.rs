@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use super::Direction;
+use crate::csr::Csr;
+
+/// Every `(rel_type, direction)` CSR from the `adjacency` table, loaded once
+/// per query so each hop is an array lookup instead of a SQL round-trip.
+pub struct AdjacencyIndex {
+    csr: HashMap<(String, Direction), Csr>,
+}
+
+impl AdjacencyIndex {
+    /// `None` when the DB predates the `adjacency` table or it is empty; the
+    /// evaluator then falls back to querying `edges` per hop.
+    pub fn load(conn: &Connection) -> Result<Option<Self>> {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'adjacency')",
+            [],
+            |r| r.get(0),
+        )?;
+        if !exists {
+            return Ok(None);
+        }
+
+        let mut stmt = conn.prepare("SELECT rel_type, direction, offsets, targets FROM adjacency")?;
+        let mut rows = stmt.query([])?;
+        let mut csr = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let rel: String = row.get(0)?;
+            let direction = match row.get::<_, String>(1)?.as_str() {
+                "forward" => Direction::Forward,
+                "reverse" => Direction::Reverse,
+                other => anyhow::bail!("Unknown adjacency direction '{}'", other),
+            };
+            let offsets: Vec<u8> = row.get(2)?;
+            let targets: Vec<u8> = row.get(3)?;
+            csr.insert((rel, direction), Csr::from_blobs(&offsets, &targets)?);
+        }
+        Ok((!csr.is_empty()).then_some(Self { csr }))
+    }
+
+    /// Neighbours of `id` over `rel` edges (every type when `None`).
+    pub fn neighbours(&self, id: i64, rel: Option<&str>, direction: Direction) -> Vec<i64> {
+        self.csr
+            .iter()
+            .filter(|((r, d), _)| *d == direction && rel.is_none_or(|rel| r == rel))
+            .flat_map(|(_, csr)| csr.neighbours(id).iter().map(|&n| n as i64))
+            .collect()
+    }
+}
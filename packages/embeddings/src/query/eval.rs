@@ -0,0 +1,316 @@
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::adjacency::AdjacencyIndex;
+use super::{Direction, Query, Selector, Step};
+use crate::bloom::SectionFilter;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryNode {
+    pub id: i64,
+    pub source: String,
+    pub source_id: String,
+    pub chunk_idx: i64,
+    pub node_type: String,
+}
+
+/// Evaluate `query` against an output graph DB, returning the final node set
+/// ordered by id.
+pub fn evaluate(conn: &Connection, query: &Query) -> Result<Vec<QueryNode>> {
+    let mut frontier = start_nodes(conn, &query.start)?;
+    let index = match query.steps.is_empty() {
+        true => None,
+        false => AdjacencyIndex::load(conn)?,
+    };
+
+    for step in &query.steps {
+        if frontier.is_empty() {
+            break;
+        }
+        let reached = match &index {
+            Some(index) => traverse_index(index, &frontier, step),
+            None => traverse(conn, &frontier, step)?,
+        };
+        frontier = filter_nodes(conn, reached, &step.target)?;
+    }
+
+    load_nodes(conn, &frontier)
+}
+
+fn start_nodes(conn: &Connection, selector: &Selector) -> Result<BTreeSet<i64>> {
+    let ids = match selector {
+        Selector::Any => {
+            let mut stmt = conn.prepare("SELECT id FROM nodes")?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        }
+        Selector::Type(node_type) => {
+            let mut stmt = conn.prepare("SELECT id FROM nodes WHERE node_type = ?1")?;
+            let rows = stmt.query_map([node_type], |r| r.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        }
+        Selector::Node { source, source_id } => {
+            let mut stmt =
+                conn.prepare("SELECT id FROM nodes WHERE source = ?1 AND source_id = ?2")?;
+            let rows = stmt.query_map([source, source_id], |r| r.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        }
+        Selector::Mentions(section) => mentioning_documents(conn, section)?,
+    };
+    Ok(ids)
+}
+
+/// Document nodes whose stored section filter may contain `section`. Only the
+/// filters are read, never the edge list.
+fn mentioning_documents(conn: &Connection, section: &str) -> Result<BTreeSet<i64>> {
+    let mut stmt =
+        conn.prepare("SELECT node_id, num_bits, num_hashes, bits FROM document_section_filters")?;
+    let mut rows = stmt.query([])?;
+    let mut ids = BTreeSet::new();
+    while let Some(row) = rows.next()? {
+        let bits: Vec<u8> = row.get(3)?;
+        let filter = SectionFilter::from_parts(row.get::<_, i64>(1)? as u64, row.get(2)?, &bits)?;
+        if filter.may_contain(section) {
+            ids.insert(row.get(0)?);
+        }
+    }
+    Ok(ids)
+}
+
+/// Follow one step's edges from `frontier`. Transitive steps keep hopping
+/// until no new nodes are reached and return everything reached along the way.
+fn traverse_index(index: &AdjacencyIndex, frontier: &BTreeSet<i64>, step: &Step) -> BTreeSet<i64> {
+    let mut reached = BTreeSet::new();
+    let mut seen: HashSet<i64> = frontier.iter().copied().collect();
+    let mut current: Vec<i64> = frontier.iter().copied().collect();
+
+    while !current.is_empty() {
+        let mut next = Vec::new();
+        for id in current {
+            for n in index.neighbours(id, step.rel.as_deref(), step.direction) {
+                reached.insert(n);
+                if step.transitive && seen.insert(n) {
+                    next.push(n);
+                }
+            }
+        }
+        current = next;
+    }
+
+    reached
+}
+
+/// Per-hop SQL fallback for DBs without an `adjacency` table.
+fn traverse(conn: &Connection, frontier: &BTreeSet<i64>, step: &Step) -> Result<BTreeSet<i64>> {
+    let (from_col, to_col) = match step.direction {
+        Direction::Forward => ("from_id", "to_id"),
+        Direction::Reverse => ("to_id", "from_id"),
+    };
+    let sql = match step.rel {
+        Some(_) => format!("SELECT {to_col} FROM edges WHERE {from_col} = ?1 AND rel_type = ?2"),
+        None => format!("SELECT {to_col} FROM edges WHERE {from_col} = ?1"),
+    };
+    let mut stmt = conn.prepare(&sql)?;
+
+    let mut reached = BTreeSet::new();
+    let mut seen: HashSet<i64> = frontier.iter().copied().collect();
+    let mut current: Vec<i64> = frontier.iter().copied().collect();
+
+    while !current.is_empty() {
+        let mut next = Vec::new();
+        for id in current {
+            let neighbours: Vec<i64> = match step.rel {
+                Some(ref rel) => stmt
+                    .query_map(rusqlite::params![id, rel], |r| r.get(0))?
+                    .collect::<rusqlite::Result<_>>()?,
+                None => stmt
+                    .query_map([id], |r| r.get(0))?
+                    .collect::<rusqlite::Result<_>>()?,
+            };
+            for n in neighbours {
+                reached.insert(n);
+                if step.transitive && seen.insert(n) {
+                    next.push(n);
+                }
+            }
+        }
+        current = next;
+    }
+
+    Ok(reached)
+}
+
+fn filter_nodes(
+    conn: &Connection,
+    ids: BTreeSet<i64>,
+    selector: &Selector,
+) -> Result<BTreeSet<i64>> {
+    match selector {
+        Selector::Any => return Ok(ids),
+        Selector::Mentions(section) => {
+            let mentioning = mentioning_documents(conn, section)?;
+            return Ok(ids.intersection(&mentioning).copied().collect());
+        }
+        _ => {}
+    }
+    let mut stmt = conn.prepare("SELECT source, source_id, node_type FROM nodes WHERE id = ?1")?;
+    let mut kept = BTreeSet::new();
+    for id in ids {
+        let (source, source_id, node_type): (String, String, String) =
+            stmt.query_row([id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        let matches = match selector {
+            Selector::Any => true,
+            Selector::Type(t) => node_type == *t,
+            Selector::Node {
+                source: s,
+                source_id: sid,
+            } => source == *s && source_id == *sid,
+            Selector::Mentions(_) => unreachable!("handled above"),
+        };
+        if matches {
+            kept.insert(id);
+        }
+    }
+    Ok(kept)
+}
+
+fn load_nodes(conn: &Connection, ids: &BTreeSet<i64>) -> Result<Vec<QueryNode>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source, source_id, chunk_idx, node_type FROM nodes WHERE id = ?1",
+    )?;
+    let mut nodes = Vec::with_capacity(ids.len());
+    for &id in ids {
+        nodes.push(stmt.query_row([id], |r| {
+            Ok(QueryNode {
+                id: r.get(0)?,
+                source: r.get(1)?,
+                source_id: r.get(2)?,
+                chunk_idx: r.get(3)?,
+                node_type: r.get(4)?,
+            })
+        })?);
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csr::Csr;
+    use crate::query::parse;
+
+    /// title 1 contains chapter 2 contains sections 3 and 4; 3 cites 4.
+    fn graph() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE nodes (id INTEGER PRIMARY KEY, source TEXT, source_id TEXT,
+                                chunk_idx INTEGER, node_type TEXT);
+            CREATE TABLE edges (from_id INTEGER, to_id INTEGER, rel_type TEXT, weight REAL);
+            INSERT INTO nodes VALUES
+                (1, 'virginia_code', '46.2', 0, 'title'),
+                (2, 'virginia_code', '46.2:8', 0, 'chapter'),
+                (3, 'virginia_code', '46.2-862', 0, 'section'),
+                (4, 'virginia_code', '46.2-870', 0, 'section');
+            INSERT INTO edges VALUES
+                (1, 2, 'contains', NULL),
+                (2, 3, 'contains', NULL),
+                (2, 4, 'contains', NULL),
+                (3, 4, 'cites', NULL);
+            CREATE TABLE document_section_filters (node_id INTEGER PRIMARY KEY,
+                num_bits INTEGER, num_hashes INTEGER, bits BLOB);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn ids(conn: &Connection, q: &str) -> Vec<i64> {
+        evaluate(conn, &parse(q).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_forward_and_reverse_steps() {
+        let conn = graph();
+        assert_eq!(ids(&conn, r#"node("virginia_code","46.2-862") -cites-> *"#), vec![4]);
+        assert_eq!(
+            ids(&conn, r#"node("virginia_code","46.2-862") -cites-> * -contains^-> chapter"#),
+            vec![2]
+        );
+        assert!(ids(&conn, r#"node("virginia_code","46.2-862") -cites-> title"#).is_empty());
+    }
+
+    #[test]
+    fn test_mentions_selector() {
+        let conn = graph();
+        conn.execute(
+            "INSERT INTO nodes VALUES (5, 'documents', 'manual.pdf', 0, 'manual')",
+            [],
+        )
+        .unwrap();
+        let filter = SectionFilter::from_keys(["46.2-862"]);
+        conn.execute(
+            "INSERT INTO document_section_filters VALUES (5, ?1, ?2, ?3)",
+            rusqlite::params![filter.num_bits as i64, filter.num_hashes, filter.to_bytes()],
+        )
+        .unwrap();
+
+        assert_eq!(ids(&conn, r#"mentions("§ 46.2-862")"#), vec![5]);
+        assert!(ids(&conn, r#"mentions("46.2-870")"#).is_empty());
+        assert!(ids(&conn, r#"node("documents","manual.pdf") -*-> mentions("46.2-862")"#).is_empty());
+    }
+
+    #[test]
+    fn test_transitive_step() {
+        let conn = graph();
+        assert_eq!(ids(&conn, "section -contains+^-> title"), vec![1]);
+        assert_eq!(ids(&conn, "title -contains+-> *"), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_csr_adjacency_matches_sql() {
+        let conn = graph();
+        let queries = [
+            r#"node("virginia_code","46.2-862") -cites-> * -contains^-> chapter"#,
+            "section -contains+^-> title",
+            "title -*+-> *",
+            "section -cites^-> *",
+        ];
+        let expected: Vec<Vec<i64>> = queries.iter().map(|q| ids(&conn, q)).collect();
+
+        conn.execute_batch(
+            "CREATE TABLE adjacency (rel_type TEXT, direction TEXT, offsets BLOB, targets BLOB)",
+        )
+        .unwrap();
+        let mut stmt = conn.prepare("SELECT from_id, to_id, rel_type FROM edges").unwrap();
+        let edges: Vec<(i64, i64, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        for rel in ["contains", "cites"] {
+            let of_type = || edges.iter().filter(|e| e.2 == rel);
+            let forward = Csr::build(of_type().map(|e| (e.0, e.1))).unwrap();
+            let reverse = Csr::build(of_type().map(|e| (e.1, e.0))).unwrap();
+            for (direction, csr) in [("forward", forward), ("reverse", reverse)] {
+                let (offsets, targets) = csr.to_blobs();
+                conn.execute(
+                    "INSERT INTO adjacency VALUES (?1, ?2, ?3, ?4)",
+                    rusqlite::params![rel, direction, offsets, targets],
+                )
+                .unwrap();
+            }
+        }
+
+        assert!(AdjacencyIndex::load(&conn).unwrap().is_some());
+        let actual: Vec<Vec<i64>> = queries.iter().map(|q| ids(&conn, q)).collect();
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,93 @@
+//! A small traversal language over the output graph.
+//!
+//! ```text
+//! node("virginia_code", "46.2-862") -cites-> * -contains^-> title
+//! ```
+//!
+//! A query is a start selector followed by zero or more steps. Each step
+//! follows edges from the current node set and keeps the nodes matching its
+//! target selector:
+//!
+//! | Syntax              | Meaning                                            |
+//! | ------------------- | -------------------------------------------------- |
+//! | `node("src", "id")` | all chunks of one node, by `source` / `source_id`  |
+//! | `section`           | every node with that `node_type`                   |
+//! | `*`                 | any node                                           |
+//! | `mentions("8.01-243")` | documents whose section filter may contain § 8.01-243 |
+//! | `-cites->`          | follow `cites` edges forward (from → to)           |
+//! | `-contains^->`      | follow `contains` edges backwards (to → from)      |
+//! | `-*->`              | follow edges of any type                           |
+//! | `-cites+->`         | follow `cites` edges one or more hops              |
+
+pub mod adjacency;
+pub mod eval;
+pub mod parse;
+
+pub use eval::{evaluate, QueryNode};
+pub use parse::parse;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// `*`
+    Any,
+    /// A bare identifier, matched against `node_type`.
+    Type(String),
+    /// `node("source", "source_id")`
+    Node { source: String, source_id: String },
+    /// `mentions("section")`: documents whose `document_section_filters`
+    /// bloom filter may contain the section (false positives possible).
+    Mentions(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// `from_id` → `to_id`
+    Forward,
+    /// `to_id` → `from_id` (`^`)
+    Reverse,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Edge `rel_type`, or `None` for `*`.
+    pub rel: Option<String>,
+    pub direction: Direction,
+    /// `+`: repeat the hop until no new nodes are reached.
+    pub transitive: bool,
+    pub target: Selector,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub start: Selector,
+    pub steps: Vec<Step>,
+}
+
+/// Print query results as a table, or as JSON lines with `json`, showing at
+/// most `limit` of them.
+pub fn print_nodes(nodes: &[QueryNode], json: bool, limit: Option<usize>) -> anyhow::Result<()> {
+    let total = nodes.len();
+    let shown = &nodes[..limit.unwrap_or(total).min(total)];
+
+    if json {
+        for node in shown {
+            println!("{}", serde_json::to_string(node)?);
+        }
+    } else {
+        println!(
+            "{:>8}  {:<14} {:<24} {:>5}  node_type",
+            "id", "source", "source_id", "chunk"
+        );
+        for node in shown {
+            println!(
+                "{:>8}  {:<14} {:<24} {:>5}  {}",
+                node.id, node.source, node.source_id, node.chunk_idx, node.node_type
+            );
+        }
+        if shown.len() < total {
+            println!("... {} more", total - shown.len());
+        }
+        eprintln!("{} node(s)", total);
+    }
+    Ok(())
+}
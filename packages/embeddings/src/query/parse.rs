@@ -0,0 +1,231 @@
+use anyhow::{bail, Result};
+
+use super::{Direction, Query, Selector, Step};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Star,
+    Plus,
+    Caret,
+    Dash,
+    Arrow,
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '*' => Token::Star,
+            '+' => Token::Plus,
+            '^' => Token::Caret,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '-' => {
+                if chars.next_if(|&(_, c)| c == '>').is_some() {
+                    Token::Arrow
+                } else {
+                    Token::Dash
+                }
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => bail!("Unterminated string at {}", pos),
+                        },
+                        Some((_, c)) if c == quote => break,
+                        Some((_, c)) => value.push(c),
+                        None => bail!("Unterminated string at {}", pos),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            other => bail!("Unexpected character '{}' at {}", other, pos),
+        };
+        tokens.push((pos, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map(|(o, _)| *o).unwrap_or(self.len)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<()> {
+        let offset = self.offset();
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            Some(t) => bail!("Expected {} at {}, found {:?}", what, offset, t),
+            None => bail!("Expected {} at {}, found end of query", what, offset),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s),
+            Some(t) => bail!("Expected a quoted string at {}, found {:?}", offset, t),
+            None => bail!("Expected a quoted string at {}, found end of query", offset),
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Star) => Ok(Selector::Any),
+            Some(Token::Ident(name)) if name == "node" && self.peek() == Some(&Token::LParen) => {
+                self.expect(Token::LParen, "'('")?;
+                let source = self.string()?;
+                self.expect(Token::Comma, "','")?;
+                let source_id = self.string()?;
+                self.expect(Token::RParen, "')'")?;
+                Ok(Selector::Node { source, source_id })
+            }
+            Some(Token::Ident(name))
+                if name == "mentions" && self.peek() == Some(&Token::LParen) =>
+            {
+                self.expect(Token::LParen, "'('")?;
+                let section = self.string()?;
+                self.expect(Token::RParen, "')'")?;
+                Ok(Selector::Mentions(section))
+            }
+            Some(Token::Ident(name)) => Ok(Selector::Type(name)),
+            Some(t) => bail!("Expected a node selector at {}, found {:?}", offset, t),
+            None => bail!("Expected a node selector at {}, found end of query", offset),
+        }
+    }
+
+    fn step(&mut self) -> Result<Step> {
+        self.expect(Token::Dash, "'-' starting an edge")?;
+        let offset = self.offset();
+        let rel = match self.next() {
+            Some(Token::Star) => None,
+            Some(Token::Ident(rel)) => Some(rel),
+            Some(t) => bail!("Expected an edge type or '*' at {}, found {:?}", offset, t),
+            None => bail!("Expected an edge type or '*' at {}, found end of query", offset),
+        };
+
+        let mut direction = Direction::Forward;
+        let mut transitive = false;
+        loop {
+            match self.peek() {
+                Some(Token::Caret) if direction == Direction::Forward => {
+                    direction = Direction::Reverse;
+                }
+                Some(Token::Plus) if !transitive => transitive = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        self.expect(Token::Arrow, "'->'")?;
+
+        let target = self.selector()?;
+        Ok(Step {
+            rel,
+            direction,
+            transitive,
+            target,
+        })
+    }
+}
+
+/// Parse a traversal query; see the [module docs](super) for the syntax.
+pub fn parse(input: &str) -> Result<Query> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        len: input.len(),
+    };
+    if parser.tokens.is_empty() {
+        bail!("Empty query");
+    }
+
+    let start = parser.selector()?;
+    let mut steps = Vec::new();
+    while parser.peek().is_some() {
+        steps.push(parser.step()?);
+    }
+    Ok(Query { start, steps })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_example_query() {
+        let q = parse(r#"node("virginia_code","46.2-862") -cites-> * -contains^-> title"#).unwrap();
+        assert_eq!(
+            q.start,
+            Selector::Node {
+                source: "virginia_code".into(),
+                source_id: "46.2-862".into()
+            }
+        );
+        assert_eq!(q.steps.len(), 2);
+        assert_eq!(q.steps[0].rel.as_deref(), Some("cites"));
+        assert_eq!(q.steps[0].direction, Direction::Forward);
+        assert_eq!(q.steps[0].target, Selector::Any);
+        assert_eq!(q.steps[1].rel.as_deref(), Some("contains"));
+        assert_eq!(q.steps[1].direction, Direction::Reverse);
+        assert_eq!(q.steps[1].target, Selector::Type("title".into()));
+    }
+
+    #[test]
+    fn test_parse_wildcard_and_transitive() {
+        let q = parse("section -*-> * -contains+^-> title").unwrap();
+        assert_eq!(q.start, Selector::Type("section".into()));
+        assert_eq!(q.steps[0].rel, None);
+        assert!(q.steps[1].transitive);
+        assert_eq!(q.steps[1].direction, Direction::Reverse);
+
+        let q = parse(r#"mentions("§ 46.2-862") -contains^-> *"#).unwrap();
+        assert_eq!(q.start, Selector::Mentions("§ 46.2-862".into()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse(r#"node("virginia_code")"#).is_err());
+        assert!(parse("section -cites title").is_err());
+        assert!(parse("section -cites->").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Opt-in log of production searches, clicks, and thumbs-up/down votes, kept
+//! in its own SQLite DB (`embedding-server --query-log`) so it survives index
+//! rebuilds; its export to the eval format read by `export queries`; and the
+//! per-node boosts the server learns from the votes.
+//!
+//! Results and clicks are stored with their `(source, source_id, chunk_idx)`
+//! keys as well as node ids, because node ids are reassigned on every build.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::search::Boosts;
+
+/// One returned hit as stored in `query_log.results`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedHit {
+    pub node_id: i64,
+    pub source: String,
+    pub source_id: String,
+    pub chunk_idx: i64,
+    pub score: f32,
+}
+
+/// One line of the eval export: what was asked, what came back, and what the
+/// user clicked. Nodes are `source:source_id` (the same spec `path` and
+/// `subgraph` accept), deduplicated across chunks in rank order.
+#[derive(Debug, Serialize)]
+pub struct EvalRecord {
+    pub query_id: i64,
+    pub ts: String,
+    pub corpus: String,
+    pub query: String,
+    pub retrieved: Vec<String>,
+    pub relevant: Vec<String>,
+}
+
+pub fn open(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open query log {}", path.display()))?;
+    conn.execute_batch(
+        "
+        PRAGMA journal_mode = WAL;
+        CREATE TABLE IF NOT EXISTS query_log (
+            id      INTEGER PRIMARY KEY,
+            ts      TEXT NOT NULL,
+            corpus  TEXT NOT NULL,
+            query   TEXT NOT NULL,
+            results TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS query_clicks (
+            query_id  INTEGER NOT NULL REFERENCES query_log(id),
+            node_id   INTEGER NOT NULL,
+            source    TEXT,
+            source_id TEXT,
+            chunk_idx INTEGER,
+            ts        TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_query_clicks_query ON query_clicks(query_id);
+        CREATE TABLE IF NOT EXISTS query_votes (
+            query_id  INTEGER NOT NULL REFERENCES query_log(id),
+            node_id   INTEGER NOT NULL,
+            source    TEXT,
+            source_id TEXT,
+            chunk_idx INTEGER,
+            vote      INTEGER NOT NULL CHECK (vote IN (-1, 1)),
+            ts        TEXT NOT NULL,
+            PRIMARY KEY (query_id, node_id)
+        );
+        ",
+    )?;
+    Ok(conn)
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Record a search and return its id (handed back to the client so it can
+/// report clicks).
+pub fn record_query(conn: &Connection, corpus: &str, query: &str, hits: &[LoggedHit]) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO query_log (ts, corpus, query, results) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![now(), corpus, query, serde_json::to_string(hits)?],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Look up `node_id` among a logged query's results: `None` if the query id
+/// is unknown, `Some(None)` if the node wasn't one of its results.
+fn logged_hit(conn: &Connection, query_id: i64, node_id: i64) -> Result<Option<Option<LoggedHit>>> {
+    let results: Option<String> = conn
+        .query_row("SELECT results FROM query_log WHERE id = ?1", [query_id], |r| r.get(0))
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e),
+        })?;
+    let Some(results) = results else {
+        return Ok(None);
+    };
+    let hits: Vec<LoggedHit> = serde_json::from_str(&results)?;
+    Ok(Some(hits.into_iter().find(|h| h.node_id == node_id)))
+}
+
+/// Record a click on `node_id` for a logged query. The node's key is taken
+/// from the query's stored results; returns `false` if the query id is unknown.
+pub fn record_click(conn: &Connection, query_id: i64, node_id: i64) -> Result<bool> {
+    let Some(hit) = logged_hit(conn, query_id, node_id)? else {
+        return Ok(false);
+    };
+    let hit = hit.as_ref();
+    conn.execute(
+        "INSERT INTO query_clicks (query_id, node_id, source, source_id, chunk_idx, ts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            query_id,
+            node_id,
+            hit.map(|h| &h.source),
+            hit.map(|h| &h.source_id),
+            hit.map(|h| h.chunk_idx),
+            now()
+        ],
+    )?;
+    Ok(true)
+}
+
+/// Record a thumbs-up (`up = true`) or thumbs-down for `node_id` on a logged
+/// query, replacing any earlier vote for the same pair. Returns `false` if the
+/// query id is unknown.
+pub fn record_vote(conn: &Connection, query_id: i64, node_id: i64, up: bool) -> Result<bool> {
+    let Some(hit) = logged_hit(conn, query_id, node_id)? else {
+        return Ok(false);
+    };
+    let hit = hit.as_ref();
+    conn.execute(
+        "INSERT OR REPLACE INTO query_votes (query_id, node_id, source, source_id, chunk_idx, vote, ts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            query_id,
+            node_id,
+            hit.map(|h| &h.source),
+            hit.map(|h| &h.source_id),
+            hit.map(|h| h.chunk_idx),
+            if up { 1 } else { -1 },
+            now()
+        ],
+    )?;
+    Ok(true)
+}
+
+/// Per-corpus boosts from all votes so far.
+///
+/// A node with `up` and `down` votes gets `strength * (up - down) / (up +
+/// down + prior)` added to its score: `prior` pseudo-votes of no opinion keep
+/// a handful of votes from moving the ranking much, and the boost stays
+/// within `± strength`.
+pub fn compute_boosts(
+    conn: &Connection,
+    strength: f32,
+    prior: f32,
+) -> Result<HashMap<String, Boosts>> {
+    let mut stmt = conn.prepare(
+        "SELECT q.corpus, v.source, v.source_id, v.chunk_idx,
+                SUM(v.vote = 1), SUM(v.vote = -1)
+         FROM query_votes v
+         JOIN query_log q ON q.id = v.query_id
+         WHERE v.source IS NOT NULL
+         GROUP BY q.corpus, v.source, v.source_id, v.chunk_idx",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut boosts: HashMap<String, Boosts> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let (up, down): (i64, i64) = (row.get(4)?, row.get(5)?);
+        let net = (up - down) as f32 / ((up + down) as f32 + prior);
+        if net == 0.0 {
+            continue;
+        }
+        boosts
+            .entry(row.get(0)?)
+            .or_default()
+            .insert((row.get(1)?, row.get(2)?, row.get(3)?), strength * net);
+    }
+    Ok(boosts)
+}
+
+fn spec(source: &str, source_id: &str) -> String {
+    format!("{}:{}", source, source_id)
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+/// Write every logged query as one JSON line; with `clicked_only`, skip
+/// queries nobody clicked on. Returns the number of records written.
+pub fn export_eval(conn: &Connection, out: &mut dyn Write, clicked_only: bool) -> Result<usize> {
+    let mut clicks_stmt = conn.prepare(
+        "SELECT source, source_id FROM query_clicks
+         WHERE query_id = ?1 AND source IS NOT NULL ORDER BY ts",
+    )?;
+    let mut stmt = conn.prepare("SELECT id, ts, corpus, query, results FROM query_log ORDER BY id")?;
+    let mut rows = stmt.query([])?;
+    let mut written = 0;
+    while let Some(row) = rows.next()? {
+        let query_id: i64 = row.get(0)?;
+        let hits: Vec<LoggedHit> = serde_json::from_str(&row.get::<_, String>(4)?)?;
+        let mut retrieved = Vec::new();
+        for h in &hits {
+            push_unique(&mut retrieved, spec(&h.source, &h.source_id));
+        }
+        let mut relevant = Vec::new();
+        for click in clicks_stmt.query_map([query_id], |r| {
+            Ok(spec(&r.get::<_, String>(0)?, &r.get::<_, String>(1)?))
+        })? {
+            push_unique(&mut relevant, click?);
+        }
+        if clicked_only && relevant.is_empty() {
+            continue;
+        }
+
+        let record = EvalRecord {
+            query_id,
+            ts: row.get(1)?,
+            corpus: row.get(2)?,
+            query: row.get(3)?,
+            retrieved,
+            relevant,
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        out.write_all(b"\n")?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(node_id: i64, source_id: &str, chunk_idx: i64) -> LoggedHit {
+        LoggedHit {
+            node_id,
+            source: "virginia_code".into(),
+            source_id: source_id.into(),
+            chunk_idx,
+            score: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_log_click_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = open(&dir.path().join("queries.db")).unwrap();
+
+        let hits = [hit(1, "46.2-862", 0), hit(2, "46.2-862", 1), hit(3, "46.2-868", 0)];
+        let q1 = record_query(&conn, "virginia", "reckless driving", &hits).unwrap();
+        record_query(&conn, "virginia", "unclicked", &hits[..1]).unwrap();
+        assert!(record_click(&conn, q1, 3).unwrap());
+        assert!(!record_click(&conn, 999, 3).unwrap());
+
+        let mut out = Vec::new();
+        assert_eq!(export_eval(&conn, &mut out, true).unwrap(), 1);
+        let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(record["query"], "reckless driving");
+        assert_eq!(
+            record["retrieved"],
+            serde_json::json!(["virginia_code:46.2-862", "virginia_code:46.2-868"])
+        );
+        assert_eq!(record["relevant"], serde_json::json!(["virginia_code:46.2-868"]));
+
+        let mut out = Vec::new();
+        assert_eq!(export_eval(&conn, &mut out, false).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_votes_become_boosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = open(&dir.path().join("queries.db")).unwrap();
+
+        let hits = [hit(1, "46.2-862", 0), hit(2, "46.2-868", 0)];
+        let q1 = record_query(&conn, "virginia", "reckless driving", &hits).unwrap();
+        let q2 = record_query(&conn, "virginia", "speeding", &hits).unwrap();
+        assert!(record_vote(&conn, q1, 1, true).unwrap());
+        assert!(record_vote(&conn, q2, 1, true).unwrap());
+        // A later vote on the same (query, node) replaces the earlier one.
+        assert!(record_vote(&conn, q1, 2, true).unwrap());
+        assert!(record_vote(&conn, q1, 2, false).unwrap());
+        assert!(!record_vote(&conn, 999, 1, true).unwrap());
+
+        let boosts = compute_boosts(&conn, 0.2, 2.0).unwrap();
+        let virginia = &boosts["virginia"];
+        let key = |sid: &str| ("virginia_code".to_string(), sid.to_string(), 0);
+        // 2 up: 0.2 * 2/4; 1 down: -0.2 * 1/3.
+        assert!((virginia[&key("46.2-862")] - 0.1).abs() < 1e-6);
+        assert!((virginia[&key("46.2-868")] + 0.2 / 3.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,97 @@
+//! Token-bucket rate limiting for the embedding server.
+//!
+//! Each `(route, client)` pair gets its own bucket that holds up to `burst`
+//! tokens and refills at `rate` tokens per second; a request spends one.
+//! The server keeps one limiter for client IPs and one for API keys, each
+//! with its own rate, and a request that sends a key spends from both: keys
+//! aren't verified, so a made-up one must not get around the IP limit. The
+//! key limit caps a key across every IP it is used from.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Drop idle buckets once the map grows past this many entries.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// `rate` requests per second, with bursts of up to `burst` (at least 1).
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1.0),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spend a token from `key`'s bucket, or return how long until one is
+    /// available.
+    pub fn check(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, b| refilled(b, rate, burst, now) < burst);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = refilled(bucket, self.rate, self.burst, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+fn refilled(bucket: &Bucket, rate: f64, burst: f64, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * rate).min(burst)
+}
+
+/// Whole seconds for a `Retry-After` header, never 0.
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let limiter = RateLimiter::new(2.0, 3.0);
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check("/v1/search ip:1.2.3.4", t0).is_ok());
+        }
+        let wait = limiter.check("/v1/search ip:1.2.3.4", t0).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        assert_eq!(retry_after_secs(wait), 1);
+
+        // Other clients and routes have their own buckets.
+        assert!(limiter.check("/v1/search ip:5.6.7.8", t0).is_ok());
+        assert!(limiter.check("/v1/embeddings ip:1.2.3.4", t0).is_ok());
+
+        let later = t0 + Duration::from_millis(500);
+        assert!(limiter.check("/v1/search ip:1.2.3.4", later).is_ok());
+        assert!(limiter.check("/v1/search ip:1.2.3.4", later).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::db::leaderboards::RankedSection;
+use crate::etl::quality::QualityReport;
+use crate::quality::QualityResult;
+
+pub mod render;
+
+/// Character-length buckets used for the text-length distribution, shared by
+/// the console summary and the written report.
+const LENGTH_BUCKETS: [(usize, usize, &str); 5] = [
+    (0, 100, "< 100"),
+    (100, 500, "100-500"),
+    (500, 1000, "500-1k"),
+    (1000, 2000, "1k-2k"),
+    (2000, usize::MAX, "2k+"),
+];
+
+/// Maximum number of unresolved citations kept as samples in the report.
+const MAX_UNRESOLVED_SAMPLES: usize = 50;
+
+/// Entries of each section leaderboard kept in the report; the output DB's
+/// `section_leaderboards` has the full lists.
+const MAX_LEADERBOARD_ENTRIES: usize = 25;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub label: String,
+    pub count: usize,
+}
+
+/// Wall time for one embedding batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchTiming {
+    pub texts: usize,
+    pub secs: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CitationSample {
+    pub from: String,
+    pub section_ref: String,
+}
+
+/// Embedding throughput over every batch of the run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Throughput {
+    pub batches: usize,
+    pub texts: usize,
+    pub secs: f64,
+    pub texts_per_sec: f64,
+    /// Spread of per-batch texts/s.
+    pub min_batch_texts_per_sec: f64,
+    pub median_batch_texts_per_sec: f64,
+    pub max_batch_texts_per_sec: f64,
+}
+
+impl Throughput {
+    fn from_batches(batches: &[BatchTiming]) -> Option<Self> {
+        if batches.is_empty() {
+            return None;
+        }
+        let texts: usize = batches.iter().map(|b| b.texts).sum();
+        let secs: f64 = batches.iter().map(|b| b.secs).sum();
+        let mut rates: Vec<f64> = batches
+            .iter()
+            .map(|b| b.texts as f64 / b.secs.max(1e-9))
+            .collect();
+        rates.sort_by(f64::total_cmp);
+        Some(Self {
+            batches: batches.len(),
+            texts,
+            secs,
+            texts_per_sec: texts as f64 / secs.max(1e-9),
+            min_batch_texts_per_sec: rates[0],
+            median_batch_texts_per_sec: rates[rates.len() / 2],
+            max_batch_texts_per_sec: rates[rates.len() - 1],
+        })
+    }
+}
+
+/// Summary of a pipeline run, filled in as the passes complete.
+///
+/// Counts and durations are keyed by short names (`nodes`, `edges.cites`,
+/// `pass1`, ...) so new passes can add entries without changing the schema.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildReport {
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub mode: &'static str,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_secs: f64,
+    pub input: Option<String>,
+    /// SHA-256 of the `--snapshot-input` copy the build read.
+    pub input_sha256: Option<String>,
+    /// The source tables the input had.
+    pub sources: Vec<&'static str>,
+    pub output: Option<String>,
+    pub output_size: Option<u64>,
+    pub output_sha256: Option<String>,
+    /// The build flags, as given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>,
+    pub counts: BTreeMap<String, usize>,
+    pub durations: BTreeMap<String, f64>,
+    pub histograms: BTreeMap<String, Vec<HistogramBucket>>,
+    pub batches: Vec<BatchTiming>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throughput: Option<Throughput>,
+    pub unresolved_samples: Vec<CitationSample>,
+    /// The most-cited sections, most cited first.
+    pub most_cited: Vec<RankedSection>,
+    /// A sample of the sections nothing cites.
+    pub orphan_samples: Vec<RankedSection>,
+    pub quality: Vec<QualityResult>,
+    /// The ETL data-quality report, written beside the output DB on its own.
+    #[serde(skip)]
+    pub etl_quality: Option<QualityReport>,
+    #[serde(skip)]
+    start: Instant,
+}
+
+impl BuildReport {
+    pub fn new() -> Self {
+        Self {
+            status: "running",
+            error: None,
+            mode: "build",
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            duration_secs: 0.0,
+            input: None,
+            input_sha256: None,
+            sources: Vec::new(),
+            output: None,
+            output_size: None,
+            output_sha256: None,
+            params: None,
+            counts: BTreeMap::new(),
+            durations: BTreeMap::new(),
+            histograms: BTreeMap::new(),
+            batches: Vec::new(),
+            throughput: None,
+            unresolved_samples: Vec::new(),
+            most_cited: Vec::new(),
+            orphan_samples: Vec::new(),
+            quality: Vec::new(),
+            etl_quality: None,
+            start: Instant::now(),
+        }
+    }
+
+    /// When the run began, for budgets that span every pass.
+    pub fn started(&self) -> Instant {
+        self.start
+    }
+
+    pub fn count(&mut self, key: &str, value: usize) {
+        self.counts.insert(key.to_string(), value);
+    }
+
+    pub fn duration(&mut self, key: &str, since: Instant) {
+        self.durations
+            .insert(key.to_string(), since.elapsed().as_secs_f64());
+    }
+
+    pub fn histogram(&mut self, key: &str, buckets: Vec<HistogramBucket>) {
+        self.histograms.insert(key.to_string(), buckets);
+    }
+
+    pub fn batch(&mut self, texts: usize, secs: f64) {
+        self.batches.push(BatchTiming { texts, secs });
+    }
+
+    /// Keep a bounded sample of unresolved citations for human review.
+    pub fn unresolved_sample(&mut self, from: String, section_ref: String) {
+        if self.unresolved_samples.len() < MAX_UNRESOLVED_SAMPLES {
+            self.unresolved_samples
+                .push(CitationSample { from, section_ref });
+        }
+    }
+
+    /// Keep the head of each section leaderboard, and count the orphans
+    /// (`orphan_sections`).
+    pub fn leaderboards(&mut self, most_cited: &[RankedSection], orphans: &[RankedSection]) {
+        self.most_cited = most_cited
+            .iter()
+            .take(MAX_LEADERBOARD_ENTRIES)
+            .cloned()
+            .collect();
+        self.orphan_samples = orphans
+            .iter()
+            .take(MAX_LEADERBOARD_ENTRIES)
+            .cloned()
+            .collect();
+        self.count("orphan_sections", orphans.len());
+    }
+
+    /// Record the final output file's size and checksum. Call only after the
+    /// output connection is closed so the WAL has been folded into the file.
+    pub fn set_output(&mut self, path: &Path) -> Result<()> {
+        self.output = Some(path.display().to_string());
+        let (size, sha256) = sha256_file(path)?;
+        self.output_size = Some(size);
+        self.output_sha256 = Some(sha256);
+        Ok(())
+    }
+
+    pub fn finish<T>(&mut self, result: &Result<T>) {
+        self.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        self.duration_secs = self.start.elapsed().as_secs_f64();
+        self.throughput = Throughput::from_batches(&self.batches);
+        match result {
+            Ok(_) => self.status = "success",
+            Err(e) => {
+                self.status = "failure";
+                self.error = Some(format!("{e:#}"));
+            }
+        }
+    }
+}
+
+/// Where a build writes its JSON report: `build-report.json` beside the
+/// output DB.
+pub fn json_path(output: &Path) -> std::path::PathBuf {
+    output.with_file_name("build-report.json")
+}
+
+/// Write the report as pretty-printed JSON.
+pub fn write_json(path: &Path, report: &BuildReport) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+    Ok(())
+}
+
+/// Bucket text lengths (in chars) into the standard length histogram.
+pub fn text_length_histogram(lengths: &[usize]) -> Vec<HistogramBucket> {
+    let mut counts = [0usize; LENGTH_BUCKETS.len()];
+    for &l in lengths {
+        if let Some(i) = LENGTH_BUCKETS
+            .iter()
+            .position(|&(lo, hi, _)| l >= lo && l < hi)
+        {
+            counts[i] += 1;
+        }
+    }
+    LENGTH_BUCKETS
+        .iter()
+        .zip(counts)
+        .map(|(&(_, _, label), count)| HistogramBucket {
+            label: label.to_string(),
+            count,
+        })
+        .collect()
+}
+
+/// POST the report as JSON to a webhook URL.
+pub async fn notify(url: &str, report: &BuildReport) -> Result<()> {
+    let resp = reqwest::Client::new()
+        .post(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .json(report)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("webhook returned HTTP {}", resp.status());
+    }
+    Ok(())
+}
+
+/// Size and hex SHA-256 of a file.
+pub fn sha256_file(path: &Path) -> Result<(u64, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_summary() {
+        assert!(Throughput::from_batches(&[]).is_none());
+        let batch = |texts, secs| BatchTiming { texts, secs };
+        let t =
+            Throughput::from_batches(&[batch(64, 2.0), batch(64, 1.0), batch(32, 1.0)]).unwrap();
+        assert_eq!((t.batches, t.texts), (3, 160));
+        assert_eq!(t.texts_per_sec, 40.0);
+        assert_eq!(
+            (
+                t.min_batch_texts_per_sec,
+                t.median_batch_texts_per_sec,
+                t.max_batch_texts_per_sec
+            ),
+            (32.0, 32.0, 64.0)
+        );
+    }
+}
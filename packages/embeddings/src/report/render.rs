@@ -0,0 +1,559 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+
+use super::{BuildReport, HistogramBucket};
+use crate::quality::{QualityResult, Severity};
+
+/// Write the report as Markdown when `path` ends in `.md`, otherwise as a
+/// self-contained HTML page.
+pub fn write_report(path: &Path, report: &BuildReport) -> Result<()> {
+    let is_markdown = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("md"));
+    let body = if is_markdown {
+        render_markdown(report)
+    } else {
+        render_html(report)
+    };
+    std::fs::write(path, body)?;
+    Ok(())
+}
+
+/// One row of the per-table table: raw rows read, rows surviving ETL.
+struct TableRow<'a> {
+    table: &'a str,
+    rows: usize,
+    kept: Option<usize>,
+}
+
+fn table_rows(report: &BuildReport) -> Vec<TableRow<'_>> {
+    report
+        .counts
+        .iter()
+        .filter_map(|(k, &rows)| {
+            let table = k.strip_prefix("rows.")?;
+            let kept = report.counts.get(&format!("etl.{table}")).copied();
+            Some(TableRow { table, rows, kept })
+        })
+        .collect()
+}
+
+/// Entries under `prefix.` (e.g. `nodes.type.` → `section`, `title`, ...).
+fn prefixed<'a>(report: &'a BuildReport, prefix: &str) -> Vec<(&'a str, usize)> {
+    let prefix = format!("{prefix}.");
+    report
+        .counts
+        .iter()
+        .filter_map(|(k, &v)| k.strip_prefix(prefix.as_str()).map(|name| (name, v)))
+        .filter(|(name, _)| !name.contains('.'))
+        .collect()
+}
+
+/// `dropped.<table>.<reason>` entries, flattened to (table, reason, count).
+fn drop_reasons(report: &BuildReport) -> Vec<(&str, &str, usize)> {
+    report
+        .counts
+        .iter()
+        .filter_map(|(k, &v)| {
+            let rest = k.strip_prefix("dropped.")?;
+            let (table, reason) = rest.split_once('.')?;
+            Some((table, reason, v))
+        })
+        .collect()
+}
+
+fn throughput(report: &BuildReport) -> Vec<f64> {
+    report
+        .batches
+        .iter()
+        .map(|b| if b.secs > 0.0 { b.texts as f64 / b.secs } else { 0.0 })
+        .collect()
+}
+
+fn pct(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        100.0 * part as f64 / whole as f64
+    }
+}
+
+fn quality_value(value: Option<f64>) -> String {
+    match value {
+        Some(v) if v.fract() == 0.0 => format!("{v:.0}"),
+        Some(v) => format!("{v:.4}"),
+        None => "–".to_string(),
+    }
+}
+
+fn quality_outcome(q: &QualityResult) -> &'static str {
+    match (q.passed, q.value, q.severity) {
+        (true, _, _) => "pass",
+        (false, None, _) => "not checked",
+        (false, _, Severity::Warn) => "warn",
+        (false, _, Severity::Error) => "FAIL",
+    }
+}
+
+// --- Markdown ---
+
+pub fn render_markdown(report: &BuildReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Build report\n");
+    let _ = writeln!(out, "| | |\n|---|---|");
+    let _ = writeln!(out, "| Status | {} |", report.status);
+    if let Some(ref e) = report.error {
+        let _ = writeln!(out, "| Error | `{}` |", e.replace('|', "\\|"));
+    }
+    let _ = writeln!(out, "| Mode | {} |", report.mode);
+    let _ = writeln!(out, "| Started | {} |", report.started_at);
+    let _ = writeln!(out, "| Duration | {:.1}s |", report.duration_secs);
+    if let Some(ref input) = report.input {
+        let _ = writeln!(out, "| Input | `{input}` |");
+    }
+    if let Some(ref sha) = report.input_sha256 {
+        let _ = writeln!(out, "| Input SHA-256 | `{sha}` |");
+    }
+    if !report.sources.is_empty() {
+        let _ = writeln!(out, "| Sources | {} |", report.sources.join(", "));
+    }
+    if let Some(ref output) = report.output {
+        let _ = writeln!(out, "| Output | `{output}` |");
+    }
+    if let Some(ref sha) = report.output_sha256 {
+        let _ = writeln!(out, "| SHA-256 | `{sha}` |");
+    }
+
+    if !report.quality.is_empty() {
+        let _ = writeln!(out, "\n## Data quality\n");
+        let _ = writeln!(out, "| Rule | Stage | Value | Result |\n|---|---|---:|---|");
+        for q in &report.quality {
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} | {} |",
+                q.rule,
+                q.stage,
+                quality_value(q.value),
+                quality_outcome(q)
+            );
+        }
+    }
+
+    let tables = table_rows(report);
+    if !tables.is_empty() {
+        let _ = writeln!(out, "\n## Source rows\n");
+        let _ = writeln!(out, "| Table | Read | After ETL | Dropped |\n|---|---:|---:|---:|");
+        for t in &tables {
+            match t.kept {
+                Some(kept) => {
+                    let dropped = t.rows.saturating_sub(kept);
+                    let _ = writeln!(
+                        out,
+                        "| {} | {} | {} | {} ({:.1}%) |",
+                        t.table,
+                        t.rows,
+                        kept,
+                        dropped,
+                        pct(dropped, t.rows)
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "| {} | {} | – | – |", t.table, t.rows);
+                }
+            }
+        }
+    }
+
+    let reasons = drop_reasons(report);
+    if !reasons.is_empty() {
+        let _ = writeln!(out, "\n## Drop reasons\n");
+        let _ = writeln!(out, "| Table | Reason | Rows |\n|---|---|---:|");
+        for (table, reason, n) in reasons {
+            let _ = writeln!(out, "| {table} | {reason} | {n} |");
+        }
+    }
+
+    for (title, prefix) in [("Nodes by type", "nodes.type"), ("Edges by type", "edges")] {
+        let entries = prefixed(report, prefix);
+        if entries.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "\n## {title}\n");
+        let _ = writeln!(out, "| Type | Count |\n|---|---:|");
+        for (name, n) in entries {
+            let _ = writeln!(out, "| {name} | {n} |");
+        }
+    }
+
+    if !report.durations.is_empty() {
+        let _ = writeln!(out, "\n## Durations\n");
+        let _ = writeln!(out, "| Stage | Seconds |\n|---|---:|");
+        for (stage, secs) in &report.durations {
+            let _ = writeln!(out, "| {stage} | {secs:.2} |");
+        }
+    }
+
+    for (name, buckets) in &report.histograms {
+        let _ = writeln!(out, "\n## Histogram: {name}\n");
+        let _ = writeln!(out, "```");
+        out.push_str(&ascii_histogram(buckets));
+        let _ = writeln!(out, "```");
+    }
+
+    let rates = throughput(report);
+    if !rates.is_empty() {
+        let total_texts: usize = report.batches.iter().map(|b| b.texts).sum();
+        let total_secs: f64 = report.batches.iter().map(|b| b.secs).sum();
+        let min = rates.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = rates.iter().cloned().fold(0.0, f64::max);
+        let _ = writeln!(out, "\n## Embedding throughput\n");
+        let _ = writeln!(
+            out,
+            "{} batches, {} texts in {:.1}s — mean {:.1} texts/s (min {:.1}, max {:.1})",
+            rates.len(),
+            total_texts,
+            total_secs,
+            total_texts as f64 / total_secs.max(f64::EPSILON),
+            min,
+            max
+        );
+    }
+
+    if let Some(&n) = report.counts.get("unresolved_citations") {
+        let _ = writeln!(out, "\n## Unresolved citations\n");
+        let _ = writeln!(out, "{n} citations did not resolve to a node.\n");
+        if !report.unresolved_samples.is_empty() {
+            let _ = writeln!(out, "| From | Reference |\n|---|---|");
+            for s in &report.unresolved_samples {
+                let _ = writeln!(out, "| {} | § {} |", s.from, s.section_ref);
+            }
+        }
+    }
+
+    if !report.most_cited.is_empty() {
+        let _ = writeln!(out, "\n## Most-cited sections\n");
+        let _ = writeln!(out, "| Source | Section | Cited by |\n|---|---|---:|");
+        for s in &report.most_cited {
+            let _ = writeln!(out, "| {} | {} | {} |", s.source, s.section, s.cited_by);
+        }
+    }
+
+    if let Some(&n) = report.counts.get("orphan_sections") {
+        let _ = writeln!(out, "\n## Orphan sections\n");
+        let _ = writeln!(out, "{n} sections are not cited by anything.\n");
+        if !report.orphan_samples.is_empty() {
+            let _ = writeln!(out, "| Source | Section |\n|---|---|");
+            for s in &report.orphan_samples {
+                let _ = writeln!(out, "| {} | {} |", s.source, s.section);
+            }
+        }
+    }
+
+    out
+}
+
+fn ascii_histogram(buckets: &[HistogramBucket]) -> String {
+    let max = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    let width = buckets.iter().map(|b| b.label.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for b in buckets {
+        let bar = "#".repeat(b.count * 40 / max);
+        let _ = writeln!(out, "{:>width$} | {:<40} {}", b.label, bar, b.count);
+    }
+    out
+}
+
+// --- HTML ---
+
+fn esc(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:960px;margin:2em auto;color:#222}\
+table{border-collapse:collapse;margin:.5em 0 1.5em}td,th{border:1px solid #ddd;padding:4px 10px}\
+th{background:#f5f5f5;text-align:left}td.n{text-align:right;font-variant-numeric:tabular-nums}\
+.ok{color:#2e7d32}.fail{color:#c62828}.bar{fill:#2196F3}.line{fill:none;stroke:#FF9800;stroke-width:1.5}\
+code{background:#f5f5f5;padding:1px 4px}";
+
+pub fn render_html(report: &BuildReport) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>Build report</title><style>{STYLE}</style></head><body>"
+    );
+    let status_class = if report.status == "success" { "ok" } else { "fail" };
+    let _ = write!(
+        out,
+        "<h1>Build report <span class=\"{status_class}\">{}</span></h1><table>",
+        esc(report.status)
+    );
+    let mut summary = |k: &str, v: String| {
+        let _ = write!(out, "<tr><th>{k}</th><td>{v}</td></tr>");
+    };
+    if let Some(ref e) = report.error {
+        summary("Error", format!("<code>{}</code>", esc(e)));
+    }
+    summary("Mode", esc(report.mode));
+    summary("Started", esc(&report.started_at));
+    summary("Duration", format!("{:.1}s", report.duration_secs));
+    if let Some(ref input) = report.input {
+        summary("Input", format!("<code>{}</code>", esc(input)));
+    }
+    if let Some(ref sha) = report.input_sha256 {
+        summary("Input SHA-256", format!("<code>{}</code>", esc(sha)));
+    }
+    if !report.sources.is_empty() {
+        summary("Sources", esc(&report.sources.join(", ")));
+    }
+    if let Some(ref output) = report.output {
+        summary("Output", format!("<code>{}</code>", esc(output)));
+    }
+    if let Some(ref sha) = report.output_sha256 {
+        summary("SHA-256", format!("<code>{}</code>", esc(sha)));
+    }
+    out.push_str("</table>");
+
+    if !report.quality.is_empty() {
+        out.push_str("<h2>Data quality</h2><table><tr><th>Rule</th><th>Stage</th><th>Value</th><th>Result</th></tr>");
+        for q in &report.quality {
+            let class = if q.passed { "ok" } else { "fail" };
+            let _ = write!(
+                out,
+                "<tr><td><code>{}</code></td><td>{}</td><td class=\"n\">{}</td><td class=\"{class}\">{}</td></tr>",
+                esc(&q.rule),
+                esc(&q.stage),
+                quality_value(q.value),
+                quality_outcome(q)
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    let tables = table_rows(report);
+    if !tables.is_empty() {
+        out.push_str("<h2>Source rows</h2><table><tr><th>Table</th><th>Read</th><th>After ETL</th><th>Dropped</th></tr>");
+        for t in &tables {
+            let (kept, dropped) = match t.kept {
+                Some(kept) => {
+                    let dropped = t.rows.saturating_sub(kept);
+                    (
+                        kept.to_string(),
+                        format!("{} ({:.1}%)", dropped, pct(dropped, t.rows)),
+                    )
+                }
+                None => ("–".into(), "–".into()),
+            };
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{kept}</td><td class=\"n\">{dropped}</td></tr>",
+                esc(t.table),
+                t.rows
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    let reasons = drop_reasons(report);
+    if !reasons.is_empty() {
+        out.push_str("<h2>Drop reasons</h2><table><tr><th>Table</th><th>Reason</th><th>Rows</th></tr>");
+        for (table, reason, n) in reasons {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td class=\"n\">{n}</td></tr>",
+                esc(table),
+                esc(reason)
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    for (title, prefix) in [("Nodes by type", "nodes.type"), ("Edges by type", "edges")] {
+        let entries = prefixed(report, prefix);
+        if entries.is_empty() {
+            continue;
+        }
+        let _ = write!(out, "<h2>{title}</h2>");
+        let buckets: Vec<HistogramBucket> = entries
+            .iter()
+            .map(|&(label, count)| HistogramBucket {
+                label: label.to_string(),
+                count,
+            })
+            .collect();
+        out.push_str(&svg_bars(&buckets));
+    }
+
+    if !report.durations.is_empty() {
+        out.push_str("<h2>Durations</h2><table><tr><th>Stage</th><th>Seconds</th></tr>");
+        for (stage, secs) in &report.durations {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td class=\"n\">{secs:.2}</td></tr>",
+                esc(stage)
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    for (name, buckets) in &report.histograms {
+        let _ = write!(out, "<h2>Histogram: {}</h2>", esc(name));
+        out.push_str(&svg_bars(buckets));
+    }
+
+    let rates = throughput(report);
+    if !rates.is_empty() {
+        let total_texts: usize = report.batches.iter().map(|b| b.texts).sum();
+        let total_secs: f64 = report.batches.iter().map(|b| b.secs).sum();
+        let _ = write!(
+            out,
+            "<h2>Embedding throughput</h2><p>{} batches, {} texts in {:.1}s — mean {:.1} texts/s</p>",
+            rates.len(),
+            total_texts,
+            total_secs,
+            total_texts as f64 / total_secs.max(f64::EPSILON)
+        );
+        out.push_str(&svg_line(&rates));
+    }
+
+    if let Some(&n) = report.counts.get("unresolved_citations") {
+        let _ = write!(
+            out,
+            "<h2>Unresolved citations</h2><p>{n} citations did not resolve to a node.</p>"
+        );
+        if !report.unresolved_samples.is_empty() {
+            out.push_str("<table><tr><th>From</th><th>Reference</th></tr>");
+            for s in &report.unresolved_samples {
+                let _ = write!(
+                    out,
+                    "<tr><td>{}</td><td>§ {}</td></tr>",
+                    esc(&s.from),
+                    esc(&s.section_ref)
+                );
+            }
+            out.push_str("</table>");
+        }
+    }
+
+    if !report.most_cited.is_empty() {
+        out.push_str(
+            "<h2>Most-cited sections</h2><table><tr><th>Source</th><th>Section</th><th>Cited by</th></tr>",
+        );
+        for s in &report.most_cited {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td class=\"n\">{}</td></tr>",
+                esc(&s.source),
+                esc(&s.section),
+                s.cited_by
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    if let Some(&n) = report.counts.get("orphan_sections") {
+        let _ = write!(
+            out,
+            "<h2>Orphan sections</h2><p>{n} sections are not cited by anything.</p>"
+        );
+        if !report.orphan_samples.is_empty() {
+            out.push_str("<table><tr><th>Source</th><th>Section</th></tr>");
+            for s in &report.orphan_samples {
+                let _ = write!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    esc(&s.source),
+                    esc(&s.section)
+                );
+            }
+            out.push_str("</table>");
+        }
+    }
+
+    out.push_str("</body></html>\n");
+    out
+}
+
+/// Horizontal bar chart, one labelled bar per bucket.
+fn svg_bars(buckets: &[HistogramBucket]) -> String {
+    const ROW: usize = 22;
+    const LABEL_W: usize = 170;
+    const BAR_W: usize = 600;
+    let max = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    let height = buckets.len() * ROW + 4;
+    let mut out = format!(
+        "<svg width=\"{}\" height=\"{height}\" font-size=\"12\">",
+        LABEL_W + BAR_W + 80
+    );
+    for (i, b) in buckets.iter().enumerate() {
+        let y = i * ROW;
+        let w = b.count * BAR_W / max;
+        let _ = write!(
+            out,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text><rect class=\"bar\" x=\"{LABEL_W}\" y=\"{}\" width=\"{w}\" height=\"{}\"/><text x=\"{}\" y=\"{}\">{}</text>",
+            LABEL_W - 6,
+            y + 15,
+            esc(&b.label),
+            y + 3,
+            ROW - 6,
+            LABEL_W + w + 6,
+            y + 15,
+            b.count
+        );
+    }
+    out.push_str("</svg>");
+    out
+}
+
+/// Line chart of a series (e.g. texts/s per batch), y-axis from 0 to max.
+fn svg_line(values: &[f64]) -> String {
+    const W: f64 = 800.0;
+    const H: f64 = 200.0;
+    let max = values.iter().cloned().fold(0.0, f64::max).max(f64::EPSILON);
+    let step = if values.len() > 1 {
+        W / (values.len() - 1) as f64
+    } else {
+        0.0
+    };
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("{:.1},{:.1}", i as f64 * step, H - v / max * (H - 10.0)))
+        .collect();
+    format!(
+        "<svg width=\"{W}\" height=\"{}\" font-size=\"12\"><polyline class=\"line\" points=\"{}\"/><text x=\"4\" y=\"12\">{max:.1}/s</text><text x=\"4\" y=\"{}\">0</text></svg>",
+        H + 4.0,
+        points.join(" "),
+        H
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_lists_tables_and_drops() {
+        let mut report = BuildReport::new();
+        report.count("rows.virginia_code", 10);
+        report.count("etl.virginia_code", 8);
+        report.count("edges.cites", 4);
+        let md = render_markdown(&report);
+        assert!(md.contains("| virginia_code | 10 | 8 | 2 (20.0%) |"));
+        assert!(md.contains("| cites | 4 |"));
+    }
+
+    #[test]
+    fn test_html_escapes_error() {
+        let mut report = BuildReport::new();
+        report.finish::<()>(&Err(anyhow::anyhow!("<bad>")));
+        let html = render_html(&report);
+        assert!(html.contains("&lt;bad&gt;"));
+        assert!(!html.contains("<bad>"));
+    }
+}
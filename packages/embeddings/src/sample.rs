@@ -0,0 +1,149 @@
+//! `--limit` / `--sample-rate`: cap the rows each source table feeds into ETL
+//! (and so into nodes, edges and embeddings) for quick iteration on pipeline
+//! changes.
+//!
+//! Sampling hashes each row's table and id rather than drawing random numbers,
+//! so the same rate picks the same rows on every run and a larger rate keeps a
+//! superset of a smaller one. Each table is sampled at the rate on its own, so
+//! the sample keeps every source's share of the corpus. `--sample-seed` mixes
+//! into the hash to draw a different, equally repeatable, sample.
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sampling {
+    /// Fraction of rows to keep, in (0, 1].
+    pub rate: Option<f64>,
+    /// Rows to keep per table, after sampling.
+    pub limit: Option<usize>,
+    /// Mixed into each row's hash; another seed picks another sample.
+    pub seed: u64,
+}
+
+/// Parse a `--sample-rate` value, which must be in (0, 1].
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if rate > 0.0 && rate <= 1.0 {
+        Ok(rate)
+    } else {
+        Err(format!("{rate} is not in (0, 1]"))
+    }
+}
+
+/// Map `(seed, table, id)` to a well-mixed value in [0, 1).
+fn unit_hash(seed: u64, table: &str, id: i64) -> f64 {
+    // FNV-1a over the seed, table name and id, then the splitmix64 finalizer.
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in seed
+        .to_le_bytes()
+        .into_iter()
+        .chain(table.bytes())
+        .chain(id.to_le_bytes())
+    {
+        h = (h ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Sampling {
+    pub fn is_active(&self) -> bool {
+        self.rate.is_some() || self.limit.is_some()
+    }
+
+    /// Keep the sampled subset of `rows`, in their original order.
+    pub fn apply<T>(&self, table: &str, rows: &mut Vec<T>, id: impl Fn(&T) -> i64) {
+        self.apply_batch(table, rows, &mut 0, id);
+    }
+
+    /// [`Sampling::apply`] for one batch of a streamed table; `kept` counts
+    /// the rows earlier batches kept, so `limit` applies to the whole table.
+    pub fn apply_batch<T>(
+        &self,
+        table: &str,
+        rows: &mut Vec<T>,
+        kept: &mut usize,
+        id: impl Fn(&T) -> i64,
+    ) {
+        if let Some(rate) = self.rate {
+            rows.retain(|r| unit_hash(self.seed, table, id(r)) < rate);
+        }
+        if let Some(limit) = self.limit {
+            rows.truncate(limit.saturating_sub(*kept));
+        }
+        *kept += rows.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_is_deterministic_and_nested() {
+        let ids: Vec<i64> = (0..10_000).collect();
+        let sample = |rate: f64| {
+            let mut rows = ids.clone();
+            Sampling {
+                rate: Some(rate),
+                ..Default::default()
+            }
+            .apply("virginia_code", &mut rows, |&id| id);
+            rows
+        };
+        let small = sample(0.1);
+        assert!((800..1200).contains(&small.len()), "{}", small.len());
+        assert_eq!(small, sample(0.1));
+        let large = sample(0.5);
+        assert!(small.iter().all(|id| large.contains(id)));
+
+        let mut rows = ids.clone();
+        Sampling {
+            rate: Some(0.5),
+            limit: Some(3),
+            ..Default::default()
+        }
+        .apply("virginia_code", &mut rows, |&id| id);
+        assert_eq!(rows, large[..3]);
+
+        let sampling = Sampling {
+            rate: Some(0.5),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let mut kept = 0;
+        let mut batched = Vec::new();
+        for chunk in ids.chunks(2) {
+            let mut batch = chunk.to_vec();
+            sampling.apply_batch("virginia_code", &mut batch, &mut kept, |&id| id);
+            batched.extend(batch);
+        }
+        assert_eq!(batched, rows);
+    }
+
+    #[test]
+    fn test_sampling_seed() {
+        let ids: Vec<i64> = (0..10_000).collect();
+        let sample = |seed: u64| {
+            let mut rows = ids.clone();
+            Sampling {
+                rate: Some(0.05),
+                seed,
+                ..Default::default()
+            }
+            .apply("acts", &mut rows, |&id| id);
+            rows
+        };
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+        assert!((350..650).contains(&sample(8).len()));
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("0.25"), Ok(0.25));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("1.5").is_err());
+        assert!(parse_rate("x").is_err());
+    }
+}
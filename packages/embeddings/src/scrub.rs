@@ -0,0 +1,362 @@
+//! Optional PII scrubbing (`[scrub]` in `--config`): redact phone numbers,
+//! SSNs, street addresses and configured patterns in the raw source rows,
+//! before ETL assembles any text, so nothing downstream (nodes, edges,
+//! embeddings, the JSONL) ever sees them.
+
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::db::reader::{
+    ActRow, AuthorityRow, ConstitutionRow, CourtRow, DocumentRow, FederalRow, PopularNameRow,
+    VirginiaCodeRow,
+};
+
+static PHONE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\+1[-.\s]?)?(?:\(\d{3}\)\s?|\b\d{3}[-.\s])\d{3}[-.]\d{4}\b").unwrap()
+});
+
+static SSN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{3}-\d{2}-\d{4}\b|\b\d{3} \d{2} \d{4}\b").unwrap());
+
+/// A house number, one to four capitalized words and a street suffix, with an
+/// optional unit. "Court" is deliberately not a suffix: the corpus is full of
+/// "2 Circuit Court"-style text.
+static STREET_ADDRESS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b\d{1,6}\s+(?:[A-Z0-9][A-Za-z0-9'-]*\.?\s+){1,4}(?:Street|St|Avenue|Ave|Road|Rd|Boulevard|Blvd|Drive|Dr|Lane|Ln|Way|Place|Pl|Parkway|Pkwy|Highway|Hwy|Circle|Cir|Pike|Turnpike|Terrace|Ter|Square|Sq|Trail|Trl)\b\.?(?:,?\s+(?:Suite|Ste|Apt|Unit|#)\s*[A-Za-z0-9-]+)?",
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    Phone,
+    Ssn,
+    StreetAddress,
+}
+
+impl Detector {
+    fn name(self) -> &'static str {
+        match self {
+            Detector::Phone => "phone",
+            Detector::Ssn => "ssn",
+            Detector::StreetAddress => "street_address",
+        }
+    }
+
+    fn regex(self) -> &'static Regex {
+        match self {
+            Detector::Phone => &PHONE_RE,
+            Detector::Ssn => &SSN_RE,
+            Detector::StreetAddress => &STREET_ADDRESS_RE,
+        }
+    }
+}
+
+/// An extra pattern to redact, replaced by `[NAME]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomPattern {
+    pub name: String,
+    pub regex: String,
+}
+
+/// The `[scrub]` config section, e.g.
+///
+/// ```toml
+/// [scrub]
+/// columns = ["courts.*", "documents.*"]   # default: every text column
+/// redact_columns = ["courts.address"]     # blanked outright
+///
+/// [[scrub.patterns]]
+/// name = "email"
+/// regex = '[\w.+-]+@[\w-]+\.[\w.]+'
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScrubConfig {
+    /// Built-in detectors to run; all of them by default.
+    pub detectors: Vec<Detector>,
+    pub patterns: Vec<CustomPattern>,
+    /// Columns to scan, as `table.column` or `table.*`.
+    pub columns: Vec<String>,
+    /// Columns replaced by `[REDACTED]` whenever they are non-empty.
+    pub redact_columns: Vec<String>,
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        Self {
+            detectors: vec![Detector::Phone, Detector::Ssn, Detector::StreetAddress],
+            patterns: Vec::new(),
+            columns: Vec::new(),
+            redact_columns: Vec::new(),
+        }
+    }
+}
+
+/// A source table whose text columns can be scrubbed.
+pub trait Scrubbable {
+    const TABLE: &'static str;
+    const COLUMNS: &'static [&'static str];
+    /// The columns named in `COLUMNS`, in the same order.
+    fn columns_mut(&mut self) -> Vec<&mut String>;
+}
+
+macro_rules! scrubbable {
+    ($row:ty, $table:literal, [$($col:ident),+]) => {
+        impl Scrubbable for $row {
+            const TABLE: &'static str = $table;
+            const COLUMNS: &'static [&'static str] = &[$(stringify!($col)),+];
+            fn columns_mut(&mut self) -> Vec<&mut String> {
+                vec![$(&mut self.$col),+]
+            }
+        }
+    };
+}
+
+scrubbable!(
+    VirginiaCodeRow,
+    "virginia_code",
+    [
+        title_num,
+        title_name,
+        chapter_num,
+        chapter_name,
+        section,
+        title,
+        body
+    ]
+);
+scrubbable!(
+    ConstitutionRow,
+    "constitution",
+    [
+        article,
+        article_name,
+        section_name,
+        section_title,
+        section_text
+    ]
+);
+scrubbable!(
+    AuthorityRow,
+    "authorities",
+    [name, short_name, codified, title, section, body]
+);
+scrubbable!(
+    CourtRow,
+    "courts",
+    [name, locality, court_type, district, address, city, state, zip]
+);
+scrubbable!(
+    PopularNameRow,
+    "popular_names",
+    [name, title_num, section, body]
+);
+scrubbable!(ActRow, "acts", [chapter, title, body]);
+scrubbable!(
+    FederalRow,
+    "federal_code",
+    [code, title_num, section, heading, body]
+);
+scrubbable!(
+    DocumentRow,
+    "documents",
+    [dataset, filename, title, content]
+);
+
+const TABLES: [(&str, &[&str]); 8] = [
+    (VirginiaCodeRow::TABLE, VirginiaCodeRow::COLUMNS),
+    (ConstitutionRow::TABLE, ConstitutionRow::COLUMNS),
+    (AuthorityRow::TABLE, AuthorityRow::COLUMNS),
+    (CourtRow::TABLE, CourtRow::COLUMNS),
+    (PopularNameRow::TABLE, PopularNameRow::COLUMNS),
+    (ActRow::TABLE, ActRow::COLUMNS),
+    (FederalRow::TABLE, FederalRow::COLUMNS),
+    (DocumentRow::TABLE, DocumentRow::COLUMNS),
+];
+
+/// Redaction counts by `(table.column, rule)`.
+pub type ScrubSummary = BTreeMap<(String, String), usize>;
+
+/// A compiled `ScrubConfig`.
+pub struct Scrubber {
+    /// `(rule name, pattern, replacement)`, applied in order.
+    rules: Vec<(String, Regex, String)>,
+    columns: Vec<String>,
+    redact_columns: Vec<String>,
+}
+
+/// Check a `table.column` / `table.*` spec against the known source columns.
+fn check_column_spec(spec: &str) -> Result<()> {
+    let Some((table, column)) = spec.split_once('.') else {
+        bail!(
+            "Invalid scrub column {:?}: expected table.column or table.*",
+            spec
+        );
+    };
+    let Some((_, columns)) = TABLES.iter().find(|(t, _)| *t == table) else {
+        bail!("Invalid scrub column {:?}: unknown table {}", spec, table);
+    };
+    if column != "*" && !columns.contains(&column) {
+        bail!(
+            "Invalid scrub column {:?}: {} has no text column {} (columns: {})",
+            spec,
+            table,
+            column,
+            columns.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn spec_matches(specs: &[String], table: &str, column: &str) -> bool {
+    specs.iter().any(|spec| match spec.split_once('.') {
+        Some((t, c)) => t == table && (c == "*" || c == column),
+        None => false,
+    })
+}
+
+impl Scrubber {
+    pub fn new(config: &ScrubConfig) -> Result<Self> {
+        for spec in config.columns.iter().chain(&config.redact_columns) {
+            check_column_spec(spec)?;
+        }
+        // SSNs first: they would otherwise be partly eaten by looser patterns.
+        let mut rules: Vec<(String, Regex, String)> = Vec::new();
+        for detector in [Detector::Ssn, Detector::Phone, Detector::StreetAddress] {
+            if config.detectors.contains(&detector) {
+                let token = match detector {
+                    Detector::StreetAddress => "[ADDRESS]".to_string(),
+                    d => format!("[{}]", d.name().to_uppercase()),
+                };
+                rules.push((detector.name().to_string(), detector.regex().clone(), token));
+            }
+        }
+        for p in &config.patterns {
+            let re = Regex::new(&p.regex)
+                .with_context(|| format!("Invalid scrub pattern {:?}", p.name))?;
+            rules.push((p.name.clone(), re, format!("[{}]", p.name.to_uppercase())));
+        }
+        Ok(Self {
+            rules,
+            columns: config.columns.clone(),
+            redact_columns: config.redact_columns.clone(),
+        })
+    }
+
+    /// Redact one value in place, adding to `summary` under `key`.
+    fn scrub_value(&self, value: &mut String, key: &str, summary: &mut ScrubSummary) {
+        for (name, re, token) in &self.rules {
+            let n = re.find_iter(value).count();
+            if n == 0 {
+                continue;
+            }
+            *value = re.replace_all(value, token.as_str()).into_owned();
+            *summary.entry((key.to_string(), name.clone())).or_default() += n;
+        }
+    }
+
+    /// Scrub the configured columns of every row.
+    pub fn scrub<R: Scrubbable>(&self, rows: &mut [R], summary: &mut ScrubSummary) {
+        for row in rows {
+            for (column, value) in R::COLUMNS.iter().zip(row.columns_mut()) {
+                let key = format!("{}.{}", R::TABLE, column);
+                if spec_matches(&self.redact_columns, R::TABLE, column) {
+                    if !value.is_empty() {
+                        *value = "[REDACTED]".to_string();
+                        *summary.entry((key, "column".to_string())).or_default() += 1;
+                    }
+                } else if self.columns.is_empty() || spec_matches(&self.columns, R::TABLE, column) {
+                    self.scrub_value(value, &key, summary);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn court(address: &str, name: &str) -> CourtRow {
+        CourtRow {
+            id: 1,
+            name: name.into(),
+            locality: "Fairfax".into(),
+            court_type: "Circuit".into(),
+            district: "19th".into(),
+            address: address.into(),
+            city: "Fairfax".into(),
+            state: "VA".into(),
+            zip: "22030".into(),
+        }
+    }
+
+    #[test]
+    fn test_detectors_redact_and_count() {
+        let scrubber = Scrubber::new(&ScrubConfig::default()).unwrap();
+        let mut rows = vec![court(
+            "4110 Chain Bridge Rd, Suite 100",
+            "Clerk (703) 555-0134, SSN 123-45-6789, 19th Circuit Court",
+        )];
+        let mut summary = ScrubSummary::new();
+        scrubber.scrub(&mut rows, &mut summary);
+
+        assert_eq!(rows[0].address, "[ADDRESS]");
+        assert_eq!(rows[0].name, "Clerk [PHONE], SSN [SSN], 19th Circuit Court");
+        let count =
+            |key: &str, rule: &str| summary.get(&(key.to_string(), rule.to_string())).copied();
+        assert_eq!(count("courts.address", "street_address"), Some(1));
+        assert_eq!(count("courts.name", "phone"), Some(1));
+        assert_eq!(count("courts.name", "ssn"), Some(1));
+        // Section numbers and dates are left alone.
+        let mut text = "See § 46.2-862 and 2019, c. 123, effective 7-1-2020.".to_string();
+        scrubber.scrub_value(&mut text, "x", &mut summary);
+        assert_eq!(text, "See § 46.2-862 and 2019, c. 123, effective 7-1-2020.");
+    }
+
+    #[test]
+    fn test_column_rules_and_custom_patterns() {
+        let config: ScrubConfig = toml::from_str(
+            r#"
+            detectors = []
+            columns = ["courts.name"]
+            redact_columns = ["courts.address"]
+            [[patterns]]
+            name = "email"
+            regex = '[\w.+-]+@[\w-]+\.[\w.]+'
+            "#,
+        )
+        .unwrap();
+        let scrubber = Scrubber::new(&config).unwrap();
+        let mut rows = vec![court("1 Main St", "clerk@example.gov"), court("", "x")];
+        rows[0].city = "clerk@example.gov".into();
+        let mut summary = ScrubSummary::new();
+        scrubber.scrub(&mut rows, &mut summary);
+
+        assert_eq!(rows[0].address, "[REDACTED]");
+        assert_eq!(rows[0].name, "[EMAIL]");
+        // Not a listed column.
+        assert_eq!(rows[0].city, "clerk@example.gov");
+        assert_eq!(rows[1].address, "");
+        assert_eq!(
+            summary.get(&("courts.address".to_string(), "column".to_string())),
+            Some(&1)
+        );
+
+        for bad in ["courts", "court.name", "courts.phone"] {
+            let config = ScrubConfig {
+                columns: vec![bad.to_string()],
+                ..ScrubConfig::default()
+            };
+            assert!(Scrubber::new(&config).is_err(), "{bad}");
+        }
+    }
+}
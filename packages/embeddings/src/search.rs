@@ -0,0 +1,1099 @@
+//! In-memory vector index over an output DB's `embeddings`, used by the
+//! embedding server's `/v1/search`.
+//!
+//! [`IndexHandle`] holds the live index behind an `Arc` so a reload can build
+//! a replacement in the background and swap it in atomically: searches that
+//! already cloned the old `Arc` finish against it, new ones see the new index.
+//! When the DB has `title_embeddings` (`--title-embeddings`), a section's
+//! score blends its full-text and heading similarities. When it has a
+//! `node_fts` index (`--fts`), a search can drop or demote nodes containing
+//! excluded terms. With [`Precision::Int8`] the vectors are held as int8
+//! (see [`crate::int8`]), a quarter of the memory, at a small cost in recall.
+//! With [`Precision::Binary`] only sign bits are held (see [`crate::binary`]):
+//! a search scores every row on its bits, then re-scores the best candidates
+//! on their f32 vectors, kept in a side file written at load.
+//! [`Corpora`] maps corpus names (`virginia`, `maryland`, ...) to handles so
+//! one server can serve several jurisdictions.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::binary::{self, BinaryVectors, SideFile};
+use crate::db;
+use crate::int8::{Int8Vectors, Quantized};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IndexedNode {
+    pub node_id: i64,
+    pub source: String,
+    pub source_id: String,
+    pub chunk_idx: i64,
+    pub node_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub node: IndexedNode,
+    pub score: f32,
+    /// Cosine similarity to the section heading, when it has a title vector;
+    /// already blended into `score`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_score: Option<f32>,
+    /// Feedback boost already added to `score`, when not 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boost: Option<f32>,
+    /// The text contains an excluded term; `score` is already multiplied by
+    /// [`DEMOTION`].
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub demoted: bool,
+}
+
+/// Per-node score adjustments learned from feedback, keyed by
+/// `(source, source_id, chunk_idx)` so they survive rebuilds.
+pub type Boosts = HashMap<(String, String, i64), f32>;
+
+/// Share of a section's score taken from its heading when the caller doesn't
+/// choose one.
+pub const DEFAULT_TITLE_WEIGHT: f32 = 0.3;
+
+/// Score multiplier for a hit containing an excluded term under
+/// [`ExcludeMode::Demote`].
+pub const DEMOTION: f32 = 0.5;
+
+/// What a search does with nodes whose text contains an excluded term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExcludeMode {
+    /// Leave them out of the results.
+    #[default]
+    Remove,
+    /// Keep them, scored [`DEMOTION`] times lower.
+    Demote,
+}
+
+impl FromStr for ExcludeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "remove" => Ok(Self::Remove),
+            "demote" => Ok(Self::Demote),
+            _ => Err(format!("expected remove or demote, got '{s}'")),
+        }
+    }
+}
+
+/// The nodes a search's excluded terms matched, and what to do with them.
+#[derive(Debug, Default)]
+pub struct Exclusion {
+    pub nodes: HashSet<i64>,
+    pub mode: ExcludeMode,
+}
+
+/// How an index holds its vectors in memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// As stored: 4 bytes per component.
+    #[default]
+    F32,
+    /// Quantized to 1 byte per component, scored with int8 dot products.
+    Int8,
+    /// 1 bit per component; Hamming distance picks candidates that are
+    /// re-scored in f32 from a side file.
+    Binary,
+}
+
+/// Candidates a binary index re-scores per hit asked for, and at least.
+pub const BINARY_CANDIDATES_PER_HIT: usize = 10;
+pub const MIN_BINARY_CANDIDATES: usize = 100;
+
+impl Precision {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::Int8 => "int8",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(Self::F32),
+            "int8" => Ok(Self::Int8),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("expected f32, int8 or binary, got '{s}'")),
+        }
+    }
+}
+
+/// L2-normalized rows at some [`Precision`].
+enum Vectors {
+    F32 { dims: usize, values: Vec<f32> },
+    Int8(Int8Vectors),
+    Binary {
+        bits: BinaryVectors,
+        exact: SideFile,
+    },
+}
+
+impl Vectors {
+    fn new(dims: usize, precision: Precision) -> Result<Self> {
+        Ok(match precision {
+            Precision::F32 => Self::F32 {
+                dims,
+                values: Vec::new(),
+            },
+            Precision::Int8 => Self::Int8(Int8Vectors::new(dims)),
+            Precision::Binary => Self::Binary {
+                bits: BinaryVectors::new(dims),
+                exact: SideFile::new(dims)?,
+            },
+        })
+    }
+
+    fn dims(&self) -> usize {
+        match self {
+            Self::F32 { dims, .. } => *dims,
+            Self::Int8(v) => v.dims(),
+            Self::Binary { bits, .. } => bits.dims(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::F32 { dims, values } => values.len() / dims,
+            Self::Int8(v) => v.len(),
+            Self::Binary { bits, .. } => bits.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decode a little-endian f32 BLOB onto the end, normalized.
+    fn push(&mut self, blob: &[u8], node_id: i64) -> Result<()> {
+        let v = decode(blob, self.dims(), node_id)?;
+        match self {
+            Self::F32 { values, .. } => values.extend(v),
+            Self::Int8(rows) => rows.push(&v),
+            Self::Binary { bits, exact } => {
+                bits.push(&v);
+                exact.push(&v)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Cosine similarity of row `row` and the normalized query; estimated
+    /// from the bits of a binary index.
+    fn score(&self, row: usize, query: &QueryVector) -> f32 {
+        match self {
+            Self::F32 { dims, values } => values[row * dims..(row + 1) * dims]
+                .iter()
+                .zip(&query.values)
+                .map(|(a, b)| a * b)
+                .sum(),
+            Self::Int8(rows) => rows.cosine(
+                row,
+                query.quantized.as_ref().expect("int8 index with an f32 query"),
+            ),
+            Self::Binary { bits, .. } => bits.cosine(
+                row,
+                query.bits.as_deref().expect("binary index with an f32 query"),
+            ),
+        }
+    }
+
+    /// [`Vectors::score`] from the f32 row, for a binary index's candidates.
+    fn exact_score(&self, row: usize, query: &QueryVector) -> Result<f32> {
+        match self {
+            Self::Binary { exact, .. } => Ok(exact
+                .read(row)?
+                .iter()
+                .zip(&query.values)
+                .map(|(a, b)| a * b)
+                .sum()),
+            _ => Ok(self.score(row, query)),
+        }
+    }
+}
+
+/// A normalized query, with its int8 codes for an int8 index or its sign
+/// bits for a binary one.
+struct QueryVector {
+    values: Vec<f32>,
+    quantized: Option<Quantized>,
+    bits: Option<Vec<u64>>,
+}
+
+
+pub struct SearchIndex {
+    pub path: PathBuf,
+    pub model_name: String,
+    pub dims: usize,
+    pub precision: Precision,
+    nodes: Vec<IndexedNode>,
+    /// Node id to its index in `nodes`.
+    positions: HashMap<i64, usize>,
+    /// Row `i` belongs to `nodes[i]`.
+    vectors: Vectors,
+    /// Heading vectors; `title_rows[i]` is the row of `nodes[i]`'s, if it
+    /// has one.
+    title_vectors: Vectors,
+    title_rows: Vec<Option<usize>>,
+    /// From `build_info`, when the DB has it.
+    pub built_at: Option<DateTime<Utc>>,
+    pub source_scraped_at: Option<DateTime<Utc>>,
+    pub source_scraped_from: Option<String>,
+    pub source_sha256: Option<String>,
+    /// From `corpus_stats`, when the DB has it.
+    pub corpus_stats: Option<db::corpus_stats::CorpusStats>,
+}
+
+impl SearchIndex {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with(path, Precision::F32)
+    }
+
+    /// [`SearchIndex::load`], holding the vectors at `precision`.
+    pub fn load_with(path: &Path, precision: Precision) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let model_info = |key: &str| -> Result<String> {
+            conn.query_row("SELECT value FROM model_info WHERE key = ?1", [key], |r| r.get(0))
+                .with_context(|| format!("{} has no model_info.{}", path.display(), key))
+        };
+        let model_name = model_info("model_name")?;
+        let dims: usize = model_info("dimensions")?.parse()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT n.id, n.source, n.source_id, n.chunk_idx, n.node_type, e.embedding
+             FROM embeddings e JOIN nodes n ON n.id = e.node_id ORDER BY n.id",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut nodes = Vec::new();
+        let mut vectors = Vectors::new(dims, precision)?;
+        while let Some(row) = rows.next()? {
+            vectors.push(&row.get::<_, Vec<u8>>(5)?, row.get(0)?)?;
+            nodes.push(indexed_node(row)?);
+        }
+
+        let positions: HashMap<i64, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.node_id, i))
+            .collect();
+        let mut title_vectors = Vectors::new(dims, precision)?;
+        let mut title_rows = vec![None; nodes.len()];
+        let has_titles: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'title_embeddings')",
+            [],
+            |r| r.get(0),
+        )?;
+        if has_titles {
+            let mut stmt =
+                conn.prepare("SELECT node_id, embedding FROM title_embeddings ORDER BY node_id")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                // Only nodes with a full-text embedding are searchable.
+                let Some(&i) = positions.get(&row.get::<_, i64>(0)?) else {
+                    continue;
+                };
+                title_rows[i] = Some(title_vectors.len());
+                title_vectors.push(&row.get::<_, Vec<u8>>(1)?, row.get(0)?)?;
+            }
+        }
+
+        let mut build_info = db::build_info::read_build_info(&conn)?;
+        let timestamp = |key: &str| {
+            build_info
+                .get(key)
+                .and_then(|v| db::build_info::parse_timestamp(v))
+        };
+        let built_at = timestamp(db::build_info::BUILT_AT);
+        let source_scraped_at = timestamp(db::build_info::SCRAPED_AT);
+        let source_scraped_from = build_info.remove(db::build_info::SCRAPED_FROM);
+        let source_sha256 = build_info.remove(db::build_info::SOURCE_SHA256);
+        let corpus_stats = db::corpus_stats::read(&conn)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            model_name,
+            dims,
+            precision,
+            nodes,
+            positions,
+            vectors,
+            title_vectors,
+            title_rows,
+            built_at,
+            source_scraped_at,
+            source_scraped_from,
+            source_sha256,
+            corpus_stats,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// `boosts` aligned with this index's rows, 0 for a node without one, for
+    /// [`SearchIndex::search`]; computed once per index rather than per query.
+    pub fn row_boosts(&self, boosts: &Boosts) -> Vec<f32> {
+        if boosts.is_empty() {
+            return Vec::new();
+        }
+        self.nodes
+            .iter()
+            .map(|n| {
+                let key = (n.source.clone(), n.source_id.clone(), n.chunk_idx);
+                boosts.get(&key).copied().unwrap_or(0.0)
+            })
+            .collect()
+    }
+
+    /// The indexed node with id `node_id`, if it has an embedding.
+    pub fn node(&self, node_id: i64) -> Option<&IndexedNode> {
+        self.positions.get(&node_id).map(|&i| &self.nodes[i])
+    }
+
+    /// How old the corpus is: since its source was scraped, or since the
+    /// build when the scrape time is unknown. `None` for DBs without
+    /// `build_info`.
+    pub fn age(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        self.source_scraped_at.or(self.built_at).map(|t| now - t)
+    }
+
+    /// Whether any node has a heading vector to blend in.
+    pub fn has_titles(&self) -> bool {
+        !self.title_vectors.is_empty()
+    }
+
+    /// Look `terms` up in the DB's `node_fts` index. Fails when the DB was
+    /// built without `--fts` and there are terms to look up.
+    pub fn exclusion(&self, terms: &[String], mode: ExcludeMode) -> Result<Exclusion> {
+        let conn =
+            Connection::open_with_flags(&self.path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        Ok(Exclusion {
+            nodes: db::fts::matching_nodes(&conn, terms)?,
+            mode,
+        })
+    }
+
+    /// Brute-force cosine search; the best `top_k` hits, highest score first.
+    /// A node with a heading vector scores `(1 - title_weight) * full +
+    /// title_weight * heading`; the rest score on full text alone. The node's
+    /// feedback boost (see [`SearchIndex::row_boosts`]) is then added, so it
+    /// moves a score the same way whatever its sign.
+    /// Nodes in `exclusion` are skipped before scoring, or demoted. A binary
+    /// index ranks on estimates from its bits, then re-scores the best
+    /// candidates on their f32 vectors.
+    pub fn search(
+        &self,
+        query: &[f32],
+        top_k: usize,
+        boosts: Option<&[f32]>,
+        title_weight: f32,
+        exclusion: Option<&Exclusion>,
+    ) -> Result<Vec<SearchHit>> {
+        if query.len() != self.dims {
+            bail!(
+                "Query has {} dimensions, index has {}",
+                query.len(),
+                self.dims
+            );
+        }
+        let mut values = query.to_vec();
+        normalize(&mut values);
+        let query = QueryVector {
+            quantized: (self.precision == Precision::Int8).then(|| Quantized::new(&values)),
+            bits: (self.precision == Precision::Binary).then(|| binary::pack(&values)),
+            values,
+        };
+        let excluded = |i: usize| exclusion.filter(|e| e.nodes.contains(&self.nodes[i].node_id));
+        let boost = |i: usize| boosts.and_then(|b| b.get(i)).copied().filter(|&b| b != 0.0);
+        let scored_row = |i: usize, score: f32, title: Option<f32>| {
+            let demoted = excluded(i).is_some();
+            let score = match title {
+                Some(t) => (1.0 - title_weight) * score + title_weight * t,
+                None => score,
+            };
+            let score = if demoted { score * DEMOTION } else { score };
+            let boost = boost(i);
+            Scored {
+                row: i,
+                score: score + boost.unwrap_or(0.0),
+                title_score: title,
+                boost,
+                demoted,
+            }
+        };
+        let mut scored: Vec<Scored> = (0..self.nodes.len())
+            .filter(|&i| !matches!(excluded(i), Some(e) if e.mode == ExcludeMode::Remove))
+            .map(|i| {
+                let title = self.title_rows[i].map(|row| self.title_vectors.score(row, &query));
+                scored_row(i, self.vectors.score(i, &query), title)
+            })
+            .collect();
+
+        if self.precision == Precision::Binary {
+            // Bit scores are estimates: re-score the best candidates exactly.
+            let n = (top_k * BINARY_CANDIDATES_PER_HIT).max(MIN_BINARY_CANDIDATES);
+            if scored.len() > n {
+                scored.select_nth_unstable_by(n - 1, |a, b| b.score.total_cmp(&a.score));
+                scored.truncate(n);
+            }
+            scored = scored
+                .into_iter()
+                .map(|s| {
+                    let title = self.title_rows[s.row]
+                        .map(|row| self.title_vectors.exact_score(row, &query))
+                        .transpose()?;
+                    Ok(scored_row(s.row, self.vectors.exact_score(s.row, &query)?, title))
+                })
+                .collect::<Result<_>>()?;
+        }
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(top_k);
+
+        Ok(scored
+            .into_iter()
+            .map(|s| SearchHit {
+                node: self.nodes[s.row].clone(),
+                score: s.score,
+                title_score: s.title_score,
+                boost: s.boost,
+                demoted: s.demoted,
+            })
+            .collect())
+    }
+}
+
+/// Rank offset of [`fuse_rankings`]; 60 is the usual choice, which keeps one
+/// list's top hit from outweighing agreement between lists.
+pub const RRF_K: f32 = 60.0;
+
+/// Merge rankings of node ids (best first) by reciprocal rank fusion: a node
+/// scores `1 / (RRF_K + rank)` summed over the lists it is in, ranks from 1.
+/// Used to combine vector and keyword search, whose scores don't compare.
+pub fn fuse_rankings(rankings: &[Vec<i64>]) -> Vec<(i64, f32)> {
+    let mut fused: Vec<(i64, f32)> = Vec::new();
+    let mut position: HashMap<i64, usize> = HashMap::new();
+    for ranking in rankings {
+        for (rank, &node_id) in ranking.iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+            match position.get(&node_id) {
+                Some(&i) => fused[i].1 += score,
+                None => {
+                    position.insert(node_id, fused.len());
+                    fused.push((node_id, score));
+                }
+            }
+        }
+    }
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused
+}
+
+/// A hit before its node is cloned in; `row` indexes `nodes`.
+struct Scored {
+    row: usize,
+    score: f32,
+    title_score: Option<f32>,
+    boost: Option<f32>,
+    demoted: bool,
+}
+
+/// Decode a little-endian f32 BLOB of `dims` components, normalized.
+fn decode(blob: &[u8], dims: usize, node_id: i64) -> Result<Vec<f32>> {
+    if blob.len() != dims * 4 {
+        bail!(
+            "Embedding for node {} is {} bytes, expected {}",
+            node_id,
+            blob.len(),
+            dims * 4
+        );
+    }
+    let mut v: Vec<f32> = blob
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    normalize(&mut v);
+    Ok(v)
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// The live index and its feedback boosts, plus a guard so only one reload
+/// runs at a time.
+pub struct IndexHandle {
+    current: RwLock<Live>,
+    reloading: AtomicBool,
+}
+
+/// The served index and its boosts, swapped together so the row-aligned
+/// boosts always match the rows they are applied to.
+struct Live {
+    index: Arc<SearchIndex>,
+    /// By node key, to re-align with a reloaded index.
+    boosts: Arc<Boosts>,
+    /// `boosts` aligned with `index`'s rows.
+    row_boosts: Arc<Vec<f32>>,
+}
+
+impl IndexHandle {
+    pub fn new(index: SearchIndex) -> Self {
+        Self {
+            current: RwLock::new(Live {
+                index: Arc::new(index),
+                boosts: Arc::default(),
+                row_boosts: Arc::default(),
+            }),
+            reloading: AtomicBool::new(false),
+        }
+    }
+
+    /// The index to run one search against. Holding the `Arc` keeps it alive
+    /// across a concurrent swap.
+    pub fn current(&self) -> Arc<SearchIndex> {
+        self.current.read().unwrap().index.clone()
+    }
+
+    /// [`Self::current`] and its row-aligned boosts.
+    pub fn current_with_boosts(&self) -> (Arc<SearchIndex>, Arc<Vec<f32>>) {
+        let live = self.current.read().unwrap();
+        (live.index.clone(), live.row_boosts.clone())
+    }
+
+    /// Replace the feedback boosts, aligning them with the served index.
+    pub fn set_boosts(&self, boosts: Boosts) {
+        // Align outside the lock so searches aren't held up; redo it in the
+        // rare case a reload swapped the index in the meantime.
+        let index = self.current();
+        let mut rows = index.row_boosts(&boosts);
+        let mut live = self.current.write().unwrap();
+        if !Arc::ptr_eq(&live.index, &index) {
+            rows = live.index.row_boosts(&boosts);
+        }
+        live.row_boosts = Arc::new(rows);
+        live.boosts = Arc::new(boosts);
+    }
+
+    /// Load `path` (the current DB when `None`) and swap it in. The old index
+    /// keeps serving until the new one is fully loaded, and stays if loading
+    /// fails or the embedding dimensions differ.
+    pub async fn reload(self: &Arc<Self>, path: Option<PathBuf>) -> Result<Arc<SearchIndex>> {
+        if self.reloading.swap(true, Ordering::AcqRel) {
+            bail!("A reload is already in progress");
+        }
+        let result = self.load_and_swap(path).await;
+        self.reloading.store(false, Ordering::Release);
+        result
+    }
+
+    async fn load_and_swap(&self, path: Option<PathBuf>) -> Result<Arc<SearchIndex>> {
+        let old = self.current();
+        let path = path.unwrap_or_else(|| old.path.clone());
+        let precision = old.precision;
+        let new =
+            tokio::task::spawn_blocking(move || SearchIndex::load_with(&path, precision)).await??;
+        if new.dims != old.dims {
+            bail!(
+                "{} has {}-dimensional embeddings, serving index has {}",
+                new.path.display(),
+                new.dims,
+                old.dims
+            );
+        }
+        let new = Arc::new(new);
+        let mut live = self.current.write().unwrap();
+        live.row_boosts = Arc::new(new.row_boosts(&live.boosts));
+        live.index = new.clone();
+        Ok(new)
+    }
+}
+
+/// Named indexes served side by side. The first one registered is the
+/// default for requests that don't name a corpus.
+#[derive(Default)]
+pub struct Corpora {
+    handles: Vec<(String, Arc<IndexHandle>)>,
+}
+
+impl Corpora {
+    pub fn insert(&mut self, name: String, index: SearchIndex) -> Result<()> {
+        if self.handles.iter().any(|(n, _)| *n == name) {
+            bail!("Corpus '{}' is mounted twice", name);
+        }
+        self.handles.push((name, Arc::new(IndexHandle::new(index))));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<IndexHandle>)> {
+        self.handles.iter().map(|(n, h)| (n.as_str(), h))
+    }
+
+    /// The named corpus, or the default one when `name` is `None`.
+    pub fn get(&self, name: Option<&str>) -> Result<&Arc<IndexHandle>> {
+        let found = match name {
+            Some(name) => self.handles.iter().find(|(n, _)| n == name),
+            None => self.handles.first(),
+        };
+        match (found, name) {
+            (Some((_, handle)), _) => Ok(handle),
+            (None, Some(name)) => bail!(
+                "Unknown corpus '{}' (serving: {})",
+                name,
+                self.handles
+                    .iter()
+                    .map(|(n, _)| n.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            (None, None) => bail!("No corpus loaded; start the server with --db or --corpus"),
+        }
+    }
+}
+
+/// A neighbour of a node through one edge, for the search UI.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Neighbour {
+    pub rel_type: String,
+    /// `out` when the node is `from_id`, `in` when it is `to_id`.
+    pub direction: &'static str,
+    #[serde(flatten)]
+    pub node: IndexedNode,
+    /// The citations in the citing node's text that produced the edge.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<CitationSpan>,
+}
+
+/// One row of `edge_provenance`: a matched citation, as byte offsets into the
+/// citing chunk's text (or, for `references`, the document's content).
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CitationSpan {
+    pub chunk_idx: i64,
+    pub char_start: i64,
+    pub char_end: i64,
+    pub matched: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeContext {
+    pub node: IndexedNode,
+    /// Containing nodes from the root down (title, chapter, ...), found by
+    /// walking `contains` edges backwards.
+    pub breadcrumbs: Vec<IndexedNode>,
+    /// `cites` / `references` neighbours in either direction.
+    pub neighbours: Vec<Neighbour>,
+}
+
+/// Breadcrumbs and citation neighbours for one node, read from the index's DB.
+pub fn node_context(path: &Path, node_id: i64, max_neighbours: usize) -> Result<Option<NodeContext>> {
+    let conn = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let load = |id: i64| -> Result<Option<IndexedNode>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, source, source_id, chunk_idx, node_type FROM nodes WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map([id], indexed_node)?;
+        Ok(rows.next().transpose()?)
+    };
+    let Some(node) = load(node_id)? else {
+        return Ok(None);
+    };
+
+    let mut breadcrumbs = Vec::new();
+    let mut current = node_id;
+    let mut parent_stmt = conn.prepare(
+        "SELECT from_id FROM edges WHERE to_id = ?1 AND rel_type = 'contains' ORDER BY from_id LIMIT 1",
+    )?;
+    // Bounded in case of a malformed cycle.
+    for _ in 0..16 {
+        let parent: Option<i64> = parent_stmt
+            .query_map([current], |r| r.get(0))?
+            .next()
+            .transpose()?;
+        let Some(parent) = parent else { break };
+        if let Some(p) = load(parent)? {
+            breadcrumbs.push(p);
+        }
+        current = parent;
+    }
+    breadcrumbs.reverse();
+
+    let mut stmt = conn.prepare(
+        "SELECT e.rel_type, 'out', n.id, n.source, n.source_id, n.chunk_idx, n.node_type
+         FROM edges e JOIN nodes n ON n.id = e.to_id
+         WHERE e.from_id = ?1 AND e.rel_type != 'contains'
+         UNION ALL
+         SELECT e.rel_type, 'in', n.id, n.source, n.source_id, n.chunk_idx, n.node_type
+         FROM edges e JOIN nodes n ON n.id = e.from_id
+         WHERE e.to_id = ?1 AND e.rel_type != 'contains'
+         LIMIT ?2",
+    )?;
+    let mut neighbours: Vec<Neighbour> = stmt
+        .query_map(rusqlite::params![node_id, max_neighbours as i64], |r| {
+            let direction: String = r.get(1)?;
+            Ok(Neighbour {
+                rel_type: r.get(0)?,
+                direction: if direction == "out" { "out" } else { "in" },
+                node: IndexedNode {
+                    node_id: r.get(2)?,
+                    source: r.get(3)?,
+                    source_id: r.get(4)?,
+                    chunk_idx: r.get(5)?,
+                    node_type: r.get(6)?,
+                },
+                provenance: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    // Builds before edge provenance have no table to read.
+    let has_provenance: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'edge_provenance')",
+        [],
+        |r| r.get(0),
+    )?;
+    if has_provenance {
+        let mut stmt = conn.prepare(
+            "SELECT chunk_idx, char_start, char_end, matched FROM edge_provenance
+             WHERE from_id = ?1 AND to_id = ?2 AND rel_type = ?3 ORDER BY chunk_idx, char_start",
+        )?;
+        for n in &mut neighbours {
+            let (from, to) = match n.direction {
+                "out" => (node_id, n.node.node_id),
+                _ => (n.node.node_id, node_id),
+            };
+            n.provenance = stmt
+                .query_map(rusqlite::params![from, to, n.rel_type], |r| {
+                    Ok(CitationSpan {
+                        chunk_idx: r.get(0)?,
+                        char_start: r.get(1)?,
+                        char_end: r.get(2)?,
+                        matched: r.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+        }
+    }
+
+    Ok(Some(NodeContext {
+        node,
+        breadcrumbs,
+        neighbours,
+    }))
+}
+
+fn indexed_node(r: &rusqlite::Row) -> rusqlite::Result<IndexedNode> {
+    Ok(IndexedNode {
+        node_id: r.get(0)?,
+        source: r.get(1)?,
+        source_id: r.get(2)?,
+        chunk_idx: r.get(3)?,
+        node_type: r.get(4)?,
+    })
+}
+
+/// Parse a `--corpus name=path` argument.
+pub fn parse_corpus_arg(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((name, path)) if !name.trim().is_empty() && !path.trim().is_empty() => {
+            Ok((name.trim().to_string(), PathBuf::from(path.trim())))
+        }
+        _ => Err(format!("expected NAME=PATH, got '{arg}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_db(path: &Path, vectors: &[[f32; 2]]) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE model_info (key TEXT PRIMARY KEY, value TEXT);
+            INSERT INTO model_info VALUES ('model_name', 'test'), ('dimensions', '2');
+            CREATE TABLE nodes (id INTEGER PRIMARY KEY, source TEXT, source_id TEXT,
+                                chunk_idx INTEGER, node_type TEXT);
+            CREATE TABLE embeddings (node_id INTEGER PRIMARY KEY, embedding BLOB);
+            ",
+        )
+        .unwrap();
+        for (i, v) in vectors.iter().enumerate() {
+            let id = i as i64 + 1;
+            conn.execute(
+                "INSERT INTO nodes VALUES (?1, 'virginia_code', ?2, 0, 'section')",
+                rusqlite::params![id, format!("1-{id}")],
+            )
+            .unwrap();
+            let blob: Vec<u8> = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+            conn.execute("INSERT INTO embeddings VALUES (?1, ?2)", rusqlite::params![id, blob])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_search_ranks_by_cosine() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[1.0, 0.0], [0.0, 2.0], [1.0, 1.0]]);
+
+        let index = SearchIndex::load(&path).unwrap();
+        let hits = index.search(&[0.0, 1.0], 2, None, 0.0, None).unwrap();
+        assert_eq!(hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>(), vec![2, 3]);
+        assert!((hits[0].score - 1.0).abs() < 1e-6);
+        assert!(index.search(&[1.0], 2, None, 0.0, None).is_err());
+
+        // A strong enough boost lifts node 3 (cosine 0.71) above node 2.
+        let boosts = Boosts::from([(("virginia_code".to_string(), "1-3".to_string(), 0), 0.5)]);
+        let rows = index.row_boosts(&boosts);
+        assert_eq!(rows, [0.0, 0.0, 0.5]);
+        let hits = index.search(&[0.0, 1.0], 2, Some(&rows), 0.0, None).unwrap();
+        assert_eq!(hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(hits[0].boost, Some(0.5));
+        // Boosting a node with a negative score still raises it.
+        let hits = index.search(&[-1.0, 0.0], 3, None, 0.0, None).unwrap();
+        let boosted = index.search(&[-1.0, 0.0], 3, Some(&rows), 0.0, None).unwrap();
+        let score = |hits: &[SearchHit], id: i64| {
+            hits.iter().find(|h| h.node.node_id == id).unwrap().score
+        };
+        assert!(score(&hits, 3) < 0.0);
+        assert!(score(&boosted, 3) > score(&hits, 3));
+    }
+
+    #[test]
+    fn test_int8_index_ranks_like_f32() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[1.0, 0.0], [0.0, 2.0], [1.0, 1.0], [-0.3, 1.0]]);
+
+        let f32_index = SearchIndex::load(&path).unwrap();
+        let int8_index = SearchIndex::load_with(&path, Precision::Int8).unwrap();
+        assert_eq!(int8_index.precision, Precision::Int8);
+        let f32_hits = f32_index.search(&[0.2, 1.0], 4, None, 0.0, None).unwrap();
+        let int8_hits = int8_index.search(&[0.2, 1.0], 4, None, 0.0, None).unwrap();
+        let ids = |hits: &[SearchHit]| hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>();
+        assert_eq!(ids(&int8_hits), ids(&f32_hits));
+        for (a, b) in int8_hits.iter().zip(&f32_hits) {
+            assert!((a.score - b.score).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_binary_index_rescores_candidates_in_f32() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[1.0, 0.0], [0.8, 0.6], [0.6, 0.8], [-1.0, 0.2]]);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE title_embeddings (node_id INTEGER PRIMARY KEY, embedding BLOB);",
+        )
+        .unwrap();
+        let blob: Vec<u8> = [0.0f32, 1.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        conn.execute("INSERT INTO title_embeddings VALUES (2, ?1)", [blob])
+            .unwrap();
+
+        let f32_index = SearchIndex::load(&path).unwrap();
+        let binary_index = SearchIndex::load_with(&path, Precision::Binary).unwrap();
+        assert!(binary_index.has_titles());
+        // Nodes 1-3 share sign bits; re-scoring in f32 still orders them.
+        let f32_hits = f32_index.search(&[0.6, 0.8], 3, None, 0.3, None).unwrap();
+        let binary_hits = binary_index.search(&[0.6, 0.8], 3, None, 0.3, None).unwrap();
+        let ids = |hits: &[SearchHit]| hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>();
+        assert_eq!(ids(&binary_hits), ids(&f32_hits));
+        for (a, b) in binary_hits.iter().zip(&f32_hits) {
+            assert!((a.score - b.score).abs() < 1e-6);
+            assert_eq!(a.title_score, b.title_score);
+        }
+
+        // A rebuild replacing the DB doesn't change what the loaded index
+        // re-scores against.
+        std::fs::remove_file(&path).unwrap();
+        write_db(&path, &[[0.0, 1.0], [1.0, 0.0], [1.0, 0.0], [1.0, 0.0]]);
+        let again = binary_index.search(&[0.6, 0.8], 3, None, 0.3, None).unwrap();
+        assert_eq!(ids(&again), ids(&f32_hits));
+        assert!((again[0].score - f32_hits[0].score).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fuse_rankings() {
+        // 2 is second in both lists, so it beats each list's own top hit
+        let fused = fuse_rankings(&[vec![1, 2, 3], vec![4, 2]]);
+        let ids: Vec<i64> = fused.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, [2, 1, 4, 3]);
+        assert!((fused[0].1 - 2.0 / 62.0).abs() < 1e-6);
+        assert!(fuse_rankings(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_title_vectors_blend_into_score() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[1.0, 0.0], [0.8, 0.6]]);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE title_embeddings (node_id INTEGER PRIMARY KEY, embedding BLOB);",
+        )
+        .unwrap();
+        let blob: Vec<u8> = [0.0f32, 1.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        conn.execute("INSERT INTO title_embeddings VALUES (2, ?1)", [blob])
+            .unwrap();
+
+        let index = SearchIndex::load(&path).unwrap();
+        assert!(index.has_titles());
+        let ids = |hits: &[SearchHit]| hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>();
+        let hits = index.search(&[0.0, 1.0], 2, None, 0.0, None).unwrap();
+        assert_eq!(ids(&hits), vec![2, 1]);
+        assert!((hits[0].score - 0.6).abs() < 1e-6);
+        assert_eq!(hits[0].title_score, Some(1.0));
+        assert_eq!(hits[1].title_score, None);
+
+        // Half the weight on the heading: 0.5 * 0.6 + 0.5 * 1.0.
+        let hits = index.search(&[0.0, 1.0], 2, None, 0.5, None).unwrap();
+        assert!((hits[0].score - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_excluded_terms_remove_or_demote() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[0.0, 1.0], [0.6, 0.8], [1.0, 0.0]]);
+        let index = SearchIndex::load(&path).unwrap();
+        let repealed = vec!["repealed".to_string()];
+        assert!(index.exclusion(&repealed, ExcludeMode::Remove).is_err());
+        assert!(index.exclusion(&[], ExcludeMode::Remove).is_ok());
+
+        let conn = Connection::open(&path).unwrap();
+        let texts = HashMap::from([
+            (1, "Repealed by Acts 2020, c. 1.".to_string()),
+            (2, "Reckless driving; penalty".to_string()),
+        ]);
+        db::fts::write_fts(&conn, &texts).unwrap();
+        let ids = |hits: &[SearchHit]| hits.iter().map(|h| h.node.node_id).collect::<Vec<_>>();
+
+        let remove = index.exclusion(&repealed, ExcludeMode::Remove).unwrap();
+        let hits = index
+            .search(&[0.0, 1.0], 3, None, 0.0, Some(&remove))
+            .unwrap();
+        assert_eq!(ids(&hits), vec![2, 3]);
+
+        // Demoted, node 1 (cosine 1.0) falls below node 2 (0.8).
+        let demote = index.exclusion(&repealed, ExcludeMode::Demote).unwrap();
+        let hits = index
+            .search(&[0.0, 1.0], 3, None, 0.0, Some(&demote))
+            .unwrap();
+        assert_eq!(ids(&hits), vec![2, 1, 3]);
+        assert!(hits[1].demoted && !hits[0].demoted);
+        assert!((hits[1].score - DEMOTION).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_without_invalidating_readers() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.db"), dir.path().join("b.db"));
+        write_db(&a, &[[1.0, 0.0]]);
+        write_db(&b, &[[1.0, 0.0], [0.0, 1.0]]);
+
+        let handle = Arc::new(IndexHandle::new(SearchIndex::load(&a).unwrap()));
+        let in_flight = handle.current();
+        handle.reload(Some(b.clone())).await.unwrap();
+
+        assert_eq!(in_flight.len(), 1);
+        assert_eq!(handle.current().len(), 2);
+        assert_eq!(handle.current().path, b);
+        assert!(handle.reload(Some(dir.path().join("missing.db"))).await.is_err());
+        assert_eq!(handle.current().len(), 2);
+    }
+
+    #[test]
+    fn test_node_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.db");
+        write_db(&path, &[[1.0, 0.0], [0.0, 1.0], [1.0, 1.0]]);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE edges (from_id INTEGER, to_id INTEGER, rel_type TEXT, weight REAL);
+            INSERT INTO nodes VALUES (10, 'virginia_code', '1', 0, 'title'),
+                                     (11, 'virginia_code', '1:1', 0, 'chapter');
+            INSERT INTO edges VALUES (10, 11, 'contains', NULL), (11, 1, 'contains', NULL),
+                                     (1, 2, 'cites', 1), (3, 1, 'cites', 2);
+            CREATE TABLE edge_provenance (from_id INTEGER, to_id INTEGER, rel_type TEXT,
+                                          chunk_idx INTEGER, char_start INTEGER,
+                                          char_end INTEGER, matched TEXT);
+            INSERT INTO edge_provenance VALUES (3, 1, 'cites', 0, 4, 9, '§ 1-1');
+            ",
+        )
+        .unwrap();
+
+        let ctx = node_context(&path, 1, 10).unwrap().unwrap();
+        let crumbs: Vec<i64> = ctx.breadcrumbs.iter().map(|n| n.node_id).collect();
+        assert_eq!(crumbs, vec![10, 11]);
+        let neighbours: Vec<(i64, &str)> = ctx
+            .neighbours
+            .iter()
+            .map(|n| (n.node.node_id, n.direction))
+            .collect();
+        assert_eq!(neighbours, vec![(2, "out"), (3, "in")]);
+        assert!(ctx.neighbours[0].provenance.is_empty());
+        assert_eq!(ctx.neighbours[1].provenance[0].matched, "§ 1-1");
+        assert!(node_context(&path, 99, 10).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corpora_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.db"), dir.path().join("b.db"));
+        write_db(&a, &[[1.0, 0.0]]);
+        write_db(&b, &[[1.0, 0.0], [0.0, 1.0]]);
+
+        let mut corpora = Corpora::default();
+        assert!(corpora.get(None).is_err());
+        corpora.insert("virginia".into(), SearchIndex::load(&a).unwrap()).unwrap();
+        corpora.insert("maryland".into(), SearchIndex::load(&b).unwrap()).unwrap();
+        assert!(corpora.insert("virginia".into(), SearchIndex::load(&a).unwrap()).is_err());
+
+        assert_eq!(corpora.get(None).unwrap().current().len(), 1);
+        assert_eq!(corpora.get(Some("maryland")).unwrap().current().len(), 2);
+        assert!(corpora.get(Some("federal")).is_err());
+
+        assert_eq!(
+            parse_corpus_arg("maryland=/data/md.db").unwrap(),
+            ("maryland".into(), PathBuf::from("/data/md.db"))
+        );
+        assert!(parse_corpus_arg("/data/md.db").is_err());
+    }
+}
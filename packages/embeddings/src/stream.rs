@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tempfile::TempDir;
+use tracing::info;
+
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Marker accepted by `--input` / `--output` for stdin / stdout.
+pub const STDIO: &str = "-";
+
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// An input database read from stdin and staged on local disk, since SQLite
+/// needs random access. The file is removed when this is dropped.
+pub struct StagedInput {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl StagedInput {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Copy an SQLite database from stdin into a temporary file.
+pub fn stage_stdin() -> Result<StagedInput> {
+    let dir = tempfile::Builder::new()
+        .prefix("proseva-input-")
+        .tempdir()?;
+    let path = dir.path().join("input.db");
+
+    let mut file = File::create(&path)?;
+    let mut stdin = std::io::stdin().lock();
+    let mut header = [0u8; 16];
+    stdin
+        .read_exact(&mut header)
+        .context("stdin ended before an SQLite header was read")?;
+    if &header != SQLITE_MAGIC {
+        anyhow::bail!("stdin is not an SQLite database (bad header)");
+    }
+    file.write_all(&header)?;
+    let copied = std::io::copy(&mut stdin, &mut file)? + header.len() as u64;
+    file.sync_all()?;
+
+    info!(bytes = copied, "Staged input from stdin");
+    Ok(StagedInput { _dir: dir, path })
+}
+
+enum Sink {
+    /// The process's original stdout, saved before fd 1 was pointed at stderr.
+    Stdout(File),
+    /// A named pipe (FIFO) given as `--output`.
+    Pipe(PathBuf),
+}
+
+/// Streaming destination for the output database. The pipeline builds into a
+/// temporary file (SQLite can't write to a pipe) and [`StreamOutput::emit`]
+/// copies the finished bytes to stdout or the FIFO.
+pub struct StreamOutput {
+    _dir: TempDir,
+    staging: PathBuf,
+    sink: Sink,
+}
+
+impl StreamOutput {
+    /// Returns a streaming output if `output` is `-` or an existing FIFO.
+    ///
+    /// For stdout, fd 1 is redirected to stderr for the rest of the run so the
+    /// console telemetry can't corrupt the database stream.
+    pub fn detect(output: Option<&Path>) -> Result<Option<Self>> {
+        let Some(output) = output else {
+            return Ok(None);
+        };
+        let sink = if is_stdio(output) {
+            Sink::Stdout(divert_stdout()?)
+        } else if is_fifo(output) {
+            Sink::Pipe(output.to_path_buf())
+        } else {
+            return Ok(None);
+        };
+
+        let dir = tempfile::Builder::new()
+            .prefix("proseva-output-")
+            .tempdir()?;
+        let staging = dir.path().join("graph.sqlite.db");
+        Ok(Some(Self {
+            _dir: dir,
+            staging,
+            sink,
+        }))
+    }
+
+    /// Path the pipeline should treat as `--output`.
+    pub fn staging_path(&self) -> &Path {
+        &self.staging
+    }
+
+    /// Path to take the build lock on: the FIFO, since the staging file is
+    /// private to this run. Stdout has no shared destination to guard.
+    pub fn lock_target(&self) -> Option<&Path> {
+        match &self.sink {
+            Sink::Stdout(_) => None,
+            Sink::Pipe(path) => Some(path),
+        }
+    }
+
+    /// Human-readable name of the destination, for logs and reports.
+    pub fn describe(&self) -> String {
+        match &self.sink {
+            Sink::Stdout(_) => "<stdout>".to_string(),
+            Sink::Pipe(path) => path.display().to_string(),
+        }
+    }
+
+    /// Copy the finished database to the sink. The output connection must be
+    /// closed first so the WAL has been checkpointed into the file.
+    pub fn emit(self) -> Result<u64> {
+        let mut db = File::open(&self.staging)
+            .with_context(|| format!("Output DB not found at {}", self.staging.display()))?;
+        let written = match self.sink {
+            Sink::Stdout(mut out) => {
+                let n = std::io::copy(&mut db, &mut out)?;
+                out.flush()?;
+                n
+            }
+            Sink::Pipe(path) => {
+                let mut out = std::fs::OpenOptions::new().write(true).open(&path)?;
+                let n = std::io::copy(&mut db, &mut out)?;
+                out.flush()?;
+                n
+            }
+        };
+        Ok(written)
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path)
+        .map(|m| m.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Duplicate the real stdout for later use, then point fd 1 at stderr.
+#[cfg(unix)]
+fn divert_stdout() -> Result<File> {
+    use std::os::fd::FromRawFd;
+
+    std::io::stdout().flush()?;
+    // SAFETY: dup/dup2 on the process's standard descriptors; the duplicated
+    // fd is owned exclusively by the returned File.
+    unsafe {
+        let saved = libc::dup(libc::STDOUT_FILENO);
+        if saved < 0 {
+            return Err(std::io::Error::last_os_error()).context("dup(stdout) failed");
+        }
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error()).context("dup2(stderr, stdout) failed");
+        }
+        Ok(File::from_raw_fd(saved))
+    }
+}
+
+#[cfg(not(unix))]
+fn divert_stdout() -> Result<File> {
+    anyhow::bail!("--output - is only supported on Unix")
+}
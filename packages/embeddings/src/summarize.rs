@@ -0,0 +1,555 @@
+//! Optional enrichment stage (`[summaries]` in `--config`): ask an
+//! OpenAI-compatible chat-completions endpoint for a one- or two-sentence
+//! plain-English summary of each section node, for the app's layperson mode.
+//!
+//! Summaries land in the output's `summaries` table with the hash of the text
+//! they summarize. Every answer is also kept in a cache DB keyed by model,
+//! prompt and text, so a rebuild (which starts a fresh output) only asks about
+//! new or edited texts. Requests go out `batch_size` at a time and each batch
+//! is committed to both before the next is sent, so a stage that died or was
+//! stopped picks up where it left off: by rebuilding, or with
+//! `build-summaries` against the existing output.
+//!
+//! ```toml
+//! [summaries]
+//! endpoint = "https://api.openai.com/v1/chat/completions"
+//! model = "gpt-4o-mini"
+//! api_key_env = "OPENAI_API_KEY"
+//! embed = true
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use proseva_embeddings::db::incremental::text_hash;
+use proseva_embeddings::db::spill::TextSpill;
+use proseva_embeddings::embed::Embedder;
+
+use crate::interrupt;
+
+const DEFAULT_PROMPT: &str = "Summarize this section of the Code of Virginia in one or two \
+     plain-English sentences for someone without legal training. Say what it requires, \
+     allows or forbids; do not give legal advice.";
+
+/// Texts read at once while matching targets against the output and cache.
+const TEXT_WINDOW: usize = 1024;
+
+/// The node texts to summarize: in memory, or parked on disk by
+/// `--max-memory` and read back a window at a time.
+#[derive(Clone, Copy)]
+pub enum Texts<'a> {
+    Map(&'a HashMap<i64, String>),
+    Spilled(&'a TextSpill),
+}
+
+impl Texts<'_> {
+    fn ids(self) -> Result<Vec<i64>> {
+        match self {
+            Texts::Map(texts) => Ok(texts.keys().copied().collect()),
+            Texts::Spilled(spill) => spill.ids(),
+        }
+    }
+
+    /// The texts of `ids`, in order.
+    fn get(self, ids: &[i64]) -> Result<Vec<String>> {
+        match self {
+            Texts::Map(texts) => Ok(ids.iter().map(|id| texts[id].clone()).collect()),
+            Texts::Spilled(spill) => spill.get(ids),
+        }
+    }
+}
+
+/// Attempts per request (per node, for summaries) before giving up.
+const ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryConfig {
+    /// Chat-completions URL, e.g. `http://localhost:11434/v1/chat/completions`.
+    pub endpoint: String,
+    pub model: String,
+    /// Environment variable holding a bearer token; unset sends no
+    /// `Authorization` header.
+    pub api_key_env: Option<String>,
+    /// Node types to summarize.
+    pub node_types: Vec<String>,
+    /// Requests in flight at once.
+    pub batch_size: usize,
+    pub max_tokens: u32,
+    /// System prompt; the node text is the user message.
+    pub prompt: String,
+    /// Cache DB (default: `summaries-cache.db` beside the output).
+    pub cache: Option<PathBuf>,
+    /// Also embed each summary with the build's model, into `summary_embeddings`.
+    pub embed: bool,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            model: String::new(),
+            api_key_env: None,
+            node_types: vec!["section".into()],
+            batch_size: 8,
+            max_tokens: 120,
+            prompt: DEFAULT_PROMPT.into(),
+            cache: None,
+            embed: false,
+        }
+    }
+}
+
+impl SummaryConfig {
+    /// Fail before any pass runs on a config that can't make a request.
+    pub fn validate(&self) -> Result<()> {
+        if self.endpoint.is_empty() || self.model.is_empty() {
+            bail!("[summaries] needs an endpoint and a model");
+        }
+        if self.batch_size == 0 {
+            bail!("[summaries] batch_size must be at least 1");
+        }
+        if let Some(ref var) = self.api_key_env {
+            std::env::var(var)
+                .with_context(|| format!("[summaries] api_key_env: ${var} is not set"))?;
+        }
+        Ok(())
+    }
+
+    pub fn cache_path(&self, output: &Path) -> PathBuf {
+        self.cache
+            .clone()
+            .unwrap_or_else(|| output.with_file_name("summaries-cache.db"))
+    }
+
+    /// Cache key: a different model, prompt or length limit asks again.
+    fn cache_key(&self, text: &str) -> String {
+        text_hash(&format!(
+            "{}\n{}\n{}\n{}",
+            self.model, self.max_tokens, self.prompt, text
+        ))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SummaryCounts {
+    /// Nodes of the configured types with a summary after the stage.
+    pub summaries: usize,
+    /// Answered by the endpoint in this run.
+    pub generated: usize,
+    /// Taken from the cache.
+    pub cached: usize,
+    /// Given up on after every attempt; asked again next run.
+    pub failed: usize,
+}
+
+/// Summarize every node in `texts` whose type is configured and whose
+/// current text has no summary yet.
+pub async fn run(
+    conn: &Connection,
+    config: &SummaryConfig,
+    cache_path: &Path,
+    texts: Texts<'_>,
+) -> Result<SummaryCounts> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS summaries (
+            node_id   INTEGER PRIMARY KEY,
+            text_hash TEXT NOT NULL,
+            model     TEXT NOT NULL,
+            summary   TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS summary_embeddings (
+            node_id   INTEGER PRIMARY KEY,
+            embedding BLOB NOT NULL
+        );
+        ",
+    )?;
+    let cache = Connection::open(cache_path)
+        .with_context(|| format!("Failed to open summary cache {}", cache_path.display()))?;
+    cache.execute_batch(
+        "CREATE TABLE IF NOT EXISTS summary_cache (
+            key     TEXT PRIMARY KEY,
+            summary TEXT NOT NULL
+        )",
+    )?;
+
+    let mut counts = SummaryCounts::default();
+    let mut todo = Vec::new();
+    {
+        let summarized = summarized(conn)?;
+        let mut lookup = cache.prepare("SELECT summary FROM summary_cache WHERE key = ?1")?;
+        for window in targets(conn, config, texts)?.chunks(TEXT_WINDOW) {
+            let mut hits = Vec::new();
+            for (&node_id, text) in window.iter().zip(texts.get(window)?) {
+                if text.is_empty() || summarized.get(&node_id) == Some(&text_hash(&text)) {
+                    continue;
+                }
+                let cached: Option<String> = lookup
+                    .query_row([config.cache_key(&text)], |r| r.get(0))
+                    .optional()?;
+                match cached {
+                    Some(summary) => hits.push((node_id, text, summary)),
+                    None => todo.push(node_id),
+                }
+            }
+            let hits: Vec<(i64, &str, String)> =
+                hits.iter().map(|(id, t, s)| (*id, t.as_str(), s.clone())).collect();
+            store_summaries(conn, config, &hits)?;
+            counts.cached += hits.len();
+        }
+    }
+    info!(
+        requests = todo.len(),
+        cached = counts.cached,
+        cache = %cache_path.display(),
+        "Summarizing nodes"
+    );
+
+    let client = reqwest::Client::new();
+    let api_key = match config.api_key_env {
+        Some(ref var) => Some(std::env::var(var)?),
+        None => None,
+    };
+    let interrupt = interrupt::Interrupt::arm();
+    let stop = interrupt.flag();
+    for batch in todo.chunks(config.batch_size) {
+        let mut requests = tokio::task::JoinSet::new();
+        for (&node_id, text) in batch.iter().zip(texts.get(batch)?) {
+            let (client, config, api_key) = (client.clone(), config.clone(), api_key.clone());
+            requests.spawn(async move {
+                let summary = request(&client, &config, api_key.as_deref(), &text).await;
+                (node_id, text, summary)
+            });
+        }
+        let mut done = Vec::new();
+        let mut first_error = None;
+        while let Some(joined) = requests.join_next().await {
+            let (node_id, text, summary) = joined?;
+            match summary {
+                Ok(summary) => done.push((node_id, text, summary)),
+                Err(err) => {
+                    warn!(node_id, error = %format!("{err:#}"), "Summary request failed");
+                    counts.failed += 1;
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        // A first batch with no answer at all is a bad endpoint or key, not
+        // bad texts; stop rather than fail every node the same way.
+        if let (true, Some(err)) = (done.is_empty() && counts.generated == 0, first_error) {
+            return Err(err.context("Summary endpoint answered no request in the first batch"));
+        }
+        let done: Vec<(i64, &str, String)> = done
+            .iter()
+            .map(|(id, t, s)| (*id, t.as_str(), s.clone()))
+            .collect();
+        store_summaries(conn, config, &done)?;
+        store_cache(&cache, config, &done)?;
+        counts.generated += done.len();
+        if stop.load(Ordering::SeqCst) {
+            bail!(
+                "Interrupted after {} summaries; they are cached, run build-summaries to finish",
+                counts.generated
+            );
+        }
+    }
+    drop(interrupt);
+
+    counts.summaries = conn.query_row("SELECT COUNT(*) FROM summaries", [], |r| r.get(0))?;
+    Ok(counts)
+}
+
+/// Nodes of the configured types that have a text, in id order; those whose
+/// current text already has a summary are skipped later, as their texts are
+/// read.
+fn targets(conn: &Connection, config: &SummaryConfig, texts: Texts<'_>) -> Result<Vec<i64>> {
+    let node_types: HashSet<&str> = config.node_types.iter().map(String::as_str).collect();
+    let mut wanted = HashSet::new();
+    {
+        let mut stmt = conn.prepare("SELECT id, node_type FROM nodes")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (id, node_type) = row?;
+            if node_types.contains(node_type.as_str()) {
+                wanted.insert(id);
+            }
+        }
+    }
+    let mut targets = texts.ids()?;
+    targets.retain(|id| wanted.contains(id));
+    targets.sort();
+    Ok(targets)
+}
+
+/// The text hash of every node that has a summary.
+fn summarized(conn: &Connection) -> Result<HashMap<i64, String>> {
+    let mut stmt = conn.prepare("SELECT node_id, text_hash FROM summaries")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Write summaries to the output, dropping embeddings of ones they replace.
+fn store_summaries(
+    conn: &Connection,
+    config: &SummaryConfig,
+    done: &[(i64, &str, String)],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO summaries (node_id, text_hash, model, summary)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut stale = tx.prepare("DELETE FROM summary_embeddings WHERE node_id = ?1")?;
+        for (node_id, text, summary) in done {
+            insert.execute(rusqlite::params![
+                node_id,
+                text_hash(text),
+                config.model,
+                summary
+            ])?;
+            stale.execute([node_id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn store_cache(
+    cache: &Connection,
+    config: &SummaryConfig,
+    done: &[(i64, &str, String)],
+) -> Result<()> {
+    let tx = cache.unchecked_transaction()?;
+    {
+        let mut insert =
+            tx.prepare("INSERT OR REPLACE INTO summary_cache (key, summary) VALUES (?1, ?2)")?;
+        for (_, text, summary) in done {
+            insert.execute(rusqlite::params![config.cache_key(text), summary])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// The summary of one node's text.
+async fn request(
+    client: &reqwest::Client,
+    config: &SummaryConfig,
+    api_key: Option<&str>,
+    text: &str,
+) -> Result<String> {
+    let body = serde_json::json!({
+        "model": config.model,
+        "max_tokens": config.max_tokens,
+        "temperature": 0,
+        "messages": [
+            {"role": "system", "content": config.prompt},
+            {"role": "user", "content": text},
+        ],
+    });
+    chat(client, &config.endpoint, api_key, &body).await
+}
+
+/// One chat-completions request (`body` as the endpoint takes it), retried
+/// with backoff on rate limits, server errors and dropped connections.
+/// Returns the first choice's message.
+pub async fn chat(
+    client: &reqwest::Client,
+    endpoint: &str,
+    api_key: Option<&str>,
+    body: &serde_json::Value,
+) -> Result<String> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut req = client
+            .post(endpoint)
+            .timeout(Duration::from_secs(120))
+            .json(body);
+        if let Some(key) = api_key {
+            req = req.bearer_auth(key);
+        }
+        let retryable = match req.send().await {
+            Ok(resp) if resp.status().is_success() => {
+                let json: serde_json::Value = resp.json().await?;
+                return completion_text(&json)
+                    .context("Chat response has no choices[0].message.content");
+            }
+            Ok(resp) => {
+                let status = resp.status();
+                let err =
+                    anyhow::anyhow!("HTTP {}: {}", status, resp.text().await.unwrap_or_default());
+                if !(status.as_u16() == 429 || status.is_server_error()) {
+                    return Err(err);
+                }
+                err
+            }
+            Err(err) => err.into(),
+        };
+        if attempt >= ATTEMPTS {
+            return Err(retryable);
+        }
+        tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+    }
+}
+
+fn completion_text(json: &serde_json::Value) -> Option<String> {
+    let text = json["choices"][0]["message"]["content"].as_str()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Embed every summary that has no embedding yet. Returns how many were written.
+pub async fn embed_summaries(conn: &Connection, embedder: &mut Embedder) -> Result<usize> {
+    let (ids, texts): (Vec<i64>, Vec<String>) = {
+        let mut stmt = conn.prepare(
+            "SELECT s.node_id, s.summary FROM summaries s
+             LEFT JOIN summary_embeddings e ON e.node_id = s.node_id
+             WHERE e.node_id IS NULL
+             ORDER BY s.node_id",
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .unzip()
+    };
+    let outcome = embedder
+        .embed_batched(&ids, &texts, |ids, vectors, _| {
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut insert = tx.prepare(
+                    "INSERT OR REPLACE INTO summary_embeddings (node_id, embedding) VALUES (?1, ?2)",
+                )?;
+                for (node_id, vector) in ids.iter().zip(vectors) {
+                    let bytes: Vec<u8> = vector.iter().flat_map(|f| f.to_le_bytes()).collect();
+                    insert.execute(rusqlite::params![node_id, bytes])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+    if !outcome.failed.is_empty() {
+        warn!(
+            summaries = outcome.failed.len(),
+            "Summaries could not be embedded; they are retried on the next run"
+        );
+    }
+    Ok(outcome.written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    /// A chat-completions stand-in that answers with the first word of the
+    /// user message and counts requests.
+    async fn serve(calls: Arc<AtomicUsize>) -> String {
+        let app = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let text = body["messages"][1]["content"].as_str().unwrap_or("").to_string();
+                let word = text.split_whitespace().next().unwrap_or("").to_string();
+                async move {
+                    axum::Json(serde_json::json!({
+                        "choices": [{"message": {"role": "assistant", "content": format!(" About {word}. ")}}]
+                    }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/v1/chat/completions")
+    }
+
+    fn output(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE nodes (id INTEGER PRIMARY KEY, source TEXT, source_id TEXT,
+                                 chunk_idx INTEGER, node_type TEXT);
+             INSERT INTO nodes VALUES (1, 'virginia_code', '1-1', 0, 'section'),
+                                      (2, 'virginia_code', '1-2', 0, 'section'),
+                                      (3, 'courts', '7', 0, 'court');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_summaries_are_cached_and_resumed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = SummaryConfig {
+            endpoint: serve(calls.clone()).await,
+            model: "test".into(),
+            batch_size: 1,
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache.db");
+        let texts: HashMap<i64, String> = [
+            (1, "Wills must be signed.".to_string()),
+            (2, "Deeds must be recorded.".to_string()),
+            (3, "Fairfax Circuit Court".to_string()),
+        ]
+        .into();
+
+        let conn = output(&dir.path().join("a.db"));
+        let counts = run(&conn, &config, &cache, Texts::Map(&texts)).await.unwrap();
+        let expected = SummaryCounts {
+            summaries: 2,
+            generated: 2,
+            cached: 0,
+            failed: 0,
+        };
+        assert_eq!(counts, expected);
+        let summary: String = conn
+            .query_row("SELECT summary FROM summaries WHERE node_id = 2", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(summary, "About Deeds.");
+
+        // Nothing left to do in the same output; a fresh one reads the cache.
+        let again = run(&conn, &config, &cache, Texts::Map(&texts)).await.unwrap();
+        assert_eq!((again.generated, again.cached), (0, 0));
+        // Spilled texts are read back from disk as they are needed.
+        let fresh = output(&dir.path().join("b.db"));
+        let spill = TextSpill::create(dir.path(), texts.clone()).unwrap();
+        let counts = run(&fresh, &config, &cache, Texts::Spilled(&spill)).await.unwrap();
+        assert_eq!((counts.generated, counts.cached), (0, 2));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // An edited text is asked about again.
+        let mut edited = texts.clone();
+        edited.insert(1, "Wills must be witnessed.".into());
+        let counts = run(&fresh, &config, &cache, Texts::Map(&edited)).await.unwrap();
+        assert_eq!((counts.generated, counts.summaries), (1, 2));
+    }
+
+    #[test]
+    fn test_cache_key_and_response() {
+        let config = SummaryConfig::default();
+        let other = SummaryConfig {
+            prompt: "Be brief.".into(),
+            ..Default::default()
+        };
+        assert_ne!(config.cache_key("text"), other.cache_key("text"));
+        assert!(SummaryConfig::default().validate().is_err());
+
+        let json = serde_json::json!({"choices": [{"message": {"content": "  Short. "}}]});
+        assert_eq!(completion_text(&json).as_deref(), Some("Short."));
+        assert_eq!(completion_text(&serde_json::json!({"choices": []})), None);
+    }
+}
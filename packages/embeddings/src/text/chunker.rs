@@ -1,9 +1,54 @@
 /// Approximate token count by splitting on whitespace.
 /// This is a rough heuristic (~1 token per word for English).
-fn approx_token_count(text: &str) -> usize {
+pub fn approx_token_count(text: &str) -> usize {
     text.split_whitespace().count()
 }
 
+/// Chunk size and overlap for long texts, in approximate tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkConfig {
+    pub max_tokens: usize,
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: 500,
+            overlap_tokens: 50,
+        }
+    }
+}
+
+/// Tokens of a model's sequence length kept free for the prompt prefix and
+/// special tokens when sizing chunks from it.
+const CHUNK_HEADROOM: usize = 12;
+
+impl ChunkConfig {
+    /// Default chunk size for a model that reads `max_seq_len` tokens: 500
+    /// for the usual 512.
+    pub fn budget(max_seq_len: usize) -> usize {
+        max_seq_len.saturating_sub(CHUNK_HEADROOM).max(1)
+    }
+
+    pub fn new(max_tokens: usize, overlap_tokens: usize) -> anyhow::Result<Self> {
+        if max_tokens == 0 {
+            anyhow::bail!("Chunk size must be at least 1 token");
+        }
+        if overlap_tokens >= max_tokens {
+            anyhow::bail!(
+                "Chunk overlap ({}) must be smaller than the chunk size ({})",
+                overlap_tokens,
+                max_tokens
+            );
+        }
+        Ok(Self {
+            max_tokens,
+            overlap_tokens,
+        })
+    }
+}
+
 /// A chunk of text with its byte offsets into the original input.
 #[derive(Debug, Clone)]
 pub struct ChunkSpan {
@@ -89,6 +134,83 @@ pub fn chunk_text(text: &str, max_tokens: usize, overlap_tokens: usize) -> Vec<C
     chunks
 }
 
+/// Like [`chunk_text`], but split at section boundaries first: `starts` are
+/// the byte offsets where headings begin. Whole sections are packed into
+/// chunks of up to `max_tokens`; a section longer than that is chunked on
+/// its own. A text that fits in one chunk stays one chunk.
+pub fn chunk_sections(
+    text: &str,
+    starts: &[usize],
+    max_tokens: usize,
+    overlap_tokens: usize,
+) -> Vec<ChunkSpan> {
+    if approx_token_count(text) <= max_tokens {
+        return chunk_text(text, max_tokens, overlap_tokens);
+    }
+    let mut bounds: Vec<usize> = starts
+        .iter()
+        .copied()
+        .filter(|&s| s > 0 && s < text.len() && text.is_char_boundary(s))
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds.insert(0, 0);
+    bounds.push(text.len());
+
+    let mut chunks = Vec::new();
+    // The sections packed so far, as a byte range
+    let mut packed: Option<(usize, usize)> = None;
+    let mut packed_len = 0usize;
+    let flush = |range: &mut Option<(usize, usize)>, chunks: &mut Vec<ChunkSpan>| {
+        if let Some((start, end)) = range.take() {
+            if let Some(chunk) = trimmed(text, start, end) {
+                chunks.push(chunk);
+            }
+        }
+    };
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let len = approx_token_count(&text[start..end]);
+        if len > max_tokens {
+            flush(&mut packed, &mut chunks);
+            packed_len = 0;
+            chunks.extend(
+                chunk_text(&text[start..end], max_tokens, overlap_tokens)
+                    .into_iter()
+                    .map(|c| ChunkSpan {
+                        text: c.text,
+                        char_start: start + c.char_start,
+                        char_end: start + c.char_end,
+                    }),
+            );
+            continue;
+        }
+        if packed_len + len > max_tokens {
+            flush(&mut packed, &mut chunks);
+            packed_len = 0;
+        }
+        packed = Some((packed.map_or(start, |(s, _)| s), end));
+        packed_len += len;
+    }
+    flush(&mut packed, &mut chunks);
+    chunks
+}
+
+/// `text[start..end]` without surrounding whitespace, if anything is left.
+fn trimmed(text: &str, start: usize, end: usize) -> Option<ChunkSpan> {
+    let slice = &text[start..end];
+    let body = slice.trim();
+    if body.is_empty() {
+        return None;
+    }
+    let char_start = start + (slice.len() - slice.trim_start().len());
+    Some(ChunkSpan {
+        text: body.to_string(),
+        char_start,
+        char_end: char_start + body.len(),
+    })
+}
+
 fn spans_to_chunk(spans: &[&SentenceSpan]) -> ChunkSpan {
     let text = spans
         .iter()
@@ -197,6 +319,35 @@ fn split_sentences(text: &str) -> Vec<SentenceSpan> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_chunk_sections_split_at_headings() {
+        let text = "Summary one two three. Background four five. Fiscal six seven eight nine.";
+        let starts: Vec<usize> = ["Background", "Fiscal"]
+            .iter()
+            .map(|h| text.find(h).unwrap())
+            .collect();
+        let chunks = chunk_sections(text, &starts, 7, 1);
+        let texts: Vec<_> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Summary one two three. Background four five.",
+                "Fiscal six seven eight nine."
+            ]
+        );
+        for c in &chunks {
+            assert_eq!(&text[c.char_start..c.char_end], c.text);
+        }
+
+        // An oversized section is chunked on its own
+        let chunks = chunk_sections(text, &starts, 3, 1);
+        assert!(chunks.iter().all(|c| approx_token_count(&c.text) <= 3));
+        assert!(chunks.iter().any(|c| c.text.starts_with("Fiscal")));
+
+        // Short texts stay whole
+        assert_eq!(chunk_sections(text, &starts, 100, 10).len(), 1);
+    }
+
     #[test]
     fn test_short_text_no_chunking() {
         let text = "This is short.";
@@ -241,6 +392,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_chunk_config_validation() {
+        assert_eq!(ChunkConfig::new(500, 50).unwrap(), ChunkConfig::default());
+        assert_eq!(ChunkConfig::budget(512), 500);
+        assert_eq!(ChunkConfig::budget(2048), 2036);
+        assert!(ChunkConfig::new(0, 0).is_err());
+        assert!(ChunkConfig::new(50, 50).is_err());
+    }
+
     #[test]
     fn test_sentence_split_offsets() {
         let text = "Hello world. Goodbye world.";
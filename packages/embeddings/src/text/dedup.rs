@@ -0,0 +1,111 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// A bare enumerator such as "A.", "12.", "(b)" or "iv)".
+static ENUMERATOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\(?[0-9A-Za-z]{1,4}[.)]$").unwrap());
+
+/// Paragraphs shorter than this are never collapsed: short repeats like
+/// "Repealed." or "No fee shall be charged." are usually meaningful.
+const MIN_WORDS: usize = 8;
+
+/// Split whitespace-normalized text into paragraph-like segments at
+/// sentence-ending punctuation (`.`, `;`, `?`, `!`) followed by a space.
+/// A bare enumerator stays attached to the text it introduces.
+fn segments(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if !matches!(c, '.' | ';' | '?' | '!') || bytes.get(i + 1) != Some(&b' ') {
+            continue;
+        }
+        let segment = text[start..=i].trim();
+        if ENUMERATOR_RE.is_match(segment) {
+            continue;
+        }
+        out.push(segment);
+        start = i + 1;
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        out.push(rest);
+    }
+    out
+}
+
+/// Collapse runs of a paragraph repeated back to back within one text. Each
+/// run is kept once and annotated with its length, e.g. `"... [repeated 3
+/// times]"`, so chunk budgets aren't spent on boilerplate. Only byte-identical
+/// paragraphs count as repeats, enumerators included, so distinct subsections
+/// that share their wording ("A. ..." and "B. ...") are all kept.
+///
+/// Returns the collapsed text and the number of copies removed. Text with no
+/// repeats is returned unchanged.
+pub fn collapse_repeats(text: &str) -> (String, usize) {
+    let segments = segments(text);
+    let mut kept = Vec::with_capacity(segments.len());
+    let mut removed = 0;
+    let mut i = 0;
+    while i < segments.len() {
+        let segment = segments[i];
+        let run = segments[i..].iter().take_while(|&&s| s == segment).count();
+        if run > 1 && segment.split_whitespace().count() >= MIN_WORDS {
+            kept.push(format!("{} [repeated {} times]", segment, run));
+            removed += run - 1;
+        } else {
+            kept.extend(segments[i..i + run].iter().map(|s| s.to_string()));
+        }
+        i += run;
+    }
+    if removed == 0 {
+        return (text.to_string(), 0);
+    }
+    (kept.join(" "), removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapses_identical_runs() {
+        let text = "A. The clerk shall collect a fee of five dollars for each filing. \
+                    A. The clerk shall collect a fee of five dollars for each filing. \
+                    A. The clerk shall collect a fee of five dollars for each filing. \
+                    B. The fee shall be paid into the general fund of the locality. \
+                    A. The clerk shall collect a fee of five dollars for each filing.";
+        let (collapsed, removed) = collapse_repeats(text);
+        assert_eq!(removed, 2);
+        assert_eq!(
+            collapsed,
+            "A. The clerk shall collect a fee of five dollars for each filing. [repeated 3 times] \
+             B. The fee shall be paid into the general fund of the locality. \
+             A. The clerk shall collect a fee of five dollars for each filing."
+        );
+    }
+
+    #[test]
+    fn test_distinct_enumerated_subsections_kept() {
+        let text = "A. The clerk shall collect a fee of five dollars for each filing. \
+                    B. The clerk shall collect a fee of five dollars for each filing. \
+                    C. The clerk shall collect a fee of five dollars for each filing.";
+        assert_eq!(collapse_repeats(text), (text.to_string(), 0));
+    }
+
+    #[test]
+    fn test_short_and_unique_text_untouched() {
+        let text = "Repealed. Repealed. Each sentence here is distinct from the next one over.";
+        assert_eq!(collapse_repeats(text), (text.to_string(), 0));
+        assert_eq!(collapse_repeats(""), (String::new(), 0));
+    }
+
+    #[test]
+    fn test_enumerator_stays_with_paragraph() {
+        assert_eq!(
+            segments("1. First item text; (a) sub item. Done"),
+            vec!["1. First item text;", "(a) sub item.", "Done"]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Word (`.docx`) files as HTML, for `--extra-documents`. Paragraphs styled
+//! as headings (`Heading 1`-`Heading 6`, `Title`, or with an outline level)
+//! become `<h1>`-`<h6>`, so document chunking can split at them; every other
+//! paragraph, table cells included, becomes a `<p>`.
+//!
+//! Only the main document part is read: no headers, footers, footnotes or
+//! comments. Elements are matched by the WordprocessingML namespace, not the
+//! `w:` prefix Word happens to use, since other writers bind it differently.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+use anyhow::{Context, Result};
+use quick_xml::escape::{escape, resolve_predefined_entity};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{LocalName, Namespace, QName, ResolveResult};
+use quick_xml::NsReader;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Largest archive part inflated, against zip bombs.
+const MAX_PART_BYTES: u64 = 64 * 1024 * 1024;
+
+const DOCUMENT_PART: &str = "word/document.xml";
+const STYLES_PART: &str = "word/styles.xml";
+
+/// The namespace of the `w:` elements and attributes.
+const W_NS: &[u8] = b"http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+pub struct Docx {
+    /// The first heading, else the first paragraph.
+    pub title: Option<String>,
+    pub html: String,
+}
+
+/// Convert a `.docx` file's bytes to HTML.
+pub fn docx_to_html(bytes: &[u8]) -> Result<Docx> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).context("Not a .docx (zip) file")?;
+    let document = part(&mut archive, DOCUMENT_PART)?
+        .with_context(|| format!("No {} in the archive", DOCUMENT_PART))?;
+    let levels = match part(&mut archive, STYLES_PART)? {
+        Some(styles) => heading_styles(&styles)?,
+        None => HashMap::new(),
+    };
+    paragraphs_to_html(&document, &levels)
+}
+
+/// The inflated contents of `name`, if the archive has it.
+fn part(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<Vec<u8>>> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", name)),
+    };
+    let mut out = Vec::new();
+    file.take(MAX_PART_BYTES)
+        .read_to_end(&mut out)
+        .with_context(|| format!("Failed to inflate {}", name))?;
+    Ok(Some(out))
+}
+
+/// The local name of a resolved element or attribute name in the
+/// WordprocessingML namespace, e.g. `p` for `<w:p>`; `None` for any other.
+fn w_name<'n>((ns, local): (ResolveResult, LocalName<'n>)) -> Option<&'n [u8]> {
+    matches!(ns, ResolveResult::Bound(Namespace(n)) if n == W_NS).then(|| local.into_inner())
+}
+
+/// An element's WordprocessingML local name.
+fn element<'n>(reader: &NsReader<&[u8]>, name: QName<'n>) -> Option<&'n [u8]> {
+    w_name(reader.resolve_element(name))
+}
+
+/// `w:<name>` attribute of an element, e.g. `val` of `<w:pStyle w:val="Heading1"/>`.
+fn attr(reader: &NsReader<&[u8]>, e: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for a in e.attributes() {
+        let a = a?;
+        if w_name(reader.resolve_attribute(a.key)) == Some(name) {
+            return Ok(Some(a.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Heading level of an outline level (`0` is the top).
+fn outline_level(v: &str) -> Option<u8> {
+    let lvl: u8 = v.parse().ok()?;
+    (lvl < 9).then(|| (lvl + 1).min(6))
+}
+
+/// Heading level of a style's display name, e.g. `heading 2` → 2.
+fn named_level(name: &str) -> Option<u8> {
+    let name = name.trim().to_ascii_lowercase();
+    if name == "title" {
+        return Some(1);
+    }
+    let n: u8 = name.strip_prefix("heading")?.trim().parse().ok()?;
+    (1..=9).contains(&n).then(|| n.min(6))
+}
+
+/// Heading level of each paragraph style id in `styles.xml`.
+fn heading_styles(xml: &[u8]) -> Result<HashMap<String, u8>> {
+    let mut reader = NsReader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut levels = HashMap::new();
+    let mut style: Option<String> = None;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) if element(&reader, e.name()) == Some(b"style") => {
+                style = attr(&reader, &e, b"styleId")?;
+            }
+            Event::End(e) if element(&reader, e.name()) == Some(b"style") => style = None,
+            Event::Start(e) | Event::Empty(e) => {
+                let val = || attr(&reader, &e, b"val");
+                let level = match element(&reader, e.name()) {
+                    Some(b"name") => val()?.as_deref().and_then(named_level),
+                    Some(b"outlineLvl") => val()?.as_deref().and_then(outline_level),
+                    _ => None,
+                };
+                if let (Some(id), Some(level)) = (&style, level) {
+                    levels.entry(id.clone()).or_insert(level);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(levels)
+}
+
+/// `document.xml`'s paragraphs as `<h1>`-`<h6>` and `<p>` elements.
+fn paragraphs_to_html(xml: &[u8], styles: &HashMap<String, u8>) -> Result<Docx> {
+    let mut reader = NsReader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut html = String::new();
+    let (mut first_heading, mut first_paragraph) = (None, None);
+    let mut text = String::new();
+    let mut level: Option<u8> = None;
+    let mut in_text = false;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) if element(&reader, e.name()) == Some(b"p") => {
+                text.clear();
+                level = None;
+            }
+            Event::Start(e) if element(&reader, e.name()) == Some(b"t") => in_text = true,
+            Event::End(e) if element(&reader, e.name()) == Some(b"t") => in_text = false,
+            Event::Text(e) if in_text => text.push_str(&e.xml_content()?),
+            Event::GeneralRef(e) if in_text => {
+                if let Some(c) = e.resolve_char_ref()? {
+                    text.push(c);
+                } else if let Some(s) = resolve_predefined_entity(&e.decode()?) {
+                    text.push_str(s);
+                }
+            }
+            Event::Start(e) | Event::Empty(e) => match element(&reader, e.name()) {
+                Some(b"pStyle") => {
+                    if let Some(id) = attr(&reader, &e, b"val")? {
+                        level = level.or_else(|| styles.get(&id).copied()).or_else(|| {
+                            // Documents without styles.xml still use the built-in ids
+                            named_level(&id.to_ascii_lowercase().replace("heading", "heading "))
+                        });
+                    }
+                }
+                Some(b"outlineLvl") => {
+                    if let Some(v) = attr(&reader, &e, b"val")? {
+                        level = outline_level(&v).or(level);
+                    }
+                }
+                Some(b"tab" | b"br" | b"cr") => text.push(' '),
+                _ => {}
+            },
+            Event::End(e) if element(&reader, e.name()) == Some(b"p") => {
+                let paragraph = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !paragraph.is_empty() {
+                    let tag = match level {
+                        Some(n) => {
+                            first_heading.get_or_insert_with(|| paragraph.clone());
+                            format!("h{}", n)
+                        }
+                        None => {
+                            first_paragraph.get_or_insert_with(|| paragraph.clone());
+                            "p".to_string()
+                        }
+                    };
+                    html.push_str(&format!("<{tag}>{}</{tag}>\n", escape(paragraph.as_str())));
+                }
+                text.clear();
+                level = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(Docx {
+        title: first_heading.or(first_paragraph),
+        html,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A zip archive of `files`, deflated.
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_docx_headings_become_html_headings() {
+        let styles = r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+            <w:style w:type="paragraph" w:styleId="Berschrift1"><w:name w:val="heading 1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="Custom"><w:pPr><w:outlineLvl w:val="2"/></w:pPr></w:style>
+        </w:styles>"#;
+        let document = r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Berschrift1"/></w:pPr><w:r><w:t>HB 1234 Summary</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Amends § 18.2-57 </w:t></w:r><w:r><w:t>&amp; adds</w:t><w:tab/><w:t>a penalty.</w:t></w:r></w:p>
+            <w:p/>
+            <w:p><w:pPr><w:pStyle w:val="Custom"/></w:pPr><w:r><w:t>Fiscal impact</w:t></w:r></w:p>
+            <w:tbl><w:tr><w:tc><w:p><w:r><w:t>None &lt;expected&gt;</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+            <w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Notes</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let bytes = zip(&[(STYLES_PART, styles), (DOCUMENT_PART, document)]);
+        let docx = docx_to_html(&bytes).unwrap();
+        assert_eq!(docx.title.as_deref(), Some("HB 1234 Summary"));
+        assert_eq!(
+            docx.html,
+            "<h1>HB 1234 Summary</h1>\n\
+             <p>Amends § 18.2-57 &amp; adds a penalty.</p>\n\
+             <h3>Fiscal impact</h3>\n\
+             <p>None &lt;expected&gt;</p>\n\
+             <h2>Notes</h2>\n"
+        );
+
+        assert!(docx_to_html(b"not a zip").is_err());
+        assert!(docx_to_html(&zip(&[(STYLES_PART, styles)])).is_err());
+    }
+
+    #[test]
+    fn test_docx_elements_match_by_namespace_not_prefix() {
+        // Some writers bind WordprocessingML to another prefix; a `w:` element
+        // in some other namespace is not a paragraph.
+        let document = r#"<ns0:document xmlns:ns0="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:w="urn:other"><ns0:body>
+            <ns0:p><ns0:pPr><ns0:pStyle ns0:val="Heading1"/></ns0:pPr><ns0:r><ns0:t>Title</ns0:t></ns0:r></ns0:p>
+            <w:p><w:t>Not a paragraph</w:t></w:p>
+            <ns0:p><ns0:r><ns0:t>Body</ns0:t></ns0:r></ns0:p>
+        </ns0:body></ns0:document>"#;
+        let docx = docx_to_html(&zip(&[(DOCUMENT_PART, document)])).unwrap();
+        assert_eq!(docx.html, "<h1>Title</h1>\n<p>Body</p>\n");
+    }
+}
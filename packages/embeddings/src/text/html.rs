@@ -1,21 +1,168 @@
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, LazyLock};
+use std::time::Duration;
+
+use regex::Regex;
 use scraper::Html;
 
+/// Inputs longer than this are truncated (at a char boundary) before stripping.
+pub const MAX_INPUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Markup longer than this skips the DOM parse and goes straight to the regex
+/// stripper: scraper can take seconds on megabytes of broken markup.
+pub const MAX_PARSE_BYTES: usize = 512 * 1024;
+
+/// Markup longer than this is parsed on a worker thread that is abandoned for
+/// the regex stripper after `PARSE_TIMEOUT`. Smaller inputs parse inline.
+const WATCHED_PARSE_BYTES: usize = 64 * 1024;
+
+pub const PARSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Once this many timed-out parses are still running, watched inputs skip the
+/// DOM parse too, so pathological markup can't pile up threads.
+const MAX_ABANDONED_PARSES: usize = 4;
+
+/// Parse threads that timed out and have not finished yet.
+static ABANDONED: AtomicUsize = AtomicUsize::new(0);
+
+/// A watched parse's state, so exactly one of the caller giving up and the
+/// thread finishing accounts for it in `ABANDONED`.
+const RUNNING: u8 = 0;
+const DONE: u8 = 1;
+const GAVE_UP: u8 = 2;
+
+/// `<script>`/`<style>` blocks and comments, whose content isn't text.
+static NON_TEXT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->").unwrap()
+});
+
+/// Any tag, including an unterminated one at the end of the input.
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*(?:>|$)").unwrap());
+
+static ENTITY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);").unwrap());
+
+/// Which safety limit an input hit while being stripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlLimit {
+    /// Longer than `MAX_INPUT_BYTES`; the tail was discarded.
+    Truncated,
+    /// Longer than `MAX_PARSE_BYTES`; stripped with the regex fallback.
+    TooLarge,
+    /// The DOM parse took longer than `PARSE_TIMEOUT`, or too many earlier
+    /// ones still are running; stripped with the regex fallback.
+    Timeout,
+}
+
+impl HtmlLimit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HtmlLimit::Truncated => "truncated",
+            HtmlLimit::TooLarge => "too_large",
+            HtmlLimit::Timeout => "parse_timeout",
+        }
+    }
+}
+
 /// Strip HTML tags, decode entities, and normalize whitespace.
 pub fn strip_html(input: &str) -> String {
+    strip_html_limited(input).0
+}
+
+/// [`strip_html`], also returning which safety limit, if any, the input hit.
+pub fn strip_html_limited(input: &str) -> (String, Option<HtmlLimit>) {
     if input.is_empty() {
-        return String::new();
+        return (String::new(), None);
+    }
+
+    let mut limit = None;
+    let mut input = input;
+    if input.len() > MAX_INPUT_BYTES {
+        let mut end = MAX_INPUT_BYTES;
+        while !input.is_char_boundary(end) {
+            end -= 1;
+        }
+        input = &input[..end];
+        limit = Some(HtmlLimit::Truncated);
     }
 
     // If it doesn't look like HTML, return as-is (with whitespace normalization)
     if !input.contains('<') {
-        return normalize_whitespace(input);
+        return (normalize_whitespace(input), limit);
+    }
+
+    if input.len() > MAX_PARSE_BYTES {
+        return (strip_html_regex(input), limit.or(Some(HtmlLimit::TooLarge)));
     }
+    if input.len() <= WATCHED_PARSE_BYTES {
+        return (parse_text(input), None);
+    }
+
+    if ABANDONED.load(Ordering::Acquire) >= MAX_ABANDONED_PARSES {
+        return (strip_html_regex(input), Some(HtmlLimit::Timeout));
+    }
+    let owned = input.to_string();
+    let state = Arc::new(AtomicU8::new(RUNNING));
+    let thread_state = state.clone();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(parse_text(&owned));
+        if thread_state.swap(DONE, Ordering::AcqRel) == GAVE_UP {
+            ABANDONED.fetch_sub(1, Ordering::AcqRel);
+        }
+    });
+    if let Ok(text) = rx.recv_timeout(PARSE_TIMEOUT) {
+        return (text, None);
+    }
+    // Count the thread before marking it given up, so its decrement can't
+    // come first; undo that if it finished in the meantime.
+    ABANDONED.fetch_add(1, Ordering::AcqRel);
+    if state.compare_exchange(RUNNING, GAVE_UP, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        ABANDONED.fetch_sub(1, Ordering::AcqRel);
+        if let Ok(text) = rx.recv() {
+            return (text, None);
+        }
+    }
+    (strip_html_regex(input), Some(HtmlLimit::Timeout))
+}
 
+fn parse_text(input: &str) -> String {
     let document = Html::parse_fragment(input);
     let text = document.root_element().text().collect::<Vec<_>>().join(" ");
     normalize_whitespace(&text)
 }
 
+/// Linear-time fallback: drop non-text blocks and tags, then decode entities.
+/// Tags become spaces, matching the DOM path's joining of text nodes.
+fn strip_html_regex(input: &str) -> String {
+    let text = NON_TEXT_RE.replace_all(input, " ");
+    let text = TAG_RE.replace_all(&text, " ");
+    let text = ENTITY_RE.replace_all(&text, |caps: &regex::Captures| {
+        decode_entity(&caps[1]).unwrap_or_else(|| caps[0].to_string())
+    });
+    normalize_whitespace(&text)
+}
+
+fn decode_entity(name: &str) -> Option<String> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{00A0}',
+        "sect" => '§',
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some(c.to_string())
+}
+
 fn normalize_whitespace(s: &str) -> String {
     s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
@@ -46,4 +193,39 @@ mod tests {
     fn test_empty_input() {
         assert_eq!(strip_html(""), "");
     }
+
+    #[test]
+    fn test_regex_fallback_matches_parse() {
+        let input = "<p>Fees &amp; costs</p><!-- note -->\
+                     <b>&sect;&#160;1-1</b> <i>unterminated";
+        assert_eq!(strip_html_regex(input), parse_text(input));
+        assert_eq!(strip_html_regex("<script>x()</script>ok"), "ok");
+        assert_eq!(strip_html_regex("a <b"), "a");
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let large = format!("<p>{}</p>", "word ".repeat(MAX_PARSE_BYTES / 5));
+        let (text, limit) = strip_html_limited(&large);
+        assert_eq!(limit, Some(HtmlLimit::TooLarge));
+        assert!(text.starts_with("word word"));
+
+        let huge = "é".repeat(MAX_INPUT_BYTES / 2 + 1);
+        let (text, limit) = strip_html_limited(&huge);
+        assert_eq!(limit, Some(HtmlLimit::Truncated));
+        assert!(text.len() <= MAX_INPUT_BYTES);
+
+        assert_eq!(strip_html_limited("<p>small</p>"), ("small".to_string(), None));
+    }
+
+    #[test]
+    fn test_abandoned_parses_are_capped() {
+        let watched = format!("<p>{}</p>", "word ".repeat(WATCHED_PARSE_BYTES / 5));
+        ABANDONED.fetch_add(MAX_ABANDONED_PARSES, Ordering::AcqRel);
+        let (text, limit) = strip_html_limited(&watched);
+        ABANDONED.fetch_sub(MAX_ABANDONED_PARSES, Ordering::AcqRel);
+        assert_eq!(limit, Some(HtmlLimit::Timeout));
+        assert_eq!(text, parse_text(&watched));
+        assert_eq!(strip_html_limited(&watched), (text, None));
+    }
 }
@@ -1,2 +1,8 @@
+//! Text cleanup shared by ETL and queries: HTML stripping, normalization,
+//! de-duplication and chunking, plus reading `.docx` files as HTML.
+
 pub mod chunker;
+pub mod dedup;
+pub mod docx;
 pub mod html;
+pub mod normalize;
@@ -0,0 +1,126 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// "Sec. 46.2-862" / "Section 46.2-862" (but not "Section 3" of an article).
+static SECTION_WORD_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:Sec\.|Section)\s*(\d+(?:\.\d+)*-\d)").unwrap()
+});
+
+/// "Secs. 1-2 and 1-3" / "Sections 1-2, 1-3".
+static SECTIONS_WORD_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:Secs\.|Sections)\s*(\d+(?:\.\d+)*-\d)").unwrap()
+});
+
+/// Normalize scraping artifacts so equivalent text compares and embeds equally:
+///
+/// - curly quotes and primes become ASCII `'` / `"`
+/// - en/em dashes, minus signs and non-breaking hyphens become `-`
+/// - NBSP and other fixed-width spaces become a plain space
+/// - zero-width characters, BOMs and soft hyphens are removed
+/// - "Sec." / "Section" before a Code section number becomes `§`
+///   (and "Secs." / "Sections" becomes `§§`)
+///
+/// The result is stable: `normalize(normalize(s)) == normalize(s)`.
+pub fn normalize(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => out.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => out.push('"'),
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
+            | '\u{2212}' => out.push('-'),
+            '\u{00A0}' | '\u{2007}' | '\u{202F}' | '\u{2002}'..='\u{2006}' | '\u{2008}'
+            | '\u{2009}' | '\u{200A}' => out.push(' '),
+            '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' => {}
+            _ => out.push(c),
+        }
+    }
+
+    if out.contains("Sec") {
+        out = SECTIONS_WORD_RE.replace_all(&out, "§§ $1").into_owned();
+        out = SECTION_WORD_RE.replace_all(&out, "§ $1").into_owned();
+    }
+    out
+}
+
+/// Fold an identifier into a lookup key: normalized, case-folded, with
+/// diacritics removed and punctuation runs collapsed to a single space.
+///
+/// `.`, `-` and `:` between two digits are kept, so section numbers like
+/// `46.2-862` and `1-2.3` stay distinct.
+pub fn fold_key(input: &str) -> String {
+    let chars: Vec<char> = normalize(input)
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let mut out = String::with_capacity(chars.len());
+    let mut pending_space = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_alphanumeric() {
+            if pending_space && !out.is_empty() {
+                out.push(' ');
+            }
+            pending_space = false;
+            out.push(c);
+        } else if matches!(c, '.' | '-' | ':')
+            && !pending_space
+            && i > 0
+            && chars[i - 1].is_ascii_digit()
+            && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())
+        {
+            out.push(c);
+        } else {
+            pending_space = true;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotes_dashes_and_spaces() {
+        assert_eq!(
+            normalize("\u{201C}Motor\u{00A0}vehicle\u{201D} isn\u{2019}t \u{2014} see 46.2\u{2013}100"),
+            "\"Motor vehicle\" isn't - see 46.2-100"
+        );
+        assert_eq!(normalize("zero\u{200B}width\u{FEFF}"), "zerowidth");
+    }
+
+    #[test]
+    fn test_section_words_become_symbols() {
+        assert_eq!(normalize("See Sec. 46.2-862."), "See § 46.2-862.");
+        assert_eq!(normalize("under Section 8.01-230"), "under § 8.01-230");
+        assert_eq!(
+            normalize("Sections 1-2 and 1-3 apply"),
+            "§§ 1-2 and 1-3 apply"
+        );
+        // Not a Code section number: left alone.
+        assert_eq!(normalize("Article I, Section 3"), "Article I, Section 3");
+    }
+
+    #[test]
+    fn test_fold_key() {
+        assert_eq!(fold_key("Tort Claims Act"), "tort claims act");
+        assert_eq!(fold_key("TORT-CLAIMS ACT."), "tort claims act");
+        assert_eq!(fold_key("Café  Re\u{0301}gie"), "cafe regie");
+        assert_eq!(fold_key("O\u{2019}Brien's Law"), fold_key("O'Brien's law"));
+        assert_eq!(fold_key("46.2\u{2013}862"), "46.2-862");
+        assert_ne!(fold_key("1-2.3"), fold_key("1.2-3"));
+        assert_eq!(fold_key("article:1"), "article 1");
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let once = normalize("Sec.\u{00A0}46.2\u{2011}862 \u{2018}x\u{2019}");
+        assert_eq!(once, "§ 46.2-862 'x'");
+        assert_eq!(normalize(&once), once);
+    }
+}
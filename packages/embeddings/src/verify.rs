@@ -0,0 +1,158 @@
+//! Citation check for generated answers. A model told to cite its passages
+//! can still cite a section it was never shown, or one that doesn't exist,
+//! and in a legal answer that is the error that matters most. Every statute
+//! an answer cites is graded by where it can be found: in a passage, in a
+//! passage's own citations, elsewhere in the graph, or nowhere.
+
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::graph::edges::cited_statutes;
+use crate::graph::lookup_key;
+
+/// Where a cited statute was found, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grounding {
+    /// One of the passages is the cited section.
+    Passage,
+    /// A passage cites the section, but its own text wasn't in the context.
+    Mentioned,
+    /// The graph has the section; it wasn't retrieved.
+    Graph,
+    /// The graph has no such section: the citation is made up or mistyped.
+    Unknown,
+}
+
+impl Grounding {
+    /// Whether the citation is backed by the context the model was given.
+    /// Under `strict`, only a retrieved passage counts: a section a passage
+    /// merely mentions was never shown to the model.
+    pub fn grounded(self, strict: bool) -> bool {
+        match self {
+            Grounding::Passage => true,
+            Grounding::Mentioned => !strict,
+            Grounding::Graph | Grounding::Unknown => false,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Grounding::Passage => "in the passages",
+            Grounding::Mentioned => "cited by a passage, but its text wasn't retrieved",
+            Grounding::Graph => "in the graph, but not in the passages",
+            Grounding::Unknown => "not in the graph",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckedCitation {
+    /// As written in the answer, e.g. `§ 46.2-862`.
+    pub text: String,
+    pub source: &'static str,
+    pub source_id: String,
+    pub grounding: Grounding,
+}
+
+/// Grade every statute `answer` cites, in order of first mention, against
+/// the context's passages, given as `(source, source_id, text)`, and
+/// `in_graph`, which says whether the output DB has a node.
+pub fn check_citations(
+    answer: &str,
+    passages: &[(&str, &str, &str)],
+    mut in_graph: impl FnMut(&str, &str) -> Result<bool>,
+) -> Result<Vec<CheckedCitation>> {
+    let retrieved: Vec<_> = passages
+        .iter()
+        .map(|(source, id, _)| lookup_key(source, id))
+        .collect();
+    let mentioned: Vec<_> = passages
+        .iter()
+        .flat_map(|(_, _, text)| cited_statutes(text))
+        .map(|(source, id, _)| lookup_key(source, &id))
+        .collect();
+
+    let mut checked = Vec::new();
+    for (source, source_id, span) in cited_statutes(answer) {
+        let key = lookup_key(source, &source_id);
+        let grounding = if retrieved.contains(&key) {
+            Grounding::Passage
+        } else if mentioned.contains(&key) {
+            Grounding::Mentioned
+        } else if in_graph(source, &source_id)? {
+            Grounding::Graph
+        } else {
+            Grounding::Unknown
+        };
+        checked.push(CheckedCitation {
+            text: answer[span].to_string(),
+            source,
+            source_id,
+            grounding,
+        });
+    }
+    Ok(checked)
+}
+
+/// Whether the output DB `conn` has a node for `(source, source_id)`.
+pub fn has_node(conn: &Connection, source: &str, source_id: &str) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM nodes WHERE source = ?1 AND source_id = ?2 LIMIT 1",
+            [source, source_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_citations() {
+        let passages = [
+            (
+                "virginia_code",
+                "46.2-862",
+                "A person is guilty of reckless driving who drives ... as provided in § 46.2-868.",
+            ),
+            ("federal_code", "usc:42:1983", "Every person who ..."),
+        ];
+        let graph = ["46.2-862", "46.2-868", "18.2-266", "usc:42:1983"];
+        let answer = "Yes: 20 over is reckless driving under § 46.2-862 [1], punished under \
+            § 46.2-868, unlike DUI (§ 18.2-266). See also §§ 46.2-862, 46.2-999 and \
+            42 U.S.C. § 1983 [2].";
+        let checked = check_citations(answer, &passages, |_, id| Ok(graph.contains(&id))).unwrap();
+        let graded: Vec<(&str, Grounding)> = checked
+            .iter()
+            .map(|c| (c.text.as_str(), c.grounding))
+            .collect();
+        assert_eq!(
+            graded,
+            [
+                ("§ 46.2-862", Grounding::Passage),
+                ("§ 46.2-868", Grounding::Mentioned),
+                ("§ 18.2-266", Grounding::Graph),
+                ("46.2-999", Grounding::Unknown),
+                ("42 U.S.C. § 1983", Grounding::Passage),
+            ]
+        );
+        assert!(checked[0].grounding.grounded(true));
+        assert!(checked[1].grounding.grounded(false));
+        assert!(!checked[1].grounding.grounded(true));
+        assert!(!checked[2].grounding.grounded(false));
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE nodes (id INTEGER PRIMARY KEY, source TEXT, source_id TEXT);
+             INSERT INTO nodes VALUES (1, 'virginia_code', '46.2-862');",
+        )
+        .unwrap();
+        assert!(has_node(&conn, "virginia_code", "46.2-862").unwrap());
+        assert!(!has_node(&conn, "federal_code", "46.2-862").unwrap());
+    }
+}
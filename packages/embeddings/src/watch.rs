@@ -0,0 +1,95 @@
+//! `--watch`: rebuild whenever the input DB changes, while the scraper keeps
+//! appending to it. Each rebuild is written to a hidden staging file beside
+//! the output and renamed over it only once it succeeded, so readers of the
+//! output never see a half-written DB.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+/// How often the input is checked for changes.
+const POLL: Duration = Duration::from_secs(2);
+
+/// Size and modification time of the input and its WAL file; SQLite writers
+/// in WAL mode only touch the main file at checkpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    db: (u64, Option<SystemTime>),
+    wal: Option<(u64, Option<SystemTime>)>,
+}
+
+pub fn signature(input: &Path) -> Result<Signature> {
+    let stat = |path: &Path| std::fs::metadata(path).map(|m| (m.len(), m.modified().ok()));
+    Ok(Signature {
+        db: stat(input).with_context(|| format!("Failed to stat {}", input.display()))?,
+        wal: stat(&sibling(input, "-wal")).ok(),
+    })
+}
+
+/// Wait until the input differs from `since` and has then stayed unchanged
+/// for one poll, so a rebuild doesn't start in the middle of a bulk write.
+pub async fn wait_for_change(input: &Path, since: Signature) -> Result<Signature> {
+    let mut last = since;
+    loop {
+        tokio::time::sleep(POLL).await;
+        let current = signature(input)?;
+        if current == last && current != since {
+            return Ok(current);
+        }
+        last = current;
+    }
+}
+
+/// Hidden file in the same directory as `path`, so the final rename stays on
+/// one filesystem and is atomic.
+pub fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.watch"))
+}
+
+/// Move a finished staging file over `dest`.
+pub fn promote(staged: &Path, dest: &Path) -> Result<()> {
+    std::fs::rename(staged, dest)
+        .with_context(|| format!("Failed to move {} to {}", staged.display(), dest.display()))
+}
+
+/// Remove a staging file and what a build leaves beside it (WAL/SHM, lock).
+pub fn discard(staged: &Path) {
+    let _ = std::fs::remove_file(staged);
+    for suffix in ["-wal", "-shm", ".lock"] {
+        let _ = std::fs::remove_file(sibling(staged, suffix));
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_and_promote() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("virginia.db");
+        std::fs::write(&input, b"v1").unwrap();
+        let before = signature(&input).unwrap();
+        std::fs::write(sibling(&input, "-wal"), b"frame").unwrap();
+        assert_ne!(signature(&input).unwrap(), before);
+
+        let output = dir.path().join("graph.sqlite.db");
+        let staged = staging_path(&output);
+        assert_eq!(staged, dir.path().join(".graph.sqlite.db.watch"));
+        std::fs::write(&output, b"old").unwrap();
+        std::fs::write(&staged, b"new").unwrap();
+        std::fs::write(sibling(&staged, ".lock"), b"").unwrap();
+        promote(&staged, &output).unwrap();
+        discard(&staged);
+        assert_eq!(std::fs::read(&output).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+}